@@ -0,0 +1,217 @@
+//! Cover art embedded in audio files and stored next to them.
+//!
+//! Albums usually keep their cover in the album directory as `cover.jpg` or
+//! `folder.jpg`, and many players also read a front cover picture embedded
+//! in each file's tags. These functions move covers between the two.
+
+use crate::error::AudioError;
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::probe::Probe;
+use lofty::tag::Tag;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// File names (without extension) recognized as an album's cover, in order
+/// of preference.
+pub const COVER_FILE_NAMES: &[&str] = &["cover", "folder", "front", "album"];
+
+/// File extensions recognized for cover files.
+const COVER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
+
+/// A cover image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverArt {
+    /// The image data.
+    pub data: Vec<u8>,
+    /// The MIME type, such as `image/jpeg`.
+    pub mime_type: String,
+}
+
+impl CoverArt {
+    /// Create a cover from image data, detecting its type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not a JPEG or PNG image.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, AudioError> {
+        let mime_type = if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            "image/jpeg"
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            "image/png"
+        } else {
+            return Err(AudioError::InvalidImage(
+                "not a JPEG or PNG image".to_string(),
+            ));
+        };
+        Ok(Self {
+            data,
+            mime_type: mime_type.to_string(),
+        })
+    }
+
+    /// Read a cover from an image file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a JPEG or PNG
+    /// image.
+    pub fn from_file(path: &Path) -> Result<Self, AudioError> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Get the file extension for the image type.
+    #[must_use]
+    pub fn extension(&self) -> &'static str {
+        match self.mime_type.as_str() {
+            "image/png" => "png",
+            _ => "jpg",
+        }
+    }
+}
+
+/// Find the cover file of an album directory, if it has one.
+#[must_use]
+pub fn find_cover_file(dir: &Path) -> Option<PathBuf> {
+    COVER_FILE_NAMES.iter().find_map(|name| {
+        COVER_EXTENSIONS
+            .iter()
+            .map(|ext| dir.join(format!("{name}.{ext}")))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Read the front cover embedded in an audio file.
+///
+/// Falls back to the first embedded picture if none is marked as the front
+/// cover. Returns `None` if the file has no pictures.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+pub fn read_embedded_art(path: &Path) -> Result<Option<CoverArt>, AudioError> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| AudioError::read(path, e))?
+        .guess_file_type()
+        .map_err(AudioError::Io)?
+        .read()
+        .map_err(|e| AudioError::read(path, e))?;
+
+    let pictures: Vec<&Picture> = tagged_file.tags().iter().flat_map(Tag::pictures).collect();
+    let picture = pictures
+        .iter()
+        .find(|picture| picture.pic_type() == PictureType::CoverFront)
+        .or_else(|| pictures.first());
+
+    Ok(picture.map(|picture| CoverArt {
+        data: picture.data().to_vec(),
+        mime_type: picture
+            .mime_type()
+            .map_or("image/jpeg", MimeType::as_str)
+            .to_string(),
+    }))
+}
+
+/// Embed a cover in an audio file as its front cover, replacing any
+/// existing front cover.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or written.
+pub fn embed_art(path: &Path, art: &CoverArt) -> Result<(), AudioError> {
+    debug!("Embedding cover art in: {}", path.display());
+
+    let mut tagged_file = Probe::open(path)
+        .map_err(|e| AudioError::read(path, e))?
+        .guess_file_type()
+        .map_err(AudioError::Io)?
+        .read()
+        .map_err(|e| AudioError::read(path, e))?;
+
+    // Not every tag type holds pictures, so use the format's primary tag
+    let tag_type = tagged_file.primary_tag_type();
+    let mut tag = tagged_file
+        .remove(tag_type)
+        .unwrap_or_else(|| Tag::new(tag_type));
+
+    tag.remove_picture_type(PictureType::CoverFront);
+    tag.push_picture(Picture::new_unchecked(
+        PictureType::CoverFront,
+        Some(MimeType::from_str(&art.mime_type)),
+        None,
+        art.data.clone(),
+    ));
+    tagged_file.insert_tag(tag);
+
+    tagged_file
+        .save_to_path(path, WriteOptions::default())
+        .map_err(|e| AudioError::write(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A tiny JPEG header; enough for type detection and embedding.
+    const JPEG: &[u8] = &[
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00,
+    ];
+
+    /// Write a silent mono 8 kHz WAV file.
+    fn write_wav(path: &Path) {
+        let samples = vec![0u8; 1600];
+        let size = u32::try_from(samples.len()).unwrap();
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + size).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&samples);
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_cover_art_type() {
+        let art = CoverArt::from_bytes(JPEG.to_vec()).unwrap();
+        assert_eq!(art.mime_type, "image/jpeg");
+        assert_eq!(art.extension(), "jpg");
+        assert!(CoverArt::from_bytes(b"GIF89a".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_find_cover_file() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(find_cover_file(dir.path()), None);
+
+        std::fs::write(dir.path().join("front.png"), b"").unwrap();
+        std::fs::write(dir.path().join("folder.jpg"), b"").unwrap();
+        assert_eq!(
+            find_cover_file(dir.path()),
+            Some(dir.path().join("folder.jpg"))
+        );
+    }
+
+    #[test]
+    fn test_embed_and_read_art() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("track.wav");
+        write_wav(&path);
+        assert_eq!(read_embedded_art(&path).unwrap(), None);
+
+        let art = CoverArt::from_bytes(JPEG.to_vec()).unwrap();
+        embed_art(&path, &art).unwrap();
+        // Embedding again replaces the cover
+        embed_art(&path, &art).unwrap();
+        assert_eq!(read_embedded_art(&path).unwrap(), Some(art));
+    }
+}
@@ -0,0 +1,290 @@
+//! Transcoding tracks to other formats with [FFmpeg](https://ffmpeg.org/).
+//!
+//! Portable copies of a library, such as for a phone or a car, are usually
+//! smaller lossy files. There are no pure Rust encoders for these formats, so
+//! conversion runs the `ffmpeg` program, which also carries the tags over.
+
+use crate::AudioError;
+use apollo_core::config::SyncFormat;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+/// Format to convert tracks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertFormat {
+    /// MP3 (LAME encoder).
+    Mp3,
+    /// Ogg Vorbis.
+    Ogg,
+    /// Opus in an Ogg container.
+    Opus,
+    /// AAC in an MPEG-4 container.
+    Aac,
+    /// FLAC, for lossless copies.
+    Flac,
+}
+
+impl ConvertFormat {
+    /// Get the file extension for converted files.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::Ogg => "ogg",
+            Self::Opus => "opus",
+            Self::Aac => "m4a",
+            Self::Flac => "flac",
+        }
+    }
+
+    /// Check if the format keeps the audio exactly, so has no bitrate.
+    #[must_use]
+    pub const fn is_lossless(self) -> bool {
+        matches!(self, Self::Flac)
+    }
+
+    /// Get the `ffmpeg` encoder and container names.
+    const fn ffmpeg_names(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Mp3 => ("libmp3lame", "mp3"),
+            Self::Ogg => ("libvorbis", "ogg"),
+            Self::Opus => ("libopus", "opus"),
+            Self::Aac => ("aac", "ipod"),
+            Self::Flac => ("flac", "flac"),
+        }
+    }
+}
+
+impl From<SyncFormat> for ConvertFormat {
+    fn from(format: SyncFormat) -> Self {
+        match format {
+            SyncFormat::Mp3 => Self::Mp3,
+            SyncFormat::Ogg => Self::Ogg,
+            SyncFormat::Opus => Self::Opus,
+            SyncFormat::Aac => Self::Aac,
+            SyncFormat::Flac => Self::Flac,
+        }
+    }
+}
+
+/// Options for converting files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// Format to convert to.
+    pub format: ConvertFormat,
+    /// Target bitrate in kbit/s, ignored for lossless formats.
+    pub bitrate_kbps: u32,
+    /// The `ffmpeg` program to run.
+    pub ffmpeg: PathBuf,
+}
+
+impl ConvertOptions {
+    /// Create options that run `ffmpeg` from the `PATH`.
+    #[must_use]
+    pub fn new(format: ConvertFormat, bitrate_kbps: u32) -> Self {
+        Self {
+            format,
+            bitrate_kbps,
+            ffmpeg: PathBuf::from("ffmpeg"),
+        }
+    }
+}
+
+/// Check if a converted file exists and is at least as new as its source.
+#[must_use]
+pub fn is_converted(source: &Path, destination: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    match (modified(source), modified(destination)) {
+        (Some(source), Some(destination)) => destination >= source,
+        _ => false,
+    }
+}
+
+/// Convert an audio file, replacing the destination if it exists.
+///
+/// Files that are already in the target format are copied as they are
+/// instead of being encoded again. The destination's directories are
+/// created, and the file only appears once it is complete.
+///
+/// # Errors
+///
+/// Returns [`AudioError::FileNotFound`] if the source does not exist,
+/// [`AudioError::Convert`] if `ffmpeg` cannot be run or fails, and an I/O
+/// error if the destination cannot be written.
+pub fn convert_file(
+    source: &Path,
+    destination: &Path,
+    options: &ConvertOptions,
+) -> Result<(), AudioError> {
+    let same_format = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(options.format.extension()));
+    if same_format {
+        return copy_file(source, destination);
+    }
+
+    debug!("Converting: {:?}", source);
+    write_complete(source, destination, |partial| {
+        run_ffmpeg(source, partial, options)
+    })
+}
+
+/// Copy an audio file, replacing the destination if it exists.
+///
+/// Like [`convert_file`], the destination's directories are created, and
+/// the file only appears once it is complete.
+///
+/// # Errors
+///
+/// Returns [`AudioError::FileNotFound`] if the source does not exist, and
+/// an I/O error if the file cannot be copied.
+pub fn copy_file(source: &Path, destination: &Path) -> Result<(), AudioError> {
+    debug!("Copying: {:?}", source);
+    write_complete(source, destination, |partial| {
+        fs::copy(source, partial)
+            .map(|_| ())
+            .map_err(AudioError::Io)
+    })
+}
+
+/// Write a file next to the destination, then move it into place.
+fn write_complete(
+    source: &Path,
+    destination: &Path,
+    write: impl FnOnce(&Path) -> Result<(), AudioError>,
+) -> Result<(), AudioError> {
+    if !source.exists() {
+        return Err(AudioError::FileNotFound(source.to_path_buf()));
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut partial = destination.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    match write(&partial).and_then(|()| fs::rename(&partial, destination).map_err(AudioError::Io)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// Encode a file with `ffmpeg`.
+fn run_ffmpeg(source: &Path, output: &Path, options: &ConvertOptions) -> Result<(), AudioError> {
+    let (codec, container) = options.format.ffmpeg_names();
+    let mut args: Vec<OsString> = ["-hide_banner", "-nostdin", "-loglevel", "error", "-y", "-i"]
+        .iter()
+        .map(OsString::from)
+        .collect();
+    args.push(source.as_os_str().to_owned());
+    // Cover art would need a video stream, which not every container takes
+    for arg in ["-map", "0:a:0", "-map_metadata", "0", "-c:a", codec] {
+        args.push(arg.into());
+    }
+    if !options.format.is_lossless() {
+        args.push("-b:a".into());
+        args.push(format!("{}k", options.bitrate_kbps).into());
+    }
+    if options.format == ConvertFormat::Mp3 {
+        // ID3v2.3 is read by more players than FFmpeg's default v2.4
+        args.push("-id3v2_version".into());
+        args.push("3".into());
+    }
+    args.push("-f".into());
+    args.push(container.into());
+    args.push(output.as_os_str().to_owned());
+
+    let convert_error = |reason: String| AudioError::Convert {
+        path: source.to_path_buf(),
+        reason,
+    };
+    let result = Command::new(&options.ffmpeg).args(&args).output();
+    let output = match result {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(convert_error(format!(
+                "{} not found; install FFmpeg to convert files",
+                options.ffmpeg.display()
+            )));
+        }
+        Err(e) => return Err(convert_error(e.to_string())),
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .map_or_else(|| output.status.to_string(), |line| line.trim().to_string());
+    Err(convert_error(reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_converted() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("song.flac");
+        let destination = dir.path().join("song.mp3");
+        fs::write(&source, b"flac").unwrap();
+        assert!(!is_converted(&source, &destination));
+
+        fs::write(&destination, b"mp3").unwrap();
+        assert!(is_converted(&source, &destination));
+
+        let later = fs::metadata(&destination).unwrap().modified().unwrap()
+            + std::time::Duration::from_mins(1);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(!is_converted(&source, &destination));
+    }
+
+    #[test]
+    fn test_convert_same_format_copies() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("song.MP3");
+        let destination = dir.path().join("out/Artist/song.mp3");
+        fs::write(&source, b"mp3 data").unwrap();
+
+        let mut options = ConvertOptions::new(ConvertFormat::Mp3, 192);
+        options.ffmpeg = dir.path().join("no-ffmpeg");
+        convert_file(&source, &destination, &options).unwrap();
+
+        assert_eq!(fs::read(&destination).unwrap(), b"mp3 data");
+        assert!(!dir.path().join("out/Artist/song.mp3.part").exists());
+    }
+
+    #[test]
+    fn test_convert_without_ffmpeg() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("song.flac");
+        let destination = dir.path().join("song.opus");
+        fs::write(&source, b"flac data").unwrap();
+
+        let mut options = ConvertOptions::new(ConvertFormat::Opus, 128);
+        options.ffmpeg = dir.path().join("no-ffmpeg");
+        let result = convert_file(&source, &destination, &options);
+
+        assert!(matches!(result, Err(AudioError::Convert { .. })));
+        assert!(!destination.exists());
+        assert!(!dir.path().join("song.opus.part").exists());
+    }
+}
@@ -38,6 +38,22 @@ pub enum AudioError {
     #[error("no tags found in audio file '{0}'")]
     NoTags(PathBuf),
 
+    /// The audio stream is damaged.
+    #[error("corrupt audio file '{path}': {reason}")]
+    Corrupt { path: PathBuf, reason: String },
+
+    /// Image data is not a supported picture.
+    #[error("invalid image: {0}")]
+    InvalidImage(String),
+
+    /// Audio playback failed.
+    #[error("playback error: {0}")]
+    Playback(String),
+
+    /// Transcoding a file failed.
+    #[error("failed to convert '{path}': {reason}")]
+    Convert { path: PathBuf, reason: String },
+
     /// Directory scan was cancelled.
     #[error("directory scan cancelled")]
     ScanCancelled,
@@ -2,7 +2,10 @@
 //!
 //! This module provides functions to move and copy audio files using path templates.
 
+use std::collections::HashMap;
 use std::fs;
+use std::hash::BuildHasher;
+use std::io;
 use std::path::{Path, PathBuf};
 
 use apollo_core::metadata::Track;
@@ -23,6 +26,7 @@ pub struct OrganizeResult {
 
 /// Options for organizing files.
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct OrganizeOptions {
     /// Move files instead of copying.
     pub move_files: bool,
@@ -30,6 +34,9 @@ pub struct OrganizeOptions {
     pub overwrite: bool,
     /// Create parent directories as needed.
     pub create_dirs: bool,
+    /// Number the file name when the destination exists, such as
+    /// `Song (2).mp3`, instead of failing. Ignored when overwriting.
+    pub rename_on_conflict: bool,
 }
 
 impl Default for OrganizeOptions {
@@ -38,6 +45,7 @@ impl Default for OrganizeOptions {
             move_files: false,
             overwrite: false,
             create_dirs: true,
+            rename_on_conflict: false,
         }
     }
 }
@@ -58,35 +66,55 @@ impl Default for OrganizeOptions {
 /// - The source file doesn't exist
 /// - The template rendering fails
 /// - The file operation fails
-/// - A destination file exists and overwrite is false
+/// - A destination file exists and neither overwrite nor rename is set
 pub fn organize_file(
     source: &Path,
     base_dir: &Path,
     template: &PathTemplate,
     track: &Track,
     options: &OrganizeOptions,
+) -> Result<OrganizeResult, AudioError> {
+    let ctx = TemplateContext::from_track(track);
+    organize_file_with_context(source, base_dir, template, &ctx, options)
+}
+
+/// Organize a file using a prepared template context.
+///
+/// Use this instead of [`organize_file`] when the context needs values that
+/// are not part of the track, such as whether its album is a compilation.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as [`organize_file`].
+pub fn organize_file_with_context(
+    source: &Path,
+    base_dir: &Path,
+    template: &PathTemplate,
+    ctx: &TemplateContext,
+    options: &OrganizeOptions,
 ) -> Result<OrganizeResult, AudioError> {
     // Check source exists
     if !source.exists() {
         return Err(AudioError::FileNotFound(source.to_path_buf()));
     }
 
-    // Build template context from track
-    let ctx = TemplateContext::from_track(track);
-
     // Render destination path
     let relative_path = template
-        .render_with_extension(&ctx)
+        .render_with_extension(ctx)
         .map_err(|e| AudioError::Io(std::io::Error::other(e.to_string())))?;
 
-    let destination = base_dir.join(&relative_path);
+    let mut destination = base_dir.join(&relative_path);
 
     // Check if destination already exists
     if destination.exists() && !options.overwrite {
-        return Err(AudioError::Io(std::io::Error::new(
-            std::io::ErrorKind::AlreadyExists,
-            format!("Destination file already exists: {}", destination.display()),
-        )));
+        if options.rename_on_conflict {
+            destination = available_path(&destination);
+        } else {
+            return Err(AudioError::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("Destination file already exists: {}", destination.display()),
+            )));
+        }
     }
 
     // Create parent directories if needed
@@ -136,6 +164,79 @@ pub fn preview_destination(
     Ok(base_dir.join(&relative_path))
 }
 
+/// Move or remove an organized file so that it is back where it was.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be moved back or removed.
+pub fn undo_organize(result: &OrganizeResult) -> std::io::Result<()> {
+    if !result.moved {
+        return fs::remove_file(&result.destination);
+    }
+    if fs::rename(&result.destination, &result.source).is_err() {
+        fs::copy(&result.destination, &result.source)?;
+        fs::remove_file(&result.destination)?;
+    }
+    Ok(())
+}
+
+/// Put a placed file back, for a track that was not imported.
+///
+/// `placed` maps the destinations of organized files to their results;
+/// paths that were not placed are left alone.
+///
+/// # Errors
+///
+/// Returns an error naming both paths if the file cannot be put back.
+pub fn unplace_file<S: BuildHasher>(
+    placed: &HashMap<PathBuf, OrganizeResult, S>,
+    path: &Path,
+) -> io::Result<()> {
+    let Some(result) = placed.get(path) else {
+        return Ok(());
+    };
+    undo_organize(result).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to restore {} from {}: {e}",
+                result.source.display(),
+                result.destination.display()
+            ),
+        )
+    })
+}
+
+/// Check if two paths are the same file.
+#[must_use]
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || a.canonicalize()
+            .is_ok_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+}
+
+/// Find a path that is not taken, numbering the file name if needed.
+///
+/// Returns the path itself if nothing is there, otherwise the first free
+/// one of `Song (2).mp3`, `Song (3).mp3`, and so on.
+#[must_use]
+pub fn available_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (2..=u32::MAX)
+        .map(|n| path.with_file_name(format!("{stem} ({n}){ext}")))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,6 +291,7 @@ mod tests {
             move_files: false,
             overwrite: false,
             create_dirs: true,
+            rename_on_conflict: false,
         };
 
         let result = organize_file(&source_file, &dest_dir, &template, &track, &options).unwrap();
@@ -219,6 +321,7 @@ mod tests {
             move_files: true,
             overwrite: false,
             create_dirs: true,
+            rename_on_conflict: false,
         };
 
         let result = organize_file(&source_file, &dest_dir, &template, &track, &options).unwrap();
@@ -236,7 +339,7 @@ mod tests {
         let source_dir = temp_dir.path().join("source");
         let dest_dir = temp_dir.path().join("dest");
         fs::create_dir_all(&source_dir).unwrap();
-        fs::create_dir_all(&dest_dir.join("Queen")).unwrap();
+        fs::create_dir_all(dest_dir.join("Queen")).unwrap();
 
         // Create source and existing destination
         let source_file = source_dir.join("test.mp3");
@@ -250,6 +353,7 @@ mod tests {
             move_files: false,
             overwrite: false,
             create_dirs: true,
+            rename_on_conflict: false,
         };
 
         let result = organize_file(&source_file, &dest_dir, &template, &track, &options);
@@ -262,7 +366,7 @@ mod tests {
         let source_dir = temp_dir.path().join("source");
         let dest_dir = temp_dir.path().join("dest");
         fs::create_dir_all(&source_dir).unwrap();
-        fs::create_dir_all(&dest_dir.join("Queen")).unwrap();
+        fs::create_dir_all(dest_dir.join("Queen")).unwrap();
 
         // Create source and existing destination
         let source_file = source_dir.join("test.mp3");
@@ -277,6 +381,7 @@ mod tests {
             move_files: false,
             overwrite: true,
             create_dirs: true,
+            rename_on_conflict: false,
         };
 
         let result = organize_file(&source_file, &dest_dir, &template, &track, &options).unwrap();
@@ -286,4 +391,68 @@ mod tests {
         let content = fs::read(&result.destination).unwrap();
         assert_eq!(content, b"source data");
     }
+
+    #[test]
+    fn test_organize_file_rename_on_conflict() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::create_dir_all(dest_dir.join("Queen")).unwrap();
+
+        let source_file = source_dir.join("test.mp3");
+        fs::write(&source_file, b"source data").unwrap();
+        let existing = dest_dir.join("Queen/Bohemian Rhapsody.mp3");
+        fs::write(&existing, b"existing").unwrap();
+        fs::write(
+            dest_dir.join("Queen/Bohemian Rhapsody (2).mp3"),
+            b"existing",
+        )
+        .unwrap();
+
+        let template = PathTemplate::parse("$artist/$title").unwrap();
+        let track = create_test_track(source_file.clone());
+
+        let options = OrganizeOptions {
+            rename_on_conflict: true,
+            ..OrganizeOptions::default()
+        };
+
+        let result = organize_file(&source_file, &dest_dir, &template, &track, &options).unwrap();
+        assert_eq!(
+            result.destination,
+            dest_dir.join("Queen/Bohemian Rhapsody (3).mp3")
+        );
+        assert_eq!(fs::read(&result.destination).unwrap(), b"source data");
+        assert_eq!(fs::read(&existing).unwrap(), b"existing");
+    }
+
+    #[test]
+    fn test_unplace_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_file = temp_dir.path().join("test.mp3");
+        fs::write(&source_file, b"source data").unwrap();
+        let dest_dir = temp_dir.path().join("dest");
+
+        let template = PathTemplate::parse("$artist/$title").unwrap();
+        let track = create_test_track(source_file.clone());
+        let options = OrganizeOptions {
+            move_files: true,
+            ..OrganizeOptions::default()
+        };
+        let result = organize_file(&source_file, &dest_dir, &template, &track, &options).unwrap();
+        assert!(!is_same_file(&source_file, &result.destination));
+        assert!(is_same_file(
+            &result.destination,
+            &dest_dir.join("Queen/../Queen/Bohemian Rhapsody.mp3")
+        ));
+
+        let destination = result.destination.clone();
+        let placed = HashMap::from([(destination.clone(), result)]);
+        unplace_file(&placed, &source_file).unwrap();
+        assert!(destination.exists());
+        unplace_file(&placed, &destination).unwrap();
+        assert!(source_file.exists());
+        assert!(!destination.exists());
+    }
 }
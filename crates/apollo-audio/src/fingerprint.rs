@@ -257,7 +257,7 @@ mod tests {
 
     #[test]
     fn test_encode_fingerprint() {
-        let fingerprint = vec![0x12345678, 0xABCDEF01];
+        let fingerprint = vec![0x1234_5678, 0xABCD_EF01];
         let encoded = encode_fingerprint(&fingerprint);
         assert!(!encoded.is_empty());
         // Should start with version marker (base64 of [1, ...])
@@ -4,13 +4,14 @@ use crate::error::AudioError;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::path::Path;
-use tracing::trace;
+use tracing::{instrument, trace};
 
 /// Compute a SHA-256 hash of a file's contents.
 ///
 /// # Errors
 ///
 /// Returns an error if the file cannot be read.
+#[instrument(level = "debug", skip_all, fields(path = %path.display()))]
 pub fn compute_file_hash(path: &Path) -> Result<String, AudioError> {
     use sha2::{Digest, Sha256};
 
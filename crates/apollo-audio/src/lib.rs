@@ -8,6 +8,11 @@
 //! - Scan directories for audio files
 //! - Compute file hashes for deduplication
 //! - Generate audio fingerprints for music identification
+//! - Decode whole files to detect corruption
+//! - Convert files to other formats with `ffmpeg`
+//! - Embed and extract cover art
+//! - Play tracks through the default audio device (with the `playback`
+//!   feature)
 //!
 //! # Examples
 //!
@@ -37,18 +42,31 @@
 //! # }
 //! ```
 
+mod art;
+mod convert;
 mod error;
 mod fileops;
 mod fingerprint;
 mod hash;
+#[cfg(feature = "playback")]
+mod player;
 mod reader;
 mod scanner;
+mod verify;
 mod writer;
 
+pub use art::{COVER_FILE_NAMES, CoverArt, embed_art, find_cover_file, read_embedded_art};
+pub use convert::{ConvertFormat, ConvertOptions, convert_file, copy_file, is_converted};
 pub use error::AudioError;
-pub use fileops::{OrganizeOptions, OrganizeResult, organize_file, preview_destination};
+pub use fileops::{
+    OrganizeOptions, OrganizeResult, available_path, is_same_file, organize_file,
+    organize_file_with_context, preview_destination, undo_organize, unplace_file,
+};
 pub use fingerprint::{FingerprintResult, generate_fingerprint};
 pub use hash::compute_file_hash;
+#[cfg(feature = "playback")]
+pub use player::Player;
 pub use reader::{AudioProperties, read_metadata};
-pub use scanner::{ScanOptions, ScanProgress, scan_directory};
-pub use writer::write_metadata;
+pub use scanner::{ScanOptions, ScanProgress, is_audio_file, scan_directory};
+pub use verify::verify_audio;
+pub use writer::{write_metadata, write_metadata_clearing, write_metadata_fields};
@@ -0,0 +1,178 @@
+//! Playing tracks through the default audio device.
+//!
+//! A [`Player`] plays a queue of files in order. The next file is decoded
+//! and queued while the current one plays, so tracks follow each other
+//! without a gap, and encoder delay and padding are trimmed where the file's
+//! metadata records them.
+
+use crate::error::AudioError;
+use rodio::{Decoder, DeviceSinkBuilder, MixerDeviceSink};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Number of tracks kept decoded and queued ahead, including the current one.
+const PRELOAD: usize = 2;
+
+/// How far into a track going back restarts it instead of playing the
+/// previous track.
+const RESTART_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// Plays a queue of audio files.
+pub struct Player {
+    output: rodio::Player,
+    queue: Vec<PathBuf>,
+    /// Queue positions of the tracks loaded into the player, current first.
+    loaded: VecDeque<usize>,
+    /// Number of loaded tracks that were skipped but not yet removed.
+    skipped: usize,
+    /// Queue position of the next track to load.
+    next: usize,
+    // Dropped after the output, which plays through it
+    _sink: MixerDeviceSink,
+}
+
+impl Player {
+    /// Open the default audio device and start playing a queue of files.
+    ///
+    /// Files that cannot be decoded are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the audio device cannot be opened.
+    pub fn new(queue: Vec<PathBuf>) -> Result<Self, AudioError> {
+        let mut sink = DeviceSinkBuilder::open_default_sink()
+            .map_err(|e| AudioError::Playback(e.to_string()))?;
+        sink.log_on_drop(false);
+        let output = rodio::Player::connect_new(sink.mixer());
+
+        let mut player = Self {
+            output,
+            queue,
+            loaded: VecDeque::new(),
+            skipped: 0,
+            next: 0,
+            _sink: sink,
+        };
+        player.fill();
+        Ok(player)
+    }
+
+    /// Get the queue.
+    #[must_use]
+    pub fn queue(&self) -> &[PathBuf] {
+        &self.queue
+    }
+
+    /// Get the queue position of the track that is playing.
+    #[must_use]
+    pub fn current(&self) -> Option<usize> {
+        self.loaded.get(self.skipped).copied()
+    }
+
+    /// Get how far into the current track playback is.
+    #[must_use]
+    pub fn position(&self) -> Duration {
+        self.output.get_pos()
+    }
+
+    /// Check if playback is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.output.is_paused()
+    }
+
+    /// Check if every track in the queue has been played or skipped.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.current().is_none()
+    }
+
+    /// Catch up with the audio device and queue the upcoming tracks.
+    ///
+    /// Call this regularly while playing. Returns the queue positions of the
+    /// tracks that played to the end since the last call; skipped tracks
+    /// are not included.
+    pub fn update(&mut self) -> Vec<usize> {
+        let done = self.loaded.len().saturating_sub(self.output.len());
+        let mut played = Vec::new();
+        for index in self.loaded.drain(..done) {
+            if self.skipped > 0 {
+                self.skipped -= 1;
+            } else {
+                played.push(index);
+            }
+        }
+        self.fill();
+        played
+    }
+
+    /// Skip to the next track.
+    pub fn next(&mut self) {
+        if self.current().is_some() {
+            self.output.skip_one();
+            self.skipped += 1;
+            self.fill();
+        }
+    }
+
+    /// Go back to the previous track, or to the start of the current one if
+    /// it has been playing for a few seconds.
+    pub fn previous(&mut self) {
+        let Some(current) = self.current() else {
+            return;
+        };
+        let index = if self.position() > RESTART_THRESHOLD {
+            current
+        } else {
+            current.saturating_sub(1)
+        };
+        self.play_from(index);
+    }
+
+    /// Pause or resume playback.
+    pub fn toggle_pause(&self) {
+        if self.output.is_paused() {
+            self.output.play();
+        } else {
+            self.output.pause();
+        }
+    }
+
+    /// Restart playback at a queue position.
+    fn play_from(&mut self, index: usize) {
+        let paused = self.output.is_paused();
+        // Clearing also pauses the player
+        self.output.clear();
+        self.loaded.clear();
+        self.skipped = 0;
+        self.next = index;
+        self.fill();
+        if !paused {
+            self.output.play();
+        }
+    }
+
+    /// Decode and queue tracks until enough are queued ahead.
+    fn fill(&mut self) {
+        while self.loaded.len() - self.skipped < PRELOAD && self.next < self.queue.len() {
+            let index = self.next;
+            self.next += 1;
+            match open(&self.queue[index]) {
+                Ok(source) => {
+                    self.output.append(source);
+                    self.loaded.push_back(index);
+                }
+                Err(e) => warn!("Skipping {}: {e}", self.queue[index].display()),
+            }
+        }
+    }
+}
+
+/// Open a file for playback.
+fn open(path: &Path) -> Result<Decoder<std::io::BufReader<File>>, AudioError> {
+    debug!("Decoding: {}", path.display());
+    Decoder::try_from(File::open(path)?).map_err(|e| AudioError::Playback(e.to_string()))
+}
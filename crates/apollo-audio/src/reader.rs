@@ -8,7 +8,7 @@ use lofty::probe::Probe;
 use lofty::tag::ItemKey;
 use std::path::Path;
 use std::time::Duration;
-use tracing::{debug, trace};
+use tracing::{debug, instrument, trace};
 
 /// Audio properties extracted from a file.
 #[derive(Debug, Clone)]
@@ -31,6 +31,7 @@ pub struct AudioProperties {
 /// - The file cannot be read
 /// - The file format is not supported
 /// - No tags are found in the file
+#[instrument(level = "debug", skip_all, fields(path = %path.display()))]
 pub fn read_metadata(path: &Path) -> Result<Track, AudioError> {
     debug!("Reading metadata from: {}", path.display());
 
@@ -97,6 +98,22 @@ pub fn read_metadata(path: &Path) -> Result<Track, AudioError> {
         .get_string(&ItemKey::Unknown("ACOUSTID_ID".to_string()))
         .map(String::from);
 
+    let isrc = tag.get_string(&ItemKey::Isrc).map(String::from);
+
+    // ReplayGain values, e.g. "-6.50 dB" and "0.988547"
+    let rg_track_gain = tag
+        .get_string(&ItemKey::ReplayGainTrackGain)
+        .and_then(parse_gain);
+    let rg_track_peak = tag
+        .get_string(&ItemKey::ReplayGainTrackPeak)
+        .and_then(parse_gain);
+    let rg_album_gain = tag
+        .get_string(&ItemKey::ReplayGainAlbumGain)
+        .and_then(parse_gain);
+    let rg_album_peak = tag
+        .get_string(&ItemKey::ReplayGainAlbumPeak)
+        .and_then(parse_gain);
+
     // Build the track
     let now = Utc::now();
     let track = Track {
@@ -116,10 +133,17 @@ pub fn read_metadata(path: &Path) -> Result<Track, AudioError> {
         duration: properties.duration(),
         bitrate: properties.audio_bitrate(),
         sample_rate: properties.sample_rate(),
+        bit_depth: properties.bit_depth(),
         channels: properties.channels(),
         format,
         musicbrainz_id,
         acoustid,
+        isrc,
+        rg_track_gain,
+        rg_track_peak,
+        rg_album_gain,
+        rg_album_peak,
+        loudness_lufs: None, // Only known after loudness analysis
         added_at: now,
         modified_at: now,
         file_hash: String::new(), // Will be computed separately if needed
@@ -164,6 +188,16 @@ fn parse_year(s: &str) -> Option<i32> {
     }
 }
 
+/// Parse a replay gain or peak value, with or without a "dB" suffix.
+fn parse_gain(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let s = s
+        .strip_suffix("dB")
+        .or_else(|| s.strip_suffix("db"))
+        .unwrap_or(s);
+    s.trim().parse().ok().filter(|gain: &f64| gain.is_finite())
+}
+
 /// Extract genres from tags, handling different formats.
 fn extract_genres(tag: &lofty::tag::Tag) -> Vec<String> {
     let mut genres = Vec::new();
@@ -219,6 +253,15 @@ mod tests {
         assert_eq!(parse_year(""), None);
     }
 
+    #[test]
+    fn test_parse_gain() {
+        assert_eq!(parse_gain("-6.50 dB"), Some(-6.5));
+        assert_eq!(parse_gain("+2.10 db"), Some(2.1));
+        assert_eq!(parse_gain("0.988547"), Some(0.988_547));
+        assert_eq!(parse_gain("loud"), None);
+        assert_eq!(parse_gain("NaN"), None);
+    }
+
     #[test]
     fn test_file_type_to_audio_format() {
         assert_eq!(file_type_to_audio_format(FileType::Mpeg), AudioFormat::Mp3);
@@ -7,7 +7,7 @@ use apollo_core::Track;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, info, instrument, trace, warn};
 use walkdir::WalkDir;
 
 /// Supported audio file extensions.
@@ -86,6 +86,7 @@ pub struct ScanResult {
 /// # Errors
 ///
 /// Returns an error if the directory cannot be read.
+#[instrument(skip_all, fields(path = %path.display()))]
 pub fn scan_directory(
     path: &Path,
     options: &ScanOptions,
@@ -174,7 +175,8 @@ pub fn scan_directory(
 }
 
 /// Check if a file is an audio file based on its extension.
-fn is_audio_file(path: &Path) -> bool {
+#[must_use]
+pub fn is_audio_file(path: &Path) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
         .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
@@ -0,0 +1,174 @@
+//! Decoder verification for detecting corrupt audio files.
+//!
+//! A file whose hash no longer matches may still play, and a file that was
+//! damaged before it was imported has a matching hash. Decoding every packet
+//! finds both kinds of damage: invalid frames, and streams that end early.
+
+use crate::AudioError;
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tracing::debug;
+
+/// Decode a whole audio file to check that it is intact.
+///
+/// # Errors
+///
+/// Returns [`AudioError::Corrupt`] if a packet cannot be decoded or the
+/// stream ends before its stated length, [`AudioError::UnsupportedFormat`]
+/// if the file cannot be decoded at all, and an I/O error if it cannot be
+/// read.
+pub fn verify_audio(path: &Path) -> Result<(), AudioError> {
+    debug!("Verifying: {:?}", path);
+
+    let file = File::open(path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            AudioError::FileNotFound(path.to_path_buf())
+        } else {
+            AudioError::Io(e)
+        }
+    })?;
+    let mss = MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|_| AudioError::UnsupportedFormat(path.to_path_buf()))?;
+    let mut format = probed.format;
+
+    let track = format
+        .default_track()
+        .ok_or_else(|| AudioError::UnsupportedFormat(path.to_path_buf()))?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions { verify: true })
+        .map_err(|_| AudioError::UnsupportedFormat(path.to_path_buf()))?;
+    let track_id = track.id;
+    let expected_frames = track.codec_params.n_frames;
+
+    let corrupt = |reason: String| AudioError::Corrupt {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let mut frames = 0u64;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break;
+            }
+            Err(DecodeError::IoError(e)) => return Err(AudioError::Io(e)),
+            Err(e) => {
+                return Err(corrupt(format!(
+                    "invalid stream after {frames} frames: {e}"
+                )));
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(buffer) => frames += buffer.frames() as u64,
+            Err(e) => {
+                return Err(corrupt(format!(
+                    "cannot decode packet at frame {}: {e}",
+                    packet.ts()
+                )));
+            }
+        }
+    }
+
+    // Decoders may drop the padding of the last packet
+    if let Some(expected) = expected_frames
+        && frames < expected.saturating_sub(expected / 100)
+    {
+        return Err(corrupt(format!(
+            "stream ends early: decoded {frames} of {expected} frames"
+        )));
+    }
+
+    // Verification needs the whole stream, such as the MD5 of a FLAC file
+    if decoder.finalize().verify_ok == Some(false) {
+        return Err(corrupt(
+            "decoded audio does not match its checksum".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    /// A 16-bit mono PCM WAV file with `frames` samples of silence, of
+    /// which only `written` are present.
+    fn wav(frames: u32, written: u32) -> NamedTempFile {
+        let data_len = frames * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&8000u32.to_le_bytes());
+        bytes.extend_from_slice(&16_000u32.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        bytes.resize(bytes.len() + written as usize * 2, 0);
+
+        let mut file = tempfile::Builder::new().suffix(".wav").tempfile().unwrap();
+        file.write_all(&bytes).unwrap();
+        file.flush().unwrap();
+        file
+    }
+
+    #[test]
+    fn test_verify_intact_file() {
+        let file = wav(8000, 8000);
+        assert!(verify_audio(file.path()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_truncated_file() {
+        let file = wav(8000, 2000);
+        assert!(matches!(
+            verify_audio(file.path()),
+            Err(AudioError::Corrupt { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_not_audio() {
+        let mut file = tempfile::Builder::new().suffix(".mp3").tempfile().unwrap();
+        file.write_all(b"not audio at all").unwrap();
+        assert!(matches!(
+            verify_audio(file.path()),
+            Err(AudioError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            verify_audio(Path::new("/nonexistent/file.mp3")),
+            Err(AudioError::FileNotFound(_))
+        ));
+    }
+}
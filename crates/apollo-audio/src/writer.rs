@@ -2,6 +2,7 @@
 
 use crate::error::AudioError;
 use apollo_core::Track;
+use apollo_core::edit::EditField;
 use lofty::config::WriteOptions;
 use lofty::file::{AudioFile, FileType, TaggedFileExt};
 use lofty::probe::Probe;
@@ -26,6 +27,53 @@ use tracing::{debug, trace};
 /// This function will not panic under normal conditions. The internal expect
 /// is guarded by logic that ensures a tag exists before access.
 pub fn write_metadata(path: &Path, track: &Track) -> Result<(), AudioError> {
+    write_metadata_clearing(path, track, &[])
+}
+
+/// Write metadata from a Track back to an audio file, and remove the tags of
+/// `cleared` fields.
+///
+/// Use this after fields were removed from a track, since
+/// [`write_metadata`] leaves the tags of `None` fields as they are.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, the format doesn't support
+/// writing, or writing fails.
+///
+/// # Panics
+///
+/// This function will not panic under normal conditions; see
+/// [`write_metadata`].
+pub fn write_metadata_clearing(
+    path: &Path,
+    track: &Track,
+    cleared: &[EditField],
+) -> Result<(), AudioError> {
+    write_metadata_fields(path, track, EditField::ALL, cleared)
+}
+
+/// Write only some fields of a Track back to an audio file, and remove the
+/// tags of the `cleared` fields among them.
+///
+/// The tags of other fields are left as they are. Replay gain values are
+/// not editable fields, and are always written.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, the format doesn't support
+/// writing, or writing fails.
+///
+/// # Panics
+///
+/// This function will not panic under normal conditions; see
+/// [`write_metadata`].
+pub fn write_metadata_fields(
+    path: &Path,
+    track: &Track,
+    fields: &[EditField],
+    cleared: &[EditField],
+) -> Result<(), AudioError> {
     debug!("Writing metadata to: {}", path.display());
 
     // Open and probe the file
@@ -56,22 +104,79 @@ pub fn write_metadata(path: &Path, track: &Track) -> Result<(), AudioError> {
         .tag_mut(tag_type)
         .expect("tag should exist after creation");
 
+    let writes = |field| fields.contains(&field);
+    set_fields(tag, track, writes);
+
+    // Set ReplayGain values
+    for (key, value) in [
+        (
+            ItemKey::ReplayGainTrackGain,
+            track.rg_track_gain.map(format_gain),
+        ),
+        (
+            ItemKey::ReplayGainTrackPeak,
+            track.rg_track_peak.map(format_peak),
+        ),
+        (
+            ItemKey::ReplayGainAlbumGain,
+            track.rg_album_gain.map(format_gain),
+        ),
+        (
+            ItemKey::ReplayGainAlbumPeak,
+            track.rg_album_peak.map(format_peak),
+        ),
+    ] {
+        if let Some(value) = value {
+            tag.insert_text(key, value);
+        }
+    }
+
+    // Remove cleared fields
+    for field in cleared.iter().filter(|field| writes(**field)) {
+        for key in item_keys(*field) {
+            tag.remove_key(&key);
+        }
+    }
+
+    trace!("Saving tags to file");
+
+    // Save the file
+    tagged_file
+        .save_to_path(path, WriteOptions::default())
+        .map_err(|e| AudioError::write(path, e))?;
+
+    debug!("Successfully wrote metadata to: {}", path.display());
+    Ok(())
+}
+
+/// Set the tags of a track's fields, for the fields that are written.
+fn set_fields(tag: &mut Tag, track: &Track, writes: impl Fn(EditField) -> bool) {
     // Set basic fields
-    tag.set_title(track.title.clone());
-    tag.set_artist(track.artist.clone());
+    if writes(EditField::Title) {
+        tag.set_title(track.title.clone());
+    }
+    if writes(EditField::Artist) {
+        tag.set_artist(track.artist.clone());
+    }
 
     // Set optional string fields
-    if let Some(ref album_artist) = track.album_artist {
+    if let Some(ref album_artist) = track.album_artist
+        && writes(EditField::AlbumArtist)
+    {
         tag.insert_text(ItemKey::AlbumArtist, album_artist.clone());
     }
 
-    if let Some(ref album_title) = track.album_title {
+    if let Some(ref album_title) = track.album_title
+        && writes(EditField::Album)
+    {
         tag.set_album(album_title.clone());
     }
 
     // Set track number
-    if let Some(num) = track.track_number {
-        if let Some(total) = track.track_total {
+    if let Some(num) = track.track_number
+        && writes(EditField::Track)
+    {
+        if let Some(total) = track.track_total.filter(|_| writes(EditField::TrackTotal)) {
             tag.insert_text(ItemKey::TrackNumber, format!("{num}/{total}"));
         } else {
             tag.set_track(num);
@@ -79,8 +184,10 @@ pub fn write_metadata(path: &Path, track: &Track) -> Result<(), AudioError> {
     }
 
     // Set disc number
-    if let Some(num) = track.disc_number {
-        if let Some(total) = track.disc_total {
+    if let Some(num) = track.disc_number
+        && writes(EditField::Disc)
+    {
+        if let Some(total) = track.disc_total.filter(|_| writes(EditField::DiscTotal)) {
             tag.insert_text(ItemKey::DiscNumber, format!("{num}/{total}"));
         } else {
             tag.set_disk(num);
@@ -89,38 +196,69 @@ pub fn write_metadata(path: &Path, track: &Track) -> Result<(), AudioError> {
 
     // Set year (convert i32 to u32, skip if negative)
     if let Some(year) = track.year
+        && writes(EditField::Year)
         && let Ok(year_u32) = u32::try_from(year)
     {
         tag.set_year(year_u32);
     }
 
     // Set genres
-    if !track.genres.is_empty() {
+    if !track.genres.is_empty() && writes(EditField::Genre) {
         tag.set_genre(track.genres.join("; "));
     }
 
     // Set MusicBrainz ID
-    if let Some(ref mbid) = track.musicbrainz_id {
+    if let Some(ref mbid) = track.musicbrainz_id
+        && writes(EditField::MusicBrainzId)
+    {
         tag.insert_text(ItemKey::MusicBrainzRecordingId, mbid.clone());
     }
 
     // Set AcoustID (uses custom key)
-    if let Some(ref acoustid) = track.acoustid {
+    if let Some(ref acoustid) = track.acoustid
+        && writes(EditField::AcoustId)
+    {
         tag.insert_text(
             ItemKey::Unknown("ACOUSTID_ID".to_string()),
             acoustid.clone(),
         );
     }
 
-    trace!("Saving tags to file");
+    // Set ISRC
+    if let Some(ref isrc) = track.isrc
+        && writes(EditField::Isrc)
+    {
+        tag.insert_text(ItemKey::Isrc, isrc.clone());
+    }
+}
 
-    // Save the file
-    tagged_file
-        .save_to_path(path, WriteOptions::default())
-        .map_err(|e| AudioError::write(path, e))?;
+/// Get the tag items that hold a field.
+fn item_keys(field: EditField) -> Vec<ItemKey> {
+    match field {
+        EditField::Title => vec![ItemKey::TrackTitle],
+        EditField::Artist => vec![ItemKey::TrackArtist],
+        EditField::AlbumArtist => vec![ItemKey::AlbumArtist],
+        EditField::Album => vec![ItemKey::AlbumTitle],
+        EditField::Track => vec![ItemKey::TrackNumber],
+        EditField::TrackTotal => vec![ItemKey::TrackTotal],
+        EditField::Disc => vec![ItemKey::DiscNumber],
+        EditField::DiscTotal => vec![ItemKey::DiscTotal],
+        EditField::Year => vec![ItemKey::Year, ItemKey::RecordingDate],
+        EditField::Genre => vec![ItemKey::Genre],
+        EditField::MusicBrainzId => vec![ItemKey::MusicBrainzRecordingId],
+        EditField::AcoustId => vec![ItemKey::Unknown("ACOUSTID_ID".to_string())],
+        EditField::Isrc => vec![ItemKey::Isrc],
+    }
+}
 
-    debug!("Successfully wrote metadata to: {}", path.display());
-    Ok(())
+/// Format a replay gain as a tag value, e.g. "-6.50 dB".
+fn format_gain(gain: f64) -> String {
+    format!("{gain:.2} dB")
+}
+
+/// Format a replay gain peak as a tag value, e.g. "0.988547".
+fn format_peak(peak: f64) -> String {
+    format!("{peak:.6}")
 }
 
 /// Get the preferred tag type for a file type.
@@ -137,6 +275,63 @@ const fn get_preferred_tag_type(file_type: FileType) -> TagType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::read_metadata;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Write a silent mono 8 kHz WAV file.
+    fn write_wav(path: &Path) {
+        let samples = vec![0u8; 1600];
+        let size = u32::try_from(samples.len()).unwrap();
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + size).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&samples);
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_write_metadata_fields() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("song.wav");
+        write_wav(&path);
+
+        let mut track = Track::new(
+            path.clone(),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_millis(100),
+        );
+        track.genres = vec!["Rock".to_string()];
+        write_metadata(&path, &track).unwrap();
+
+        track.title = "New Song".to_string();
+        track.artist = "New Artist".to_string();
+        track.genres = vec!["Pop".to_string()];
+        write_metadata_fields(&path, &track, &[EditField::Title], &[EditField::Genre]).unwrap();
+
+        let read = read_metadata(&path).unwrap();
+        assert_eq!(read.title, "New Song");
+        assert_eq!(read.artist, "Artist");
+        assert_eq!(read.genres, vec!["Rock"]);
+    }
+
+    #[test]
+    fn test_format_replaygain() {
+        assert_eq!(format_gain(-6.5), "-6.50 dB");
+        assert_eq!(format_gain(2.104), "2.10 dB");
+        assert_eq!(format_peak(0.988_547_3), "0.988547");
+    }
 
     #[test]
     fn test_get_preferred_tag_type() {
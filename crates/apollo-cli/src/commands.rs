@@ -0,0 +1,3 @@
+//! Subcommands that are kept in modules of their own.
+
+pub mod undo;
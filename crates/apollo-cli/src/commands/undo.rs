@@ -0,0 +1,398 @@
+//! `apollo undo`: revert operations recorded in the library's log.
+//!
+//! Commands that change files or tracks, such as `organize` and `tag`,
+//! record an [`Operation`] with the steps they took. Undoing it reverts the
+//! steps last first: tracks get their old field values back, moved files
+//! are moved back, and copies are removed.
+
+use crate::{OutputFormat, ensure_parent_dir, print_json_list};
+use anyhow::{Context, Result};
+use apollo_audio::{OrganizeResult, undo_organize};
+use apollo_core::Config;
+use apollo_core::edit::EditField;
+use apollo_core::operation::{Operation, OperationStep};
+use apollo_db::SqliteLibrary;
+use apollo_web::writeback::write_tags;
+use chrono::{Local, Utc};
+use std::path::Path;
+
+/// Number of operations that `apollo undo --list` shows.
+const UNDO_LIST_LIMIT: u32 = 20;
+
+/// List recent operations that can be undone.
+pub async fn cmd_undo_list(lib_path: &Path, output: OutputFormat) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let operations = db.list_operations(UNDO_LIST_LIMIT).await?;
+    if output != OutputFormat::Plain {
+        return print_json_list(output, &operations);
+    }
+    if operations.is_empty() {
+        println!("No operations recorded.");
+        return Ok(());
+    }
+
+    for operation in &operations {
+        let created_at = operation.created_at.with_timezone(&Local);
+        let status = if operation.is_undone() {
+            " (undone)"
+        } else {
+            ""
+        };
+        println!(
+            "{:>5}  {}  {}: {} files, {} tracks{status}",
+            operation.id,
+            created_at.format("%Y-%m-%d %H:%M"),
+            operation.description,
+            operation.file_count(),
+            operation.track_count(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Undo an operation, or the most recent one that is not undone yet.
+pub async fn cmd_undo(
+    lib_path: &Path,
+    id: Option<i64>,
+    dry_run: bool,
+    config: &Config,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let operation = if let Some(id) = id {
+        db.get_operation(id)
+            .await?
+            .with_context(|| format!("Operation not found: {id}"))?
+    } else {
+        let latest = db
+            .list_operations(u32::MAX)
+            .await?
+            .into_iter()
+            .find(|operation| !operation.is_undone());
+        let Some(operation) = latest else {
+            println!("Nothing to undo.");
+            return Ok(());
+        };
+        operation
+    };
+    if operation.is_undone() {
+        anyhow::bail!("Operation {} was already undone", operation.id);
+    }
+
+    let created_at = operation.created_at.with_timezone(&Local);
+    println!(
+        "Undoing {} from {}: {}",
+        operation.id,
+        created_at.format("%Y-%m-%d %H:%M"),
+        operation.description
+    );
+    if dry_run {
+        println!("DRY RUN - no changes will be made");
+    }
+    println!();
+
+    let restored = undo_operation(&db, &operation, dry_run, config).await?;
+
+    println!();
+    if dry_run {
+        println!(
+            "Would restore {} files and {} tracks",
+            restored.files, restored.tracks
+        );
+    } else {
+        db.mark_operation_undone(operation.id, Utc::now()).await?;
+        println!(
+            "Restored {} files and {} tracks",
+            restored.files, restored.tracks
+        );
+    }
+    if restored.failed > 0 {
+        println!("Failed to restore {} changes", restored.failed);
+    }
+
+    Ok(())
+}
+
+/// Number of steps that undoing an operation reverted, or failed to.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Restored {
+    files: u64,
+    tracks: u64,
+    failed: u64,
+}
+
+/// What reverting a single step did.
+enum Reverted {
+    File,
+    Track,
+    Failed,
+}
+
+/// Revert the steps of an operation, last first, or only print them for a
+/// dry run.
+///
+/// Steps that cannot be reverted, such as a file that was removed since,
+/// are reported and skipped.
+async fn undo_operation(
+    db: &SqliteLibrary,
+    operation: &Operation,
+    dry_run: bool,
+    config: &Config,
+) -> Result<Restored> {
+    let mut restored = Restored::default();
+    for step in operation.steps.iter().rev() {
+        match revert_step(db, step, dry_run, config).await? {
+            Reverted::File => restored.files += 1,
+            Reverted::Track => restored.tracks += 1,
+            Reverted::Failed => restored.failed += 1,
+        }
+    }
+    Ok(restored)
+}
+
+/// Revert one step of an operation.
+async fn revert_step(
+    db: &SqliteLibrary,
+    step: &OperationStep,
+    dry_run: bool,
+    config: &Config,
+) -> Result<Reverted> {
+    match step {
+        OperationStep::Track {
+            id,
+            diff,
+            tags_written,
+        } => {
+            let Some(mut track) = db.get_track(id).await? else {
+                eprintln!("Track not found: {id}");
+                return Ok(Reverted::Failed);
+            };
+            if dry_run {
+                println!("{} - {} ({})", track.artist, track.title, track.id);
+                for change in &diff.reversed().changes {
+                    println!("  {change}");
+                }
+                return Ok(Reverted::Track);
+            }
+            diff.revert(&mut track)?;
+            // Tags that were written go back to their old values too
+            let cleared: Vec<EditField> = diff
+                .changes
+                .iter()
+                .filter_map(|change| change.field.parse().ok())
+                .collect();
+            if *tags_written
+                && !config.write.deferred
+                && let Err(e) = write_tags(&mut track, &config.write.written_fields(), &cleared)
+            {
+                eprintln!("Failed to write tags to {}: {e}", track.path.display());
+            }
+            db.update_track(&track).await?;
+            if *tags_written && config.write.deferred {
+                db.queue_write(&track.id, &cleared).await?;
+            }
+            Ok(Reverted::Track)
+        }
+        OperationStep::File {
+            source,
+            destination,
+            moved,
+        } => {
+            if !destination.exists() {
+                eprintln!("File not found: {}", destination.display());
+                return Ok(Reverted::Failed);
+            }
+            if *moved && source.exists() {
+                eprintln!(
+                    "Not moving {} back: {} exists",
+                    destination.display(),
+                    source.display()
+                );
+                return Ok(Reverted::Failed);
+            }
+            if dry_run {
+                if *moved {
+                    println!("{} -> {}", destination.display(), source.display());
+                } else {
+                    println!("Remove copy {}", destination.display());
+                }
+                return Ok(Reverted::File);
+            }
+            if *moved {
+                ensure_parent_dir(source)?;
+            }
+            let result = OrganizeResult {
+                source: source.clone(),
+                destination: destination.clone(),
+                moved: *moved,
+            };
+            if let Err(e) = undo_organize(&result) {
+                eprintln!("Failed to restore {}: {e}", source.display());
+                return Ok(Reverted::Failed);
+            }
+            Ok(Reverted::File)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd_organize;
+    use apollo_core::Track;
+    use apollo_core::operation::OperationKind;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn track(path: PathBuf) -> Track {
+        Track::new(
+            path,
+            "Creep".to_string(),
+            "Radiohead".to_string(),
+            Duration::from_secs(238),
+        )
+    }
+
+    /// Move a track into `music` with `apollo organize --move --relink`, and
+    /// get its library, its old and new path, and the recorded operation.
+    async fn organized(dir: &Path) -> (SqliteLibrary, Track, PathBuf, Operation) {
+        let lib_path = dir.join("library.db");
+        let db = SqliteLibrary::new(&format!("sqlite:{}?mode=rwc", lib_path.display()))
+            .await
+            .unwrap();
+        let source = dir.join("incoming").join("creep.mp3");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, b"audio").unwrap();
+        let track = track(source);
+        db.add_track(&track).await.unwrap();
+
+        let music = dir.join("music");
+        cmd_organize(
+            &lib_path,
+            &music,
+            Some("$artist/$title"),
+            &Config::default(),
+            true,
+            true,
+            false,
+            false,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+        let moved = music.join("Radiohead").join("Creep.mp3");
+        assert!(moved.exists());
+        assert!(!track.path.exists());
+        assert_eq!(db.get_track(&track.id).await.unwrap().unwrap().path, moved);
+
+        let operation = db.list_operations(1).await.unwrap().remove(0);
+        (db, track, moved, operation)
+    }
+
+    #[tokio::test]
+    async fn test_undo_tag_edit() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = track(PathBuf::from("/music/Radiohead/Creep.mp3"));
+        db.add_track(&track).await.unwrap();
+        let mut edited = track.clone();
+        edited.title = "Creep (Acoustic)".to_string();
+        edited.genres = vec!["Rock".to_string()];
+        db.update_track(&edited).await.unwrap();
+        let mut operation = Operation::new(OperationKind::Tag, "tag 1 track");
+        operation.record_track(&track, &edited, false);
+
+        // A dry run only prints the changes
+        let restored = undo_operation(&db, &operation, true, &Config::default())
+            .await
+            .unwrap();
+        assert_eq!(restored.tracks, 1);
+        let current = db.get_track(&track.id).await.unwrap().unwrap();
+        assert_eq!(current.title, "Creep (Acoustic)");
+
+        let restored = undo_operation(&db, &operation, false, &Config::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            restored,
+            Restored {
+                files: 0,
+                tracks: 1,
+                failed: 0
+            }
+        );
+        let reverted = db.get_track(&track.id).await.unwrap().unwrap();
+        assert_eq!(reverted.title, "Creep");
+        assert!(reverted.genres.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_undo_move_and_relink() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, track, moved, operation) = organized(dir.path()).await;
+
+        let restored = undo_operation(&db, &operation, false, &Config::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            restored,
+            Restored {
+                files: 1,
+                tracks: 1,
+                failed: 0
+            }
+        );
+        assert!(track.path.exists());
+        assert!(!moved.exists());
+        let reverted = db.get_track(&track.id).await.unwrap().unwrap();
+        assert_eq!(reverted.path, track.path);
+    }
+
+    #[tokio::test]
+    async fn test_undo_with_file_gone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, track, moved, operation) = organized(dir.path()).await;
+        fs::remove_file(&moved).unwrap();
+
+        // The file cannot be moved back, but the track is still reverted
+        let restored = undo_operation(&db, &operation, false, &Config::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            restored,
+            Restored {
+                files: 0,
+                tracks: 1,
+                failed: 1
+            }
+        );
+        assert!(!track.path.exists());
+        let reverted = db.get_track(&track.id).await.unwrap().unwrap();
+        assert_eq!(reverted.path, track.path);
+    }
+}
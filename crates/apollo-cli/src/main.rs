@@ -5,17 +5,77 @@
 // - List lengths from Vec won't exceed u32::MAX in practice
 #![allow(clippy::cast_possible_truncation)]
 
+mod commands;
+
 use anyhow::{Context, Result};
-use apollo_audio::{OrganizeOptions, ScanOptions, ScanProgress, organize_file, scan_directory};
-use apollo_core::playlist::{Playlist, PlaylistId, PlaylistSort};
-use apollo_core::query::Query;
-use apollo_core::{Config, PathTemplate, TrackId};
-use apollo_db::SqliteLibrary;
-use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(feature = "playback")]
+use apollo_audio::Player;
+use apollo_audio::{
+    AudioError, ConvertFormat, ConvertOptions, CoverArt, OrganizeOptions, OrganizeResult,
+    ScanOptions, ScanProgress, available_path, compute_file_hash, convert_file, copy_file,
+    embed_art, find_cover_file, generate_fingerprint, is_audio_file, is_converted, is_same_file,
+    organize_file_with_context, read_embedded_art, read_metadata, scan_directory, undo_organize,
+    unplace_file, verify_audio,
+};
+use apollo_core::config::{ImportProfile, TelemetryConfig};
+use apollo_core::duplicate::{KeepRule, choose_kept};
+use apollo_core::edit::EditField;
+use apollo_core::export::{ExportFormat, Exporter};
+use apollo_core::featured::CreditNormalizer;
+use apollo_core::genre::GenreNormalizer;
+use apollo_core::library::StatsBreakdown;
+use apollo_core::lock::FieldLocks;
+use apollo_core::operation::{Operation, OperationKind};
+use apollo_core::playlist::{
+    Playlist, PlaylistFormat, PlaylistId, PlaylistSort, read_m3u, write_playlist,
+};
+use apollo_core::provenance::{MetadataSource, Provenance};
+use apollo_core::query::{Query, SortSpec};
+use apollo_core::{
+    Album, AlbumId, AlbumSet, Config, PathTemplate, TemplateContext, Track, TrackDiff, TrackEdit,
+    TrackId, TrackStats,
+};
+use apollo_db::{SCHEMA_VERSION, SqliteLibrary};
+use apollo_lua::{HookResult, LuaRuntime, LuaWorkerPool, load_plugin_metadata, spawn_scheduler};
+use apollo_sources::SourceError;
+use apollo_sources::acoustid::AcoustIdClient;
+use apollo_sources::artistart::ArtistImageClient;
+use apollo_sources::coverart::{CoverArtClient, ImageSize};
+use apollo_sources::discogs::DiscogsClient;
+use apollo_sources::migrate::{MigratedPlaylist, MigratedTrack, beets, itunes};
+use apollo_sources::musicbrainz::{MusicBrainzClient, ReleaseCandidate, ReleaseMatcher};
+use apollo_sources::remote::{self, RemoteLibrary};
+use apollo_sources::streaming::{
+    DeezerClient, MatchMethod, PlaylistMatcher, PlaylistUrl, SpotifyClient, read_csv_export,
+};
+use apollo_web::artist_image::ArtistImages;
+use apollo_web::candidates::TagSources;
+use apollo_web::dlna::{self, SsdpServer};
+use apollo_web::identify::TrackIdentifier;
+use apollo_web::writeback::write_tags;
+use apollo_web::{FolderWatcher, JobScheduler, MpdServer, PlaylistExporter, Scrobbler, TagWriter};
+use chrono::{DateTime, Local, Utc};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::{CompleteEnv, Shells};
+use commands::undo::{cmd_undo, cmd_undo_list};
 use indicatif::{ProgressBar, ProgressStyle};
+use opentelemetry::KeyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::runtime;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
+use tracing_opentelemetry::MetricsLayer;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
 
 #[derive(Parser)]
 #[command(name = "apollo")]
@@ -29,6 +89,10 @@ struct Cli {
     #[arg(short, long, global = true)]
     library: Option<PathBuf>,
 
+    /// Output format for commands that list library data
+    #[arg(long, global = true, value_enum, default_value = "plain")]
+    output: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -53,6 +117,36 @@ enum Commands {
         /// Follow symbolic links
         #[arg(short = 's', long)]
         follow_symlinks: bool,
+
+        /// Match albums to releases and choose how to tag them
+        #[arg(short, long)]
+        autotag: bool,
+
+        /// Don't ask; apply the best match if it scores at least --threshold,
+        /// and import the album as is otherwise
+        #[arg(short, long, requires = "autotag")]
+        quiet: bool,
+
+        /// Minimum score (0.0 to 1.0) to apply a match in quiet mode
+        #[arg(long, default_value = "0.9", requires = "quiet")]
+        threshold: f64,
+
+        /// Copy files into the music directory (default from config)
+        #[arg(long, conflicts_with = "move_files")]
+        copy: bool,
+
+        /// Move files into the music directory (default from config)
+        #[arg(long = "move")]
+        move_files: bool,
+
+        /// Import files where they are, instead of copying or moving them
+        #[arg(long, conflicts_with_all = ["copy", "move_files"])]
+        in_place: bool,
+
+        /// Show what would be imported, and where files would go, without
+        /// changing anything
+        #[arg(short = 'n', long, conflicts_with = "autotag")]
+        dry_run: bool,
     },
     /// List items in the library
     List {
@@ -61,21 +155,39 @@ enum Commands {
         type_: ListType,
 
         /// Maximum number of items to show
-        #[arg(short, long, default_value = "50")]
+        #[arg(long, default_value = "50")]
         limit: u32,
 
         /// Offset for pagination
         #[arg(short, long, default_value = "0")]
         offset: u32,
+
+        /// Print each item with a template, like '$artist - $title ($year)'
+        #[arg(short, long)]
+        format: Option<String>,
     },
     /// Search the library
     Query {
-        /// Search query (searches title, artist, album)
+        /// Search query (searches title, artist, album), with optional sort
+        /// terms like "sort:year-"
         query: String,
 
+        /// What to search for
+        #[arg(long = "type", value_enum, default_value = "tracks")]
+        kind: SearchType,
+
         /// Maximum number of results
-        #[arg(short, long, default_value = "50")]
+        #[arg(long, default_value = "50")]
         limit: u32,
+
+        /// Print each track or album with a template, like
+        /// '$artist - $title ($year)'
+        #[arg(short, long)]
+        format: Option<String>,
+
+        /// Comma-separated fields to print, such as title,artist,year
+        #[arg(long, conflicts_with = "format")]
+        fields: Option<String>,
     },
     /// Start the web server
     Web {
@@ -88,9 +200,23 @@ enum Commands {
         /// Path to directory containing static web UI files
         #[arg(short, long)]
         static_dir: Option<PathBuf>,
+        /// Write the process ID to this file while the server runs
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+        /// Append log messages to this file instead of printing them
+        #[arg(long)]
+        log_file: Option<PathBuf>,
     },
     /// Show library statistics
-    Stats,
+    Stats {
+        /// Break the tracks down by a field
+        #[arg(long, value_enum)]
+        by: Option<StatsBy>,
+
+        /// Number of newest and most played tracks to show
+        #[arg(long, default_value = "5")]
+        top: u32,
+    },
     /// Manage configuration
     Config {
         #[command(subcommand)]
@@ -109,6 +235,22 @@ enum Commands {
         /// Show file paths
         #[arg(short, long)]
         paths: bool,
+
+        /// Remove duplicates, keeping one track of each group
+        #[arg(short, long, value_enum)]
+        resolve: Option<ResolveStrategy>,
+
+        /// Also delete the files of removed duplicates
+        #[arg(long, requires = "resolve")]
+        delete_files: bool,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long, requires = "resolve")]
+        dry_run: bool,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long, requires = "resolve")]
+        yes: bool,
     },
     /// Organize files using path templates
     Organize {
@@ -123,6 +265,17 @@ enum Commands {
         #[arg(short, long)]
         move_files: bool,
 
+        /// Point the library at the organized files (the default with --move-files)
+        ///
+        /// Files already at their destination with the same content, such as
+        /// after reorganizing the files by hand, are relinked in place.
+        #[arg(long, overrides_with = "no_relink")]
+        relink: bool,
+
+        /// Leave the library's paths unchanged, even with --move-files
+        #[arg(long, overrides_with = "relink")]
+        no_relink: bool,
+
         /// Overwrite existing files
         #[arg(short = 'f', long)]
         force: bool,
@@ -136,14 +289,395 @@ enum Commands {
         track_ids: Vec<String>,
 
         /// Maximum number of tracks to organize
-        #[arg(short, long)]
+        #[arg(long)]
         limit: Option<u32>,
     },
+    /// Undo an organize or tag operation, restoring files and metadata
+    ///
+    /// Without an ID, the most recent operation that has not been undone
+    /// yet is undone.
+    Undo {
+        /// ID of the operation to undo, as shown by --list
+        id: Option<i64>,
+
+        /// List recent operations instead of undoing one
+        #[arg(long, conflicts_with = "id")]
+        list: bool,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Convert tracks to another format, for a portable copy of the library
+    ///
+    /// Tracks whose converted copy is newer than the original are skipped,
+    /// so running it again only converts new and changed tracks.
+    Convert {
+        /// Track IDs, a file or directory, or a query selecting the tracks
+        /// (default: all tracks)
+        tracks: Vec<String>,
+
+        /// Format to convert to
+        #[arg(long, value_enum, default_value = "mp3")]
+        to: ConvertTo,
+
+        /// Bitrate in kbit/s for lossy formats
+        #[arg(long, default_value = "192")]
+        bitrate: u32,
+
+        /// Directory for the converted files (default from config)
+        #[arg(long)]
+        dest: Option<PathBuf>,
+
+        /// Path template (default from config)
+        #[arg(short, long)]
+        template: Option<String>,
+
+        /// Convert tracks again even if their copy is up to date
+        #[arg(short = 'f', long)]
+        force: bool,
+
+        /// Show which tracks would be converted
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Mirror selected tracks and playlists to a device, or sync with
+    /// another library
+    ///
+    /// Profiles are set up in the configuration file, as [sync.<name>]
+    /// tables with a destination, the tracks to include, and how to convert
+    /// them.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Sync {
+        #[command(subcommand)]
+        target: Option<SyncTarget>,
+
+        /// Name of the sync profile
+        #[arg(required = true, add = ArgValueCandidates::new(sync_profiles))]
+        profile: Option<String>,
+
+        /// Copy and convert tracks again even if their copy is up to date
+        #[arg(short = 'f', long)]
+        force: bool,
+
+        /// Show what would change without changing anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
     /// Manage playlists
     Playlist {
         #[command(subcommand)]
         action: PlaylistAction,
     },
+    /// Manage plugins and run their commands
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
+    },
+    /// Run Lua scripts against the library
+    Lua {
+        #[command(subcommand)]
+        action: LuaAction,
+    },
+    /// Normalize genres of all tracks and albums in the library
+    FixGenres {
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Credit featured artists of all tracks as featured.style says
+    FixFeatured {
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Rescan library files for changes
+    Update {
+        /// Only update tracks under this directory, and look for moved files
+        /// in it (default: the music directory from the config)
+        path: Option<PathBuf>,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// List tracks whose files no longer exist
+    Missing {
+        /// Search this directory for the missing files, by content, and
+        /// update their paths
+        #[arg(long, value_name = "DIR")]
+        relink: Option<PathBuf>,
+
+        /// Remove missing tracks from the library
+        #[arg(long)]
+        remove: bool,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Check library files for changes and corruption
+    ///
+    /// Recomputes each file's hash and compares it with the hash stored when
+    /// the file was imported or last updated. A file that changed without
+    /// its modified time changing may be damaged. Exits with status 1 if any
+    /// file changed, is corrupt, or is missing.
+    Verify {
+        /// Track IDs, a file or directory, or a query selecting the tracks
+        /// (default: all tracks)
+        tracks: Vec<String>,
+
+        /// Also decode each file completely to find corrupt audio
+        #[arg(long)]
+        deep: bool,
+    },
+    /// Back up, restore, and maintain the library database
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    /// Watch directories and import new files as they appear
+    Watch {
+        /// Directories to watch (default: the watch directories from the
+        /// config)
+        directories: Vec<PathBuf>,
+    },
+    /// Edit track metadata
+    Tag {
+        /// Track IDs, a file or directory, or a query selecting the tracks
+        /// to edit
+        #[arg(required = true)]
+        tracks: Vec<String>,
+
+        /// Set a field, as field=value (genres are comma-separated)
+        #[arg(short, long = "set", value_name = "FIELD=VALUE")]
+        set: Vec<String>,
+
+        /// Remove a field
+        #[arg(short, long = "remove", value_name = "FIELD")]
+        remove: Vec<String>,
+
+        /// Lock a field, so that lookups, plugins and later bulk edits leave
+        /// it alone
+        #[arg(long = "lock", value_name = "FIELD")]
+        lock: Vec<String>,
+
+        /// Unlock a field, before the other changes are made
+        #[arg(long = "unlock", value_name = "FIELD")]
+        unlock: Vec<String>,
+
+        /// Also write the changes to the tags of the audio files, or queue
+        /// them for 'apollo write' if writes are deferred
+        #[arg(short, long)]
+        write: bool,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Identify tracks by audio fingerprint and fetch their metadata
+    Identify {
+        /// Track IDs, a file or directory, or a query selecting the tracks
+        /// to identify
+        #[arg(required = true)]
+        tracks: Vec<String>,
+
+        /// Minimum fingerprint match score (0.0 to 1.0)
+        #[arg(long, default_value = "0.8")]
+        min_score: f64,
+
+        /// Also write the changes to the tags of the audio files, or queue
+        /// them for 'apollo write' if writes are deferred
+        #[arg(short, long)]
+        write: bool,
+
+        /// Apply the changes (default: only show them)
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Write queued tag changes to the audio files
+    Write {
+        /// Track IDs, a file or directory, or a query selecting the tracks
+        /// to write (default: all queued tracks)
+        tracks: Vec<String>,
+
+        /// Show the files that would be written without writing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Manage album cover art
+    Art {
+        #[command(subcommand)]
+        action: ArtAction,
+    },
+    /// Play tracks or a playlist
+    ///
+    /// While playing, press n for the next track, p for the previous one,
+    /// space to pause, and q to quit.
+    Play {
+        /// Playlist name or ID, track IDs, a file or directory, or a query
+        /// selecting the tracks to play
+        #[arg(required = true, add = ArgValueCandidates::new(playlist_names))]
+        tracks: Vec<String>,
+    },
+    /// Send recorded plays to scrobble services
+    ///
+    /// Plays are queued for the services set up in the configuration file,
+    /// under [lastfm] and [listenbrainz], and sent as they are recorded.
+    /// Plays that could not be sent stay queued until they can.
+    Scrobble {
+        #[command(subcommand)]
+        action: ScrobbleAction,
+    },
+    /// Migrate a beets or iTunes library, keeping its history
+    ///
+    /// Adds the tracks with their metadata, and brings along ratings, play
+    /// counts, added dates, and playlists. Tracks already in the library keep
+    /// their metadata, and only get history they don't have yet, so
+    /// migrating twice is safe.
+    Migrate {
+        /// Music manager to migrate from
+        #[arg(long, value_enum)]
+        from: MigrateSource,
+
+        /// Path to the beets library database or the iTunes library XML file
+        path: PathBuf,
+
+        /// Show what would be migrated without making changes
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Export tracks or albums as CSV, JSON, or JSON Lines
+    ///
+    /// The export is written to standard output; redirect it to a file to
+    /// save it.
+    #[command(args_conflicts_with_subcommands = true)]
+    Export {
+        #[command(subcommand)]
+        target: Option<ExportTarget>,
+
+        /// Track IDs, a file or directory, or a query selecting the tracks
+        /// to export (default: all tracks)
+        tracks: Vec<String>,
+
+        /// File format
+        #[arg(short, long, value_enum, default_value = "csv")]
+        format: ExportFileFormat,
+
+        /// Comma-separated fields to export, such as title,artist,year
+        #[arg(long)]
+        fields: Option<String>,
+    },
+    /// Print a shell completion script
+    ///
+    /// Load it from your shell's startup file, for example with
+    /// `source <(apollo completions bash)` in ~/.bashrc.
+    Completions {
+        /// Shell to complete for
+        shell: Shell,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable text
+    Plain,
+    /// A single JSON document
+    Json,
+    /// One JSON value per line
+    Ndjson,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SearchType {
+    /// Tracks, by title, artist, and album
+    Tracks,
+    /// Albums, by title and artist
+    Albums,
+    /// Track and album artists, by name
+    Artists,
+    /// Playlists, by name and description
+    Playlists,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MigrateSource {
+    /// A beets library database
+    Beets,
+    /// An iTunes or Music.app library XML file
+    Itunes,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFileFormat {
+    /// Comma-separated values with a header row
+    Csv,
+    /// A JSON array of objects
+    Json,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl From<ExportFileFormat> for ExportFormat {
+    fn from(format: ExportFileFormat) -> Self {
+        match format {
+            ExportFileFormat::Csv => Self::Csv,
+            ExportFileFormat::Json => Self::Json,
+            ExportFileFormat::Jsonl => Self::Jsonl,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Export albums instead of tracks
+    Albums {
+        /// File format
+        #[arg(short, long, value_enum, default_value = "csv")]
+        format: ExportFileFormat,
+
+        /// Comma-separated fields to export, such as title,artist,year
+        #[arg(long)]
+        fields: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SyncTarget {
+    /// Copy the tracks another Apollo library is missing to it, and the
+    /// tracks this library is missing from it
+    ///
+    /// The other library is reached through the API of 'apollo web'. Tracks
+    /// are the same when they have the same ID, file hash, or recording
+    /// ID, and keep their ID when copied.
+    Remote {
+        /// URL of the other library, such as `http://homeserver:8337`
+        url: String,
+
+        /// Only copy tracks to the other library
+        #[arg(long, conflicts_with = "pull")]
+        push: bool,
+
+        /// Only copy tracks from the other library
+        #[arg(long)]
+        pull: bool,
+
+        /// Copy the audio files along with the metadata
+        #[arg(long)]
+        files: bool,
+
+        /// Show what would be copied without copying anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -161,6 +695,7 @@ enum ConfigAction {
     /// Edit a configuration value
     Set {
         /// Configuration key (e.g., `web.port`, `acoustid.api_key`)
+        #[arg(add = ArgValueCandidates::new(config_keys))]
         key: String,
         /// Value to set
         value: String,
@@ -168,8 +703,48 @@ enum ConfigAction {
     /// Get a configuration value
     Get {
         /// Configuration key (e.g., `web.port`, `acoustid.api_key`)
+        #[arg(add = ArgValueCandidates::new(config_keys))]
+        key: String,
+    },
+    /// Reset a configuration value to its default
+    Unset {
+        /// Configuration key (e.g., `web.port`, `sync.car`)
+        #[arg(add = ArgValueCandidates::new(config_keys))]
         key: String,
     },
+    /// Open the configuration file in $VISUAL or $EDITOR
+    Edit,
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Copy the library database to a backup file, while it is in use
+    Backup {
+        /// Backup file (default: next to the library, named with the
+        /// current time)
+        path: Option<PathBuf>,
+    },
+    /// Replace the library database with a backup
+    Restore {
+        /// Backup file to restore
+        backup: PathBuf,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Compact the database file and refresh its query statistics
+    Vacuum,
+    /// Check the database for corruption
+    Check,
+    /// Upgrade the database schema to the current version
+    Migrate,
+}
+
+#[derive(Subcommand)]
+enum ScrobbleAction {
+    /// Send the plays that are still queued
+    Sync,
 }
 
 #[derive(Clone, Copy, ValueEnum)]
@@ -188,6 +763,30 @@ enum DuplicateType {
     All,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ResolveStrategy {
+    /// Keep the track with the best format, bit depth, sample rate, and
+    /// bitrate
+    KeepBest,
+    /// Keep the track that was added to the library first
+    KeepOldest,
+    /// Choose the track to keep for each group
+    Interactive,
+}
+
+/// How `apollo duplicates --resolve` removes duplicates.
+#[derive(Clone, Copy)]
+struct ResolveOptions {
+    /// How to choose the track to keep.
+    strategy: ResolveStrategy,
+    /// Delete the files of removed tracks.
+    delete_files: bool,
+    /// Only show what would be removed.
+    dry_run: bool,
+    /// Don't ask before removing.
+    yes: bool,
+}
+
 #[derive(Subcommand)]
 enum PlaylistAction {
     /// Create a new playlist
@@ -216,11 +815,13 @@ enum PlaylistAction {
     /// Show a playlist's details and tracks
     Show {
         /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
         playlist: String,
     },
     /// Add a track to a static playlist
     AddTrack {
         /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
         playlist: String,
 
         /// Track ID(s) to add
@@ -230,81 +831,542 @@ enum PlaylistAction {
     /// Remove a track from a static playlist
     RemoveTrack {
         /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
         playlist: String,
 
         /// Track ID(s) to remove
         #[arg(required = true)]
         track_ids: Vec<String>,
     },
+    /// Add the tracks of other playlists to a static playlist
+    ///
+    /// Tracks are added in order, and each track only once.
+    Merge {
+        /// Playlist ID or name to add the tracks to
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
+        playlist: String,
+
+        /// Playlist IDs or names to take the tracks from
+        #[arg(required = true, add = ArgValueCandidates::new(playlist_names))]
+        sources: Vec<String>,
+    },
+    /// Copy a playlist under a new name
+    Duplicate {
+        /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
+        playlist: String,
+
+        /// Name of the copy (default: the name with " (copy)" added)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Copy a smart playlist's current tracks into a static playlist
+        #[arg(long)]
+        snapshot: bool,
+    },
+    /// List the track lists a static playlist had before it changed
+    Versions {
+        /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
+        playlist: String,
+    },
+    /// Give a static playlist the tracks of one of its versions
+    ///
+    /// The tracks it has are kept as a new version first, so a restore can
+    /// be undone too.
+    Restore {
+        /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
+        playlist: String,
+
+        /// Version ID, as listed by `apollo playlist versions`
+        version: i64,
+    },
     /// Delete a playlist
     Delete {
         /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
         playlist: String,
 
         /// Skip confirmation
         #[arg(short = 'y', long)]
         yes: bool,
     },
+    /// Print a playlist as an M3U8 or XSPF file
+    Export {
+        /// Playlist ID or name
+        #[arg(add = ArgValueCandidates::new(playlist_names))]
+        playlist: String,
+
+        /// File format
+        #[arg(short, long, value_enum, default_value = "m3u8")]
+        format: PlaylistFileFormat,
+
+        /// Write paths relative to this directory, where the file will be
+        /// saved (default: absolute paths)
+        #[arg(long, value_name = "DIR")]
+        relative_to: Option<PathBuf>,
+    },
+    /// Create a static playlist from an M3U or M3U8 file, a CSV export, or
+    /// a Spotify or Deezer playlist
+    Import {
+        /// Path to the playlist file, or the URL of a Spotify or Deezer
+        /// playlist
+        source: String,
+
+        /// Playlist name (default: the file or playlist name)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Only show which tracks would be added
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
 }
 
-#[derive(Clone, Copy, ValueEnum, Default)]
-enum PlaylistSortArg {
-    /// Sort by artist name, then album, then track number
-    #[default]
-    Artist,
-    /// Sort by album name, then track number
-    Album,
-    /// Sort by track title
-    Title,
-    /// Sort by date added (newest first)
-    AddedDesc,
-    /// Sort by date added (oldest first)
-    AddedAsc,
-    /// Sort by year (newest first)
-    YearDesc,
-    /// Sort by year (oldest first)
-    YearAsc,
-    /// Random order
-    Random,
+#[derive(Clone, Copy, ValueEnum)]
+enum ConvertTo {
+    /// MP3
+    Mp3,
+    /// Ogg Vorbis
+    Ogg,
+    /// Opus
+    Opus,
+    /// AAC in an .m4a file
+    Aac,
+    /// FLAC
+    Flac,
 }
 
-impl From<PlaylistSortArg> for PlaylistSort {
-    fn from(arg: PlaylistSortArg) -> Self {
-        match arg {
-            PlaylistSortArg::Artist => Self::Artist,
-            PlaylistSortArg::Album => Self::Album,
-            PlaylistSortArg::Title => Self::Title,
-            PlaylistSortArg::AddedDesc => Self::AddedDesc,
-            PlaylistSortArg::AddedAsc => Self::AddedAsc,
-            PlaylistSortArg::YearDesc => Self::YearDesc,
-            PlaylistSortArg::YearAsc => Self::YearAsc,
-            PlaylistSortArg::Random => Self::Random,
+impl From<ConvertTo> for ConvertFormat {
+    fn from(to: ConvertTo) -> Self {
+        match to {
+            ConvertTo::Mp3 => Self::Mp3,
+            ConvertTo::Ogg => Self::Ogg,
+            ConvertTo::Opus => Self::Opus,
+            ConvertTo::Aac => Self::Aac,
+            ConvertTo::Flac => Self::Flac,
         }
     }
 }
 
-/// Load configuration from file or use defaults.
-fn load_config(config_path: Option<&Path>) -> Result<Config> {
-    config_path.map_or_else(
-        || Config::load().context("Failed to load configuration"),
-        |path| Config::load_from(path).context("Failed to load configuration file"),
-    )
+#[derive(Clone, Copy, ValueEnum)]
+enum StatsBy {
+    /// Audio file format
+    Format,
+    /// Genre; tracks with several genres count for each
+    Genre,
+    /// Decade of release
+    Decade,
+    /// Track artist
+    Artist,
 }
 
-/// Get the library path from CLI args, config, or default.
-fn get_library_path(cli_path: Option<&Path>, config: &Config) -> PathBuf {
-    cli_path.map_or_else(|| config.library_path(), Path::to_path_buf)
+impl From<StatsBy> for StatsBreakdown {
+    fn from(by: StatsBy) -> Self {
+        match by {
+            StatsBy::Format => Self::Format,
+            StatsBy::Genre => Self::Genre,
+            StatsBy::Decade => Self::Decade,
+            StatsBy::Artist => Self::Artist,
+        }
+    }
 }
 
-/// Ensure the parent directory for a path exists.
-fn ensure_parent_dir(path: &Path) -> Result<()> {
-    if let Some(parent) = path.parent()
-        && !parent.exists()
-    {
-        std::fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
-    }
-    Ok(())
+#[derive(Clone, Copy, ValueEnum)]
+enum PlaylistFileFormat {
+    /// Extended M3U in UTF-8
+    M3u8,
+    /// XML Shareable Playlist Format
+    Xspf,
+}
+
+impl From<PlaylistFileFormat> for PlaylistFormat {
+    fn from(format: PlaylistFileFormat) -> Self {
+        match format {
+            PlaylistFileFormat::M3u8 => Self::M3u8,
+            PlaylistFileFormat::Xspf => Self::Xspf,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ArtAction {
+    /// Download covers for albums that have no cover file, from the Cover
+    /// Art Archive or Discogs
+    Fetch {
+        /// Track IDs, a file or directory, or a query selecting the albums
+        /// (default: all tracks)
+        tracks: Vec<String>,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Embed album cover files into the tags of their tracks
+    Embed {
+        /// Track IDs, a file or directory, or a query selecting the tracks
+        /// (default: all tracks)
+        tracks: Vec<String>,
+
+        /// Replace covers that are already embedded
+        #[arg(short, long)]
+        force: bool,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Save embedded covers as folder.jpg in albums that have no cover file
+    Extract {
+        /// Track IDs, a file or directory, or a query selecting the albums
+        /// (default: all tracks)
+        tracks: Vec<String>,
+
+        /// Preview changes without making them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginAction {
+    /// List plugins in the plugins directory
+    List,
+    /// Show a plugin's details, hooks, commands, and scheduled tasks
+    Info {
+        /// Plugin file name (without .lua) or declared name
+        #[arg(add = ArgValueCandidates::new(plugin_names))]
+        name: String,
+    },
+    /// Enable a plugin
+    Enable {
+        /// Plugin file name (without .lua) or declared name
+        #[arg(add = ArgValueCandidates::new(plugin_names))]
+        name: String,
+    },
+    /// Disable a plugin
+    Disable {
+        /// Plugin file name (without .lua) or declared name
+        #[arg(add = ArgValueCandidates::new(plugin_names))]
+        name: String,
+    },
+    /// List commands registered by plugins
+    Commands,
+    /// Run a command registered by a plugin
+    Run {
+        /// Command name
+        name: String,
+
+        /// Arguments passed to the command
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum LuaAction {
+    /// Start an interactive Lua session
+    Repl,
+    /// Run a Lua script
+    Run {
+        /// Path to the script
+        script: PathBuf,
+
+        /// Arguments passed to the script
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum, Default)]
+enum PlaylistSortArg {
+    /// Sort by artist name, then album, then track number
+    #[default]
+    Artist,
+    /// Sort by album name, then track number
+    Album,
+    /// Sort by track title
+    Title,
+    /// Sort by date added (newest first)
+    AddedDesc,
+    /// Sort by date added (oldest first)
+    AddedAsc,
+    /// Sort by year (newest first)
+    YearDesc,
+    /// Sort by year (oldest first)
+    YearAsc,
+    /// Random order
+    Random,
+}
+
+impl From<PlaylistSortArg> for PlaylistSort {
+    fn from(arg: PlaylistSortArg) -> Self {
+        match arg {
+            PlaylistSortArg::Artist => Self::Artist,
+            PlaylistSortArg::Album => Self::Album,
+            PlaylistSortArg::Title => Self::Title,
+            PlaylistSortArg::AddedDesc => Self::AddedDesc,
+            PlaylistSortArg::AddedAsc => Self::AddedAsc,
+            PlaylistSortArg::YearDesc => Self::YearDesc,
+            PlaylistSortArg::YearAsc => Self::YearAsc,
+            PlaylistSortArg::Random => Self::Random,
+        }
+    }
+}
+
+/// Set up logging, to a file for a web server started with `--log-file`,
+/// and export of spans and metrics if telemetry is set up.
+fn init_logging(command: &Commands, telemetry: Option<&Telemetry>) -> Result<()> {
+    let fmt_layer = if let Commands::Web {
+        log_file: Some(path),
+        ..
+    } = command
+    {
+        ensure_parent_dir(path)?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+        // Unlike the terminal, a server's log should show what it is doing
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::sync::Mutex::new(file))
+            .with_ansi(false)
+            .with_filter(filter)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer()
+            .with_filter(EnvFilter::from_default_env())
+            .boxed()
+    };
+
+    let mut layers = vec![fmt_layer];
+    if let Some(telemetry) = telemetry {
+        layers.push(telemetry.layer());
+    }
+    tracing_subscriber::registry().with(layers).init();
+    Ok(())
+}
+
+/// Export of spans and metrics to an OpenTelemetry collector.
+struct Telemetry {
+    tracer_provider: opentelemetry_sdk::trace::TracerProvider,
+    meter_provider: Option<SdkMeterProvider>,
+}
+
+impl Telemetry {
+    /// Start exporting to the collector of the `[telemetry]` settings.
+    fn start(config: &TelemetryConfig) -> Result<Self> {
+        let endpoint = config.endpoint.trim_end_matches('/');
+        let resource = Resource::new([KeyValue::new("service.name", config.service_name.clone())]);
+
+        let span_exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{endpoint}/v1/traces"))
+            .build()
+            .context("Failed to set up span export")?;
+        let tracer_provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_batch_exporter(span_exporter, runtime::Tokio)
+            .with_resource(resource.clone())
+            .build();
+
+        let meter_provider = if config.metrics {
+            let metric_exporter = opentelemetry_otlp::MetricExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{endpoint}/v1/metrics"))
+                .build()
+                .context("Failed to set up metric export")?;
+            let reader = PeriodicReader::builder(metric_exporter, runtime::Tokio).build();
+            Some(
+                SdkMeterProvider::builder()
+                    .with_reader(reader)
+                    .with_resource(resource)
+                    .build(),
+            )
+        } else {
+            None
+        };
+
+        Ok(Self {
+            tracer_provider,
+            meter_provider,
+        })
+    }
+
+    /// Layer exporting the spans of Apollo's crates, and the metrics their
+    /// events carry.
+    fn layer(&self) -> Box<dyn Layer<Registry> + Send + Sync> {
+        // Leave out the HTTP client of the exporter itself
+        let filter = Targets::new().with_target("apollo", tracing::Level::DEBUG);
+        tracing_opentelemetry::layer()
+            .with_tracer(self.tracer_provider.tracer("apollo"))
+            .and_then(self.meter_provider.clone().map(MetricsLayer::new))
+            .with_filter(filter)
+            .boxed()
+    }
+
+    /// Send what has not been exported yet, and stop exporting.
+    fn shutdown(self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            eprintln!("Failed to export spans: {e}");
+        }
+        if let Some(meter_provider) = self.meter_provider
+            && let Err(e) = meter_provider.shutdown()
+        {
+            eprintln!("Failed to export metrics: {e}");
+        }
+    }
+}
+
+/// Load configuration from file or use defaults.
+fn load_config(config_path: Option<&Path>) -> Result<Config> {
+    config_path.map_or_else(
+        || Config::load().context("Failed to load configuration"),
+        |path| Config::load_from(path).context("Failed to load configuration file"),
+    )
+}
+
+/// Read configuration from file or use defaults, without validating it.
+fn read_config(config_path: Option<&Path>) -> Result<Config> {
+    if let Some(path) = config_path {
+        return Config::read_from(path).context("Failed to load configuration file");
+    }
+    Config::default_path()
+        .filter(|path| path.exists())
+        .map_or_else(
+            || Ok(Config::default()),
+            |path| Config::read_from(&path).context("Failed to load configuration"),
+        )
+}
+
+/// Save the configuration file, to the default path unless one is given.
+fn save_config(config: &Config, config_path: Option<&Path>) -> Result<()> {
+    let path = config_path
+        .map(PathBuf::from)
+        .or_else(Config::default_path)
+        .context("Could not determine config path")?;
+
+    config.save_to(&path).context("Failed to save config")
+}
+
+/// Get the library path from CLI args, config, or default.
+fn get_library_path(cli_path: Option<&Path>, config: &Config) -> PathBuf {
+    cli_path.map_or_else(|| config.library_path(), Path::to_path_buf)
+}
+
+/// Ensure the parent directory for a path exists.
+fn ensure_parent_dir(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.exists()
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+/// Create a Lua runtime with the enabled plugins loaded.
+///
+/// If no plugins are explicitly enabled in the configuration, all plugins
+/// in the plugins directory are loaded.
+fn load_plugin_runtime(config: &Config) -> Result<LuaRuntime> {
+    let mut runtime = LuaRuntime::new().context("Failed to create Lua runtime")?;
+    runtime
+        .enable_sources(config)
+        .context("Failed to set up metadata sources for plugins")?;
+    for path in plugin_files(config)? {
+        if !config.plugins.is_enabled(plugin_file_name(&path)) {
+            continue;
+        }
+        if let Err(e) = runtime.load_plugin(&path) {
+            eprintln!("Warning: failed to load plugin {}: {e}", path.display());
+        }
+    }
+
+    Ok(runtime)
+}
+
+/// List the Lua files in the plugins directory, sorted by path.
+fn plugin_files(config: &Config) -> Result<Vec<PathBuf>> {
+    let plugins_dir = config.plugins_directory();
+    if !plugins_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = std::fs::read_dir(&plugins_dir).with_context(|| {
+        format!(
+            "Failed to read plugins directory: {}",
+            plugins_dir.display()
+        )
+    })?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Get the name a plugin is enabled by, which is its file name without `.lua`.
+fn plugin_file_name(path: &Path) -> &str {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+}
+
+/// Find a plugin file by its file name or the name declared in the plugin.
+fn find_plugin_file(config: &Config, name: &str) -> Result<PathBuf> {
+    let paths = plugin_files(config)?;
+    paths
+        .iter()
+        .find(|path| plugin_file_name(path) == name)
+        .or_else(|| {
+            paths
+                .iter()
+                .find(|path| load_plugin_metadata(path).is_ok_and(|plugin| plugin.name == name))
+        })
+        .cloned()
+        .with_context(|| {
+            format!(
+                "Plugin not found: {name} (looked in {})",
+                config.plugins_directory().display()
+            )
+        })
+}
+
+/// Format a file size in bytes, KB, MB, GB, or TB.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Format a total playing time in days, hours, and minutes.
+fn format_long_duration(total_secs: u64) -> String {
+    let days = total_secs / 86400;
+    let hours = (total_secs % 86400) / 3600;
+    let minutes = (total_secs % 3600) / 60;
+
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
 }
 
 /// Format a duration as MM:SS or HH:MM:SS.
@@ -321,77 +1383,209 @@ fn format_duration(duration: std::time::Duration) -> String {
     }
 }
 
+/// Parse a `--format` template.
+fn parse_format(format: Option<&str>) -> Result<Option<PathTemplate>> {
+    format
+        .map(|format| {
+            PathTemplate::parse(format).with_context(|| format!("Invalid format: {format}"))
+        })
+        .transpose()
+}
+
+/// Print a value as JSON, pretty-printed for `json` and on one line for
+/// `ndjson`.
+fn print_json<T: Serialize + ?Sized>(output: OutputFormat, value: &T) -> Result<()> {
+    let json = if output == OutputFormat::Ndjson {
+        serde_json::to_string(value)?
+    } else {
+        serde_json::to_string_pretty(value)?
+    };
+    println!("{json}");
+    Ok(())
+}
+
+/// Print a list as JSON: one array for `json`, or one item per line for
+/// `ndjson`.
+fn print_json_list<T: Serialize>(output: OutputFormat, items: &[T]) -> Result<()> {
+    if output == OutputFormat::Ndjson {
+        for item in items {
+            print_json(output, item)?;
+        }
+        Ok(())
+    } else {
+        print_json(output, items)
+    }
+}
+
 #[tokio::main]
+#[allow(clippy::too_many_lines)]
 async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
+    // Answer completion requests from the shell script printed by
+    // `apollo completions`
+    CompleteEnv::with_factory(Cli::command).complete();
 
     let cli = Cli::parse();
 
-    // Load configuration
-    let config = load_config(cli.config.as_deref())?;
+    // Load configuration. Config commands read the file themselves, so that
+    // an invalid file can still be shown and fixed.
+    let config = if matches!(cli.command, Commands::Config { .. }) {
+        Config::default()
+    } else {
+        load_config(cli.config.as_deref())?
+    };
+
+    // Initialize logging, and telemetry export if it is set up
+    let telemetry = if config.telemetry.is_enabled() {
+        Some(Telemetry::start(&config.telemetry)?)
+    } else {
+        None
+    };
+    init_logging(&cli.command, telemetry.as_ref())?;
 
-    match cli.command {
+    let result = match cli.command {
         Commands::Init { path } => cmd_init(path, &config).await,
         Commands::Import {
             path,
             depth,
             follow_symlinks,
+            autotag,
+            quiet,
+            threshold,
+            copy,
+            move_files,
+            in_place,
+            dry_run,
         } => {
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            cmd_import(&lib_path, &path, depth, follow_symlinks).await
+            let normalizer = config
+                .genres
+                .normalize_on_import
+                .then(|| GenreNormalizer::from_config(&config.genres));
+            let autotag = autotag.then_some(AutotagOptions { quiet, threshold });
+            let organize = !in_place && (copy || move_files || config.import.organizes_files());
+            let placement = if organize {
+                Some(FilePlacement::new(
+                    &config,
+                    move_files || (!copy && config.import.move_files),
+                )?)
+            } else {
+                None
+            };
+            cmd_import(
+                &lib_path,
+                &path,
+                depth,
+                follow_symlinks,
+                normalizer.as_ref(),
+                autotag,
+                placement.as_ref(),
+                dry_run,
+                &config,
+            )
+            .await
         }
         Commands::List {
             type_,
             limit,
             offset,
+            format,
         } => {
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            cmd_list(&lib_path, type_, limit, offset).await
+            let format = parse_format(format.as_deref())?;
+            cmd_list(&lib_path, type_, limit, offset, cli.output, format.as_ref()).await
         }
-        Commands::Query { query, limit } => {
+        Commands::Query {
+            query,
+            kind,
+            limit,
+            format,
+            fields,
+        } => {
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            cmd_query(&lib_path, &query, limit).await
+            let format = parse_format(format.as_deref())?;
+            cmd_query(
+                &lib_path,
+                &query,
+                kind,
+                limit,
+                cli.output,
+                format.as_ref(),
+                fields.as_deref(),
+            )
+            .await
         }
-        Commands::Stats => {
+        Commands::Stats { by, top } => {
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            cmd_stats(&lib_path).await
+            cmd_stats(&lib_path, by.map(Into::into), top, cli.output).await
         }
         Commands::Web {
             host,
             port,
             static_dir,
+            pid_file,
+            log_file: _,
         } => {
             let host = host.unwrap_or_else(|| config.web.host.clone());
             let port = port.unwrap_or(config.web.port);
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            cmd_web(&lib_path, &host, port, static_dir.as_deref()).await
+            cmd_web(
+                &lib_path,
+                &host,
+                port,
+                static_dir.as_deref(),
+                pid_file.as_deref(),
+                &config,
+            )
+            .await
         }
         Commands::Config { action } => cmd_config(action, cli.config.as_deref()),
         Commands::Duplicates {
             type_,
             duration_tolerance,
             paths,
+            resolve,
+            delete_files,
+            dry_run,
+            yes,
         } => {
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            cmd_duplicates(&lib_path, type_, duration_tolerance, paths).await
+            let resolve = resolve.map(|strategy| ResolveOptions {
+                strategy,
+                delete_files,
+                dry_run,
+                yes,
+            });
+            let music_dir = config.music_directory();
+            cmd_duplicates(
+                &lib_path,
+                type_,
+                duration_tolerance,
+                paths,
+                resolve,
+                music_dir.as_deref(),
+                cli.output,
+            )
+            .await
         }
         Commands::Organize {
             destination,
             template,
             move_files,
+            relink,
+            no_relink,
             force,
             dry_run,
             track_ids,
             limit,
         } => {
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            let template_str = template.unwrap_or_else(|| config.paths.path_template.clone());
             cmd_organize(
                 &lib_path,
                 &destination,
-                &template_str,
+                template.as_deref(),
+                &config,
                 move_files,
+                relink || (move_files && !no_relink),
                 force,
                 dry_run,
                 &track_ids,
@@ -399,50 +1593,3766 @@ async fn main() -> Result<()> {
             )
             .await
         }
+        Commands::Undo { id, list, dry_run } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            if list {
+                cmd_undo_list(&lib_path, cli.output).await
+            } else {
+                cmd_undo(&lib_path, id, dry_run, &config).await
+            }
+        }
         Commands::Playlist { action } => {
             let lib_path = get_library_path(cli.library.as_deref(), &config);
-            cmd_playlist(&lib_path, action).await
+            cmd_playlist(&lib_path, action, cli.output, &config).await
         }
-    }
-}
-
-/// Initialize a new library.
-async fn cmd_init(path: Option<PathBuf>, config: &Config) -> Result<()> {
-    let lib_path = path.unwrap_or_else(|| config.library_path());
-
-    // Check if library already exists
-    if lib_path.exists() {
-        println!("Library already exists at: {}", lib_path.display());
-        println!("Use --path to specify a different location");
-        return Ok(());
-    }
+        Commands::Plugin { action } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_plugin(action, &lib_path, &config, cli.config.as_deref()).await
+        }
+        Commands::Lua { action } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_lua(action, &lib_path, &config).await
+        }
+        Commands::FixGenres { dry_run } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_fix_genres(&lib_path, &config, dry_run).await
+        }
+        Commands::FixFeatured { dry_run } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_fix_featured(&lib_path, &config, dry_run).await
+        }
+        Commands::Update { path, dry_run } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            let normalizer = config
+                .genres
+                .normalize_on_import
+                .then(|| GenreNormalizer::from_config(&config.genres));
+            let search_dir = path.or_else(|| config.music_directory());
+            cmd_update(
+                &lib_path,
+                search_dir.as_deref(),
+                normalizer.as_ref(),
+                dry_run,
+            )
+            .await
+        }
+        Commands::Missing {
+            relink,
+            remove,
+            dry_run,
+            yes,
+        } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_missing(&lib_path, relink.as_deref(), remove, dry_run, yes).await
+        }
+        Commands::Convert {
+            tracks,
+            to,
+            bitrate,
+            dest,
+            template,
+            force,
+            dry_run,
+        } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            let Some(destination) = dest.or_else(|| config.convert_destination()) else {
+                anyhow::bail!("No destination; use --dest or set convert.destination");
+            };
+            let mut options = ConvertOptions::new(to.into(), bitrate);
+            options.ffmpeg.clone_from(&config.convert.ffmpeg);
+            cmd_convert(
+                &lib_path,
+                &tracks,
+                &destination,
+                template.as_deref(),
+                &config,
+                &options,
+                force,
+                dry_run,
+            )
+            .await
+        }
+        Commands::Sync {
+            target,
+            profile,
+            force,
+            dry_run,
+        } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            match (target, profile) {
+                (
+                    Some(SyncTarget::Remote {
+                        url,
+                        push,
+                        pull,
+                        files,
+                        dry_run,
+                    }),
+                    _,
+                ) => {
+                    let only = if push {
+                        Some(SyncDirection::Push)
+                    } else {
+                        pull.then_some(SyncDirection::Pull)
+                    };
+                    cmd_sync_remote(&lib_path, &config, &url, only, files, dry_run).await
+                }
+                (None, Some(profile)) => {
+                    cmd_sync(&lib_path, &config, &profile, force, dry_run).await
+                }
+                (None, None) => unreachable!("clap requires a sync profile"),
+            }
+        }
+        Commands::Verify { tracks, deep } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_verify(&lib_path, &tracks, deep).await
+        }
+        Commands::Db { action } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_db(&lib_path, action).await
+        }
+        Commands::Watch { directories } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_watch(&lib_path, directories, &config).await
+        }
+        Commands::Tag {
+            tracks,
+            set,
+            remove,
+            lock,
+            unlock,
+            write,
+            dry_run,
+            yes,
+        } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            let edit = parse_track_edit(&set, &remove)?;
+            let locks = LockChanges {
+                lock: parse_fields(&lock, "--lock")?,
+                unlock: parse_fields(&unlock, "--unlock")?,
+            };
+            if edit.is_empty() && locks.is_empty() {
+                anyhow::bail!("Nothing to change; use --set, --remove, --lock or --unlock");
+            }
+            let options = TagOptions {
+                write,
+                dry_run,
+                yes,
+            };
+            cmd_tag(&lib_path, &tracks, &edit, &locks, options, &config).await
+        }
+        Commands::Identify {
+            tracks,
+            min_score,
+            write,
+            yes,
+        } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_identify(&lib_path, &tracks, &config, min_score, write, yes).await
+        }
+        Commands::Write { tracks, dry_run } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_write(&lib_path, &tracks, &config, dry_run).await
+        }
+        Commands::Art { action } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_art(&lib_path, action, &config).await
+        }
+        Commands::Play { tracks } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_play(&lib_path, &tracks, &config).await
+        }
+        Commands::Scrobble { action } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            cmd_scrobble(&lib_path, action, &config).await
+        }
+        Commands::Migrate {
+            from,
+            path,
+            dry_run,
+        } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            let normalizer = config
+                .genres
+                .normalize_on_import
+                .then(|| GenreNormalizer::from_config(&config.genres));
+            cmd_migrate(&lib_path, from, &path, dry_run, normalizer.as_ref()).await
+        }
+        Commands::Export {
+            target,
+            tracks,
+            format,
+            fields,
+        } => {
+            let lib_path = get_library_path(cli.library.as_deref(), &config);
+            match target {
+                Some(ExportTarget::Albums { format, fields }) => {
+                    cmd_export_albums(&lib_path, format.into(), fields.as_deref()).await
+                }
+                None => {
+                    cmd_export_tracks(&lib_path, &tracks, format.into(), fields.as_deref()).await
+                }
+            }
+        }
+        Commands::Completions { shell } => cmd_completions(shell),
+    };
+
+    if let Some(telemetry) = telemetry {
+        telemetry.shutdown();
+    }
+    result
+}
+
+/// Initialize a new library.
+async fn cmd_init(path: Option<PathBuf>, config: &Config) -> Result<()> {
+    let lib_path = path.unwrap_or_else(|| config.library_path());
+
+    // Check if library already exists
+    if lib_path.exists() {
+        println!("Library already exists at: {}", lib_path.display());
+        println!("Use --path to specify a different location");
+        return Ok(());
+    }
+
+    // Ensure parent directory exists
+    ensure_parent_dir(&lib_path)?;
+
+    // Create the database
+    let db_url = format!("sqlite:{}?mode=rwc", lib_path.display());
+    let _db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to create library database")?;
+
+    println!("Initialized new library at: {}", lib_path.display());
+    println!();
+    println!("Next steps:");
+    println!("  apollo import /path/to/music   Import music files");
+    println!("  apollo list                    List tracks in library");
+    println!("  apollo query \"search term\"     Search the library");
+
+    Ok(())
+}
+
+/// Import music files from a directory.
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+#[tracing::instrument(skip_all, fields(source = %source_path.display()))]
+async fn cmd_import(
+    lib_path: &Path,
+    source_path: &Path,
+    depth: Option<usize>,
+    follow_symlinks: bool,
+    genre_normalizer: Option<&GenreNormalizer>,
+    autotag: Option<AutotagOptions>,
+    placement: Option<&FilePlacement>,
+    dry_run: bool,
+    config: &Config,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Check if source directory exists
+    if !source_path.exists() {
+        eprintln!("Source directory not found: {}", source_path.display());
+        std::process::exit(1);
+    }
+
+    if !source_path.is_dir() {
+        eprintln!("Source path is not a directory: {}", source_path.display());
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    println!("Scanning: {}", source_path.display());
+
+    // Hashes are computed in their own phase below
+    let options = ScanOptions {
+        recursive: true,
+        max_depth: depth,
+        follow_symlinks,
+        compute_hashes: false,
+    };
+
+    // Cancellation token (not used in CLI for now, but API requires it)
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let scan_bar = import_phase_bar(0, "Reading");
+    scan_bar.set_message("looking for audio files");
+    scan_bar.enable_steady_tick(std::time::Duration::from_millis(100));
+    let progress_callback = |progress: &ScanProgress| {
+        scan_bar.set_length(progress.files_found as u64);
+        scan_bar.set_position((progress.files_processed + progress.files_failed) as u64);
+        if let Some(ref current) = progress.current_file {
+            scan_bar.set_message(file_label(current));
+        }
+    };
+
+    // Run the scan
+    let result = scan_directory(
+        source_path,
+        &options,
+        Some(&cancel),
+        Some(progress_callback),
+    )
+    .context("Failed to scan directory")?;
+
+    scan_bar.finish_and_clear();
+
+    let total_found = result.tracks.len();
+    let errors = result.errors.len();
+
+    if total_found == 0 {
+        println!("No audio files found in {}", source_path.display());
+        return Ok(());
+    }
+
+    println!("Found {total_found} audio files");
+    if errors > 0 {
+        println!("Skipped {errors} files with errors");
+    }
+
+    let mut tracks = result.tracks;
+    if config.import.compute_hashes {
+        let _span = tracing::info_span!("hash_files", files = tracks.len()).entered();
+        let hash_bar = import_phase_bar(tracks.len() as u64, "Hashing");
+        for track in &mut tracks {
+            hash_bar.set_message(file_label(&track.path));
+            match compute_file_hash(&track.path) {
+                Ok(hash) => track.file_hash = hash,
+                Err(e) => {
+                    tracing::warn!("Failed to compute hash for {}: {}", track.path.display(), e);
+                }
+            }
+            hash_bar.inc(1);
+        }
+        hash_bar.finish_and_clear();
+    }
+    let (mut tracks, copies) = drop_content_duplicates(&db, tracks).await?;
+
+    let mut albums = Vec::new();
+    let mut tagged = HashSet::new();
+    if let Some(options) = autotag {
+        (tracks, albums, tagged) = autotag_tracks(&db, tracks, config, options).await?;
+    }
+
+    let locks = config.locks.locked_fields();
+    if let Some(normalizer) = genre_normalizer.filter(|_| !locks.is_locked(EditField::Genre)) {
+        for track in &mut tracks {
+            track.genres = normalizer.normalize(&track.genres);
+        }
+    }
+
+    // Featured artists move between the artist and the title, so both must
+    // be free to change
+    if config.featured.normalize_on_import
+        && !locks.is_locked(EditField::Artist)
+        && !locks.is_locked(EditField::Title)
+    {
+        let normalizer = CreditNormalizer::from_config(&config.featured);
+        for track in &mut tracks {
+            normalizer.normalize(track);
+        }
+    }
+
+    if dry_run {
+        return preview_import(&db, &tracks, placement, copies, config).await;
+    }
+
+    // Plugins see the tracks as they will be imported, and may skip them
+    let hook_db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?
+        .with_events(db.events().clone());
+    let hooks = spawn_import_hooks(config, Arc::new(hook_db))?;
+    let (mut tracks, plugin_skipped) = run_import_hooks(&hooks, tracks, &locks).await?;
+    let albums = create_imported_albums(&db, &hooks, albums, &mut tracks).await?;
+
+    // Files are laid out once their albums are known
+    let mut placed = HashMap::new();
+    let mut failed = 0u64;
+    if let Some(placement) = placement {
+        (tracks, placed, failed) = place_imported_files(&db, tracks, placement, config).await?;
+    }
+
+    // Import tracks into database
+    let import_bar = import_phase_bar(tracks.len() as u64, "Importing");
+
+    let mut imported = 0u64;
+    let mut skipped = 0u64;
+
+    for track in tracks {
+        import_bar.inc(1);
+        import_bar.set_message(file_label(&track.path));
+
+        // Try to add track; handle duplicate errors gracefully
+        match db.add_track(&track).await {
+            Ok(_) => {
+                imported += 1;
+                if config.import.write_tags
+                    && config.write.deferred
+                    && tagged.contains(&track.id)
+                    && let Err(e) = db.queue_write(&track.id, &[]).await
+                {
+                    tracing::warn!("Failed to queue tags of {}: {e}", track.path.display());
+                }
+                let path = track.path.clone();
+                if let Err(e) = hooks.run_post_import_async(track).await {
+                    tracing::warn!("post_import hook failed for {}: {e}", path.display());
+                }
+            }
+            Err(apollo_db::DbError::Sqlx(ref e)) if e.to_string().contains("UNIQUE constraint") => {
+                if let Err(e) = unplace_file(&placed, &track.path) {
+                    eprintln!("{e}");
+                }
+                skipped += 1;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to import {}: {}", track.path.display(), e);
+                if let Err(e) = unplace_file(&placed, &track.path) {
+                    eprintln!("{e}");
+                }
+                failed += 1;
+            }
+        }
+    }
+
+    import_bar.finish_and_clear();
+
+    for album in albums {
+        let title = album.title.clone();
+        if let Err(e) = hooks.run_post_album_import_async(album).await {
+            tracing::warn!("post_album_import hook failed for {title}: {e}");
+        }
+    }
+    hooks.shutdown();
+
+    tracing::debug!(
+        monotonic_counter.import_tracks_imported = imported,
+        monotonic_counter.import_tracks_skipped = skipped,
+        monotonic_counter.import_tracks_duplicate = copies,
+        monotonic_counter.import_tracks_failed = failed,
+        "Import metrics"
+    );
+
+    println!();
+    println!("Import complete:");
+    println!("  Imported: {imported}");
+    if let Some(placement) = placement.filter(|_| !placed.is_empty()) {
+        let verb = if placement.move_files {
+            "Moved"
+        } else {
+            "Copied"
+        };
+        println!(
+            "  {verb} into {}: {}",
+            placement.music_dir.display(),
+            placed.len()
+        );
+    }
+    if skipped > 0 {
+        println!("  Skipped (already imported): {skipped}");
+    }
+    if copies > 0 {
+        println!("  Skipped (same file elsewhere): {copies}");
+    }
+    if plugin_skipped > 0 {
+        println!("  Skipped by plugins: {plugin_skipped}");
+    }
+    if failed > 0 {
+        println!("  Failed: {failed}");
+    }
+
+    // Show summary
+    let total_tracks = db.count_tracks().await?;
+    println!();
+    println!("Library now contains {total_tracks} tracks");
+
+    Ok(())
+}
+
+/// Where `apollo import` puts files: copied or moved into the music
+/// directory.
+struct FilePlacement {
+    /// The music directory, as an absolute path.
+    music_dir: PathBuf,
+    /// Move files instead of copying them.
+    move_files: bool,
+}
+
+impl FilePlacement {
+    /// Place files in the configured music directory.
+    fn new(config: &Config, move_files: bool) -> Result<Self> {
+        let Some(music_dir) = config.music_directory() else {
+            anyhow::bail!(
+                "No music directory to import into; set one with 'apollo config set paths.music_directory <dir>'"
+            );
+        };
+        // Stored paths should not depend on the working directory
+        let music_dir = std::path::absolute(&music_dir)
+            .with_context(|| format!("Invalid music directory: {}", music_dir.display()))?;
+        Ok(Self {
+            music_dir,
+            move_files,
+        })
+    }
+}
+
+/// How imported files are laid out in the music directory.
+struct ImportLayout<'a> {
+    template: PathTemplate,
+    profile_templates: Vec<(&'a ImportProfile, PathTemplate)>,
+    albums: HashMap<AlbumId, Album>,
+    album_set: AlbumSet,
+}
+
+impl<'a> ImportLayout<'a> {
+    /// Load the configured templates, and the albums of the library.
+    async fn load(db: &SqliteLibrary, config: &'a Config) -> Result<Self> {
+        let template_str = &config.paths.path_template;
+        let template = PathTemplate::parse(template_str)
+            .with_context(|| format!("Invalid path template: {template_str}"))?;
+        let profile_templates = profile_templates(&config.import.profiles)?;
+
+        // Albums are needed for compilations and %aunique
+        let albums = db.list_albums(u32::MAX, 0).await?;
+        let album_set = AlbumSet::new(albums.iter().cloned());
+        let albums = albums
+            .into_iter()
+            .map(|album| (album.id.clone(), album))
+            .collect();
+        Ok(Self {
+            template,
+            profile_templates,
+            albums,
+            album_set,
+        })
+    }
+
+    /// Get the template for a track, and the values to render it with.
+    fn template_for(&self, track: &Track) -> (&PathTemplate, TemplateContext) {
+        let mut ctx = TemplateContext::from_track(track);
+        if let Some(album) = track.album_id.as_ref().and_then(|id| self.albums.get(id)) {
+            ctx.set_album(album, &self.album_set);
+        }
+        let template = self
+            .profile_templates
+            .iter()
+            .find(|(profile, _)| profile.matches(&track.path))
+            .map_or(&self.template, |(_, template)| template);
+        (template, ctx)
+    }
+
+    /// Render where a track belongs under a directory.
+    fn destination(&self, track: &Track, dir: &Path) -> Result<PathBuf> {
+        let (template, ctx) = self.template_for(track);
+        Ok(dir.join(template.render_with_extension(&ctx)?))
+    }
+}
+
+/// Copy or move new tracks into the music directory, and point them at
+/// their new paths.
+///
+/// Tracks that are in the library already, or where they belong, stay where
+/// they are. A file in the way is kept, and the track gets a numbered name
+/// next to it. Tracks whose file cannot be placed are left out. Returns the
+/// tracks to import, how each file was placed by its new path, and how many
+/// failed.
+#[tracing::instrument(skip_all)]
+async fn place_imported_files(
+    db: &SqliteLibrary,
+    tracks: Vec<Track>,
+    placement: &FilePlacement,
+    config: &Config,
+) -> Result<(Vec<Track>, HashMap<PathBuf, OrganizeResult>, u64)> {
+    let layout = ImportLayout::load(db, config).await?;
+    let options = OrganizeOptions {
+        move_files: placement.move_files,
+        overwrite: false,
+        create_dirs: true,
+        rename_on_conflict: true,
+    };
+
+    let phase = if placement.move_files {
+        "Moving"
+    } else {
+        "Copying"
+    };
+    let bar = import_phase_bar(tracks.len() as u64, phase);
+    let mut kept = Vec::with_capacity(tracks.len());
+    let mut placed = HashMap::new();
+    let mut failed = 0u64;
+    for mut track in tracks {
+        bar.inc(1);
+        bar.set_message(file_label(&track.path));
+        if db.get_track_by_path(&track.path).await?.is_some() {
+            kept.push(track);
+            continue;
+        }
+        match layout.destination(&track, &placement.music_dir) {
+            Ok(dest) if is_same_file(&dest, &track.path) => {
+                kept.push(track);
+                continue;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                bar.suspend(|| eprintln!("Template error for {}: {e}", track.path.display()));
+                failed += 1;
+                continue;
+            }
+        }
+
+        let (template, ctx) = layout.template_for(&track);
+        match organize_file_with_context(
+            &track.path,
+            &placement.music_dir,
+            template,
+            &ctx,
+            &options,
+        ) {
+            Ok(result) => {
+                track.path.clone_from(&result.destination);
+                placed.insert(result.destination.clone(), result);
+                kept.push(track);
+            }
+            Err(e) => {
+                bar.suspend(|| eprintln!("Failed to place {}: {e}", track.path.display()));
+                failed += 1;
+            }
+        }
+    }
+    bar.finish_and_clear();
+    Ok((kept, placed, failed))
+}
+
+/// Show which tracks an import would add, and where their files would go.
+async fn preview_import(
+    db: &SqliteLibrary,
+    tracks: &[Track],
+    placement: Option<&FilePlacement>,
+    copies: u64,
+    config: &Config,
+) -> Result<()> {
+    let layout = match placement {
+        Some(placement) => {
+            let verb = if placement.move_files { "Move" } else { "Copy" };
+            println!("{verb} files into: {}", placement.music_dir.display());
+            Some(ImportLayout::load(db, config).await?)
+        }
+        None => None,
+    };
+    println!("DRY RUN - nothing will be imported");
+    println!();
+
+    let mut new = 0u64;
+    let mut existing = 0u64;
+    let mut failed = 0u64;
+    for track in tracks {
+        if db.get_track_by_path(&track.path).await?.is_some() {
+            existing += 1;
+            continue;
+        }
+        let Some((placement, layout)) = placement.zip(layout.as_ref()) else {
+            println!("{}", track.path.display());
+            new += 1;
+            continue;
+        };
+        match layout.destination(track, &placement.music_dir) {
+            Ok(dest) if is_same_file(&dest, &track.path) => {
+                println!("{} (in place)", track.path.display());
+            }
+            Ok(dest) => {
+                println!(
+                    "{} -> {}",
+                    track.path.display(),
+                    available_path(&dest).display()
+                );
+            }
+            Err(e) => {
+                println!("Template error for {}: {e}", track.path.display());
+                failed += 1;
+                continue;
+            }
+        }
+        new += 1;
+    }
+
+    println!();
+    println!("Dry run complete:");
+    println!("  Would import: {new}");
+    if existing > 0 {
+        println!("  Skipped (already imported): {existing}");
+    }
+    if copies > 0 {
+        println!("  Skipped (same file elsewhere): {copies}");
+    }
+    if failed > 0 {
+        println!("  Failed: {failed}");
+    }
+    Ok(())
+}
+
+/// Start a worker that runs the import hooks of the enabled plugins.
+///
+/// A single worker runs the hooks in order, and keeps plugin state between
+/// them.
+fn spawn_import_hooks(config: &Config, db: Arc<SqliteLibrary>) -> Result<LuaWorkerPool> {
+    let config = config.clone();
+    LuaWorkerPool::new(1, move || {
+        let runtime = load_plugin_runtime(&config)?;
+        let db = Arc::clone(&db);
+        runtime
+            .enable_library(db)
+            .context("Failed to set up library access for plugins")?;
+        anyhow::Ok(runtime)
+    })
+    .context("Failed to start plugin hooks")
+}
+
+/// Run the `on_import` hooks of plugins on the tracks to import.
+///
+/// Returns the tracks to import, and how many plugins skipped. Tracks that
+/// a hook fails for are imported unchanged, and changes that hooks make to
+/// locked fields are undone.
+#[tracing::instrument(skip_all)]
+async fn run_import_hooks(
+    hooks: &LuaWorkerPool,
+    tracks: Vec<Track>,
+    locks: &FieldLocks,
+) -> Result<(Vec<Track>, u64)> {
+    let mut kept = Vec::with_capacity(tracks.len());
+    let mut skipped = 0u64;
+    for track in tracks {
+        match hooks.run_on_import_async(track.clone()).await {
+            Ok((HookResult::Continue, mut changed)) => {
+                keep_locked_fields(locks, &track, &mut changed);
+                kept.push(changed);
+            }
+            Ok((HookResult::Skip, _)) => skipped += 1,
+            Ok((HookResult::Abort { reason }, _)) => {
+                anyhow::bail!("Import aborted by plugin: {reason}")
+            }
+            Err(e) => {
+                eprintln!("Warning: {}: {e}", track.path.display());
+                kept.push(track);
+            }
+        }
+    }
+    Ok((kept, skipped))
+}
+
+/// Undo the changes from `original` to `track` of locked fields.
+fn keep_locked_fields(locks: &FieldLocks, original: &Track, track: &mut Track) {
+    if let Err(e) = locks.restore(original, track) {
+        eprintln!(
+            "Failed to keep the locked fields of {}: {e}",
+            track.path.display()
+        );
+    }
+}
+
+/// Create the albums found while autotagging, after their `on_album_import`
+/// hooks.
+///
+/// Tracks of albums that plugins skip are imported without one. Returns the
+/// albums that were created.
+#[tracing::instrument(skip_all)]
+async fn create_imported_albums(
+    db: &SqliteLibrary,
+    hooks: &LuaWorkerPool,
+    albums: Vec<Album>,
+    tracks: &mut [Track],
+) -> Result<Vec<Album>> {
+    // Every hook runs before the first album is created, so that an
+    // aborted import leaves nothing behind
+    let mut kept = Vec::with_capacity(albums.len());
+    for album in albums {
+        match hooks.run_on_album_import_async(album.clone()).await {
+            Ok((HookResult::Continue, album)) => kept.push(album),
+            Ok((HookResult::Skip, _)) => {
+                for track in tracks.iter_mut() {
+                    if track.album_id.as_ref() == Some(&album.id) {
+                        track.album_id = None;
+                    }
+                }
+            }
+            Ok((HookResult::Abort { reason }, _)) => {
+                anyhow::bail!("Import aborted by plugin: {reason}")
+            }
+            Err(e) => {
+                eprintln!("Warning: {}: {e}", album.title);
+                kept.push(album);
+            }
+        }
+    }
+
+    for album in &kept {
+        db.add_album(album).await?;
+    }
+    Ok(kept)
+}
+
+/// Progress bar layout: elapsed time, the bar, counts, and time left.
+const BAR_TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})";
+
+/// Progress bar layout with a message after the counts, such as the file
+/// being worked on.
+const MESSAGE_BAR_TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {wide_msg}";
+
+/// Create a progress bar drawn with a template.
+fn progress_bar(len: u64, template: &str) -> ProgressBar {
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::with_template(template)
+            .unwrap()
+            .progress_chars("█▓▒░"),
+    );
+    bar
+}
+
+/// Create a progress bar for one phase of an import.
+fn import_phase_bar(len: u64, phase: &'static str) -> ProgressBar {
+    let bar = progress_bar(
+        len,
+        "{spinner:.green} [{elapsed_precise}] {prefix} [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {wide_msg}",
+    );
+    bar.set_prefix(phase);
+    bar
+}
+
+/// Get the file name of a path for display in a progress bar.
+fn file_label(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || "...".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// How `apollo import --autotag` chooses matches.
+#[derive(Clone, Copy)]
+struct AutotagOptions {
+    /// Don't ask; decide by score.
+    quiet: bool,
+    /// Minimum score to apply a match in quiet mode.
+    threshold: f64,
+}
+
+/// What to do with an album during autotagging.
+enum AutotagChoice {
+    /// Apply a release's metadata.
+    Accept(Box<ReleaseCandidate>),
+    /// Import the tracks unchanged.
+    AsIs,
+    /// Don't import the tracks.
+    Skip,
+}
+
+/// Match scanned tracks to releases album by album, and tag them.
+///
+/// Returns the tracks to import, the albums to create for them, and the IDs
+/// of the tracks tagged from a release. Tracks already in the library are
+/// passed through untouched.
+#[tracing::instrument(skip_all)]
+async fn autotag_tracks(
+    db: &SqliteLibrary,
+    tracks: Vec<Track>,
+    config: &Config,
+    options: AutotagOptions,
+) -> Result<(Vec<Track>, Vec<Album>, HashSet<TrackId>)> {
+    if !config.musicbrainz.enabled {
+        anyhow::bail!(
+            "MusicBrainz is disabled; enable it with 'apollo config set musicbrainz.enabled true'"
+        );
+    }
+    let client = MusicBrainzClient::new(
+        &config.musicbrainz.app_name,
+        &config.musicbrainz.app_version,
+        &config.musicbrainz.contact_email,
+    )
+    .context("Failed to create MusicBrainz client")?;
+    let matcher = ReleaseMatcher::new(&client);
+
+    let mut result = Vec::with_capacity(tracks.len());
+    let mut new_tracks = Vec::new();
+    for track in tracks {
+        if db.get_track_by_path(&track.path).await?.is_some() {
+            result.push(track);
+        } else {
+            new_tracks.push(track);
+        }
+    }
+
+    let mut albums = Vec::new();
+    let mut tagged = HashSet::new();
+    let mut accepted = 0u64;
+    let mut as_is = 0u64;
+    let mut skipped = 0u64;
+    for (dir, mut group) in group_albums(new_tracks) {
+        let album = group
+            .iter()
+            .find_map(|t| t.album_title.as_deref())
+            .unwrap_or("(no album)");
+        println!();
+        println!("{} ({} tracks)", dir.display(), group.len());
+        println!(
+            "  {} - {album}",
+            group[0].album_artist.as_ref().unwrap_or(&group[0].artist)
+        );
+
+        let candidates = match matcher.candidates(&group).await {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                println!("  Release search failed: {e}");
+                Vec::new()
+            }
+        };
+
+        let choice = if options.quiet {
+            match candidates.into_iter().next() {
+                Some(best) if best.score >= options.threshold => {
+                    println!("  Matched {}", describe_release(&best));
+                    AutotagChoice::Accept(Box::new(best))
+                }
+                best => {
+                    if let Some(best) = best {
+                        println!("  Best match too weak: {}", describe_release(&best));
+                    } else {
+                        println!("  No matching releases");
+                    }
+                    AutotagChoice::AsIs
+                }
+            }
+        } else {
+            choose_release(&matcher, &group, candidates).await?
+        };
+
+        match choice {
+            AutotagChoice::Accept(candidate) => {
+                albums.extend(apply_release(&mut group, &candidate, config));
+                accepted += 1;
+                tagged.extend(group.iter().map(|track| track.id.clone()));
+                result.extend(group);
+            }
+            AutotagChoice::AsIs => {
+                as_is += 1;
+                result.extend(group);
+            }
+            AutotagChoice::Skip => skipped += 1,
+        }
+    }
+
+    println!();
+    println!("Tagged {accepted} albums, {as_is} imported as is, {skipped} skipped");
+    println!();
+    Ok((result, albums, tagged))
+}
+
+/// Drop tracks whose files are in the library, or earlier in the tracks,
+/// under another path.
+///
+/// Files are compared by hash, so tracks without one are kept. Returns the
+/// tracks to import and the number dropped.
+#[tracing::instrument(skip_all)]
+async fn drop_content_duplicates(
+    db: &SqliteLibrary,
+    tracks: Vec<Track>,
+) -> Result<(Vec<Track>, u64)> {
+    let mut seen = HashSet::new();
+    let mut kept = Vec::with_capacity(tracks.len());
+    let mut dropped = 0u64;
+    for track in tracks {
+        if track.file_hash.is_empty() {
+            kept.push(track);
+            continue;
+        }
+        let elsewhere = db
+            .get_track_by_hash(&track.file_hash)
+            .await?
+            .is_some_and(|existing| existing.path != track.path);
+        if elsewhere || !seen.insert(track.file_hash.clone()) {
+            tracing::debug!("Skipping copy of a known file: {}", track.path.display());
+            dropped += 1;
+        } else {
+            kept.push(track);
+        }
+    }
+    Ok((kept, dropped))
+}
+
+/// Group tracks into albums by directory and album tags.
+fn group_albums(tracks: Vec<Track>) -> Vec<(PathBuf, Vec<Track>)> {
+    let mut albums: BTreeMap<(PathBuf, String), Vec<Track>> = BTreeMap::new();
+    for track in tracks {
+        let dir = track
+            .path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let album = format!(
+            "{}::{}",
+            track
+                .album_artist
+                .as_ref()
+                .unwrap_or(&track.artist)
+                .to_lowercase(),
+            track
+                .album_title
+                .as_deref()
+                .unwrap_or_default()
+                .to_lowercase()
+        );
+        albums.entry((dir, album)).or_default().push(track);
+    }
+    albums
+        .into_iter()
+        .map(|((dir, _), mut tracks)| {
+            tracks.sort_by_key(|t| (t.disc_number, t.track_number, t.path.clone()));
+            (dir, tracks)
+        })
+        .collect()
+}
+
+/// Describe a release on one line, with its score.
+fn describe_release(candidate: &ReleaseCandidate) -> String {
+    let release = &candidate.release;
+    let details: Vec<String> = [
+        release.year().map(|year| year.to_string()),
+        release.country.clone(),
+        release
+            .media
+            .first()
+            .and_then(|medium| medium.format.clone()),
+        Some(format!("{} tracks", candidate.release_tracks.len())),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    format!(
+        "{} - {} ({}) {:.0}%",
+        release.artist_name(),
+        release.title,
+        details.join(", "),
+        candidate.score * 100.0
+    )
+}
+
+/// Show how a release would change each track.
+fn print_release_diff(tracks: &[Track], candidate: &ReleaseCandidate) {
+    let mut tagged = tracks.to_vec();
+    candidate.apply(&mut tagged);
+    for (index, (track, new)) in tracks.iter().zip(&tagged).enumerate() {
+        let file = track.path.file_name().map_or_else(
+            || track.path.display().to_string(),
+            |f| f.to_string_lossy().into_owned(),
+        );
+        if candidate.release_track(index).is_none() {
+            println!("    {file} (no matching track)");
+        } else {
+            println!("    {file}");
+        }
+        for change in &TrackDiff::between(track, new).changes {
+            println!("      {change}");
+        }
+    }
+    let missing = candidate.missing_tracks();
+    if missing > 0 {
+        println!("    Missing {missing} tracks of the release");
+    }
+}
+
+/// Ask which release to apply to an album.
+async fn choose_release(
+    matcher: &ReleaseMatcher<'_>,
+    tracks: &[Track],
+    mut candidates: Vec<ReleaseCandidate>,
+) -> Result<AutotagChoice> {
+    let mut selected = 0;
+    loop {
+        if candidates.is_empty() {
+            println!("  No matching releases");
+        } else {
+            println!("  Candidates:");
+            for (index, candidate) in candidates.iter().enumerate() {
+                let marker = if index == selected { '*' } else { ' ' };
+                println!("  {marker}{}. {}", index + 1, describe_release(candidate));
+            }
+            println!("  Changes for {}:", selected + 1);
+            print_release_diff(tracks, &candidates[selected]);
+        }
+
+        let input = prompt(if candidates.is_empty() {
+            "[U]se as is, [S]kip, [M]anual search? "
+        } else {
+            "[A]ccept, [1-9] show candidate, [U]se as is, [S]kip, [M]anual search? "
+        })?;
+        match input.to_lowercase().as_str() {
+            "a" if !candidates.is_empty() => {
+                return Ok(AutotagChoice::Accept(Box::new(
+                    candidates.swap_remove(selected),
+                )));
+            }
+            "u" => return Ok(AutotagChoice::AsIs),
+            "s" => return Ok(AutotagChoice::Skip),
+            "m" => {
+                let search = prompt("Album, artist - album, or release ID: ")?;
+                let found = if uuid::Uuid::parse_str(&search).is_ok() {
+                    matcher.lookup(tracks, &search).await.map(|c| vec![c])
+                } else if let Some((artist, album)) = search.split_once(" - ") {
+                    matcher
+                        .search(tracks, album.trim(), Some(artist.trim()))
+                        .await
+                } else {
+                    matcher.search(tracks, &search, None).await
+                };
+                match found {
+                    Ok(found) => {
+                        candidates = found;
+                        selected = 0;
+                    }
+                    Err(e) => println!("  Search failed: {e}"),
+                }
+            }
+            other => match other.parse::<usize>() {
+                Ok(number) if (1..=candidates.len()).contains(&number) => selected = number - 1,
+                _ => println!("  Unknown choice: {input}"),
+            },
+        }
+    }
+}
+
+/// Print a prompt and read a trimmed line from stdin.
+fn prompt(message: &str) -> Result<String> {
+    use std::io::Write;
+
+    print!("{message}");
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    if std::io::stdin().read_line(&mut input)? == 0 {
+        anyhow::bail!("No more input; use --quiet to import without prompts");
+    }
+    Ok(input.trim().to_string())
+}
+
+/// Tag an album's tracks from a release.
+///
+/// Locked fields keep their values. Returns the album to create for the
+/// tracks, if albums are created on import. The tracks already refer to it.
+fn apply_release(
+    tracks: &mut [Track],
+    candidate: &ReleaseCandidate,
+    config: &Config,
+) -> Option<Album> {
+    let locks = config.locks.locked_fields();
+    let before = (!locks.is_empty()).then(|| tracks.to_vec());
+    candidate.apply(tracks);
+    for (original, track) in before.iter().flatten().zip(tracks.iter_mut()) {
+        keep_locked_fields(&locks, original, track);
+    }
+
+    let album = config.import.auto_create_albums.then(|| {
+        let release = &candidate.release;
+        let mut album = Album::new(release.title.clone(), release.artist_name());
+        release.apply_to_album(&mut album);
+        album.track_count = u32::try_from(candidate.release_tracks.len()).unwrap_or(u32::MAX);
+        album.disc_count = u32::try_from(release.media.len()).unwrap_or(u32::MAX);
+        let refs: Vec<&Track> = tracks.iter().collect();
+        album.is_compilation =
+            album.detect_compilation(&refs, config.import.compilation_min_artists);
+        for track in tracks.iter_mut() {
+            track.album_id = Some(album.id.clone());
+        }
+        album
+    });
+
+    // Deferred writes are queued once the tracks are in the library
+    if config.import.write_tags && !config.write.deferred {
+        let fields = config.write.written_fields();
+        for track in tracks.iter_mut() {
+            if let Err(e) = write_tags(track, &fields, &[]) {
+                eprintln!("Failed to write tags to {}: {e}", track.path.display());
+            }
+        }
+    }
+
+    album
+}
+
+/// List items in the library.
+async fn cmd_list(
+    lib_path: &Path,
+    list_type: ListType,
+    limit: u32,
+    offset: u32,
+    output: OutputFormat,
+    format: Option<&PathTemplate>,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    match list_type {
+        ListType::Tracks => {
+            let tracks = db.list_tracks(limit, offset).await?;
+            if output != OutputFormat::Plain {
+                return print_json_list(output, &tracks);
+            }
+            if let Some(template) = format {
+                for track in &tracks {
+                    println!(
+                        "{}",
+                        template.render_text(&TemplateContext::from_track(track))?
+                    );
+                }
+                return Ok(());
+            }
+            let total = db.count_tracks().await?;
+
+            if tracks.is_empty() {
+                println!("No tracks in library");
+                return Ok(());
+            }
+
+            let count = tracks.len() as u32;
+            println!(
+                "Showing tracks {}-{} of {total}",
+                offset + 1,
+                offset + count
+            );
+            println!();
+
+            for track in &tracks {
+                let duration = format_duration(track.duration);
+                let album = track.album_title.as_deref().unwrap_or("-");
+                let track_num = track
+                    .track_number
+                    .map_or_else(|| "--".to_string(), |n| format!("{n:02}"));
+
+                println!(
+                    "{track_num}. {} - {} [{album}] ({duration})",
+                    track.artist, track.title
+                );
+            }
+
+            if offset + count < total as u32 {
+                println!();
+                println!("Use --offset {} to see more", offset + count);
+            }
+        }
+        ListType::Albums => {
+            let albums = db.list_albums(limit, offset).await?;
+            if output != OutputFormat::Plain {
+                return print_json_list(output, &albums);
+            }
+            if let Some(template) = format {
+                for album in &albums {
+                    println!(
+                        "{}",
+                        template.render_text(&TemplateContext::from_album(album))?
+                    );
+                }
+                return Ok(());
+            }
+            let total = db.count_albums().await?;
+
+            if albums.is_empty() {
+                println!("No albums in library");
+                return Ok(());
+            }
+
+            let count = albums.len() as u32;
+            println!(
+                "Showing albums {}-{} of {total}",
+                offset + 1,
+                offset + count
+            );
+            println!();
+
+            for album in &albums {
+                let year = album.year.map_or_else(String::new, |y| format!(" ({y})"));
+                let tracks = album.track_count;
+
+                println!("{} - {}{year} [{tracks} tracks]", album.artist, album.title);
+            }
+
+            if offset + count < total as u32 {
+                println!();
+                println!("Use --offset {} to see more", offset + count);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Search the library.
+async fn cmd_query(
+    lib_path: &Path,
+    query: &str,
+    kind: SearchType,
+    limit: u32,
+    output: OutputFormat,
+    format: Option<&PathTemplate>,
+    fields: Option<&str>,
+) -> Result<()> {
+    // Check fields before opening the library
+    let export_format = if output == OutputFormat::Ndjson {
+        ExportFormat::Jsonl
+    } else {
+        ExportFormat::Json
+    };
+    let exporter = fields
+        .map(|fields| match kind {
+            SearchType::Tracks => Exporter::tracks(export_format, Some(fields)),
+            SearchType::Albums => Exporter::albums(export_format, Some(fields)),
+            SearchType::Artists => Exporter::artists(export_format, Some(fields)),
+            SearchType::Playlists => Exporter::playlists(export_format, Some(fields)),
+        })
+        .transpose()?;
+    if format.is_some() && matches!(kind, SearchType::Artists | SearchType::Playlists) {
+        anyhow::bail!("--format can only be used for tracks and albums");
+    }
+
+    // Split off sort terms like "sort:year-"
+    let (search, sort) = SortSpec::extract(query).context("Invalid sort")?;
+    if !sort.is_empty() && kind != SearchType::Tracks {
+        anyhow::bail!("Sort terms can only be used when searching tracks");
+    }
+
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let fts_query = to_fts_query(search);
+    let results = SearchResults {
+        query,
+        limit: limit as usize,
+        output,
+        exporter,
+        templated: format.is_some(),
+    };
+    match kind {
+        SearchType::Tracks => {
+            let tracks = db.search_tracks_sorted(&fts_query, &sort).await?;
+            results.print("tracks", &tracks, |track| {
+                if let Some(template) = format {
+                    return Ok(template.render_text(&TemplateContext::from_track(track))?);
+                }
+                let duration = format_duration(track.duration);
+                let album = track.album_title.as_deref().unwrap_or("-");
+                Ok(format!(
+                    "{} - {} [{album}] ({duration})",
+                    track.artist, track.title
+                ))
+            })
+        }
+        SearchType::Albums => {
+            let albums = db.search_albums(&fts_query).await?;
+            results.print("albums", &albums, |album| {
+                if let Some(template) = format {
+                    return Ok(template.render_text(&TemplateContext::from_album(album))?);
+                }
+                let year = album.year.map_or_else(String::new, |y| format!(" ({y})"));
+                Ok(format!(
+                    "{} - {}{year} [{} tracks]",
+                    album.artist, album.title, album.track_count
+                ))
+            })
+        }
+        SearchType::Artists => {
+            let artists = db.search_artists(&fts_query).await?;
+            results.print("artists", &artists, |artist| {
+                Ok(format!(
+                    "{} [{} tracks, {} albums]",
+                    artist.name, artist.track_count, artist.album_count
+                ))
+            })
+        }
+        SearchType::Playlists => {
+            let playlists = db.search_playlists(&fts_query).await?;
+            results.print("playlists", &playlists, |playlist| {
+                let kind = if playlist.is_smart() {
+                    "smart".to_string()
+                } else {
+                    format!("{} tracks", playlist.track_ids.len())
+                };
+                let desc = playlist
+                    .description
+                    .as_ref()
+                    .map(|d| format!(" - {d}"))
+                    .unwrap_or_default();
+                Ok(format!("{} [{kind}]{desc}", playlist.name))
+            })
+        }
+    }
+}
+
+/// Convert a search to full-text search syntax.
+fn to_fts_query(search: String) -> String {
+    // FTS5 requires special query syntax; wrap in quotes for phrase search
+    // or use * for prefix matching
+    if search.contains(':') || search.contains('"') || search.contains('*') {
+        // User provided FTS syntax, use as-is
+        search
+    } else {
+        // Simple search: add wildcards for prefix matching
+        search
+            .split_whitespace()
+            .map(|word| format!("{word}*"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// How to print the results of `apollo query`.
+struct SearchResults<'a> {
+    query: &'a str,
+    limit: usize,
+    output: OutputFormat,
+    /// Picks the fields to print, if they were chosen
+    exporter: Option<Exporter>,
+    /// Whether results are printed with a template, one per line
+    templated: bool,
+}
+
+impl SearchResults<'_> {
+    /// Print up to the limit of results, described by `line` in plain output.
+    fn print<T: Serialize>(
+        &self,
+        noun: &str,
+        items: &[T],
+        line: impl Fn(&T) -> Result<String>,
+    ) -> Result<()> {
+        let limited = &items[..items.len().min(self.limit)];
+        if self.output != OutputFormat::Plain {
+            return match &self.exporter {
+                Some(exporter) => {
+                    print!("{}", exporter.export(limited)?);
+                    Ok(())
+                }
+                None => print_json_list(self.output, limited),
+            };
+        }
+        if let Some(exporter) = &self.exporter {
+            for item in limited {
+                println!("{}", exporter.values(item)?.join("\t"));
+            }
+            return Ok(());
+        }
+        if self.templated {
+            for item in limited {
+                println!("{}", line(item)?);
+            }
+            return Ok(());
+        }
+
+        if items.is_empty() {
+            println!("No {noun} found matching: {}", self.query);
+            return Ok(());
+        }
+
+        println!("Found {} {noun} matching: {}", items.len(), self.query);
+        println!();
+
+        for item in limited {
+            println!("{}", line(item)?);
+        }
+
+        if items.len() > limited.len() {
+            println!();
+            println!("...and {} more", items.len() - limited.len());
+        }
+
+        Ok(())
+    }
+}
+
+/// Show library statistics.
+#[allow(clippy::too_many_lines)]
+async fn cmd_stats(
+    lib_path: &Path,
+    by: Option<StatsBreakdown>,
+    top: u32,
+    output: OutputFormat,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let mut stats = db.library_stats().await?;
+
+    // File sizes are not stored, so read them from disk
+    let mut missing_files = 0u64;
+    for track in db.list_tracks(u32::MAX, 0).await? {
+        match std::fs::metadata(&track.path) {
+            Ok(metadata) => stats.total_size_bytes += metadata.len(),
+            Err(_) => missing_files += 1,
+        }
+    }
+
+    let newest = db.newest_tracks(top).await?;
+    let most_played = db.most_played_tracks(top).await?;
+    let groups = match by {
+        Some(breakdown) => db.stats_by(breakdown).await?,
+        None => Vec::new(),
+    };
+
+    if output != OutputFormat::Plain {
+        let newest: Vec<_> = newest
+            .iter()
+            .map(|track| {
+                serde_json::json!({
+                    "id": track.id,
+                    "title": track.title,
+                    "artist": track.artist,
+                    "added_at": track.added_at,
+                })
+            })
+            .collect();
+        let most_played: Vec<_> = most_played
+            .iter()
+            .map(|(track, play_count)| {
+                serde_json::json!({
+                    "id": track.id,
+                    "title": track.title,
+                    "artist": track.artist,
+                    "play_count": play_count,
+                })
+            })
+            .collect();
+        let mut json = serde_json::json!({
+            "library": lib_path,
+            "tracks": stats.track_count,
+            "albums": stats.album_count,
+            "artists": stats.artist_count,
+            "total_duration_secs": stats.total_duration_secs,
+            "total_size_bytes": stats.total_size_bytes,
+            "missing_files": missing_files,
+            "newest": newest,
+            "most_played": most_played,
+        });
+        if let Some(breakdown) = by {
+            json["by"] = serde_json::json!(breakdown);
+            json["groups"] = serde_json::json!(groups);
+        }
+        return print_json(output, &json);
+    }
+
+    println!("Library: {}", lib_path.display());
+    println!();
+    println!("Tracks:   {}", stats.track_count);
+    println!("Albums:   {}", stats.album_count);
+    println!("Artists:  {}", stats.artist_count);
+    println!(
+        "Duration: {}",
+        format_long_duration(stats.total_duration_secs)
+    );
+    if missing_files > 0 {
+        println!(
+            "Size:     {} ({missing_files} files missing)",
+            format_size(stats.total_size_bytes)
+        );
+    } else {
+        println!("Size:     {}", format_size(stats.total_size_bytes));
+    }
+
+    if !newest.is_empty() {
+        println!();
+        println!("Newest additions:");
+        for track in &newest {
+            println!(
+                "  {}  {} - {}",
+                track.added_at.format("%Y-%m-%d"),
+                track.artist,
+                track.title
+            );
+        }
+    }
+
+    if !most_played.is_empty() {
+        println!();
+        println!("Most played:");
+        for (track, play_count) in &most_played {
+            println!("  {play_count:>5}  {} - {}", track.artist, track.title);
+        }
+    }
+
+    if let Some(breakdown) = by {
+        let heading = match breakdown {
+            StatsBreakdown::Format => "Format",
+            StatsBreakdown::Genre => "Genre",
+            StatsBreakdown::Decade => "Decade",
+            StatsBreakdown::Artist => "Artist",
+        };
+        let names: Vec<&str> = groups
+            .iter()
+            .map(|group| group.name.as_deref().unwrap_or("(none)"))
+            .collect();
+        let width = names
+            .iter()
+            .map(|name| name.chars().count())
+            .chain([heading.len()])
+            .max()
+            .unwrap_or_default();
+
+        println!();
+        println!("{heading:<width$}  {:>7}  {:>12}", "Tracks", "Duration");
+        for (name, group) in names.iter().zip(&groups) {
+            println!(
+                "{name:<width$}  {:>7}  {:>12}",
+                group.track_count,
+                format_long_duration(group.total_duration_secs)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Normalize genres of all tracks and albums.
+async fn cmd_fix_genres(lib_path: &Path, config: &Config, dry_run: bool) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let normalizer = GenreNormalizer::from_config(&config.genres);
+    if dry_run {
+        println!("DRY RUN - no changes will be made");
+        println!();
+    }
+
+    let mut tracks_fixed = 0u64;
+    for mut track in db.list_tracks(u32::MAX, 0).await? {
+        let genres = normalizer.normalize(&track.genres);
+        if genres == track.genres {
+            continue;
+        }
+        println!(
+            "{} - {}: {} -> {}",
+            track.artist,
+            track.title,
+            track.genres.join(", "),
+            genres.join(", ")
+        );
+        if !dry_run {
+            track.genres = genres;
+            db.update_track(&track).await?;
+        }
+        tracks_fixed += 1;
+    }
+
+    let mut albums_fixed = 0u64;
+    for mut album in db.list_albums(u32::MAX, 0).await? {
+        let genres = normalizer.normalize(&album.genres);
+        if genres == album.genres {
+            continue;
+        }
+        println!(
+            "{} - {}: {} -> {}",
+            album.artist,
+            album.title,
+            album.genres.join(", "),
+            genres.join(", ")
+        );
+        if !dry_run {
+            album.genres = genres;
+            db.update_album(&album).await?;
+        }
+        albums_fixed += 1;
+    }
+
+    println!();
+    if dry_run {
+        println!("Would fix {tracks_fixed} tracks and {albums_fixed} albums");
+    } else {
+        println!("Fixed {tracks_fixed} tracks and {albums_fixed} albums");
+    }
+
+    Ok(())
+}
+
+/// Credit featured artists of all tracks in the configured style.
+///
+/// Tracks whose artist or title is locked are left alone.
+async fn cmd_fix_featured(lib_path: &Path, config: &Config, dry_run: bool) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let normalizer = CreditNormalizer::from_config(&config.featured);
+    if dry_run {
+        println!("DRY RUN - no changes will be made");
+        println!();
+    }
+
+    let global_locks = config.locks.locked_fields();
+    let mut fixed = 0u64;
+    for mut track in db.list_tracks(u32::MAX, 0).await? {
+        let original = (track.artist.clone(), track.title.clone());
+        if !normalizer.normalize(&mut track) {
+            continue;
+        }
+        let mut locks = global_locks.clone();
+        locks.extend(db.get_field_locks(&track.id).await?);
+        if locks.is_locked(EditField::Artist) || locks.is_locked(EditField::Title) {
+            continue;
+        }
+        println!(
+            "{} - {} -> {} - {}",
+            original.0, original.1, track.artist, track.title
+        );
+        if !dry_run {
+            db.update_track(&track).await?;
+        }
+        fixed += 1;
+    }
+
+    println!();
+    if dry_run {
+        println!("Would fix {fixed} tracks");
+    } else {
+        println!("Fixed {fixed} tracks");
+    }
+
+    Ok(())
+}
+
+/// Rescan library files: re-read changed files, relink moved files, and mark
+/// missing ones.
+#[allow(clippy::too_many_lines)]
+async fn cmd_update(
+    lib_path: &Path,
+    search_dir: Option<&Path>,
+    genre_normalizer: Option<&GenreNormalizer>,
+    dry_run: bool,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    if dry_run {
+        println!("DRY RUN - no changes will be made");
+        println!();
+    }
+
+    let marked_missing: HashSet<TrackId> = db
+        .list_missing_tracks()
+        .await?
+        .into_iter()
+        .map(|(track, _)| track.id)
+        .collect();
+    let mut tracks = db.list_tracks(u32::MAX, 0).await?;
+    if let Some(dir) = search_dir {
+        tracks.retain(|track| track.path.starts_with(dir));
+    }
+
+    let mut updated = 0u64;
+    let mut found = 0u64;
+    let mut failed = 0u64;
+    let mut missing = Vec::new();
+    for track in tracks {
+        if !track.path.exists() {
+            missing.push(track);
+            continue;
+        }
+        if marked_missing.contains(&track.id) {
+            println!("Found: {}", track.path.display());
+            if !dry_run {
+                db.set_track_missing(&track.id, false).await?;
+            }
+            found += 1;
+        }
+
+        // Only hash files that were modified since the track was saved
+        let modified = std::fs::metadata(&track.path)
+            .and_then(|meta| meta.modified())
+            .map(DateTime::<Utc>::from)?;
+        if modified <= track.modified_at {
+            continue;
+        }
+        let hash = compute_file_hash(&track.path)?;
+        if hash == track.file_hash {
+            continue;
+        }
+
+        match read_metadata(&track.path) {
+            Ok(read) => {
+                let refreshed = refresh_track(&track, read, hash, genre_normalizer);
+                let diff = TrackDiff::between(&track, &refreshed);
+                println!("Updated: {}", track.path.display());
+                for change in diff.changes.iter().filter(|c| c.field != "file_hash") {
+                    println!("  {change}");
+                }
+                if !dry_run {
+                    db.update_track(&refreshed).await?;
+                    db.record_provenance(
+                        &track.id,
+                        &Provenance::from_diff(&diff, MetadataSource::File),
+                    )
+                    .await?;
+                }
+                updated += 1;
+            }
+            Err(e) => {
+                eprintln!("Failed to read {}: {e}", track.path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    // Look for missing files elsewhere, by content
+    let mut moved = 0u64;
+    if !missing.is_empty()
+        && let Some(dir) = search_dir
+    {
+        let mut by_hash = unknown_files_by_hash(&db, dir).await?;
+        let mut still_missing = Vec::new();
+        for track in missing {
+            let Some(file) = by_hash.remove(&track.file_hash) else {
+                still_missing.push(track);
+                continue;
+            };
+            println!("Moved: {} -> {}", track.path.display(), file.path.display());
+            if !dry_run {
+                let hash = file.file_hash.clone();
+                db.update_track(&refresh_track(&track, file, hash, genre_normalizer))
+                    .await?;
+                if marked_missing.contains(&track.id) {
+                    db.set_track_missing(&track.id, false).await?;
+                }
+            }
+            moved += 1;
+        }
+        missing = still_missing;
+    }
+
+    for track in &missing {
+        println!("Missing: {}", track.path.display());
+        if !dry_run && !marked_missing.contains(&track.id) {
+            db.set_track_missing(&track.id, true).await?;
+        }
+    }
+
+    println!();
+    let verb = if dry_run { "Would update" } else { "Updated" };
+    println!(
+        "{verb} {updated} changed, {moved} moved, {found} found, and {} missing tracks",
+        missing.len()
+    );
+    if failed > 0 {
+        println!("Failed to read {failed} files");
+    }
+
+    Ok(())
+}
+
+/// Scan a directory for audio files that are not in the library, keyed by
+/// content hash.
+async fn unknown_files_by_hash(db: &SqliteLibrary, dir: &Path) -> Result<HashMap<String, Track>> {
+    let scan = scan_directory(
+        dir,
+        &ScanOptions::default(),
+        None,
+        None::<fn(&ScanProgress)>,
+    )
+    .context("Failed to scan directory")?;
+
+    let mut by_hash = HashMap::new();
+    for file in scan.tracks {
+        if db.get_track_by_path(&file.path).await?.is_none() {
+            by_hash.insert(file.file_hash.clone(), file);
+        }
+    }
+    Ok(by_hash)
+}
+
+/// List tracks whose files are missing, and optionally relink or remove
+/// them.
+async fn cmd_missing(
+    lib_path: &Path,
+    relink: Option<&Path>,
+    remove: bool,
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let marked_missing: HashMap<TrackId, DateTime<Utc>> = db
+        .list_missing_tracks()
+        .await?
+        .into_iter()
+        .map(|(track, since)| (track.id, since))
+        .collect();
+    let mut missing: Vec<Track> = db
+        .list_tracks(u32::MAX, 0)
+        .await?
+        .into_iter()
+        .filter(|track| !track.path.exists())
+        .collect();
+
+    if missing.is_empty() {
+        println!("No missing tracks.");
+        return Ok(());
+    }
+
+    // Look for the files elsewhere, by content
+    let mut relinked = 0u64;
+    if let Some(dir) = relink {
+        let mut by_hash = unknown_files_by_hash(&db, dir).await?;
+        let mut still_missing = Vec::new();
+        for mut track in missing {
+            let file = (!track.file_hash.is_empty())
+                .then(|| by_hash.remove(&track.file_hash))
+                .flatten();
+            let Some(file) = file else {
+                still_missing.push(track);
+                continue;
+            };
+            println!(
+                "Relinked: {} -> {}",
+                track.path.display(),
+                file.path.display()
+            );
+            if !dry_run {
+                track.path = file.path;
+                db.update_track(&track).await?;
+                if marked_missing.contains_key(&track.id) {
+                    db.set_track_missing(&track.id, false).await?;
+                }
+            }
+            relinked += 1;
+        }
+        missing = still_missing;
+        println!();
+    }
+
+    for track in &missing {
+        let since = marked_missing
+            .get(&track.id)
+            .map(|since| format!(" (missing since {})", since.format("%Y-%m-%d")))
+            .unwrap_or_default();
+        println!("{} - {}{since}", track.artist, track.title);
+        println!("  {}", track.path.display());
+    }
+    println!();
+
+    let verb = if dry_run { "Would relink" } else { "Relinked" };
+    if relink.is_some() {
+        println!("{verb} {relinked} tracks");
+    }
+    println!("{} tracks missing", missing.len());
+
+    if !remove || missing.is_empty() {
+        return Ok(());
+    }
+    if dry_run {
+        println!("Would remove {} tracks", missing.len());
+        return Ok(());
+    }
+    if !yes {
+        println!("Remove {} tracks from the library? [y/N] ", missing.len());
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled");
+            return Ok(());
+        }
+    }
+    for track in &missing {
+        db.remove_track(&track.id).await?;
+    }
+    println!("Removed {} tracks", missing.len());
+
+    Ok(())
+}
+
+/// Counts of verified files, by result.
+#[derive(Default)]
+struct VerifySummary {
+    ok: u64,
+    changed: u64,
+    corrupt: u64,
+    missing: u64,
+    unhashed: u64,
+    undecodable: u64,
+}
+
+/// Convert tracks to another format under a destination directory.
+#[allow(clippy::too_many_arguments, clippy::fn_params_excessive_bools)]
+async fn cmd_convert(
+    lib_path: &Path,
+    selection: &[String],
+    destination: &Path,
+    template: Option<&str>,
+    config: &Config,
+    options: &ConvertOptions,
+    force: bool,
+    dry_run: bool,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let template_str = template
+        .or(config.convert.path_template.as_deref())
+        .unwrap_or(&config.paths.path_template);
+    let template = PathTemplate::parse(template_str)
+        .with_context(|| format!("Invalid path template: {template_str}"))?;
+
+    let tracks = if selection.is_empty() {
+        db.list_tracks(u32::MAX, 0).await?
+    } else {
+        find_tracks(&db, selection).await?
+    };
+    if tracks.is_empty() {
+        println!("No tracks to convert");
+        return Ok(());
+    }
+
+    // Albums are needed for compilations and %aunique
+    let albums = db.list_albums(u32::MAX, 0).await?;
+    let album_set = AlbumSet::new(albums.iter().cloned());
+    let albums: HashMap<AlbumId, Album> = albums
+        .into_iter()
+        .map(|album| (album.id.clone(), album))
+        .collect();
+
+    let bar = progress_bar(tracks.len() as u64, MESSAGE_BAR_TEMPLATE);
+
+    let mut converted = 0u64;
+    let mut up_to_date = 0u64;
+    let mut failed = 0u64;
+    let mut messages = Vec::new();
+    for track in &tracks {
+        bar.inc(1);
+
+        let ext = Some(options.format.extension());
+        let dest = match copy_path(&template, track, &albums, &album_set, ext) {
+            Ok(relative) => destination.join(relative),
+            Err(e) => {
+                messages.push(format!("Template error for {}: {e}", track.path.display()));
+                failed += 1;
+                continue;
+            }
+        };
+
+        if !force && is_converted(&track.path, &dest) {
+            up_to_date += 1;
+            continue;
+        }
+        if dry_run {
+            messages.push(format!("{} -> {}", track.path.display(), dest.display()));
+            converted += 1;
+            continue;
+        }
+
+        bar.set_message(file_label(&track.path));
+        match convert_file(&track.path, &dest, options) {
+            Ok(()) => converted += 1,
+            Err(e) => {
+                messages.push(format!("Failed: {e}"));
+                failed += 1;
+            }
+        }
+    }
+    bar.finish_and_clear();
+
+    for message in &messages {
+        println!("{message}");
+    }
+    if !messages.is_empty() {
+        println!();
+    }
+    if dry_run {
+        println!("Would convert: {converted}");
+    } else {
+        println!("Converted: {converted}");
+    }
+    println!("Up to date: {up_to_date}");
+    if failed > 0 {
+        println!("Failed: {failed}");
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Render the path of a track's copy, relative to the destination.
+///
+/// Copies that are converted get the extension of their new format.
+fn copy_path(
+    template: &PathTemplate,
+    track: &Track,
+    albums: &HashMap<AlbumId, Album>,
+    album_set: &AlbumSet,
+    ext: Option<&str>,
+) -> Result<PathBuf> {
+    let mut ctx = TemplateContext::from_track(track);
+    if let Some(album) = track.album_id.as_ref().and_then(|id| albums.get(id)) {
+        ctx.set_album(album, album_set);
+    }
+    if let Some(ext) = ext {
+        ctx.set("ext", ext);
+    }
+    Ok(template.render_with_extension(&ctx)?)
+}
+
+/// Mirror the tracks and playlists of a sync profile to its destination.
+#[allow(clippy::too_many_lines)]
+async fn cmd_sync(
+    lib_path: &Path,
+    config: &Config,
+    name: &str,
+    force: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let Some(profile) = config.sync.get(name) else {
+        if config.sync.is_empty() {
+            anyhow::bail!(
+                "No sync profiles configured; add one under [sync.{name}] in the configuration file"
+            );
+        }
+        let names: Vec<&str> = config.sync.keys().map(String::as_str).collect();
+        anyhow::bail!(
+            "Unknown sync profile: {name} (available: {})",
+            names.join(", ")
+        );
+    };
+
+    // An unmounted device must not be filled up as a local directory
+    let destination = profile.destination_path();
+    if !destination.is_dir() {
+        anyhow::bail!(
+            "Destination not found: {}; is the device connected?",
+            destination.display()
+        );
+    }
+
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let template_str = profile
+        .path_template
+        .as_deref()
+        .unwrap_or(&config.paths.path_template);
+    let template = PathTemplate::parse(template_str)
+        .with_context(|| format!("Invalid path template: {template_str}"))?;
+
+    // Tracks from the query and the playlists, each once
+    let mut tracks = Vec::new();
+    let mut playlists = Vec::new();
+    if profile.query.is_none() && profile.playlists.is_empty() {
+        tracks = db.list_tracks(u32::MAX, 0).await?;
+    } else {
+        let mut seen = HashSet::new();
+        if let Some(query_str) = &profile.query {
+            let (query, sort) = Query::parse_sorted(query_str)
+                .with_context(|| format!("Invalid query: {query_str}"))?;
+            for track in db.query_tracks(&query, &sort).await? {
+                if seen.insert(track.id.clone()) {
+                    tracks.push(track);
+                }
+            }
+        }
+        for name_or_id in &profile.playlists {
+            let playlist = find_playlist(&db, name_or_id).await?;
+            let playlist_tracks = db.get_playlist_tracks(&playlist.id).await?;
+            for track in &playlist_tracks {
+                if seen.insert(track.id.clone()) {
+                    tracks.push(track.clone());
+                }
+            }
+            playlists.push((playlist, playlist_tracks));
+        }
+    }
+
+    // Albums are needed for compilations and %aunique
+    let albums = db.list_albums(u32::MAX, 0).await?;
+    let album_set = AlbumSet::new(albums.iter().cloned());
+    let albums: HashMap<AlbumId, Album> = albums
+        .into_iter()
+        .map(|album| (album.id.clone(), album))
+        .collect();
+
+    let convert_options = profile.format.map(|format| {
+        let mut options = ConvertOptions::new(format.into(), profile.bitrate);
+        options.ffmpeg.clone_from(&config.convert.ffmpeg);
+        options
+    });
+
+    let bar = progress_bar(tracks.len() as u64, MESSAGE_BAR_TEMPLATE);
+
+    let mut copied = 0u64;
+    let mut converted = 0u64;
+    let mut up_to_date = 0u64;
+    let mut failed = 0u64;
+    let mut messages = Vec::new();
+    // Where each track's copy is, including copies that failed to update
+    let mut synced: HashMap<TrackId, PathBuf> = HashMap::new();
+    for track in &tracks {
+        bar.inc(1);
+
+        let options = convert_options
+            .as_ref()
+            .filter(|_| !profile.lossless_only || track.format.is_lossless());
+        let ext = options.map(|options| options.format.extension());
+        let dest = match copy_path(&template, track, &albums, &album_set, ext) {
+            Ok(relative) => destination.join(relative),
+            Err(e) => {
+                messages.push(format!("Template error for {}: {e}", track.path.display()));
+                failed += 1;
+                continue;
+            }
+        };
+        synced.insert(track.id.clone(), dest.clone());
+
+        if !force && is_converted(&track.path, &dest) {
+            up_to_date += 1;
+            continue;
+        }
+        if dry_run {
+            let action = if options.is_some() { "Convert" } else { "Copy" };
+            messages.push(format!("{action}: {}", dest.display()));
+        } else {
+            bar.set_message(file_label(&track.path));
+            let result = options.map_or_else(
+                || copy_file(&track.path, &dest),
+                |options| convert_file(&track.path, &dest, options),
+            );
+            if let Err(e) = result {
+                messages.push(format!("Failed: {e}"));
+                failed += 1;
+                continue;
+            }
+        }
+        if options.is_some() {
+            converted += 1;
+        } else {
+            copied += 1;
+        }
+    }
+    bar.finish_and_clear();
+
+    // Playlists point at the copies, relative to the destination
+    for (playlist, playlist_tracks) in &playlists {
+        let synced_tracks: Vec<Track> = playlist_tracks
+            .iter()
+            .filter_map(|track| {
+                let path = synced.get(&track.id)?;
+                let mut copy = track.clone();
+                copy.path.clone_from(path);
+                Some(copy)
+            })
+            .collect();
+        let file = destination.join(format!(
+            "{}.m3u8",
+            apollo_core::template::sanitize_path_component(&playlist.name)
+        ));
+        if dry_run {
+            messages.push(format!("Write playlist: {}", file.display()));
+            continue;
+        }
+        let content = write_playlist(
+            PlaylistFormat::M3u8,
+            &playlist.name,
+            &synced_tracks,
+            Some(&destination),
+        );
+        if let Err(e) = std::fs::write(&file, content) {
+            messages.push(format!("Failed to write {}: {e}", file.display()));
+            failed += 1;
+        }
+    }
+
+    // Only audio files are deleted, so other files on the device are kept
+    let mut deleted = 0u64;
+    if profile.delete {
+        let keep: HashSet<&Path> = synced.values().map(PathBuf::as_path).collect();
+        let stale: Vec<PathBuf> = walkdir::WalkDir::new(&destination)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(walkdir::DirEntry::into_path)
+            .filter(|path| is_audio_file(path) && !keep.contains(path.as_path()))
+            .collect();
+        for path in stale {
+            if dry_run {
+                messages.push(format!("Delete: {}", path.display()));
+                deleted += 1;
+                continue;
+            }
+            if let Err(e) = std::fs::remove_file(&path) {
+                messages.push(format!("Failed to delete {}: {e}", path.display()));
+                failed += 1;
+                continue;
+            }
+            deleted += 1;
+            // Remove directories the file leaves empty
+            for dir in path.ancestors().skip(1) {
+                if dir == destination || std::fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    for message in &messages {
+        println!("{message}");
+    }
+    if !messages.is_empty() {
+        println!();
+    }
+    let [copy_label, convert_label, delete_label] = if dry_run {
+        ["Would copy", "Would convert", "Would delete"]
+    } else {
+        ["Copied", "Converted", "Deleted"]
+    };
+    println!(
+        "Synced {} tracks to {}:",
+        tracks.len(),
+        destination.display()
+    );
+    println!("  {copy_label}: {copied}");
+    println!("  {convert_label}: {converted}");
+    println!("  Up to date: {up_to_date}");
+    if profile.delete {
+        println!("  {delete_label}: {deleted}");
+    }
+    if !playlists.is_empty() {
+        println!("  Playlists: {}", playlists.len());
+    }
+    if failed > 0 {
+        println!("  Failed: {failed}");
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Which way `apollo sync remote` copies tracks.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SyncDirection {
+    Pull,
+    Push,
+}
+
+/// What a remote sync copied in one direction.
+#[derive(Default)]
+struct RemoteSyncResult {
+    tracks: u64,
+    files: u64,
+    albums: u64,
+    failed: u64,
+    messages: Vec<String>,
+}
+
+/// Copy the tracks that this library and another Apollo library are missing
+/// to each other, over the web API of the other library.
+async fn cmd_sync_remote(
+    lib_path: &Path,
+    config: &Config,
+    url: &str,
+    only: Option<SyncDirection>,
+    files: bool,
+    dry_run: bool,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let remote = RemoteLibrary::new(url)?;
+    let remote_tracks = remote
+        .list_tracks()
+        .await
+        .with_context(|| format!("Failed to read the library at {url}"))?;
+    let remote_albums = remote
+        .list_albums()
+        .await
+        .with_context(|| format!("Failed to read the library at {url}"))?;
+    let local_tracks = db.list_tracks(u32::MAX, 0).await?;
+    let local_albums = db.list_albums(u32::MAX, 0).await?;
+
+    let verb = |done: &str, would: &str| if dry_run { would } else { done }.to_string();
+    let mut failed = 0;
+    if only != Some(SyncDirection::Push) {
+        let missing = remote::missing_tracks(&remote_tracks, &local_tracks);
+        let result = pull_tracks(
+            &db,
+            &remote,
+            config,
+            &missing,
+            (&remote_albums, &local_albums),
+            files,
+            dry_run,
+        )
+        .await?;
+        print_remote_sync(&verb("Pulled", "Would pull"), &result);
+        failed += result.failed;
+    }
+    if only != Some(SyncDirection::Pull) {
+        let missing = remote::missing_tracks(&local_tracks, &remote_tracks);
+        let result = push_tracks(
+            &remote,
+            &missing,
+            (&local_albums, &remote_albums),
+            files,
+            dry_run,
+        )
+        .await;
+        print_remote_sync(&verb("Pushed", "Would push"), &result);
+        failed += result.failed;
+    }
+
+    if failed > 0 {
+        println!("Failed: {failed}");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Print what a remote sync copied in one direction.
+fn print_remote_sync(verb: &str, result: &RemoteSyncResult) {
+    for message in &result.messages {
+        println!("{message}");
+    }
+    println!(
+        "{verb}: {} tracks, {} files, {} albums",
+        result.tracks, result.files, result.albums
+    );
+}
+
+/// Add the tracks of the remote library to this one, with their albums, and
+/// download their files into the music directory with `files`.
+///
+/// Without `files`, tracks keep the path of the remote library, for files
+/// that are copied another way.
+async fn pull_tracks(
+    db: &SqliteLibrary,
+    remote: &RemoteLibrary,
+    config: &Config,
+    tracks: &[&Track],
+    (remote_albums, local_albums): (&[Album], &[Album]),
+    files: bool,
+    dry_run: bool,
+) -> Result<RemoteSyncResult> {
+    let music_dir = match config.music_directory() {
+        Some(dir) => dir,
+        None if files => anyhow::bail!("Set paths.music_directory to choose where pulled files go"),
+        None => PathBuf::new(),
+    };
+    let template = PathTemplate::parse(&config.paths.path_template)
+        .with_context(|| format!("Invalid path template: {}", config.paths.path_template))?;
+    let album_set = AlbumSet::new(local_albums.iter().cloned());
+    let mut albums: HashMap<AlbumId, Album> = local_albums
+        .iter()
+        .map(|album| (album.id.clone(), album.clone()))
+        .collect();
+    let mut matched = remote::match_albums(remote_albums, local_albums);
+
+    let mut result = RemoteSyncResult::default();
+    for &track in tracks {
+        let label = format!("{} - {}", track.artist, track.title);
+        if dry_run {
+            println!("Would pull: {label}");
+            result.tracks += 1;
+            result.files += u64::from(files);
+            continue;
+        }
+
+        let mut track = track.clone();
+        if let Some(remote_id) = track.album_id.take() {
+            if let Some(id) = matched.get(&remote_id) {
+                track.album_id = Some(id.clone());
+            } else if let Some(album) = remote_albums.iter().find(|a| a.id == remote_id) {
+                db.add_album(album).await?;
+                matched.insert(remote_id.clone(), remote_id.clone());
+                albums.insert(remote_id.clone(), album.clone());
+                track.album_id = Some(remote_id);
+                result.albums += 1;
+            }
+        }
+        if files {
+            let dest = match copy_path(&template, &track, &albums, &album_set, None) {
+                Ok(relative) => music_dir.join(relative),
+                Err(e) => {
+                    result
+                        .messages
+                        .push(format!("Template error for {label}: {e}"));
+                    result.failed += 1;
+                    continue;
+                }
+            };
+            if dest.exists() {
+                result.messages.push(format!(
+                    "Failed to pull {label}: {} is in the way",
+                    dest.display()
+                ));
+                result.failed += 1;
+                continue;
+            }
+            if let Err(e) = remote.download_file(&track.id, &dest).await {
+                result
+                    .messages
+                    .push(format!("Failed to download {label}: {e}"));
+                result.failed += 1;
+                continue;
+            }
+            track.path = dest;
+            result.files += 1;
+        }
+        match db.add_track(&track).await {
+            Ok(_) => result.tracks += 1,
+            Err(e) => {
+                result.messages.push(format!("Failed to pull {label}: {e}"));
+                result.failed += 1;
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Add the tracks of this library to the remote one, with their albums, and
+/// upload their files with `files`.
+///
+/// The remote library places uploaded files in its own music directory.
+/// Without `files`, tracks keep the path of this library.
+async fn push_tracks(
+    remote: &RemoteLibrary,
+    tracks: &[&Track],
+    (local_albums, remote_albums): (&[Album], &[Album]),
+    files: bool,
+    dry_run: bool,
+) -> RemoteSyncResult {
+    let mut matched = remote::match_albums(local_albums, remote_albums);
+
+    let mut result = RemoteSyncResult::default();
+    for &track in tracks {
+        let label = format!("{} - {}", track.artist, track.title);
+        if dry_run {
+            println!("Would push: {label}");
+            result.tracks += 1;
+            result.files += u64::from(files);
+            continue;
+        }
+
+        let mut track = track.clone();
+        if let Some(local_id) = track.album_id.take() {
+            if let Some(id) = matched.get(&local_id) {
+                track.album_id = Some(id.clone());
+            } else if let Some(album) = local_albums.iter().find(|a| a.id == local_id) {
+                if let Err(e) = remote.add_album(album).await {
+                    result
+                        .messages
+                        .push(format!("Failed to push album {}: {e}", album.title));
+                    result.failed += 1;
+                    continue;
+                }
+                matched.insert(local_id.clone(), local_id.clone());
+                track.album_id = Some(local_id);
+                result.albums += 1;
+            }
+        }
+        if let Err(e) = remote.add_track(&track).await {
+            result.messages.push(format!("Failed to push {label}: {e}"));
+            result.failed += 1;
+            continue;
+        }
+        result.tracks += 1;
+        if files {
+            match remote.upload_file(&track.id, &track.path).await {
+                Ok(_) => result.files += 1,
+                Err(e) => {
+                    result
+                        .messages
+                        .push(format!("Failed to upload {label}: {e}"));
+                    result.failed += 1;
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Check library files against their stored hashes, and optionally decode
+/// them.
+async fn cmd_verify(lib_path: &Path, selection: &[String], deep: bool) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let tracks = if selection.is_empty() {
+        db.list_tracks(u32::MAX, 0).await?
+    } else {
+        find_tracks(&db, selection).await?
+    };
+    if tracks.is_empty() {
+        println!("No tracks to verify");
+        return Ok(());
+    }
+
+    let bar = progress_bar(tracks.len() as u64, BAR_TEMPLATE);
+
+    let mut summary = VerifySummary::default();
+    let mut problems = Vec::new();
+    for track in &tracks {
+        bar.inc(1);
+        let path = &track.path;
+        if !path.exists() {
+            problems.push(format!("Missing: {}", path.display()));
+            summary.missing += 1;
+            continue;
+        }
+
+        let mut intact = true;
+        if track.file_hash.is_empty() {
+            summary.unhashed += 1;
+        } else if compute_file_hash(path)? != track.file_hash {
+            // Files edited outside Apollo are newer than the track
+            let modified = std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .map(DateTime::<Utc>::from)?;
+            let reason = if modified > track.modified_at {
+                "modified since the last update; run 'apollo update' if this was intended"
+            } else {
+                "modified time unchanged; the file may be damaged"
+            };
+            problems.push(format!("Changed: {} ({reason})", path.display()));
+            summary.changed += 1;
+            intact = false;
+        }
+
+        if deep {
+            match verify_audio(path) {
+                Ok(()) => {}
+                Err(AudioError::UnsupportedFormat(_)) => summary.undecodable += 1,
+                Err(AudioError::Corrupt { reason, .. }) => {
+                    problems.push(format!("Corrupt: {} ({reason})", path.display()));
+                    summary.corrupt += 1;
+                    intact = false;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if intact {
+            summary.ok += 1;
+        }
+    }
+    bar.finish_and_clear();
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    if !problems.is_empty() {
+        println!();
+    }
+    println!("Verified {} tracks:", tracks.len());
+    println!("  OK: {}", summary.ok);
+    println!("  Changed: {}", summary.changed);
+    if deep {
+        println!("  Corrupt: {}", summary.corrupt);
+    }
+    println!("  Missing: {}", summary.missing);
+    if summary.unhashed > 0 {
+        println!("  No stored hash: {}", summary.unhashed);
+    }
+    if summary.undecodable > 0 {
+        println!("  Cannot decode: {}", summary.undecodable);
+    }
+
+    if summary.changed + summary.corrupt + summary.missing > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Handle database maintenance commands.
+async fn cmd_db(lib_path: &Path, action: DbAction) -> Result<()> {
+    let file_size = || std::fs::metadata(lib_path).map(|meta| meta.len());
+
+    match action {
+        DbAction::Backup { path } => {
+            let db = open_unmigrated(lib_path).await?;
+            let path = path.unwrap_or_else(|| {
+                let stem = lib_path
+                    .file_stem()
+                    .map_or_else(|| "library".into(), |stem| stem.to_string_lossy());
+                let time = Local::now().format("%Y%m%d-%H%M%S");
+                lib_path.with_file_name(format!("{stem}-backup-{time}.db"))
+            });
+            println!("Backing up library to {}", path.display());
+            db.backup(&path)
+                .await
+                .context("Failed to back up library")?;
+            let size = std::fs::metadata(&path)?.len();
+            println!("Backup complete ({})", format_size(size));
+        }
+        DbAction::Restore { backup, yes } => {
+            // A backup can be restored where no library exists yet
+            if lib_path.exists() && !yes {
+                println!(
+                    "Replace the library at {} with {}? [y/N] ",
+                    lib_path.display(),
+                    backup.display()
+                );
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input)?;
+                if !input.trim().eq_ignore_ascii_case("y") {
+                    println!("Cancelled");
+                    return Ok(());
+                }
+            }
+            println!("Restoring library from {}", backup.display());
+            SqliteLibrary::restore(&backup, lib_path)
+                .await
+                .context("Failed to restore library")?;
+            println!("Restored library at {}", lib_path.display());
+        }
+        DbAction::Vacuum => {
+            let db = open_unmigrated(lib_path).await?;
+            let before = file_size()?;
+            println!("Vacuuming {}", lib_path.display());
+            db.vacuum().await.context("Failed to vacuum library")?;
+            println!(
+                "Vacuum complete: {} -> {}",
+                format_size(before),
+                format_size(file_size()?)
+            );
+        }
+        DbAction::Check => {
+            let db = open_unmigrated(lib_path).await?;
+            println!("Checking {}", lib_path.display());
+            let problems = db.check().await.context("Failed to check library")?;
+            let version = db.schema_version().await?;
+            if version < SCHEMA_VERSION {
+                println!(
+                    "Schema version {version} is out of date; run 'apollo db migrate' to upgrade it to {SCHEMA_VERSION}"
+                );
+            }
+            if !problems.is_empty() {
+                println!("Found {} problems:", problems.len());
+                for problem in &problems {
+                    println!("  {problem}");
+                }
+                println!();
+                println!("Restore a backup with 'apollo db restore'");
+                std::process::exit(1);
+            }
+            println!("No problems found");
+        }
+        DbAction::Migrate => {
+            let db = open_unmigrated(lib_path).await?;
+            let version = db.schema_version().await?;
+            db.migrate().await.context("Failed to migrate library")?;
+            if version == SCHEMA_VERSION {
+                println!("Schema is up to date (version {SCHEMA_VERSION})");
+            } else {
+                println!("Migrated schema from version {version} to {SCHEMA_VERSION}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Open the library database without migrating it, so maintenance commands
+/// see the schema as it is.
+async fn open_unmigrated(lib_path: &Path) -> Result<SqliteLibrary> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    let db_url = format!("sqlite:{}", lib_path.display());
+    SqliteLibrary::connect(&db_url)
+        .await
+        .context("Failed to open library database")
+}
+
+/// Replace a track's file metadata with metadata read from its file, keeping
+/// its identity in the library.
+fn refresh_track(
+    track: &Track,
+    mut read: Track,
+    file_hash: String,
+    genre_normalizer: Option<&GenreNormalizer>,
+) -> Track {
+    read.id = track.id.clone();
+    read.album_id.clone_from(&track.album_id);
+    read.added_at = track.added_at;
+    read.file_hash = file_hash;
+    if let Some(normalizer) = genre_normalizer {
+        read.genres = normalizer.normalize(&read.genres);
+    }
+    read
+}
+
+/// Watch directories and import new files.
+async fn cmd_watch(lib_path: &Path, directories: Vec<PathBuf>, config: &Config) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let hook_db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?
+        .with_events(db.events().clone());
+    let hooks = spawn_import_hooks(config, Arc::new(hook_db))?;
+
+    let mut watcher = FolderWatcher::new(Arc::new(db), config).with_hooks(Arc::new(hooks));
+    if !directories.is_empty() {
+        watcher = watcher.with_directories(directories);
+    }
+    if watcher.directories().is_empty() {
+        anyhow::bail!("No directories to watch; pass one or set watch.directories");
+    }
+    for directory in watcher.directories() {
+        if !directory.is_dir() {
+            anyhow::bail!("Not a directory: {}", directory.display());
+        }
+        println!("Watching: {}", directory.display());
+    }
+    println!();
+    println!("Press Ctrl+C to stop");
+
+    watcher
+        .run()
+        .await
+        .map_err(|e| anyhow::anyhow!("Watcher failed: {e:?}"))
+}
+
+/// How `apollo tag` applies its changes.
+#[derive(Clone, Copy)]
+struct TagOptions {
+    /// Write the changes to the audio files.
+    write: bool,
+    /// Only show the changes.
+    dry_run: bool,
+    /// Don't ask before making the changes.
+    yes: bool,
+}
+
+/// Build a track edit from `--set` and `--remove` arguments.
+fn parse_track_edit(set: &[String], remove: &[String]) -> Result<TrackEdit> {
+    let mut edit = TrackEdit::new();
+    for assignment in set {
+        edit = edit
+            .set_assignment(assignment)
+            .with_context(|| format!("Invalid --set: {assignment}"))?;
+    }
+    for field in remove {
+        edit = edit
+            .remove(field)
+            .with_context(|| format!("Invalid --remove: {field}"))?;
+    }
+    Ok(edit)
+}
+
+/// Fields that `apollo tag` locks and unlocks.
+struct LockChanges {
+    /// Fields to lock after the edit.
+    lock: Vec<EditField>,
+    /// Fields to unlock before the edit.
+    unlock: Vec<EditField>,
+}
+
+impl LockChanges {
+    /// Check if no field is locked or unlocked.
+    const fn is_empty(&self) -> bool {
+        self.lock.is_empty() && self.unlock.is_empty()
+    }
+
+    /// Get the locked fields of a track while it is edited, and after.
+    fn apply(&self, locked: &[EditField]) -> (Vec<EditField>, Vec<EditField>) {
+        let during: Vec<EditField> = locked
+            .iter()
+            .copied()
+            .filter(|field| !self.unlock.contains(field))
+            .collect();
+        let after = EditField::ALL
+            .iter()
+            .copied()
+            .filter(|field| during.contains(field) || self.lock.contains(field))
+            .collect();
+        (during, after)
+    }
+}
+
+/// Parse field names given to an option.
+fn parse_fields(names: &[String], option: &str) -> Result<Vec<EditField>> {
+    names
+        .iter()
+        .map(|name| {
+            name.parse()
+                .with_context(|| format!("Invalid {option}: {name}"))
+        })
+        .collect()
+}
+
+/// Find the tracks selected by track IDs, a path, or a query.
+async fn find_tracks(db: &SqliteLibrary, selection: &[String]) -> Result<Vec<Track>> {
+    if let [path] = selection {
+        let path = Path::new(path);
+        if path.exists() {
+            // Library paths are stored as imported, which may be relative
+            let canonical =
+                |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            let path = canonical(path);
+            let tracks = db.list_tracks(u32::MAX, 0).await?;
+            return Ok(tracks
+                .into_iter()
+                .filter(|track| canonical(&track.path).starts_with(&path))
+                .collect());
+        }
+    }
+
+    let ids: Option<Vec<uuid::Uuid>> = selection
+        .iter()
+        .map(|s| uuid::Uuid::parse_str(s).ok())
+        .collect();
+
+    if let Some(ids) = ids {
+        let mut tracks = Vec::new();
+        for id in ids {
+            let track = db
+                .get_track(&TrackId(id))
+                .await?
+                .with_context(|| format!("Track not found: {id}"))?;
+            tracks.push(track);
+        }
+        return Ok(tracks);
+    }
+
+    let query_str = selection.join(" ");
+    let (query, sort) =
+        Query::parse_sorted(&query_str).with_context(|| format!("Invalid query: {query_str}"))?;
+    Ok(db.query_tracks(&query, &sort).await?)
+}
+
+/// Edit the metadata of tracks, and lock or unlock their fields.
+///
+/// Locked fields are left alone, unless they are unlocked by the same
+/// command.
+#[allow(clippy::too_many_lines)]
+async fn cmd_tag(
+    lib_path: &Path,
+    selection: &[String],
+    edit: &TrackEdit,
+    lock_changes: &LockChanges,
+    options: TagOptions,
+    config: &Config,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    // Work out the changes before making any, leaving locked fields alone
+    let global_locks = config.locks.locked_fields();
+    let mut changes = Vec::new();
+    for track in find_tracks(&db, selection).await? {
+        let locked = db.get_field_locks(&track.id).await?;
+        let (during, after) = lock_changes.apply(&locked);
+        let mut locks = global_locks.clone();
+        locks.extend(during);
+
+        let mut edited = track.clone();
+        edit.apply(&mut edited)?;
+        let skipped = locks.restore(&track, &mut edited)?;
+        let diff = TrackDiff::between(&track, &edited);
+        let new_locks = (after != locked).then_some(after);
+        if !diff.is_empty() || !skipped.is_empty() || new_locks.is_some() {
+            changes.push((track, edited, diff, skipped, new_locks));
+        }
+    }
+
+    if changes.is_empty() {
+        println!("No tracks to change.");
+        return Ok(());
+    }
+
+    for (_, track, diff, skipped, new_locks) in &changes {
+        println!("{} - {} ({})", track.artist, track.title, track.id);
+        for change in &diff.changes {
+            println!("  {change}");
+        }
+        for change in &skipped.changes {
+            println!("  {}: locked, not changed", change.field);
+        }
+        if let Some(fields) = new_locks {
+            let names: Vec<&str> = fields.iter().map(|field| field.name()).collect();
+            if names.is_empty() {
+                println!("  locked fields: (none)");
+            } else {
+                println!("  locked fields: {}", names.join(", "));
+            }
+        }
+    }
+    println!();
+    // Tracks whose fields are all locked have nothing to change
+    changes.retain(|(_, _, diff, _, new_locks)| !diff.is_empty() || new_locks.is_some());
+    if changes.is_empty() {
+        println!("No tracks to change.");
+        return Ok(());
+    }
+
+    if options.dry_run {
+        println!("Would change {} tracks", changes.len());
+        return Ok(());
+    }
+
+    if !options.yes {
+        println!("Change {} tracks? [y/N] ", changes.len());
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled");
+            return Ok(());
+        }
+    }
+
+    let cleared = edit.cleared_fields();
+    let fields = config.write.written_fields();
+    let mut operation = Operation::new(OperationKind::Tag, format!("tag {}", selection.join(" ")));
+    let mut updated = 0u64;
+    let mut queued = 0u64;
+    let mut failed = 0u64;
+    for (original, mut track, diff, _, new_locks) in changes {
+        if let Some(locked) = new_locks {
+            db.set_field_locks(&track.id, &locked).await?;
+        }
+        if diff.is_empty() {
+            updated += 1;
+            continue;
+        }
+        if options.write
+            && !config.write.deferred
+            && let Err(e) = write_tags(&mut track, &fields, &cleared)
+        {
+            eprintln!("Failed to write tags to {}: {e}", track.path.display());
+            failed += 1;
+            continue;
+        }
+        db.update_track(&track).await?;
+        let diff = TrackDiff::between(&original, &track);
+        db.record_provenance(
+            &track.id,
+            &Provenance::from_diff(&diff, MetadataSource::Manual),
+        )
+        .await?;
+        operation.record_track(&original, &track, options.write);
+        updated += 1;
+        if options.write && config.write.deferred {
+            db.queue_write(&track.id, &cleared).await?;
+            queued += 1;
+        }
+    }
+
+    println!("Changed {updated} tracks");
+    record_operation(&db, &operation).await?;
+    if queued > 0 {
+        println!("Queued tags of {queued} tracks; write them with 'apollo write'");
+    }
+    if failed > 0 {
+        println!("Failed to change {failed} tracks");
+    }
+
+    Ok(())
+}
+
+/// Identify tracks by fingerprint and update their metadata.
+#[allow(clippy::too_many_lines)]
+async fn cmd_identify(
+    lib_path: &Path,
+    selection: &[String],
+    config: &Config,
+    min_score: f64,
+    write: bool,
+    yes: bool,
+) -> Result<()> {
+    if !config.acoustid.enabled || config.acoustid.api_key.is_empty() {
+        anyhow::bail!(
+            "AcoustID is not configured; set an API key with 'apollo config set acoustid.api_key <key>'"
+        );
+    }
+    if !config.musicbrainz.enabled {
+        anyhow::bail!(
+            "MusicBrainz is disabled; enable it with 'apollo config set musicbrainz.enabled true'"
+        );
+    }
+
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let acoustid = AcoustIdClient::new(config.acoustid.api_key.clone())
+        .context("Failed to create AcoustID client")?;
+    let musicbrainz = MusicBrainzClient::new(
+        &config.musicbrainz.app_name,
+        &config.musicbrainz.app_version,
+        &config.musicbrainz.contact_email,
+    )
+    .context("Failed to create MusicBrainz client")?;
+
+    let tracks = find_tracks(&db, selection).await?;
+    if tracks.is_empty() {
+        println!("No tracks to identify.");
+        return Ok(());
+    }
+
+    let mut changes = Vec::new();
+    let mut unidentified = 0u64;
+    for track in tracks {
+        println!(
+            "{} - {} ({})",
+            track.artist,
+            track.title,
+            track.path.display()
+        );
+
+        let fingerprint = match generate_fingerprint(&track.path) {
+            Ok(fingerprint) => fingerprint,
+            Err(e) => {
+                println!("  Failed to fingerprint: {e}");
+                unidentified += 1;
+                continue;
+            }
+        };
+        let results = match acoustid
+            .lookup(&fingerprint.fingerprint, fingerprint.duration)
+            .await
+        {
+            Ok(results) => results,
+            Err(e) => {
+                println!("  AcoustID lookup failed: {e}");
+                unidentified += 1;
+                continue;
+            }
+        };
+        // Results are sorted by score, so the first good one is the best
+        let Some((result, recording_id)) = results.into_iter().find_map(|result| {
+            let recording_id = result.recordings.first()?.id.clone();
+            (result.score >= min_score).then_some((result, recording_id))
+        }) else {
+            println!("  No match");
+            unidentified += 1;
+            continue;
+        };
+        let recording = match musicbrainz
+            .lookup_recording(&recording_id, &["artists", "releases", "isrcs"])
+            .await
+        {
+            Ok(recording) => recording,
+            Err(e) => {
+                println!("  MusicBrainz lookup failed: {e}");
+                unidentified += 1;
+                continue;
+            }
+        };
+
+        let mut identified = track.clone();
+        identified.acoustid = Some(result.id);
+        recording.apply_to_track(&mut identified);
+        println!(
+            "  Matched {} - {} ({:.0}%)",
+            identified.artist,
+            identified.title,
+            result.score * 100.0
+        );
+        let kept = db
+            .get_track_provenance(&track.id)
+            .await?
+            .keep_manual_edits(&track, &mut identified)?;
+        for change in &kept.changes {
+            println!("  Keeping {}, which was edited by hand", change.field);
+        }
+        let mut locks = config.locks.locked_fields();
+        locks.extend(db.get_field_locks(&track.id).await?);
+        for change in &locks.restore(&track, &mut identified)?.changes {
+            println!("  Keeping {}, which is locked", change.field);
+        }
+        let diff = TrackDiff::between(&track, &identified);
+        if diff.is_empty() {
+            println!("  Already up to date");
+            continue;
+        }
+        for change in &diff.changes {
+            println!("  {change}");
+        }
+        changes.push((track, identified));
+    }
+    println!();
+
+    if !yes {
+        println!(
+            "Would change {} tracks ({unidentified} not identified); run with --yes to apply",
+            changes.len()
+        );
+        return Ok(());
+    }
+
+    let fields = config.write.written_fields();
+    let mut updated = 0u64;
+    let mut queued = 0u64;
+    let mut failed = 0u64;
+    for (original, mut track) in changes {
+        if write
+            && !config.write.deferred
+            && let Err(e) = write_tags(&mut track, &fields, &[])
+        {
+            eprintln!("Failed to write tags to {}: {e}", track.path.display());
+            failed += 1;
+            continue;
+        }
+        db.update_track(&track).await?;
+        let diff = TrackDiff::between(&original, &track);
+        db.record_provenance(
+            &track.id,
+            &Provenance::from_diff(&diff, MetadataSource::MusicBrainz),
+        )
+        .await?;
+        updated += 1;
+        if write && config.write.deferred {
+            db.queue_write(&track.id, &[]).await?;
+            queued += 1;
+        }
+    }
+
+    println!("Changed {updated} tracks ({unidentified} not identified)");
+    if queued > 0 {
+        println!("Queued tags of {queued} tracks; write them with 'apollo write'");
+    }
+    if failed > 0 {
+        println!("Failed to change {failed} tracks");
+    }
+
+    Ok(())
+}
+
+/// Write queued tag changes to the audio files.
+async fn cmd_write(
+    lib_path: &Path,
+    selection: &[String],
+    config: &Config,
+    dry_run: bool,
+) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let only: Option<HashSet<TrackId>> = if selection.is_empty() {
+        None
+    } else {
+        let tracks = find_tracks(&db, selection).await?;
+        Some(tracks.into_iter().map(|track| track.id).collect())
+    };
+
+    if dry_run {
+        let pending = db.list_pending_writes().await?;
+        let mut count = 0u64;
+        for (track, _) in &pending {
+            if only.as_ref().is_none_or(|ids| ids.contains(&track.id)) {
+                println!("{}", track.path.display());
+                count += 1;
+            }
+        }
+        println!();
+        println!("Would write tags to {count} files");
+        return Ok(());
+    }
+
+    let writer = TagWriter::new(Arc::new(db), config);
+    let result = writer
+        .write_pending(only.as_ref())
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to write queued tags: {e:?}"))?;
+    for error in &result.errors {
+        eprintln!("{error}");
+    }
+    println!("Wrote tags to {} files", result.written);
+    if !result.errors.is_empty() {
+        println!("Failed: {} (still queued)", result.errors.len());
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Fetch, embed, or extract album cover art.
+async fn cmd_art(lib_path: &Path, action: ArtAction, config: &Config) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
+
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let (ArtAction::Fetch { tracks, dry_run }
+    | ArtAction::Embed {
+        tracks, dry_run, ..
+    }
+    | ArtAction::Extract { tracks, dry_run }) = &action;
+    let dry_run = *dry_run;
+    let tracks = if tracks.is_empty() {
+        db.list_tracks(u32::MAX, 0).await?
+    } else {
+        find_tracks(&db, tracks).await?
+    };
+    if tracks.is_empty() {
+        println!("No tracks selected.");
+        return Ok(());
+    }
+
+    // Covers are stored per album directory
+    let mut albums: BTreeMap<PathBuf, Vec<Track>> = BTreeMap::new();
+    for track in tracks {
+        let dir = track
+            .path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        albums.entry(dir).or_default().push(track);
+    }
+
+    if dry_run {
+        println!("DRY RUN - no changes will be made");
+        println!();
+    }
+
+    match action {
+        ArtAction::Fetch { .. } => fetch_art(&db, &albums, config, dry_run).await,
+        ArtAction::Embed { force, .. } => embed_album_art(&db, &albums, force, dry_run).await,
+        ArtAction::Extract { .. } => extract_art(&albums, dry_run),
+    }
+}
+
+/// Create a progress bar for cover art operations.
+fn art_progress_bar(len: usize) -> ProgressBar {
+    progress_bar(len as u64, BAR_TEMPLATE)
+}
+
+/// Download covers for album directories without a cover file.
+async fn fetch_art(
+    db: &SqliteLibrary,
+    albums: &BTreeMap<PathBuf, Vec<Track>>,
+    config: &Config,
+    dry_run: bool,
+) -> Result<()> {
+    let discogs = if config.discogs.token.is_empty() {
+        None
+    } else {
+        Some(
+            DiscogsClient::new(
+                &config.musicbrainz.app_name,
+                &config.musicbrainz.app_version,
+                &config.discogs.token,
+            )
+            .context("Failed to create Discogs client")?,
+        )
+    };
+    if !config.musicbrainz.enabled && discogs.is_none() {
+        anyhow::bail!(
+            "No cover art source; enable MusicBrainz or set a Discogs token with 'apollo config set discogs.token <token>'"
+        );
+    }
+    let coverart = CoverArtClient::new(
+        &config.musicbrainz.app_name,
+        &config.musicbrainz.app_version,
+    )
+    .context("Failed to create Cover Art Archive client")?;
+
+    let bar = art_progress_bar(albums.len());
+    let mut fetched = 0u64;
+    let mut existing = 0u64;
+    let mut not_found = 0u64;
+    let mut failed = 0u64;
+    for (dir, tracks) in albums {
+        bar.inc(1);
+        if find_cover_file(dir).is_some() {
+            existing += 1;
+            continue;
+        }
+
+        let album = match tracks.iter().find_map(|track| track.album_id.as_ref()) {
+            Some(album_id) => db.get_album(album_id).await?,
+            None => None,
+        };
+        let url = find_cover_url(
+            &coverart,
+            discogs.as_ref(),
+            album.as_ref(),
+            &tracks[0],
+            config.musicbrainz.enabled,
+        )
+        .await;
+        let Some(url) = url else {
+            println!("No cover found: {}", dir.display());
+            not_found += 1;
+            continue;
+        };
+
+        if dry_run {
+            println!("Would fetch: {url} -> {}", dir.display());
+            fetched += 1;
+            continue;
+        }
+        let art = match coverart.download_image(&url).await {
+            Ok(data) => CoverArt::from_bytes(data).map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        match art {
+            Ok(art) => {
+                let path = dir.join(format!("folder.{}", art.extension()));
+                std::fs::write(&path, &art.data)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                println!("Fetched: {}", path.display());
+                fetched += 1;
+            }
+            Err(e) => {
+                eprintln!("Failed to download {url}: {e}");
+                failed += 1;
+            }
+        }
+    }
+    bar.finish_and_clear();
+
+    println!();
+    let verb = if dry_run { "Would fetch" } else { "Fetched" };
+    println!("{verb} {fetched} covers ({existing} albums already have one, {not_found} not found)");
+    if failed > 0 {
+        println!("Failed to download {failed} covers");
+    }
+
+    Ok(())
+}
+
+/// Find the URL of an album's front cover, on the Cover Art Archive if the
+/// album has a release ID, or else on Discogs.
+async fn find_cover_url(
+    coverart: &CoverArtClient,
+    discogs: Option<&DiscogsClient>,
+    album: Option<&Album>,
+    track: &Track,
+    use_musicbrainz: bool,
+) -> Option<String> {
+    if use_musicbrainz && let Some(mbid) = album.and_then(|album| album.musicbrainz_id.as_ref()) {
+        match coverart.get_front_cover(mbid, ImageSize::Large).await {
+            Ok(cover) => return Some(cover.url),
+            Err(SourceError::NotFound) => {}
+            Err(e) => eprintln!("Cover Art Archive lookup failed for {mbid}: {e}"),
+        }
+    }
+
+    let discogs = discogs?;
+    let title = album
+        .map(|album| album.title.clone())
+        .or_else(|| track.album_title.clone())?;
+    let artist = album.map_or_else(
+        || {
+            track
+                .album_artist
+                .clone()
+                .unwrap_or_else(|| track.artist.clone())
+        },
+        |album| album.artist.clone(),
+    );
+    let year = album.map_or(track.year, |album| album.year);
+    match discogs.find_best_release(&title, &artist, year).await {
+        Ok(result) => result.and_then(|result| result.cover_image),
+        Err(e) => {
+            eprintln!("Discogs search failed for {title}: {e}");
+            None
+        }
+    }
+}
+
+/// Embed the cover file of each album directory into its tracks.
+async fn embed_album_art(
+    db: &SqliteLibrary,
+    albums: &BTreeMap<PathBuf, Vec<Track>>,
+    force: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let bar = art_progress_bar(albums.values().map(Vec::len).sum());
+    let mut embedded = 0u64;
+    let mut skipped = 0u64;
+    let mut no_cover = 0u64;
+    let mut failed = 0u64;
+    for (dir, tracks) in albums {
+        let art = match find_cover_file(dir).map(|path| CoverArt::from_file(&path)) {
+            Some(Ok(art)) => art,
+            Some(Err(e)) => {
+                eprintln!("Failed to read cover in {}: {e}", dir.display());
+                bar.inc(tracks.len() as u64);
+                failed += tracks.len() as u64;
+                continue;
+            }
+            None => {
+                bar.inc(tracks.len() as u64);
+                no_cover += tracks.len() as u64;
+                continue;
+            }
+        };
+
+        for track in tracks {
+            bar.inc(1);
+            if !force && matches!(read_embedded_art(&track.path), Ok(Some(_))) {
+                skipped += 1;
+                continue;
+            }
+            if dry_run {
+                println!("Would embed: {}", track.path.display());
+                embedded += 1;
+                continue;
+            }
+            // Embedding changes the file contents, and so its hash
+            match embed_art(&track.path, &art).and_then(|()| compute_file_hash(&track.path)) {
+                Ok(hash) => {
+                    let mut track = track.clone();
+                    track.file_hash = hash;
+                    db.update_track(&track).await?;
+                    embedded += 1;
+                }
+                Err(e) => {
+                    eprintln!("Failed to embed in {}: {e}", track.path.display());
+                    failed += 1;
+                }
+            }
+        }
+    }
+    bar.finish_and_clear();
+
+    println!();
+    let verb = if dry_run { "Would embed" } else { "Embedded" };
+    println!(
+        "{verb} covers in {embedded} tracks ({skipped} already have one, {no_cover} have no cover file)"
+    );
+    if failed > 0 {
+        println!("Failed to embed {failed} covers");
+    }
+
+    Ok(())
+}
 
-    // Ensure parent directory exists
-    ensure_parent_dir(&lib_path)?;
+/// Save the embedded cover of each album directory without a cover file.
+fn extract_art(albums: &BTreeMap<PathBuf, Vec<Track>>, dry_run: bool) -> Result<()> {
+    let bar = art_progress_bar(albums.len());
+    let mut extracted = 0u64;
+    let mut existing = 0u64;
+    let mut no_art = 0u64;
+    for (dir, tracks) in albums {
+        bar.inc(1);
+        if find_cover_file(dir).is_some() {
+            existing += 1;
+            continue;
+        }
+        let Some(art) = tracks
+            .iter()
+            .find_map(|track| read_embedded_art(&track.path).ok().flatten())
+        else {
+            no_art += 1;
+            continue;
+        };
 
-    // Create the database
-    let db_url = format!("sqlite:{}?mode=rwc", lib_path.display());
-    let _db = SqliteLibrary::new(&db_url)
-        .await
-        .context("Failed to create library database")?;
+        let path = dir.join(format!("folder.{}", art.extension()));
+        if dry_run {
+            println!("Would extract: {}", path.display());
+        } else {
+            std::fs::write(&path, &art.data)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("Extracted: {}", path.display());
+        }
+        extracted += 1;
+    }
+    bar.finish_and_clear();
 
-    println!("Initialized new library at: {}", lib_path.display());
     println!();
-    println!("Next steps:");
-    println!("  apollo import /path/to/music   Import music files");
-    println!("  apollo list                    List tracks in library");
-    println!("  apollo query \"search term\"     Search the library");
+    let verb = if dry_run {
+        "Would extract"
+    } else {
+        "Extracted"
+    };
+    println!(
+        "{verb} {extracted} covers ({existing} albums already have one, {no_art} have no embedded cover)"
+    );
 
     Ok(())
 }
 
-/// Import music files from a directory.
-#[allow(clippy::too_many_lines)]
-async fn cmd_import(
+/// Counts of what `apollo migrate` changed.
+#[derive(Default)]
+struct MigrateSummary {
+    added: u64,
+    existing: u64,
+    missing: u64,
+    failed: u64,
+    plays: u64,
+    ratings: u64,
+    favorites: u64,
+    playlists: u64,
+}
+
+/// Migrate another music manager's library.
+async fn cmd_migrate(
     lib_path: &Path,
+    from: MigrateSource,
     source_path: &Path,
-    depth: Option<usize>,
-    follow_symlinks: bool,
+    dry_run: bool,
+    genre_normalizer: Option<&GenreNormalizer>,
 ) -> Result<()> {
     // Check if library exists
     if !lib_path.exists() {
@@ -451,138 +5361,234 @@ async fn cmd_import(
         std::process::exit(1);
     }
 
-    // Check if source directory exists
-    if !source_path.exists() {
-        eprintln!("Source directory not found: {}", source_path.display());
-        std::process::exit(1);
-    }
-
-    if !source_path.is_dir() {
-        eprintln!("Source path is not a directory: {}", source_path.display());
-        std::process::exit(1);
-    }
-
     // Connect to database
     let db_url = format!("sqlite:{}", lib_path.display());
     let db = SqliteLibrary::new(&db_url)
         .await
         .context("Failed to open library database")?;
 
-    println!("Scanning: {}", source_path.display());
-
-    // Set up progress tracking
-    let progress_bar = ProgressBar::new_spinner();
-    progress_bar.set_style(
-        ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {msg}")
-            .unwrap()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
-    );
-
-    // Configure scan options
-    let options = ScanOptions {
-        recursive: true,
-        max_depth: depth,
-        follow_symlinks,
-        compute_hashes: true,
+    println!("Reading library: {}", source_path.display());
+    let library = match from {
+        MigrateSource::Beets => beets::read_library(source_path).await?,
+        MigrateSource::Itunes => itunes::read_library(source_path)?,
     };
+    println!(
+        "Found {} tracks and {} playlists",
+        library.tracks.len(),
+        library.playlists.len()
+    );
+    if dry_run {
+        println!("DRY RUN - no changes will be made");
+    }
 
-    // Cancellation token (not used in CLI for now, but API requires it)
-    let cancel = Arc::new(AtomicBool::new(false));
+    let bar = progress_bar(library.tracks.len() as u64, BAR_TEMPLATE);
 
-    // Progress callback
-    let progress_callback = |progress: &ScanProgress| {
-        if let Some(ref current) = progress.current_file {
-            let filename = current
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("...");
-            // Note: we can't access progress_bar inside the closure easily
-            // so we just log via tracing
-            tracing::debug!(
-                "Processing {}/{}: {}",
-                progress.files_processed,
-                progress.files_found,
-                filename
-            );
+    // Playlists refer to tracks by their path in the other library
+    let mut ids: HashMap<PathBuf, TrackId> = HashMap::new();
+    let mut summary = MigrateSummary::default();
+    for migrated in &library.tracks {
+        bar.inc(1);
+        match migrate_track(&db, migrated, genre_normalizer, dry_run, &mut summary).await {
+            Ok(Some(id)) => {
+                ids.insert(migrated.track.path.clone(), id);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!("Failed to migrate {}: {e}", migrated.track.path.display());
+                summary.failed += 1;
+            }
         }
-    };
-
-    // Run the scan
-    let result = scan_directory(
-        source_path,
-        &options,
-        Some(&cancel),
-        Some(progress_callback),
-    )
-    .context("Failed to scan directory")?;
-
-    progress_bar.finish_and_clear();
+    }
+    bar.finish_and_clear();
 
-    let total_found = result.tracks.len();
-    let errors = result.errors.len();
+    migrate_playlists(&db, &library.playlists, &ids, dry_run, &mut summary).await?;
 
-    if total_found == 0 {
-        println!("No audio files found in {}", source_path.display());
-        return Ok(());
+    println!();
+    println!(
+        "{}:",
+        if dry_run {
+            "Migration preview"
+        } else {
+            "Migration complete"
+        }
+    );
+    println!("  Added: {}", summary.added);
+    println!("  Already in library: {}", summary.existing);
+    if summary.missing > 0 {
+        println!("  Skipped (file not found): {}", summary.missing);
     }
-
-    println!("Found {total_found} audio files");
-    if errors > 0 {
-        println!("Skipped {errors} files with errors");
+    if summary.failed > 0 {
+        println!("  Failed: {}", summary.failed);
     }
+    println!("  Plays: {}", summary.plays);
+    println!("  Ratings: {}", summary.ratings);
+    println!("  Favorites: {}", summary.favorites);
+    println!("  Playlists: {}", summary.playlists);
 
-    // Import tracks into database
-    let import_bar = ProgressBar::new(total_found as u64);
-    import_bar.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )
-        .unwrap()
-        .progress_chars("█▓▒░"),
-    );
+    Ok(())
+}
 
-    let mut imported = 0u64;
-    let mut skipped = 0u64;
-    let mut failed = 0u64;
+/// Add a migrated track, or bring its history to the track already in the
+/// library. Returns the track's ID in the library, or `None` if its file no
+/// longer exists.
+async fn migrate_track(
+    db: &SqliteLibrary,
+    migrated: &MigratedTrack,
+    genre_normalizer: Option<&GenreNormalizer>,
+    dry_run: bool,
+    summary: &mut MigrateSummary,
+) -> Result<Option<TrackId>> {
+    let path = &migrated.track.path;
+    let (track, stats) = if let Some(track) = db.get_track_by_path(path).await? {
+        // Keep the library's metadata, but the earliest added date
+        if migrated.track.added_at < track.added_at && !dry_run {
+            db.set_track_added_at(&track.id, migrated.track.added_at)
+                .await?;
+        }
+        summary.existing += 1;
+        let stats = db.get_track_stats(&track.id).await?;
+        (track, stats)
+    } else if path.is_file() {
+        let mut track = read_metadata(path)?;
+        migrated.merge_into(&mut track);
+        if let Some(normalizer) = genre_normalizer {
+            track.genres = normalizer.normalize(&track.genres);
+        }
+        track.file_hash = compute_file_hash(path)?;
+        if !dry_run {
+            db.add_track(&track).await?;
+        }
+        summary.added += 1;
+        (track, TrackStats::default())
+    } else {
+        summary.missing += 1;
+        return Ok(None);
+    };
 
-    for track in &result.tracks {
-        import_bar.inc(1);
+    // Only history the library doesn't have yet, so plays aren't counted
+    // twice when migrating again
+    let source = &migrated.stats;
+    if stats.play_count == 0 && source.play_count > 0 {
+        // Only the last play is known, so date every play then
+        let played_at = source.last_played.unwrap_or(migrated.track.added_at);
+        if !dry_run {
+            let plays = vec![played_at; source.play_count as usize];
+            db.import_plays(&track.id, &plays).await?;
+        }
+        summary.plays += u64::from(source.play_count);
+    }
+    if stats.rating.is_none()
+        && let Some(rating) = source.rating
+    {
+        if !dry_run {
+            db.set_track_rating(&track.id, Some(rating)).await?;
+        }
+        summary.ratings += 1;
+    }
+    if !stats.favorite && source.favorite {
+        if !dry_run {
+            db.set_track_favorite(&track.id, true).await?;
+        }
+        summary.favorites += 1;
+    }
 
-        // Try to add track; handle duplicate errors gracefully
-        match db.add_track(track).await {
-            Ok(_) => imported += 1,
-            Err(apollo_db::DbError::Sqlx(ref e)) if e.to_string().contains("UNIQUE constraint") => {
-                skipped += 1;
+    Ok(Some(track.id))
+}
+
+/// Create the migrated playlists that the library doesn't have yet.
+async fn migrate_playlists(
+    db: &SqliteLibrary,
+    playlists: &[MigratedPlaylist],
+    ids: &HashMap<PathBuf, TrackId>,
+    dry_run: bool,
+    summary: &mut MigrateSummary,
+) -> Result<()> {
+    let existing: HashSet<String> = db
+        .list_playlists()
+        .await?
+        .into_iter()
+        .map(|playlist| playlist.name.to_lowercase())
+        .collect();
+
+    for migrated in playlists {
+        if existing.contains(&migrated.name.to_lowercase()) {
+            println!("Skipping playlist '{}': already exists", migrated.name);
+            continue;
+        }
+        // Other players allow a track more than once; keep its first place
+        let mut tracks: Vec<&TrackId> = Vec::new();
+        for id in migrated.tracks.iter().filter_map(|path| ids.get(path)) {
+            if !tracks.contains(&id) {
+                tracks.push(id);
             }
-            Err(e) => {
-                tracing::warn!("Failed to import {}: {}", track.path.display(), e);
-                failed += 1;
+        }
+
+        if dry_run {
+            println!(
+                "Would create playlist '{}' with {} tracks",
+                migrated.name,
+                tracks.len()
+            );
+        } else {
+            let playlist = Playlist::new_static(&migrated.name);
+            db.add_playlist(&playlist).await?;
+            for id in tracks.iter().copied() {
+                db.add_track_to_playlist(&playlist.id, id).await?;
             }
+            println!(
+                "Created playlist '{}' with {} tracks",
+                migrated.name,
+                tracks.len()
+            );
         }
+        summary.playlists += 1;
     }
 
-    import_bar.finish_and_clear();
+    Ok(())
+}
 
-    println!();
-    println!("Import complete:");
-    println!("  Imported: {imported}");
-    if skipped > 0 {
-        println!("  Skipped (duplicates): {skipped}");
-    }
-    if failed > 0 {
-        println!("  Failed: {failed}");
+/// Export tracks to standard output.
+async fn cmd_export_tracks(
+    lib_path: &Path,
+    selection: &[String],
+    format: ExportFormat,
+    fields: Option<&str>,
+) -> Result<()> {
+    // Check fields before opening the library
+    let exporter = Exporter::tracks(format, fields)?;
+
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
     }
 
-    // Show summary
-    let total_tracks = db.count_tracks().await?;
-    println!();
-    println!("Library now contains {total_tracks} tracks");
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
+
+    let tracks = if selection.is_empty() {
+        db.list_tracks(u32::MAX, 0).await?
+    } else {
+        find_tracks(&db, selection).await?
+    };
+    print!("{}", exporter.export(&tracks)?);
+    eprintln!("Exported {} tracks", tracks.len());
 
     Ok(())
 }
 
-/// List items in the library.
-async fn cmd_list(lib_path: &Path, list_type: ListType, limit: u32, offset: u32) -> Result<()> {
+/// Export albums to standard output.
+async fn cmd_export_albums(
+    lib_path: &Path,
+    format: ExportFormat,
+    fields: Option<&str>,
+) -> Result<()> {
+    let exporter = Exporter::albums(format, fields)?;
+
     // Check if library exists
     if !lib_path.exists() {
         eprintln!("Library not found at: {}", lib_path.display());
@@ -596,69 +5602,57 @@ async fn cmd_list(lib_path: &Path, list_type: ListType, limit: u32, offset: u32)
         .await
         .context("Failed to open library database")?;
 
-    match list_type {
-        ListType::Tracks => {
-            let tracks = db.list_tracks(limit, offset).await?;
-            let total = db.count_tracks().await?;
+    let albums = db.list_albums(u32::MAX, 0).await?;
+    print!("{}", exporter.export(&albums)?);
+    eprintln!("Exported {} albums", albums.len());
 
-            if tracks.is_empty() {
-                println!("No tracks in library");
-                return Ok(());
-            }
+    Ok(())
+}
 
-            let count = tracks.len() as u32;
-            println!(
-                "Showing tracks {}-{} of {total}",
-                offset + 1,
-                offset + count
-            );
-            println!();
+/// Send recorded plays to the configured scrobble services.
+async fn cmd_scrobble(lib_path: &Path, action: ScrobbleAction, config: &Config) -> Result<()> {
+    // Check if library exists
+    if !lib_path.exists() {
+        eprintln!("Library not found at: {}", lib_path.display());
+        eprintln!("Run 'apollo init' first to create a library");
+        std::process::exit(1);
+    }
 
-            for track in &tracks {
-                let duration = format_duration(track.duration);
-                let album = track.album_title.as_deref().unwrap_or("-");
-                let track_num = track
-                    .track_number
-                    .map_or_else(|| "--".to_string(), |n| format!("{n:02}"));
+    // Connect to database
+    let db_url = format!("sqlite:{}", lib_path.display());
+    let db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?;
 
+    match action {
+        ScrobbleAction::Sync => {
+            let scrobbler = Scrobbler::new(Arc::new(db), config)
+                .map_err(|e| anyhow::anyhow!("Failed to set up scrobbling: {e:?}"))?;
+            if !scrobbler.is_enabled() {
+                println!("No scrobble services are configured.");
                 println!(
-                    "{track_num}. {} - {} [{album}] ({duration})",
-                    track.artist, track.title
+                    "Set lastfm.api_key, lastfm.api_secret, and lastfm.session_key, or listenbrainz.token, with 'apollo config set'"
                 );
-            }
-
-            if offset + count < total as u32 {
-                println!();
-                println!("Use --offset {} to see more", offset + count);
-            }
-        }
-        ListType::Albums => {
-            let albums = db.list_albums(limit, offset).await?;
-            let total = db.count_albums().await?;
-
-            if albums.is_empty() {
-                println!("No albums in library");
                 return Ok(());
             }
 
-            let count = albums.len() as u32;
+            let result = scrobbler
+                .sync()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to send queued plays: {e:?}"))?;
+            for error in &result.errors {
+                eprintln!("{error}");
+            }
             println!(
-                "Showing albums {}-{} of {total}",
-                offset + 1,
-                offset + count
+                "Scrobbled {} plays to {}",
+                result.sent,
+                scrobbler.services().join(", ")
             );
-            println!();
-
-            for album in &albums {
-                let year = album.year.map_or_else(String::new, |y| format!(" ({y})"));
-                let tracks = album.track_count;
-
-                println!("{} - {}{year} [{tracks} tracks]", album.artist, album.title);
+            if result.queued > 0 {
+                println!("Still queued: {}", result.queued);
             }
-
-            if offset + count < total as u32 {
-                println!();
-                println!("Use --offset {} to see more", offset + count);
+            if !result.errors.is_empty() {
+                std::process::exit(1);
             }
         }
     }
@@ -666,8 +5660,12 @@ async fn cmd_list(lib_path: &Path, list_type: ListType, limit: u32, offset: u32)
     Ok(())
 }
 
-/// Search the library.
-async fn cmd_query(lib_path: &Path, query: &str, limit: u32) -> Result<()> {
+/// Play tracks or a playlist through the default audio device.
+#[cfg(feature = "playback")]
+async fn cmd_play(lib_path: &Path, selection: &[String], config: &Config) -> Result<()> {
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use std::io::IsTerminal;
+
     // Check if library exists
     if !lib_path.exists() {
         eprintln!("Library not found at: {}", lib_path.display());
@@ -677,74 +5675,160 @@ async fn cmd_query(lib_path: &Path, query: &str, limit: u32) -> Result<()> {
 
     // Connect to database
     let db_url = format!("sqlite:{}", lib_path.display());
-    let db = SqliteLibrary::new(&db_url)
-        .await
-        .context("Failed to open library database")?;
+    let db = Arc::new(
+        SqliteLibrary::new(&db_url)
+            .await
+            .context("Failed to open library database")?,
+    );
+    let scrobbler = Arc::new(
+        Scrobbler::new(
+            Arc::clone(&db) as Arc<dyn apollo_core::library::Library>,
+            config,
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to set up scrobbling: {e:?}"))?,
+    );
 
-    // FTS5 requires special query syntax; wrap in quotes for phrase search
-    // or use * for prefix matching
-    let fts_query = if query.contains(':') || query.contains('"') || query.contains('*') {
-        // User provided FTS syntax, use as-is
-        query.to_string()
-    } else {
-        // Simple search: add wildcards for prefix matching
-        query
-            .split_whitespace()
-            .map(|word| format!("{word}*"))
-            .collect::<Vec<_>>()
-            .join(" ")
+    // Playlist names may contain spaces, so try the whole selection first
+    let tracks = match find_playlist(&db, &selection.join(" ")).await {
+        Ok(playlist) => db.get_playlist_tracks(&playlist.id).await?,
+        Err(_) => find_tracks(&db, selection).await?,
     };
-
-    let tracks = db.search_tracks(&fts_query).await?;
-
+    let (tracks, missing): (Vec<Track>, Vec<Track>) =
+        tracks.into_iter().partition(|track| track.path.exists());
+    if !missing.is_empty() {
+        eprintln!(
+            "Skipping {} tracks with missing files (see 'apollo missing')",
+            missing.len()
+        );
+    }
     if tracks.is_empty() {
-        println!("No tracks found matching: {query}");
+        println!("No tracks selected.");
         return Ok(());
-    }
-
-    let shown = tracks.len().min(limit as usize);
-    println!("Found {} tracks matching: {query}", tracks.len());
-    println!();
+    }
 
-    for track in tracks.iter().take(shown) {
-        let duration = format_duration(track.duration);
-        let album = track.album_title.as_deref().unwrap_or("-");
+    let mut player = Player::new(tracks.iter().map(|track| track.path.clone()).collect())
+        .context("Failed to start playback")?;
 
-        println!("{} - {} [{album}] ({duration})", track.artist, track.title);
+    // Without a terminal there are no keys to read, so just play through
+    let raw_mode = if std::io::stdin().is_terminal() {
+        Some(RawMode::enable()?)
+    } else {
+        None
+    };
+    if raw_mode.is_some() {
+        print!("Keys: n next, p previous, space pause, q quit\r\n");
     }
 
-    if tracks.len() > shown {
-        println!();
-        println!("...and {} more", tracks.len() - shown);
+    let mut shown = None;
+    while !player.is_finished() {
+        if player.current() != shown {
+            shown = player.current();
+            if let Some(index) = shown {
+                let track = &tracks[index];
+                let duration = format_duration(track.duration);
+                print!(
+                    "Now playing: {} - {} ({duration})\r\n",
+                    track.artist, track.title
+                );
+            }
+        }
+
+        let key = if raw_mode.is_some() {
+            read_key()?
+        } else {
+            tokio::time::sleep(PLAY_POLL_INTERVAL).await;
+            None
+        };
+        if let Some(key) = key {
+            match key.code {
+                KeyCode::Char('n') | KeyCode::Right => player.next(),
+                KeyCode::Char('p') | KeyCode::Left => player.previous(),
+                KeyCode::Char(' ') => {
+                    player.toggle_pause();
+                    let state = if player.is_paused() {
+                        "Paused"
+                    } else {
+                        "Resumed"
+                    };
+                    print!("{state}\r\n");
+                }
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                _ => {}
+            }
+        }
+
+        for index in player.update() {
+            record_play(&db, &scrobbler, &tracks[index].id).await?;
+        }
     }
 
     Ok(())
 }
 
-/// Show library statistics.
-async fn cmd_stats(lib_path: &Path) -> Result<()> {
-    // Check if library exists
-    if !lib_path.exists() {
-        eprintln!("Library not found at: {}", lib_path.display());
-        eprintln!("Run 'apollo init' first to create a library");
-        std::process::exit(1);
+/// Record a play of a track, and send it to the scrobble services without
+/// holding up playback.
+#[cfg(feature = "playback")]
+async fn record_play(db: &SqliteLibrary, scrobbler: &Arc<Scrobbler>, id: &TrackId) -> Result<()> {
+    let played_at = Utc::now();
+    db.record_play(id, played_at).await?;
+    if scrobbler.is_enabled() {
+        scrobbler
+            .queue(id, played_at)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to queue play for scrobbling: {e:?}"))?;
+        let scrobbler = Arc::clone(scrobbler);
+        tokio::spawn(async move {
+            if let Err(e) = scrobbler.sync().await {
+                tracing::warn!("Failed to send queued scrobbles: {e:?}");
+            }
+        });
     }
+    Ok(())
+}
 
-    // Connect to database
-    let db_url = format!("sqlite:{}", lib_path.display());
-    let db = SqliteLibrary::new(&db_url)
-        .await
-        .context("Failed to open library database")?;
+#[cfg(not(feature = "playback"))]
+#[allow(clippy::unused_async)]
+async fn cmd_play(_lib_path: &Path, _selection: &[String], _config: &Config) -> Result<()> {
+    anyhow::bail!("Apollo was built without playback support; rebuild it with --features playback")
+}
 
-    let track_count = db.count_tracks().await?;
-    let album_count = db.count_albums().await?;
+/// How often playback checks for key presses and finished tracks.
+#[cfg(feature = "playback")]
+const PLAY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
 
-    println!("Library: {}", lib_path.display());
-    println!();
-    println!("Tracks: {track_count}");
-    println!("Albums: {album_count}");
+/// Wait briefly for a key press.
+#[cfg(feature = "playback")]
+fn read_key() -> Result<Option<crossterm::event::KeyEvent>> {
+    use crossterm::event::{self, Event, KeyEventKind};
 
-    Ok(())
+    if !event::poll(PLAY_POLL_INTERVAL)? {
+        return Ok(None);
+    }
+    match event::read()? {
+        Event::Key(key) if key.kind == KeyEventKind::Press => Ok(Some(key)),
+        _ => Ok(None),
+    }
+}
+
+/// Keeps the terminal in raw mode, so keys are read as soon as they are
+/// pressed, until dropped.
+#[cfg(feature = "playback")]
+struct RawMode;
+
+#[cfg(feature = "playback")]
+impl RawMode {
+    fn enable() -> Result<Self> {
+        crossterm::terminal::enable_raw_mode().context("Failed to read keys from the terminal")?;
+        Ok(Self)
+    }
+}
+
+#[cfg(feature = "playback")]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
 }
 
 /// Find duplicate tracks in the library.
@@ -753,6 +5837,9 @@ async fn cmd_duplicates(
     dup_type: DuplicateType,
     duration_tolerance_secs: u32,
     show_paths: bool,
+    resolve: Option<ResolveOptions>,
+    music_dir: Option<&Path>,
+    output: OutputFormat,
 ) -> Result<()> {
     // Check if library exists
     if !lib_path.exists() {
@@ -768,8 +5855,16 @@ async fn cmd_duplicates(
         .context("Failed to open library database")?;
 
     let duration_tolerance_ms = i64::from(duration_tolerance_secs) * 1000;
+    if output != OutputFormat::Plain {
+        if resolve.is_some() {
+            anyhow::bail!("--resolve can only be used with plain output");
+        }
+        return print_duplicates(&db, dup_type, duration_tolerance_ms, output).await;
+    }
+
     let mut total_groups = 0;
     let mut total_duplicates = 0;
+    let mut groups = Vec::new();
 
     // Find exact duplicates
     if matches!(dup_type, DuplicateType::Exact | DuplicateType::All) {
@@ -794,6 +5889,7 @@ async fn cmd_duplicates(
                 }
                 println!();
             }
+            groups.extend(exact_groups);
         }
     }
 
@@ -827,12 +5923,15 @@ async fn cmd_duplicates(
                 }
                 println!();
             }
+            groups.extend(similar_groups);
         }
     }
 
     // Summary
     if total_groups == 0 {
         println!("No duplicates found.");
+    } else if let Some(options) = resolve {
+        resolve_duplicates(&db, groups, options, music_dir).await?;
     } else {
         println!("Summary: {total_groups} groups, {total_duplicates} potential duplicates");
         println!();
@@ -842,13 +5941,206 @@ async fn cmd_duplicates(
     Ok(())
 }
 
+/// A group of duplicate tracks, for JSON output.
+#[derive(Serialize)]
+struct DuplicateGroup {
+    /// How the tracks match: `exact` or `similar`.
+    #[serde(rename = "type")]
+    kind: &'static str,
+    tracks: Vec<Track>,
+}
+
+/// Print duplicate groups as JSON.
+async fn print_duplicates(
+    db: &SqliteLibrary,
+    dup_type: DuplicateType,
+    duration_tolerance_ms: i64,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut groups = Vec::new();
+    if matches!(dup_type, DuplicateType::Exact | DuplicateType::All) {
+        for tracks in db.find_exact_duplicates().await? {
+            groups.push(DuplicateGroup {
+                kind: "exact",
+                tracks,
+            });
+        }
+    }
+    if matches!(dup_type, DuplicateType::Similar | DuplicateType::All) {
+        for tracks in db.find_similar_duplicates(duration_tolerance_ms).await? {
+            groups.push(DuplicateGroup {
+                kind: "similar",
+                tracks,
+            });
+        }
+    }
+    print_json_list(output, &groups)
+}
+
+/// Remove duplicates, keeping one track of each group.
+async fn resolve_duplicates(
+    db: &SqliteLibrary,
+    groups: Vec<Vec<Track>>,
+    options: ResolveOptions,
+    music_dir: Option<&Path>,
+) -> Result<()> {
+    // Pairs of (duplicate, kept track)
+    let mut merges: Vec<(Track, Track)> = Vec::new();
+    let mut removed = HashSet::new();
+    for group in groups {
+        // A track can be in an exact and a similar group
+        let group: Vec<Track> = group
+            .into_iter()
+            .filter(|track| !removed.contains(&track.id))
+            .collect();
+        if group.len() < 2 {
+            continue;
+        }
+
+        let kept = match options.strategy {
+            ResolveStrategy::KeepBest => choose_kept(&group, KeepRule::Best, music_dir),
+            ResolveStrategy::KeepOldest => choose_kept(&group, KeepRule::Oldest, music_dir),
+            ResolveStrategy::Interactive => choose_kept_interactively(&group, music_dir)?,
+        };
+        let Some(kept) = kept else {
+            continue;
+        };
+        for (index, track) in group.iter().enumerate() {
+            if index != kept {
+                removed.insert(track.id.clone());
+                merges.push((track.clone(), group[kept].clone()));
+            }
+        }
+    }
+
+    if merges.is_empty() {
+        println!("No duplicates to remove.");
+        return Ok(());
+    }
+
+    let mut last_kept = None;
+    for (duplicate, kept) in &merges {
+        if last_kept != Some(&kept.id) {
+            println!("Keep:   {}", kept.path.display());
+            last_kept = Some(&kept.id);
+        }
+        println!("Remove: {}", duplicate.path.display());
+    }
+    println!();
+
+    let action = if options.delete_files {
+        "Remove and delete"
+    } else {
+        "Remove"
+    };
+    if options.dry_run {
+        println!("Would {} {} tracks", action.to_lowercase(), merges.len());
+        return Ok(());
+    }
+
+    if !options.yes {
+        println!("{action} {} tracks? [y/N] ", merges.len());
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled");
+            return Ok(());
+        }
+    }
+
+    let mut resolved = 0u64;
+    let mut failed = 0u64;
+    for (duplicate, kept) in &merges {
+        if let Err(e) = db.merge_duplicate_track(&duplicate.id, &kept.id).await {
+            eprintln!("Failed to remove {}: {e}", duplicate.path.display());
+            failed += 1;
+            continue;
+        }
+        resolved += 1;
+        // Exact duplicates can be the same file under another path
+        let same_file = matches!(
+            (duplicate.path.canonicalize(), kept.path.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        );
+        if options.delete_files
+            && !same_file
+            && let Err(e) = std::fs::remove_file(&duplicate.path)
+        {
+            eprintln!("Failed to delete {}: {e}", duplicate.path.display());
+            failed += 1;
+        }
+    }
+
+    println!("Removed {resolved} duplicates");
+    if failed > 0 {
+        println!("Failed to remove {failed} duplicates");
+    }
+
+    Ok(())
+}
+
+/// Ask which track of a duplicate group to keep.
+///
+/// Returns `None` to leave the group as is.
+fn choose_kept_interactively(group: &[Track], music_dir: Option<&Path>) -> Result<Option<usize>> {
+    let suggested = choose_kept(group, KeepRule::Best, music_dir).unwrap_or(0);
+    println!("{} - {}:", group[0].artist, group[0].title);
+    for (index, track) in group.iter().enumerate() {
+        let quality = [
+            Some(track.format.to_string()),
+            track.bitrate.map(|bitrate| format!("{bitrate} kbps")),
+            track.sample_rate.map(|rate| format!("{rate} Hz")),
+            track.bit_depth.map(|depth| format!("{depth} bit")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+        let marker = if index == suggested { '*' } else { ' ' };
+        println!(
+            " {marker}{}. {} ({quality}, added {})",
+            index + 1,
+            track.path.display(),
+            track.added_at.format("%Y-%m-%d")
+        );
+    }
+
+    loop {
+        let input = prompt(&format!(
+            "Keep which track? [1-{}, Enter for {}, s to skip] ",
+            group.len(),
+            suggested + 1
+        ))?;
+        if input.is_empty() {
+            return Ok(Some(suggested));
+        }
+        if input.eq_ignore_ascii_case("s") {
+            return Ok(None);
+        }
+        match input.parse::<usize>() {
+            Ok(number) if (1..=group.len()).contains(&number) => return Ok(Some(number - 1)),
+            _ => println!("Unknown choice: {input}"),
+        }
+    }
+}
+
 /// Organize files using path templates.
-#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
+///
+/// Tracks under the path of an import profile with a template use that
+/// template instead. With `relink`, each track's path is updated along with
+/// its file, and the file operation is undone if the update fails.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::too_many_lines,
+    clippy::fn_params_excessive_bools
+)]
 async fn cmd_organize(
     lib_path: &Path,
     destination: &Path,
-    template_str: &str,
+    template: Option<&str>,
+    config: &Config,
     move_files: bool,
+    relink: bool,
     force: bool,
     dry_run: bool,
     track_ids: &[String],
@@ -861,17 +6153,30 @@ async fn cmd_organize(
         std::process::exit(1);
     }
 
-    // Parse the template
+    // An explicit template applies to all tracks
+    let profiles = if template.is_some() {
+        &[][..]
+    } else {
+        &config.import.profiles[..]
+    };
+    let template_str = template.unwrap_or(&config.paths.path_template);
     let template = PathTemplate::parse(template_str)
         .with_context(|| format!("Invalid path template: {template_str}"))?;
+    let profile_templates = profile_templates(profiles)?;
 
     println!("Using template: {template_str}");
+    for (profile, _) in &profile_templates {
+        println!("  {} uses its profile template", profile.pattern);
+    }
     println!("Destination: {}", destination.display());
     if move_files {
         println!("Mode: MOVE (files will be moved, not copied)");
     } else {
         println!("Mode: COPY");
     }
+    if relink {
+        println!("Library paths will be updated to the organized files");
+    }
     if dry_run {
         println!("DRY RUN - no files will be modified");
     }
@@ -914,14 +6219,7 @@ async fn cmd_organize(
     println!();
 
     // Set up progress bar
-    let progress_bar = ProgressBar::new(total as u64);
-    progress_bar.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )
-        .unwrap()
-        .progress_chars("█▓▒░"),
-    );
+    let progress_bar = progress_bar(total as u64, BAR_TEMPLATE);
 
     let mut organized = 0u64;
     let mut skipped = 0u64;
@@ -931,11 +6229,70 @@ async fn cmd_organize(
         move_files,
         overwrite: force,
         create_dirs: true,
+        rename_on_conflict: false,
     };
 
+    // Albums are needed for compilations and %aunique
+    let albums = db.list_albums(u32::MAX, 0).await?;
+    let album_set = AlbumSet::new(albums.iter().cloned());
+    let albums: HashMap<AlbumId, Album> = albums
+        .into_iter()
+        .map(|album| (album.id.clone(), album))
+        .collect();
+
+    // Stored paths should not depend on the working directory
+    let destination = std::path::absolute(destination)
+        .with_context(|| format!("Invalid destination: {}", destination.display()))?;
+    let mut relinked = 0u64;
+    let mut operation = Operation::new(
+        OperationKind::Organize,
+        format!("organize into {}", destination.display()),
+    );
+
     for track in &tracks {
         progress_bar.inc(1);
 
+        let mut ctx = TemplateContext::from_track(track);
+        if let Some(album) = track.album_id.as_ref().and_then(|id| albums.get(id)) {
+            ctx.set_album(album, &album_set);
+        }
+        let template = profile_templates
+            .iter()
+            .find(|(profile, _)| profile.matches(&track.path))
+            .map_or(&template, |(_, profile_template)| profile_template);
+
+        let dest = match template.render_with_extension(&ctx) {
+            Ok(relative) => destination.join(relative),
+            Err(e) => {
+                eprintln!("Template error for {}: {e}", track.path.display());
+                failed += 1;
+                continue;
+            }
+        };
+
+        // A file that is already in place, such as after reorganizing the
+        // files by hand, only needs its path updated
+        if relink
+            && dest.canonicalize().ok() != track.path.canonicalize().ok()
+            && has_track_content(&dest, track)
+        {
+            if dry_run {
+                println!(
+                    "{} -> {} (already in place)",
+                    track.path.display(),
+                    dest.display()
+                );
+            } else if let Err(e) = db.set_track_path(&track.id, &dest).await {
+                tracing::warn!("Failed to relink {}: {e}", track.path.display());
+                failed += 1;
+                continue;
+            } else {
+                operation.record_track(track, &relocated(track, &dest), false);
+            }
+            relinked += 1;
+            continue;
+        }
+
         // Check if source file exists
         if !track.path.exists() {
             tracing::warn!("Source file missing: {}", track.path.display());
@@ -945,39 +6302,49 @@ async fn cmd_organize(
 
         if dry_run {
             // Just preview the destination
-            let ctx = apollo_core::TemplateContext::from_track(track);
-            match template.render_with_extension(&ctx) {
-                Ok(relative) => {
-                    let dest = destination.join(&relative);
-                    println!("{} -> {}", track.path.display(), dest.display());
-                    organized += 1;
-                }
-                Err(e) => {
-                    eprintln!("Template error for {}: {e}", track.path.display());
+            println!("{} -> {}", track.path.display(), dest.display());
+            organized += 1;
+            continue;
+        }
+
+        // Actually organize the file
+        match organize_file_with_context(&track.path, &destination, template, &ctx, &options) {
+            Ok(result) => {
+                tracing::debug!(
+                    "{} {} -> {}",
+                    if result.moved { "Moved" } else { "Copied" },
+                    result.source.display(),
+                    result.destination.display()
+                );
+                if relink && let Err(e) = db.set_track_path(&track.id, &result.destination).await {
+                    tracing::warn!("Failed to relink {}: {e}", track.path.display());
+                    // Keep the library and the files in step
+                    if let Err(e) = undo_organize(&result) {
+                        progress_bar.suspend(|| {
+                            eprintln!(
+                                "Failed to restore {} from {}: {e}",
+                                result.source.display(),
+                                result.destination.display()
+                            );
+                        });
+                    }
                     failed += 1;
+                    continue;
                 }
-            }
-        } else {
-            // Actually organize the file
-            match organize_file(&track.path, destination, &template, track, &options) {
-                Ok(result) => {
-                    tracing::debug!(
-                        "{} {} -> {}",
-                        if result.moved { "Moved" } else { "Copied" },
-                        result.source.display(),
-                        result.destination.display()
-                    );
-                    organized += 1;
+                operation.record_file(&result.source, &result.destination, result.moved);
+                if relink {
+                    operation.record_track(track, &relocated(track, &result.destination), false);
                 }
-                Err(e) => {
-                    // Check if it's just a "file exists" error and we should skip
-                    let err_str = e.to_string();
-                    if err_str.contains("already exists") {
-                        skipped += 1;
-                    } else {
-                        tracing::warn!("Failed to organize {}: {e}", track.path.display());
-                        failed += 1;
-                    }
+                organized += 1;
+            }
+            Err(e) => {
+                // Check if it's just a "file exists" error and we should skip
+                let err_str = e.to_string();
+                if err_str.contains("already exists") {
+                    skipped += 1;
+                } else {
+                    tracing::warn!("Failed to organize {}: {e}", track.path.display());
+                    failed += 1;
                 }
             }
         }
@@ -993,18 +6360,143 @@ async fn cmd_organize(
         println!("Organization complete:");
         println!("  Organized: {organized}");
     }
+    if relinked > 0 {
+        if dry_run {
+            println!("  Would relink (already in place): {relinked}");
+        } else {
+            println!("  Relinked (already in place): {relinked}");
+        }
+    }
     if skipped > 0 {
         println!("  Skipped: {skipped}");
     }
     if failed > 0 {
         println!("  Failed: {failed}");
     }
+    record_operation(&db, &operation).await?;
+
+    Ok(())
+}
+
+/// Get a copy of a track at another path.
+fn relocated(track: &Track, path: &Path) -> Track {
+    let mut relocated = track.clone();
+    relocated.path = path.to_path_buf();
+    relocated
+}
 
+/// Record an operation in the library's log, unless it changed nothing, and
+/// tell how to undo it.
+async fn record_operation(db: &SqliteLibrary, operation: &Operation) -> Result<()> {
+    if operation.is_empty() {
+        return Ok(());
+    }
+    let id = db
+        .record_operation(operation)
+        .await
+        .context("Failed to record the operation")?;
+    println!("Undo with 'apollo undo {id}'");
     Ok(())
 }
 
+/// Parse the path templates of the import profiles that have one.
+fn profile_templates(profiles: &[ImportProfile]) -> Result<Vec<(&ImportProfile, PathTemplate)>> {
+    let mut templates = Vec::new();
+    for profile in profiles {
+        if let Some(profile_template) = &profile.path_template {
+            let parsed = PathTemplate::parse(profile_template).with_context(|| {
+                format!(
+                    "Invalid path template for {}: {profile_template}",
+                    profile.pattern
+                )
+            })?;
+            templates.push((profile, parsed));
+        }
+    }
+    Ok(templates)
+}
+
+/// Check if a file has the same content as when a track was imported.
+fn has_track_content(path: &Path, track: &Track) -> bool {
+    !track.file_hash.is_empty()
+        && path.is_file()
+        && compute_file_hash(path).is_ok_and(|hash| hash == track.file_hash)
+}
+
+/// How long to wait for open connections when the web server stops.
+const WEB_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Set up the services that the web server looks up artists and tracks on.
+fn with_lookup_services(
+    mut state: apollo_web::AppState,
+    config: &Config,
+    lib_path: &Path,
+) -> Result<apollo_web::AppState> {
+    // Artists are found on MusicBrainz, and their images kept next to the library
+    if config.musicbrainz.enabled {
+        let client = ArtistImageClient::new(
+            &config.musicbrainz.app_name,
+            &config.musicbrainz.app_version,
+            &config.musicbrainz.contact_email,
+        )
+        .context("Failed to create artist image client")?
+        .with_fanart_api_key(config.fanart.api_key.clone());
+        let directory = lib_path.with_file_name("artists");
+        state = state.with_artist_images(ArtistImages::new(client, directory));
+    }
+
+    // Tracks are identified by their audio once AcoustID has an API key
+    if config.musicbrainz.enabled && config.acoustid.enabled && !config.acoustid.api_key.is_empty()
+    {
+        let acoustid = AcoustIdClient::new(config.acoustid.api_key.clone())
+            .context("Failed to create AcoustID client")?;
+        let musicbrainz = MusicBrainzClient::new(
+            &config.musicbrainz.app_name,
+            &config.musicbrainz.app_version,
+            &config.musicbrainz.contact_email,
+        )
+        .context("Failed to create MusicBrainz client")?;
+        state = state.with_identifier(TrackIdentifier::new(acoustid, musicbrainz));
+    }
+
+    // Candidate tags for tracks are looked up on the services set up
+    let mut tag_sources = TagSources::default();
+    if config.musicbrainz.enabled {
+        tag_sources = tag_sources.with_musicbrainz(
+            MusicBrainzClient::new(
+                &config.musicbrainz.app_name,
+                &config.musicbrainz.app_version,
+                &config.musicbrainz.contact_email,
+            )
+            .context("Failed to create MusicBrainz client")?,
+        );
+    }
+    if !config.discogs.token.is_empty() {
+        tag_sources = tag_sources.with_discogs(
+            DiscogsClient::new(
+                &config.musicbrainz.app_name,
+                &config.musicbrainz.app_version,
+                &config.discogs.token,
+            )
+            .context("Failed to create Discogs client")?,
+        );
+    }
+    Ok(state.with_tag_sources(tag_sources))
+}
+
 /// Start the web server.
-async fn cmd_web(lib_path: &Path, host: &str, port: u16, static_dir: Option<&Path>) -> Result<()> {
+///
+/// The server stops on Ctrl+C or SIGTERM, once open connections finish or
+/// [`WEB_SHUTDOWN_TIMEOUT`] passes. When run as a systemd service with
+/// `Type=notify`, it reports when it is ready and when it is stopping.
+async fn cmd_web(
+    lib_path: &Path,
+    host: &str,
+    port: u16,
+    static_dir: Option<&Path>,
+    pid_file: Option<&Path>,
+    config: &Config,
+) -> Result<()> {
     // Check if library exists
     if !lib_path.exists() {
         eprintln!("Library not found at: {}", lib_path.display());
@@ -1017,8 +6509,54 @@ async fn cmd_web(lib_path: &Path, host: &str, port: u16, static_dir: Option<&Pat
     let db = SqliteLibrary::new(&db_url)
         .await
         .context("Failed to open library database")?;
+    let events = db.events().clone();
+
+    // Run plugin scheduled tasks and event handlers alongside the server,
+    // sharing the event bus so changes made by either side are announced
+    let plugin_db = Arc::new(
+        SqliteLibrary::new(&db_url)
+            .await
+            .context("Failed to open library database")?
+            .with_events(events.clone()),
+    );
+    let plugin_config = config.clone();
+    let scheduler = spawn_scheduler(
+        move || {
+            let runtime = load_plugin_runtime(&plugin_config)?;
+            runtime
+                .enable_library(plugin_db)
+                .context("Failed to set up library access for plugins")?;
+            anyhow::Ok(runtime)
+        },
+        Some(events.subscribe()),
+    )
+    .context("Failed to start plugin scheduler")?;
 
-    let state = std::sync::Arc::new(apollo_web::AppState::new(db));
+    // Imports run the plugins' import hooks, in a worker of their own
+    let hook_db = SqliteLibrary::new(&db_url)
+        .await
+        .context("Failed to open library database")?
+        .with_events(events.clone());
+    let hooks = Arc::new(spawn_import_hooks(config, Arc::new(hook_db))?);
+
+    let state = apollo_web::AppState::new(db)
+        .with_config(config.clone())
+        .with_hooks(Arc::clone(&hooks));
+    let state = std::sync::Arc::new(with_lookup_services(state, config, lib_path)?);
+
+    let ssdp = config
+        .dlna
+        .enabled
+        .then(|| SsdpServer::new(dlna::device_uuid(config), port));
+    let mpd = if config.mpd.enabled {
+        let listener = tokio::net::TcpListener::bind((host, config.mpd.port))
+            .await
+            .context("Failed to bind MPD port")?;
+        Some(listener)
+    } else {
+        None
+    };
+    let tasks = spawn_server_tasks(&state, &hooks, lib_path, config, host, ssdp, mpd);
     let app = apollo_web::create_router_with_static_files(state, static_dir);
 
     let addr = format!("{host}:{port}");
@@ -1027,27 +6565,238 @@ async fn cmd_web(lib_path: &Path, host: &str, port: u16, static_dir: Option<&Pat
         println!("Web UI available at http://{addr}/");
     }
     println!("Swagger UI available at http://{addr}/swagger-ui");
+    if config.web.read_only {
+        println!("Serving read-only: the library cannot be changed over the API");
+    }
     println!();
     println!("Press Ctrl+C to stop");
 
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .context("Failed to bind to address")?;
+    let _pid_file = pid_file.map(PidFile::create).transpose()?;
+    notify_systemd("READY=1");
+
+    let (stopping, stopped) = tokio::sync::oneshot::channel();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        println!("Shutting down, waiting for open connections");
+        notify_systemd("STOPPING=1");
+        let _ = stopping.send(());
+    });
+    // Streaming responses can keep connections open indefinitely
+    let timeout = async {
+        if stopped.await.is_ok() {
+            tokio::time::sleep(WEB_SHUTDOWN_TIMEOUT).await;
+        } else {
+            std::future::pending::<()>().await;
+        }
+    };
+    tokio::select! {
+        result = server.into_future() => result.context("Web server error")?,
+        () = timeout => eprintln!(
+            "Closing connections still open after {}s",
+            WEB_SHUTDOWN_TIMEOUT.as_secs()
+        ),
+    }
 
-    axum::serve(listener, app)
-        .await
-        .context("Web server error")?;
-
+    for task in tasks {
+        task.abort();
+    }
+    if let Some(ssdp) = ssdp {
+        ssdp.bye().await;
+    }
+    // Waits for a running task, then runs the plugins' on_close hooks
+    scheduler.stop();
+    println!("Server stopped");
     Ok(())
 }
 
+/// Start the tasks that run alongside the web server: the folder watcher,
+/// the writer of queued tags, the scheduled maintenance jobs, the DLNA
+/// announcements, the scrobbler, the playlist file exporter, and the MPD
+/// listener.
+fn spawn_server_tasks(
+    state: &Arc<apollo_web::AppState>,
+    hooks: &Arc<LuaWorkerPool>,
+    lib_path: &Path,
+    config: &Config,
+    host: &str,
+    ssdp: Option<SsdpServer>,
+    mpd: Option<tokio::net::TcpListener>,
+) -> Vec<tokio::task::JoinHandle<()>> {
+    let mut tasks = Vec::new();
+
+    // Import files dropped into watched folders while the server runs
+    if config.watch.enabled {
+        let watcher =
+            FolderWatcher::new(Arc::clone(&state.db), config).with_hooks(Arc::clone(hooks));
+        for directory in watcher.directories() {
+            println!("Watching {} for new music", directory.display());
+        }
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = watcher.run().await {
+                tracing::error!("Folder watcher stopped: {e:?}");
+            }
+        }));
+    }
+    // Write queued tag changes while the server runs
+    if config.write.deferred {
+        tasks.push(tokio::spawn(
+            TagWriter::new(Arc::clone(&state.db), config).run(),
+        ));
+    }
+    // Run the maintenance jobs configured under [schedule]
+    let jobs = JobScheduler::new(Arc::clone(state)).with_library_path(lib_path.to_path_buf());
+    for (job, interval) in jobs.jobs() {
+        println!("Running {} every {}s", job.name(), interval.as_secs());
+    }
+    tasks.push(tokio::spawn(jobs.run()));
+    // Let players on the local network find the media server
+    if let Some(ssdp) = ssdp {
+        println!(
+            "Serving the library to DLNA players as '{}'",
+            config.dlna.name
+        );
+        if host == "localhost"
+            || host
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback())
+        {
+            eprintln!(
+                "Warning: players on the network cannot reach {host}, use --host 0.0.0.0 to serve them"
+            );
+        }
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = ssdp.run().await {
+                tracing::error!("DLNA discovery stopped: {e:?}");
+            }
+        }));
+    }
+    // Send plays recorded through the API or Lua scripts to scrobble services
+    match Scrobbler::new(Arc::clone(&state.db), config) {
+        Ok(scrobbler) if scrobbler.is_enabled() => {
+            println!("Scrobbling plays to {}", scrobbler.services().join(", "));
+            let events = state.db.events().subscribe();
+            tasks.push(tokio::spawn(scrobbler.run(events)));
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: not scrobbling plays: {e:?}"),
+    }
+    // Keep the files of exported playlists up to date
+    let exporter = PlaylistExporter::new(Arc::clone(&state.db), config);
+    for target in exporter.targets() {
+        println!(
+            "Writing playlist '{}' to {}",
+            target.playlist,
+            target.path.display()
+        );
+    }
+    if exporter.is_enabled() {
+        let events = state.db.events().subscribe();
+        tasks.push(tokio::spawn(exporter.run(events)));
+    }
+    // Let MPD clients browse the library and control the play queue
+    if let Some(listener) = mpd {
+        println!("Accepting MPD clients at {host}:{}", config.mpd.port);
+        tasks.push(tokio::spawn(
+            MpdServer::new(Arc::clone(state)).serve(listener),
+        ));
+    }
+    tasks
+}
+
+/// Wait for Ctrl+C, or SIGTERM from a service manager.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
+
+/// Send a state change such as `READY=1` to systemd.
+///
+/// Does nothing unless systemd set `NOTIFY_SOCKET`, as it does for services
+/// with `Type=notify`.
+#[cfg(unix)]
+fn notify_systemd(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let result = UnixDatagram::unbound().and_then(|datagram| {
+        // A leading '@' names a socket in the abstract namespace
+        #[cfg(target_os = "linux")]
+        if let Some(name) = socket.as_encoded_bytes().strip_prefix(b"@") {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            return datagram.send_to_addr(state.as_bytes(), &addr);
+        }
+        datagram.send_to(state.as_bytes(), &socket)
+    });
+    if let Err(e) = result {
+        tracing::warn!("Failed to notify systemd: {e}");
+    }
+}
+
+/// Send a state change to systemd, which is not available here.
+#[cfg(not(unix))]
+const fn notify_systemd(_state: &str) {}
+
+/// A file holding the process ID, which is removed when dropped.
+struct PidFile(PathBuf);
+
+impl PidFile {
+    /// Write the current process ID to a file.
+    fn create(path: &Path) -> Result<Self> {
+        ensure_parent_dir(path)?;
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write PID file: {}", path.display()))?;
+        Ok(Self(path.to_path_buf()))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
 /// Handle configuration commands.
 fn cmd_config(action: ConfigAction, config_path: Option<&Path>) -> Result<()> {
     match action {
         ConfigAction::Show => {
-            let config = load_config(config_path)?;
+            let config = read_config(config_path)?;
             let toml = config.to_toml().context("Failed to serialize config")?;
             println!("{toml}");
+            if let Err(e) = config.validate() {
+                eprintln!("Warning: {e}");
+            }
             Ok(())
         }
         ConfigAction::Init { force } => {
@@ -1089,119 +6838,188 @@ fn cmd_config(action: ConfigAction, config_path: Option<&Path>) -> Result<()> {
             Ok(())
         }
         ConfigAction::Get { key } => {
-            let config = load_config(config_path)?;
-            let value = get_config_value(&config, &key)?;
-            println!("{value}");
+            let config = read_config(config_path)?;
+            match config.get_value(&key)? {
+                Some(toml::Value::Table(table)) => print!("{}", toml::to_string_pretty(&table)?),
+                Some(value) => println!("{}", format_config_value(&value)),
+                // Unset optional settings print as empty
+                None => println!(),
+            }
             Ok(())
         }
         ConfigAction::Set { key, value } => {
-            let mut config = load_config(config_path)?;
-            set_config_value(&mut config, &key, &value)?;
-
-            let path = config_path
-                .map(PathBuf::from)
-                .or_else(Config::default_path)
-                .context("Could not determine config path")?;
-
-            config.save_to(&path).context("Failed to save config")?;
+            let mut config = read_config(config_path)?;
+            config.set_value(&key, &value)?;
+            save_config(&config, config_path)?;
             println!("Set {key} = {value}");
+            if let Err(e) = config.validate() {
+                eprintln!("Warning: {e}");
+            }
+
+            Ok(())
+        }
+        ConfigAction::Unset { key } => {
+            let mut config = read_config(config_path)?;
+            config.unset_value(&key)?;
+            save_config(&config, config_path)?;
+            match config.get_value(&key)? {
+                Some(value) if !value.is_table() => {
+                    println!("Reset {key} to {}", format_config_value(&value));
+                }
+                _ => println!("Unset {key}"),
+            }
+            if let Err(e) = config.validate() {
+                eprintln!("Warning: {e}");
+            }
 
             Ok(())
         }
+        ConfigAction::Edit => edit_config(config_path),
     }
 }
 
-/// Get a configuration value by key path.
-fn get_config_value(config: &Config, key: &str) -> Result<String> {
-    let parts: Vec<&str> = key.split('.').collect();
+/// Open the configuration file in an editor, creating it if needed.
+fn edit_config(config_path: Option<&Path>) -> Result<()> {
+    let path = config_path
+        .map(PathBuf::from)
+        .or_else(Config::default_path)
+        .context("Could not determine config path")?;
+    if !path.exists() {
+        Config::default()
+            .save_to(&path)
+            .context("Failed to save config")?;
+    }
 
-    match parts.as_slice() {
-        ["library", "path"] => Ok(config.library.path.display().to_string()),
-        ["import", "move_files"] => Ok(config.import.move_files.to_string()),
-        ["import", "write_tags"] => Ok(config.import.write_tags.to_string()),
-        ["import", "copy_album_art"] => Ok(config.import.copy_album_art.to_string()),
-        ["import", "auto_create_albums"] => Ok(config.import.auto_create_albums.to_string()),
-        ["import", "compute_hashes"] => Ok(config.import.compute_hashes.to_string()),
-        ["paths", "music_directory"] => Ok(config
-            .paths
-            .music_directory
-            .as_ref()
-            .map(|p| p.display().to_string())
-            .unwrap_or_default()),
-        ["paths", "path_template"] => Ok(config.paths.path_template.clone()),
-        ["musicbrainz", "enabled"] => Ok(config.musicbrainz.enabled.to_string()),
-        ["musicbrainz", "auto_tag"] => Ok(config.musicbrainz.auto_tag.to_string()),
-        ["musicbrainz", "app_name"] => Ok(config.musicbrainz.app_name.clone()),
-        ["musicbrainz", "app_version"] => Ok(config.musicbrainz.app_version.clone()),
-        ["musicbrainz", "contact_email"] => Ok(config.musicbrainz.contact_email.clone()),
-        ["acoustid", "enabled"] => Ok(config.acoustid.enabled.to_string()),
-        ["acoustid", "api_key"] => Ok(config.acoustid.api_key.clone()),
-        ["acoustid", "auto_lookup"] => Ok(config.acoustid.auto_lookup.to_string()),
-        ["web", "host"] => Ok(config.web.host.clone()),
-        ["web", "port"] => Ok(config.web.port.to_string()),
-        ["web", "swagger_ui"] => Ok(config.web.swagger_ui.to_string()),
-        ["plugins", "directory"] => Ok(config.plugins.directory.display().to_string()),
-        ["plugins", "enabled"] => Ok(config.plugins.enabled.join(", ")),
-        _ => anyhow::bail!("Unknown configuration key: {key}"),
-    }
-}
-
-/// Set a configuration value by key path.
-fn set_config_value(config: &mut Config, key: &str, value: &str) -> Result<()> {
-    let parts: Vec<&str> = key.split('.').collect();
-
-    match parts.as_slice() {
-        ["library", "path"] => config.library.path = PathBuf::from(value),
-        ["import", "move_files"] => config.import.move_files = parse_bool(value)?,
-        ["import", "write_tags"] => config.import.write_tags = parse_bool(value)?,
-        ["import", "copy_album_art"] => config.import.copy_album_art = parse_bool(value)?,
-        ["import", "auto_create_albums"] => config.import.auto_create_albums = parse_bool(value)?,
-        ["import", "compute_hashes"] => config.import.compute_hashes = parse_bool(value)?,
-        ["paths", "music_directory"] => {
-            config.paths.music_directory = if value.is_empty() {
-                None
-            } else {
-                Some(PathBuf::from(value))
-            };
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+    // Editors are often set with arguments, such as "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("No editor set; set $EDITOR")?;
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start editor: {editor}"))?;
+    if !status.success() {
+        anyhow::bail!("Editor exited with {status}");
+    }
+
+    let config = match Config::read_from(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!("Run 'apollo config edit' again to fix it");
+            std::process::exit(1);
         }
-        ["paths", "path_template"] => config.paths.path_template = value.to_string(),
-        ["musicbrainz", "enabled"] => config.musicbrainz.enabled = parse_bool(value)?,
-        ["musicbrainz", "auto_tag"] => config.musicbrainz.auto_tag = parse_bool(value)?,
-        ["musicbrainz", "app_name"] => config.musicbrainz.app_name = value.to_string(),
-        ["musicbrainz", "app_version"] => config.musicbrainz.app_version = value.to_string(),
-        ["musicbrainz", "contact_email"] => config.musicbrainz.contact_email = value.to_string(),
-        ["acoustid", "enabled"] => config.acoustid.enabled = parse_bool(value)?,
-        ["acoustid", "api_key"] => config.acoustid.api_key = value.to_string(),
-        ["acoustid", "auto_lookup"] => config.acoustid.auto_lookup = parse_bool(value)?,
-        ["web", "host"] => config.web.host = value.to_string(),
-        ["web", "port"] => config.web.port = value.parse().context("Invalid port number")?,
-        ["web", "swagger_ui"] => config.web.swagger_ui = parse_bool(value)?,
-        ["plugins", "directory"] => config.plugins.directory = PathBuf::from(value),
-        ["plugins", "enabled"] => {
-            config.plugins.enabled = value
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect();
-        }
-        _ => anyhow::bail!("Unknown configuration key: {key}"),
+    };
+    if let Err(e) = config.validate() {
+        eprintln!("Warning: {e}");
     }
 
     Ok(())
 }
 
-/// Parse a boolean value from string.
-fn parse_bool(value: &str) -> Result<bool> {
-    match value.to_lowercase().as_str() {
-        "true" | "1" | "yes" | "on" => Ok(true),
-        "false" | "0" | "no" | "off" => Ok(false),
-        _ => anyhow::bail!("Invalid boolean value: {value} (use true/false)"),
+/// Editor for `apollo config edit` when neither `$VISUAL` nor `$EDITOR` is set.
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Format a configuration value for `apollo config get`.
+fn format_config_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(format_config_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        value => value.to_string(),
+    }
+}
+
+/// Complete configuration keys.
+fn config_keys() -> Vec<CompletionCandidate> {
+    read_config(None)
+        .unwrap_or_default()
+        .keys()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Complete playlist names from the library.
+fn playlist_names() -> Vec<CompletionCandidate> {
+    // Completion runs before arguments are parsed, so --config and
+    // --library are not known here
+    let Ok(config) = load_config(None) else {
+        return Vec::new();
+    };
+    let lib_path = get_library_path(None, &config);
+    if !lib_path.exists() {
+        return Vec::new();
     }
+
+    let playlists = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(async {
+            let db = SqliteLibrary::new(&format!("sqlite:{}", lib_path.display())).await?;
+            db.list_playlists().await
+        })
+    });
+    playlists
+        .unwrap_or_default()
+        .into_iter()
+        .map(|playlist| CompletionCandidate::new(playlist.name))
+        .collect()
+}
+
+/// Complete plugin names from the plugins directory.
+fn plugin_names() -> Vec<CompletionCandidate> {
+    let Ok(config) = load_config(None) else {
+        return Vec::new();
+    };
+    plugin_files(&config)
+        .unwrap_or_default()
+        .iter()
+        .map(|path| CompletionCandidate::new(plugin_file_name(path)))
+        .collect()
+}
+
+/// Complete sync profile names from the configuration.
+fn sync_profiles() -> Vec<CompletionCandidate> {
+    let Ok(config) = load_config(None) else {
+        return Vec::new();
+    };
+    config.sync.keys().map(CompletionCandidate::new).collect()
+}
+
+/// Print the completion script for a shell.
+fn cmd_completions(shell: Shell) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .with_context(|| format!("Unsupported shell: {shell}"))?;
+    let bin = std::env::current_exe().context("Failed to find the apollo executable")?;
+    completer.write_registration(
+        "COMPLETE",
+        "apollo",
+        "apollo",
+        &bin.to_string_lossy(),
+        &mut std::io::stdout(),
+    )?;
+    Ok(())
 }
 
 /// Handle playlist commands.
 #[allow(clippy::too_many_lines)]
-async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
+async fn cmd_playlist(
+    lib_path: &Path,
+    action: PlaylistAction,
+    output: OutputFormat,
+    config: &Config,
+) -> Result<()> {
     // Check if library exists
     if !lib_path.exists() {
         eprintln!("Library not found at: {}", lib_path.display());
@@ -1225,10 +7043,12 @@ async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
         } => {
             let playlist = if let Some(query_str) = query {
                 // Parse the query
-                let parsed_query = Query::parse(&query_str)
+                let (parsed_query, sort_spec) = Query::parse_sorted(&query_str)
                     .with_context(|| format!("Invalid query: {query_str}"))?;
 
-                let mut pl = Playlist::new_smart(&name, parsed_query).with_sort(sort.into());
+                let mut pl = Playlist::new_smart(&name, parsed_query)
+                    .with_sort(sort.into())
+                    .with_sort_spec(sort_spec);
 
                 if let Some(max) = max_tracks {
                     pl = pl.with_max_tracks(max);
@@ -1263,7 +7083,7 @@ async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
                 if let Some(ref q) = playlist.query {
                     println!("Query: {q}");
                 }
-                println!("Sort: {}", playlist.sort);
+                println!("Sort: {}", playlist.sort_label());
                 if let Some(ref limit) = playlist.limit
                     && let Some(max) = limit.max_tracks
                 {
@@ -1323,6 +7143,15 @@ async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
             playlist: name_or_id,
         } => {
             let playlist = find_playlist(&db, &name_or_id).await?;
+            if output != OutputFormat::Plain {
+                let tracks = db.get_playlist_tracks(&playlist.id).await?;
+                if output == OutputFormat::Ndjson {
+                    return print_json_list(output, &tracks);
+                }
+                let mut json = serde_json::to_value(&playlist)?;
+                json["tracks"] = serde_json::to_value(&tracks)?;
+                return print_json(output, &json);
+            }
 
             println!("Playlist: {}", playlist.name);
             println!("ID: {}", playlist.id);
@@ -1343,7 +7172,7 @@ async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
                 if let Some(ref q) = playlist.query {
                     println!("Query: {q}");
                 }
-                println!("Sort: {}", playlist.sort);
+                println!("Sort: {}", playlist.sort_label());
                 if let Some(ref limit) = playlist.limit
                     && let Some(max) = limit.max_tracks
                 {
@@ -1379,7 +7208,7 @@ async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
             playlist: name_or_id,
             track_ids,
         } => {
-            let playlist = find_playlist(&db, &name_or_id).await?;
+            let mut playlist = find_playlist(&db, &name_or_id).await?;
 
             if playlist.is_smart() {
                 anyhow::bail!("Cannot add tracks to a smart playlist");
@@ -1396,6 +7225,10 @@ async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
                     eprintln!("Warning: Track not found: {id_str}");
                     continue;
                 }
+                if !playlist.add_track(track_id.clone()) {
+                    eprintln!("Warning: Track already in playlist: {id_str}");
+                    continue;
+                }
 
                 db.add_track_to_playlist(&playlist.id, &track_id).await?;
                 added += 1;
@@ -1457,7 +7290,395 @@ async fn cmd_playlist(lib_path: &Path, action: PlaylistAction) -> Result<()> {
 
             Ok(())
         }
+        PlaylistAction::Merge {
+            playlist: name_or_id,
+            sources,
+        } => merge_playlists(&db, &name_or_id, &sources).await,
+        PlaylistAction::Duplicate {
+            playlist: name_or_id,
+            name,
+            snapshot,
+        } => duplicate_playlist(&db, &name_or_id, name, snapshot).await,
+        PlaylistAction::Versions {
+            playlist: name_or_id,
+        } => list_playlist_versions(&db, &name_or_id).await,
+        PlaylistAction::Restore {
+            playlist: name_or_id,
+            version,
+        } => restore_playlist_version(&db, &name_or_id, version).await,
+        PlaylistAction::Export {
+            playlist: name_or_id,
+            format,
+            relative_to,
+        } => export_playlist(&db, &name_or_id, format.into(), relative_to.as_deref()).await,
+        PlaylistAction::Import {
+            source,
+            name,
+            dry_run,
+        } => {
+            let path = Path::new(&source);
+            let is_csv = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+            if is_csv || PlaylistUrl::parse(&source).is_some() {
+                import_streaming_playlist(&db, &source, name, dry_run, config).await
+            } else {
+                import_playlist(&db, path, name, dry_run).await
+            }
+        }
+    }
+}
+
+/// Add the tracks of other playlists to a static playlist.
+async fn merge_playlists(db: &SqliteLibrary, name_or_id: &str, sources: &[String]) -> Result<()> {
+    let mut playlist = find_playlist(db, name_or_id).await?;
+    if playlist.is_smart() {
+        anyhow::bail!("Cannot merge into a smart playlist");
+    }
+
+    let mut track_ids = Vec::new();
+    for source in sources {
+        let source = find_playlist(db, source).await?;
+        if source.id == playlist.id {
+            anyhow::bail!("Cannot merge playlist '{}' into itself", playlist.name);
+        }
+        // Smart playlists are merged with the tracks they have now
+        let tracks = db.get_playlist_tracks(&source.id).await?;
+        track_ids.extend(tracks.into_iter().map(|track| track.id));
+    }
+
+    let added = playlist.merge_tracks(track_ids);
+    if added > 0 {
+        db.update_playlist(&playlist).await?;
+    }
+    println!(
+        "Added {added} track(s) to playlist '{}' ({} total)",
+        playlist.name,
+        playlist.track_ids.len()
+    );
+
+    Ok(())
+}
+
+/// Copy a playlist under a new name.
+async fn duplicate_playlist(
+    db: &SqliteLibrary,
+    name_or_id: &str,
+    name: Option<String>,
+    snapshot: bool,
+) -> Result<()> {
+    let playlist = find_playlist(db, name_or_id).await?;
+    let name = name.unwrap_or_else(|| format!("{} (copy)", playlist.name));
+    if db
+        .list_playlists()
+        .await?
+        .iter()
+        .any(|playlist| playlist.name.eq_ignore_ascii_case(&name))
+    {
+        anyhow::bail!("Playlist already exists: {name} (choose another name with --name)");
+    }
+
+    let copy = if snapshot && playlist.is_smart() {
+        let tracks = db.get_playlist_tracks(&playlist.id).await?;
+        playlist.snapshot(&name, tracks.into_iter().map(|track| track.id).collect())
+    } else {
+        playlist.duplicate(&name)
+    };
+    db.add_playlist(&copy).await?;
+
+    if copy.is_smart() {
+        println!("Created smart playlist: {name}");
+    } else {
+        println!(
+            "Created static playlist: {name} ({} tracks)",
+            copy.track_ids.len()
+        );
+    }
+    println!("ID: {}", copy.id);
+
+    Ok(())
+}
+
+/// List the versions of a static playlist, newest first.
+async fn list_playlist_versions(db: &SqliteLibrary, name_or_id: &str) -> Result<()> {
+    let playlist = find_playlist(db, name_or_id).await?;
+    if playlist.is_smart() {
+        anyhow::bail!("Smart playlists have no versions");
+    }
+
+    let versions = db.list_playlist_versions(&playlist.id).await?;
+    if versions.is_empty() {
+        println!("No versions of playlist '{}' yet.", playlist.name);
+        return Ok(());
+    }
+
+    for version in &versions {
+        println!(
+            "{:>5}  {}  {} tracks",
+            version.id,
+            version
+                .created_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            version.track_ids.len(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Give a static playlist the tracks of one of its versions.
+async fn restore_playlist_version(
+    db: &SqliteLibrary,
+    name_or_id: &str,
+    version: i64,
+) -> Result<()> {
+    let playlist = find_playlist(db, name_or_id).await?;
+    if playlist.is_smart() {
+        anyhow::bail!("Smart playlists have no versions");
+    }
+
+    let restored = db.restore_playlist_version(&playlist.id, version).await?;
+    println!(
+        "Restored playlist '{}' to version {version} ({} tracks)",
+        restored.name,
+        restored.track_ids.len()
+    );
+
+    Ok(())
+}
+
+/// Print a playlist file to standard output.
+async fn export_playlist(
+    db: &SqliteLibrary,
+    name_or_id: &str,
+    format: PlaylistFormat,
+    relative_to: Option<&Path>,
+) -> Result<()> {
+    let playlist = find_playlist(db, name_or_id).await?;
+    let relative_to = relative_to
+        .map(|dir| {
+            dir.canonicalize()
+                .with_context(|| format!("Directory not found: {}", dir.display()))
+        })
+        .transpose()?;
+
+    let mut tracks = db.get_playlist_tracks(&playlist.id).await?;
+    // Library paths are stored as imported, which may be relative
+    for track in &mut tracks {
+        if let Ok(path) = track.path.canonicalize() {
+            track.path = path;
+        }
+    }
+    print!(
+        "{}",
+        write_playlist(format, &playlist.name, &tracks, relative_to.as_deref())
+    );
+    eprintln!("Exported {} tracks", tracks.len());
+
+    Ok(())
+}
+
+/// Create a static playlist from the library tracks an M3U file lists.
+///
+/// Entries are matched by path, or else by the artist and title of their
+/// `#EXTINF` line if exactly one track has them.
+async fn import_playlist(
+    db: &SqliteLibrary,
+    file: &Path,
+    name: Option<String>,
+    dry_run: bool,
+) -> Result<()> {
+    if PlaylistFormat::from_path(file) != Some(PlaylistFormat::M3u8) {
+        anyhow::bail!(
+            "Only M3U and M3U8 playlists can be imported: {}",
+            file.display()
+        );
+    }
+    let text = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read playlist: {}", file.display()))?;
+    let file = file.canonicalize()?;
+    let base = file.parent().unwrap_or_else(|| Path::new("/"));
+    let entries = read_m3u(&text, base);
+
+    let name = name.unwrap_or_else(|| {
+        file.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    if db
+        .list_playlists()
+        .await?
+        .iter()
+        .any(|playlist| playlist.name.eq_ignore_ascii_case(&name))
+    {
+        anyhow::bail!("Playlist already exists: {name} (choose another name with --name)");
+    }
+
+    let tracks = db.list_tracks(u32::MAX, 0).await?;
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let by_path: HashMap<PathBuf, &Track> = tracks
+        .iter()
+        .map(|track| (canonical(&track.path), track))
+        .collect();
+    let mut by_name: HashMap<(String, String), Vec<&Track>> = HashMap::new();
+    for track in &tracks {
+        by_name
+            .entry((track.artist.to_lowercase(), track.title.to_lowercase()))
+            .or_default()
+            .push(track);
+    }
+
+    let mut playlist = Playlist::new_static(&name);
+    let mut unresolved = Vec::new();
+    for entry in &entries {
+        let by_info = || {
+            let key = (
+                entry.artist.as_ref()?.to_lowercase(),
+                entry.title.as_ref()?.to_lowercase(),
+            );
+            match by_name.get(&key)?.as_slice() {
+                [track] => Some(*track),
+                _ => None,
+            }
+        };
+        match by_path
+            .get(&canonical(&entry.path))
+            .copied()
+            .or_else(by_info)
+        {
+            // A track listed twice keeps its first place
+            Some(track) => {
+                playlist.add_track(track.id.clone());
+            }
+            None => unresolved.push(&entry.path),
+        }
+    }
+
+    if dry_run {
+        println!(
+            "Would create playlist '{name}' with {} of {} tracks",
+            playlist.track_count(),
+            entries.len()
+        );
+    } else {
+        db.add_playlist(&playlist).await?;
+        println!(
+            "Created playlist '{name}' with {} of {} tracks",
+            playlist.track_count(),
+            entries.len()
+        );
+    }
+
+    if !unresolved.is_empty() {
+        println!();
+        println!("Not found in library ({}):", unresolved.len());
+        for path in unresolved {
+            println!("  {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Create a static playlist from the library tracks on a Spotify or Deezer
+/// playlist, or a CSV export of one.
+///
+/// Songs are matched by ISRC, then by artist, title, and length. The songs
+/// the library does not have are listed afterwards.
+async fn import_streaming_playlist(
+    db: &SqliteLibrary,
+    source: &str,
+    name: Option<String>,
+    dry_run: bool,
+    config: &Config,
+) -> Result<()> {
+    let streaming = match PlaylistUrl::parse(source) {
+        Some(PlaylistUrl::Spotify(id)) => {
+            if !config.spotify.is_enabled() {
+                anyhow::bail!(
+                    "Spotify is not configured. Set spotify.client_id and \
+                     spotify.client_secret with 'apollo config set'"
+                );
+            }
+            SpotifyClient::new(&config.spotify.client_id, &config.spotify.client_secret)?
+                .get_playlist(&id)
+                .await
+                .context("Failed to read Spotify playlist")?
+        }
+        Some(PlaylistUrl::Deezer(id)) => DeezerClient::new()?
+            .get_playlist(&id)
+            .await
+            .context("Failed to read Deezer playlist")?,
+        None => {
+            let path = Path::new(source);
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read playlist: {}", path.display()))?;
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            read_csv_export(&stem, &text)?
+        }
+    };
+
+    let name = name.unwrap_or_else(|| streaming.name.clone());
+    if db
+        .list_playlists()
+        .await?
+        .iter()
+        .any(|playlist| playlist.name.eq_ignore_ascii_case(&name))
+    {
+        anyhow::bail!("Playlist already exists: {name} (choose another name with --name)");
+    }
+
+    let tracks = db.list_tracks(u32::MAX, 0).await?;
+    let matcher = PlaylistMatcher::new(&tracks);
+    let mut playlist = Playlist::new_static(&name);
+    let mut methods: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut unmatched = Vec::new();
+    for song in &streaming.tracks {
+        match matcher.find(song) {
+            Some(found) => {
+                playlist.add_track(found.track.id.clone());
+                let method = match found.method {
+                    MatchMethod::Isrc => "ISRC",
+                    MatchMethod::Metadata => "artist and title",
+                    MatchMethod::Fuzzy => "similar artist and title",
+                };
+                *methods.entry(method).or_default() += 1;
+            }
+            None => unmatched.push(song),
+        }
+    }
+
+    if dry_run {
+        println!(
+            "Would create playlist '{name}' with {} of {} tracks",
+            playlist.track_count(),
+            streaming.tracks.len()
+        );
+    } else {
+        db.add_playlist(&playlist).await?;
+        println!(
+            "Created playlist '{name}' with {} of {} tracks",
+            playlist.track_count(),
+            streaming.tracks.len()
+        );
+    }
+    for (method, count) in &methods {
+        println!("  {count} matched by {method}");
+    }
+
+    if !unmatched.is_empty() {
+        println!();
+        println!("Not found in library ({}):", unmatched.len());
+        for song in unmatched {
+            println!("  {song}");
+        }
     }
+
+    Ok(())
 }
 
 /// Find a playlist by ID or name.
@@ -1480,3 +7701,350 @@ async fn find_playlist(db: &SqliteLibrary, name_or_id: &str) -> Result<Playlist>
 
     anyhow::bail!("Playlist not found: {name_or_id}")
 }
+
+/// Create a plugin runtime with access to the library, if one exists.
+async fn load_library_runtime(lib_path: &Path, config: &Config) -> Result<LuaRuntime> {
+    let db = if lib_path.exists() {
+        let db_url = format!("sqlite:{}", lib_path.display());
+        let db = SqliteLibrary::new(&db_url)
+            .await
+            .context("Failed to open library database")?;
+        Some(Arc::new(db))
+    } else {
+        None
+    };
+
+    let runtime = load_plugin_runtime(config)?;
+    if let Some(db) = db {
+        runtime
+            .enable_library(db)
+            .context("Failed to set up library access for plugins")?;
+    }
+
+    Ok(runtime)
+}
+
+/// Manage plugins and run plugin commands.
+#[allow(clippy::too_many_lines)]
+async fn cmd_plugin(
+    action: PluginAction,
+    lib_path: &Path,
+    config: &Config,
+    config_path: Option<&Path>,
+) -> Result<()> {
+    match action {
+        PluginAction::List => {
+            let paths = plugin_files(config)?;
+            if paths.is_empty() {
+                println!(
+                    "No plugins found in {}",
+                    config.plugins_directory().display()
+                );
+                return Ok(());
+            }
+
+            println!("{:<20} {:<10} {:<9} Hooks", "Plugin", "Version", "Status");
+            println!("{}", "-".repeat(70));
+            for path in &paths {
+                let name = plugin_file_name(path);
+                let status = if config.plugins.is_enabled(name) {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                match load_plugin_metadata(path) {
+                    Ok(plugin) => {
+                        let hooks: Vec<String> =
+                            plugin.hooks.iter().map(ToString::to_string).collect();
+                        println!(
+                            "{name:<20} {:<10} {status:<9} {}",
+                            plugin.version,
+                            if hooks.is_empty() {
+                                "-".to_string()
+                            } else {
+                                hooks.join(", ")
+                            }
+                        );
+                    }
+                    Err(e) => println!("{name:<20} {:<10} {status:<9} invalid: {e}", "-"),
+                }
+            }
+        }
+        PluginAction::Info { name } => {
+            let path = find_plugin_file(config, &name)?;
+            // Commands and scheduled tasks are only known once the plugin runs
+            let mut runtime = LuaRuntime::new().context("Failed to create Lua runtime")?;
+            let plugin = match runtime.load_plugin(&path) {
+                Ok(plugin) => plugin.clone(),
+                Err(e) => {
+                    eprintln!("Warning: failed to load plugin {}: {e}", path.display());
+                    load_plugin_metadata(&path)?
+                }
+            };
+
+            println!("Name: {}", plugin.name);
+            println!("Version: {}", plugin.version);
+            if let Some(author) = &plugin.author {
+                println!("Author: {author}");
+            }
+            if !plugin.description.is_empty() {
+                println!("Description: {}", plugin.description);
+            }
+            println!("File: {}", path.display());
+            println!(
+                "Status: {}",
+                if config.plugins.is_enabled(plugin_file_name(&path)) {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+
+            if !plugin.hooks.is_empty() {
+                println!("\nHooks:");
+                for hook in &plugin.hooks {
+                    println!("  {hook}");
+                }
+            }
+            if !plugin.commands.is_empty() {
+                println!("\nCommands:");
+                for command in &plugin.commands {
+                    println!("  {:<20} {}", command.name, command.description);
+                }
+            }
+            if !plugin.schedules.is_empty() {
+                println!("\nScheduled tasks:");
+                for task in &plugin.schedules {
+                    println!("  {task}");
+                }
+            }
+        }
+        PluginAction::Enable { name } => {
+            let path = find_plugin_file(config, &name)?;
+            let name = plugin_file_name(&path);
+            let mut config = read_config(config_path)?;
+            if config.plugins.is_enabled(name) {
+                println!("Plugin '{name}' is already enabled");
+                return Ok(());
+            }
+
+            config.plugins.enabled.push(name.to_string());
+            save_config(&config, config_path)?;
+            println!("Enabled plugin '{name}'");
+        }
+        PluginAction::Disable { name } => {
+            let path = find_plugin_file(config, &name)?;
+            let name = plugin_file_name(&path);
+            let mut config = read_config(config_path)?;
+            if !config.plugins.is_enabled(name) {
+                println!("Plugin '{name}' is already disabled");
+                return Ok(());
+            }
+
+            // An empty list enables every plugin, so list the others instead
+            let enabled: Vec<String> = if config.plugins.enabled.is_empty() {
+                plugin_files(&config)?
+                    .iter()
+                    .map(|path| plugin_file_name(path).to_string())
+                    .filter(|other| other != name)
+                    .collect()
+            } else {
+                config
+                    .plugins
+                    .enabled
+                    .iter()
+                    .filter(|other| *other != name)
+                    .cloned()
+                    .collect()
+            };
+            if enabled.is_empty() {
+                anyhow::bail!(
+                    "Cannot disable the last enabled plugin, because an empty plugins.enabled list enables all plugins. Move {} out of the plugins directory instead",
+                    path.display()
+                );
+            }
+
+            config.plugins.enabled = enabled;
+            save_config(&config, config_path)?;
+            println!("Disabled plugin '{name}'");
+        }
+        PluginAction::Commands => {
+            let runtime = load_library_runtime(lib_path, config).await?;
+            let commands = runtime.commands();
+            if commands.is_empty() {
+                println!("No plugin commands registered.");
+                return Ok(());
+            }
+
+            println!("{:<20} {:<20} Description", "Command", "Plugin");
+            println!("{}", "-".repeat(70));
+            for command in commands {
+                println!(
+                    "{:<20} {:<20} {}",
+                    command.name, command.plugin, command.description
+                );
+            }
+        }
+        PluginAction::Run { name, args } => {
+            let runtime = load_library_runtime(lib_path, config).await?;
+            if runtime.get_command(&name).is_none() {
+                eprintln!("Unknown plugin command: {name}");
+                eprintln!("Run 'apollo plugin commands' to see available commands");
+                std::process::exit(1);
+            }
+
+            runtime
+                .run_command(&name, &args)
+                .with_context(|| format!("Plugin command '{name}' failed"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Run Lua scripts or an interactive session.
+async fn cmd_lua(action: LuaAction, lib_path: &Path, config: &Config) -> Result<()> {
+    let runtime = load_library_runtime(lib_path, config).await?;
+
+    match action {
+        LuaAction::Run { script, args } => {
+            if !script.exists() {
+                eprintln!("Script not found: {}", script.display());
+                std::process::exit(1);
+            }
+
+            runtime
+                .run_script(&script, &args)
+                .with_context(|| format!("Script failed: {}", script.display()))?;
+        }
+        LuaAction::Repl => {
+            use std::io::{BufRead, Write};
+
+            println!("Apollo Lua {} (Ctrl+D to exit)", env!("CARGO_PKG_VERSION"));
+
+            let stdin = std::io::stdin();
+            let mut buffer = String::new();
+            loop {
+                print!("{}", if buffer.is_empty() { "> " } else { ">> " });
+                std::io::stdout().flush()?;
+
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line)? == 0 {
+                    println!();
+                    break;
+                }
+                buffer.push_str(&line);
+
+                if buffer.trim().is_empty() {
+                    buffer.clear();
+                    continue;
+                }
+
+                // Keep reading lines until the statement is complete
+                match runtime.eval_interactive(&buffer) {
+                    Ok(Some(values)) => {
+                        if !values.is_empty() {
+                            println!("{}", values.join("\t"));
+                        }
+                        buffer.clear();
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!("{e}");
+                        buffer.clear();
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_sources::musicbrainz::Release;
+    use std::time::Duration;
+
+    fn track(title: &str, number: u32) -> Track {
+        let mut track = Track::new(
+            PathBuf::from(format!("/incoming/{title}.mp3")),
+            title.to_string(),
+            "radiohead".to_string(),
+            Duration::from_secs(208),
+        );
+        track.album_title = Some("Pablo Honey".to_string());
+        track.track_number = Some(number);
+        track
+    }
+
+    fn locked(fields: &[&str]) -> Config {
+        let mut config = Config::default();
+        config.locks.fields = fields.iter().map(ToString::to_string).collect();
+        config.import.write_tags = false;
+        config
+    }
+
+    #[test]
+    fn test_apply_release_keeps_locked_fields() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": "release",
+                "title": "Pablo Honey",
+                "artist-credit": [{"artist": {"id": "radiohead", "name": "Radiohead"}}],
+                "media": [{
+                    "position": 1,
+                    "track-count": 1,
+                    "tracks": [
+                        {"id": "t1", "position": 1, "title": "You", "length": 208000,
+                         "recording": {"id": "r1", "title": "You"}}
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+        let mut tracks = vec![track("you (live)", 1)];
+        let candidate = ReleaseCandidate::new(release, &tracks);
+
+        apply_release(&mut tracks, &candidate, &locked(&["title"]));
+        assert_eq!(tracks[0].title, "you (live)");
+        assert_eq!(tracks[0].artist, "Radiohead");
+        assert_eq!(tracks[0].musicbrainz_id.as_deref(), Some("r1"));
+    }
+
+    #[tokio::test]
+    async fn test_import_hooks_keep_locked_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = dir.path().join("retitle.lua");
+        std::fs::write(
+            &plugin,
+            r#"
+            local plugin = { name = "retitle", version = "1.0.0" }
+
+            function plugin.on_import(track)
+                track.title = "Retitled"
+                track.year = 2001
+            end
+
+            return plugin
+            "#,
+        )
+        .unwrap();
+        let hooks = LuaWorkerPool::new(1, move || {
+            let mut runtime = LuaRuntime::new()?;
+            runtime.load_plugin(&plugin)?;
+            Ok::<_, apollo_lua::Error>(runtime)
+        })
+        .unwrap();
+
+        let locks = locked(&["title"]).locks.locked_fields();
+        let (tracks, skipped) = run_import_hooks(&hooks, vec![track("You", 1)], &locks)
+            .await
+            .unwrap();
+        hooks.shutdown();
+        assert_eq!(skipped, 0);
+        assert_eq!(tracks[0].title, "You");
+        assert_eq!(tracks[0].year, Some(2001));
+    }
+}
@@ -0,0 +1,254 @@
+//! Change log of tracks and playlists, for clients that sync incrementally.
+//!
+//! Every track and playlist that is created, updated, or deleted is recorded
+//! as a [`Change`] with an increasing sequence number. A client remembers the
+//! sequence number of its last sync and asks for the changes since then,
+//! which [`LibraryChanges::collect`] sums up to what the client has to fetch
+//! or drop.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::changes::{Change, ChangeKind, ChangedItem, LibraryChanges};
+//! use chrono::Utc;
+//!
+//! let change = |seq, kind| Change {
+//!     seq,
+//!     item: ChangedItem::Track,
+//!     id: "track-1".to_string(),
+//!     kind,
+//!     changed_at: Utc::now(),
+//! };
+//! let changes = [change(4, ChangeKind::Created), change(5, ChangeKind::Updated)];
+//!
+//! let summary = LibraryChanges::collect(3, &changes);
+//! assert_eq!(summary.seq, 5);
+//! assert_eq!(summary.tracks.created, vec!["track-1".to_string()]);
+//! assert!(summary.tracks.updated.is_empty());
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use utoipa::ToSchema;
+
+/// What happened to a changed item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// The item was added.
+    Created,
+    /// The item was changed.
+    Updated,
+    /// The item was removed.
+    Deleted,
+}
+
+impl ChangeKind {
+    /// Parse a change kind from its name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "created" => Some(Self::Created),
+            "updated" => Some(Self::Updated),
+            "deleted" => Some(Self::Deleted),
+            _ => None,
+        }
+    }
+
+    /// Get the name, as stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The kind of item that changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangedItem {
+    /// A track.
+    Track,
+    /// A playlist, or the tracks in it.
+    Playlist,
+}
+
+impl ChangedItem {
+    /// Parse an item kind from its name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "track" => Some(Self::Track),
+            "playlist" => Some(Self::Playlist),
+            _ => None,
+        }
+    }
+
+    /// Get the name, as stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Track => "track",
+            Self::Playlist => "playlist",
+        }
+    }
+}
+
+/// A recorded change to a track or playlist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    /// Sequence number in the change log; later changes have higher numbers.
+    pub seq: i64,
+    /// Kind of the changed item.
+    pub item: ChangedItem,
+    /// ID of the changed item.
+    pub id: String,
+    /// What happened to the item.
+    pub kind: ChangeKind,
+    /// When the change was made.
+    pub changed_at: DateTime<Utc>,
+}
+
+/// IDs of the items of one kind that changed since a sync.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ChangedIds {
+    /// Items that were added, and that the client does not have yet.
+    #[schema(example = json!(["550e8400-e29b-41d4-a716-446655440000"]))]
+    pub created: Vec<String>,
+    /// Items that the client has, but that changed.
+    pub updated: Vec<String>,
+    /// Items that the client has, but that were removed.
+    pub deleted: Vec<String>,
+}
+
+/// Changes to the library since a sync, by item.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct LibraryChanges {
+    /// Sequence number to ask for changes since at the next sync.
+    #[schema(example = 1234)]
+    pub seq: i64,
+    /// Changed tracks.
+    pub tracks: ChangedIds,
+    /// Changed playlists.
+    pub playlists: ChangedIds,
+}
+
+impl LibraryChanges {
+    /// Sum up the changes made after sequence number `since`, in order.
+    ///
+    /// Each item is listed once, by what happened to it overall: an item
+    /// that was created and then updated is only created, and one that was
+    /// created and deleted again is left out, since the client never had it.
+    #[must_use]
+    pub fn collect(since: i64, changes: &[Change]) -> Self {
+        // The first and last change of each item, in the order of first change
+        let mut order = Vec::new();
+        let mut spans: HashMap<(ChangedItem, &str), (ChangeKind, ChangeKind)> = HashMap::new();
+        for change in changes {
+            let key = (change.item, change.id.as_str());
+            spans
+                .entry(key)
+                .and_modify(|(_, last)| *last = change.kind)
+                .or_insert_with(|| {
+                    order.push(key);
+                    (change.kind, change.kind)
+                });
+        }
+
+        let mut result = Self {
+            seq: changes
+                .iter()
+                .map(|change| change.seq)
+                .fold(since, i64::max),
+            ..Self::default()
+        };
+        for key in order {
+            let (first, last) = spans[&key];
+            let ids = match key.0 {
+                ChangedItem::Track => &mut result.tracks,
+                ChangedItem::Playlist => &mut result.playlists,
+            };
+            let bucket = match (first, last) {
+                (ChangeKind::Created, ChangeKind::Deleted) => continue,
+                (_, ChangeKind::Deleted) => &mut ids.deleted,
+                (ChangeKind::Created, _) => &mut ids.created,
+                _ => &mut ids.updated,
+            };
+            bucket.push(key.1.to_string());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(seq: i64, item: ChangedItem, id: &str, kind: ChangeKind) -> Change {
+        Change {
+            seq,
+            item,
+            id: id.to_string(),
+            kind,
+            changed_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_collect_changes() {
+        use ChangeKind::{Created, Deleted, Updated};
+        use ChangedItem::{Playlist, Track};
+
+        let changes = [
+            change(11, Track, "new", Created),
+            change(12, Track, "edited", Updated),
+            change(13, Track, "new", Updated),
+            change(14, Track, "gone", Updated),
+            change(15, Track, "gone", Deleted),
+            change(16, Track, "brief", Created),
+            change(17, Track, "brief", Deleted),
+            change(18, Playlist, "replaced", Deleted),
+            change(19, Playlist, "replaced", Created),
+            change(20, Track, "edited", Updated),
+        ];
+
+        let summary = LibraryChanges::collect(10, &changes);
+        assert_eq!(summary.seq, 20);
+        assert_eq!(summary.tracks.created, vec!["new"]);
+        assert_eq!(summary.tracks.updated, vec!["edited"]);
+        assert_eq!(summary.tracks.deleted, vec!["gone"]);
+        assert_eq!(summary.playlists.updated, vec!["replaced"]);
+        assert!(summary.playlists.created.is_empty());
+
+        // Without changes, the client is up to date
+        let summary = LibraryChanges::collect(20, &[]);
+        assert_eq!(summary.seq, 20);
+        assert_eq!(summary.tracks, ChangedIds::default());
+    }
+
+    #[test]
+    fn test_names_roundtrip() {
+        for kind in [
+            ChangeKind::Created,
+            ChangeKind::Updated,
+            ChangeKind::Deleted,
+        ] {
+            assert_eq!(ChangeKind::from_name(kind.as_str()), Some(kind));
+        }
+        for item in [ChangedItem::Track, ChangedItem::Playlist] {
+            assert_eq!(ChangedItem::from_name(item.as_str()), Some(item));
+        }
+        assert_eq!(ChangeKind::from_name("moved"), None);
+    }
+}
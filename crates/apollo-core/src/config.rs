@@ -11,10 +11,18 @@
 //! path = "~/.apollo/apollo.db"
 //!
 //! [import]
+//! copy_files = false
 //! move_files = false
 //! write_tags = true
 //! copy_album_art = true
 //!
+//! # Settings for imports from matching paths
+//! [[import.profiles]]
+//! match = "~/Music/Audiobooks/**"
+//! path_template = "Audiobooks/$album_artist/$album/$track"
+//! auto_tag = false
+//! copy_album_art = false
+//!
 //! [paths]
 //! music_directory = "~/Music"
 //! path_template = "$artist/$album/$track - $title"
@@ -26,19 +34,107 @@
 //! [acoustid]
 //! api_key = ""
 //!
+//! [discogs]
+//! token = ""
+//!
+//! # Artist images for the web interface
+//! [fanart]
+//! api_key = ""
+//!
+//! # Scrobble recorded plays
+//! [lastfm]
+//! api_key = "your-api-key"
+//! api_secret = "your-api-secret"
+//! session_key = "your-session-key"
+//!
+//! [listenbrainz]
+//! token = "your-user-token"
+//!
+//! # Import Spotify playlists
+//! [spotify]
+//! client_id = "your-client-id"
+//! client_secret = "your-client-secret"
+//!
 //! [web]
 //! host = "127.0.0.1"
 //! port = 8337
+//! # Items in a page of tracks or albums, unless a client asks for more
+//! default_page_size = 50
+//! max_page_size = 500
+//!
+//! # Log each request, with an ID that error responses carry too
+//! [web.logging]
+//! access_log = true
+//! request_id_header = "x-request-id"
+//!
+//! # Let TVs and receivers on the network play the library
+//! [dlna]
+//! enabled = true
+//! name = "Living Room Music"
+//!
+//! # Let MPD clients browse the library and manage the play queue
+//! [mpd]
+//! enabled = true
+//! port = 6600
 //!
 //! [plugins]
 //! directory = "~/.config/apollo/plugins"
 //! enabled = ["clean_tags", "skip_hidden"]
+//!
+//! [genres]
+//! normalize_on_import = true
+//!
+//! # Credit featured artists in titles, as in "Title (feat. Guest)"
+//! [featured]
+//! normalize_on_import = true
+//! style = "title"
+//!
+//! # Where `apollo convert` puts portable copies
+//! [convert]
+//! destination = "/media/phone/Music"
+//! path_template = "$artist/$album/$track - $title"
+//!
+//! # Queue tag changes for `apollo write`, and keep genres as they are
+//! [write]
+//! deferred = true
+//! exclude = ["genre"]
+//!
+//! # Keep hand-curated genres and titles when tracks are tagged again
+//! [locks]
+//! fields = ["genre", "title"]
+//!
+//! # Maintenance while the web server runs, in seconds between runs
+//! [schedule]
+//! rescan_secs = 3600
+//! backup_secs = 86400
+//! backups_kept = 7
+//!
+//! # Keep playlist files up to date for other players, under
+//! # paths.music_directory
+//! [[playlists.exports]]
+//! playlist = "Recently Added"
+//! path = "Playlists/Recently Added.m3u8"
+//!
+//! # Export spans and metrics to an OpenTelemetry collector
+//! [telemetry]
+//! endpoint = "http://localhost:4318"
+//!
+//! [genres.aliases]
+//! Britpop = "Alternative Rock"
 //! ```
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use crate::edit::EditField;
 use crate::error::Error;
+use crate::lock::FieldLocks;
+
+mod keys;
+mod validate;
+
+pub use validate::ConfigProblem;
 
 /// Default configuration file name.
 const CONFIG_FILE_NAME: &str = "config.toml";
@@ -58,6 +154,15 @@ const DEFAULT_WEB_PORT: u16 = 8337;
 /// Default web server host.
 const DEFAULT_WEB_HOST: &str = "127.0.0.1";
 
+/// Default number of items in a page of a list from the web API.
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Default largest number of items in a page of a list from the web API.
+const DEFAULT_MAX_PAGE_SIZE: u32 = 500;
+
+/// Default port of the MPD protocol listener, the port MPD itself uses.
+const DEFAULT_MPD_PORT: u16 = 6600;
+
 /// Apollo configuration.
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(default)]
@@ -72,10 +177,44 @@ pub struct Config {
     pub musicbrainz: MusicBrainzConfig,
     /// [AcoustID](https://acoustid.org/) settings.
     pub acoustid: AcoustIdConfig,
+    /// [Discogs](https://discogs.com/) settings.
+    pub discogs: DiscogsConfig,
+    /// [fanart.tv](https://fanart.tv/) settings.
+    pub fanart: FanartConfig,
+    /// [Last.fm](https://www.last.fm/) scrobbling settings.
+    pub lastfm: LastFmConfig,
+    /// [ListenBrainz](https://listenbrainz.org/) scrobbling settings.
+    pub listenbrainz: ListenBrainzConfig,
+    /// [Spotify](https://spotify.com/) playlist import settings.
+    pub spotify: SpotifyConfig,
     /// Web server settings.
     pub web: WebConfig,
+    /// DLNA media server settings.
+    pub dlna: DlnaConfig,
+    /// MPD protocol listener settings.
+    pub mpd: MpdConfig,
     /// Plugin settings.
     pub plugins: PluginsConfig,
+    /// Genre normalization settings.
+    pub genres: GenreConfig,
+    /// Featured artist normalization settings.
+    pub featured: FeaturedConfig,
+    /// Folder watching settings.
+    pub watch: WatchConfig,
+    /// Format conversion settings.
+    pub convert: ConvertConfig,
+    /// Tag writing settings.
+    pub write: WriteConfig,
+    /// Fields that automatic tagging leaves alone.
+    pub locks: LocksConfig,
+    /// Playlist file settings.
+    pub playlists: PlaylistsConfig,
+    /// Maintenance tasks run while the web server runs.
+    pub schedule: ScheduleConfig,
+    /// OpenTelemetry export settings.
+    pub telemetry: TelemetryConfig,
+    /// Device sync profiles, by name.
+    pub sync: BTreeMap<String, SyncProfile>,
 }
 
 impl Config {
@@ -108,7 +247,8 @@ impl Config {
     ///
     /// # Errors
     ///
-    /// Returns an error if the configuration file exists but cannot be read or parsed.
+    /// Returns an error if the configuration file exists but cannot be read,
+    /// parsed, or [validated](Self::validate).
     pub fn load() -> Result<Self, Error> {
         if let Some(path) = Self::default_path()
             && path.exists()
@@ -122,8 +262,20 @@ impl Config {
     ///
     /// # Errors
     ///
-    /// Returns an error if the file cannot be read or parsed.
+    /// Returns an error if the file cannot be read, parsed, or
+    /// [validated](Self::validate).
     pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let config = Self::read_from(path)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read configuration from a specific path without validating it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn read_from(path: &Path) -> Result<Self, Error> {
         let content = std::fs::read_to_string(path).map_err(|e| Error::Config {
             message: format!("Failed to read config file: {e}"),
         })?;
@@ -202,11 +354,59 @@ impl Config {
         self.paths.music_directory.as_ref().map(|p| expand_tilde(p))
     }
 
+    /// Get where an exported playlist file is written, expanding `~` to
+    /// home directory.
+    ///
+    /// Relative paths are under the music directory; returns `None` for
+    /// those if no music directory is set.
+    #[must_use]
+    pub fn playlist_export_path(&self, export: &PlaylistExport) -> Option<PathBuf> {
+        let path = expand_tilde(&export.path);
+        if path.is_absolute() {
+            Some(path)
+        } else {
+            self.music_directory().map(|directory| directory.join(path))
+        }
+    }
+
     /// Get the plugins directory path, expanding `~` to home directory.
     #[must_use]
     pub fn plugins_directory(&self) -> PathBuf {
         expand_tilde(&self.plugins.directory)
     }
+
+    /// Get the watched directory paths, expanding `~` to home directory.
+    #[must_use]
+    pub fn watch_directories(&self) -> Vec<PathBuf> {
+        self.watch
+            .directories
+            .iter()
+            .map(|p| expand_tilde(p))
+            .collect()
+    }
+
+    /// Get the directory for scheduled backups, expanding `~` to home
+    /// directory.
+    ///
+    /// Backups go next to the library unless a directory is configured.
+    #[must_use]
+    pub fn backup_directory(&self) -> PathBuf {
+        self.schedule.backup_directory.as_ref().map_or_else(
+            || {
+                self.library_path()
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default()
+            },
+            |p| expand_tilde(p),
+        )
+    }
+
+    /// Get the directory for converted copies, expanding `~` to home directory.
+    #[must_use]
+    pub fn convert_destination(&self) -> Option<PathBuf> {
+        self.convert.destination.as_ref().map(|p| expand_tilde(p))
+    }
 }
 
 /// Library configuration.
@@ -230,7 +430,11 @@ impl Default for LibraryConfig {
 #[serde(default)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct ImportConfig {
-    /// Move files instead of copying during import.
+    /// Copy imported files into `paths.music_directory`, laid out by the
+    /// path template, and import the copies.
+    pub copy_files: bool,
+    /// Move imported files into `paths.music_directory` instead of copying
+    /// them. Implies `copy_files`.
     pub move_files: bool,
     /// Write metadata tags to imported files.
     pub write_tags: bool,
@@ -240,16 +444,73 @@ pub struct ImportConfig {
     pub auto_create_albums: bool,
     /// Compute and store file hashes for deduplication.
     pub compute_hashes: bool,
+    /// Minimum number of distinct track artists for an album to be detected
+    /// as a compilation (0 disables detection by artist count).
+    pub compilation_min_artists: usize,
+    /// Settings for paths that need different import behavior.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<ImportProfile>,
+}
+
+impl ImportConfig {
+    /// Check if imported files are copied or moved into the music directory.
+    #[must_use]
+    pub const fn organizes_files(&self) -> bool {
+        self.copy_files || self.move_files
+    }
+
+    /// Get the first profile that matches a path.
+    #[must_use]
+    pub fn profile_for(&self, path: &Path) -> Option<&ImportProfile> {
+        self.profiles.iter().find(|profile| profile.matches(path))
+    }
+}
+
+/// Import settings for files under matching paths.
+///
+/// Unset settings fall back to the global configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImportProfile {
+    /// Glob pattern for the paths this profile applies to.
+    ///
+    /// `*` matches within a path component, `**` matches across components,
+    /// and `?` matches a single character. A trailing `/**` also matches the
+    /// directory itself.
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Template for organizing files, instead of `paths.path_template`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_template: Option<String>,
+    /// Fetch and apply tags from [MusicBrainz](https://musicbrainz.org/),
+    /// instead of `musicbrainz.auto_tag`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_tag: Option<bool>,
+    /// Copy album art, instead of `import.copy_album_art`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy_album_art: Option<bool>,
+}
+
+impl ImportProfile {
+    /// Check if the profile applies to a path.
+    #[must_use]
+    pub fn matches(&self, path: &Path) -> bool {
+        let pattern = expand_tilde(Path::new(&self.pattern));
+        glob_to_regex(&pattern.to_string_lossy())
+            .is_ok_and(|regex| regex.is_match(&path.to_string_lossy()))
+    }
 }
 
 impl Default for ImportConfig {
     fn default() -> Self {
         Self {
+            copy_files: false,
             move_files: false,
             write_tags: true,
             copy_album_art: true,
             auto_create_albums: true,
             compute_hashes: true,
+            compilation_min_artists: 4,
+            profiles: Vec::new(),
         }
     }
 }
@@ -324,6 +585,79 @@ impl Default for AcoustIdConfig {
     }
 }
 
+/// [Discogs](https://discogs.com/) configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DiscogsConfig {
+    /// Personal access token (get one at <https://www.discogs.com/settings/developers>);
+    /// [Discogs](https://discogs.com/) is not used without one.
+    pub token: String,
+}
+
+/// [fanart.tv](https://fanart.tv/) configuration.
+///
+/// Artist images are looked up on fanart.tv before Wikidata once an API key
+/// is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FanartConfig {
+    /// Personal API key (get one at <https://fanart.tv/get-an-api-key/>).
+    pub api_key: String,
+}
+
+/// [Last.fm](https://www.last.fm/) scrobbling configuration.
+///
+/// Recorded plays are scrobbled to the account of the session key once all
+/// three values are set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LastFmConfig {
+    /// API key (get one at <https://www.last.fm/api/account/create>).
+    pub api_key: String,
+    /// Shared secret of the API key.
+    pub api_secret: String,
+    /// Session key of the account to scrobble to.
+    pub session_key: String,
+}
+
+impl LastFmConfig {
+    /// Check if plays are scrobbled to [Last.fm](https://www.last.fm/).
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        !self.api_key.is_empty() && !self.api_secret.is_empty() && !self.session_key.is_empty()
+    }
+}
+
+/// [ListenBrainz](https://listenbrainz.org/) scrobbling configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ListenBrainzConfig {
+    /// User token (find it at <https://listenbrainz.org/settings/>);
+    /// plays are not submitted without one.
+    pub token: String,
+}
+
+/// [Spotify](https://spotify.com/) configuration.
+///
+/// Spotify playlists can only be read with the credentials of an app
+/// registered at <https://developer.spotify.com/dashboard>.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SpotifyConfig {
+    /// Client ID of the app.
+    pub client_id: String,
+    /// Client secret of the app.
+    pub client_secret: String,
+}
+
+impl SpotifyConfig {
+    /// Check if Spotify playlists can be read.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        !self.client_id.is_empty() && !self.client_secret.is_empty()
+    }
+}
+
 /// Web server configuration.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(default)]
@@ -334,6 +668,16 @@ pub struct WebConfig {
     pub port: u16,
     /// Enable Swagger UI.
     pub swagger_ui: bool,
+    /// Serve only the endpoints that browse the library, leaving out those
+    /// that change it, such as imports and playlist edits.
+    pub read_only: bool,
+    /// Number of items in a page of a list, when a client asks for none.
+    pub default_page_size: u32,
+    /// Largest number of items in a page of a list. Clients that ask for
+    /// more get this many.
+    pub max_page_size: u32,
+    /// Logging of requests.
+    pub logging: WebLoggingConfig,
 }
 
 impl Default for WebConfig {
@@ -342,6 +686,79 @@ impl Default for WebConfig {
             host: DEFAULT_WEB_HOST.to_string(),
             port: DEFAULT_WEB_PORT,
             swagger_ui: true,
+            read_only: false,
+            default_page_size: DEFAULT_PAGE_SIZE,
+            max_page_size: DEFAULT_MAX_PAGE_SIZE,
+            logging: WebLoggingConfig::default(),
+        }
+    }
+}
+
+/// Request logging configuration.
+///
+/// Every request gets an ID, which is sent back in a response header and in
+/// error responses, so that a problem a user reports can be found in the
+/// server's log.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct WebLoggingConfig {
+    /// Log the method, path, status, and duration of each request.
+    pub access_log: bool,
+    /// Header that carries the request ID. An ID that a proxy in front of
+    /// the server already set in it is kept.
+    pub request_id_header: String,
+}
+
+impl Default for WebLoggingConfig {
+    fn default() -> Self {
+        Self {
+            access_log: true,
+            request_id_header: "x-request-id".to_string(),
+        }
+    }
+}
+
+/// DLNA media server configuration.
+///
+/// The media server runs alongside the web server, so that smart TVs and
+/// network receivers can browse and play the library.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DlnaConfig {
+    /// Announce the library on the local network while the web server runs.
+    pub enabled: bool,
+    /// Name that players show for the library.
+    pub name: String,
+}
+
+impl Default for DlnaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: "Apollo".to_string(),
+        }
+    }
+}
+
+/// MPD protocol listener configuration.
+///
+/// The listener runs alongside the web server and speaks the protocol of
+/// the Music Player Daemon, so that MPD clients can browse the library and
+/// manage the server's play queue.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct MpdConfig {
+    /// Accept MPD clients while the web server runs.
+    pub enabled: bool,
+    /// Port to listen on, on the same host as the web server.
+    pub port: u16,
+}
+
+impl Default for MpdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_MPD_PORT,
         }
     }
 }
@@ -353,6 +770,8 @@ pub struct PluginsConfig {
     /// Directory containing Lua plugins.
     pub directory: PathBuf,
     /// List of enabled plugins (by name, without .lua extension).
+    ///
+    /// All plugins in the directory are enabled when the list is empty.
     pub enabled: Vec<String>,
 }
 
@@ -370,6 +789,348 @@ impl Default for PluginsConfig {
     }
 }
 
+impl PluginsConfig {
+    /// Check if a plugin is enabled, by its file name without `.lua`.
+    #[must_use]
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.is_empty() || self.enabled.iter().any(|e| e == name)
+    }
+}
+
+/// Genre normalization configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct GenreConfig {
+    /// Normalize genres of imported tracks.
+    pub normalize_on_import: bool,
+    /// Additional genre spellings, mapped to the genre they stand for.
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// Featured artist normalization configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FeaturedConfig {
+    /// Normalize featured artists of imported tracks.
+    pub normalize_on_import: bool,
+    /// Where featured artists are credited.
+    pub style: FeaturedStyle,
+    /// Word that introduces featured artists, such as "feat." or "ft.".
+    pub keyword: String,
+}
+
+impl Default for FeaturedConfig {
+    fn default() -> Self {
+        Self {
+            normalize_on_import: false,
+            style: FeaturedStyle::default(),
+            keyword: "feat.".to_string(),
+        }
+    }
+}
+
+/// Where featured artists are credited.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeaturedStyle {
+    /// In the title, as in "Title (feat. Guest)", leaving the main artist
+    /// in the artist tag.
+    #[default]
+    Title,
+    /// In the artist tag, as in "Artist feat. Guest".
+    Artist,
+}
+
+/// Folder watching configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Watch the directories and import new files while the web server runs.
+    pub enabled: bool,
+    /// Directories to watch for new files.
+    pub directories: Vec<PathBuf>,
+    /// Seconds to wait after the last change before importing.
+    pub debounce_secs: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directories: Vec::new(),
+            debounce_secs: 5,
+        }
+    }
+}
+
+/// Format conversion configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ConvertConfig {
+    /// The `ffmpeg` program used to encode files.
+    pub ffmpeg: PathBuf,
+    /// Default directory for converted copies.
+    pub destination: Option<PathBuf>,
+    /// Path template for converted copies (default: `paths.path_template`).
+    pub path_template: Option<String>,
+}
+
+impl Default for ConvertConfig {
+    fn default() -> Self {
+        Self {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            destination: None,
+            path_template: None,
+        }
+    }
+}
+
+/// Tag writing configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct WriteConfig {
+    /// Queue tag changes in the library instead of writing them to the
+    /// files right away. Queued changes are written by `apollo write`.
+    pub deferred: bool,
+    /// Seconds between writing queued changes while the web server runs
+    /// (0 leaves them for `apollo write`).
+    pub interval_secs: u64,
+    /// Fields to write to files; all fields if empty.
+    pub fields: Vec<String>,
+    /// Fields never to write to files.
+    pub exclude: Vec<String>,
+}
+
+impl WriteConfig {
+    /// Get the fields to write to files.
+    ///
+    /// Unknown field names are left out; they are reported when the
+    /// configuration is loaded.
+    #[must_use]
+    pub fn written_fields(&self) -> Vec<EditField> {
+        let parse = |names: &[String]| -> Vec<EditField> {
+            names.iter().filter_map(|name| name.parse().ok()).collect()
+        };
+        let included = parse(&self.fields);
+        let excluded = parse(&self.exclude);
+        EditField::ALL
+            .iter()
+            .copied()
+            .filter(|field| self.fields.is_empty() || included.contains(field))
+            .filter(|field| !excluded.contains(field))
+            .collect()
+    }
+}
+
+impl Default for WriteConfig {
+    fn default() -> Self {
+        Self {
+            deferred: false,
+            interval_secs: 300,
+            fields: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+/// Field lock configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LocksConfig {
+    /// Fields that lookups, plugins and bulk edits never change, on any
+    /// track.
+    pub fields: Vec<String>,
+}
+
+impl LocksConfig {
+    /// Get the locked fields.
+    ///
+    /// Unknown field names are left out; they are reported when the
+    /// configuration is loaded.
+    #[must_use]
+    pub fn locked_fields(&self) -> FieldLocks {
+        FieldLocks::new(self.fields.iter().filter_map(|name| name.parse().ok()))
+    }
+}
+
+/// Playlist file configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PlaylistsConfig {
+    /// Playlists kept up to date as `.m3u8` files while the web server
+    /// runs, for hardware players and other apps.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exports: Vec<PlaylistExport>,
+}
+
+/// A playlist that is written to a file whenever its tracks change.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PlaylistExport {
+    /// Name of the playlist.
+    pub playlist: String,
+    /// Where to write the file; relative paths are under
+    /// `paths.music_directory`.
+    pub path: PathBuf,
+    /// Write track paths relative to the file, rather than absolute.
+    pub relative_paths: bool,
+}
+
+impl Default for PlaylistExport {
+    fn default() -> Self {
+        Self {
+            playlist: String::new(),
+            path: PathBuf::new(),
+            relative_paths: true,
+        }
+    }
+}
+
+/// Maintenance tasks run while the web server runs.
+///
+/// Each task runs every so many seconds, starting one interval after the
+/// server starts; tasks with an interval of 0 do not run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Seconds between imports of new files in the watched folders.
+    pub rescan_secs: u64,
+    /// Seconds between evaluating smart playlists ahead of requests.
+    pub playlists_secs: u64,
+    /// Seconds between dropping expired metadata lookups.
+    pub prune_cache_secs: u64,
+    /// Seconds between downloading covers for albums without one.
+    pub fetch_art_secs: u64,
+    /// Seconds between backups of the library.
+    pub backup_secs: u64,
+    /// Directory for backups (default: next to the library).
+    pub backup_directory: Option<PathBuf>,
+    /// Number of scheduled backups to keep; all of them if 0.
+    pub backups_kept: usize,
+}
+
+/// OpenTelemetry export configuration.
+///
+/// Spans of imports, library queries, and metadata lookups are exported
+/// with counters of imported tracks and requests made, so that a slow
+/// import can be traced to the step that holds it up.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Base URL of an OTLP/HTTP collector, such as
+    /// `http://localhost:4318`; nothing is exported if empty.
+    pub endpoint: String,
+    /// Service name that spans and metrics are reported under.
+    pub service_name: String,
+    /// Export metrics along with spans.
+    pub metrics: bool,
+}
+
+impl TelemetryConfig {
+    /// Check if spans and metrics are exported.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        !self.endpoint.is_empty()
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            service_name: "apollo".to_string(),
+            metrics: true,
+        }
+    }
+}
+
+/// Format that synced tracks are converted to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncFormat {
+    /// MP3.
+    Mp3,
+    /// Ogg Vorbis.
+    Ogg,
+    /// Opus.
+    Opus,
+    /// AAC in an `.m4a` file.
+    Aac,
+    /// FLAC.
+    Flac,
+}
+
+/// A selection of tracks to keep in sync on an external device.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SyncProfile {
+    /// Directory to sync to, such as a mounted USB drive.
+    pub destination: PathBuf,
+    /// Query selecting tracks to include.
+    pub query: Option<String>,
+    /// Playlists to include, which are also written to the destination.
+    ///
+    /// All tracks are synced if neither a query nor playlists are given.
+    pub playlists: Vec<String>,
+    /// Format to convert tracks to; tracks are copied as they are if unset.
+    pub format: Option<SyncFormat>,
+    /// Bitrate in kbit/s for lossy formats.
+    pub bitrate: u32,
+    /// Only convert lossless tracks, and copy lossy ones as they are.
+    pub lossless_only: bool,
+    /// Path template (default: `paths.path_template`).
+    pub path_template: Option<String>,
+    /// Delete audio files from the destination that are no longer synced.
+    pub delete: bool,
+}
+
+impl SyncProfile {
+    /// Get the destination directory, expanding `~` to home directory.
+    #[must_use]
+    pub fn destination_path(&self) -> PathBuf {
+        expand_tilde(&self.destination)
+    }
+}
+
+impl Default for SyncProfile {
+    fn default() -> Self {
+        Self {
+            destination: PathBuf::new(),
+            query: None,
+            playlists: Vec::new(),
+            format: None,
+            bitrate: 192,
+            lossless_only: false,
+            path_template: None,
+            delete: false,
+        }
+    }
+}
+
+/// Convert a path glob pattern to an anchored regular expression.
+fn glob_to_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    let pattern = pattern.trim_end_matches('/');
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            _ => regex.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    // `dir/**` also matches `dir` itself
+    if let Some(base) = regex.strip_suffix("/.*") {
+        regex = format!("{base}(/.*)?");
+    }
+    regex.push('$');
+    regex::Regex::new(&regex)
+}
+
 /// Expand `~` to the home directory in a path.
 fn expand_tilde(path: &Path) -> PathBuf {
     let path_str = path.to_string_lossy();
@@ -397,6 +1158,13 @@ mod tests {
         assert!(config.acoustid.enabled);
         assert!(config.acoustid.api_key.is_empty());
         assert_eq!(config.web.port, 8337);
+        assert!(!config.mpd.enabled);
+        assert_eq!(config.mpd.port, 6600);
+        assert!(!config.lastfm.is_enabled());
+        assert!(config.listenbrainz.token.is_empty());
+        assert!(!config.spotify.is_enabled());
+        assert!(!config.telemetry.is_enabled());
+        assert_eq!(config.telemetry.service_name, "apollo");
     }
 
     #[test]
@@ -428,6 +1196,51 @@ enabled = true
         assert_eq!(config.acoustid.api_key, "my-api-key");
     }
 
+    #[test]
+    fn test_import_profiles() {
+        let toml = r#"
+[[import.profiles]]
+match = "/music/audiobooks/**"
+path_template = "Audiobooks/$album/$track"
+auto_tag = false
+
+[[import.profiles]]
+match = "/music/*/live"
+copy_album_art = false
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.import.profiles.len(), 2);
+
+        let profile = config
+            .import
+            .profile_for(Path::new("/music/audiobooks/Dune/01.mp3"))
+            .unwrap();
+        assert_eq!(
+            profile.path_template.as_deref(),
+            Some("Audiobooks/$album/$track")
+        );
+        assert_eq!(profile.auto_tag, Some(false));
+        assert_eq!(profile.copy_album_art, None);
+
+        assert!(profile.matches(Path::new("/music/audiobooks")));
+        assert!(!profile.matches(Path::new("/music/audiobooks-old")));
+
+        let live = &config.import.profiles[1];
+        assert!(live.matches(Path::new("/music/Queen/live")));
+        assert!(!live.matches(Path::new("/music/Queen/1986/live")));
+        assert!(
+            config
+                .import
+                .profile_for(Path::new("/music/Queen"))
+                .is_none()
+        );
+
+        assert_eq!(
+            Config::from_toml(&config.to_toml().unwrap()).unwrap(),
+            config
+        );
+    }
+
     #[test]
     fn test_expand_tilde() {
         let home = dirs::home_dir();
@@ -451,10 +1264,10 @@ enabled = true
     #[test]
     fn test_partial_config() {
         // Only specify some values, rest should use defaults
-        let toml = r#"
+        let toml = r"
 [web]
 port = 3000
-"#;
+";
         let config = Config::from_toml(toml).unwrap();
         assert_eq!(config.web.port, 3000);
         assert_eq!(config.web.host, DEFAULT_WEB_HOST); // Default
@@ -472,6 +1285,136 @@ enabled = ["clean_tags", "skip_hidden", "custom_plugin"]
         assert_eq!(config.plugins.directory, PathBuf::from("~/my-plugins"));
         assert_eq!(config.plugins.enabled.len(), 3);
         assert!(config.plugins.enabled.contains(&"clean_tags".to_string()));
+        assert!(config.plugins.is_enabled("skip_hidden"));
+        assert!(!config.plugins.is_enabled("other"));
+        assert!(PluginsConfig::default().is_enabled("other"));
+    }
+
+    #[test]
+    fn test_convert_config() {
+        let config = Config::default();
+        assert_eq!(config.convert.ffmpeg, PathBuf::from("ffmpeg"));
+        assert_eq!(config.convert_destination(), None);
+
+        let toml = r#"
+[convert]
+ffmpeg = "/opt/ffmpeg/bin/ffmpeg"
+destination = "/media/phone/Music"
+path_template = "$artist - $title"
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(
+            config.convert_destination(),
+            Some(PathBuf::from("/media/phone/Music"))
+        );
+        assert_eq!(
+            config.convert.path_template.as_deref(),
+            Some("$artist - $title")
+        );
+    }
+
+    #[test]
+    fn test_write_config() {
+        let config = Config::default();
+        assert!(!config.write.deferred);
+        assert_eq!(config.write.written_fields(), EditField::ALL);
+
+        let toml = r#"
+[write]
+deferred = true
+fields = ["title", "artist", "album", "genre"]
+exclude = ["Genre"]
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config.write.deferred);
+        assert_eq!(
+            config.write.written_fields(),
+            vec![EditField::Title, EditField::Artist, EditField::Album]
+        );
+    }
+
+    #[test]
+    fn test_featured_config() {
+        let config = Config::default();
+        assert!(!config.featured.normalize_on_import);
+        assert_eq!(config.featured.style, FeaturedStyle::Title);
+        assert_eq!(config.featured.keyword, "feat.");
+
+        let toml = r#"
+[featured]
+normalize_on_import = true
+style = "artist"
+keyword = "ft."
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert!(config.featured.normalize_on_import);
+        assert_eq!(config.featured.style, FeaturedStyle::Artist);
+        assert_eq!(config.featured.keyword, "ft.");
+    }
+
+    #[test]
+    fn test_schedule_config() {
+        let config = Config::default();
+        assert_eq!(config.schedule.backup_secs, 0);
+        assert_eq!(
+            config.backup_directory(),
+            config.library_path().parent().unwrap()
+        );
+
+        let toml = r#"
+[library]
+path = "/data/apollo.db"
+
+[schedule]
+rescan_secs = 3600
+backup_secs = 86400
+backup_directory = "/backups"
+backups_kept = 7
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.schedule.rescan_secs, 3600);
+        assert_eq!(config.schedule.fetch_art_secs, 0);
+        assert_eq!(config.schedule.backups_kept, 7);
+        assert_eq!(config.backup_directory(), PathBuf::from("/backups"));
+    }
+
+    #[test]
+    fn test_sync_profiles() {
+        let toml = r#"
+[sync.phone]
+destination = "/media/phone/Music"
+playlists = ["Road Trip", "Running"]
+format = "opus"
+bitrate = 128
+
+[sync.car]
+destination = "/media/usb"
+query = "genre:Rock"
+delete = true
+"#;
+        let config = Config::from_toml(toml).unwrap();
+        assert_eq!(config.sync.len(), 2);
+
+        let phone = &config.sync["phone"];
+        assert_eq!(
+            phone.destination_path(),
+            PathBuf::from("/media/phone/Music")
+        );
+        assert_eq!(phone.playlists, vec!["Road Trip", "Running"]);
+        assert_eq!(phone.format, Some(SyncFormat::Opus));
+        assert_eq!(phone.bitrate, 128);
+        assert!(!phone.delete);
+
+        let car = &config.sync["car"];
+        assert_eq!(car.query.as_deref(), Some("genre:Rock"));
+        assert_eq!(car.format, None);
+        assert_eq!(car.bitrate, 192);
+        assert!(car.delete);
+
+        assert_eq!(
+            Config::from_toml(&config.to_toml().unwrap()).unwrap(),
+            config
+        );
     }
 
     #[test]
@@ -491,16 +1434,19 @@ enabled = ["clean_tags", "skip_hidden", "custom_plugin"]
 
     #[test]
     fn test_import_config() {
-        let toml = r#"
+        let toml = r"
 [import]
 move_files = true
 write_tags = false
 copy_album_art = false
-"#;
+";
         let config = Config::from_toml(toml).unwrap();
         assert!(config.import.move_files);
+        assert!(!config.import.copy_files); // Default
+        assert!(config.import.organizes_files());
         assert!(!config.import.write_tags);
         assert!(!config.import.copy_album_art);
         assert!(config.import.auto_create_albums); // Default
+        assert!(!Config::default().import.organizes_files());
     }
 }
@@ -0,0 +1,346 @@
+//! Access to configuration values by key path, such as `web.port`.
+//!
+//! Values are read and written through the TOML form of the configuration,
+//! so every setting can be reached by its key without being listed here.
+//! Optional settings that are not set are left out of that form, so a key
+//! that is not found is checked by trying to set it.
+
+use toml::{Table, Value};
+
+use super::Config;
+use crate::error::Error;
+
+impl Config {
+    /// Get a configuration value by key path.
+    ///
+    /// Returns `None` for optional settings that are not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not a configuration setting.
+    pub fn get_value(&self, key: &str) -> Result<Option<Value>, Error> {
+        let table = self.to_table()?;
+        if let Some(value) = lookup(&table, key)? {
+            return Ok(Some(value.clone()));
+        }
+        if self.accepts_key(key)? {
+            Ok(None)
+        } else {
+            Err(unknown_key(key))
+        }
+    }
+
+    /// Set a configuration value by key path.
+    ///
+    /// The value is parsed as the type of the setting: `true`/`false` (or
+    /// `yes`/`no`, `on`/`off`, `1`/`0`) for switches, numbers, and
+    /// comma-separated items for lists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not a configuration setting, or the
+    /// value does not fit it.
+    pub fn set_value(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let table = self.to_table()?;
+        let candidates = if let Some(current) = lookup(&table, key)? {
+            vec![parse_like(current, value, key)?]
+        } else {
+            // An unset setting has no type to go by, so try each kind
+            let mut candidates = vec![Value::String(value.to_string())];
+            if let Ok(literal) = value.parse::<Value>() {
+                candidates.push(literal);
+            }
+            candidates.push(Value::Array(
+                split_list(value)
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+            ));
+            candidates
+        };
+
+        let mut mismatch = None;
+        for candidate in candidates {
+            let mut table = table.clone();
+            insert(&mut table, key, candidate)?;
+            match Self::from_table(table) {
+                Ok(config) if lookup(&config.to_table()?, key)?.is_some() => {
+                    *self = config;
+                    return Ok(());
+                }
+                Ok(_) => {}
+                Err(e) => mismatch = mismatch.or(Some(e)),
+            }
+        }
+        Err(mismatch.map_or_else(
+            || unknown_key(key),
+            |e| Error::Config {
+                message: format!("Invalid value for {key}: {e}"),
+            },
+        ))
+    }
+
+    /// Reset a configuration value to its default.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not a configuration setting.
+    pub fn unset_value(&mut self, key: &str) -> Result<(), Error> {
+        let mut table = self.to_table()?;
+        if lookup(&table, key)?.is_none() {
+            return if self.accepts_key(key)? {
+                Ok(())
+            } else {
+                Err(unknown_key(key))
+            };
+        }
+
+        remove(&mut table, key);
+        *self = Self::from_table(table).map_err(|e| Error::Config {
+            message: format!("Failed to reset {key}: {e}"),
+        })?;
+        Ok(())
+    }
+
+    /// List the key paths of all settings that are set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be serialized.
+    pub fn keys(&self) -> Result<Vec<String>, Error> {
+        let mut keys = Vec::new();
+        collect_keys(&self.to_table()?, "", &mut keys);
+        Ok(keys)
+    }
+
+    /// Check if a key that is not set names a setting.
+    fn accepts_key(&self, key: &str) -> Result<bool, Error> {
+        let mut table = self.to_table()?;
+        insert(&mut table, key, Value::String(String::new()))?;
+        // A setting of another type fails to load; unknown keys are ignored
+        match Self::from_table(table) {
+            Ok(config) => Ok(lookup(&config.to_table()?, key)?.is_some()),
+            Err(_) => Ok(true),
+        }
+    }
+
+    /// Convert the configuration to a TOML table.
+    fn to_table(&self) -> Result<Table, Error> {
+        Table::try_from(self).map_err(|e| Error::Config {
+            message: format!("Failed to serialize config: {e}"),
+        })
+    }
+
+    /// Load the configuration from a TOML table.
+    fn from_table(table: Table) -> Result<Self, toml::de::Error> {
+        Value::Table(table).try_into()
+    }
+}
+
+/// Create the error for a key that is not a setting.
+fn unknown_key(key: &str) -> Error {
+    Error::Config {
+        message: format!("Unknown configuration key: {key}"),
+    }
+}
+
+/// Find a value by key path.
+///
+/// Fails if the path runs through a value that is not a table.
+fn lookup<'a>(table: &'a Table, key: &str) -> Result<Option<&'a Value>, Error> {
+    let mut parts = key.split('.');
+    let mut value = parts.next().and_then(|part| table.get(part));
+    for part in parts {
+        value = match value {
+            Some(Value::Table(table)) => table.get(part),
+            Some(_) => return Err(unknown_key(key)),
+            None => return Ok(None),
+        };
+    }
+    Ok(value)
+}
+
+/// Insert a value by key path, creating tables along the way.
+fn insert(table: &mut Table, key: &str, value: Value) -> Result<(), Error> {
+    let (parents, name) = key.rsplit_once('.').map_or(("", key), |(p, n)| (p, n));
+    let mut table = table;
+    for part in parents.split('.').filter(|part| !part.is_empty()) {
+        let entry = table
+            .entry(part)
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = entry.as_table_mut().ok_or_else(|| unknown_key(key))?;
+    }
+    table.insert(name.to_string(), value);
+    Ok(())
+}
+
+/// Remove a value by key path.
+fn remove(table: &mut Table, key: &str) {
+    match key.split_once('.') {
+        Some((part, rest)) => {
+            if let Some(Value::Table(table)) = table.get_mut(part) {
+                remove(table, rest);
+            }
+        }
+        None => {
+            table.remove(key);
+        }
+    }
+}
+
+/// Collect the key paths of all values that are not tables.
+fn collect_keys(table: &Table, prefix: &str, keys: &mut Vec<String>) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}.{name}")
+        };
+        match value {
+            Value::Table(table) => collect_keys(table, &key, keys),
+            _ => keys.push(key),
+        }
+    }
+}
+
+/// Parse a value as the same type as the current one.
+fn parse_like(current: &Value, value: &str, key: &str) -> Result<Value, Error> {
+    let invalid = |kind: &str| Error::Config {
+        message: format!("Invalid value for {key}: expected {kind}, got {value:?}"),
+    };
+    Ok(match current {
+        Value::String(_) => Value::String(value.to_string()),
+        Value::Boolean(_) => {
+            Value::Boolean(parse_bool(value).ok_or_else(|| invalid("true or false"))?)
+        }
+        Value::Integer(_) => Value::Integer(
+            value
+                .trim()
+                .parse()
+                .map_err(|_| invalid("a whole number"))?,
+        ),
+        Value::Float(_) => Value::Float(value.trim().parse().map_err(|_| invalid("a number"))?),
+        Value::Array(items) => {
+            if items.iter().any(Value::is_table) {
+                return Err(Error::Config {
+                    message: format!(
+                        "{key} is a list of tables; edit the configuration file to change it"
+                    ),
+                });
+            }
+            let item = items
+                .first()
+                .cloned()
+                .unwrap_or(Value::String(String::new()));
+            Value::Array(
+                split_list(value)
+                    .map(|part| parse_like(&item, part, key))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        Value::Table(_) => {
+            return Err(Error::Config {
+                message: format!("{key} is a table; set its values one at a time"),
+            });
+        }
+        Value::Datetime(_) => value.parse().map_err(|_| invalid("a date"))?,
+    })
+}
+
+/// Split a comma-separated list, skipping empty items.
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// Parse a boolean, accepting the usual spellings.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn get_values() {
+        let config = Config::default();
+        assert_eq!(
+            config.get_value("web.port").unwrap(),
+            Some(Value::Integer(8337))
+        );
+        assert_eq!(config.get_value("paths.music_directory").unwrap(), None);
+        assert!(config.get_value("web").unwrap().unwrap().is_table());
+        assert!(config.get_value("web.nonexistent").is_err());
+        assert!(config.get_value("web.port.number").is_err());
+        assert!(config.get_value("nonexistent").is_err());
+    }
+
+    #[test]
+    fn set_values() {
+        let mut config = Config::default();
+        config.set_value("web.port", "9000").unwrap();
+        config.set_value("web.swagger_ui", "off").unwrap();
+        config
+            .set_value("plugins.enabled", "clean_tags, skip_hidden")
+            .unwrap();
+        config.set_value("paths.music_directory", "/music").unwrap();
+        config.set_value("genres.aliases.Britpop", "Rock").unwrap();
+
+        assert_eq!(config.web.port, 9000);
+        assert!(!config.web.swagger_ui);
+        assert_eq!(config.plugins.enabled, vec!["clean_tags", "skip_hidden"]);
+        assert_eq!(config.paths.music_directory, Some(PathBuf::from("/music")));
+        assert_eq!(config.genres.aliases["Britpop"], "Rock");
+
+        assert!(config.set_value("web.port", "many").is_err());
+        assert!(config.set_value("web.port", "70000").is_err());
+        assert!(config.set_value("web", "x").is_err());
+        assert!(config.set_value("web.nonexistent", "x").is_err());
+        assert_eq!(config.web.port, 9000);
+    }
+
+    #[test]
+    fn set_new_sync_profile() {
+        let mut config = Config::default();
+        config
+            .set_value("sync.car.destination", "/media/usb")
+            .unwrap();
+        config.set_value("sync.car.bitrate", "128").unwrap();
+        config.set_value("sync.car.playlists", "Road Trip").unwrap();
+        config.set_value("sync.car.format", "opus").unwrap();
+
+        let profile = &config.sync["car"];
+        assert_eq!(profile.destination, PathBuf::from("/media/usb"));
+        assert_eq!(profile.bitrate, 128);
+        assert_eq!(profile.playlists, vec!["Road Trip"]);
+        assert!(config.set_value("sync.car.format", "wma").is_err());
+    }
+
+    #[test]
+    fn unset_values() {
+        let mut config = Config::default();
+        config.set_value("web.port", "9000").unwrap();
+        config.set_value("paths.music_directory", "/music").unwrap();
+
+        config.unset_value("web.port").unwrap();
+        config.unset_value("paths.music_directory").unwrap();
+        config.unset_value("paths.music_directory").unwrap();
+        assert_eq!(config, Config::default());
+        assert!(config.unset_value("web.nonexistent").is_err());
+    }
+
+    #[test]
+    fn list_keys() {
+        let keys = Config::default().keys().unwrap();
+        assert!(keys.contains(&"web.port".to_string()));
+        assert!(keys.contains(&"plugins.enabled".to_string()));
+        assert!(!keys.contains(&"web".to_string()));
+    }
+}
@@ -0,0 +1,461 @@
+//! Validation of configuration values.
+//!
+//! Values are checked when the configuration is loaded, so that mistakes are
+//! reported together and up front, instead of one at a time when a value is
+//! first used.
+
+use std::fmt;
+use std::path::Path;
+
+use super::{
+    Config, ImportConfig, LastFmConfig, LocksConfig, MusicBrainzConfig, SpotifyConfig, SyncProfile,
+    TelemetryConfig, WatchConfig, WebConfig, WriteConfig, expand_tilde, glob_to_regex,
+};
+use crate::edit::EditField;
+use crate::error::Error;
+use crate::query::Query;
+use crate::template::PathTemplate;
+
+/// A problem with a configuration value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// Key path of the value, such as `web.port`.
+    pub key: String,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl Config {
+    /// Check that all configuration values are usable.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`] listing every problem found.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut problems = Vec::new();
+        let mut report = |key: &str, message: Option<String>| {
+            if let Some(message) = message {
+                problems.push(ConfigProblem {
+                    key: key.to_string(),
+                    message,
+                });
+            }
+        };
+
+        check_paths(self, &mut report);
+        check_import(&self.import, &mut report);
+        check_musicbrainz(&self.musicbrainz, &mut report);
+        check_lastfm(&self.lastfm, &mut report);
+        check_spotify(&self.spotify, &mut report);
+        check_web(&self.web, &mut report);
+        report(
+            "plugins.directory",
+            check_directory(&self.plugins.directory),
+        );
+        if self.featured.keyword.trim().is_empty() {
+            report("featured.keyword", Some("must not be empty".to_string()));
+        }
+        check_watch(&self.watch, &mut report);
+        if let Some(template) = &self.convert.path_template {
+            report("convert.path_template", check_template(template));
+        }
+        check_write(&self.write, &mut report);
+        check_locks(&self.locks, &mut report);
+        check_playlists(self, &mut report);
+        check_telemetry(&self.telemetry, &mut report);
+        for (name, profile) in &self.sync {
+            check_sync_profile(name, profile, &mut report);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(problems))
+        }
+    }
+}
+
+/// Check the library path and the paths music is organized into.
+fn check_paths(config: &Config, report: &mut impl FnMut(&str, Option<String>)) {
+    report("library.path", check_file(&config.library.path));
+    if let Some(directory) = &config.paths.music_directory {
+        report("paths.music_directory", check_directory(directory));
+    }
+    report(
+        "paths.path_template",
+        check_template(&config.paths.path_template),
+    );
+}
+
+/// Check the patterns and templates of the import profiles.
+fn check_import(import: &ImportConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    for (i, profile) in import.profiles.iter().enumerate() {
+        let pattern = profile.pattern.trim();
+        let message = if pattern.is_empty() {
+            Some("must not be empty".to_string())
+        } else {
+            glob_to_regex(pattern)
+                .err()
+                .map(|_| format!("invalid pattern: {pattern}"))
+        };
+        report(&format!("import.profiles[{i}].match"), message);
+        if let Some(template) = &profile.path_template {
+            report(
+                &format!("import.profiles[{i}].path_template"),
+                check_template(template),
+            );
+        }
+    }
+}
+
+/// Check the contact address sent with `MusicBrainz` requests.
+fn check_musicbrainz(
+    musicbrainz: &MusicBrainzConfig,
+    report: &mut impl FnMut(&str, Option<String>),
+) {
+    let email = &musicbrainz.contact_email;
+    if !email.is_empty() && !is_valid_email(email) {
+        report(
+            "musicbrainz.contact_email",
+            Some(format!("not a valid email address: {email}")),
+        );
+    }
+}
+
+/// Check that Last.fm is either not set up, or has all values it needs to
+/// scrobble.
+fn check_lastfm(lastfm: &LastFmConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    let values = [
+        ("api_key", &lastfm.api_key),
+        ("api_secret", &lastfm.api_secret),
+        ("session_key", &lastfm.session_key),
+    ];
+    if let Some((set, _)) = values.iter().find(|(_, value)| !value.is_empty()) {
+        for (key, value) in values {
+            if value.is_empty() {
+                report(
+                    &format!("lastfm.{key}"),
+                    Some(format!("must be set along with lastfm.{set}")),
+                );
+            }
+        }
+    }
+}
+
+/// Check that Spotify has both or neither of its credentials.
+fn check_spotify(spotify: &SpotifyConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    if spotify.client_id.is_empty() != spotify.client_secret.is_empty() {
+        let (missing, set) = if spotify.client_id.is_empty() {
+            ("client_id", "client_secret")
+        } else {
+            ("client_secret", "client_id")
+        };
+        report(
+            &format!("spotify.{missing}"),
+            Some(format!("must be set along with spotify.{set}")),
+        );
+    }
+}
+
+/// Check the address, page sizes and logging of the web server.
+fn check_web(web: &WebConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    if web.host.trim().is_empty() {
+        report("web.host", Some("must not be empty".to_string()));
+    }
+    if web.port == 0 {
+        report("web.port", Some("must be between 1 and 65535".to_string()));
+    }
+    if web.max_page_size == 0 {
+        report("web.max_page_size", Some("must be at least 1".to_string()));
+    }
+    if web.default_page_size == 0 || web.default_page_size > web.max_page_size {
+        report(
+            "web.default_page_size",
+            Some(format!(
+                "must be between 1 and web.max_page_size ({})",
+                web.max_page_size
+            )),
+        );
+    }
+    let header = &web.logging.request_id_header;
+    if header.is_empty()
+        || !header
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        report(
+            "web.logging.request_id_header",
+            Some(format!("not a valid header name: {header:?}")),
+        );
+    }
+}
+
+/// Check the watched directories and the time to wait for changes.
+fn check_watch(watch: &WatchConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    for (i, directory) in watch.directories.iter().enumerate() {
+        let path = expand_tilde(directory);
+        let message = (!path.is_dir()).then(|| format!("{} is not a directory", path.display()));
+        report(&format!("watch.directories[{i}]"), message);
+    }
+    if watch.debounce_secs == 0 {
+        report(
+            "watch.debounce_secs",
+            Some("must be at least 1".to_string()),
+        );
+    }
+}
+
+/// Check the fields that are written to files.
+fn check_write(write: &WriteConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    let fields = [("fields", &write.fields), ("exclude", &write.exclude)];
+    for (key, names) in fields {
+        for (i, name) in names.iter().enumerate() {
+            report(
+                &format!("write.{key}[{i}]"),
+                name.parse::<EditField>().err().map(|e| e.to_string()),
+            );
+        }
+    }
+}
+
+/// Check the fields that are locked against automatic tagging.
+fn check_locks(locks: &LocksConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    for (i, name) in locks.fields.iter().enumerate() {
+        report(
+            &format!("locks.fields[{i}]"),
+            name.parse::<EditField>().err().map(|e| e.to_string()),
+        );
+    }
+}
+
+/// Check the playlists that are kept written as files.
+fn check_playlists(config: &Config, report: &mut impl FnMut(&str, Option<String>)) {
+    for (i, export) in config.playlists.exports.iter().enumerate() {
+        let key = format!("playlists.exports[{i}]");
+        let message = export
+            .playlist
+            .trim()
+            .is_empty()
+            .then(|| "must not be empty".to_string());
+        report(&format!("{key}.playlist"), message);
+        let message = if !matches!(
+            export.path.extension().and_then(|ext| ext.to_str()),
+            Some("m3u8" | "m3u")
+        ) {
+            Some("must be an .m3u8 file".to_string())
+        } else if config.playlist_export_path(export).is_none() {
+            Some("is relative, but paths.music_directory is not set".to_string())
+        } else {
+            None
+        };
+        report(&format!("{key}.path"), message);
+    }
+}
+
+/// Check where traces and metrics are exported to.
+fn check_telemetry(telemetry: &TelemetryConfig, report: &mut impl FnMut(&str, Option<String>)) {
+    let endpoint = &telemetry.endpoint;
+    if !endpoint.is_empty() && !endpoint.starts_with("http://") && !endpoint.starts_with("https://")
+    {
+        report(
+            "telemetry.endpoint",
+            Some(format!("not an http:// or https:// URL: {endpoint}")),
+        );
+    }
+    if telemetry.service_name.trim().is_empty() {
+        report(
+            "telemetry.service_name",
+            Some("must not be empty".to_string()),
+        );
+    }
+}
+
+/// Check the values of a sync profile.
+fn check_sync_profile(
+    name: &str,
+    profile: &SyncProfile,
+    report: &mut impl FnMut(&str, Option<String>),
+) {
+    if profile.destination.as_os_str().is_empty() {
+        report(
+            &format!("sync.{name}.destination"),
+            Some("must not be empty".to_string()),
+        );
+    }
+    if let Some(query) = &profile.query {
+        report(
+            &format!("sync.{name}.query"),
+            Query::parse_sorted(query)
+                .err()
+                .map(|e| format!("invalid query: {e}")),
+        );
+    }
+    if profile.bitrate == 0 {
+        report(
+            &format!("sync.{name}.bitrate"),
+            Some("must be at least 1".to_string()),
+        );
+    }
+    if let Some(template) = &profile.path_template {
+        report(
+            &format!("sync.{name}.path_template"),
+            check_template(template),
+        );
+    }
+}
+
+/// Check that a template parses.
+fn check_template(template: &str) -> Option<String> {
+    PathTemplate::parse(template)
+        .err()
+        .map(|e| format!("invalid template: {e}"))
+}
+
+/// Check that a file path is not a directory and its directory can exist.
+fn check_file(path: &Path) -> Option<String> {
+    let path = expand_tilde(path);
+    if path.is_dir() {
+        return Some(format!("{} is a directory", path.display()));
+    }
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .and_then(check_directory)
+}
+
+/// Check that a directory exists or can be created.
+fn check_directory(path: &Path) -> Option<String> {
+    let path = expand_tilde(path);
+    if path.exists() {
+        return (!path.is_dir()).then(|| format!("{} is not a directory", path.display()));
+    }
+    // A missing directory can be created if its closest existing ancestor
+    // is a directory
+    let ancestor = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.exists())?;
+    (!ancestor.is_dir()).then(|| {
+        format!(
+            "cannot create {}: {} is not a directory",
+            path.display(),
+            ancestor.display()
+        )
+    })
+}
+
+/// Check that an email address looks like `name@example.com`.
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !email.chars().any(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.contains('.')
+        && domain.split('.').all(|part| !part.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ImportProfile, PlaylistExport};
+    use std::path::PathBuf;
+
+    fn problem_keys(config: &Config) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(Error::InvalidConfig(problems)) => {
+                problems.into_iter().map(|problem| problem.key).collect()
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let manifest = manifest_dir.join("Cargo.toml");
+
+        let mut config = Config::default();
+        config.library.path.clone_from(&manifest_dir);
+        config.paths.music_directory = Some(manifest.clone());
+        config.paths.path_template = "$artist/%upper{$album".to_string();
+        config.import.profiles.push(ImportProfile::default());
+        config.musicbrainz.contact_email = "apollo at example".to_string();
+        config.lastfm.api_key = "key".to_string();
+        config.spotify.client_secret = "secret".to_string();
+        config.web.port = 0;
+        config.web.default_page_size = 1000;
+        config.web.logging.request_id_header = "x request id".to_string();
+        config.plugins.directory = manifest.join("plugins");
+        config.featured.keyword = " ".to_string();
+        config.watch.directories = vec![manifest_dir.clone(), manifest];
+        config.watch.debounce_secs = 0;
+        config.write.exclude = vec!["genre".to_string(), "mood".to_string()];
+        config.locks.fields = vec!["lyrics".to_string()];
+        config.playlists.exports.push(PlaylistExport {
+            playlist: "Mix".to_string(),
+            path: PathBuf::from("Playlists/Mix.txt"),
+            ..PlaylistExport::default()
+        });
+        config.telemetry.endpoint = "localhost:4318".to_string();
+        config.sync.insert(
+            "phone".to_string(),
+            SyncProfile {
+                query: Some("sort:nosuchfield".to_string()),
+                ..SyncProfile::default()
+            },
+        );
+
+        assert_eq!(
+            problem_keys(&config),
+            vec![
+                "library.path",
+                "paths.music_directory",
+                "paths.path_template",
+                "import.profiles[0].match",
+                "musicbrainz.contact_email",
+                "lastfm.api_secret",
+                "lastfm.session_key",
+                "spotify.client_id",
+                "web.port",
+                "web.default_page_size",
+                "web.logging.request_id_header",
+                "plugins.directory",
+                "featured.keyword",
+                "watch.directories[1]",
+                "watch.debounce_secs",
+                "write.exclude[1]",
+                "locks.fields[0]",
+                "playlists.exports[0].path",
+                "telemetry.endpoint",
+                "sync.phone.destination",
+                "sync.phone.query",
+            ]
+        );
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("web.port: must be between 1 and 65535"));
+        assert!(message.contains("Cargo.toml is not a directory"));
+    }
+
+    #[test]
+    fn email_format() {
+        assert!(is_valid_email("apollo@example.com"));
+        assert!(!is_valid_email("apollo@example"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("apollo@@example.com"));
+        assert!(!is_valid_email("apollo @example.com"));
+    }
+}
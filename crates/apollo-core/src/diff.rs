@@ -0,0 +1,265 @@
+//! Field-level differences between tracks.
+//!
+//! A [`TrackDiff`] records which fields differ between two versions of a
+//! track, with the old and new values. It can be shown to the user before a
+//! change is made, applied to a track to make the change, and reverted to undo
+//! it.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::{Track, TrackDiff};
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//!
+//! let old = Track::new(
+//!     PathBuf::from("/music/track.mp3"),
+//!     "Bohemian Rapsody".to_string(),
+//!     "Queen".to_string(),
+//!     Duration::from_secs(354),
+//! );
+//! let mut new = old.clone();
+//! new.title = "Bohemian Rhapsody".to_string();
+//! new.year = Some(1975);
+//!
+//! let diff = TrackDiff::between(&old, &new);
+//! assert_eq!(diff.to_string(), "title: \"Bohemian Rapsody\" -> \"Bohemian Rhapsody\"\nyear: (none) -> 1975");
+//!
+//! let mut track = old.clone();
+//! diff.apply(&mut track).unwrap();
+//! assert_eq!(track.year, Some(1975));
+//! diff.revert(&mut track).unwrap();
+//! assert_eq!(track.year, None);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use utoipa::ToSchema;
+
+use crate::error::{Error, Result};
+use crate::metadata::Track;
+
+/// Track fields that identify or describe the library entry rather than the
+/// music, and are not compared.
+const IGNORED_FIELDS: &[&str] = &["id", "added_at", "modified_at"];
+
+/// A change to a single track field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FieldChange {
+    /// Field name, as in the track's JSON representation.
+    #[schema(example = "title")]
+    pub field: String,
+    /// Value before the change.
+    #[schema(value_type = Object)]
+    pub old: Value,
+    /// Value after the change.
+    #[schema(value_type = Object)]
+    pub new: Value,
+}
+
+/// Field-level differences between two versions of a track.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TrackDiff {
+    /// Changed fields, ordered by field name.
+    pub changes: Vec<FieldChange>,
+}
+
+impl TrackDiff {
+    /// Compute the differences from `old` to `new`.
+    ///
+    /// The track ID and timestamps are not compared.
+    #[must_use]
+    pub fn between(old: &Track, new: &Track) -> Self {
+        let old = to_fields(old);
+        let mut new = to_fields(new);
+
+        let mut changes: Vec<FieldChange> = old
+            .into_iter()
+            .filter(|(field, _)| !IGNORED_FIELDS.contains(&field.as_str()))
+            .filter_map(|(field, old)| {
+                let new = new.remove(&field).unwrap_or(Value::Null);
+                (old != new).then_some(FieldChange { field, old, new })
+            })
+            .collect();
+        changes.sort_by(|a, b| a.field.cmp(&b.field));
+        Self { changes }
+    }
+
+    /// Check if there are no differences.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Get the change to a field, if it changed.
+    #[must_use]
+    pub fn get(&self, field: &str) -> Option<&FieldChange> {
+        self.changes.iter().find(|change| change.field == field)
+    }
+
+    /// Get the diff that undoes this one.
+    #[must_use]
+    pub fn reversed(&self) -> Self {
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| FieldChange {
+                field: change.field.clone(),
+                old: change.new.clone(),
+                new: change.old.clone(),
+            })
+            .collect();
+        Self { changes }
+    }
+
+    /// Set the changed fields of a track to their new values.
+    ///
+    /// Fields that did not change are left as they are, so a diff can be
+    /// applied to a track that was modified in other ways.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a value does not fit the track field, in which
+    /// case the track is not modified.
+    pub fn apply(&self, track: &mut Track) -> Result<()> {
+        self.set_fields(track, |change| &change.new)
+    }
+
+    /// Set the changed fields of a track back to their old values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a value does not fit the track field, in which
+    /// case the track is not modified.
+    pub fn revert(&self, track: &mut Track) -> Result<()> {
+        self.set_fields(track, |change| &change.old)
+    }
+
+    fn set_fields(&self, track: &mut Track, value: impl Fn(&FieldChange) -> &Value) -> Result<()> {
+        let mut fields = to_fields(track);
+        for change in &self.changes {
+            fields.insert(change.field.clone(), value(change).clone());
+        }
+        *track = serde_json::from_value(Value::Object(fields))
+            .map_err(|e| Error::Validation(format!("cannot apply track changes: {e}")))?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            display_value(&self.old),
+            display_value(&self.new)
+        )
+    }
+}
+
+impl fmt::Display for TrackDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.changes.iter().map(ToString::to_string).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Get the fields of a track as JSON values.
+fn to_fields(track: &Track) -> Map<String, Value> {
+    match serde_json::to_value(track) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+/// Format a field value for people to read.
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "(none)".to_string(),
+        Value::Array(items) if items.is_empty() => "(none)".to_string(),
+        Value::Array(items) => items
+            .iter()
+            .map(display_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn track() -> Track {
+        Track::new(
+            PathBuf::from("/music/time.flac"),
+            "Time".to_string(),
+            "Pink Floyd".to_string(),
+            Duration::from_secs(413),
+        )
+    }
+
+    #[test]
+    fn diff_changed_fields() {
+        let old = track();
+        let mut new = old.clone();
+        new.genres = vec!["Rock".to_string(), "Progressive Rock".to_string()];
+        new.album_title = Some("The Dark Side of the Moon".to_string());
+        new.modified_at = chrono::Utc::now() + chrono::Duration::seconds(1);
+
+        let diff = TrackDiff::between(&old, &new);
+        let fields: Vec<&str> = diff.changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["album_title", "genres"]);
+        assert_eq!(
+            diff.get("genres").unwrap().to_string(),
+            "genres: (none) -> \"Rock\", \"Progressive Rock\""
+        );
+        assert!(TrackDiff::between(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn apply_and_revert() {
+        let old = track();
+        let mut new = old.clone();
+        new.title = "Time (Remastered)".to_string();
+        new.track_number = Some(4);
+        let diff = TrackDiff::between(&old, &new);
+
+        // Applying keeps unrelated changes
+        let mut edited = old.clone();
+        edited.year = Some(1973);
+        diff.apply(&mut edited).unwrap();
+        assert_eq!(edited.title, "Time (Remastered)");
+        assert_eq!(edited.track_number, Some(4));
+        assert_eq!(edited.year, Some(1973));
+
+        diff.revert(&mut edited).unwrap();
+        assert_eq!(edited.title, "Time");
+        assert_eq!(edited.track_number, None);
+
+        assert_eq!(diff.reversed().reversed(), diff);
+        assert_eq!(TrackDiff::between(&new, &old), diff.reversed());
+    }
+
+    #[test]
+    fn apply_invalid_value() {
+        let diff = TrackDiff {
+            changes: vec![FieldChange {
+                field: "track_number".to_string(),
+                old: Value::Null,
+                new: Value::String("four".to_string()),
+            }],
+        };
+        let mut edited = track();
+        let before = edited.clone();
+        assert!(diff.apply(&mut edited).is_err());
+        assert_eq!(
+            serde_json::to_value(&edited).unwrap(),
+            serde_json::to_value(&before).unwrap()
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Choosing which of a group of duplicate tracks to keep.
+//!
+//! [`KeepRule::Best`] keeps the track with the best audio quality: lossless
+//! formats first, then higher bit depth, sample rate, and bitrate. Ties go
+//! to tracks under the preferred directory (usually the music directory),
+//! then to shorter paths, which tend to be the organized copy rather than a
+//! stray download.
+//!
+//! [`KeepRule::Oldest`] keeps the track that was added to the library first,
+//! with ties broken the same way.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::duplicate::{KeepRule, choose_kept};
+//! use apollo_core::{AudioFormat, Track};
+//! use std::path::{Path, PathBuf};
+//! use std::time::Duration;
+//!
+//! let track = |path: &str, format| {
+//!     let mut track = Track::new(
+//!         PathBuf::from(path),
+//!         "Creep".to_string(),
+//!         "Radiohead".to_string(),
+//!         Duration::from_secs(238),
+//!     );
+//!     track.format = format;
+//!     track
+//! };
+//! let tracks = [
+//!     track("/downloads/creep.mp3", AudioFormat::Mp3),
+//!     track("/music/Radiohead/Pablo Honey/02 Creep.flac", AudioFormat::Flac),
+//! ];
+//!
+//! assert_eq!(choose_kept(&tracks, KeepRule::Best, Some(Path::new("/music"))), Some(1));
+//! ```
+
+use std::cmp::Reverse;
+use std::path::Path;
+
+use crate::metadata::Track;
+
+/// How to choose the track to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepRule {
+    /// Keep the track with the best audio quality.
+    Best,
+    /// Keep the track that was added first.
+    Oldest,
+}
+
+/// Choose the track to keep from a group of duplicates, by index.
+///
+/// Returns `None` if the group is empty.
+#[must_use]
+pub fn choose_kept(
+    tracks: &[Track],
+    rule: KeepRule,
+    preferred_dir: Option<&Path>,
+) -> Option<usize> {
+    let in_preferred = |track: &Track| preferred_dir.is_some_and(|dir| track.path.starts_with(dir));
+    let tie_break = |track: &Track| (in_preferred(track), Reverse(track.path.as_os_str().len()));
+
+    let kept = match rule {
+        KeepRule::Best => tracks
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, track)| (quality(track), tie_break(track))),
+        KeepRule::Oldest => tracks
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, track)| (Reverse(track.added_at), quality(track), tie_break(track))),
+    };
+    kept.map(|(index, _)| index)
+}
+
+/// Rank the audio quality of a track; higher is better.
+const fn quality(track: &Track) -> (bool, Option<u8>, Option<u32>, Option<u32>) {
+    (
+        track.format.is_lossless(),
+        track.bit_depth,
+        track.sample_rate,
+        track.bitrate,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::AudioFormat;
+    use chrono::Duration as ChronoDuration;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn track(path: &str, format: AudioFormat, bitrate: u32) -> Track {
+        let mut track = Track::new(
+            PathBuf::from(path),
+            "Creep".to_string(),
+            "Radiohead".to_string(),
+            Duration::from_secs(238),
+        );
+        track.format = format;
+        track.bitrate = Some(bitrate);
+        track
+    }
+
+    #[test]
+    fn keep_best() {
+        let tracks = [
+            track("/music/creep.mp3", AudioFormat::Mp3, 192),
+            track("/downloads/creep.mp3", AudioFormat::Mp3, 320),
+            track("/music/other/creep.mp3", AudioFormat::Mp3, 320),
+        ];
+        assert_eq!(choose_kept(&tracks, KeepRule::Best, None), Some(1));
+        assert_eq!(
+            choose_kept(&tracks, KeepRule::Best, Some(Path::new("/music"))),
+            Some(2)
+        );
+        assert_eq!(choose_kept(&[], KeepRule::Best, None), None);
+    }
+
+    #[test]
+    fn keep_oldest() {
+        let mut tracks = [
+            track("/music/a.flac", AudioFormat::Flac, 900),
+            track("/music/b.mp3", AudioFormat::Mp3, 320),
+            track("/music/c.ogg", AudioFormat::Ogg, 256),
+        ];
+        tracks[1].added_at = tracks[0].added_at - ChronoDuration::days(1);
+        tracks[2].added_at = tracks[1].added_at;
+        assert_eq!(choose_kept(&tracks, KeepRule::Oldest, None), Some(1));
+    }
+}
@@ -0,0 +1,409 @@
+//! Editing track metadata by field name.
+//!
+//! A [`TrackEdit`] is a list of fields to set or remove, as given on the
+//! command line with `--set year=1994` or `--remove genre`. Field names are
+//! the same as in queries, so `album` is the album title and `track` the
+//! track number.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::{Track, TrackEdit};
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//!
+//! let mut track = Track::new(
+//!     PathBuf::from("/music/track.mp3"),
+//!     "Bohemian Rapsody".to_string(),
+//!     "Queen".to_string(),
+//!     Duration::from_secs(354),
+//! );
+//! track.genres = vec!["Rock".to_string()];
+//!
+//! let edit = TrackEdit::new()
+//!     .set_assignment("title=Bohemian Rhapsody")
+//!     .unwrap()
+//!     .set("year", "1975")
+//!     .unwrap()
+//!     .remove("genre")
+//!     .unwrap();
+//! edit.apply(&mut track).unwrap();
+//!
+//! assert_eq!(track.title, "Bohemian Rhapsody");
+//! assert_eq!(track.year, Some(1975));
+//! assert!(track.genres.is_empty());
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::metadata::Track;
+
+/// A track field that can be edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditField {
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    Track,
+    TrackTotal,
+    Disc,
+    DiscTotal,
+    Year,
+    Genre,
+    MusicBrainzId,
+    AcoustId,
+    Isrc,
+}
+
+impl EditField {
+    /// All editable fields.
+    pub const ALL: &[Self] = &[
+        Self::Title,
+        Self::Artist,
+        Self::AlbumArtist,
+        Self::Album,
+        Self::Track,
+        Self::TrackTotal,
+        Self::Disc,
+        Self::DiscTotal,
+        Self::Year,
+        Self::Genre,
+        Self::MusicBrainzId,
+        Self::AcoustId,
+        Self::Isrc,
+    ];
+
+    /// Get the field name, as accepted by [`EditField::from_str`].
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::AlbumArtist => "album_artist",
+            Self::Album => "album",
+            Self::Track => "track",
+            Self::TrackTotal => "track_total",
+            Self::Disc => "disc",
+            Self::DiscTotal => "disc_total",
+            Self::Year => "year",
+            Self::Genre => "genre",
+            Self::MusicBrainzId => "musicbrainz_id",
+            Self::AcoustId => "acoustid",
+            Self::Isrc => "isrc",
+        }
+    }
+
+    /// Get the name of the track field in the track's JSON representation,
+    /// as in a [`TrackDiff`](crate::diff::TrackDiff).
+    #[must_use]
+    pub const fn track_field(self) -> &'static str {
+        match self {
+            Self::Album => "album_title",
+            Self::Track => "track_number",
+            Self::Disc => "disc_number",
+            Self::Genre => "genres",
+            other => other.name(),
+        }
+    }
+
+    /// Check if the field must have a value.
+    #[must_use]
+    pub const fn is_required(self) -> bool {
+        matches!(self, Self::Title | Self::Artist)
+    }
+
+    /// Check if a track has a value for the field.
+    #[must_use]
+    pub const fn is_set(self, track: &Track) -> bool {
+        match self {
+            Self::Title | Self::Artist => true,
+            Self::AlbumArtist => track.album_artist.is_some(),
+            Self::Album => track.album_title.is_some(),
+            Self::Track => track.track_number.is_some(),
+            Self::TrackTotal => track.track_total.is_some(),
+            Self::Disc => track.disc_number.is_some(),
+            Self::DiscTotal => track.disc_total.is_some(),
+            Self::Year => track.year.is_some(),
+            Self::Genre => !track.genres.is_empty(),
+            Self::MusicBrainzId => track.musicbrainz_id.is_some(),
+            Self::AcoustId => track.acoustid.is_some(),
+            Self::Isrc => track.isrc.is_some(),
+        }
+    }
+}
+
+impl FromStr for EditField {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        let field = match name.to_lowercase().as_str() {
+            "title" => Self::Title,
+            "artist" => Self::Artist,
+            "albumartist" | "album_artist" => Self::AlbumArtist,
+            "album" | "album_title" => Self::Album,
+            "track" | "tracknumber" | "track_number" => Self::Track,
+            "tracktotal" | "track_total" => Self::TrackTotal,
+            "disc" | "discnumber" | "disc_number" => Self::Disc,
+            "disctotal" | "disc_total" => Self::DiscTotal,
+            "year" => Self::Year,
+            "genre" | "genres" => Self::Genre,
+            "musicbrainz_id" | "mbid" => Self::MusicBrainzId,
+            "acoustid" => Self::AcoustId,
+            "isrc" => Self::Isrc,
+            _ => {
+                return Err(Error::Validation(format!(
+                    "unknown field: {name} (expected one of: {})",
+                    Self::ALL
+                        .iter()
+                        .map(|field| field.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        };
+        Ok(field)
+    }
+}
+
+impl fmt::Display for EditField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A change to one field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldEdit {
+    /// Set the field to a value.
+    Set { field: EditField, value: String },
+    /// Clear the field.
+    Remove { field: EditField },
+}
+
+/// A list of field changes to make to tracks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackEdit {
+    /// Changes, applied in order.
+    pub edits: Vec<FieldEdit>,
+}
+
+impl TrackEdit {
+    /// Create an edit that changes nothing.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { edits: Vec::new() }
+    }
+
+    /// Set a field to a value.
+    ///
+    /// Genres are given as a comma-separated list.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is unknown or the value is not valid
+    /// for it.
+    pub fn set(mut self, field: &str, value: &str) -> Result<Self> {
+        let field: EditField = field.trim().parse()?;
+        let value = value.trim();
+        if value.is_empty() && field.is_required() {
+            return Err(Error::Validation(format!("{field} cannot be empty")));
+        }
+        // Check the value now rather than when applying it
+        set_field(&mut placeholder_track(), field, value)?;
+        self.edits.push(FieldEdit::Set {
+            field,
+            value: value.to_string(),
+        });
+        Ok(self)
+    }
+
+    /// Set a field from a `field=value` assignment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the assignment has no `=`, or if [`Self::set`]
+    /// fails.
+    pub fn set_assignment(self, assignment: &str) -> Result<Self> {
+        let (field, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| Error::Validation(format!("expected field=value, got: {assignment}")))?;
+        self.set(field, value)
+    }
+
+    /// Clear a field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is unknown or must have a value.
+    pub fn remove(mut self, field: &str) -> Result<Self> {
+        let field: EditField = field.trim().parse()?;
+        if field.is_required() {
+            return Err(Error::Validation(format!("{field} cannot be removed")));
+        }
+        self.edits.push(FieldEdit::Remove { field });
+        Ok(self)
+    }
+
+    /// Check if the edit changes nothing.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Get the fields the edit leaves without a value, by removing them or
+    /// setting them to an empty value.
+    #[must_use]
+    pub fn cleared_fields(&self) -> Vec<EditField> {
+        let mut cleared: Vec<EditField> = Vec::new();
+        for edit in &self.edits {
+            let (field, clears) = match edit {
+                FieldEdit::Set { field, value } => (*field, value.is_empty()),
+                FieldEdit::Remove { field } => (*field, true),
+            };
+            cleared.retain(|f| *f != field);
+            if clears {
+                cleared.push(field);
+            }
+        }
+        cleared
+    }
+
+    /// Make the changes to a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a value is not valid for its field, in which case
+    /// the track is not modified.
+    pub fn apply(&self, track: &mut Track) -> Result<()> {
+        let mut edited = track.clone();
+        for edit in &self.edits {
+            match edit {
+                FieldEdit::Set { field, value } => set_field(&mut edited, *field, value)?,
+                FieldEdit::Remove { field } => remove_field(&mut edited, *field),
+            }
+        }
+        *track = edited;
+        Ok(())
+    }
+}
+
+/// A track to check values against.
+fn placeholder_track() -> Track {
+    Track::new(
+        std::path::PathBuf::new(),
+        String::new(),
+        String::new(),
+        std::time::Duration::ZERO,
+    )
+}
+
+fn set_field(track: &mut Track, field: EditField, value: &str) -> Result<()> {
+    let text = || (!value.is_empty()).then(|| value.to_string());
+    match field {
+        EditField::Title => track.title = value.to_string(),
+        EditField::Artist => track.artist = value.to_string(),
+        EditField::AlbumArtist => track.album_artist = text(),
+        EditField::Album => track.album_title = text(),
+        EditField::Track => track.track_number = parse_number(field, value)?,
+        EditField::TrackTotal => track.track_total = parse_number(field, value)?,
+        EditField::Disc => track.disc_number = parse_number(field, value)?,
+        EditField::DiscTotal => track.disc_total = parse_number(field, value)?,
+        EditField::Year => track.year = parse_number(field, value)?,
+        EditField::Genre => {
+            track.genres = value
+                .split(',')
+                .map(str::trim)
+                .filter(|genre| !genre.is_empty())
+                .map(ToString::to_string)
+                .collect();
+        }
+        EditField::MusicBrainzId => track.musicbrainz_id = text(),
+        EditField::AcoustId => track.acoustid = text(),
+        EditField::Isrc => track.isrc = text(),
+    }
+    Ok(())
+}
+
+fn remove_field(track: &mut Track, field: EditField) {
+    match field {
+        // Required fields are rejected by `TrackEdit::remove`
+        EditField::Title | EditField::Artist => {}
+        EditField::AlbumArtist => track.album_artist = None,
+        EditField::Album => track.album_title = None,
+        EditField::Track => track.track_number = None,
+        EditField::TrackTotal => track.track_total = None,
+        EditField::Disc => track.disc_number = None,
+        EditField::DiscTotal => track.disc_total = None,
+        EditField::Year => track.year = None,
+        EditField::Genre => track.genres.clear(),
+        EditField::MusicBrainzId => track.musicbrainz_id = None,
+        EditField::AcoustId => track.acoustid = None,
+        EditField::Isrc => track.isrc = None,
+    }
+}
+
+/// Parse a number, where an empty value means no value.
+fn parse_number<T: FromStr>(field: EditField, value: &str) -> Result<Option<T>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| Error::Validation(format!("invalid {field}: {value}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_remove() {
+        let mut track = placeholder_track();
+        track.title = "Creep".to_string();
+        track.album_title = Some("Pablo Honey".to_string());
+        track.genres = vec!["Rock".to_string()];
+
+        let edit = TrackEdit::new()
+            .set_assignment("Year = 1993")
+            .unwrap()
+            .set("genres", "Alternative, Grunge")
+            .unwrap()
+            .set_assignment("title=Creep (Acoustic)")
+            .unwrap()
+            .remove("album")
+            .unwrap();
+        edit.apply(&mut track).unwrap();
+
+        assert_eq!(track.title, "Creep (Acoustic)");
+        assert_eq!(track.year, Some(1993));
+        assert_eq!(track.genres, vec!["Alternative", "Grunge"]);
+        assert_eq!(track.album_title, None);
+        assert_eq!(edit.cleared_fields(), vec![EditField::Album]);
+        assert!(!EditField::Album.is_set(&track));
+        assert!(EditField::Genre.is_set(&track));
+
+        let edit = TrackEdit::new()
+            .remove("year")
+            .unwrap()
+            .set("genre", "")
+            .unwrap()
+            .set("year", "2001")
+            .unwrap();
+        assert_eq!(edit.cleared_fields(), vec![EditField::Genre]);
+    }
+
+    #[test]
+    fn invalid_edits() {
+        assert!(TrackEdit::new().set("year", "nineteen").is_err());
+        assert!(TrackEdit::new().set("track", "-1").is_err());
+        assert!(TrackEdit::new().set("bitrate", "320").is_err());
+        assert!(TrackEdit::new().set("title", " ").is_err());
+        assert!(TrackEdit::new().set_assignment("year").is_err());
+        assert!(TrackEdit::new().remove("artist").is_err());
+        assert!(TrackEdit::new().set("year", "").is_ok());
+    }
+}
@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::config::ConfigProblem;
+
 /// Core error type for Apollo operations.
 #[derive(Debug, Error)]
 pub enum Error {
@@ -13,6 +15,19 @@ pub enum Error {
     #[error("album not found: {0}")]
     AlbumNotFound(String),
 
+    /// A record was not found in the library.
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// A record conflicts with an existing one, such as a second track with
+    /// the same path.
+    #[error("duplicate: {0}")]
+    Duplicate(String),
+
+    /// The library storage backend failed.
+    #[error("storage error: {0}")]
+    Storage(String),
+
     /// Invalid query syntax.
     #[error("invalid query: {0}")]
     InvalidQuery(String),
@@ -27,6 +42,13 @@ pub enum Error {
         /// Error message describing what went wrong.
         message: String,
     },
+
+    /// Configuration values that cannot be used.
+    #[error(
+        "invalid configuration: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    InvalidConfig(Vec<ConfigProblem>),
 }
 
 /// Result type alias using the core Error type.
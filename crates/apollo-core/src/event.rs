@@ -0,0 +1,251 @@
+//! Library change events.
+//!
+//! Storage backends emit a [`LibraryEvent`] on an [`EventBus`] after every
+//! change to the library. Anything that needs to react to changes, such as
+//! the web server's event stream, plugin subscriptions, or cached smart
+//! playlist results, subscribes to the bus instead of being called directly
+//! by the code that made the change.
+//!
+//! The bus is a broadcast channel: every subscriber receives every event
+//! emitted after it subscribed. Subscribers that fall too far behind miss
+//! the oldest events and are told how many they missed.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::event::{EventBus, LibraryEvent};
+//! use apollo_core::TrackId;
+//!
+//! let bus = EventBus::new();
+//! let mut events = bus.subscribe();
+//!
+//! let track_id = TrackId::new();
+//! bus.emit(LibraryEvent::TrackAdded { track_id: track_id.clone() });
+//!
+//! let event = events.try_recv().unwrap();
+//! assert_eq!(event.name(), "track.added");
+//! assert_eq!(event.track_id(), Some(&track_id));
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::metadata::{AlbumId, TrackId};
+use crate::playlist::PlaylistId;
+
+/// Number of events a subscriber can fall behind before missing events.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// A change to the library.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LibraryEvent {
+    /// A track was added.
+    TrackAdded { track_id: TrackId },
+    /// A track's metadata was updated.
+    TrackUpdated { track_id: TrackId },
+    /// A track was removed.
+    TrackRemoved { track_id: TrackId },
+    /// A track's rating or favorite flag changed.
+    TrackRated { track_id: TrackId },
+    /// A play of a track was recorded.
+    TrackPlayed {
+        track_id: TrackId,
+        played_at: DateTime<Utc>,
+    },
+    /// An album was added.
+    AlbumAdded { album_id: AlbumId },
+    /// An album was updated.
+    AlbumUpdated { album_id: AlbumId },
+    /// An album was removed.
+    AlbumRemoved { album_id: AlbumId },
+    /// A playlist was created.
+    PlaylistCreated { playlist_id: PlaylistId },
+    /// A playlist's settings or tracks changed.
+    PlaylistChanged { playlist_id: PlaylistId },
+    /// A playlist was removed.
+    PlaylistRemoved { playlist_id: PlaylistId },
+}
+
+impl LibraryEvent {
+    /// Names of all event types, as returned by [`Self::name`].
+    pub const NAMES: &[&str] = &[
+        "track.added",
+        "track.updated",
+        "track.removed",
+        "track.rated",
+        "track.played",
+        "album.added",
+        "album.updated",
+        "album.removed",
+        "playlist.created",
+        "playlist.changed",
+        "playlist.removed",
+    ];
+
+    /// Get the name of the event type, such as `track.added`.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::TrackAdded { .. } => "track.added",
+            Self::TrackUpdated { .. } => "track.updated",
+            Self::TrackRemoved { .. } => "track.removed",
+            Self::TrackRated { .. } => "track.rated",
+            Self::TrackPlayed { .. } => "track.played",
+            Self::AlbumAdded { .. } => "album.added",
+            Self::AlbumUpdated { .. } => "album.updated",
+            Self::AlbumRemoved { .. } => "album.removed",
+            Self::PlaylistCreated { .. } => "playlist.created",
+            Self::PlaylistChanged { .. } => "playlist.changed",
+            Self::PlaylistRemoved { .. } => "playlist.removed",
+        }
+    }
+
+    /// Get the ID of the track the event is about, if any.
+    #[must_use]
+    pub const fn track_id(&self) -> Option<&TrackId> {
+        match self {
+            Self::TrackAdded { track_id }
+            | Self::TrackUpdated { track_id }
+            | Self::TrackRemoved { track_id }
+            | Self::TrackRated { track_id }
+            | Self::TrackPlayed { track_id, .. } => Some(track_id),
+            _ => None,
+        }
+    }
+
+    /// Get the ID of the playlist the event is about, if any.
+    #[must_use]
+    pub const fn playlist_id(&self) -> Option<&PlaylistId> {
+        match self {
+            Self::PlaylistCreated { playlist_id }
+            | Self::PlaylistChanged { playlist_id }
+            | Self::PlaylistRemoved { playlist_id } => Some(playlist_id),
+            _ => None,
+        }
+    }
+
+    /// Check if the event can change which tracks match a query.
+    ///
+    /// This is true for changes to tracks, albums, ratings, and play
+    /// history, which queries can all match on.
+    #[must_use]
+    pub const fn affects_queries(&self) -> bool {
+        self.playlist_id().is_none()
+    }
+}
+
+/// Receives events from an [`EventBus`].
+pub type EventReceiver = broadcast::Receiver<LibraryEvent>;
+
+/// Broadcasts library events to subscribers.
+///
+/// Cloning the bus gives another handle to the same channel, so several
+/// storage handles can share one bus.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<LibraryEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    /// Create an event bus with the default capacity.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Create an event bus that buffers up to `capacity` events per
+    /// subscriber.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Send an event to all current subscribers.
+    pub fn emit(&self, event: LibraryEvent) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribe to events emitted from now on.
+    #[must_use]
+    pub fn subscribe(&self) -> EventReceiver {
+        self.sender.subscribe()
+    }
+
+    /// Get the number of current subscribers.
+    #[must_use]
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    #[test]
+    fn emit_to_subscribers() {
+        let bus = EventBus::new();
+        bus.emit(LibraryEvent::AlbumAdded {
+            album_id: AlbumId::new(),
+        });
+
+        let shared = bus.clone();
+        let mut first = bus.subscribe();
+        let mut second = shared.subscribe();
+        assert_eq!(bus.subscriber_count(), 2);
+
+        let playlist_id = PlaylistId::new();
+        let event = LibraryEvent::PlaylistChanged {
+            playlist_id: playlist_id.clone(),
+        };
+        shared.emit(event.clone());
+
+        assert_eq!(first.try_recv().unwrap(), event);
+        assert_eq!(second.try_recv().unwrap(), event);
+        assert!(matches!(first.try_recv(), Err(TryRecvError::Empty)));
+        assert_eq!(event.playlist_id(), Some(&playlist_id));
+        assert!(!event.affects_queries());
+    }
+
+    #[test]
+    fn lagging_subscriber() {
+        let bus = EventBus::with_capacity(2);
+        let mut events = bus.subscribe();
+        for _ in 0..3 {
+            bus.emit(LibraryEvent::TrackUpdated {
+                track_id: TrackId::new(),
+            });
+        }
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Lagged(1))));
+        assert!(events.try_recv().is_ok());
+    }
+
+    #[test]
+    fn event_names_and_json() {
+        let track_id = TrackId::new();
+        let event = LibraryEvent::TrackRated {
+            track_id: track_id.clone(),
+        };
+        assert!(LibraryEvent::NAMES.contains(&event.name()));
+        assert!(event.affects_queries());
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "track_rated");
+        assert_eq!(json["track_id"], track_id.to_string());
+    }
+}
@@ -0,0 +1,496 @@
+//! Exporting tracks, albums, artists, and playlists as CSV, JSON, or JSON
+//! Lines.
+//!
+//! Fields have the same names as in the JSON output and the web API, such as
+//! `album_title` or `track_number`. Durations are exported in milliseconds,
+//! and in CSV lists like genres are joined with `; `.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::Track;
+//! use apollo_core::export::{ExportFormat, Exporter};
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//!
+//! let mut track = Track::new(
+//!     PathBuf::from("/music/track.mp3"),
+//!     "Bohemian Rhapsody".to_string(),
+//!     "Queen".to_string(),
+//!     Duration::from_secs(354),
+//! );
+//! track.year = Some(1975);
+//!
+//! let exporter = Exporter::tracks(ExportFormat::Csv, Some("title, artist, year")).unwrap();
+//! assert_eq!(
+//!     exporter.export(&[track]).unwrap(),
+//!     "title,artist,year\nBohemian Rhapsody,Queen,1975\n"
+//! );
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// Track fields that can be exported.
+pub const TRACK_FIELDS: &[&str] = &[
+    "id",
+    "path",
+    "title",
+    "artist",
+    "album_artist",
+    "album_id",
+    "album_title",
+    "track_number",
+    "track_total",
+    "disc_number",
+    "disc_total",
+    "year",
+    "genres",
+    "duration",
+    "bitrate",
+    "sample_rate",
+    "bit_depth",
+    "channels",
+    "format",
+    "musicbrainz_id",
+    "acoustid",
+    "isrc",
+    "rg_track_gain",
+    "rg_track_peak",
+    "rg_album_gain",
+    "rg_album_peak",
+    "loudness_lufs",
+    "added_at",
+    "modified_at",
+    "file_hash",
+];
+
+/// Track fields exported when none are given.
+pub const DEFAULT_TRACK_FIELDS: &[&str] = &[
+    "id",
+    "path",
+    "title",
+    "artist",
+    "album_artist",
+    "album_title",
+    "track_number",
+    "disc_number",
+    "year",
+    "genres",
+    "duration",
+    "format",
+    "bitrate",
+];
+
+/// Album fields that can be exported.
+pub const ALBUM_FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "artist",
+    "year",
+    "genres",
+    "track_count",
+    "disc_count",
+    "musicbrainz_id",
+    "album_type",
+    "release_date",
+    "country",
+    "label",
+    "catalog_number",
+    "is_compilation",
+    "disambiguation",
+    "media",
+    "edition",
+    "barcode",
+    "discogs_id",
+    "rg_album_gain",
+    "rg_album_peak",
+    "loudness_lufs",
+    "added_at",
+    "modified_at",
+];
+
+/// Album fields exported when none are given.
+pub const DEFAULT_ALBUM_FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "artist",
+    "year",
+    "genres",
+    "track_count",
+    "disc_count",
+    "album_type",
+    "label",
+    "country",
+];
+
+/// Artist fields that can be exported.
+pub const ARTIST_FIELDS: &[&str] = &["name", "track_count", "album_count", "total_duration_secs"];
+
+/// Playlist fields that can be exported.
+pub const PLAYLIST_FIELDS: &[&str] = &[
+    "id",
+    "name",
+    "description",
+    "kind",
+    "query",
+    "sort",
+    "sort_spec",
+    "limit",
+    "track_ids",
+    "created_at",
+    "modified_at",
+];
+
+/// Playlist fields exported when none are given.
+pub const DEFAULT_PLAYLIST_FIELDS: &[&str] = &["id", "name", "description", "kind"];
+
+/// A file format to export to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row.
+    Csv,
+    /// A JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            _ => Err(Error::Validation(format!(
+                "unknown export format: {s} (expected csv, json, or jsonl)"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Json => write!(f, "json"),
+            Self::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+/// Exports records with a chosen set of fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exporter {
+    format: ExportFormat,
+    fields: Vec<String>,
+}
+
+impl Exporter {
+    /// Create an exporter for tracks.
+    ///
+    /// `fields` is a comma-separated list of [`TRACK_FIELDS`]; without one,
+    /// the [`DEFAULT_TRACK_FIELDS`] are exported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a field is unknown.
+    pub fn tracks(format: ExportFormat, fields: Option<&str>) -> Result<Self> {
+        Self::with_fields(format, fields, TRACK_FIELDS, DEFAULT_TRACK_FIELDS)
+    }
+
+    /// Create an exporter for albums.
+    ///
+    /// `fields` is a comma-separated list of [`ALBUM_FIELDS`]; without one,
+    /// the [`DEFAULT_ALBUM_FIELDS`] are exported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a field is unknown.
+    pub fn albums(format: ExportFormat, fields: Option<&str>) -> Result<Self> {
+        Self::with_fields(format, fields, ALBUM_FIELDS, DEFAULT_ALBUM_FIELDS)
+    }
+
+    /// Create an exporter for artists.
+    ///
+    /// `fields` is a comma-separated list of [`ARTIST_FIELDS`]; without one,
+    /// all of them are exported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a field is unknown.
+    pub fn artists(format: ExportFormat, fields: Option<&str>) -> Result<Self> {
+        Self::with_fields(format, fields, ARTIST_FIELDS, ARTIST_FIELDS)
+    }
+
+    /// Create an exporter for playlists.
+    ///
+    /// `fields` is a comma-separated list of [`PLAYLIST_FIELDS`]; without
+    /// one, the [`DEFAULT_PLAYLIST_FIELDS`] are exported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a field is unknown.
+    pub fn playlists(format: ExportFormat, fields: Option<&str>) -> Result<Self> {
+        Self::with_fields(format, fields, PLAYLIST_FIELDS, DEFAULT_PLAYLIST_FIELDS)
+    }
+
+    fn with_fields(
+        format: ExportFormat,
+        fields: Option<&str>,
+        known: &[&str],
+        default: &[&str],
+    ) -> Result<Self> {
+        let fields = match fields {
+            Some(fields) => fields
+                .split(',')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .map(|field| {
+                    let field = field.to_lowercase();
+                    if known.contains(&field.as_str()) {
+                        Ok(field)
+                    } else {
+                        Err(Error::Validation(format!(
+                            "unknown field: {field} (expected one of: {})",
+                            known.join(", ")
+                        )))
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => default.iter().map(ToString::to_string).collect(),
+        };
+        if fields.is_empty() {
+            return Err(Error::Validation("no fields to export".to_string()));
+        }
+        Ok(Self { format, fields })
+    }
+
+    /// Get the exported fields, in order.
+    #[must_use]
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Export records as text in the exporter's format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be serialized.
+    pub fn export<T: Serialize>(&self, records: &[T]) -> Result<String> {
+        let records = records
+            .iter()
+            .map(|record| self.select(record))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut out = String::new();
+        match self.format {
+            ExportFormat::Csv => {
+                push_csv_row(&mut out, self.fields.iter().map(|field| csv_field(field)));
+                for record in &records {
+                    push_csv_row(&mut out, record.0.iter().map(|(_, value)| csv_value(value)));
+                }
+            }
+            ExportFormat::Json => {
+                out = serde_json::to_string_pretty(&records).map_err(|e| serialize_error(&e))?;
+                out.push('\n');
+            }
+            ExportFormat::Jsonl => {
+                for record in &records {
+                    out.push_str(&serde_json::to_string(record).map_err(|e| serialize_error(&e))?);
+                    out.push('\n');
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Get the exported fields of a record as text, in order.
+    ///
+    /// Values are formatted as in CSV, but without quoting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record cannot be serialized.
+    pub fn values<T: Serialize>(&self, record: &T) -> Result<Vec<String>> {
+        Ok(self
+            .select(record)?
+            .0
+            .iter()
+            .map(|(_, value)| text_value(value))
+            .collect())
+    }
+
+    /// Pick the exported fields of a record.
+    fn select<T: Serialize>(&self, record: &T) -> Result<Record<'_>> {
+        let Value::Object(mut object) =
+            serde_json::to_value(record).map_err(|e| serialize_error(&e))?
+        else {
+            return Err(Error::Validation(
+                "only records with fields can be exported".to_string(),
+            ));
+        };
+        Ok(Record(
+            self.fields
+                .iter()
+                .map(|field| (field.as_str(), object.remove(field).unwrap_or(Value::Null)))
+                .collect(),
+        ))
+    }
+}
+
+/// The exported fields of a record, serialized in order.
+struct Record<'a>(Vec<(&'a str, Value)>);
+
+impl Serialize for Record<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(field, value)| (field, value)))
+    }
+}
+
+fn serialize_error(e: &serde_json::Error) -> Error {
+    Error::Validation(format!("cannot export record: {e}"))
+}
+
+fn push_csv_row(out: &mut String, fields: impl Iterator<Item = String>) {
+    out.push_str(&fields.collect::<Vec<_>>().join(","));
+    out.push('\n');
+}
+
+/// Format a value as CSV text.
+fn csv_value(value: &Value) -> String {
+    csv_field(&text_value(value))
+}
+
+/// Format a value as text, joining lists with `; `.
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => s.clone(),
+                item => item.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("; "),
+        value => value.to_string(),
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote, or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::ArtistSummary;
+    use crate::metadata::{Album, Track};
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn track() -> Track {
+        let mut track = Track::new(
+            PathBuf::from("/music/Queen/A Night at the Opera/11 Bohemian Rhapsody.flac"),
+            "Bohemian Rhapsody".to_string(),
+            "Queen".to_string(),
+            Duration::from_secs(354),
+        );
+        track.album_title = Some("A Night at the Opera".to_string());
+        track.genres = vec!["Rock".to_string(), "Progressive Rock".to_string()];
+        track
+    }
+
+    fn keys<T: Serialize>(record: &T) -> BTreeSet<String> {
+        match serde_json::to_value(record).unwrap() {
+            Value::Object(object) => object.keys().cloned().collect(),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn known_fields_match_records() {
+        let fields = |fields: &[&str]| fields.iter().map(ToString::to_string).collect();
+        assert_eq!(keys(&track()), fields(TRACK_FIELDS));
+        let album = Album::new("A Night at the Opera".to_string(), "Queen".to_string());
+        assert_eq!(keys(&album), fields(ALBUM_FIELDS));
+        let artist = ArtistSummary {
+            name: "Queen".to_string(),
+            track_count: 1,
+            album_count: 1,
+            total_duration_secs: 354,
+        };
+        assert_eq!(keys(&artist), fields(ARTIST_FIELDS));
+    }
+
+    #[test]
+    fn export_csv() {
+        let mut track = track();
+        track.title = "Bohemian Rhapsody, \"Live\"".to_string();
+        let exporter = Exporter::tracks(
+            ExportFormat::Csv,
+            Some("title,album_title,genres,year,duration"),
+        )
+        .unwrap();
+        assert_eq!(
+            exporter.export(&[track]).unwrap(),
+            "title,album_title,genres,year,duration\n\
+             \"Bohemian Rhapsody, \"\"Live\"\"\",A Night at the Opera,Rock; Progressive Rock,,354000\n"
+        );
+    }
+
+    #[test]
+    fn export_json() {
+        let exporter = Exporter::tracks(ExportFormat::Jsonl, Some("title,artist,year")).unwrap();
+        assert_eq!(
+            exporter.export(&[track(), track()]).unwrap(),
+            "{\"title\":\"Bohemian Rhapsody\",\"artist\":\"Queen\",\"year\":null}\n".repeat(2)
+        );
+
+        let exporter = Exporter::tracks(ExportFormat::Json, Some("genres")).unwrap();
+        let json: Value = serde_json::from_str(&exporter.export(&[track()]).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{"genres": ["Rock", "Progressive Rock"]}])
+        );
+    }
+
+    #[test]
+    fn choose_fields() {
+        let exporter = Exporter::albums(ExportFormat::Csv, None).unwrap();
+        assert_eq!(exporter.fields(), DEFAULT_ALBUM_FIELDS);
+        assert!(Exporter::tracks(ExportFormat::Csv, Some("title, rating")).is_err());
+        assert!(Exporter::tracks(ExportFormat::Csv, Some(" , ")).is_err());
+        assert_eq!(
+            "NDJSON".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Jsonl
+        );
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn record_values() {
+        let exporter = Exporter::tracks(ExportFormat::Csv, Some("title,genres,year")).unwrap();
+        let mut track = track();
+        track.title = "Bohemian Rhapsody, Live".to_string();
+        assert_eq!(
+            exporter.values(&track).unwrap(),
+            vec!["Bohemian Rhapsody, Live", "Rock; Progressive Rock", ""]
+        );
+    }
+}
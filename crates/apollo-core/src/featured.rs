@@ -0,0 +1,275 @@
+//! Featured artist normalization.
+//!
+//! Guest artists are credited in many different ways: "Artist feat. Guest"
+//! in the artist tag, "Title (ft. Guest)" in the title, or sometimes both.
+//! [`ArtistCredit`] parses them out of a track's artist and title, and
+//! [`CreditNormalizer`] writes them back in one configured
+//! [`FeaturedStyle`], so that guest appearances are tagged the same way
+//! throughout the library.
+//!
+//! "feat.", "ft." and "featuring" are recognized in any case and with or
+//! without the dot, either in brackets or up to the end of the text. Several
+//! featured artists are separated by commas or `&`.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::config::FeaturedStyle;
+//! use apollo_core::featured::CreditNormalizer;
+//! use apollo_core::Track;
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//!
+//! let mut track = Track::new(
+//!     PathBuf::from("/music/get-lucky.mp3"),
+//!     "Get Lucky".to_string(),
+//!     "Daft Punk ft. Pharrell Williams & Nile Rodgers".to_string(),
+//!     Duration::from_secs(369),
+//! );
+//!
+//! let normalizer = CreditNormalizer::new(FeaturedStyle::Title, "feat.");
+//! assert!(normalizer.normalize(&mut track));
+//! assert_eq!(track.artist, "Daft Punk");
+//! assert_eq!(
+//!     track.title,
+//!     "Get Lucky (feat. Pharrell Williams & Nile Rodgers)"
+//! );
+//! ```
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::config::{FeaturedConfig, FeaturedStyle};
+use crate::metadata::Track;
+
+/// Featured artists in brackets, such as " (feat. Guest)".
+static BRACKETED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s*[(\[](?:feat\.?|ft\.?|featuring)\s+([^)\]]*)[)\]]")
+        .expect("pattern is valid")
+});
+
+/// Featured artists up to the end of the text, such as " ft. Guest".
+static TRAILING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s+(?:feat\.?|ft\.?|featuring)\s+(.*)$").expect("pattern is valid")
+});
+
+/// Separators between featured artists.
+static SEPARATOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*,\s*|\s+&\s+").expect("pattern is valid"));
+
+/// The artists credited on a track.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArtistCredit {
+    /// Main artist.
+    pub artist: String,
+    /// Featured artists, in the order they are credited.
+    pub featured: Vec<String>,
+}
+
+impl ArtistCredit {
+    /// Parse an artist tag, such as "Artist feat. Guest".
+    #[must_use]
+    pub fn parse(artist: &str) -> Self {
+        let (artist, featured) = split_featured(artist);
+        let mut credit = Self {
+            artist,
+            featured: Vec::new(),
+        };
+        credit.add_featured(featured.as_deref());
+        credit
+    }
+
+    /// Parse the credits of a track from both its artist and its title.
+    ///
+    /// Returns the credit and the title without featured artists.
+    #[must_use]
+    pub fn from_track(track: &Track) -> (Self, String) {
+        let mut credit = Self::parse(&track.artist);
+        let (title, featured) = split_featured(&track.title);
+        credit.add_featured(featured.as_deref());
+        (credit, title)
+    }
+
+    /// Check if the track features other artists.
+    #[must_use]
+    pub const fn has_featured(&self) -> bool {
+        !self.featured.is_empty()
+    }
+
+    /// Get the featured artists as text, such as "Guest, Other & Third".
+    #[must_use]
+    pub fn featured_text(&self) -> String {
+        match self.featured.as_slice() {
+            [] => String::new(),
+            [only] => only.clone(),
+            [rest @ .., last] => format!("{} & {last}", rest.join(", ")),
+        }
+    }
+
+    /// Add featured artists from text, skipping the main artist and ones
+    /// already credited.
+    fn add_featured(&mut self, text: Option<&str>) {
+        for name in text.into_iter().flat_map(|text| SEPARATOR.split(text)) {
+            let name = name.trim();
+            let credited = name.eq_ignore_ascii_case(&self.artist)
+                || self
+                    .featured
+                    .iter()
+                    .any(|other| other.eq_ignore_ascii_case(name));
+            if !name.is_empty() && !credited {
+                self.featured.push(name.to_string());
+            }
+        }
+    }
+}
+
+/// Split featured artists off text, returning the rest of the text and the
+/// featured artists, if any.
+///
+/// Text that would be left empty is not split, so an artist named
+/// "Featuring Guest" stays as it is.
+fn split_featured(text: &str) -> (String, Option<String>) {
+    let text = text.trim();
+    let split = BRACKETED
+        .captures(text)
+        .or_else(|| TRAILING.captures(text))
+        .and_then(|captures| {
+            let whole = captures.get(0)?;
+            let rest = format!("{}{}", &text[..whole.start()], &text[whole.end()..]);
+            Some((rest.trim().to_string(), captures[1].trim().to_string()))
+        });
+    match split {
+        Some((rest, featured)) if !rest.is_empty() => (rest, Some(featured)),
+        _ => (text.to_string(), None),
+    }
+}
+
+/// Writes the featured artists of tracks in one style.
+#[derive(Debug, Clone)]
+pub struct CreditNormalizer {
+    style: FeaturedStyle,
+    keyword: String,
+}
+
+impl CreditNormalizer {
+    /// Create a normalizer that puts featured artists where `style` says,
+    /// introduced by `keyword`, such as "feat.".
+    #[must_use]
+    pub fn new(style: FeaturedStyle, keyword: &str) -> Self {
+        Self {
+            style,
+            keyword: keyword.trim().to_string(),
+        }
+    }
+
+    /// Create a normalizer from configuration.
+    #[must_use]
+    pub fn from_config(config: &FeaturedConfig) -> Self {
+        Self::new(config.style, &config.keyword)
+    }
+
+    /// Rewrite the featured artists of a track in the normalizer's style.
+    ///
+    /// Returns whether the track changed. Tracks without featured artists
+    /// are left as they are.
+    pub fn normalize(&self, track: &mut Track) -> bool {
+        let (credit, title) = ArtistCredit::from_track(track);
+        if !credit.has_featured() {
+            return false;
+        }
+
+        let featured = format!("{} {}", self.keyword, credit.featured_text());
+        let (artist, title) = match self.style {
+            FeaturedStyle::Title => (credit.artist, format!("{title} ({featured})")),
+            FeaturedStyle::Artist => (format!("{} {featured}", credit.artist), title),
+        };
+        let changed = artist != track.artist || title != track.title;
+        track.artist = artist;
+        track.title = title;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn track(artist: &str, title: &str) -> Track {
+        Track::new(
+            PathBuf::from("/music/track.mp3"),
+            title.to_string(),
+            artist.to_string(),
+            Duration::from_secs(200),
+        )
+    }
+
+    #[test]
+    fn parse_artist() {
+        let credit = ArtistCredit::parse("Santana featuring Rob Thomas");
+        assert_eq!(credit.artist, "Santana");
+        assert_eq!(credit.featured, vec!["Rob Thomas"]);
+
+        let credit = ArtistCredit::parse("Gorillaz (Ft. De La Soul, Gruff Rhys & Jamie)");
+        assert_eq!(credit.artist, "Gorillaz");
+        assert_eq!(credit.featured, vec!["De La Soul", "Gruff Rhys", "Jamie"]);
+        assert_eq!(credit.featured_text(), "De La Soul, Gruff Rhys & Jamie");
+
+        // Keywords within words, or with no artist before them, are not credits
+        for artist in [
+            "Daft Punk",
+            "Feat Fighters",
+            "Loft Feathers",
+            "Featuring Guest",
+        ] {
+            assert_eq!(ArtistCredit::parse(artist).artist, artist);
+            assert!(!ArtistCredit::parse(artist).has_featured());
+        }
+    }
+
+    #[test]
+    fn parse_track() {
+        let (credit, title) = ArtistCredit::from_track(&track(
+            "Mark Ronson feat. Bruno Mars",
+            "Uptown Funk [feat. Bruno Mars] (Live)",
+        ));
+        assert_eq!(credit.artist, "Mark Ronson");
+        assert_eq!(credit.featured, vec!["Bruno Mars"]);
+        assert_eq!(title, "Uptown Funk (Live)");
+    }
+
+    #[test]
+    fn normalize_styles() {
+        let title = CreditNormalizer::new(FeaturedStyle::Title, "feat.");
+        let artist = CreditNormalizer::new(FeaturedStyle::Artist, "ft.");
+
+        let mut stan = track("Eminem", "Stan ft Dido");
+        assert!(artist.normalize(&mut stan));
+        assert_eq!(stan.artist, "Eminem ft. Dido");
+        assert_eq!(stan.title, "Stan");
+        assert!(!artist.normalize(&mut stan));
+
+        assert!(title.normalize(&mut stan));
+        assert_eq!(stan.artist, "Eminem");
+        assert_eq!(stan.title, "Stan (feat. Dido)");
+        assert!(!title.normalize(&mut stan));
+
+        let mut solo = track("Dido", "Thank You");
+        assert!(!title.normalize(&mut solo));
+        assert_eq!(solo.title, "Thank You");
+    }
+
+    #[test]
+    fn configured_style() {
+        let config = FeaturedConfig {
+            style: FeaturedStyle::Artist,
+            keyword: "featuring".to_string(),
+            ..FeaturedConfig::default()
+        };
+        let mut way_you_move = track("Outkast", "The Way You Move (feat. Sleepy Brown)");
+        assert!(CreditNormalizer::from_config(&config).normalize(&mut way_you_move));
+        assert_eq!(way_you_move.artist, "Outkast featuring Sleepy Brown");
+        assert_eq!(way_you_move.title, "The Way You Move");
+    }
+}
@@ -0,0 +1,265 @@
+//! Genre normalization.
+//!
+//! Genre tags are written in many different ways: "Alt Rock", "alt-rock" and
+//! "Alternative" usually all mean "Alternative Rock". [`GenreNormalizer`]
+//! maps such spellings to canonical names from a built-in taxonomy, which
+//! also arranges genres in a tree (e.g., "Alternative Rock" is a kind of
+//! "Rock").
+//!
+//! Spellings are compared ignoring case, whitespace, and punctuation, and
+//! with `&` read as "and". Genres that are not in the taxonomy are kept, with
+//! surrounding whitespace removed.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::genre::GenreNormalizer;
+//!
+//! let normalizer = GenreNormalizer::new().with_alias("Britpop", "Alternative Rock");
+//!
+//! assert_eq!(normalizer.canonicalize("alt-rock"), "Alternative Rock");
+//! assert_eq!(normalizer.canonicalize("Britpop"), "Alternative Rock");
+//! assert_eq!(normalizer.parent("Alternative Rock"), Some("Rock"));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use crate::config::GenreConfig;
+
+/// Built-in genre taxonomy: canonical name, parent, and alternative spellings.
+const TAXONOMY: &[(&str, Option<&str>, &[&str])] = &[
+    ("Rock", None, &["rock music", "rock & roll", "rock n roll"]),
+    (
+        "Alternative Rock",
+        Some("Rock"),
+        &["alt rock", "alternative", "alt"],
+    ),
+    ("Indie Rock", Some("Rock"), &["indie", "indie-rock"]),
+    ("Hard Rock", Some("Rock"), &[]),
+    ("Classic Rock", Some("Rock"), &[]),
+    ("Progressive Rock", Some("Rock"), &["prog rock", "prog"]),
+    (
+        "Psychedelic Rock",
+        Some("Rock"),
+        &["psych rock", "psychedelic"],
+    ),
+    ("Punk Rock", Some("Rock"), &["punk"]),
+    ("Post-Punk", Some("Punk Rock"), &[]),
+    ("Grunge", Some("Alternative Rock"), &[]),
+    ("Shoegaze", Some("Alternative Rock"), &[]),
+    ("Post-Rock", Some("Rock"), &[]),
+    ("Metal", None, &["heavy metal"]),
+    ("Death Metal", Some("Metal"), &[]),
+    ("Black Metal", Some("Metal"), &[]),
+    ("Thrash Metal", Some("Metal"), &["thrash"]),
+    ("Pop", None, &["pop music"]),
+    ("Synth-Pop", Some("Pop"), &["synthpop", "synth pop"]),
+    ("Indie Pop", Some("Pop"), &[]),
+    ("Electronic", None, &["electronica", "electro"]),
+    ("House", Some("Electronic"), &[]),
+    ("Techno", Some("Electronic"), &[]),
+    ("Trance", Some("Electronic"), &[]),
+    ("Ambient", Some("Electronic"), &[]),
+    (
+        "Drum and Bass",
+        Some("Electronic"),
+        &["drum n bass", "dnb", "d&b"],
+    ),
+    ("Dubstep", Some("Electronic"), &[]),
+    (
+        "Hip Hop",
+        None,
+        &["hiphop", "hip-hop", "rap", "hip hop/rap"],
+    ),
+    ("Trip Hop", Some("Electronic"), &["triphop"]),
+    ("R&B", None, &["rnb", "rhythm and blues", "r and b"]),
+    ("Soul", Some("R&B"), &[]),
+    ("Funk", Some("R&B"), &[]),
+    ("Jazz", None, &[]),
+    ("Blues", None, &[]),
+    ("Classical", None, &["classical music"]),
+    ("Country", None, &["country music"]),
+    ("Folk", None, &["folk music"]),
+    ("Singer-Songwriter", Some("Folk"), &["singer songwriter"]),
+    ("Reggae", None, &[]),
+    ("Latin", None, &[]),
+    ("World", None, &["world music"]),
+    ("Soundtrack", None, &["ost", "score", "film score"]),
+];
+
+/// Maps genre spellings to canonical genres.
+#[derive(Debug, Clone)]
+pub struct GenreNormalizer {
+    /// Canonical name by spelling key.
+    canonical: HashMap<String, String>,
+    /// Parent genre by canonical name.
+    parents: HashMap<String, String>,
+}
+
+impl Default for GenreNormalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GenreNormalizer {
+    /// Create a normalizer with the built-in taxonomy.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut normalizer = Self {
+            canonical: HashMap::new(),
+            parents: HashMap::new(),
+        };
+        for (name, parent, aliases) in TAXONOMY {
+            normalizer.canonical.insert(key(name), (*name).to_string());
+            for alias in *aliases {
+                normalizer.canonical.insert(key(alias), (*name).to_string());
+            }
+            if let Some(parent) = parent {
+                normalizer
+                    .parents
+                    .insert((*name).to_string(), (*parent).to_string());
+            }
+        }
+        normalizer
+    }
+
+    /// Create a normalizer with the built-in taxonomy and configured aliases.
+    #[must_use]
+    pub fn from_config(config: &GenreConfig) -> Self {
+        config
+            .aliases
+            .iter()
+            .fold(Self::new(), |normalizer, (alias, genre)| {
+                normalizer.with_alias(alias, genre)
+            })
+    }
+
+    /// Add an alias, overriding any built-in mapping for the same spelling.
+    ///
+    /// The target may itself be a spelling of a known genre.
+    #[must_use]
+    pub fn with_alias(mut self, alias: &str, genre: &str) -> Self {
+        let genre = self.canonicalize(genre);
+        self.canonical.insert(key(alias), genre);
+        self
+    }
+
+    /// Get the canonical name for a genre.
+    ///
+    /// Unknown genres are returned with surrounding whitespace removed.
+    #[must_use]
+    pub fn canonicalize(&self, genre: &str) -> String {
+        let genre = genre.trim();
+        self.canonical
+            .get(&key(genre))
+            .cloned()
+            .unwrap_or_else(|| genre.to_string())
+    }
+
+    /// Check if a genre is canonical or a known spelling of one.
+    #[must_use]
+    pub fn is_known(&self, genre: &str) -> bool {
+        self.canonical.contains_key(&key(genre))
+    }
+
+    /// Normalize a list of genres.
+    ///
+    /// Entries containing several genres separated by `;` are split, every
+    /// genre is canonicalized, and empty entries and duplicates are removed.
+    #[must_use]
+    pub fn normalize(&self, genres: &[String]) -> Vec<String> {
+        let mut seen = HashSet::new();
+        genres
+            .iter()
+            .flat_map(|genre| genre.split(';'))
+            .filter(|genre| !genre.trim().is_empty())
+            .map(|genre| self.canonicalize(genre))
+            .filter(|genre| seen.insert(genre.clone()))
+            .collect()
+    }
+
+    /// Get the parent of a canonical genre in the taxonomy.
+    #[must_use]
+    pub fn parent(&self, genre: &str) -> Option<&str> {
+        self.parents.get(genre).map(String::as_str)
+    }
+
+    /// Get the ancestors of a canonical genre, from its parent to the root.
+    #[must_use]
+    pub fn ancestors(&self, genre: &str) -> Vec<&str> {
+        let mut ancestors = Vec::new();
+        let mut current = self.parent(genre);
+        while let Some(parent) = current {
+            ancestors.push(parent);
+            current = self.parent(parent);
+        }
+        ancestors
+    }
+}
+
+/// Get the lookup key of a genre spelling.
+fn key(genre: &str) -> String {
+    genre
+        .replace('&', " and ")
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalize_spellings() {
+        let normalizer = GenreNormalizer::new();
+        assert_eq!(normalizer.canonicalize("Alt Rock"), "Alternative Rock");
+        assert_eq!(normalizer.canonicalize("alt-rock"), "Alternative Rock");
+        assert_eq!(normalizer.canonicalize("HIP-HOP"), "Hip Hop");
+        assert_eq!(normalizer.canonicalize("Drum & Bass"), "Drum and Bass");
+        assert_eq!(normalizer.canonicalize("RnB"), "R&B");
+        assert_eq!(normalizer.canonicalize(" Vaporwave "), "Vaporwave");
+        assert!(!normalizer.is_known("Vaporwave"));
+    }
+
+    #[test]
+    fn normalize_list() {
+        let normalizer = GenreNormalizer::new();
+        let genres = vec![
+            "alt rock; Grunge".to_string(),
+            "Alternative".to_string(),
+            String::new(),
+        ];
+        assert_eq!(
+            normalizer.normalize(&genres),
+            vec!["Alternative Rock", "Grunge"]
+        );
+    }
+
+    #[test]
+    fn taxonomy_tree() {
+        let normalizer = GenreNormalizer::new();
+        assert_eq!(normalizer.parent("Rock"), None);
+        assert_eq!(
+            normalizer.ancestors("Grunge"),
+            vec!["Alternative Rock", "Rock"]
+        );
+    }
+
+    #[test]
+    fn configured_aliases() {
+        let mut config = GenreConfig::default();
+        config
+            .aliases
+            .insert("Britpop".to_string(), "alt rock".to_string());
+        config
+            .aliases
+            .insert("Alternative".to_string(), "Indie Rock".to_string());
+
+        let normalizer = GenreNormalizer::from_config(&config);
+        assert_eq!(normalizer.canonicalize("britpop"), "Alternative Rock");
+        assert_eq!(normalizer.canonicalize("alternative"), "Indie Rock");
+    }
+}
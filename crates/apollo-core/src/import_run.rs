@@ -0,0 +1,56 @@
+//! History of imports.
+//!
+//! Each import is recorded as an [`ImportRun`] once it finishes: where it
+//! imported from, with which options, and what came of it. The files that
+//! failed are kept, so that they can be imported again once the problem is
+//! fixed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use utoipa::ToSchema;
+
+/// A finished import, as recorded in the import history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ImportRun {
+    /// ID in the import history; 0 until the run is recorded.
+    #[schema(example = 7)]
+    pub id: i64,
+    /// Directory that was imported from.
+    #[schema(value_type = String, example = "/home/user/Music/NewAlbum")]
+    pub source_path: PathBuf,
+    /// When the import started.
+    pub started_at: DateTime<Utc>,
+    /// How long the import took, in milliseconds.
+    #[schema(example = 5400)]
+    pub duration_ms: u64,
+    /// Options the import ran with.
+    #[schema(value_type = Object)]
+    pub options: Value,
+    /// Number of tracks found.
+    #[schema(example = 12)]
+    pub tracks_found: usize,
+    /// Number of tracks imported.
+    #[schema(example = 10)]
+    pub tracks_imported: usize,
+    /// Number of tracks skipped (already imported, or skipped by a plugin).
+    #[schema(example = 1)]
+    pub tracks_skipped: usize,
+    /// Number of tracks skipped because the same file was in the library.
+    #[schema(example = 0)]
+    pub tracks_duplicate: usize,
+    /// Number of tracks that failed to import.
+    #[schema(example = 1)]
+    pub tracks_failed: usize,
+    /// Number of albums created.
+    #[schema(example = 1)]
+    pub albums_created: usize,
+    /// Whether the import was cancelled before it finished.
+    pub cancelled: bool,
+    /// Errors encountered during the import.
+    pub errors: Vec<String>,
+    /// Files that could not be read or imported.
+    #[schema(value_type = Vec<String>)]
+    pub failed_files: Vec<PathBuf>,
+}
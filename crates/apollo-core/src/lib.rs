@@ -5,16 +5,34 @@
 //! This crate contains no I/O operations and is designed to be purely functional
 //! where possible.
 
+pub mod changes;
 pub mod config;
+pub mod diff;
+pub mod duplicate;
+pub mod edit;
 pub mod error;
+pub mod event;
+pub mod export;
+pub mod featured;
+pub mod genre;
+pub mod import_run;
 pub mod library;
+pub mod lock;
 pub mod metadata;
+pub mod operation;
 pub mod playlist;
+pub mod provenance;
 pub mod query;
 pub mod template;
 
 pub use config::Config;
+pub use diff::{FieldChange, TrackDiff};
+pub use edit::TrackEdit;
 pub use error::Error;
-pub use metadata::{Album, AlbumId, Artist, AudioFormat, Track, TrackId};
+pub use event::{EventBus, LibraryEvent};
+pub use metadata::{
+    Album, AlbumId, AlbumType, Artist, ArtistId, Artwork, AudioFormat, Media, Track, TrackId,
+    TrackStats,
+};
 pub use playlist::{Playlist, PlaylistId, PlaylistKind, PlaylistLimit, PlaylistSort};
-pub use template::{PathTemplate, TemplateContext};
+pub use template::{AlbumSet, PathTemplate, TemplateContext};
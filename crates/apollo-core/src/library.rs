@@ -1,76 +1,595 @@
 //! Library abstraction for track and album management.
+//!
+//! [`Library`] is implemented by storage backends, such as the `SQLite`
+//! library in apollo-db. Code that only needs to read and modify the library
+//! should depend on the trait, so that other backends and test doubles can be
+//! used instead.
 
+use std::path::Path;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::changes::Change;
+use crate::edit::EditField;
 use crate::error::Result;
-use crate::metadata::{Album, AlbumId, Track, TrackId};
+use crate::event::EventBus;
+use crate::import_run::ImportRun;
+use crate::metadata::{
+    Album, AlbumId, Artist, ArtistId, Artwork, Fingerprint, Track, TrackId, TrackStats,
+};
+use crate::operation::Operation;
+use crate::playlist::{Playlist, PlaylistId, PlaylistVersion};
+use crate::provenance::Provenance;
+use crate::query::{Query, SortSpec};
 
 /// Trait for library storage backends.
-pub trait Library {
+#[async_trait]
+pub trait Library: Send + Sync {
     /// Get a track by its ID.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    fn get_track(&self, id: &TrackId) -> Result<Option<Track>>;
+    async fn get_track(&self, id: &TrackId) -> Result<Option<Track>>;
 
     /// Get an album by its ID.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    fn get_album(&self, id: &AlbumId) -> Result<Option<Album>>;
+    async fn get_album(&self, id: &AlbumId) -> Result<Option<Album>>;
+
+    /// Get an artist by their ID.
+    ///
+    /// Artists are not stored separately, so this finds the track or album
+    /// artist whose name has the ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_artist(&self, id: &ArtistId) -> Result<Option<Artist>>;
 
     /// Get all tracks in an album.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    fn get_album_tracks(&self, album_id: &AlbumId) -> Result<Vec<Track>>;
+    async fn get_album_tracks(&self, album_id: &AlbumId) -> Result<Vec<Track>>;
 
     /// Add a track to the library.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    fn add_track(&mut self, track: Track) -> Result<TrackId>;
+    async fn add_track(&self, track: &Track) -> Result<TrackId>;
 
     /// Update an existing track.
     ///
     /// # Errors
     ///
     /// Returns an error if the track doesn't exist or the database operation fails.
-    fn update_track(&mut self, track: Track) -> Result<()>;
+    async fn update_track(&self, track: &Track) -> Result<()>;
 
     /// Remove a track from the library.
     ///
     /// # Errors
     ///
     /// Returns an error if the track doesn't exist or the database operation fails.
-    fn remove_track(&mut self, id: &TrackId) -> Result<()>;
+    async fn remove_track(&self, id: &TrackId) -> Result<()>;
+
+    /// Remove a duplicate track, moving its playlist entries, plays, and
+    /// rating to the track kept in its place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either track doesn't exist or the database operation fails.
+    async fn merge_duplicate_track(&self, duplicate: &TrackId, kept: &TrackId) -> Result<()>;
 
     /// Add an album to the library.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    fn add_album(&mut self, album: Album) -> Result<AlbumId>;
+    async fn add_album(&self, album: &Album) -> Result<AlbumId>;
 
     /// Update an existing album.
     ///
     /// # Errors
     ///
     /// Returns an error if the album doesn't exist or the database operation fails.
-    fn update_album(&mut self, album: Album) -> Result<()>;
+    async fn update_album(&self, album: &Album) -> Result<()>;
 
     /// Remove an album from the library.
     ///
     /// # Errors
     ///
     /// Returns an error if the album doesn't exist or the database operation fails.
-    fn remove_album(&mut self, id: &AlbumId) -> Result<()>;
+    async fn remove_album(&self, id: &AlbumId) -> Result<()>;
+
+    /// Search tracks using full-text search.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn search_tracks(&self, query: &str) -> Result<Vec<Track>>;
+
+    /// Search tracks using full-text search, in the given sort order.
+    ///
+    /// An empty sort specification orders results by relevance.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn search_tracks_sorted(&self, query: &str, sort: &SortSpec) -> Result<Vec<Track>>;
+
+    /// Suggest artist, album, and track names that start with the words of
+    /// a partly typed search, for type-ahead.
+    ///
+    /// At most `limit` names of each kind are suggested, best first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn suggest(&self, text: &str, limit: u32) -> Result<Suggestions>;
+
+    /// Get tracks matching a query, in the given sort order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn query_tracks(&self, query: &Query, sort: &SortSpec) -> Result<Vec<Track>>;
+
+    /// List all tracks in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_tracks(&self, limit: u32, offset: u32) -> Result<Vec<Track>>;
+
+    /// List all albums in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_albums(&self, limit: u32, offset: u32) -> Result<Vec<Album>>;
+
+    /// Count total tracks in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn count_tracks(&self) -> Result<u64>;
+
+    /// Count total albums in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn count_albums(&self) -> Result<u64>;
+
+    /// Find tracks with duplicate file hashes (exact byte-for-byte duplicates).
+    ///
+    /// Returns groups of tracks that have the same file hash.
+    /// Each group contains 2 or more tracks that are exact duplicates.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn find_exact_duplicates(&self) -> Result<Vec<Vec<Track>>>;
+
+    /// Find tracks that are likely duplicates based on metadata similarity.
+    ///
+    /// Matches tracks with the same title, artist, and similar duration (within tolerance).
+    /// Returns groups of potentially duplicate tracks.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration_tolerance_ms` - Maximum duration difference in milliseconds to consider similar
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn find_similar_duplicates(&self, duration_tolerance_ms: i64) -> Result<Vec<Vec<Track>>>;
+
+    /// Check if a track with the given file hash already exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn track_exists_by_hash(&self, file_hash: &str) -> Result<bool>;
+
+    /// Get a track by its file hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_track_by_hash(&self, file_hash: &str) -> Result<Option<Track>>;
+
+    /// Get a track by its file path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_track_by_path(&self, path: &Path) -> Result<Option<Track>>;
+
+    /// Mark a track's file as missing, or as found again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track doesn't exist or the database operation fails.
+    async fn set_track_missing(&self, id: &TrackId, missing: bool) -> Result<()>;
+
+    /// List tracks whose files are marked as missing, with the time each was
+    /// found to be missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_missing_tracks(&self) -> Result<Vec<(Track, DateTime<Utc>)>>;
+
+    /// Queue a track's tags to be written to its file, removing the
+    /// `cleared` fields from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track doesn't exist or the database operation fails.
+    async fn queue_write(&self, id: &TrackId, cleared: &[EditField]) -> Result<()>;
+
+    /// List tracks whose tags are queued to be written, with the fields to
+    /// remove from each file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_pending_writes(&self) -> Result<Vec<(Track, Vec<EditField>)>>;
+
+    /// Remove a track from the queue of tags to write.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn remove_pending_write(&self, id: &TrackId) -> Result<()>;
+
+    /// Set or clear the rating of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rating is not between 1 and 5, the track does
+    /// not exist, or the database operation fails.
+    async fn set_track_rating(&self, id: &TrackId, rating: Option<u8>) -> Result<()>;
+
+    /// Mark or unmark a track as a favorite.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    async fn set_track_favorite(&self, id: &TrackId, favorite: bool) -> Result<()>;
+
+    /// Record a play of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    async fn record_play(&self, id: &TrackId, played_at: DateTime<Utc>) -> Result<()>;
+
+    /// Queue a play of a track to be scrobbled to a service, such as
+    /// `lastfm`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    async fn queue_scrobble(
+        &self,
+        id: &TrackId,
+        service: &str,
+        played_at: DateTime<Utc>,
+    ) -> Result<()>;
+
+    /// List plays queued to be scrobbled, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_scrobbles(&self) -> Result<Vec<QueuedScrobble>>;
+
+    /// Remove a play from the queue of scrobbles, once it has been sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn remove_scrobble(&self, id: i64) -> Result<()>;
+
+    /// Note a failed attempt to send a queued scrobble, which stays queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn fail_scrobble(&self, id: i64, error: &str) -> Result<()>;
+
+    /// Record an operation in the log, so that it can be undone.
+    ///
+    /// Returns the ID of the recorded operation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn record_operation(&self, operation: &Operation) -> Result<i64>;
+
+    /// List the most recent operations, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_operations(&self, limit: u32) -> Result<Vec<Operation>>;
+
+    /// Get an operation by its ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_operation(&self, id: i64) -> Result<Option<Operation>>;
+
+    /// Note that an operation has been undone.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the operation does not exist or the database
+    /// operation fails.
+    async fn mark_operation_undone(&self, id: i64, undone_at: DateTime<Utc>) -> Result<()>;
+
+    /// Record a finished import in the import history.
+    ///
+    /// Returns the ID of the recorded run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn record_import_run(&self, run: &ImportRun) -> Result<i64>;
+
+    /// List the most recent imports, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_import_runs(&self, limit: u32) -> Result<Vec<ImportRun>>;
+
+    /// List the changes to tracks and playlists made after sequence number
+    /// `since`, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_changes(&self, since: i64) -> Result<Vec<Change>>;
+
+    /// Get the sequence number of the last change made at or before a time,
+    /// or 0 if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn change_seq_at(&self, at: DateTime<Utc>) -> Result<i64>;
+
+    /// Get the rating and play statistics of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_track_stats(&self, id: &TrackId) -> Result<TrackStats>;
+
+    /// Set the cover art of an album, replacing any it has.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the album does not exist or the database operation
+    /// fails.
+    async fn set_album_artwork(&self, id: &AlbumId, artwork: &Artwork) -> Result<()>;
+
+    /// Get the cover art of an album.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_album_artwork(&self, id: &AlbumId) -> Result<Option<Artwork>>;
+
+    /// Get when the cover art of an album was last stored, without loading
+    /// the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_album_artwork_modified(&self, id: &AlbumId) -> Result<Option<DateTime<Utc>>>;
+
+    /// Store the audio fingerprint of a track, replacing any it has.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    async fn set_fingerprint(&self, id: &TrackId, fingerprint: &Fingerprint) -> Result<()>;
+
+    /// Get the stored audio fingerprint of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_fingerprint(&self, id: &TrackId) -> Result<Option<Fingerprint>>;
+
+    /// Record where fields of a track came from, keeping the sources of
+    /// other fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    async fn record_provenance(&self, id: &TrackId, provenance: &Provenance) -> Result<()>;
+
+    /// Get where the fields of a track came from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_track_provenance(&self, id: &TrackId) -> Result<Provenance>;
+
+    /// Set the locked fields of a track, replacing those it had.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    async fn set_field_locks(&self, id: &TrackId, fields: &[EditField]) -> Result<()>;
+
+    /// Get the locked fields of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_field_locks(&self, id: &TrackId) -> Result<Vec<EditField>>;
+
+    /// Get a playlist by its ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_playlist(&self, id: &PlaylistId) -> Result<Option<Playlist>>;
+
+    /// Add a playlist to the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn add_playlist(&self, playlist: &Playlist) -> Result<PlaylistId>;
+
+    /// Update an existing playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist or the database operation fails.
+    async fn update_playlist(&self, playlist: &Playlist) -> Result<()>;
+
+    /// Remove a playlist from the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist or the database operation fails.
+    async fn remove_playlist(&self, id: &PlaylistId) -> Result<()>;
+
+    /// List all playlists in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_playlists(&self) -> Result<Vec<Playlist>>;
+
+    /// Count total playlists in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn count_playlists(&self) -> Result<u64>;
+
+    /// Add a track to the end of a static playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist, already has the
+    /// track, or the database operation fails.
+    async fn add_track_to_playlist(
+        &self,
+        playlist_id: &PlaylistId,
+        track_id: &TrackId,
+    ) -> Result<()>;
+
+    /// Remove a track from a static playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn remove_track_from_playlist(
+        &self,
+        playlist_id: &PlaylistId,
+        track_id: &TrackId,
+    ) -> Result<()>;
+
+    /// Get all tracks in a playlist.
+    ///
+    /// For static playlists, returns the stored tracks in order.
+    /// For smart playlists, evaluates the query and returns matching tracks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn get_playlist_tracks(&self, playlist_id: &PlaylistId) -> Result<Vec<Track>>;
+
+    /// Get a page of the tracks in a playlist, in playlist order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist or the database
+    /// operation fails.
+    async fn get_playlist_tracks_page(
+        &self,
+        playlist_id: &PlaylistId,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Track>>;
+
+    /// Count the tracks in a playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist or the database
+    /// operation fails.
+    async fn count_playlist_tracks(&self, playlist_id: &PlaylistId) -> Result<u64>;
+
+    /// List the versions of a static playlist, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    async fn list_playlist_versions(
+        &self,
+        playlist_id: &PlaylistId,
+    ) -> Result<Vec<PlaylistVersion>>;
+
+    /// Give a static playlist the tracks of one of its versions, and return
+    /// the playlist.
+    ///
+    /// The tracks it has are kept as a new version first, so a restore can
+    /// be undone too. Tracks no longer in the library are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist or version doesn't exist, the
+    /// playlist is a smart playlist, or the database operation fails.
+    async fn restore_playlist_version(
+        &self,
+        playlist_id: &PlaylistId,
+        version_id: i64,
+    ) -> Result<Playlist>;
+
+    /// Write a consistent copy of the library to a new file, while the
+    /// library stays in use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` already exists or cannot be written.
+    async fn backup(&self, path: &Path) -> Result<()>;
+
+    /// Get the bus on which changes to the library are announced.
+    ///
+    /// Implementations emit a [`LibraryEvent`](crate::event::LibraryEvent)
+    /// after every successful change.
+    fn events(&self) -> &EventBus;
 }
 
 /// Statistics about the library.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct LibraryStats {
     /// Total number of tracks.
     pub track_count: u64,
@@ -83,3 +602,86 @@ pub struct LibraryStats {
     /// Total file size in bytes.
     pub total_size_bytes: u64,
 }
+
+/// A play waiting to be scrobbled.
+#[derive(Debug, Clone)]
+pub struct QueuedScrobble {
+    /// ID of the queued play.
+    pub id: i64,
+    /// Service to scrobble to, such as `lastfm` or `listenbrainz`.
+    pub service: String,
+    /// The played track.
+    pub track: Track,
+    /// When the track was played.
+    pub played_at: DateTime<Utc>,
+    /// Number of failed attempts to send the play.
+    pub attempts: u32,
+    /// Error of the last failed attempt.
+    pub last_error: Option<String>,
+}
+
+/// A field that library statistics can be broken down by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsBreakdown {
+    /// Audio format, such as FLAC or MP3.
+    Format,
+    /// Genre. Tracks with several genres count towards each of them.
+    Genre,
+    /// Decade of release, such as the 1990s.
+    Decade,
+    /// Track artist.
+    Artist,
+}
+
+/// Statistics about the tracks that share a value, such as a genre.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatsGroup {
+    /// The shared value, or `None` for tracks without one.
+    pub name: Option<String>,
+    /// Number of tracks.
+    pub track_count: u64,
+    /// Total duration of the tracks.
+    pub total_duration_secs: u64,
+}
+
+/// An artist found in the library, with how much of it there is.
+///
+/// Artists are not stored separately, so these are collected from the
+/// track and album artists of the tracks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArtistSummary {
+    /// Artist name.
+    pub name: String,
+    /// Number of tracks by or credited to the artist.
+    pub track_count: u64,
+    /// Number of albums with those tracks.
+    pub album_count: u64,
+    /// Total duration of the tracks.
+    pub total_duration_secs: u64,
+}
+
+/// A name suggested for a partly typed search.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Suggestion {
+    /// ID of the artist, album, or track.
+    #[schema(example = "880e8400-e29b-51d4-a716-446655440003")]
+    pub id: String,
+    /// Name of the artist, or title of the album or track.
+    #[schema(example = "Bohemian Rhapsody")]
+    pub name: String,
+    /// Artist of the album or track.
+    #[schema(example = "Queen")]
+    pub artist: Option<String>,
+}
+
+/// Names suggested for a partly typed search, by kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Suggestions {
+    /// Artists, with the most tracks first.
+    pub artists: Vec<Suggestion>,
+    /// Albums, best match first.
+    pub albums: Vec<Suggestion>,
+    /// Tracks, best match first.
+    pub tracks: Vec<Suggestion>,
+}
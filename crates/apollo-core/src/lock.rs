@@ -0,0 +1,142 @@
+//! Fields that automatic tagging leaves alone.
+//!
+//! Fields are locked for all tracks with `locks.fields` in the
+//! configuration, or for single tracks in the library. Lookups, plugins and
+//! bulk edits make their changes to a track, and [`FieldLocks::restore`]
+//! then undoes those to locked fields, so hand-curated values survive being
+//! tagged again.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::edit::EditField;
+//! use apollo_core::lock::FieldLocks;
+//! use apollo_core::Track;
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//!
+//! let track = Track::new(
+//!     PathBuf::from("/music/track.mp3"),
+//!     "Bohemian Rhapsody".to_string(),
+//!     "Queen".to_string(),
+//!     Duration::from_secs(354),
+//! );
+//! let mut tagged = track.clone();
+//! tagged.title = "Bohemian Rhapsody (Remastered 2011)".to_string();
+//! tagged.year = Some(1975);
+//!
+//! let locks = FieldLocks::new([EditField::Title]);
+//! locks.restore(&track, &mut tagged).unwrap();
+//! assert_eq!(tagged.title, "Bohemian Rhapsody");
+//! assert_eq!(tagged.year, Some(1975));
+//! ```
+
+use crate::diff::TrackDiff;
+use crate::edit::EditField;
+use crate::error::Result;
+use crate::metadata::Track;
+
+/// A set of locked fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldLocks {
+    fields: Vec<EditField>,
+}
+
+impl FieldLocks {
+    /// Lock some fields.
+    #[must_use]
+    pub fn new(fields: impl IntoIterator<Item = EditField>) -> Self {
+        let mut locks = Self::default();
+        locks.extend(fields);
+        locks
+    }
+
+    /// Lock more fields.
+    pub fn extend(&mut self, fields: impl IntoIterator<Item = EditField>) {
+        for field in fields {
+            if !self.fields.contains(&field) {
+                self.fields.push(field);
+            }
+        }
+    }
+
+    /// Get the locked fields, in the order they were locked.
+    #[must_use]
+    pub fn fields(&self) -> &[EditField] {
+        &self.fields
+    }
+
+    /// Check if no field is locked.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Check if a field is locked.
+    #[must_use]
+    pub fn is_locked(&self, field: EditField) -> bool {
+        self.fields.contains(&field)
+    }
+
+    /// Undo the changes from `original` to `track` of locked fields.
+    ///
+    /// Returns the changes that were undone.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an old value no longer fits its field, in which
+    /// case the track is not modified.
+    pub fn restore(&self, original: &Track, track: &mut Track) -> Result<TrackDiff> {
+        let mut restored = TrackDiff::between(original, track);
+        restored.changes.retain(|change| {
+            self.fields
+                .iter()
+                .any(|field| field.track_field() == change.field)
+        });
+        restored.revert(track)?;
+        Ok(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn restore_locked_fields() {
+        let mut original = Track::new(
+            PathBuf::from("/music/time.flac"),
+            "Time".to_string(),
+            "Pink Floyd".to_string(),
+            Duration::from_secs(413),
+        );
+        original.genres = vec!["Art Rock".to_string()];
+        let mut tagged = original.clone();
+        tagged.genres = vec!["Progressive Rock".to_string()];
+        tagged.album_title = Some("The Dark Side of the Moon".to_string());
+        tagged.track_number = Some(4);
+
+        let mut locks = FieldLocks::new([EditField::Genre, EditField::Track]);
+        locks.extend([EditField::Genre]);
+        assert_eq!(locks.fields(), &[EditField::Genre, EditField::Track]);
+        assert!(!locks.is_locked(EditField::Album));
+
+        let restored = locks.restore(&original, &mut tagged).unwrap();
+        assert_eq!(restored.changes.len(), 2);
+        assert_eq!(tagged.genres, vec!["Art Rock".to_string()]);
+        assert_eq!(tagged.track_number, None);
+        assert_eq!(
+            tagged.album_title.as_deref(),
+            Some("The Dark Side of the Moon")
+        );
+
+        assert!(
+            FieldLocks::default()
+                .restore(&original, &mut tagged)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,12 +1,26 @@
 //! Metadata types for tracks, albums, and artists.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::time::Duration;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Album artist name used for compilations.
+pub const VARIOUS_ARTISTS: &str = "Various Artists";
+
+/// Check if an artist name denotes various artists, such as "Various Artists"
+/// or "VA".
+#[must_use]
+pub fn is_various_artists(name: &str) -> bool {
+    matches!(
+        name.trim().to_lowercase().as_str(),
+        "various artists" | "various" | "va" | "v.a." | "v/a"
+    )
+}
+
 /// Unique identifier for a track.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
@@ -57,6 +71,32 @@ impl std::fmt::Display for AlbumId {
     }
 }
 
+/// Unique identifier for an artist.
+///
+/// Artist IDs are derived from the artist name with [`ArtistId::from_name`],
+/// so tracks and albums can refer to their artist without storing an ID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[schema(example = "880e8400-e29b-51d4-a716-446655440003")]
+pub struct ArtistId(pub Uuid);
+
+impl ArtistId {
+    /// Derive the artist ID for an artist name.
+    ///
+    /// Names are compared case-insensitively and ignoring surrounding
+    /// whitespace, so "Queen" and " queen" have the same ID.
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        let normalized = name.trim().to_lowercase();
+        Self(Uuid::new_v5(&Uuid::NAMESPACE_OID, normalized.as_bytes()))
+    }
+}
+
+impl std::fmt::Display for ArtistId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// Audio format/codec.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
@@ -80,6 +120,43 @@ pub enum AudioFormat {
     Unknown,
 }
 
+impl AudioFormat {
+    /// Check if the format stores audio without lossy compression.
+    #[must_use]
+    pub const fn is_lossless(self) -> bool {
+        matches!(self, Self::Flac | Self::Wav | Self::Aiff)
+    }
+
+    /// Get the MIME type of files in the format, such as `audio/flac`.
+    #[must_use]
+    pub const fn mime_type(self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Flac => "audio/flac",
+            Self::Ogg | Self::Opus => "audio/ogg",
+            Self::Aac => "audio/mp4",
+            Self::Wav => "audio/wav",
+            Self::Aiff => "audio/aiff",
+            Self::Unknown => "application/octet-stream",
+        }
+    }
+
+    /// Guess the format from a file extension, such as `flac` or `m4a`.
+    #[must_use]
+    pub fn from_extension(ext: &str) -> Self {
+        match ext.to_lowercase().as_str() {
+            "mp3" => Self::Mp3,
+            "flac" => Self::Flac,
+            "ogg" | "oga" => Self::Ogg,
+            "opus" => Self::Opus,
+            "aac" | "m4a" | "mp4" => Self::Aac,
+            "wav" => Self::Wav,
+            "aif" | "aiff" => Self::Aiff,
+            _ => Self::Unknown,
+        }
+    }
+}
+
 impl std::fmt::Display for AudioFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -95,6 +172,129 @@ impl std::fmt::Display for AudioFormat {
     }
 }
 
+/// Type of album release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[schema(example = "album")]
+pub enum AlbumType {
+    /// A regular studio album
+    Album,
+    /// An extended play
+    Ep,
+    /// A single
+    Single,
+    /// A live recording
+    Live,
+    /// A compilation of previously released material
+    Compilation,
+    /// A film, TV, or game soundtrack
+    Soundtrack,
+}
+
+impl AlbumType {
+    /// Parse an album type name, ignoring case.
+    ///
+    /// Accepts the names used by `MusicBrainz` release groups and Discogs
+    /// format descriptions, such as "EP", "Compilation", or "LP".
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        let album_type = match name.trim().to_lowercase().as_str() {
+            "album" | "lp" => Self::Album,
+            "ep" => Self::Ep,
+            "single" => Self::Single,
+            "live" => Self::Live,
+            "compilation" => Self::Compilation,
+            "soundtrack" => Self::Soundtrack,
+            _ => return None,
+        };
+        Some(album_type)
+    }
+
+    /// Get the lowercase name, as stored in the database and used in queries.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Album => "album",
+            Self::Ep => "ep",
+            Self::Single => "single",
+            Self::Live => "live",
+            Self::Compilation => "compilation",
+            Self::Soundtrack => "soundtrack",
+        }
+    }
+}
+
+impl std::fmt::Display for AlbumType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Album => write!(f, "Album"),
+            Self::Ep => write!(f, "EP"),
+            Self::Single => write!(f, "Single"),
+            Self::Live => write!(f, "Live"),
+            Self::Compilation => write!(f, "Compilation"),
+            Self::Soundtrack => write!(f, "Soundtrack"),
+        }
+    }
+}
+
+/// Physical or digital medium an album was released on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[schema(example = "vinyl")]
+pub enum Media {
+    /// A compact disc, including variants such as SACD
+    Cd,
+    /// A vinyl record of any size
+    Vinyl,
+    /// A cassette tape
+    Cassette,
+    /// A digital release, such as a download
+    Digital,
+}
+
+impl Media {
+    /// Parse a media name, ignoring case.
+    ///
+    /// Accepts the format names used by `MusicBrainz` media and Discogs
+    /// releases, such as `12" Vinyl`, "Digital Media", or "File".
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        let media = match name.as_str() {
+            "cd" | "cdr" | "cd-r" | "enhanced cd" | "hdcd" | "sacd" | "hybrid sacd" | "shm-cd" => {
+                Self::Cd
+            }
+            "cassette" => Self::Cassette,
+            "digital" | "digital media" | "file" => Self::Digital,
+            _ if name.contains("vinyl") => Self::Vinyl,
+            _ => return None,
+        };
+        Some(media)
+    }
+
+    /// Get the lowercase name, as stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Cd => "cd",
+            Self::Vinyl => "vinyl",
+            Self::Cassette => "cassette",
+            Self::Digital => "digital",
+        }
+    }
+}
+
+impl std::fmt::Display for Media {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cd => write!(f, "CD"),
+            Self::Vinyl => write!(f, "Vinyl"),
+            Self::Cassette => write!(f, "Cassette"),
+            Self::Digital => write!(f, "Digital"),
+        }
+    }
+}
+
 /// Represents a single audio track in the library.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Track {
@@ -145,6 +345,10 @@ pub struct Track {
     /// Sample rate in Hz.
     #[schema(example = 44100)]
     pub sample_rate: Option<u32>,
+    /// Bits per sample (lossless formats only).
+    #[serde(default)]
+    #[schema(example = 16)]
+    pub bit_depth: Option<u8>,
     /// Number of audio channels.
     #[schema(example = 2)]
     pub channels: Option<u8>,
@@ -156,6 +360,31 @@ pub struct Track {
     /// [AcoustID](https://acoustid.org/) fingerprint identifier.
     #[schema(example = "a1b2c3d4-e5f6-7890-abcd-ef1234567890")]
     pub acoustid: Option<String>,
+    /// International Standard Recording Code.
+    #[serde(default)]
+    #[schema(example = "GBUM71029604")]
+    pub isrc: Option<String>,
+    /// [ReplayGain](https://wiki.hydrogenaud.io/index.php?title=ReplayGain_2.0_specification)
+    /// track gain in dB.
+    #[serde(default)]
+    #[schema(example = -7.85)]
+    pub rg_track_gain: Option<f64>,
+    /// Track peak for replay gain, as a fraction of full scale.
+    #[serde(default)]
+    #[schema(example = 0.988_547)]
+    pub rg_track_peak: Option<f64>,
+    /// Album replay gain in dB.
+    #[serde(default)]
+    #[schema(example = -8.12)]
+    pub rg_album_gain: Option<f64>,
+    /// Album peak for replay gain, as a fraction of full scale.
+    #[serde(default)]
+    #[schema(example = 1.0)]
+    pub rg_album_peak: Option<f64>,
+    /// Integrated loudness in LUFS.
+    #[serde(default)]
+    #[schema(example = -10.15)]
+    pub loudness_lufs: Option<f64>,
     /// When the track was added to the library.
     pub added_at: DateTime<Utc>,
     /// When the track metadata was last modified.
@@ -187,10 +416,17 @@ impl Track {
             duration,
             bitrate: None,
             sample_rate: None,
+            bit_depth: None,
             channels: None,
             format: AudioFormat::Unknown,
             musicbrainz_id: None,
             acoustid: None,
+            isrc: None,
+            rg_track_gain: None,
+            rg_track_peak: None,
+            rg_album_gain: None,
+            rg_album_peak: None,
+            loudness_lufs: None,
             added_at: now,
             modified_at: now,
             file_hash: String::new(),
@@ -198,6 +434,58 @@ impl Track {
     }
 }
 
+impl Track {
+    /// Get the ID of the track artist.
+    #[must_use]
+    pub fn artist_id(&self) -> ArtistId {
+        ArtistId::from_name(&self.artist)
+    }
+
+    /// Get the ID of the album artist, falling back to the track artist.
+    #[must_use]
+    pub fn album_artist_id(&self) -> ArtistId {
+        ArtistId::from_name(self.album_artist.as_deref().unwrap_or(&self.artist))
+    }
+}
+
+/// Listening statistics for a track.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TrackStats {
+    /// Rating from 1 to 5 stars, if rated.
+    #[schema(example = 4)]
+    pub rating: Option<u8>,
+    /// Whether the track is marked as a favorite.
+    pub favorite: bool,
+    /// Number of recorded plays.
+    #[schema(example = 12)]
+    pub play_count: u32,
+    /// When the track was last played.
+    pub last_played: Option<DateTime<Utc>>,
+}
+
+/// Cover art of an album.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artwork {
+    /// The image data.
+    pub data: Vec<u8>,
+    /// The MIME type, such as `image/jpeg`.
+    pub mime_type: String,
+    /// The URL the image was fetched from, if any.
+    pub source: Option<String>,
+}
+
+/// [Chromaprint](https://acoustid.org/chromaprint) fingerprint of a track's
+/// audio, as used to look it up on [AcoustID](https://acoustid.org/).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Fingerprint {
+    /// The compressed fingerprint.
+    pub fingerprint: String,
+    /// Duration of the fingerprinted audio in seconds.
+    pub duration_secs: u32,
+    /// When the fingerprint was computed.
+    pub computed_at: DateTime<Utc>,
+}
+
 /// Represents an album in the library.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Album {
@@ -224,6 +512,55 @@ pub struct Album {
     /// [MusicBrainz](https://musicbrainz.org/) release ID.
     #[schema(example = "6defd963-fe91-4550-b18e-82c685603c2b")]
     pub musicbrainz_id: Option<String>,
+    /// Type of release.
+    pub album_type: Option<AlbumType>,
+    /// Full release date.
+    #[schema(value_type = Option<String>, example = "1975-11-21")]
+    pub release_date: Option<NaiveDate>,
+    /// Country of release, as an ISO 3166-1 code.
+    #[schema(example = "GB")]
+    pub country: Option<String>,
+    /// Record label.
+    #[schema(example = "EMI")]
+    pub label: Option<String>,
+    /// Label catalog number.
+    #[schema(example = "EMTC 103")]
+    pub catalog_number: Option<String>,
+    /// Whether the album is a compilation of various artists.
+    #[serde(default)]
+    pub is_compilation: bool,
+    /// Comment that tells the album apart from others with the same artist
+    /// and title, as on `MusicBrainz`.
+    #[serde(default)]
+    #[schema(example = "40th anniversary")]
+    pub disambiguation: Option<String>,
+    /// Medium the album was released on.
+    #[serde(default)]
+    pub media: Option<Media>,
+    /// Edition of the album, such as a deluxe edition or a remaster.
+    #[serde(default)]
+    #[schema(example = "Deluxe Edition")]
+    pub edition: Option<String>,
+    /// Barcode of the release, usually a UPC or EAN.
+    #[serde(default)]
+    #[schema(example = "077774600125")]
+    pub barcode: Option<String>,
+    /// [Discogs](https://discogs.com/) release ID.
+    #[serde(default)]
+    #[schema(example = 367_084)]
+    pub discogs_id: Option<u64>,
+    /// Album replay gain in dB.
+    #[serde(default)]
+    #[schema(example = -8.12)]
+    pub rg_album_gain: Option<f64>,
+    /// Album peak for replay gain, as a fraction of full scale.
+    #[serde(default)]
+    #[schema(example = 1.0)]
+    pub rg_album_peak: Option<f64>,
+    /// Integrated loudness of the whole album in LUFS.
+    #[serde(default)]
+    #[schema(example = -10.4)]
+    pub loudness_lufs: Option<f64>,
     /// When the album was added to the library.
     pub added_at: DateTime<Utc>,
     /// When the album was last modified.
@@ -244,16 +581,67 @@ impl Album {
             track_count: 0,
             disc_count: 1,
             musicbrainz_id: None,
+            album_type: None,
+            release_date: None,
+            country: None,
+            label: None,
+            catalog_number: None,
+            is_compilation: false,
+            disambiguation: None,
+            media: None,
+            edition: None,
+            barcode: None,
+            discogs_id: None,
+            rg_album_gain: None,
+            rg_album_peak: None,
+            loudness_lufs: None,
             added_at: now,
             modified_at: now,
         }
     }
+
+    /// Check if the album looks like a compilation.
+    ///
+    /// An album is a compilation if it is already flagged or typed as one,
+    /// if its artist is "Various Artists", or if its tracks have at least
+    /// `min_artists` distinct artists. A `min_artists` below 2 disables the
+    /// artist count check.
+    #[must_use]
+    pub fn detect_compilation(&self, tracks: &[&Track], min_artists: usize) -> bool {
+        if self.is_compilation
+            || self.album_type == Some(AlbumType::Compilation)
+            || is_various_artists(&self.artist)
+        {
+            return true;
+        }
+        if min_artists < 2 {
+            return false;
+        }
+        let artists: HashSet<String> = tracks
+            .iter()
+            .map(|track| track.artist.trim().to_lowercase())
+            .collect();
+        artists.len() >= min_artists
+    }
+}
+
+impl Album {
+    /// Get the ID of the album artist.
+    #[must_use]
+    pub fn artist_id(&self) -> ArtistId {
+        ArtistId::from_name(&self.artist)
+    }
 }
 
 /// Represents an artist in the library.
+///
+/// Tracks and albums refer to artists by name; use [`Track::artist_id`],
+/// [`Track::album_artist_id`], and [`Album::artist_id`] to relate them.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Artist {
-    /// Artist name (primary identifier).
+    /// Unique identifier, derived from the name.
+    pub id: ArtistId,
+    /// Artist name.
     #[schema(example = "Queen")]
     pub name: String,
     /// Sort name (e.g., "Beatles, The").
@@ -262,18 +650,95 @@ pub struct Artist {
     /// [MusicBrainz](https://musicbrainz.org/) artist ID.
     #[schema(example = "0383dadf-2a4e-4d10-a46a-e9e041da8eb3")]
     pub musicbrainz_id: Option<String>,
+    /// Genre tags.
+    #[schema(example = json!(["Rock"]))]
+    pub genres: Vec<String>,
+    /// Short biography.
+    pub bio: Option<String>,
+    /// When the artist was added to the library.
+    pub added_at: DateTime<Utc>,
+    /// When the artist was last modified.
+    pub modified_at: DateTime<Utc>,
 }
 
 impl Artist {
-    /// Create a new artist.
+    /// Create a new artist with minimal required fields.
     #[must_use]
-    pub const fn new(name: String) -> Self {
+    pub fn new(name: String) -> Self {
+        let now = Utc::now();
         Self {
+            id: ArtistId::from_name(&name),
             name,
             sort_name: None,
             musicbrainz_id: None,
+            genres: Vec::new(),
+            bio: None,
+            added_at: now,
+            modified_at: now,
         }
     }
+
+    /// Create the artist of a track.
+    #[must_use]
+    pub fn from_track(track: &Track) -> Self {
+        let mut artist = Self::new(track.artist.clone());
+        artist.genres.clone_from(&track.genres);
+        artist
+    }
+
+    /// Create the artist of an album.
+    #[must_use]
+    pub fn from_album(album: &Album) -> Self {
+        let mut artist = Self::new(album.artist.clone());
+        artist.genres.clone_from(&album.genres);
+        artist
+    }
+
+    /// Set the sort name.
+    #[must_use]
+    pub fn with_sort_name(mut self, sort_name: impl Into<String>) -> Self {
+        self.sort_name = Some(sort_name.into());
+        self
+    }
+
+    /// Set the [MusicBrainz](https://musicbrainz.org/) artist ID.
+    #[must_use]
+    pub fn with_musicbrainz_id(mut self, musicbrainz_id: impl Into<String>) -> Self {
+        self.musicbrainz_id = Some(musicbrainz_id.into());
+        self
+    }
+
+    /// Set the genre tags.
+    #[must_use]
+    pub fn with_genres(mut self, genres: Vec<String>) -> Self {
+        self.genres = genres;
+        self
+    }
+
+    /// Set the biography.
+    #[must_use]
+    pub fn with_bio(mut self, bio: impl Into<String>) -> Self {
+        self.bio = Some(bio.into());
+        self
+    }
+
+    /// Get the name to sort by, preferring the sort name.
+    #[must_use]
+    pub fn sort_key(&self) -> &str {
+        self.sort_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Check if this artist is the track artist or album artist of a track.
+    #[must_use]
+    pub fn is_credited_on_track(&self, track: &Track) -> bool {
+        track.artist_id() == self.id || track.album_artist_id() == self.id
+    }
+
+    /// Check if this artist is the artist of an album.
+    #[must_use]
+    pub fn is_credited_on_album(&self, album: &Album) -> bool {
+        album.artist_id() == self.id
+    }
 }
 
 /// Custom serde module for Duration.
@@ -308,12 +773,12 @@ mod tests {
             PathBuf::from("/music/test.mp3"),
             "Test Song".to_string(),
             "Test Artist".to_string(),
-            Duration::from_secs(180),
+            Duration::from_mins(3),
         );
 
         assert_eq!(track.title, "Test Song");
         assert_eq!(track.artist, "Test Artist");
-        assert_eq!(track.duration, Duration::from_secs(180));
+        assert_eq!(track.duration, Duration::from_mins(3));
     }
 
     #[test]
@@ -322,7 +787,7 @@ mod tests {
             PathBuf::from("/music/test.mp3"),
             "Test Song".to_string(),
             "Test Artist".to_string(),
-            Duration::from_secs(180),
+            Duration::from_mins(3),
         );
 
         let json = serde_json::to_string(&track).unwrap();
@@ -333,6 +798,105 @@ mod tests {
         assert_eq!(track.duration, deserialized.duration);
     }
 
+    #[test]
+    fn audio_format_from_extension() {
+        assert_eq!(AudioFormat::from_extension("FLAC"), AudioFormat::Flac);
+        assert_eq!(AudioFormat::from_extension("m4a"), AudioFormat::Aac);
+        assert_eq!(AudioFormat::from_extension("txt"), AudioFormat::Unknown);
+        assert_eq!(AudioFormat::Flac.mime_type(), "audio/flac");
+        assert_eq!(AudioFormat::Opus.mime_type(), "audio/ogg");
+    }
+
+    #[test]
+    fn album_type_from_name() {
+        assert_eq!(AlbumType::from_name("EP"), Some(AlbumType::Ep));
+        assert_eq!(AlbumType::from_name("LP"), Some(AlbumType::Album));
+        assert_eq!(
+            AlbumType::from_name(" Compilation "),
+            Some(AlbumType::Compilation)
+        );
+        assert_eq!(AlbumType::from_name("Broadcast"), None);
+        assert_eq!(AlbumType::Soundtrack.as_str(), "soundtrack");
+    }
+
+    #[test]
+    fn media_from_name() {
+        assert_eq!(Media::from_name("CD"), Some(Media::Cd));
+        assert_eq!(Media::from_name("Hybrid SACD"), Some(Media::Cd));
+        assert_eq!(Media::from_name("12\" Vinyl"), Some(Media::Vinyl));
+        assert_eq!(Media::from_name("Digital Media"), Some(Media::Digital));
+        assert_eq!(Media::from_name("File"), Some(Media::Digital));
+        assert_eq!(Media::from_name("DVD"), None);
+        assert_eq!(Media::Vinyl.as_str(), "vinyl");
+        assert_eq!(Media::Cd.to_string(), "CD");
+    }
+
+    #[test]
+    fn detect_compilation() {
+        let tracks: Vec<Track> = ["Queen", "Bowie", "Blur", "Queen"]
+            .iter()
+            .map(|artist| {
+                Track::new(
+                    PathBuf::from("/music/test.mp3"),
+                    "Song".to_string(),
+                    (*artist).to_string(),
+                    Duration::from_mins(3),
+                )
+            })
+            .collect();
+        let tracks: Vec<&Track> = tracks.iter().collect();
+
+        let album = Album::new("Hits".to_string(), "Queen".to_string());
+        assert!(album.detect_compilation(&tracks, 3));
+        assert!(!album.detect_compilation(&tracks, 4));
+        assert!(!album.detect_compilation(&tracks, 0));
+
+        let album = Album::new("Hits".to_string(), "VA".to_string());
+        assert!(album.detect_compilation(&[], 3));
+        assert!(is_various_artists(" Various Artists "));
+        assert!(!is_various_artists("Various Positions"));
+    }
+
+    #[test]
+    fn artist_id_from_name() {
+        assert_eq!(ArtistId::from_name("Queen"), ArtistId::from_name(" queen "));
+        assert_ne!(ArtistId::from_name("Queen"), ArtistId::from_name("Queens"));
+    }
+
+    #[test]
+    fn artist_builder() {
+        let artist = Artist::new("The Beatles".to_string())
+            .with_sort_name("Beatles, The")
+            .with_musicbrainz_id("b10bbbfc-cf9e-42e0-be17-e2c3e1d2600d")
+            .with_genres(vec!["Rock".to_string()])
+            .with_bio("A band from Liverpool.");
+
+        assert_eq!(artist.id, ArtistId::from_name("the beatles"));
+        assert_eq!(artist.sort_key(), "Beatles, The");
+        assert_eq!(artist.genres, vec!["Rock"]);
+        assert_eq!(artist.bio.as_deref(), Some("A band from Liverpool."));
+    }
+
+    #[test]
+    fn artist_relationships() {
+        let mut track = Track::new(
+            PathBuf::from("/music/test.mp3"),
+            "Under Pressure".to_string(),
+            "David Bowie".to_string(),
+            Duration::from_mins(4),
+        );
+        track.album_artist = Some("Queen".to_string());
+        let album = Album::new("Hot Space".to_string(), "Queen".to_string());
+
+        let queen = Artist::from_album(&album);
+        let bowie = Artist::from_track(&track);
+        assert!(queen.is_credited_on_album(&album));
+        assert!(queen.is_credited_on_track(&track));
+        assert!(bowie.is_credited_on_track(&track));
+        assert!(!bowie.is_credited_on_album(&album));
+        assert_eq!(track.album_artist_id(), album.artist_id());
+    }
+
     /// Strategy for generating valid audio formats.
     fn audio_format_strategy() -> impl Strategy<Value = AudioFormat> {
         prop_oneof![
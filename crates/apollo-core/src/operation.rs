@@ -0,0 +1,243 @@
+//! Reversible changes to the library.
+//!
+//! Organizing files and editing many tracks at once are recorded as an
+//! [`Operation`] in the library's operation log: the files that were moved
+//! or copied, and the fields of each track that changed. Undoing an
+//! operation reverts its steps, last step first.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::operation::{Operation, OperationKind};
+//! use apollo_core::Track;
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//!
+//! let old = Track::new(
+//!     PathBuf::from("/incoming/track.mp3"),
+//!     "Yellow".to_string(),
+//!     "Coldplay".to_string(),
+//!     Duration::from_secs(266),
+//! );
+//! let mut new = old.clone();
+//! new.path = PathBuf::from("/music/Coldplay/Yellow.mp3");
+//!
+//! let mut operation = Operation::new(OperationKind::Organize, "organize 1 track");
+//! operation.record_file(&old.path, &new.path, true);
+//! operation.record_track(&old, &new, false);
+//! assert_eq!(operation.steps.len(), 2);
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::diff::TrackDiff;
+use crate::metadata::{Track, TrackId};
+
+/// What kind of command made an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    /// Files copied or moved by `apollo organize`.
+    Organize,
+    /// Metadata edited by `apollo tag`.
+    Tag,
+}
+
+impl OperationKind {
+    /// Parse an operation kind from its name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "organize" => Some(Self::Organize),
+            "tag" => Some(Self::Tag),
+            _ => None,
+        }
+    }
+
+    /// Get the name, as stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Organize => "organize",
+            Self::Tag => "tag",
+        }
+    }
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single reversible change made by an operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OperationStep {
+    /// A file was copied or moved.
+    File {
+        /// Where the file was.
+        source: PathBuf,
+        /// Where the file was copied or moved to.
+        destination: PathBuf,
+        /// Whether the file was moved rather than copied.
+        moved: bool,
+    },
+    /// Fields of a track changed in the library.
+    Track {
+        /// The changed track.
+        id: TrackId,
+        /// The changed fields, with their old and new values.
+        diff: TrackDiff,
+        /// Whether the new values were also written to the file's tags.
+        #[serde(default)]
+        tags_written: bool,
+    },
+}
+
+/// A recorded change to the library that can be undone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+    /// ID in the operation log; 0 until the operation is recorded.
+    pub id: i64,
+    /// Command that made the operation.
+    pub kind: OperationKind,
+    /// What the operation did, for people to read.
+    pub description: String,
+    /// When the operation was made.
+    pub created_at: DateTime<Utc>,
+    /// When the operation was undone, if it was.
+    pub undone_at: Option<DateTime<Utc>>,
+    /// Changes made, in order.
+    pub steps: Vec<OperationStep>,
+}
+
+impl Operation {
+    /// Start an operation without any steps.
+    #[must_use]
+    pub fn new(kind: OperationKind, description: impl Into<String>) -> Self {
+        Self {
+            id: 0,
+            kind,
+            description: description.into(),
+            created_at: Utc::now(),
+            undone_at: None,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Record that a file was copied or moved.
+    pub fn record_file(&mut self, source: &Path, destination: &Path, moved: bool) {
+        self.steps.push(OperationStep::File {
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            moved,
+        });
+    }
+
+    /// Record that a track changed from `old` to `new`.
+    ///
+    /// Nothing is recorded if no fields changed.
+    pub fn record_track(&mut self, old: &Track, new: &Track, tags_written: bool) {
+        let diff = TrackDiff::between(old, new);
+        if !diff.is_empty() {
+            self.steps.push(OperationStep::Track {
+                id: old.id.clone(),
+                diff,
+                tags_written,
+            });
+        }
+    }
+
+    /// Check if the operation made no changes.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Check if the operation has been undone.
+    #[must_use]
+    pub const fn is_undone(&self) -> bool {
+        self.undone_at.is_some()
+    }
+
+    /// Count the files copied or moved.
+    #[must_use]
+    pub fn file_count(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step, OperationStep::File { .. }))
+            .count()
+    }
+
+    /// Count the track changes.
+    #[must_use]
+    pub fn track_count(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step, OperationStep::Track { .. }))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn track() -> Track {
+        Track::new(
+            PathBuf::from("/incoming/clocks.flac"),
+            "Clocks".to_string(),
+            "Coldplay".to_string(),
+            Duration::from_secs(307),
+        )
+    }
+
+    #[test]
+    fn test_record_steps() {
+        let old = track();
+        let mut new = old.clone();
+        new.path = PathBuf::from("/music/Coldplay/Clocks.flac");
+
+        let mut operation = Operation::new(OperationKind::Organize, "organize");
+        assert!(operation.is_empty());
+        operation.record_file(&old.path, &new.path, false);
+        operation.record_track(&old, &new, false);
+        // Unchanged tracks are left out
+        operation.record_track(&new, &new, false);
+
+        assert_eq!(operation.file_count(), 1);
+        assert_eq!(operation.track_count(), 1);
+        let OperationStep::Track { id, diff, .. } = &operation.steps[1] else {
+            panic!("expected a track step");
+        };
+        assert_eq!(id, &old.id);
+        assert!(diff.get("path").is_some());
+    }
+
+    #[test]
+    fn test_steps_roundtrip_json() {
+        let old = track();
+        let mut new = old.clone();
+        new.year = Some(2002);
+
+        let mut operation = Operation::new(OperationKind::Tag, "tag year=2002");
+        operation.record_track(&old, &new, true);
+
+        let json = serde_json::to_string(&operation.steps).unwrap();
+        let steps: Vec<OperationStep> = serde_json::from_str(&json).unwrap();
+        assert_eq!(steps, operation.steps);
+    }
+
+    #[test]
+    fn test_kind_names() {
+        for kind in [OperationKind::Organize, OperationKind::Tag] {
+            assert_eq!(OperationKind::from_name(kind.as_str()), Some(kind));
+        }
+        assert_eq!(OperationKind::from_name("import"), None);
+    }
+}
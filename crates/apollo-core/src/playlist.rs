@@ -15,15 +15,37 @@
 //! - `artist:Beatles` - All Beatles songs
 //! - `year:2020..2024` - Songs from 2020-2024
 //! - `genre:rock` - All rock songs
-//! - `added:30d` - Songs added in the last 30 days
+//! - `added:-30d` - Songs added in the last 30 days
+//!
+//! ## Sorting
+//!
+//! Smart playlists are sorted by a [`PlaylistSort`] preset, or by a
+//! [`SortSpec`] parsed from `sort:` terms, e.g. `genre:rock sort:year-`.
+//!
+//! # Versions
+//!
+//! Before the tracks of a static playlist change, the library keeps the
+//! tracks it had as a [`PlaylistVersion`], so that a change such as clearing
+//! the playlist can be undone by restoring the version.
+//!
+//! # Playlist Files
+//!
+//! Playlists can be exported as M3U8 or XSPF files with [`write_playlist`],
+//! and imported from M3U files with [`read_m3u`].
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::metadata::TrackId;
-use crate::query::Query;
+use crate::query::{Query, SortSpec};
+
+mod file;
+
+pub use file::{PlaylistEntry, PlaylistFormat, read_m3u, relative_path, write_playlist};
 
 /// Unique identifier for a playlist.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -132,10 +154,13 @@ pub struct Playlist {
     pub query: Option<Query>,
     /// Sort order for smart playlists.
     pub sort: PlaylistSort,
+    /// Custom sort order for smart playlists, overriding `sort`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_spec: Option<SortSpec>,
     /// Limits for smart playlists.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<PlaylistLimit>,
-    /// Track IDs for static playlists.
+    /// Track IDs for static playlists, each at most once.
     pub track_ids: Vec<TrackId>,
     /// When the playlist was created.
     pub created_at: DateTime<Utc>,
@@ -155,6 +180,7 @@ impl Playlist {
             kind: PlaylistKind::Static,
             query: None,
             sort: PlaylistSort::default(),
+            sort_spec: None,
             limit: None,
             track_ids: Vec::new(),
             created_at: now,
@@ -173,6 +199,7 @@ impl Playlist {
             kind: PlaylistKind::Smart,
             query: Some(query),
             sort: PlaylistSort::default(),
+            sort_spec: None,
             limit: None,
             track_ids: Vec::new(),
             created_at: now,
@@ -194,6 +221,31 @@ impl Playlist {
         self
     }
 
+    /// Set a custom sort order, overriding the sort preset.
+    ///
+    /// An empty spec clears the custom sort order.
+    #[must_use]
+    pub fn with_sort_spec(mut self, spec: SortSpec) -> Self {
+        self.sort_spec = (!spec.is_empty()).then_some(spec);
+        self
+    }
+
+    /// Get the sort order to apply, from the custom spec or the preset.
+    #[must_use]
+    pub fn effective_sort(&self) -> SortSpec {
+        self.sort_spec
+            .clone()
+            .unwrap_or_else(|| SortSpec::from(self.sort))
+    }
+
+    /// Describe the sort order, as the custom spec or the preset name.
+    #[must_use]
+    pub fn sort_label(&self) -> String {
+        self.sort_spec
+            .as_ref()
+            .map_or_else(|| self.sort.to_string(), ToString::to_string)
+    }
+
     /// Set the limits.
     #[must_use]
     pub const fn with_limit(mut self, limit: PlaylistLimit) -> Self {
@@ -217,14 +269,16 @@ impl Playlist {
         self
     }
 
-    /// Add a track to a static playlist.
+    /// Add a track to a static playlist, unless it already has the track.
     ///
-    /// Does nothing for smart playlists.
-    pub fn add_track(&mut self, track_id: TrackId) {
-        if self.kind == PlaylistKind::Static {
-            self.track_ids.push(track_id);
-            self.modified_at = Utc::now();
+    /// Returns whether the track was added. Does nothing for smart playlists.
+    pub fn add_track(&mut self, track_id: TrackId) -> bool {
+        if self.kind != PlaylistKind::Static || self.track_ids.contains(&track_id) {
+            return false;
         }
+        self.track_ids.push(track_id);
+        self.modified_at = Utc::now();
+        true
     }
 
     /// Remove a track from a static playlist.
@@ -237,6 +291,66 @@ impl Playlist {
         }
     }
 
+    /// Add tracks to a static playlist, in order, skipping tracks it
+    /// already has.
+    ///
+    /// Returns the number of tracks added. Does nothing for smart playlists.
+    pub fn merge_tracks(&mut self, track_ids: impl IntoIterator<Item = TrackId>) -> usize {
+        if self.kind != PlaylistKind::Static {
+            return 0;
+        }
+        let mut seen: HashSet<TrackId> = self.track_ids.iter().cloned().collect();
+        let before = self.track_ids.len();
+        for track_id in track_ids {
+            if seen.insert(track_id.clone()) {
+                self.track_ids.push(track_id);
+            }
+        }
+        let added = self.track_ids.len() - before;
+        if added > 0 {
+            self.modified_at = Utc::now();
+        }
+        added
+    }
+
+    /// Remove tracks that the playlist has more than once, keeping the
+    /// first of each.
+    ///
+    /// Returns the number of tracks removed.
+    pub fn dedupe(&mut self) -> usize {
+        let mut seen = HashSet::new();
+        let before = self.track_ids.len();
+        self.track_ids.retain(|id| seen.insert(id.clone()));
+        let removed = before - self.track_ids.len();
+        if removed > 0 {
+            self.modified_at = Utc::now();
+        }
+        removed
+    }
+
+    /// Copy the playlist under a new name and ID.
+    #[must_use]
+    pub fn duplicate(&self, name: impl Into<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: PlaylistId::new(),
+            name: name.into(),
+            created_at: now,
+            modified_at: now,
+            ..self.clone()
+        }
+    }
+
+    /// Copy the playlist as a static playlist of the given tracks, such as
+    /// the tracks a smart playlist currently has.
+    #[must_use]
+    pub fn snapshot(&self, name: impl Into<String>, track_ids: Vec<TrackId>) -> Self {
+        let mut playlist = Self::new_static(name);
+        playlist.description.clone_from(&self.description);
+        playlist.track_ids = track_ids;
+        playlist
+    }
+
     /// Check if this is a smart playlist.
     #[must_use]
     pub fn is_smart(&self) -> bool {
@@ -260,6 +374,21 @@ impl Playlist {
     }
 }
 
+/// The tracks a static playlist had before they changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PlaylistVersion {
+    /// ID of the version, increasing with each version kept.
+    #[schema(example = 12)]
+    pub id: i64,
+    /// Playlist the version is of.
+    #[schema(value_type = String, example = "770e8400-e29b-41d4-a716-446655440002")]
+    pub playlist_id: PlaylistId,
+    /// When the tracks changed.
+    pub created_at: DateTime<Utc>,
+    /// Tracks the playlist had, in order.
+    pub track_ids: Vec<TrackId>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,24 +415,73 @@ mod tests {
         assert!(playlist.query.is_some());
     }
 
+    #[test]
+    fn test_merge_tracks() {
+        let [a, b, c] = [TrackId::new(), TrackId::new(), TrackId::new()];
+        let mut playlist = Playlist::new_static("Test");
+        playlist.add_track(a.clone());
+
+        let added = playlist.merge_tracks([b.clone(), a.clone(), c.clone(), b.clone()]);
+        assert_eq!(added, 2);
+        assert_eq!(playlist.track_ids, vec![a.clone(), b, c]);
+
+        let mut smart = Playlist::new_smart("Smart", Query::parse("artist:Beatles").unwrap());
+        assert_eq!(smart.merge_tracks([a]), 0);
+        assert!(smart.track_ids.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_and_snapshot() {
+        let query = Query::parse("artist:Beatles").unwrap();
+        let smart = Playlist::new_smart("Beatles", query)
+            .with_description("All of them")
+            .with_max_tracks(10);
+
+        let copy = smart.duplicate("Beatles 2");
+        assert_ne!(copy.id, smart.id);
+        assert_eq!(copy.name, "Beatles 2");
+        assert!(copy.is_smart());
+        assert!(copy.query.is_some());
+        assert_eq!(copy.limit.and_then(|limit| limit.max_tracks), Some(10));
+
+        let track_id = TrackId::new();
+        let snapshot = smart.snapshot("Beatles Now", vec![track_id.clone()]);
+        assert!(snapshot.is_static());
+        assert!(snapshot.query.is_none());
+        assert_eq!(snapshot.description.as_deref(), Some("All of them"));
+        assert_eq!(snapshot.track_ids, vec![track_id]);
+    }
+
     #[test]
     fn test_add_track_to_static() {
         let mut playlist = Playlist::new_static("Test");
         let track_id = TrackId::new();
 
-        playlist.add_track(track_id.clone());
+        assert!(playlist.add_track(track_id.clone()));
+        assert!(!playlist.add_track(track_id.clone()));
 
         assert_eq!(playlist.track_count(), 1);
         assert_eq!(playlist.track_ids[0], track_id);
     }
 
+    #[test]
+    fn test_dedupe() {
+        let [a, b, c] = [TrackId::new(), TrackId::new(), TrackId::new()];
+        let mut playlist = Playlist::new_static("Test");
+        playlist.track_ids = vec![a.clone(), b.clone(), a.clone(), c.clone(), b.clone()];
+
+        assert_eq!(playlist.dedupe(), 2);
+        assert_eq!(playlist.track_ids, vec![a, b, c]);
+        assert_eq!(playlist.dedupe(), 0);
+    }
+
     #[test]
     fn test_add_track_to_smart_noop() {
         let query = Query::parse("artist:Test").unwrap();
         let mut playlist = Playlist::new_smart("Test", query);
         let track_id = TrackId::new();
 
-        playlist.add_track(track_id);
+        assert!(!playlist.add_track(track_id));
 
         // Should not add track to smart playlist
         assert_eq!(playlist.track_count(), 0);
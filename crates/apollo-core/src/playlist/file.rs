@@ -0,0 +1,405 @@
+//! Playlist files, for sharing playlists with other players.
+//!
+//! Playlists are written as extended M3U in UTF-8 (`.m3u8`) or as
+//! [XSPF](https://xspf.org/), and read from M3U or M3U8. Paths in a
+//! playlist file are either absolute or relative to the file's directory.
+
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::metadata::Track;
+
+/// A playlist file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    /// Extended M3U in UTF-8.
+    M3u8,
+    /// XML Shareable Playlist Format.
+    Xspf,
+}
+
+impl PlaylistFormat {
+    /// Guess the format of a playlist file from its extension.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        ext.parse().ok()
+    }
+
+    /// Get the file extension for this format.
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::M3u8 => "m3u8",
+            Self::Xspf => "xspf",
+        }
+    }
+}
+
+impl FromStr for PlaylistFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "m3u" | "m3u8" => Ok(Self::M3u8),
+            "xspf" => Ok(Self::Xspf),
+            _ => Err(Error::Validation(format!(
+                "unknown playlist format: {s} (expected m3u8 or xspf)"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PlaylistFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// An entry read from a playlist file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    /// Path to the file, resolved against the playlist's directory.
+    ///
+    /// Entries that are not local files, such as stream URLs, keep their
+    /// location as written.
+    pub path: PathBuf,
+    /// Artist from the `#EXTINF` line, if any.
+    pub artist: Option<String>,
+    /// Title from the `#EXTINF` line, if any.
+    pub title: Option<String>,
+}
+
+/// Write tracks as a playlist file.
+///
+/// With `relative_to`, paths are written relative to that directory, which
+/// should be where the playlist file is saved. Track paths and `relative_to`
+/// are expected to be absolute.
+#[must_use]
+pub fn write_playlist(
+    format: PlaylistFormat,
+    name: &str,
+    tracks: &[Track],
+    relative_to: Option<&Path>,
+) -> String {
+    let location = |track: &Track| {
+        relative_to.map_or_else(
+            || track.path.clone(),
+            |base| relative_path(&track.path, base),
+        )
+    };
+    match format {
+        PlaylistFormat::M3u8 => {
+            let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", single_line(name));
+            for track in tracks {
+                let _ = write!(
+                    out,
+                    "#EXTINF:{},{} - {}\n{}\n",
+                    track.duration.as_secs(),
+                    single_line(&track.artist),
+                    single_line(&track.title),
+                    location(track).display()
+                );
+            }
+            out
+        }
+        PlaylistFormat::Xspf => {
+            let mut out = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+            );
+            let _ = writeln!(out, "  <title>{}</title>", xml_escape(name));
+            out.push_str("  <trackList>\n");
+            for track in tracks {
+                out.push_str("    <track>\n");
+                let element = |out: &mut String, tag: &str, text: &str| {
+                    let _ = writeln!(out, "      <{tag}>{}</{tag}>", xml_escape(text));
+                };
+                element(&mut out, "location", &file_uri(&location(track)));
+                element(&mut out, "title", &track.title);
+                element(&mut out, "creator", &track.artist);
+                if let Some(album) = &track.album_title {
+                    element(&mut out, "album", album);
+                }
+                if let Some(number) = track.track_number {
+                    element(&mut out, "trackNum", &number.to_string());
+                }
+                element(
+                    &mut out,
+                    "duration",
+                    &track.duration.as_millis().to_string(),
+                );
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+            out
+        }
+    }
+}
+
+/// Read the entries of an M3U or M3U8 playlist.
+///
+/// Relative paths are resolved against `base`, the playlist's directory.
+#[must_use]
+pub fn read_m3u(text: &str, base: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut info: Option<(Option<String>, Option<String>)> = None;
+
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<seconds>,<artist> - <title>
+            let display = extinf.split_once(',').map_or("", |(_, display)| display);
+            info = Some(match display.split_once(" - ") {
+                Some((artist, title)) => (non_empty(artist), non_empty(title)),
+                None => (None, non_empty(display)),
+            });
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (artist, title) = info.take().unwrap_or_default();
+        entries.push(PlaylistEntry {
+            path: resolve_location(line, base),
+            artist,
+            title,
+        });
+    }
+
+    entries
+}
+
+/// Get the path to `path` from the directory `base`.
+///
+/// Both paths are compared as written, without resolving symbolic links.
+/// If they share no root, `path` is returned unchanged.
+#[must_use]
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path = normalize(path);
+    let base = normalize(base);
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+
+    if path_components.peek() != base_components.peek() {
+        return path;
+    }
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
+
+    base_components
+        .map(|_| Component::ParentDir)
+        .chain(path_components)
+        .collect()
+}
+
+/// Resolve a playlist location to a path.
+fn resolve_location(location: &str, base: &Path) -> PathBuf {
+    if let Some(path) = location.strip_prefix("file://") {
+        // file:///music/a.mp3 or file://localhost/music/a.mp3
+        let path = path.strip_prefix("localhost").unwrap_or(path);
+        let path = urlencoding::decode(path).map_or_else(|_| path.to_string(), Cow::into_owned);
+        return normalize(Path::new(&path));
+    }
+    if location.contains("://") {
+        return PathBuf::from(location);
+    }
+    normalize(&base.join(location))
+}
+
+/// Remove `.` components and resolve `..` components without touching the
+/// file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Format a path as a URI: absolute paths as `file://` URIs, relative
+/// paths as relative references.
+fn file_uri(path: &Path) -> String {
+    let segments = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(segment) => {
+                Some(urlencoding::encode(&segment.to_string_lossy()).into_owned())
+            }
+            Component::ParentDir => Some("..".to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    if path.has_root() {
+        format!("file:///{segments}")
+    } else {
+        segments
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Keep a value on one line of an M3U file.
+fn single_line(text: &str) -> String {
+    text.replace(['\n', '\r'], " ")
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn track(path: &str, title: &str) -> Track {
+        let mut track = Track::new(
+            PathBuf::from(path),
+            title.to_string(),
+            "Radiohead".to_string(),
+            Duration::from_secs(238),
+        );
+        track.album_title = Some("OK Computer".to_string());
+        track
+    }
+
+    #[test]
+    fn test_write_m3u8() {
+        let tracks = [
+            track("/music/Radiohead/01 Airbag.flac", "Airbag"),
+            track(
+                "/music/Radiohead/02 Paranoid Android.flac",
+                "Paranoid Android",
+            ),
+        ];
+        assert_eq!(
+            write_playlist(
+                PlaylistFormat::M3u8,
+                "Road Trip",
+                &tracks,
+                Some(Path::new("/music/playlists"))
+            ),
+            "#EXTM3U\n#PLAYLIST:Road Trip\n\
+             #EXTINF:238,Radiohead - Airbag\n../Radiohead/01 Airbag.flac\n\
+             #EXTINF:238,Radiohead - Paranoid Android\n../Radiohead/02 Paranoid Android.flac\n"
+        );
+    }
+
+    #[test]
+    fn test_write_xspf() {
+        let tracks = [track("/music/R&B/01 Airbag.flac", "Airbag <Live>")];
+        let xspf = write_playlist(PlaylistFormat::Xspf, "Mine", &tracks, None);
+        assert!(xspf.contains("<title>Mine</title>"));
+        assert!(xspf.contains("<location>file:///music/R%26B/01%20Airbag.flac</location>"));
+        assert!(xspf.contains("<title>Airbag &lt;Live&gt;</title>"));
+        assert!(xspf.contains("<album>OK Computer</album>"));
+        assert!(xspf.contains("<duration>238000</duration>"));
+
+        let xspf = write_playlist(
+            PlaylistFormat::Xspf,
+            "Mine",
+            &tracks,
+            Some(Path::new("/music/R&B")),
+        );
+        assert!(xspf.contains("<location>01%20Airbag.flac</location>"));
+    }
+
+    #[test]
+    fn test_read_m3u() {
+        let text = "\u{feff}#EXTM3U\n\
+                    #EXTINF:238,Radiohead - Airbag\n\
+                    ../Radiohead/01 Airbag.flac\n\
+                    \n\
+                    # a comment\n\
+                    /music/Other/track.mp3\n\
+                    #EXTINF:-1,Live Stream\n\
+                    http://example.com/stream\n\
+                    file:///music/With%20Space.ogg\n";
+        let entries = read_m3u(text, Path::new("/music/playlists"));
+        assert_eq!(
+            entries,
+            vec![
+                PlaylistEntry {
+                    path: PathBuf::from("/music/Radiohead/01 Airbag.flac"),
+                    artist: Some("Radiohead".to_string()),
+                    title: Some("Airbag".to_string()),
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("/music/Other/track.mp3"),
+                    artist: None,
+                    title: None,
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("http://example.com/stream"),
+                    artist: None,
+                    title: Some("Live Stream".to_string()),
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("/music/With Space.ogg"),
+                    artist: None,
+                    title: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let relative = |path: &str, base: &str| relative_path(Path::new(path), Path::new(base));
+        assert_eq!(
+            relative("/music/a/b.mp3", "/music/a"),
+            PathBuf::from("b.mp3")
+        );
+        assert_eq!(
+            relative("/music/a/b.mp3", "/music/lists/x"),
+            PathBuf::from("../../a/b.mp3")
+        );
+        assert_eq!(
+            relative("/music/a/b.mp3", "/music/./a/"),
+            PathBuf::from("b.mp3")
+        );
+        assert_eq!(
+            relative("music/a.mp3", "/music"),
+            PathBuf::from("music/a.mp3")
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("list.M3U")),
+            Some(PlaylistFormat::M3u8)
+        );
+        assert_eq!(
+            PlaylistFormat::from_path(Path::new("list.xspf")),
+            Some(PlaylistFormat::Xspf)
+        );
+        assert_eq!(PlaylistFormat::from_path(Path::new("list.txt")), None);
+        assert_eq!(PlaylistFormat::Xspf.to_string(), "xspf");
+    }
+}
@@ -0,0 +1,253 @@
+//! Where track metadata came from.
+//!
+//! A track's [`Provenance`] records, for each field, which
+//! [`MetadataSource`] last set it and when. Fields without a recorded
+//! source have the value read from the file's tags. Knowing which fields
+//! were edited by hand lets automatic tagging leave them alone, rather than
+//! overwriting a correction with the data it corrected.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::provenance::{MetadataSource, Provenance};
+//! use apollo_core::{Track, TrackDiff};
+//! use std::path::PathBuf;
+//! use std::time::Duration;
+//!
+//! let track = Track::new(
+//!     PathBuf::from("/music/track.mp3"),
+//!     "Bohemian Rapsody".to_string(),
+//!     "Queen".to_string(),
+//!     Duration::from_secs(354),
+//! );
+//! let mut edited = track.clone();
+//! edited.title = "Bohemian Rhapsody".to_string();
+//!
+//! let mut provenance = Provenance::default();
+//! provenance.record(&TrackDiff::between(&track, &edited), MetadataSource::Manual);
+//! assert_eq!(provenance.source("title"), MetadataSource::Manual);
+//! assert_eq!(provenance.source("artist"), MetadataSource::File);
+//!
+//! // Tagging from MusicBrainz keeps the title that was edited by hand
+//! let mut tagged = edited.clone();
+//! tagged.title = "Bohemian Rhapsody (Remastered)".to_string();
+//! tagged.year = Some(1975);
+//! provenance.keep_manual_edits(&edited, &mut tagged).unwrap();
+//! assert_eq!(tagged.title, "Bohemian Rhapsody");
+//! assert_eq!(tagged.year, Some(1975));
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use utoipa::ToSchema;
+
+use crate::diff::TrackDiff;
+use crate::error::Result;
+use crate::metadata::Track;
+
+/// Where the value of a track field came from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataSource {
+    /// Read from the tags of the audio file.
+    #[default]
+    File,
+    /// Looked up on [MusicBrainz](https://musicbrainz.org/).
+    MusicBrainz,
+    /// Looked up on [Discogs](https://discogs.com/).
+    Discogs,
+    /// Set by a plugin's import hook.
+    Plugin,
+    /// Edited by hand.
+    Manual,
+}
+
+impl MetadataSource {
+    /// Parse a source from its name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "file" => Some(Self::File),
+            "musicbrainz" => Some(Self::MusicBrainz),
+            "discogs" => Some(Self::Discogs),
+            "plugin" => Some(Self::Plugin),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+
+    /// Get the name, as stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::MusicBrainz => "musicbrainz",
+            Self::Discogs => "discogs",
+            Self::Plugin => "plugin",
+            Self::Manual => "manual",
+        }
+    }
+}
+
+impl fmt::Display for MetadataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where one field of a track came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FieldSource {
+    /// Source of the field's value.
+    pub source: MetadataSource,
+    /// When the source set the field.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Where the fields of a track came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Provenance {
+    /// Sources by field name, as in the track's JSON representation.
+    ///
+    /// Fields that are not listed came from the file.
+    pub fields: BTreeMap<String, FieldSource>,
+}
+
+impl Provenance {
+    /// Get the provenance of the fields that a diff changes.
+    #[must_use]
+    pub fn from_diff(diff: &TrackDiff, source: MetadataSource) -> Self {
+        let mut provenance = Self::default();
+        provenance.record(diff, source);
+        provenance
+    }
+
+    /// Check if no field has a recorded source.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Get the source of a field.
+    #[must_use]
+    pub fn source(&self, field: &str) -> MetadataSource {
+        self.fields
+            .get(field)
+            .map_or(MetadataSource::File, |field| field.source)
+    }
+
+    /// Check if a field was last edited by hand.
+    #[must_use]
+    pub fn is_manual(&self, field: &str) -> bool {
+        self.source(field) == MetadataSource::Manual
+    }
+
+    /// Note that a source set the fields that a diff changes.
+    pub fn record(&mut self, diff: &TrackDiff, source: MetadataSource) {
+        let updated_at = Utc::now();
+        for change in &diff.changes {
+            self.fields
+                .insert(change.field.clone(), FieldSource { source, updated_at });
+        }
+    }
+
+    /// Add the fields of another provenance, which wins where both have a
+    /// field.
+    pub fn merge(&mut self, other: Self) {
+        self.fields.extend(other.fields);
+    }
+
+    /// Undo the changes from `original` to `track` of fields that were
+    /// edited by hand.
+    ///
+    /// Returns the changes that were undone.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an old value no longer fits its field, in which
+    /// case the track is not modified.
+    pub fn keep_manual_edits(&self, original: &Track, track: &mut Track) -> Result<TrackDiff> {
+        let mut kept = TrackDiff::between(original, track);
+        kept.changes.retain(|change| self.is_manual(&change.field));
+        kept.revert(track)?;
+        Ok(kept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn track() -> Track {
+        Track::new(
+            PathBuf::from("/music/time.flac"),
+            "Time".to_string(),
+            "Pink Floyd".to_string(),
+            Duration::from_secs(413),
+        )
+    }
+
+    #[test]
+    fn record_changed_fields() {
+        let old = track();
+        let mut new = old.clone();
+        new.year = Some(1973);
+        new.genres = vec!["Rock".to_string()];
+
+        let mut provenance =
+            Provenance::from_diff(&TrackDiff::between(&old, &new), MetadataSource::MusicBrainz);
+        assert_eq!(provenance.source("year"), MetadataSource::MusicBrainz);
+        assert_eq!(provenance.source("genres"), MetadataSource::MusicBrainz);
+        assert_eq!(provenance.source("title"), MetadataSource::File);
+
+        let mut edited = new.clone();
+        edited.year = Some(1974);
+        provenance.merge(Provenance::from_diff(
+            &TrackDiff::between(&new, &edited),
+            MetadataSource::Manual,
+        ));
+        assert!(provenance.is_manual("year"));
+        assert!(!provenance.is_manual("genres"));
+    }
+
+    #[test]
+    fn keep_manual_edits() {
+        let original = track();
+        let mut provenance = Provenance::default();
+        provenance.fields.insert(
+            "artist".to_string(),
+            FieldSource {
+                source: MetadataSource::Manual,
+                updated_at: Utc::now(),
+            },
+        );
+
+        let mut tagged = original.clone();
+        tagged.artist = "Pink Floyd (UK)".to_string();
+        tagged.track_number = Some(4);
+        let kept = provenance
+            .keep_manual_edits(&original, &mut tagged)
+            .unwrap();
+        assert_eq!(kept.changes.len(), 1);
+        assert_eq!(tagged.artist, "Pink Floyd");
+        assert_eq!(tagged.track_number, Some(4));
+    }
+
+    #[test]
+    fn source_names() {
+        for source in [
+            MetadataSource::File,
+            MetadataSource::MusicBrainz,
+            MetadataSource::Discogs,
+            MetadataSource::Plugin,
+            MetadataSource::Manual,
+        ] {
+            assert_eq!(MetadataSource::from_name(source.as_str()), Some(source));
+        }
+        assert_eq!(MetadataSource::from_name("acoustid"), None);
+    }
+}
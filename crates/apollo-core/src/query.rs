@@ -9,11 +9,47 @@
 //! - `title:name` - Match title field
 //! - `year:2020` - Match exact year
 //! - `year:2020..2023` - Match year range
+//! - `year>=1990`, `duration<240`, `bitrate>=320`, `track<5` - Numeric
+//!   comparisons with `<`, `<=`, `>`, `>=`, or `=` (durations are in seconds
+//!   or `m:ss`)
+//! - `added:-30d` - Added in the last 30 days (`d`, `w`, `m`, or `y`)
+//! - `added:2024-01..2024-06` - Added between January and June 2024
+//! - `modified>2024-05-01` - Modified after May 1st, 2024 (dates can be
+//!   `YYYY`, `YYYY-MM`, or `YYYY-MM-DD`)
+//! - `format:flac` - Match audio format (`lossless` and `lossy` match groups
+//!   of formats)
+//! - `samplerate:44100`, `channels:2` - Match audio properties (these support
+//!   comparisons too)
+//! - `artist::^The\s` - Match a field against a regular expression
+//! - `album:="Live"` - Match a field exactly (case-insensitive)
+//! - `album:`, `year:` - Match items where the field is missing or empty
+//! - `-genre:Podcast` - Negate a field term
+//! - `rating>=4`, `plays=0`, `favorite:true` - Match ratings and play
+//!   history (`lastplayed` supports the same syntax as `added`)
+//! - `albumtype:live`, `country:GB`, `label:EMI`, `catalog:EMSP` -
+//!   Match album release metadata (`released` supports the same syntax as
+//!   `added`)
+//! - `compilation:true` - Match tracks on compilations
 //! - `genre:rock` - Match genre
 //! - `path:/music/` - Match path prefix
+//! - `playlist:"Workout"` - Match tracks in another playlist, by name
+//!   (`-playlist:"Heard recently"` matches tracks that are not)
 //! - Simple text searches all fields
+//!
+//! Results can be sorted with `sort:` terms such as `sort:year-`; see
+//! [`SortSpec`] and [`Query::parse_sorted`].
+//!
+//! Queries can also be built in code with [`Query::field`] and
+//! [`Query::date`], without formatting query strings.
+
+mod builder;
+mod sort;
+
+pub use builder::{DateQuery, FieldQuery};
+pub use sort::{SortField, SortKey, SortSpec};
 
 use crate::error::{Error, Result};
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -28,12 +64,36 @@ pub enum Query {
     Field { field: Field, value: String },
     /// Match a year range.
     YearRange { start: i32, end: i32 },
+    /// Match items where a field is missing or empty.
+    Empty { field: Field },
+    /// Match a field against a regular expression.
+    Regex { field: Field, pattern: String },
+    /// Match a field exactly, ignoring case.
+    Exact { field: Field, value: String },
+    /// Compare a numeric field against a value.
+    Compare {
+        field: Field,
+        op: CompareOp,
+        value: i64,
+    },
+    /// Match a date range. The start is inclusive and the end exclusive;
+    /// a missing bound is unlimited.
+    DateRange {
+        field: DateField,
+        start: Option<DateBound>,
+        end: Option<DateBound>,
+    },
     /// Combine queries with AND.
-    And(Vec<Query>),
+    And(Vec<Self>),
     /// Combine queries with OR.
-    Or(Vec<Query>),
+    Or(Vec<Self>),
     /// Negate a query.
-    Not(Box<Query>),
+    Not(Box<Self>),
+    /// Match tracks in a playlist, found by its name ignoring case.
+    ///
+    /// Smart playlists are evaluated with their own query and limits, so
+    /// that playlists can be built on top of each other.
+    Playlist { name: String },
 }
 
 /// Fields that can be queried.
@@ -47,6 +107,156 @@ pub enum Field {
     Year,
     Genre,
     Path,
+    Duration,
+    Bitrate,
+    Track,
+    Format,
+    SampleRate,
+    Channels,
+    Rating,
+    Plays,
+    Favorite,
+    AlbumType,
+    Country,
+    Label,
+    CatalogNumber,
+    Compilation,
+}
+
+/// Comparison operators for numeric fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareOp {
+    /// Equal to.
+    Eq,
+    /// Less than.
+    Lt,
+    /// Less than or equal to.
+    Le,
+    /// Greater than.
+    Gt,
+    /// Greater than or equal to.
+    Ge,
+}
+
+/// Date fields that can be queried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateField {
+    /// When the item was added to the library.
+    Added,
+    /// When the item was last modified.
+    Modified,
+    /// When the track was last played.
+    LastPlayed,
+    /// When the album was released.
+    Released,
+}
+
+/// A bound of a date range.
+///
+/// Relative bounds are stored as-is so that saved queries like "added in
+/// the last 30 days" stay relative to the time they are evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateBound {
+    /// Midnight UTC at the start of a calendar date.
+    Date(NaiveDate),
+    /// A number of days before the time of evaluation.
+    DaysAgo(u32),
+}
+
+impl DateBound {
+    /// Resolve the bound to a point in time, relative to `now`.
+    #[must_use]
+    pub fn resolve(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Self::Date(date) => date.and_time(chrono::NaiveTime::MIN).and_utc(),
+            Self::DaysAgo(days) => now - TimeDelta::days(i64::from(days)),
+        }
+    }
+}
+
+impl DateField {
+    /// Look up a date field by its query name.
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "added" => Some(Self::Added),
+            "modified" => Some(Self::Modified),
+            "lastplayed" | "last_played" => Some(Self::LastPlayed),
+            "released" | "releasedate" | "release_date" => Some(Self::Released),
+            _ => None,
+        }
+    }
+}
+
+impl CompareOp {
+    /// Get the operator symbol, as used in queries and SQL.
+    #[must_use]
+    pub const fn symbol(self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        }
+    }
+}
+
+impl Field {
+    /// Check if this field holds a number that supports comparisons.
+    #[must_use]
+    pub const fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Self::Year
+                | Self::Duration
+                | Self::Bitrate
+                | Self::Track
+                | Self::SampleRate
+                | Self::Channels
+                | Self::Rating
+                | Self::Plays
+                | Self::Favorite
+                | Self::Compilation
+        )
+    }
+
+    /// Check if this field is a boolean flag, queried as `field:true`.
+    #[must_use]
+    pub const fn is_flag(self) -> bool {
+        matches!(self, Self::Favorite | Self::Compilation)
+    }
+
+    /// Look up a field by its query name.
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name.to_lowercase().as_str() {
+            "artist" => Self::Artist,
+            "albumartist" | "album_artist" => Self::AlbumArtist,
+            "album" => Self::Album,
+            "title" => Self::Title,
+            "year" => Self::Year,
+            "genre" => Self::Genre,
+            "path" => Self::Path,
+            "duration" | "length" => Self::Duration,
+            "bitrate" => Self::Bitrate,
+            "track" | "tracknumber" => Self::Track,
+            "format" => Self::Format,
+            "samplerate" | "sample_rate" => Self::SampleRate,
+            "channels" => Self::Channels,
+            "rating" => Self::Rating,
+            "plays" | "playcount" | "play_count" => Self::Plays,
+            "favorite" | "favourite" => Self::Favorite,
+            "albumtype" | "album_type" | "type" => Self::AlbumType,
+            "country" => Self::Country,
+            "label" => Self::Label,
+            "catalog" | "catalognumber" | "catalog_number" | "catno" => Self::CatalogNumber,
+            "compilation" => Self::Compilation,
+            _ => return None,
+        };
+        Some(field)
+    }
 }
 
 impl fmt::Display for Query {
@@ -56,6 +266,27 @@ impl fmt::Display for Query {
             Self::Text(text) => write!(f, "{text}"),
             Self::Field { field, value } => write!(f, "{field}:{value}"),
             Self::YearRange { start, end } => write!(f, "year:{start}..{end}"),
+            Self::Empty { field } => write!(f, "{field}:"),
+            Self::Regex { field, pattern } => write!(f, "{field}::{pattern}"),
+            Self::Exact { field, value } => write!(f, "{field}:=\"{value}\""),
+            Self::Compare { field, op, value } => write!(f, "{field}{}{value}", op.symbol()),
+            Self::DateRange { field, start, end } => {
+                write!(f, "{field}:")?;
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                write!(f, "..")?;
+                match end {
+                    // Range ends are inclusive when parsed, but stored exclusive
+                    Some(DateBound::Date(date)) => {
+                        let last = date.pred_opt().unwrap_or(*date);
+                        write!(f, "{}", DateBound::Date(last))?;
+                    }
+                    Some(end) => write!(f, "{end}")?,
+                    None => {}
+                }
+                Ok(())
+            }
             Self::And(queries) => {
                 let parts: Vec<String> = queries.iter().map(|q| format!("({q})")).collect();
                 write!(f, "{}", parts.join(" AND "))
@@ -65,6 +296,7 @@ impl fmt::Display for Query {
                 write!(f, "{}", parts.join(" OR "))
             }
             Self::Not(query) => write!(f, "NOT ({query})"),
+            Self::Playlist { name } => write!(f, "playlist:\"{name}\""),
         }
     }
 }
@@ -79,6 +311,40 @@ impl fmt::Display for Field {
             Self::Year => write!(f, "year"),
             Self::Genre => write!(f, "genre"),
             Self::Path => write!(f, "path"),
+            Self::Duration => write!(f, "duration"),
+            Self::Bitrate => write!(f, "bitrate"),
+            Self::Track => write!(f, "track"),
+            Self::Format => write!(f, "format"),
+            Self::SampleRate => write!(f, "samplerate"),
+            Self::Channels => write!(f, "channels"),
+            Self::Rating => write!(f, "rating"),
+            Self::Plays => write!(f, "plays"),
+            Self::Favorite => write!(f, "favorite"),
+            Self::AlbumType => write!(f, "albumtype"),
+            Self::Country => write!(f, "country"),
+            Self::Label => write!(f, "label"),
+            Self::CatalogNumber => write!(f, "catalog"),
+            Self::Compilation => write!(f, "compilation"),
+        }
+    }
+}
+
+impl fmt::Display for DateField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added => write!(f, "added"),
+            Self::Modified => write!(f, "modified"),
+            Self::LastPlayed => write!(f, "lastplayed"),
+            Self::Released => write!(f, "released"),
+        }
+    }
+}
+
+impl fmt::Display for DateBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            Self::DaysAgo(days) => write!(f, "-{days}d"),
         }
     }
 }
@@ -96,18 +362,69 @@ impl Query {
             return Ok(Self::All);
         }
 
+        // A leading dash negates field terms; plain text keeps it
+        if let Some(rest) = input.strip_prefix('-') {
+            let inner = Self::parse(rest)?;
+            if !matches!(inner, Self::Text(_) | Self::All) {
+                return Ok(Self::Not(Box::new(inner)));
+            }
+        }
+
+        if let Some(query) = Self::parse_comparison(input)? {
+            return Ok(query);
+        }
+
         // Simple implementation: check for field:value patterns
         if let Some((field, value)) = input.split_once(':') {
-            let field = match field.to_lowercase().as_str() {
-                "artist" => Field::Artist,
-                "albumartist" | "album_artist" => Field::AlbumArtist,
-                "album" => Field::Album,
-                "title" => Field::Title,
-                "year" => Field::Year,
-                "genre" => Field::Genre,
-                "path" => Field::Path,
-                _ => return Err(Error::InvalidQuery(format!("unknown field: {field}"))),
-            };
+            if let Some(field) = DateField::from_name(field) {
+                return parse_date_range(field, value.trim());
+            }
+
+            if field.eq_ignore_ascii_case("playlist") {
+                let name = unquote(value.trim());
+                if name.is_empty() {
+                    return Err(Error::InvalidQuery("missing playlist name".to_string()));
+                }
+                return Ok(Self::Playlist {
+                    name: name.to_string(),
+                });
+            }
+
+            let field = Field::from_name(field)
+                .ok_or_else(|| Error::InvalidQuery(format!("unknown field: {field}")))?;
+
+            if value.trim().is_empty() {
+                return Ok(Self::Empty { field });
+            }
+
+            // Flags like `favorite:true` are comparisons
+            if field.is_flag() {
+                return Ok(Self::Compare {
+                    field,
+                    op: CompareOp::Eq,
+                    value: parse_number(field, value.trim())?,
+                });
+            }
+
+            if let Some(pattern) = value.strip_prefix(':') {
+                return parse_regex(field, pattern);
+            }
+
+            if let Some(value) = value.strip_prefix('=') {
+                let value = unquote(value.trim());
+                if field.is_numeric() {
+                    let value = parse_number(field, value)?;
+                    return Ok(Self::Compare {
+                        field,
+                        op: CompareOp::Eq,
+                        value,
+                    });
+                }
+                return Ok(Self::Exact {
+                    field,
+                    value: value.to_string(),
+                });
+            }
 
             // Check for year range
             if field == Field::Year
@@ -132,6 +449,231 @@ impl Query {
             Ok(Self::Text(input.to_string()))
         }
     }
+
+    /// Get the names of the playlists that the query refers to, in the
+    /// order they appear.
+    #[must_use]
+    pub fn playlists(&self) -> Vec<&str> {
+        match self {
+            Self::Playlist { name } => vec![name.as_str()],
+            Self::And(queries) | Self::Or(queries) => {
+                queries.iter().flat_map(Self::playlists).collect()
+            }
+            Self::Not(query) => query.playlists(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Parse a query string with optional `sort:` terms.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query or sort syntax is invalid.
+    pub fn parse_sorted(input: &str) -> Result<(Self, SortSpec)> {
+        let (rest, sort) = SortSpec::extract(input)?;
+        Ok((Self::parse(&rest)?, sort))
+    }
+
+    /// Parse a numeric comparison such as `year>=1990`.
+    ///
+    /// Returns `Ok(None)` if the input is not a comparison on a known field,
+    /// so that it can be handled as a regular query.
+    fn parse_comparison(input: &str) -> Result<Option<Self>> {
+        let Some(pos) = input.find(['<', '>', '=', ':']) else {
+            return Ok(None);
+        };
+        if input[pos..].starts_with(':') {
+            return Ok(None);
+        }
+
+        let name = &input[..pos];
+        let field = Field::from_name(name);
+        let date_field = DateField::from_name(name);
+        if field.is_none() && date_field.is_none() {
+            return Ok(None);
+        }
+
+        let rest = &input[pos..];
+        // Two-character operators must be checked first
+        let (op, value) = [
+            CompareOp::Ge,
+            CompareOp::Le,
+            CompareOp::Gt,
+            CompareOp::Lt,
+            CompareOp::Eq,
+        ]
+        .into_iter()
+        .find_map(|op| rest.strip_prefix(op.symbol()).map(|value| (op, value)))
+        .ok_or_else(|| Error::InvalidQuery(format!("invalid comparison: {input}")))?;
+
+        if let Some(field) = date_field {
+            return parse_date_comparison(field, op, value.trim()).map(Some);
+        }
+
+        let field = field.filter(|field| field.is_numeric()).ok_or_else(|| {
+            Error::InvalidQuery(format!("field does not support comparisons: {name}"))
+        })?;
+
+        let value = parse_number(field, value.trim())?;
+        Ok(Some(Self::Compare { field, op, value }))
+    }
+}
+
+/// Parse a numeric query value for a field.
+///
+/// Durations are given in seconds or as `m:ss`, and flags as a boolean.
+fn parse_number(field: Field, value: &str) -> Result<i64> {
+    let invalid = || Error::InvalidQuery(format!("invalid {field}: {value}"));
+
+    if field.is_flag() {
+        return match value.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Ok(1),
+            "false" | "no" | "0" => Ok(0),
+            _ => Err(invalid()),
+        };
+    }
+
+    if field == Field::Duration
+        && let Some((minutes, seconds)) = value.split_once(':')
+    {
+        if minutes.starts_with('-') {
+            return Err(invalid());
+        }
+        let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+        let seconds: i64 = seconds.parse().map_err(|_| invalid())?;
+        if !(0..60).contains(&seconds) {
+            return Err(invalid());
+        }
+        return minutes
+            .checked_mul(60)
+            .and_then(|secs| secs.checked_add(seconds))
+            .ok_or_else(invalid);
+    }
+
+    value.parse().map_err(|_| invalid())
+}
+
+/// Parse a regular expression match, checking that the pattern compiles.
+fn parse_regex(field: Field, pattern: &str) -> Result<Query> {
+    if field.is_numeric() {
+        return Err(Error::InvalidQuery(format!(
+            "field does not support regular expressions: {field}"
+        )));
+    }
+    regex::Regex::new(pattern)
+        .map_err(|e| Error::InvalidQuery(format!("invalid regular expression: {e}")))?;
+    Ok(Query::Regex {
+        field,
+        pattern: pattern.to_string(),
+    })
+}
+
+/// Strip surrounding double quotes from a value.
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Parse a date value into the bounds of the period it covers.
+///
+/// Calendar dates cover a year, month, or day. Relative dates such as `-30d`
+/// are a single point in time, so both bounds are the same.
+fn parse_date(value: &str) -> Result<(DateBound, DateBound)> {
+    let invalid = || Error::InvalidQuery(format!("invalid date: {value}"));
+
+    if let Some(relative) = value.strip_prefix('-') {
+        let unit = relative.chars().last().ok_or_else(invalid)?;
+        let amount: u32 = relative[..relative.len() - unit.len_utf8()]
+            .parse()
+            .map_err(|_| invalid())?;
+        let days = match unit {
+            'd' => amount,
+            'w' => amount.saturating_mul(7),
+            'm' => amount.saturating_mul(30),
+            'y' => amount.saturating_mul(365),
+            _ => return Err(invalid()),
+        };
+        return Ok((DateBound::DaysAgo(days), DateBound::DaysAgo(days)));
+    }
+
+    let parts: Vec<&str> = value.split('-').collect();
+    let numbers = parts
+        .iter()
+        .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>>>()?;
+    let year = i32::try_from(numbers[0]).map_err(|_| invalid())?;
+
+    let (start, end) = match numbers[1..] {
+        [] => (
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(year + 1, 1, 1),
+        ),
+        [month] => {
+            let start = NaiveDate::from_ymd_opt(year, month, 1);
+            (
+                start,
+                start.and_then(|d| d.checked_add_months(chrono::Months::new(1))),
+            )
+        }
+        [month, day] => {
+            let start = NaiveDate::from_ymd_opt(year, month, day);
+            (start, start.and_then(|d| d.succ_opt()))
+        }
+        _ => (None, None),
+    };
+
+    match (start, end) {
+        (Some(start), Some(end)) if parts[0].len() == 4 && start.year() == year => {
+            Ok((DateBound::Date(start), DateBound::Date(end)))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse a date field range such as `added:2024-01..2024-06` or `added:-30d`.
+fn parse_date_range(field: DateField, value: &str) -> Result<Query> {
+    if let Some((from, to)) = value.split_once("..") {
+        let start = (!from.is_empty())
+            .then(|| parse_date(from))
+            .transpose()?
+            .map(|(start, _)| start);
+        let end = (!to.is_empty())
+            .then(|| parse_date(to))
+            .transpose()?
+            .map(|(_, end)| end);
+        return Ok(Query::DateRange { field, start, end });
+    }
+
+    let (start, end) = parse_date(value)?;
+    // A relative date on its own means "since"
+    let end = if matches!(start, DateBound::DaysAgo(_)) {
+        None
+    } else {
+        Some(end)
+    };
+    Ok(Query::DateRange {
+        field,
+        start: Some(start),
+        end,
+    })
+}
+
+/// Parse a date field comparison such as `modified>2024-05-01`.
+fn parse_date_comparison(field: DateField, op: CompareOp, value: &str) -> Result<Query> {
+    if op == CompareOp::Eq {
+        return parse_date_range(field, value);
+    }
+
+    let (first, last) = parse_date(value)?;
+    let (start, end) = match op {
+        CompareOp::Gt => (Some(last), None),
+        CompareOp::Ge => (Some(first), None),
+        CompareOp::Lt => (None, Some(first)),
+        CompareOp::Le | CompareOp::Eq => (None, Some(last)),
+    };
+    Ok(Query::DateRange { field, start, end })
 }
 
 #[cfg(test)]
@@ -172,6 +714,326 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn parse_comparisons() {
+        let cases = [
+            ("year>=1990", Field::Year, CompareOp::Ge, 1990),
+            ("duration<240", Field::Duration, CompareOp::Lt, 240),
+            ("bitrate>=320", Field::Bitrate, CompareOp::Ge, 320),
+            ("track<5", Field::Track, CompareOp::Lt, 5),
+            ("year>2000", Field::Year, CompareOp::Gt, 2000),
+            ("year<=1999", Field::Year, CompareOp::Le, 1999),
+            ("bitrate=128", Field::Bitrate, CompareOp::Eq, 128),
+            ("duration>4:30", Field::Duration, CompareOp::Gt, 270),
+            ("samplerate>=48000", Field::SampleRate, CompareOp::Ge, 48000),
+            ("channels=2", Field::Channels, CompareOp::Eq, 2),
+        ];
+
+        for (input, expected_field, expected_op, expected_value) in cases {
+            let query = Query::parse(input).unwrap();
+            match query {
+                Query::Compare { field, op, value } => {
+                    assert_eq!(field, expected_field, "{input}");
+                    assert_eq!(op, expected_op, "{input}");
+                    assert_eq!(value, expected_value, "{input}");
+                }
+                other => panic!("expected comparison for {input}, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_comparison_errors() {
+        assert!(Query::parse("year>=abc").is_err());
+        assert!(Query::parse("duration<4:75").is_err());
+        assert!(Query::parse("duration<-4:30").is_err());
+        assert!(Query::parse(&format!("duration<{}:00", i64::MAX)).is_err());
+        assert!(Query::parse("artist>=5").is_err());
+        assert!(Query::parse("format>flac").is_err());
+    }
+
+    #[test]
+    fn parse_comparison_unknown_field_is_text() {
+        let query = Query::parse("a<b").unwrap();
+        assert!(matches!(query, Query::Text(ref s) if s == "a<b"));
+    }
+
+    #[test]
+    fn comparison_display_roundtrip() {
+        let query = Query::parse("bitrate>=320").unwrap();
+        assert_eq!(query.to_string(), "bitrate>=320");
+        let reparsed = Query::parse(&query.to_string()).unwrap();
+        assert!(matches!(
+            reparsed,
+            Query::Compare {
+                field: Field::Bitrate,
+                op: CompareOp::Ge,
+                value: 320
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_empty_field() {
+        let query = Query::parse("album:").unwrap();
+        assert!(matches!(
+            query,
+            Query::Empty {
+                field: Field::Album
+            }
+        ));
+        assert_eq!(query.to_string(), "album:");
+
+        let query = Query::parse("year:").unwrap();
+        assert!(matches!(query, Query::Empty { field: Field::Year }));
+    }
+
+    #[test]
+    fn parse_negated_term() {
+        let query = Query::parse("-genre:Podcast").unwrap();
+        match query {
+            Query::Not(inner) => assert!(matches!(
+                *inner,
+                Query::Field { field: Field::Genre, ref value } if value == "Podcast"
+            )),
+            other => panic!("expected negation, got {other:?}"),
+        }
+
+        let query = Query::parse("-year>=2000").unwrap();
+        assert!(matches!(query, Query::Not(_)));
+
+        let query = Query::parse("-album:").unwrap();
+        assert!(matches!(query, Query::Not(_)));
+    }
+
+    #[test]
+    fn parse_dash_text_is_text() {
+        let query = Query::parse("-ish").unwrap();
+        assert!(matches!(query, Query::Text(ref s) if s == "-ish"));
+        assert!(Query::parse("-bogus:value").is_err());
+    }
+
+    #[test]
+    fn parse_rating_and_play_fields() {
+        let cases = [
+            ("rating>=4", Field::Rating, CompareOp::Ge, 4),
+            ("plays=0", Field::Plays, CompareOp::Eq, 0),
+            ("favorite:true", Field::Favorite, CompareOp::Eq, 1),
+            ("favorite:no", Field::Favorite, CompareOp::Eq, 0),
+            ("compilation:yes", Field::Compilation, CompareOp::Eq, 1),
+        ];
+        for (input, expected_field, expected_op, expected_value) in cases {
+            let query = Query::parse(input).unwrap();
+            assert!(
+                matches!(
+                    query,
+                    Query::Compare { field, op, value }
+                        if field == expected_field && op == expected_op && value == expected_value
+                ),
+                "{input}: {query:?}"
+            );
+        }
+
+        assert!(Query::parse("favorite:maybe").is_err());
+        assert!(matches!(
+            Query::parse("lastplayed<-90d").unwrap(),
+            Query::DateRange {
+                field: DateField::LastPlayed,
+                start: None,
+                end: Some(DateBound::DaysAgo(90))
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_playlist_query() {
+        let query = Query::parse(r#"playlist:"Workout""#).unwrap();
+        assert!(matches!(query, Query::Playlist { ref name } if name == "Workout"));
+        assert_eq!(query.to_string(), r#"playlist:"Workout""#);
+
+        let query = Query::parse(r#"-playlist:"Heard recently""#).unwrap();
+        match query {
+            Query::Not(ref inner) => assert!(matches!(
+                **inner,
+                Query::Playlist { ref name } if name == "Heard recently"
+            )),
+            ref other => panic!("expected negation, got {other:?}"),
+        }
+        assert_eq!(query.playlists(), vec!["Heard recently"]);
+
+        assert!(matches!(
+            Query::parse("Playlist:Chill").unwrap(),
+            Query::Playlist { ref name } if name == "Chill"
+        ));
+        assert!(Query::parse("playlist:").is_err());
+        assert!(Query::parse(r#"playlist:"""#).is_err());
+    }
+
+    #[test]
+    fn parse_sorted_query() {
+        let (query, sort) = Query::parse_sorted("genre:rock sort:year- sort:album+").unwrap();
+        assert!(matches!(
+            query,
+            Query::Field { field: Field::Genre, ref value } if value == "rock"
+        ));
+        assert_eq!(sort.to_string(), "sort:year- sort:album+");
+
+        let (query, sort) = Query::parse_sorted("sort:random").unwrap();
+        assert!(matches!(query, Query::All));
+        assert_eq!(sort.keys, vec![SortKey::asc(SortField::Random)]);
+    }
+
+    #[test]
+    fn parse_regex_query() {
+        let query = Query::parse(r"artist::^The\s").unwrap();
+        assert!(matches!(
+            query,
+            Query::Regex { field: Field::Artist, ref pattern } if pattern == r"^The\s"
+        ));
+        assert_eq!(query.to_string(), r"artist::^The\s");
+    }
+
+    #[test]
+    fn parse_regex_errors() {
+        assert!(Query::parse("artist::(unclosed").is_err());
+        assert!(Query::parse("year::^19").is_err());
+    }
+
+    #[test]
+    fn parse_exact_query() {
+        let query = Query::parse(r#"album:="Live""#).unwrap();
+        assert!(matches!(
+            query,
+            Query::Exact { field: Field::Album, ref value } if value == "Live"
+        ));
+        assert_eq!(query.to_string(), r#"album:="Live""#);
+
+        let query = Query::parse("title:=Intro").unwrap();
+        assert!(matches!(
+            query,
+            Query::Exact { field: Field::Title, ref value } if value == "Intro"
+        ));
+
+        let query = Query::parse("year:=1990").unwrap();
+        assert!(matches!(
+            query,
+            Query::Compare {
+                field: Field::Year,
+                op: CompareOp::Eq,
+                value: 1990
+            }
+        ));
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> DateBound {
+        DateBound::Date(NaiveDate::from_ymd_opt(year, month, day).unwrap())
+    }
+
+    fn date_range(input: &str) -> (DateField, Option<DateBound>, Option<DateBound>) {
+        match Query::parse(input).unwrap() {
+            Query::DateRange { field, start, end } => (field, start, end),
+            other => panic!("expected date range for {input}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_relative_date() {
+        assert_eq!(
+            date_range("added:-30d"),
+            (DateField::Added, Some(DateBound::DaysAgo(30)), None)
+        );
+        assert_eq!(
+            date_range("added:-2w"),
+            (DateField::Added, Some(DateBound::DaysAgo(14)), None)
+        );
+        assert_eq!(
+            date_range("modified<-1y"),
+            (DateField::Modified, None, Some(DateBound::DaysAgo(365)))
+        );
+    }
+
+    #[test]
+    fn parse_date_ranges() {
+        assert_eq!(
+            date_range("added:2024-01..2024-06"),
+            (
+                DateField::Added,
+                Some(date(2024, 1, 1)),
+                Some(date(2024, 7, 1))
+            )
+        );
+        assert_eq!(
+            date_range("added:2023"),
+            (
+                DateField::Added,
+                Some(date(2023, 1, 1)),
+                Some(date(2024, 1, 1))
+            )
+        );
+        assert_eq!(
+            date_range("added:..2020"),
+            (DateField::Added, None, Some(date(2021, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn parse_date_comparisons() {
+        assert_eq!(
+            date_range("modified>2024-05-01"),
+            (DateField::Modified, Some(date(2024, 5, 2)), None)
+        );
+        assert_eq!(
+            date_range("modified>=2024-05-01"),
+            (DateField::Modified, Some(date(2024, 5, 1)), None)
+        );
+        assert_eq!(
+            date_range("modified<2020"),
+            (DateField::Modified, None, Some(date(2020, 1, 1)))
+        );
+        assert_eq!(
+            date_range("modified<=2024-12"),
+            (DateField::Modified, None, Some(date(2025, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_dates() {
+        for input in [
+            "added:yesterday",
+            "added:-30x",
+            "added:2024-13",
+            "added:2024-02-30",
+            "added:24-01-01",
+            "modified>-d",
+        ] {
+            assert!(Query::parse(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn date_range_display_roundtrip() {
+        for input in [
+            "added:-30d",
+            "added:2024-01..2024-06",
+            "modified>2024-05-01",
+        ] {
+            let query = Query::parse(input).unwrap();
+            let reparsed = Query::parse(&query.to_string()).unwrap();
+            assert_eq!(date_range(&query.to_string()), date_range(input));
+            assert!(matches!(reparsed, Query::DateRange { .. }));
+        }
+    }
+
+    #[test]
+    fn date_bound_resolve() {
+        let now = Utc::now();
+        assert_eq!(DateBound::DaysAgo(7).resolve(now), now - TimeDelta::days(7));
+        assert_eq!(
+            date(2024, 5, 1).resolve(now).to_rfc3339(),
+            "2024-05-01T00:00:00+00:00"
+        );
+    }
+
     /// Strategy for generating valid field names.
     fn field_name_strategy() -> impl Strategy<Value = &'static str> {
         prop_oneof![
@@ -183,6 +1045,14 @@ mod tests {
             Just("year"),
             Just("genre"),
             Just("path"),
+            Just("duration"),
+            Just("bitrate"),
+            Just("track"),
+            Just("format"),
+            Just("samplerate"),
+            Just("channels"),
+            Just("rating"),
+            Just("plays"),
         ]
     }
 
@@ -259,7 +1129,13 @@ mod tests {
             value in search_value_strategy(),
         ) {
             // Only test if the field is not a valid field name
-            let valid_fields = ["artist", "albumartist", "album_artist", "album", "title", "year", "genre", "path"];
+            let valid_fields = [
+                "artist", "albumartist", "album_artist", "album", "title", "year", "genre", "path",
+                "duration", "length", "bitrate", "track", "tracknumber", "added", "modified",
+                "format", "samplerate", "sample_rate", "channels", "rating", "plays", "playcount",
+                "play_count", "favorite", "favourite", "lastplayed", "last_played",
+                "playlist",
+            ];
             if !valid_fields.contains(&field.as_str()) {
                 let input = format!("{field}:{value}");
                 let result = Query::parse(&input);
@@ -0,0 +1,276 @@
+//! Typed construction of queries.
+//!
+//! The builder produces the same [`Query`] values as [`Query::parse`], without
+//! formatting and escaping query strings.
+//!
+//! # Example
+//!
+//! ```
+//! use apollo_core::query::{DateField, Field, Query};
+//!
+//! let query = Query::field(Field::Artist)
+//!     .contains("Beatles")
+//!     .and(Query::field(Field::Year).ge(1965))
+//!     .and(!Query::field(Field::Genre).equals("Podcast"))
+//!     .and(Query::date(DateField::Added).within_days(30));
+//!
+//! assert!(matches!(query, Query::And(ref terms) if terms.len() == 4));
+//! ```
+
+use super::{CompareOp, DateBound, DateField, Field, Query};
+use std::ops::Not;
+
+/// Builder for a query on a single field, created by [`Query::field`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldQuery {
+    field: Field,
+}
+
+/// Builder for a query on a date field, created by [`Query::date`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateQuery {
+    field: DateField,
+}
+
+impl Query {
+    /// Start building a query on a field.
+    #[must_use]
+    pub const fn field(field: Field) -> FieldQuery {
+        FieldQuery { field }
+    }
+
+    /// Start building a query on a date field.
+    #[must_use]
+    pub const fn date(field: DateField) -> DateQuery {
+        DateQuery { field }
+    }
+
+    /// Create a full-text search across all fields.
+    #[must_use]
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Match tracks in a playlist, found by its name.
+    #[must_use]
+    pub fn playlist(name: impl Into<String>) -> Self {
+        Self::Playlist { name: name.into() }
+    }
+
+    /// Combine this query with another, matching items that match both.
+    #[must_use]
+    pub fn and(self, other: Self) -> Self {
+        match self {
+            Self::All => other,
+            Self::And(mut queries) => {
+                queries.push(other);
+                Self::And(queries)
+            }
+            query => Self::And(vec![query, other]),
+        }
+    }
+
+    /// Combine this query with another, matching items that match either.
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        match self {
+            Self::Or(mut queries) => {
+                queries.push(other);
+                Self::Or(queries)
+            }
+            query => Self::Or(vec![query, other]),
+        }
+    }
+}
+
+impl Not for Query {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            Self::Not(query) => *query,
+            query => Self::Not(Box::new(query)),
+        }
+    }
+}
+
+impl FieldQuery {
+    /// Match items where the field contains a value.
+    #[must_use]
+    pub fn contains(self, value: impl Into<String>) -> Query {
+        Query::Field {
+            field: self.field,
+            value: value.into(),
+        }
+    }
+
+    /// Match items where the field equals a value, ignoring case.
+    #[must_use]
+    pub fn equals(self, value: impl Into<String>) -> Query {
+        Query::Exact {
+            field: self.field,
+            value: value.into(),
+        }
+    }
+
+    /// Match items where the field matches a regular expression.
+    ///
+    /// The pattern is not validated until the query is evaluated.
+    #[must_use]
+    pub fn matches(self, pattern: impl Into<String>) -> Query {
+        Query::Regex {
+            field: self.field,
+            pattern: pattern.into(),
+        }
+    }
+
+    /// Match items where the field is missing or empty.
+    #[must_use]
+    pub const fn missing(self) -> Query {
+        Query::Empty { field: self.field }
+    }
+
+    /// Compare the field against a number.
+    #[must_use]
+    pub const fn compare(self, op: CompareOp, value: i64) -> Query {
+        Query::Compare {
+            field: self.field,
+            op,
+            value,
+        }
+    }
+
+    /// Match items where the field equals a number.
+    #[must_use]
+    pub const fn eq(self, value: i64) -> Query {
+        self.compare(CompareOp::Eq, value)
+    }
+
+    /// Match items where the field is less than a number.
+    #[must_use]
+    pub const fn lt(self, value: i64) -> Query {
+        self.compare(CompareOp::Lt, value)
+    }
+
+    /// Match items where the field is less than or equal to a number.
+    #[must_use]
+    pub const fn le(self, value: i64) -> Query {
+        self.compare(CompareOp::Le, value)
+    }
+
+    /// Match items where the field is greater than a number.
+    #[must_use]
+    pub const fn gt(self, value: i64) -> Query {
+        self.compare(CompareOp::Gt, value)
+    }
+
+    /// Match items where the field is greater than or equal to a number.
+    #[must_use]
+    pub const fn ge(self, value: i64) -> Query {
+        self.compare(CompareOp::Ge, value)
+    }
+
+    /// Match items where the field is between two numbers, inclusive.
+    #[must_use]
+    pub fn between(self, start: i64, end: i64) -> Query {
+        if self.field == Field::Year
+            && let (Ok(start), Ok(end)) = (i32::try_from(start), i32::try_from(end))
+        {
+            return Query::YearRange { start, end };
+        }
+        self.ge(start).and(self.le(end))
+    }
+}
+
+impl DateQuery {
+    /// Match items with a date at or after a bound.
+    #[must_use]
+    pub const fn since(self, start: DateBound) -> Query {
+        self.range(Some(start), None)
+    }
+
+    /// Match items with a date before a bound.
+    #[must_use]
+    pub const fn before(self, end: DateBound) -> Query {
+        self.range(None, Some(end))
+    }
+
+    /// Match items with a date at or after `start` and before `end`.
+    #[must_use]
+    pub const fn between(self, start: DateBound, end: DateBound) -> Query {
+        self.range(Some(start), Some(end))
+    }
+
+    /// Match items with a date in the last number of days.
+    #[must_use]
+    pub const fn within_days(self, days: u32) -> Query {
+        self.since(DateBound::DaysAgo(days))
+    }
+
+    const fn range(self, start: Option<DateBound>, end: Option<DateBound>) -> Query {
+        Query::DateRange {
+            field: self.field,
+            start,
+            end,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_matches_parser() {
+        let cases = [
+            (
+                Query::field(Field::Artist).contains("Beatles"),
+                "artist:Beatles",
+            ),
+            (Query::field(Field::Album).equals("Live"), "album:=Live"),
+            (Query::field(Field::Artist).matches("^The"), "artist::^The"),
+            (Query::field(Field::Year).missing(), "year:"),
+            (Query::field(Field::Bitrate).ge(320), "bitrate>=320"),
+            (
+                Query::field(Field::Year).between(1990, 1999),
+                "year:1990..1999",
+            ),
+            (Query::date(DateField::Added).within_days(30), "added:-30d"),
+            (
+                !Query::field(Field::Genre).contains("Podcast"),
+                "-genre:Podcast",
+            ),
+            (Query::playlist("Workout"), r#"playlist:"Workout""#),
+        ];
+
+        for (built, input) in cases {
+            let parsed = Query::parse(input).unwrap();
+            assert_eq!(
+                serde_json::to_value(&built).unwrap(),
+                serde_json::to_value(&parsed).unwrap(),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn combinators_flatten() {
+        let query = Query::All
+            .and(Query::text("a"))
+            .and(Query::text("b"))
+            .and(Query::text("c"));
+        assert!(matches!(query, Query::And(ref terms) if terms.len() == 3));
+
+        let query = Query::text("a").or(Query::text("b")).or(Query::text("c"));
+        assert!(matches!(query, Query::Or(ref terms) if terms.len() == 3));
+
+        let query = !!Query::text("a");
+        assert!(matches!(query, Query::Text(ref s) if s == "a"));
+    }
+
+    #[test]
+    fn between_non_year_uses_comparisons() {
+        let query = Query::field(Field::Duration).between(120, 240);
+        assert_eq!(query.to_string(), "(duration>=120) AND (duration<=240)");
+    }
+}
@@ -0,0 +1,275 @@
+//! Sort specifications for query results.
+//!
+//! Sort terms are written as `sort:<field>` suffixes on a query, with an
+//! optional `+` (ascending, the default) or `-` (descending):
+//!
+//! ```text
+//! genre:rock sort:year- sort:album+
+//! ```
+//!
+//! Earlier terms take precedence; later terms break ties.
+
+use crate::error::{Error, Result};
+use crate::playlist::PlaylistSort;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Fields that results can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+    Artist,
+    AlbumArtist,
+    Album,
+    Title,
+    Year,
+    Track,
+    Disc,
+    Duration,
+    Bitrate,
+    Path,
+    Added,
+    Modified,
+    Rating,
+    Plays,
+    LastPlayed,
+    Random,
+}
+
+/// A single sort key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    /// Field to sort by.
+    pub field: SortField,
+    /// Whether to sort in descending order.
+    pub descending: bool,
+}
+
+/// An ordered list of sort keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortSpec {
+    /// Sort keys, from most to least significant.
+    pub keys: Vec<SortKey>,
+}
+
+impl SortField {
+    /// Look up a sort field by its query name.
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name.to_lowercase().as_str() {
+            "artist" => Self::Artist,
+            "albumartist" | "album_artist" => Self::AlbumArtist,
+            "album" => Self::Album,
+            "title" => Self::Title,
+            "year" => Self::Year,
+            "track" | "tracknumber" => Self::Track,
+            "disc" | "discnumber" => Self::Disc,
+            "duration" | "length" => Self::Duration,
+            "bitrate" => Self::Bitrate,
+            "path" => Self::Path,
+            "added" => Self::Added,
+            "modified" => Self::Modified,
+            "rating" => Self::Rating,
+            "plays" | "playcount" | "play_count" => Self::Plays,
+            "lastplayed" | "last_played" => Self::LastPlayed,
+            "random" | "shuffle" => Self::Random,
+            _ => return None,
+        };
+        Some(field)
+    }
+}
+
+impl SortKey {
+    /// Sort ascending by a field.
+    #[must_use]
+    pub const fn asc(field: SortField) -> Self {
+        Self {
+            field,
+            descending: false,
+        }
+    }
+
+    /// Sort descending by a field.
+    #[must_use]
+    pub const fn desc(field: SortField) -> Self {
+        Self {
+            field,
+            descending: true,
+        }
+    }
+
+    /// Parse a sort key such as `year-` or `album`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is unknown.
+    pub fn parse(input: &str) -> Result<Self> {
+        let (name, descending) = input.strip_suffix('-').map_or_else(
+            || (input.strip_suffix('+').unwrap_or(input), false),
+            |name| (name, true),
+        );
+        let field = SortField::from_name(name)
+            .ok_or_else(|| Error::InvalidQuery(format!("unknown sort field: {name}")))?;
+        Ok(Self { field, descending })
+    }
+}
+
+impl SortSpec {
+    /// Check if the spec has no sort keys.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Split `sort:` terms from a query string.
+    ///
+    /// Returns the remaining query and the sort specification.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a sort term is invalid.
+    pub fn extract(input: &str) -> Result<(String, Self)> {
+        let mut rest = Vec::new();
+        let mut keys = Vec::new();
+        for word in input.split_whitespace() {
+            match word.strip_prefix("sort:") {
+                Some(key) => keys.push(SortKey::parse(key)?),
+                None => rest.push(word),
+            }
+        }
+        Ok((rest.join(" "), Self { keys }))
+    }
+
+    /// Parse a sort specification consisting only of `sort:` terms.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input contains anything but valid sort terms.
+    pub fn parse(input: &str) -> Result<Self> {
+        let (rest, spec) = Self::extract(input)?;
+        if !rest.is_empty() {
+            return Err(Error::InvalidQuery(format!("invalid sort: {rest}")));
+        }
+        Ok(spec)
+    }
+}
+
+impl From<PlaylistSort> for SortSpec {
+    fn from(sort: PlaylistSort) -> Self {
+        use SortField::{Added, Album, Artist, Disc, Random, Title, Track, Year};
+
+        let keys = match sort {
+            PlaylistSort::Artist => vec![
+                SortKey::asc(Artist),
+                SortKey::asc(Album),
+                SortKey::asc(Disc),
+                SortKey::asc(Track),
+            ],
+            PlaylistSort::Album => {
+                vec![SortKey::asc(Album), SortKey::asc(Disc), SortKey::asc(Track)]
+            }
+            PlaylistSort::Title => vec![SortKey::asc(Title)],
+            PlaylistSort::AddedDesc => vec![SortKey::desc(Added)],
+            PlaylistSort::AddedAsc => vec![SortKey::asc(Added)],
+            PlaylistSort::YearDesc => vec![
+                SortKey::desc(Year),
+                SortKey::asc(Album),
+                SortKey::asc(Disc),
+                SortKey::asc(Track),
+            ],
+            PlaylistSort::YearAsc => vec![
+                SortKey::asc(Year),
+                SortKey::asc(Album),
+                SortKey::asc(Disc),
+                SortKey::asc(Track),
+            ],
+            PlaylistSort::Random => vec![SortKey::asc(Random)],
+        };
+        Self { keys }
+    }
+}
+
+impl fmt::Display for SortField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Artist => "artist",
+            Self::AlbumArtist => "albumartist",
+            Self::Album => "album",
+            Self::Title => "title",
+            Self::Year => "year",
+            Self::Track => "track",
+            Self::Disc => "disc",
+            Self::Duration => "duration",
+            Self::Bitrate => "bitrate",
+            Self::Path => "path",
+            Self::Added => "added",
+            Self::Modified => "modified",
+            Self::Rating => "rating",
+            Self::Plays => "plays",
+            Self::LastPlayed => "lastplayed",
+            Self::Random => "random",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = if self.descending { '-' } else { '+' };
+        write!(f, "{}{direction}", self.field)
+    }
+}
+
+impl fmt::Display for SortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<String> = self.keys.iter().map(|key| format!("sort:{key}")).collect();
+        write!(f, "{}", terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_sort_terms() {
+        let (rest, spec) = SortSpec::extract("genre:rock sort:year- sort:album+").unwrap();
+        assert_eq!(rest, "genre:rock");
+        assert_eq!(
+            spec.keys,
+            vec![
+                SortKey::desc(SortField::Year),
+                SortKey::asc(SortField::Album)
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_without_sort_terms() {
+        let (rest, spec) = SortSpec::extract("pink floyd").unwrap();
+        assert_eq!(rest, "pink floyd");
+        assert!(spec.is_empty());
+    }
+
+    #[test]
+    fn sort_key_defaults_to_ascending() {
+        assert_eq!(
+            SortKey::parse("title").unwrap(),
+            SortKey::asc(SortField::Title)
+        );
+        assert!(SortKey::parse("colour-").is_err());
+    }
+
+    #[test]
+    fn sort_spec_display_roundtrip() {
+        let spec = SortSpec::parse("sort:plays- sort:lastplayed").unwrap();
+        assert_eq!(spec.to_string(), "sort:plays- sort:lastplayed+");
+        assert_eq!(SortSpec::parse(&spec.to_string()).unwrap(), spec);
+        assert!(SortSpec::parse("sort:year artist:x").is_err());
+    }
+
+    #[test]
+    fn from_playlist_sort() {
+        let spec = SortSpec::from(PlaylistSort::AddedDesc);
+        assert_eq!(spec.keys, vec![SortKey::desc(SortField::Added)]);
+    }
+}
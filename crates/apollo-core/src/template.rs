@@ -2,6 +2,8 @@
 //!
 //! Apollo uses template strings to determine how files should be organized.
 //! Templates support variable substitution and functions for text transformation.
+//! The same templates format command output, with
+//! [`PathTemplate::render_text`].
 //!
 //! # Template Syntax
 //!
@@ -16,13 +18,29 @@
 //!
 //! - `$artist` - Track artist
 //! - `$album_artist` - Album artist (falls back to artist if not set)
+//! - `$album_artist_or_va` - "Various Artists" for compilations, otherwise the
+//!   album artist
+//! - `$compilation` - `1` for compilations, empty otherwise (for use with `%if`)
 //! - `$album` - Album title
 //! - `$title` - Track title
 //! - `$track` - Track number (zero-padded to 2 digits)
 //! - `$disc` - Disc number
 //! - `$year` - Release year
 //! - `$genre` - First genre (if any)
+//! - `$media` - Medium the album was released on, such as `CD` or `Vinyl`
+//! - `$edition` - Edition of the album, such as `Deluxe Edition`
+//! - `$disambiguation` - Comment that tells the album apart from others with
+//!   the same artist and title
 //! - `$ext` - File extension (without dot)
+//! - `$format` - Audio format (e.g., `FLAC`, `MP3`)
+//! - `$bitrate` - Bitrate in kbps
+//! - `$samplerate` - Sample rate in Hz
+//! - `$bitdepth` - Bits per sample (lossless formats only)
+//! - `$lossless` - `1` for lossless formats, empty otherwise (for use with `%if`)
+//! - `$quality` - Format and quality, such as `FLAC-16/44` (bit depth and
+//!   sample rate in kHz) or `MP3-320` (bitrate)
+//! - `$added` - When the track was added, as `YYYY-MM-DD HH:MM:SS` (UTC)
+//! - `$modified` - When the track was last modified, in the same format
 //!
 //! ## Functions
 //!
@@ -33,11 +51,44 @@
 //! - `%title{text}` - Convert to title case
 //! - `%left{text,n}` - Take first n characters
 //! - `%right{text,n}` - Take last n characters
+//! - `%truncate{text,n}` - Shorten to at most n characters, cutting at a word
+//!   boundary where possible
 //! - `%if{condition,then}` - Output `then` if condition is non-empty
 //! - `%if{condition,then,else}` - Output `then` or `else` based on condition
 //! - `%first{text,text,...}` - Return first non-empty value
+//! - `%ifdef{var,then}` - Output `then` if the variable `var` is set and
+//!   non-empty
+//! - `%ifdef{var,then,else}` - Output `then` or `else` based on whether `var`
+//!   is set
+//! - `%default{var,fallback}` - Output the value of `var`, or `fallback` if it
+//!   is not set or empty
+//! - `%delete{text}` - Output `text`, or nothing if it uses a variable that is
+//!   not set (e.g., `%delete{$year - }$album`)
 //! - `%replace{text,from,to}` - Replace occurrences
 //! - `%sanitize{text}` - Remove/replace filesystem-unsafe characters
+//! - `%slug{text}` - Convert to a lowercase, ASCII, dash-separated slug
+//! - `%date{date,format}` - Format a date such as `$added` with a
+//!   [strftime](chrono::format::strftime) format (e.g., `%date{$added,%Y-%m}`)
+//! - `%aunique{}` - Disambiguate albums with the same artist and title
+//!
+//! ## Album Disambiguation
+//!
+//! Two albums with the same artist and title (such as an original release and
+//! a remaster) would be organized into the same directory. `%aunique{}`
+//! renders a disambiguator like ` [1975]` for such albums, and nothing for
+//! albums that are unique. It tries `year`, `label`, `catalog`, `albumtype`,
+//! `country`, `edition`, and `media` in turn, using the first field whose
+//! values differ between all albums, and falls back to a number. Other
+//! fields can be given as `%aunique{label year}`, including `disambiguation`
+//! and `released`.
+//!
+//! Editions can also be kept apart explicitly, as in
+//! `$album%ifdef{edition, [$edition]}%ifdef{media, [$media]}`.
+//!
+//! `%aunique` and the album variables `$media`, `$edition` and
+//! `$disambiguation` need the track's album, which is passed to the context
+//! with [`TemplateContext::set_album`], along with the library's albums.
+//! Without them `%aunique` renders nothing.
 //!
 //! # Examples
 //!
@@ -61,8 +112,25 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
 use crate::error::Error;
-use crate::metadata::Track;
+use crate::metadata::{Album, Track, VARIOUS_ARTISTS, is_various_artists};
+
+/// Format of the `$added` and `$modified` variables.
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Fields tried by `%aunique` when none are given.
+const DEFAULT_DISAMBIGUATORS: &[&str] = &[
+    "year",
+    "label",
+    "catalog",
+    "albumtype",
+    "country",
+    "edition",
+    "media",
+];
 
 /// A parsed path template.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -95,16 +163,55 @@ enum TemplateExpr {
     /// Variable reference.
     Variable(String),
     /// Nested function call.
-    Function {
-        name: String,
-        args: Vec<TemplateExpr>,
-    },
+    Function { name: String, args: Vec<Self> },
 }
 
 /// Context for template rendering, containing variable values.
 #[derive(Debug, Clone, Default)]
 pub struct TemplateContext {
     variables: HashMap<String, String>,
+    /// The track's album and all albums with the same artist and title.
+    album: Option<(Album, Vec<Album>)>,
+    /// Whether variables that are not set render as empty text.
+    unset_as_empty: bool,
+}
+
+/// Albums of a library, grouped by artist and title for `%aunique`.
+#[derive(Debug, Clone, Default)]
+pub struct AlbumSet {
+    groups: HashMap<String, Vec<Album>>,
+}
+
+impl AlbumSet {
+    /// Group albums by artist and title.
+    #[must_use]
+    pub fn new(albums: impl IntoIterator<Item = Album>) -> Self {
+        let mut groups: HashMap<String, Vec<Album>> = HashMap::new();
+        for album in albums {
+            groups.entry(album_key(&album)).or_default().push(album);
+        }
+        for group in groups.values_mut() {
+            group.sort_by(|a, b| a.added_at.cmp(&b.added_at).then(a.id.0.cmp(&b.id.0)));
+        }
+        Self { groups }
+    }
+
+    /// Get all albums with the same artist and title as an album.
+    #[must_use]
+    pub fn homonyms(&self, album: &Album) -> &[Album] {
+        self.groups
+            .get(&album_key(album))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Get the key albums are grouped by in an [`AlbumSet`].
+fn album_key(album: &Album) -> String {
+    format!(
+        "{}::{}",
+        album.artist.to_lowercase(),
+        album.title.to_lowercase()
+    )
 }
 
 impl TemplateContext {
@@ -125,17 +232,49 @@ impl TemplateContext {
         self.variables.get(name).map(String::as_str)
     }
 
+    /// Set the album of the track, for `%aunique` and album variables.
+    ///
+    /// `library` must contain the albums of the library so that albums with
+    /// the same artist and title can be told apart.
+    pub fn set_album(&mut self, album: &Album, library: &AlbumSet) {
+        if let Some(album_type) = album.album_type {
+            self.set("albumtype", &album_type.to_string());
+        }
+        if let Some(ref label) = album.label {
+            self.set("label", label);
+        }
+        self.set_release_variables(album);
+        if album.is_compilation {
+            self.set_compilation(true);
+        }
+        self.album = Some((album.clone(), library.homonyms(album).to_vec()));
+    }
+
+    /// Mark the track as part of a compilation, or not.
+    ///
+    /// Compilations have `$album_artist_or_va` set to "Various Artists" and
+    /// `$compilation` set to `1`.
+    pub fn set_compilation(&mut self, is_compilation: bool) {
+        if is_compilation {
+            self.set("album_artist_or_va", VARIOUS_ARTISTS);
+            self.set("compilation", "1");
+        } else {
+            let album_artist = self.get("album_artist").unwrap_or_default().to_string();
+            self.set("album_artist_or_va", &album_artist);
+            self.set("compilation", "");
+        }
+    }
+
     /// Create a context from a Track.
     #[must_use]
     pub fn from_track(track: &Track) -> Self {
         let mut ctx = Self::new();
 
+        let album_artist = track.album_artist.as_deref().unwrap_or(&track.artist);
         ctx.set("artist", &track.artist);
-        ctx.set(
-            "album_artist",
-            track.album_artist.as_deref().unwrap_or(&track.artist),
-        );
+        ctx.set("album_artist", album_artist);
         ctx.set("title", &track.title);
+        ctx.set_compilation(is_various_artists(album_artist));
 
         if let Some(album) = &track.album_title {
             ctx.set("album", album);
@@ -162,8 +301,94 @@ impl TemplateContext {
             ctx.set("ext", ext);
         }
 
+        ctx.set_audio_properties(track);
+        ctx.set_datetime("added", &track.added_at);
+        ctx.set_datetime("modified", &track.modified_at);
+
         ctx
     }
+
+    /// Create a context from an album.
+    ///
+    /// Sets the album variables, with `$artist` being the album artist, and
+    /// `$tracks` to the number of tracks.
+    #[must_use]
+    pub fn from_album(album: &Album) -> Self {
+        let mut ctx = Self::new();
+
+        ctx.set("artist", &album.artist);
+        ctx.set("album_artist", &album.artist);
+        ctx.set("album", &album.title);
+        ctx.set("tracks", &album.track_count.to_string());
+        ctx.set_compilation(album.is_compilation || is_various_artists(&album.artist));
+
+        if let Some(year) = album.year {
+            ctx.set("year", &format!("{year}"));
+        }
+        if let Some(genre) = album.genres.first() {
+            ctx.set("genre", genre);
+        }
+        if let Some(album_type) = album.album_type {
+            ctx.set("albumtype", &album_type.to_string());
+        }
+        if let Some(ref label) = album.label {
+            ctx.set("label", label);
+        }
+        if let Some(ref country) = album.country {
+            ctx.set("country", country);
+        }
+        ctx.set_release_variables(album);
+
+        ctx.set_datetime("added", &album.added_at);
+        ctx.set_datetime("modified", &album.modified_at);
+
+        ctx
+    }
+
+    /// Set the variables that tell editions of an album apart.
+    fn set_release_variables(&mut self, album: &Album) {
+        if let Some(media) = album.media {
+            self.set("media", &media.to_string());
+        }
+        if let Some(ref edition) = album.edition {
+            self.set("edition", edition);
+        }
+        if let Some(ref disambiguation) = album.disambiguation {
+            self.set("disambiguation", disambiguation);
+        }
+    }
+
+    /// Set the format and quality variables of a track.
+    fn set_audio_properties(&mut self, track: &Track) {
+        let format = track.format.to_string();
+        let lossless = track.format.is_lossless();
+        self.set("format", &format);
+        self.set("lossless", if lossless { "1" } else { "" });
+
+        if let Some(bitrate) = track.bitrate {
+            self.set("bitrate", &bitrate.to_string());
+        }
+        if let Some(sample_rate) = track.sample_rate {
+            self.set("samplerate", &sample_rate.to_string());
+        }
+        if let Some(bit_depth) = track.bit_depth {
+            self.set("bitdepth", &bit_depth.to_string());
+        }
+
+        let quality = match (lossless, track.bit_depth, track.sample_rate, track.bitrate) {
+            (true, Some(bit_depth), Some(sample_rate), _) => {
+                format!("{format}-{bit_depth}/{}", sample_rate / 1000)
+            }
+            (false, _, _, Some(bitrate)) => format!("{format}-{bitrate}"),
+            _ => format,
+        };
+        self.set("quality", &quality);
+    }
+
+    /// Set a variable to a date and time, in the format `%date` reads.
+    pub fn set_datetime(&mut self, name: &str, value: &DateTime<Utc>) {
+        self.set(name, &value.format(DATETIME_FORMAT).to_string());
+    }
 }
 
 impl PathTemplate {
@@ -210,6 +435,24 @@ impl PathTemplate {
         Ok(PathBuf::from(result))
     }
 
+    /// Render the template as text, such as a line of command output.
+    ///
+    /// Unlike [`Self::render`], slashes are kept as they are, and variables
+    /// that are not set render as empty text instead of failing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a function fails.
+    pub fn render_text(&self, ctx: &TemplateContext) -> Result<String, Error> {
+        let mut ctx = ctx.clone();
+        ctx.unset_as_empty = true;
+
+        self.parts
+            .iter()
+            .map(|part| render_part(part, &ctx))
+            .collect()
+    }
+
     /// Render the template and include the file extension.
     ///
     /// This is a convenience method that appends `.$ext` if not already in the template.
@@ -394,6 +637,10 @@ fn parse_function_args(
                 let var_name = parse_variable_name(chars)?;
                 current_arg.push(TemplateExpr::Variable(var_name));
             }
+            '%' if !starts_function(chars) => {
+                // Literal percent sign, e.g., in a `%date` format
+                current_arg.push(TemplateExpr::Literal("%".to_string()));
+            }
             '%' => {
                 // Nested function
                 let (name, nested_args) = parse_function(chars)?;
@@ -424,6 +671,19 @@ fn parse_function_args(
     ))
 }
 
+/// Check if the characters after a `%` form a function call (`name{`).
+fn starts_function(chars: &std::iter::Peekable<std::str::Chars>) -> bool {
+    let mut lookahead = chars.clone();
+    let mut has_name = false;
+    while lookahead
+        .next_if(|ch| ch.is_alphanumeric() || *ch == '_')
+        .is_some()
+    {
+        has_name = true;
+    }
+    has_name && lookahead.peek() == Some(&'{')
+}
+
 /// Flatten a list of expressions into a single expression.
 fn flatten_exprs(exprs: Vec<TemplateExpr>) -> TemplateExpr {
     if exprs.len() == 1 {
@@ -467,22 +727,25 @@ fn flatten_exprs(exprs: Vec<TemplateExpr>) -> TemplateExpr {
 fn render_part(part: &TemplatePart, ctx: &TemplateContext) -> Result<String, Error> {
     match part {
         TemplatePart::Literal(s) => Ok(s.clone()),
-        TemplatePart::Variable(name) => ctx
-            .get(name)
-            .map(String::from)
-            .ok_or_else(|| Error::Validation(format!("Unknown variable: ${name}"))),
+        TemplatePart::Variable(name) => render_variable(name, ctx),
         TemplatePart::Function { name, args } => render_function(name, args, ctx),
     }
 }
 
+/// Render a variable.
+fn render_variable(name: &str, ctx: &TemplateContext) -> Result<String, Error> {
+    match ctx.get(name) {
+        Some(value) => Ok(value.to_string()),
+        None if ctx.unset_as_empty => Ok(String::new()),
+        None => Err(Error::Validation(format!("Unknown variable: ${name}"))),
+    }
+}
+
 /// Render a template expression.
 fn render_expr(expr: &TemplateExpr, ctx: &TemplateContext) -> Result<String, Error> {
     match expr {
         TemplateExpr::Literal(s) => Ok(s.clone()),
-        TemplateExpr::Variable(name) => ctx
-            .get(name)
-            .map(String::from)
-            .ok_or_else(|| Error::Validation(format!("Unknown variable: ${name}"))),
+        TemplateExpr::Variable(name) => render_variable(name, ctx),
         TemplateExpr::Function { name, args } => render_function(name, args, ctx),
     }
 }
@@ -517,32 +780,36 @@ fn render_function(
         "left" => {
             require_args(name, args, 2)?;
             let text = render_expr(&args[0], ctx)?;
-            let n: usize = render_expr(&args[1], ctx)?
-                .parse()
-                .map_err(|_| Error::Validation("left: second argument must be a number".into()))?;
+            let n = number_arg(name, &args[1], ctx)?;
             Ok(text.chars().take(n).collect())
         }
         "right" => {
             require_args(name, args, 2)?;
             let text = render_expr(&args[0], ctx)?;
-            let n: usize = render_expr(&args[1], ctx)?
-                .parse()
-                .map_err(|_| Error::Validation("right: second argument must be a number".into()))?;
+            let n = number_arg(name, &args[1], ctx)?;
             let chars: Vec<char> = text.chars().collect();
             let start = chars.len().saturating_sub(n);
             Ok(chars[start..].iter().collect())
         }
-        "if" => {
-            if args.len() < 2 || args.len() > 3 {
-                return Err(Error::Validation(
-                    "if: requires 2 or 3 arguments".to_string(),
-                ));
-            }
-            let condition = render_expr(&args[0], ctx)?;
-            if !condition.is_empty() {
+        "truncate" => {
+            require_args(name, args, 2)?;
+            let text = render_expr(&args[0], ctx)?;
+            let n = number_arg(name, &args[1], ctx)?;
+            Ok(truncate(&text, n))
+        }
+        "if" | "ifdef" => render_conditional(name, args, ctx),
+        "default" => {
+            require_args(name, args, 2)?;
+            if is_set(&args[0], ctx) {
+                render_expr(&args[0], ctx)
+            } else {
                 render_expr(&args[1], ctx)
-            } else if args.len() == 3 {
-                render_expr(&args[2], ctx)
+            }
+        }
+        "delete" => {
+            require_args(name, args, 1)?;
+            if uses_only_set_variables(&args[0], ctx) {
+                render_expr(&args[0], ctx)
             } else {
                 Ok(String::new())
             }
@@ -568,17 +835,21 @@ fn render_function(
             let text = render_expr(&args[0], ctx)?;
             Ok(sanitize_path_component(&text))
         }
+        "slug" => {
+            require_args(name, args, 1)?;
+            Ok(slugify(&render_expr(&args[0], ctx)?))
+        }
+        "date" => render_date(args, ctx),
         "asciify" => {
             require_args(name, args, 1)?;
             let text = render_expr(&args[0], ctx)?;
             Ok(asciify(&text))
         }
+        "aunique" => render_aunique(args, ctx),
         "padnum" => {
             require_args(name, args, 2)?;
             let text = render_expr(&args[0], ctx)?;
-            let width: usize = render_expr(&args[1], ctx)?.parse().map_err(|_| {
-                Error::Validation("padnum: second argument must be a number".into())
-            })?;
+            let width = number_arg(name, &args[1], ctx)?;
             // Try to parse as number and pad
             Ok(text
                 .parse::<u32>()
@@ -588,6 +859,167 @@ fn render_function(
     }
 }
 
+/// Render `%if{condition,then,else}` or `%ifdef{var,then,else}`.
+///
+/// `%if` checks whether the condition renders to a non-empty string, and
+/// `%ifdef` whether the variable is set.
+fn render_conditional(
+    name: &str,
+    args: &[TemplateExpr],
+    ctx: &TemplateContext,
+) -> Result<String, Error> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(Error::Validation(format!(
+            "{name}: requires 2 or 3 arguments"
+        )));
+    }
+    let condition = if name == "ifdef" {
+        is_set(&args[0], ctx)
+    } else {
+        !render_expr(&args[0], ctx)?.is_empty()
+    };
+    if condition {
+        render_expr(&args[1], ctx)
+    } else if let Some(otherwise) = args.get(2) {
+        render_expr(otherwise, ctx)
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Check if a variable argument is set to a non-empty value.
+///
+/// The variable can be given by name (`year`) or as a reference (`$year`).
+fn is_set(arg: &TemplateExpr, ctx: &TemplateContext) -> bool {
+    let name = match arg {
+        TemplateExpr::Variable(name) => name.as_str(),
+        TemplateExpr::Literal(name) => name.trim().trim_start_matches('$'),
+        TemplateExpr::Function { .. } => return false,
+    };
+    ctx.get(name).is_some_and(|value| !value.is_empty())
+}
+
+/// Check if every variable used by an expression is set.
+fn uses_only_set_variables(expr: &TemplateExpr, ctx: &TemplateContext) -> bool {
+    match expr {
+        TemplateExpr::Literal(_) => true,
+        TemplateExpr::Variable(name) => ctx.get(name).is_some(),
+        TemplateExpr::Function { args, .. } => {
+            args.iter().all(|arg| uses_only_set_variables(arg, ctx))
+        }
+    }
+}
+
+/// Render `%aunique{fields}`.
+fn render_aunique(args: &[TemplateExpr], ctx: &TemplateContext) -> Result<String, Error> {
+    if args.len() > 1 {
+        return Err(Error::Validation(
+            "aunique: requires 0 or 1 arguments".to_string(),
+        ));
+    }
+    let fields = match args.first() {
+        Some(arg) => render_expr(arg, ctx)?,
+        None => DEFAULT_DISAMBIGUATORS.join(" "),
+    };
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    ctx.album.as_ref().map_or_else(
+        || Ok(String::new()),
+        |(album, homonyms)| disambiguate(album, homonyms, &fields),
+    )
+}
+
+/// Render the disambiguator of an album among albums with the same artist
+/// and title.
+fn disambiguate(album: &Album, homonyms: &[Album], fields: &[&str]) -> Result<String, Error> {
+    if homonyms.len() < 2 {
+        return Ok(String::new());
+    }
+
+    for field in fields {
+        let values = homonyms
+            .iter()
+            .map(|other| album_field(other, field))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut distinct: Vec<&String> = values.iter().flatten().collect();
+        distinct.sort();
+        distinct.dedup();
+        if distinct.len() == homonyms.len()
+            && let Some(value) = album_field(album, field)?
+        {
+            return Ok(format!(" [{value}]"));
+        }
+    }
+
+    // No field tells the albums apart, so number them in the order they were added
+    let index = homonyms
+        .iter()
+        .position(|other| other.id == album.id)
+        .unwrap_or(homonyms.len());
+    Ok(format!(" [{}]", index + 1))
+}
+
+/// Get the value of an album field used by `%aunique`.
+fn album_field(album: &Album, field: &str) -> Result<Option<String>, Error> {
+    let value = match field {
+        "year" => album.year.map(|year| year.to_string()),
+        "label" => album.label.clone(),
+        "catalog" | "catalognum" | "catalog_number" => album.catalog_number.clone(),
+        "albumtype" | "album_type" => album.album_type.map(|t| t.to_string()),
+        "country" => album.country.clone(),
+        "edition" => album.edition.clone(),
+        "media" => album.media.map(|media| media.to_string()),
+        "disambiguation" => album.disambiguation.clone(),
+        "released" => album.release_date.map(|date| date.to_string()),
+        _ => {
+            return Err(Error::Validation(format!(
+                "aunique: unknown album field: {field}"
+            )));
+        }
+    };
+    Ok(value)
+}
+
+/// Render `%date{date,format}`.
+///
+/// Empty dates render as nothing, so `%date` can be combined with `%default`.
+fn render_date(args: &[TemplateExpr], ctx: &TemplateContext) -> Result<String, Error> {
+    require_args("date", args, 2)?;
+    let value = render_expr(&args[0], ctx)?;
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(String::new());
+    }
+
+    let datetime = NaiveDateTime::parse_from_str(value, DATETIME_FORMAT)
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|d| d.naive_utc())
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| Error::Validation(format!("date: invalid date: {value}")))?;
+
+    let format = render_expr(&args[1], ctx)?;
+    let items: Vec<Item> = StrftimeItems::new(&format).collect();
+    if items.contains(&Item::Error) {
+        return Err(Error::Validation(format!("date: invalid format: {format}")));
+    }
+    Ok(datetime.format_with_items(items.into_iter()).to_string())
+}
+
+/// Render a numeric function argument.
+fn number_arg(name: &str, arg: &TemplateExpr, ctx: &TemplateContext) -> Result<usize, Error> {
+    render_expr(arg, ctx)?
+        .trim()
+        .parse()
+        .map_err(|_| Error::Validation(format!("{name}: second argument must be a number")))
+}
+
 /// Check that a function has the required number of arguments.
 fn require_args(name: &str, args: &[TemplateExpr], count: usize) -> Result<(), Error> {
     if args.len() != count {
@@ -650,6 +1082,32 @@ pub fn sanitize_path_component(s: &str) -> String {
     result.to_string()
 }
 
+/// Shorten a string to at most `max` characters.
+///
+/// Words are kept whole unless the first word alone is too long.
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let head: String = s.chars().take(max).collect();
+    let next_is_space = s.chars().nth(max).is_some_and(char::is_whitespace);
+    let head = match head.rfind(char::is_whitespace) {
+        Some(end) if !next_is_space => &head[..end],
+        _ => head.as_str(),
+    };
+    head.trim_end().to_string()
+}
+
+/// Convert a string to a slug: lowercase ASCII words separated by dashes.
+fn slugify(s: &str) -> String {
+    asciify(s)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
 /// Convert a string to ASCII, removing or replacing non-ASCII characters.
 fn asciify(s: &str) -> String {
     let mut result = String::with_capacity(s.len());
@@ -916,6 +1374,289 @@ mod tests {
         assert_eq!(ctx.get("year"), Some("1975"));
         assert_eq!(ctx.get("genre"), Some("Rock"));
         assert_eq!(ctx.get("ext"), Some("mp3"));
+        assert_eq!(
+            ctx.get("added"),
+            Some(
+                track
+                    .added_at
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+                    .as_str()
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_text() {
+        let template =
+            PathTemplate::parse("$artist - $title%delete{ ($year)} [$album/$genre]").unwrap();
+        let mut ctx = TemplateContext::new();
+        ctx.set("artist", "AC/DC");
+        ctx.set("title", "Thunderstruck");
+
+        assert_eq!(
+            template.render_text(&ctx).unwrap(),
+            "AC/DC - Thunderstruck [/]"
+        );
+        assert!(template.render(&ctx).is_err());
+    }
+
+    #[test]
+    fn test_from_album() {
+        let mut album = Album::new("Back in Black".to_string(), "AC/DC".to_string());
+        album.year = Some(1980);
+        album.track_count = 10;
+
+        let ctx = TemplateContext::from_album(&album);
+
+        assert_eq!(ctx.get("artist"), Some("AC/DC"));
+        assert_eq!(ctx.get("album"), Some("Back in Black"));
+        assert_eq!(ctx.get("year"), Some("1980"));
+        assert_eq!(ctx.get("tracks"), Some("10"));
+        assert_eq!(ctx.get("compilation"), Some(""));
+    }
+
+    #[test]
+    fn test_from_track_quality() {
+        use crate::metadata::AudioFormat;
+        use std::time::Duration;
+
+        let mut track = Track::new(
+            PathBuf::from("/music/test.flac"),
+            "Time".to_string(),
+            "Pink Floyd".to_string(),
+            Duration::from_secs(413),
+        );
+        track.format = AudioFormat::Flac;
+        track.sample_rate = Some(44_100);
+        track.bit_depth = Some(16);
+        track.bitrate = Some(1011);
+
+        let ctx = TemplateContext::from_track(&track);
+        assert_eq!(ctx.get("format"), Some("FLAC"));
+        assert_eq!(ctx.get("samplerate"), Some("44100"));
+        assert_eq!(ctx.get("bitdepth"), Some("16"));
+        assert_eq!(ctx.get("quality"), Some("FLAC-16/44"));
+
+        let template = PathTemplate::parse("%if{$lossless,Lossless,Lossy}/$artist").unwrap();
+        assert_eq!(
+            template.render(&ctx).unwrap(),
+            PathBuf::from("Lossless/Pink Floyd")
+        );
+
+        track.format = AudioFormat::Mp3;
+        track.bit_depth = None;
+        track.bitrate = Some(320);
+        let ctx = TemplateContext::from_track(&track);
+        assert_eq!(ctx.get("quality"), Some("MP3-320"));
+        assert_eq!(ctx.get("lossless"), Some(""));
+
+        track.bitrate = None;
+        let ctx = TemplateContext::from_track(&track);
+        assert_eq!(ctx.get("quality"), Some("MP3"));
+    }
+
+    #[test]
+    fn test_render_date() {
+        let template = PathTemplate::parse("%date{$added,%Y-%m}/%date{$released,%Y}").unwrap();
+        let mut ctx = TemplateContext::new();
+        let added = DateTime::parse_from_rfc3339("2024-03-09T18:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        ctx.set_datetime("added", &added);
+        ctx.set("released", "1975-11-21");
+        assert_eq!(
+            template.render(&ctx).unwrap(),
+            PathBuf::from("2024-03/1975")
+        );
+
+        ctx.set("released", "");
+        let template = PathTemplate::parse("%default{%date{$released,%Y},unknown}").unwrap();
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("unknown"));
+
+        let template = PathTemplate::parse("%date{$added,%Q}").unwrap();
+        assert!(template.render(&ctx).is_err());
+        ctx.set("added", "yesterday");
+        let template = PathTemplate::parse("%date{$added,%Y}").unwrap();
+        assert!(template.render(&ctx).is_err());
+    }
+
+    #[test]
+    fn test_render_slug() {
+        let template = PathTemplate::parse("%slug{$album}").unwrap();
+        let mut ctx = TemplateContext::new();
+        ctx.set("album", "Björk: Live at Café — 2001!");
+        assert_eq!(
+            template.render(&ctx).unwrap(),
+            PathBuf::from("bjork-live-at-cafe-2001")
+        );
+    }
+
+    #[test]
+    fn test_render_truncate() {
+        let template = PathTemplate::parse("%truncate{$title,10}").unwrap();
+        let mut ctx = TemplateContext::new();
+        ctx.set("title", "Shine On You Crazy Diamond");
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("Shine On"));
+
+        ctx.set("title", "Time");
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("Time"));
+
+        let template = PathTemplate::parse("%truncate{$title,ten}").unwrap();
+        assert!(template.render(&ctx).is_err());
+    }
+
+    #[test]
+    fn test_render_ifdef() {
+        let template = PathTemplate::parse("%ifdef{year,$year - }$album").unwrap();
+        let mut ctx = TemplateContext::new();
+        ctx.set("album", "Abbey Road");
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("Abbey Road"));
+
+        ctx.set("year", "1969");
+        assert_eq!(
+            template.render(&ctx).unwrap(),
+            PathBuf::from("1969 - Abbey Road")
+        );
+
+        let template = PathTemplate::parse("%ifdef{$genre,$genre,Unknown}").unwrap();
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("Unknown"));
+    }
+
+    #[test]
+    fn test_render_default() {
+        let template = PathTemplate::parse("%default{$album_artist,Unknown Artist}").unwrap();
+        let mut ctx = TemplateContext::new();
+        assert_eq!(
+            template.render(&ctx).unwrap(),
+            PathBuf::from("Unknown Artist")
+        );
+
+        ctx.set("album_artist", "Queen");
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("Queen"));
+    }
+
+    #[test]
+    fn test_render_delete() {
+        let template = PathTemplate::parse("%delete{$year - }%delete{%upper{$album}}").unwrap();
+        let mut ctx = TemplateContext::new();
+        ctx.set("album", "Help!");
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("HELP!"));
+
+        ctx.set("year", "1965");
+        assert_eq!(
+            template.render(&ctx).unwrap(),
+            PathBuf::from("1965 - HELP!")
+        );
+    }
+
+    #[test]
+    fn test_aunique() {
+        let template = PathTemplate::parse("$album%aunique{}").unwrap();
+        let mut original = Album::new("Abbey Road".to_string(), "The Beatles".to_string());
+        original.year = Some(1969);
+        let mut remaster = Album::new("Abbey Road".to_string(), "The Beatles".to_string());
+        remaster.year = Some(2019);
+        let other = Album::new("Help!".to_string(), "The Beatles".to_string());
+        let library = AlbumSet::new([original.clone(), remaster.clone(), other.clone()]);
+
+        let render = |album: &Album| {
+            let mut ctx = TemplateContext::new();
+            ctx.set("album", &album.title);
+            ctx.set_album(album, &library);
+            template.render(&ctx).unwrap()
+        };
+
+        assert_eq!(render(&original), PathBuf::from("Abbey Road [1969]"));
+        assert_eq!(render(&remaster), PathBuf::from("Abbey Road [2019]"));
+        assert_eq!(render(&other), PathBuf::from("Help!"));
+    }
+
+    #[test]
+    fn test_aunique_fallbacks() {
+        let mut first = Album::new("Live".to_string(), "Band".to_string());
+        first.label = Some("Sub Pop".to_string());
+        let mut second = Album::new("live".to_string(), "Band".to_string());
+        second.label = Some("Matador".to_string());
+        second.added_at = first.added_at + chrono::TimeDelta::seconds(1);
+        let library = AlbumSet::new([first, second.clone()]);
+
+        let mut ctx = TemplateContext::new();
+        ctx.set_album(&second, &library);
+        let render = |source: &str| PathTemplate::parse(source).unwrap().render(&ctx);
+
+        assert_eq!(render("%aunique{}").unwrap(), PathBuf::from(" [Matador]"));
+        assert_eq!(render("%aunique{year}").unwrap(), PathBuf::from(" [2]"));
+        assert!(render("%aunique{mood}").is_err());
+    }
+
+    #[test]
+    fn test_album_editions() {
+        use crate::metadata::Media;
+
+        let mut deluxe = Album::new("Rumours".to_string(), "Fleetwood Mac".to_string());
+        deluxe.year = Some(1977);
+        deluxe.edition = Some("Deluxe Edition".to_string());
+        deluxe.media = Some(Media::Cd);
+        let mut vinyl = Album::new("Rumours".to_string(), "Fleetwood Mac".to_string());
+        vinyl.year = Some(1977);
+        vinyl.media = Some(Media::Vinyl);
+        let library = AlbumSet::new([deluxe.clone(), vinyl.clone()]);
+
+        let render = |source: &str, album: &Album| {
+            let mut ctx = TemplateContext::new();
+            ctx.set("album", &album.title);
+            ctx.set_album(album, &library);
+            PathTemplate::parse(source).unwrap().render(&ctx).unwrap()
+        };
+
+        let editions = "$album%ifdef{edition, [$edition]}%ifdef{media, [$media]}";
+        assert_eq!(
+            render(editions, &deluxe),
+            PathBuf::from("Rumours [Deluxe Edition] [CD]")
+        );
+        assert_eq!(render(editions, &vinyl), PathBuf::from("Rumours [Vinyl]"));
+
+        // Only one of the albums has an edition, so the media tells them apart
+        assert_eq!(
+            render("$album%aunique{}", &vinyl),
+            PathBuf::from("Rumours [Vinyl]")
+        );
+
+        let ctx = TemplateContext::from_album(&deluxe);
+        assert_eq!(ctx.get("edition"), Some("Deluxe Edition"));
+        assert_eq!(ctx.get("media"), Some("CD"));
+        assert_eq!(ctx.get("disambiguation"), None);
+    }
+
+    #[test]
+    fn test_aunique_without_library() {
+        let template = PathTemplate::parse("Album%aunique{}").unwrap();
+        let ctx = TemplateContext::new();
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("Album"));
+    }
+
+    #[test]
+    fn test_compilation_album_artist() {
+        use std::time::Duration;
+
+        let track = Track::new(
+            PathBuf::from("/music/test.mp3"),
+            "Song 2".to_string(),
+            "Blur".to_string(),
+            Duration::from_secs(122),
+        );
+        let template =
+            PathTemplate::parse("$album_artist_or_va/%if{$compilation,$artist - }$title").unwrap();
+
+        let mut ctx = TemplateContext::from_track(&track);
+        assert_eq!(template.render(&ctx).unwrap(), PathBuf::from("Blur/Song 2"));
+
+        ctx.set_compilation(true);
+        assert_eq!(
+            template.render(&ctx).unwrap(),
+            PathBuf::from("Various Artists/Blur - Song 2")
+        );
     }
 
     #[test]
@@ -0,0 +1,25 @@
+-- Apollo Music Library Schema
+-- Migration: 0003_ratings_and_plays
+-- Description: Add track ratings, favorites, and play history
+
+-- Track ratings table
+-- Kept separate from tracks so re-importing a file does not reset it
+CREATE TABLE IF NOT EXISTS track_ratings (
+    track_id TEXT PRIMARY KEY NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    rating INTEGER CHECK (rating BETWEEN 1 AND 5),  -- NULL when unrated
+    favorite INTEGER NOT NULL DEFAULT 0,  -- Boolean
+    modified_at TEXT NOT NULL  -- ISO8601 timestamp
+);
+
+CREATE INDEX IF NOT EXISTS idx_track_ratings_rating ON track_ratings(rating);
+
+-- Play history table
+-- One row per play, so play counts and last played dates are derived
+CREATE TABLE IF NOT EXISTS plays (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    track_id TEXT NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    played_at TEXT NOT NULL  -- ISO8601 timestamp
+);
+
+CREATE INDEX IF NOT EXISTS idx_plays_track_id ON plays(track_id);
+CREATE INDEX IF NOT EXISTS idx_plays_played_at ON plays(played_at);
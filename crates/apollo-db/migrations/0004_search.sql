@@ -0,0 +1,55 @@
+-- Apollo Music Library Schema
+-- Migration: 0004_search
+-- Description: Add full-text search for albums and playlists
+
+-- Full-text search for albums
+CREATE VIRTUAL TABLE IF NOT EXISTS albums_fts USING fts5(
+    title,
+    artist,
+    content='albums',
+    content_rowid='rowid'
+);
+
+-- Triggers to keep album FTS in sync
+CREATE TRIGGER IF NOT EXISTS albums_ai AFTER INSERT ON albums BEGIN
+    INSERT INTO albums_fts(rowid, title, artist)
+    VALUES (new.rowid, new.title, new.artist);
+END;
+
+CREATE TRIGGER IF NOT EXISTS albums_ad AFTER DELETE ON albums BEGIN
+    INSERT INTO albums_fts(albums_fts, rowid, title, artist)
+    VALUES ('delete', old.rowid, old.title, old.artist);
+END;
+
+CREATE TRIGGER IF NOT EXISTS albums_au AFTER UPDATE OF title, artist ON albums BEGIN
+    INSERT INTO albums_fts(albums_fts, rowid, title, artist)
+    VALUES ('delete', old.rowid, old.title, old.artist);
+    INSERT INTO albums_fts(rowid, title, artist)
+    VALUES (new.rowid, new.title, new.artist);
+END;
+
+-- Full-text search for playlists
+CREATE VIRTUAL TABLE IF NOT EXISTS playlists_fts USING fts5(
+    name,
+    description,
+    content='playlists',
+    content_rowid='rowid'
+);
+
+-- Triggers to keep playlist FTS in sync
+CREATE TRIGGER IF NOT EXISTS playlists_ai AFTER INSERT ON playlists BEGIN
+    INSERT INTO playlists_fts(rowid, name, description)
+    VALUES (new.rowid, new.name, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlists_ad AFTER DELETE ON playlists BEGIN
+    INSERT INTO playlists_fts(playlists_fts, rowid, name, description)
+    VALUES ('delete', old.rowid, old.name, old.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlists_au AFTER UPDATE OF name, description ON playlists BEGIN
+    INSERT INTO playlists_fts(playlists_fts, rowid, name, description)
+    VALUES ('delete', old.rowid, old.name, old.description);
+    INSERT INTO playlists_fts(rowid, name, description)
+    VALUES (new.rowid, new.name, new.description);
+END;
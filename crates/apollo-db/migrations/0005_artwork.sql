@@ -0,0 +1,13 @@
+-- Apollo Music Library Schema
+-- Migration: 0005_artwork
+-- Description: Add album cover art
+
+-- Artwork table
+-- One front cover per album, so art can be served without the files
+CREATE TABLE IF NOT EXISTS artwork (
+    album_id TEXT PRIMARY KEY NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+    mime_type TEXT NOT NULL,
+    data BLOB NOT NULL,
+    source TEXT,  -- URL the image was fetched from, if any
+    modified_at TEXT NOT NULL  -- ISO8601 timestamp
+);
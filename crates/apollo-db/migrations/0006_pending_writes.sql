@@ -0,0 +1,11 @@
+-- Apollo Music Library Schema
+-- Migration: 0006_pending_writes
+-- Description: Queue tag changes to write to files later
+
+-- Pending writes table
+-- Tracks whose tags changed in the library but not yet in their files
+CREATE TABLE IF NOT EXISTS pending_writes (
+    track_id TEXT PRIMARY KEY NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    cleared TEXT NOT NULL DEFAULT '',  -- Comma-separated fields to remove from the file
+    queued_at TEXT NOT NULL  -- ISO8601 timestamp
+);
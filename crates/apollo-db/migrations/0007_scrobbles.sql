@@ -0,0 +1,16 @@
+-- Apollo Music Library Schema
+-- Migration: 0007_scrobbles
+-- Description: Queue plays to scrobble to Last.fm and ListenBrainz
+
+-- Scrobbles table
+-- Plays that have not been sent to a scrobble service yet
+CREATE TABLE IF NOT EXISTS scrobbles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    track_id TEXT NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    service TEXT NOT NULL,  -- 'lastfm' or 'listenbrainz'
+    played_at TEXT NOT NULL,  -- ISO8601 timestamp
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_scrobbles_played_at ON scrobbles(played_at);
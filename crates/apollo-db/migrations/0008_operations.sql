@@ -0,0 +1,16 @@
+-- Apollo Music Library Schema
+-- Migration: 0008_operations
+-- Description: Log organize and bulk-edit operations so they can be undone
+
+-- Operations table
+-- File moves and track changes, recorded in the order they were made
+CREATE TABLE IF NOT EXISTS operations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,  -- 'organize' or 'tag'
+    description TEXT NOT NULL,
+    steps TEXT NOT NULL,  -- JSON array of steps
+    created_at TEXT NOT NULL,  -- ISO8601 timestamp
+    undone_at TEXT  -- ISO8601 timestamp, once undone
+);
+
+CREATE INDEX IF NOT EXISTS idx_operations_created_at ON operations(created_at);
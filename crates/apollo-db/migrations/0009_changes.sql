@@ -0,0 +1,51 @@
+-- Apollo Music Library Schema
+-- Migration: 0009_changes
+-- Description: Log changes to tracks and playlists for incremental sync
+
+-- Changes table
+-- One row per created, updated, or deleted track or playlist, in order
+CREATE TABLE IF NOT EXISTS changes (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    item TEXT NOT NULL,  -- 'track' or 'playlist'
+    item_id TEXT NOT NULL,
+    kind TEXT NOT NULL,  -- 'created', 'updated', or 'deleted'
+    changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))  -- ISO8601 timestamp
+);
+
+CREATE INDEX IF NOT EXISTS idx_changes_changed_at ON changes(changed_at);
+
+-- Triggers to log track changes
+CREATE TRIGGER IF NOT EXISTS tracks_changes_ai AFTER INSERT ON tracks BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('track', new.id, 'created');
+END;
+
+CREATE TRIGGER IF NOT EXISTS tracks_changes_au AFTER UPDATE ON tracks BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('track', new.id, 'updated');
+END;
+
+CREATE TRIGGER IF NOT EXISTS tracks_changes_ad AFTER DELETE ON tracks BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('track', old.id, 'deleted');
+END;
+
+-- Triggers to log playlist changes
+CREATE TRIGGER IF NOT EXISTS playlists_changes_ai AFTER INSERT ON playlists BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('playlist', new.id, 'created');
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlists_changes_au AFTER UPDATE ON playlists BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('playlist', new.id, 'updated');
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlists_changes_ad AFTER DELETE ON playlists BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('playlist', old.id, 'deleted');
+END;
+
+-- Adding or removing tracks changes a playlist, unless it is being deleted
+CREATE TRIGGER IF NOT EXISTS playlist_tracks_changes_ai AFTER INSERT ON playlist_tracks BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('playlist', new.playlist_id, 'updated');
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlist_tracks_changes_ad AFTER DELETE ON playlist_tracks
+WHEN EXISTS (SELECT 1 FROM playlists WHERE id = old.playlist_id) BEGIN
+    INSERT INTO changes(item, item_id, kind) VALUES ('playlist', old.playlist_id, 'updated');
+END;
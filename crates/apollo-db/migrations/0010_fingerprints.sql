@@ -0,0 +1,12 @@
+-- Apollo Music Library Schema
+-- Migration: 0010_fingerprints
+-- Description: Store audio fingerprints so tracks can be identified again
+
+-- Fingerprints table
+-- The Chromaprint fingerprint of a track's audio, computed on request
+CREATE TABLE IF NOT EXISTS fingerprints (
+    track_id TEXT PRIMARY KEY NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    fingerprint TEXT NOT NULL,
+    duration_secs INTEGER NOT NULL,  -- Duration of the fingerprinted audio
+    computed_at TEXT NOT NULL  -- ISO8601 timestamp
+);
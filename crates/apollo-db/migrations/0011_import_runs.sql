@@ -0,0 +1,22 @@
+-- Apollo Music Library Schema
+-- Migration: 0011_import_runs
+-- Description: Keep a history of imports and what came of them
+
+-- Import runs table
+-- One row per finished import, newest last
+CREATE TABLE IF NOT EXISTS import_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_path TEXT NOT NULL,
+    started_at TEXT NOT NULL,  -- ISO8601 timestamp
+    duration_ms INTEGER NOT NULL,
+    options TEXT NOT NULL,  -- JSON object of import options
+    tracks_found INTEGER NOT NULL,
+    tracks_imported INTEGER NOT NULL,
+    tracks_skipped INTEGER NOT NULL,
+    tracks_duplicate INTEGER NOT NULL,
+    tracks_failed INTEGER NOT NULL,
+    albums_created INTEGER NOT NULL,
+    cancelled INTEGER NOT NULL DEFAULT 0,
+    errors TEXT NOT NULL,  -- JSON array of messages
+    failed_files TEXT NOT NULL  -- JSON array of paths
+);
@@ -0,0 +1,14 @@
+-- Apollo Music Library Schema
+-- Migration: 0012_field_sources
+-- Description: Record where each track field came from
+
+-- Field sources table
+-- The source that last set a field of a track; fields without a row came
+-- from the file's tags
+CREATE TABLE IF NOT EXISTS field_sources (
+    track_id TEXT NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    field TEXT NOT NULL,  -- Field name, as in the track's JSON representation
+    source TEXT NOT NULL,  -- file, musicbrainz, discogs, plugin or manual
+    updated_at TEXT NOT NULL,  -- ISO8601 timestamp
+    PRIMARY KEY (track_id, field)
+);
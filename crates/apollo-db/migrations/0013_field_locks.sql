@@ -0,0 +1,11 @@
+-- Apollo Music Library Schema
+-- Migration: 0013_field_locks
+-- Description: Lock fields of tracks against automatic tagging
+
+-- Field locks table
+-- Fields of a track that lookups, plugins and bulk edits leave alone
+CREATE TABLE IF NOT EXISTS field_locks (
+    track_id TEXT NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+    field TEXT NOT NULL,  -- Field name, as in `apollo tag`
+    PRIMARY KEY (track_id, field)
+);
@@ -0,0 +1,14 @@
+-- Apollo Music Library Schema
+-- Migration: 0014_playlist_versions
+-- Description: Keep the tracks static playlists had before they changed
+
+-- Playlist versions table
+-- One row per change to the tracks of a static playlist, newest last
+CREATE TABLE IF NOT EXISTS playlist_versions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playlist_id TEXT NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,  -- ISO8601 timestamp
+    track_ids TEXT NOT NULL  -- JSON array of track IDs, in playlist order
+);
+
+CREATE INDEX IF NOT EXISTS idx_playlist_versions_playlist ON playlist_versions(playlist_id, id);
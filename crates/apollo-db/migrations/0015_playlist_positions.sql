@@ -0,0 +1,20 @@
+-- Apollo Music Library Schema
+-- Migration: 0015_playlist_positions
+-- Description: Give each track of a static playlist its own position
+
+-- Number the tracks of each playlist from 0 again, in playlist order.
+-- Adding a track that a playlist already had moved it to the end, and
+-- merging duplicate tracks could leave two tracks at the same position.
+UPDATE playlist_tracks SET position = numbered.position
+FROM (
+    SELECT rowid AS row_id,
+           ROW_NUMBER() OVER (
+               PARTITION BY playlist_id ORDER BY position, added_at, rowid
+           ) - 1 AS position
+    FROM playlist_tracks
+) AS numbered
+WHERE playlist_tracks.rowid = numbered.row_id;
+
+-- Keep positions unique from now on
+CREATE UNIQUE INDEX IF NOT EXISTS idx_playlist_tracks_position
+    ON playlist_tracks(playlist_id, position);
@@ -24,6 +24,10 @@ pub enum DbError {
     /// Invalid data in database.
     #[error("invalid data: {0}")]
     InvalidData(String),
+
+    /// File system error, such as while backing up or restoring.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 /// Result type for database operations.
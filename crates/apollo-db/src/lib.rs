@@ -6,10 +6,11 @@
 //! [`Library`](apollo_core::library::Library) trait from apollo-core.
 
 mod error;
+mod library;
 mod schema;
 
 pub use error::{DbError, DbResult};
-pub use schema::SqliteLibrary;
+pub use schema::{MAX_PLAYLIST_VERSIONS, SCHEMA_VERSION, SqliteLibrary};
 
 /// Re-export sqlx for convenience.
 pub use sqlx;
@@ -0,0 +1,338 @@
+//! [`Library`] implementation for [`SqliteLibrary`].
+
+use std::path::Path;
+
+use apollo_core::changes::Change;
+use apollo_core::edit::EditField;
+use apollo_core::error::{Error, Result};
+use apollo_core::event::EventBus;
+use apollo_core::import_run::ImportRun;
+use apollo_core::library::{Library, QueuedScrobble, Suggestions};
+use apollo_core::metadata::{
+    Album, AlbumId, Artist, ArtistId, Artwork, Fingerprint, Track, TrackId, TrackStats,
+};
+use apollo_core::operation::Operation;
+use apollo_core::playlist::{Playlist, PlaylistId, PlaylistVersion};
+use apollo_core::provenance::Provenance;
+use apollo_core::query::{Query, SortSpec};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::error::DbError;
+use crate::schema::SqliteLibrary;
+
+impl From<DbError> for Error {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::NotFound(resource) => Self::NotFound(resource),
+            DbError::Sqlx(ref e) if e.to_string().contains("UNIQUE constraint") => {
+                Self::Duplicate(err.to_string())
+            }
+            _ => Self::Storage(err.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl Library for SqliteLibrary {
+    async fn get_track(&self, id: &TrackId) -> Result<Option<Track>> {
+        Ok(Self::get_track(self, id).await?)
+    }
+
+    async fn get_album(&self, id: &AlbumId) -> Result<Option<Album>> {
+        Ok(Self::get_album(self, id).await?)
+    }
+
+    async fn get_artist(&self, id: &ArtistId) -> Result<Option<Artist>> {
+        Ok(Self::get_artist(self, id).await?)
+    }
+
+    async fn get_album_tracks(&self, album_id: &AlbumId) -> Result<Vec<Track>> {
+        Ok(Self::get_album_tracks(self, album_id).await?)
+    }
+
+    async fn add_track(&self, track: &Track) -> Result<TrackId> {
+        Ok(Self::add_track(self, track).await?)
+    }
+
+    async fn update_track(&self, track: &Track) -> Result<()> {
+        Ok(Self::update_track(self, track).await?)
+    }
+
+    async fn remove_track(&self, id: &TrackId) -> Result<()> {
+        Ok(Self::remove_track(self, id).await?)
+    }
+
+    async fn merge_duplicate_track(&self, duplicate: &TrackId, kept: &TrackId) -> Result<()> {
+        Ok(Self::merge_duplicate_track(self, duplicate, kept).await?)
+    }
+
+    async fn add_album(&self, album: &Album) -> Result<AlbumId> {
+        Ok(Self::add_album(self, album).await?)
+    }
+
+    async fn update_album(&self, album: &Album) -> Result<()> {
+        Ok(Self::update_album(self, album).await?)
+    }
+
+    async fn remove_album(&self, id: &AlbumId) -> Result<()> {
+        Ok(Self::remove_album(self, id).await?)
+    }
+
+    async fn search_tracks(&self, query: &str) -> Result<Vec<Track>> {
+        Ok(Self::search_tracks(self, query).await?)
+    }
+
+    async fn search_tracks_sorted(&self, query: &str, sort: &SortSpec) -> Result<Vec<Track>> {
+        Ok(Self::search_tracks_sorted(self, query, sort).await?)
+    }
+
+    async fn suggest(&self, text: &str, limit: u32) -> Result<Suggestions> {
+        Ok(Self::suggest(self, text, limit).await?)
+    }
+
+    async fn query_tracks(&self, query: &Query, sort: &SortSpec) -> Result<Vec<Track>> {
+        Ok(Self::query_tracks(self, query, sort).await?)
+    }
+
+    async fn list_tracks(&self, limit: u32, offset: u32) -> Result<Vec<Track>> {
+        Ok(Self::list_tracks(self, limit, offset).await?)
+    }
+
+    async fn list_albums(&self, limit: u32, offset: u32) -> Result<Vec<Album>> {
+        Ok(Self::list_albums(self, limit, offset).await?)
+    }
+
+    async fn count_tracks(&self) -> Result<u64> {
+        Ok(Self::count_tracks(self).await?)
+    }
+
+    async fn count_albums(&self) -> Result<u64> {
+        Ok(Self::count_albums(self).await?)
+    }
+
+    async fn find_exact_duplicates(&self) -> Result<Vec<Vec<Track>>> {
+        Ok(Self::find_exact_duplicates(self).await?)
+    }
+
+    async fn find_similar_duplicates(&self, duration_tolerance_ms: i64) -> Result<Vec<Vec<Track>>> {
+        Ok(Self::find_similar_duplicates(self, duration_tolerance_ms).await?)
+    }
+
+    async fn track_exists_by_hash(&self, file_hash: &str) -> Result<bool> {
+        Ok(Self::track_exists_by_hash(self, file_hash).await?)
+    }
+
+    async fn get_track_by_hash(&self, file_hash: &str) -> Result<Option<Track>> {
+        Ok(Self::get_track_by_hash(self, file_hash).await?)
+    }
+
+    async fn get_track_by_path(&self, path: &Path) -> Result<Option<Track>> {
+        Ok(Self::get_track_by_path(self, path).await?)
+    }
+
+    async fn set_track_missing(&self, id: &TrackId, missing: bool) -> Result<()> {
+        Ok(Self::set_track_missing(self, id, missing).await?)
+    }
+
+    async fn list_missing_tracks(&self) -> Result<Vec<(Track, DateTime<Utc>)>> {
+        Ok(Self::list_missing_tracks(self).await?)
+    }
+
+    async fn queue_write(&self, id: &TrackId, cleared: &[EditField]) -> Result<()> {
+        Ok(Self::queue_write(self, id, cleared).await?)
+    }
+
+    async fn list_pending_writes(&self) -> Result<Vec<(Track, Vec<EditField>)>> {
+        Ok(Self::list_pending_writes(self).await?)
+    }
+
+    async fn remove_pending_write(&self, id: &TrackId) -> Result<()> {
+        Ok(Self::remove_pending_write(self, id).await?)
+    }
+
+    async fn set_track_rating(&self, id: &TrackId, rating: Option<u8>) -> Result<()> {
+        Ok(Self::set_track_rating(self, id, rating).await?)
+    }
+
+    async fn set_track_favorite(&self, id: &TrackId, favorite: bool) -> Result<()> {
+        Ok(Self::set_track_favorite(self, id, favorite).await?)
+    }
+
+    async fn record_play(&self, id: &TrackId, played_at: DateTime<Utc>) -> Result<()> {
+        Ok(Self::record_play(self, id, played_at).await?)
+    }
+
+    async fn queue_scrobble(
+        &self,
+        id: &TrackId,
+        service: &str,
+        played_at: DateTime<Utc>,
+    ) -> Result<()> {
+        Ok(Self::queue_scrobble(self, id, service, played_at).await?)
+    }
+
+    async fn list_scrobbles(&self) -> Result<Vec<QueuedScrobble>> {
+        Ok(Self::list_scrobbles(self).await?)
+    }
+
+    async fn remove_scrobble(&self, id: i64) -> Result<()> {
+        Ok(Self::remove_scrobble(self, id).await?)
+    }
+
+    async fn fail_scrobble(&self, id: i64, error: &str) -> Result<()> {
+        Ok(Self::fail_scrobble(self, id, error).await?)
+    }
+
+    async fn record_operation(&self, operation: &Operation) -> Result<i64> {
+        Ok(Self::record_operation(self, operation).await?)
+    }
+
+    async fn list_operations(&self, limit: u32) -> Result<Vec<Operation>> {
+        Ok(Self::list_operations(self, limit).await?)
+    }
+
+    async fn get_operation(&self, id: i64) -> Result<Option<Operation>> {
+        Ok(Self::get_operation(self, id).await?)
+    }
+
+    async fn mark_operation_undone(&self, id: i64, undone_at: DateTime<Utc>) -> Result<()> {
+        Ok(Self::mark_operation_undone(self, id, undone_at).await?)
+    }
+
+    async fn record_import_run(&self, run: &ImportRun) -> Result<i64> {
+        Ok(Self::record_import_run(self, run).await?)
+    }
+
+    async fn list_import_runs(&self, limit: u32) -> Result<Vec<ImportRun>> {
+        Ok(Self::list_import_runs(self, limit).await?)
+    }
+
+    async fn list_changes(&self, since: i64) -> Result<Vec<Change>> {
+        Ok(Self::list_changes(self, since).await?)
+    }
+
+    async fn change_seq_at(&self, at: DateTime<Utc>) -> Result<i64> {
+        Ok(Self::change_seq_at(self, at).await?)
+    }
+
+    async fn get_track_stats(&self, id: &TrackId) -> Result<TrackStats> {
+        Ok(Self::get_track_stats(self, id).await?)
+    }
+
+    async fn set_album_artwork(&self, id: &AlbumId, artwork: &Artwork) -> Result<()> {
+        Ok(Self::set_album_artwork(self, id, artwork).await?)
+    }
+
+    async fn get_album_artwork(&self, id: &AlbumId) -> Result<Option<Artwork>> {
+        Ok(Self::get_album_artwork(self, id).await?)
+    }
+
+    async fn get_album_artwork_modified(&self, id: &AlbumId) -> Result<Option<DateTime<Utc>>> {
+        Ok(Self::get_album_artwork_modified(self, id).await?)
+    }
+
+    async fn set_fingerprint(&self, id: &TrackId, fingerprint: &Fingerprint) -> Result<()> {
+        Ok(Self::set_fingerprint(self, id, fingerprint).await?)
+    }
+
+    async fn get_fingerprint(&self, id: &TrackId) -> Result<Option<Fingerprint>> {
+        Ok(Self::get_fingerprint(self, id).await?)
+    }
+
+    async fn record_provenance(&self, id: &TrackId, provenance: &Provenance) -> Result<()> {
+        Ok(Self::record_provenance(self, id, provenance).await?)
+    }
+
+    async fn get_track_provenance(&self, id: &TrackId) -> Result<Provenance> {
+        Ok(Self::get_track_provenance(self, id).await?)
+    }
+
+    async fn set_field_locks(&self, id: &TrackId, fields: &[EditField]) -> Result<()> {
+        Ok(Self::set_field_locks(self, id, fields).await?)
+    }
+
+    async fn get_field_locks(&self, id: &TrackId) -> Result<Vec<EditField>> {
+        Ok(Self::get_field_locks(self, id).await?)
+    }
+
+    async fn get_playlist(&self, id: &PlaylistId) -> Result<Option<Playlist>> {
+        Ok(Self::get_playlist(self, id).await?)
+    }
+
+    async fn add_playlist(&self, playlist: &Playlist) -> Result<PlaylistId> {
+        Ok(Self::add_playlist(self, playlist).await?)
+    }
+
+    async fn update_playlist(&self, playlist: &Playlist) -> Result<()> {
+        Ok(Self::update_playlist(self, playlist).await?)
+    }
+
+    async fn remove_playlist(&self, id: &PlaylistId) -> Result<()> {
+        Ok(Self::remove_playlist(self, id).await?)
+    }
+
+    async fn list_playlists(&self) -> Result<Vec<Playlist>> {
+        Ok(Self::list_playlists(self).await?)
+    }
+
+    async fn count_playlists(&self) -> Result<u64> {
+        Ok(Self::count_playlists(self).await?)
+    }
+
+    async fn add_track_to_playlist(
+        &self,
+        playlist_id: &PlaylistId,
+        track_id: &TrackId,
+    ) -> Result<()> {
+        Ok(Self::add_track_to_playlist(self, playlist_id, track_id).await?)
+    }
+
+    async fn remove_track_from_playlist(
+        &self,
+        playlist_id: &PlaylistId,
+        track_id: &TrackId,
+    ) -> Result<()> {
+        Ok(Self::remove_track_from_playlist(self, playlist_id, track_id).await?)
+    }
+
+    async fn get_playlist_tracks(&self, playlist_id: &PlaylistId) -> Result<Vec<Track>> {
+        Ok(Self::get_playlist_tracks(self, playlist_id).await?)
+    }
+
+    async fn get_playlist_tracks_page(
+        &self,
+        playlist_id: &PlaylistId,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Track>> {
+        Ok(Self::get_playlist_tracks_page(self, playlist_id, limit, offset).await?)
+    }
+
+    async fn count_playlist_tracks(&self, playlist_id: &PlaylistId) -> Result<u64> {
+        Ok(Self::count_playlist_tracks(self, playlist_id).await?)
+    }
+
+    async fn list_playlist_versions(
+        &self,
+        playlist_id: &PlaylistId,
+    ) -> Result<Vec<PlaylistVersion>> {
+        Ok(Self::list_playlist_versions(self, playlist_id).await?)
+    }
+
+    async fn restore_playlist_version(
+        &self,
+        playlist_id: &PlaylistId,
+        version_id: i64,
+    ) -> Result<Playlist> {
+        Ok(Self::restore_playlist_version(self, playlist_id, version_id).await?)
+    }
+
+    async fn backup(&self, path: &Path) -> Result<()> {
+        Ok(Self::backup(self, path).await?)
+    }
+
+    fn events(&self) -> &EventBus {
+        Self::events(self)
+    }
+}
@@ -12,19 +12,52 @@
 )]
 
 use crate::error::{DbError, DbResult};
-use apollo_core::metadata::{Album, AlbumId, AudioFormat, Track, TrackId};
-use apollo_core::playlist::{Playlist, PlaylistId, PlaylistKind, PlaylistLimit, PlaylistSort};
-use chrono::{DateTime, Utc};
-use sqlx::Row;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
-use std::path::PathBuf;
+use apollo_core::changes::{Change, ChangeKind, ChangedItem};
+use apollo_core::edit::EditField;
+use apollo_core::event::{EventBus, LibraryEvent};
+use apollo_core::import_run::ImportRun;
+use apollo_core::library::{
+    ArtistSummary, LibraryStats, QueuedScrobble, StatsBreakdown, StatsGroup, Suggestion,
+    Suggestions,
+};
+use apollo_core::metadata::{
+    Album, AlbumId, AlbumType, Artist, ArtistId, Artwork, AudioFormat, Fingerprint, Media, Track,
+    TrackId, TrackStats,
+};
+use apollo_core::operation::{Operation, OperationKind};
+use apollo_core::playlist::{
+    Playlist, PlaylistId, PlaylistKind, PlaylistLimit, PlaylistSort, PlaylistVersion,
+};
+use apollo_core::provenance::{FieldSource, MetadataSource, Provenance};
+use apollo_core::query::SortSpec;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions};
+use sqlx::{Connection, Row};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
 use std::time::Duration;
-use tracing::{debug, info};
+use tracing::{debug, info, instrument};
 use uuid::Uuid;
 
+/// Version of the database schema that this build creates.
+///
+/// Stored in the database as `PRAGMA user_version`. Bump it with each
+/// migration step.
+pub const SCHEMA_VERSION: u32 = 21;
+
+/// Most versions kept of each playlist; older versions are removed.
+pub const MAX_PLAYLIST_VERSIONS: u32 = 50;
+
+/// A boxed future, for async functions that call themselves.
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
 /// SQLite-based library storage.
 pub struct SqliteLibrary {
     pool: SqlitePool,
+    events: EventBus,
 }
 
 impl SqliteLibrary {
@@ -34,17 +67,34 @@ impl SqliteLibrary {
     ///
     /// Returns an error if the database connection fails or migrations fail.
     pub async fn new(database_url: &str) -> DbResult<Self> {
+        let library = Self::connect(database_url).await?;
+        library.migrate().await?;
+        Ok(library)
+    }
+
+    /// Connect to a database without running migrations.
+    ///
+    /// Use this for maintenance, such as checking the schema version before
+    /// calling [`migrate`](Self::migrate). Other operations may fail until
+    /// the database is migrated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database connection fails.
+    pub async fn connect(database_url: &str) -> DbResult<Self> {
         info!("Connecting to database: {database_url}");
 
+        // Register REGEXP for regular expression queries
+        let options = SqliteConnectOptions::from_str(database_url)?.with_regexp();
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
-            .connect(database_url)
+            .connect_with(options)
             .await?;
 
-        let library = Self { pool };
-        library.run_migrations().await?;
-
-        Ok(library)
+        Ok(Self {
+            pool,
+            events: EventBus::new(),
+        })
     }
 
     /// Create an in-memory database (useful for testing).
@@ -56,6 +106,189 @@ impl SqliteLibrary {
         Self::new("sqlite::memory:").await
     }
 
+    /// Announce changes on an existing event bus instead of a new one.
+    ///
+    /// Use this to share one bus between several connections to the same
+    /// library.
+    #[must_use]
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Get the bus on which changes to the library are announced.
+    #[must_use]
+    pub const fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Get the schema version of the database.
+    ///
+    /// Databases created before schema versions were recorded report 0
+    /// until they are migrated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub async fn schema_version(&self) -> DbResult<u32> {
+        schema_version(&self.pool).await
+    }
+
+    /// Bring the database schema up to [`SCHEMA_VERSION`].
+    ///
+    /// Migrations are safe to run on every start, and [`new`](Self::new)
+    /// runs them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database was created by a newer version of
+    /// Apollo, or a migration fails.
+    pub async fn migrate(&self) -> DbResult<()> {
+        let version = self.schema_version().await?;
+        if version > SCHEMA_VERSION {
+            return Err(DbError::InvalidData(format!(
+                "database schema version {version} is newer than this version of Apollo supports ({SCHEMA_VERSION})"
+            )));
+        }
+        self.run_migrations().await?;
+        // PRAGMA does not accept bound parameters
+        sqlx::query(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Write a consistent copy of the database to a new file.
+    ///
+    /// The copy is compacted, and the library can be used while it is made.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` already exists or cannot be written.
+    pub async fn backup(&self, path: &Path) -> DbResult<()> {
+        if path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("backup file already exists: {}", path.display()),
+            )
+            .into());
+        }
+        info!("Backing up database to {}", path.display());
+        // Pooled connections can fail the full-text search triggers after
+        // running VACUUM INTO, so use a connection that is closed afterwards
+        let mut conn = SqliteConnection::connect_with(&self.pool.connect_options()).await?;
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy())
+            .execute(&mut conn)
+            .await?;
+        conn.close().await?;
+        Ok(())
+    }
+
+    /// Replace the database file at `database` with a backup.
+    ///
+    /// The backup is checked before anything is replaced. It is copied next
+    /// to `database` first and then moved into place, so an interrupted
+    /// restore leaves the old database intact. No connections to `database`
+    /// should be open.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backup is damaged, is not an Apollo library,
+    /// or is newer than this version supports, or if the database file
+    /// cannot be replaced.
+    pub async fn restore(backup: &Path, database: &Path) -> DbResult<()> {
+        if !backup.is_file() {
+            return Err(DbError::NotFound(format!(
+                "backup file: {}",
+                backup.display()
+            )));
+        }
+        let options = SqliteConnectOptions::new().filename(backup).read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        let problems = integrity_problems(&pool).await?;
+        if let Some(problem) = problems.first() {
+            return Err(DbError::InvalidData(format!(
+                "backup is damaged: {problem}"
+            )));
+        }
+        let tables: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('tracks', 'albums')",
+        )
+        .fetch_one(&pool)
+        .await?;
+        if tables < 2 {
+            return Err(DbError::InvalidData(format!(
+                "not an Apollo library: {}",
+                backup.display()
+            )));
+        }
+        let version = schema_version(&pool).await?;
+        if version > SCHEMA_VERSION {
+            return Err(DbError::InvalidData(format!(
+                "backup schema version {version} is newer than this version of Apollo supports ({SCHEMA_VERSION})"
+            )));
+        }
+
+        info!("Restoring {} from {}", database.display(), backup.display());
+        let temp = PathBuf::from(format!("{}.restore", database.display()));
+        if temp.exists() {
+            std::fs::remove_file(&temp)?;
+        }
+        sqlx::query("VACUUM INTO ?")
+            .bind(temp.to_string_lossy())
+            .execute(&pool)
+            .await?;
+        pool.close().await;
+
+        // A leftover journal would be applied to the restored database
+        for suffix in ["-journal", "-wal", "-shm"] {
+            let path = PathBuf::from(format!("{}{suffix}", database.display()));
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        std::fs::rename(&temp, database)?;
+        Ok(())
+    }
+
+    /// Rebuild the database file to reclaim unused space, and refresh the
+    /// statistics used to plan queries.
+    ///
+    /// `VACUUM` needs the only connection to the database, so this closes
+    /// the library. Open it again to keep using it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database is busy or cannot be written.
+    pub async fn vacuum(self) -> DbResult<()> {
+        info!("Vacuuming database");
+        let options = self.pool.connect_options();
+        self.pool.close().await;
+        let mut conn = SqliteConnection::connect_with(&options).await?;
+        sqlx::query("VACUUM").execute(&mut conn).await?;
+        sqlx::query("PRAGMA optimize").execute(&mut conn).await?;
+        conn.close().await?;
+        Ok(())
+    }
+
+    /// Check the database for corruption and broken references.
+    ///
+    /// Returns a description of each problem found, or nothing if the
+    /// database is intact.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checks cannot run.
+    pub async fn check(&self) -> DbResult<Vec<String>> {
+        integrity_problems(&self.pool).await
+    }
+
     /// Run database migrations.
     async fn run_migrations(&self) -> DbResult<()> {
         debug!("Running database migrations");
@@ -70,23 +303,205 @@ impl SqliteLibrary {
             .execute(&self.pool)
             .await?;
 
+        // Run the ratings and play history migration
+        sqlx::query(include_str!("../migrations/0003_ratings_and_plays.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Add track bit depth
+        self.add_columns("tracks", &[("bit_depth", "INTEGER")])
+            .await?;
+
+        // Add release identifiers
+        self.add_columns("tracks", &[("isrc", "TEXT")]).await?;
+        self.add_columns("albums", &[("barcode", "TEXT"), ("discogs_id", "INTEGER")])
+            .await?;
+
+        // Add ReplayGain and loudness
+        self.add_columns(
+            "tracks",
+            &[
+                ("rg_track_gain", "REAL"),
+                ("rg_track_peak", "REAL"),
+                ("rg_album_gain", "REAL"),
+                ("rg_album_peak", "REAL"),
+                ("loudness_lufs", "REAL"),
+            ],
+        )
+        .await?;
+        self.add_columns(
+            "albums",
+            &[
+                ("rg_album_gain", "REAL"),
+                ("rg_album_peak", "REAL"),
+                ("loudness_lufs", "REAL"),
+            ],
+        )
+        .await?;
+
+        // Track files that could not be found
+        self.add_columns("tracks", &[("missing_since", "TEXT")])
+            .await?;
+
+        // Add album release metadata
+        self.add_columns(
+            "albums",
+            &[
+                ("album_type", "TEXT"),
+                ("release_date", "TEXT"),
+                ("country", "TEXT"),
+                ("label", "TEXT"),
+                ("catalog_number", "TEXT"),
+                ("is_compilation", "INTEGER NOT NULL DEFAULT 0"),
+            ],
+        )
+        .await?;
+
+        // Add album and playlist search
+        self.add_search().await?;
+
+        // Add album cover art
+        sqlx::query(include_str!("../migrations/0005_artwork.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Queue tag changes to write to files later
+        sqlx::query(include_str!("../migrations/0006_pending_writes.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Queue plays to scrobble
+        sqlx::query(include_str!("../migrations/0007_scrobbles.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Log operations so they can be undone
+        sqlx::query(include_str!("../migrations/0008_operations.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Log changes to tracks and playlists for clients that sync
+        sqlx::query(include_str!("../migrations/0009_changes.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Store audio fingerprints
+        sqlx::query(include_str!("../migrations/0010_fingerprints.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Keep a history of imports
+        sqlx::query(include_str!("../migrations/0011_import_runs.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Record where track fields came from
+        sqlx::query(include_str!("../migrations/0012_field_sources.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Lock track fields against automatic tagging
+        sqlx::query(include_str!("../migrations/0013_field_locks.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Tell editions of an album apart
+        self.add_columns(
+            "albums",
+            &[
+                ("disambiguation", "TEXT"),
+                ("media", "TEXT"),
+                ("edition", "TEXT"),
+            ],
+        )
+        .await?;
+
+        // Keep the tracks static playlists had before they changed
+        sqlx::query(include_str!("../migrations/0014_playlist_versions.sql"))
+            .execute(&self.pool)
+            .await?;
+
+        // Give each track of a static playlist its own position
+        self.add_unique_playlist_positions().await?;
+
         info!("Database migrations completed");
         Ok(())
     }
 
+    /// Add album and playlist search, indexing existing rows once.
+    async fn add_search(&self) -> DbResult<()> {
+        let search_exists: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'albums_fts'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        sqlx::query(include_str!("../migrations/0004_search.sql"))
+            .execute(&self.pool)
+            .await?;
+        if search_exists == 0 {
+            sqlx::query(
+                "INSERT INTO albums_fts(albums_fts) VALUES ('rebuild');
+                 INSERT INTO playlists_fts(playlists_fts) VALUES ('rebuild');",
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Renumber playlist positions once, then keep them unique.
+    async fn add_unique_playlist_positions(&self) -> DbResult<()> {
+        let positions_unique: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = 'idx_playlist_tracks_position'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if positions_unique == 0 {
+            sqlx::query(include_str!("../migrations/0015_playlist_positions.sql"))
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Add columns to a table unless they already exist.
+    ///
+    /// `SQLite` has no `ADD COLUMN IF NOT EXISTS`, so check the table info
+    /// first to keep migrations safe to run on every start.
+    async fn add_columns(&self, table: &str, columns: &[(&str, &str)]) -> DbResult<()> {
+        for (name, definition) in columns {
+            let exists: i64 =
+                sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+                    .bind(table)
+                    .bind(name)
+                    .fetch_one(&self.pool)
+                    .await?;
+            if exists == 0 {
+                sqlx::query(&format!(
+                    "ALTER TABLE {table} ADD COLUMN {name} {definition}"
+                ))
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
     /// Get a track by its ID.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn get_track(&self, id: &TrackId) -> DbResult<Option<Track>> {
         let id_str = id.0.to_string();
 
         let row = sqlx::query(
             r"SELECT id, path, title, artist, album_artist, album_id, album_title,
                      track_number, track_total, disc_number, disc_total, year,
-                     genres, duration_ms, bitrate, sample_rate, channels, format,
-                     musicbrainz_id, acoustid, added_at, modified_at, file_hash
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
               FROM tracks WHERE id = ?",
         )
         .bind(&id_str)
@@ -101,12 +516,16 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn get_album(&self, id: &AlbumId) -> DbResult<Option<Album>> {
         let id_str = id.0.to_string();
 
         let row = sqlx::query(
             r"SELECT id, title, artist, year, genres, track_count, disc_count,
-                     musicbrainz_id, added_at, modified_at
+                     musicbrainz_id, album_type, release_date, country, label,
+                     catalog_number, is_compilation, added_at, modified_at,
+                     barcode, discogs_id, rg_album_gain, rg_album_peak, loudness_lufs,
+                     disambiguation, media, edition
               FROM albums WHERE id = ?",
         )
         .bind(&id_str)
@@ -116,6 +535,28 @@ impl SqliteLibrary {
         row.map(|r| row_to_album(&r)).transpose()
     }
 
+    /// Get an artist by their ID.
+    ///
+    /// Artists are not stored separately, so this finds the track or album
+    /// artist whose name has the ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_artist(&self, id: &ArtistId) -> DbResult<Option<Artist>> {
+        // IDs are derived from names, which SQL cannot do
+        let names: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM artists WHERE name IS NOT NULL")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(names
+            .into_iter()
+            .find(|name| ArtistId::from_name(name) == *id)
+            .map(Artist::new))
+    }
+
     /// Get all tracks in an album.
     ///
     /// # Errors
@@ -127,8 +568,9 @@ impl SqliteLibrary {
         let rows = sqlx::query(
             r"SELECT id, path, title, artist, album_artist, album_id, album_title,
                      track_number, track_total, disc_number, disc_total, year,
-                     genres, duration_ms, bitrate, sample_rate, channels, format,
-                     musicbrainz_id, acoustid, added_at, modified_at, file_hash
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
               FROM tracks WHERE album_id = ?
               ORDER BY disc_number, track_number",
         )
@@ -144,6 +586,7 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn add_track(&self, track: &Track) -> DbResult<TrackId> {
         let id_str = track.id.0.to_string();
         let path_str = track.path.to_string_lossy().to_string();
@@ -158,9 +601,12 @@ impl SqliteLibrary {
         sqlx::query(
             r"INSERT INTO tracks (id, path, title, artist, album_artist, album_id, album_title,
                                   track_number, track_total, disc_number, disc_total, year,
-                                  genres, duration_ms, bitrate, sample_rate, channels, format,
-                                  musicbrainz_id, acoustid, added_at, modified_at, file_hash)
-              VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                                  genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                                  musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                                  isrc, rg_track_gain, rg_track_peak, rg_album_gain,
+                                  rg_album_peak, loudness_lufs)
+              VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+                      ?, ?)",
         )
         .bind(&id_str)
         .bind(&path_str)
@@ -178,6 +624,7 @@ impl SqliteLibrary {
         .bind(duration_ms)
         .bind(track.bitrate.map(|n| n as i32))
         .bind(track.sample_rate.map(|n| n as i32))
+        .bind(track.bit_depth.map(i32::from))
         .bind(track.channels.map(|n| n as i32))
         .bind(&format_str)
         .bind(&track.musicbrainz_id)
@@ -185,9 +632,19 @@ impl SqliteLibrary {
         .bind(&added_at_str)
         .bind(&modified_at_str)
         .bind(&track.file_hash)
+        .bind(&track.isrc)
+        .bind(track.rg_track_gain)
+        .bind(track.rg_track_peak)
+        .bind(track.rg_album_gain)
+        .bind(track.rg_album_peak)
+        .bind(track.loudness_lufs)
         .execute(&self.pool)
         .await?;
 
+        self.events.emit(LibraryEvent::TrackAdded {
+            track_id: track.id.clone(),
+        });
+
         Ok(track.id.clone())
     }
 
@@ -196,6 +653,7 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the track doesn't exist or the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn update_track(&self, track: &Track) -> DbResult<()> {
         let id_str = track.id.0.to_string();
         let path_str = track.path.to_string_lossy().to_string();
@@ -211,8 +669,9 @@ impl SqliteLibrary {
                 path = ?, title = ?, artist = ?, album_artist = ?, album_id = ?,
                 album_title = ?, track_number = ?, track_total = ?, disc_number = ?,
                 disc_total = ?, year = ?, genres = ?, duration_ms = ?, bitrate = ?,
-                sample_rate = ?, channels = ?, format = ?, musicbrainz_id = ?,
-                acoustid = ?, modified_at = ?, file_hash = ?
+                sample_rate = ?, bit_depth = ?, channels = ?, format = ?, musicbrainz_id = ?,
+                acoustid = ?, modified_at = ?, file_hash = ?, isrc = ?,
+                rg_track_gain = ?, rg_track_peak = ?, rg_album_gain = ?, rg_album_peak = ?, loudness_lufs = ?
               WHERE id = ?",
         )
         .bind(&path_str)
@@ -230,12 +689,19 @@ impl SqliteLibrary {
         .bind(duration_ms)
         .bind(track.bitrate.map(|n| n as i32))
         .bind(track.sample_rate.map(|n| n as i32))
+        .bind(track.bit_depth.map(i32::from))
         .bind(track.channels.map(|n| n as i32))
         .bind(&format_str)
         .bind(&track.musicbrainz_id)
         .bind(&track.acoustid)
         .bind(&modified_at_str)
         .bind(&track.file_hash)
+        .bind(&track.isrc)
+        .bind(track.rg_track_gain)
+        .bind(track.rg_track_peak)
+        .bind(track.rg_album_gain)
+        .bind(track.rg_album_peak)
+        .bind(track.loudness_lufs)
         .bind(&id_str)
         .execute(&self.pool)
         .await?;
@@ -244,6 +710,10 @@ impl SqliteLibrary {
             return Err(DbError::NotFound(format!("track {id_str}")));
         }
 
+        self.events.emit(LibraryEvent::TrackUpdated {
+            track_id: track.id.clone(),
+        });
+
         Ok(())
     }
 
@@ -264,6 +734,84 @@ impl SqliteLibrary {
             return Err(DbError::NotFound(format!("track {id_str}")));
         }
 
+        self.events.emit(LibraryEvent::TrackRemoved {
+            track_id: id.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Remove a duplicate track, moving what refers to it to the track kept
+    /// in its place.
+    ///
+    /// Playlists that contain the duplicate get the kept track instead (or
+    /// just lose the duplicate if they already contain the kept track), its
+    /// plays move to the kept track, and its rating and favorite flag are
+    /// kept if the kept track has none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either track doesn't exist, they are the same
+    /// track, or the database operation fails.
+    pub async fn merge_duplicate_track(&self, duplicate: &TrackId, kept: &TrackId) -> DbResult<()> {
+        if duplicate == kept {
+            return Err(DbError::InvalidData(format!(
+                "cannot merge track {duplicate} into itself"
+            )));
+        }
+        self.ensure_track_exists(duplicate).await?;
+        self.ensure_track_exists(kept).await?;
+        let duplicate_str = duplicate.0.to_string();
+        let kept_str = kept.0.to_string();
+
+        let playlist_ids: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT playlist_id FROM playlist_tracks WHERE track_id = ?",
+        )
+        .bind(&duplicate_str)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tx = self.pool.begin().await?;
+        // Rows for playlists that already contain the kept track are left
+        // behind, and deleted with the duplicate via ON DELETE CASCADE
+        sqlx::query("UPDATE OR IGNORE playlist_tracks SET track_id = ? WHERE track_id = ?")
+            .bind(&kept_str)
+            .bind(&duplicate_str)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE plays SET track_id = ? WHERE track_id = ?")
+            .bind(&kept_str)
+            .bind(&duplicate_str)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r"INSERT OR IGNORE INTO track_ratings (track_id, rating, favorite, modified_at)
+              SELECT ?, rating, favorite, modified_at FROM track_ratings WHERE track_id = ?",
+        )
+        .bind(&kept_str)
+        .bind(&duplicate_str)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM tracks WHERE id = ?")
+            .bind(&duplicate_str)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.events.emit(LibraryEvent::TrackRemoved {
+            track_id: duplicate.clone(),
+        });
+        self.events.emit(LibraryEvent::TrackUpdated {
+            track_id: kept.clone(),
+        });
+        for playlist_id in playlist_ids {
+            if let Ok(uuid) = uuid::Uuid::parse_str(&playlist_id) {
+                self.events.emit(LibraryEvent::PlaylistChanged {
+                    playlist_id: PlaylistId(uuid),
+                });
+            }
+        }
+
         Ok(())
     }
 
@@ -272,6 +820,7 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn add_album(&self, album: &Album) -> DbResult<AlbumId> {
         let id_str = album.id.0.to_string();
         let genres_json = serde_json::to_string(&album.genres)
@@ -281,8 +830,11 @@ impl SqliteLibrary {
 
         sqlx::query(
             r"INSERT INTO albums (id, title, artist, year, genres, track_count, disc_count,
-                                  musicbrainz_id, added_at, modified_at)
-              VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                                  musicbrainz_id, album_type, release_date, country, label,
+                                  catalog_number, is_compilation, added_at, modified_at,
+                                  barcode, discogs_id, rg_album_gain, rg_album_peak,
+                                  loudness_lufs, disambiguation, media, edition)
+              VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&id_str)
         .bind(&album.title)
@@ -292,11 +844,29 @@ impl SqliteLibrary {
         .bind(album.track_count as i32)
         .bind(album.disc_count as i32)
         .bind(&album.musicbrainz_id)
+        .bind(album.album_type.map(AlbumType::as_str))
+        .bind(album.release_date.map(|d| d.to_string()))
+        .bind(&album.country)
+        .bind(&album.label)
+        .bind(&album.catalog_number)
+        .bind(album.is_compilation)
         .bind(&added_at_str)
         .bind(&modified_at_str)
+        .bind(&album.barcode)
+        .bind(album.discogs_id.map(|id| id as i64))
+        .bind(album.rg_album_gain)
+        .bind(album.rg_album_peak)
+        .bind(album.loudness_lufs)
+        .bind(&album.disambiguation)
+        .bind(album.media.map(Media::as_str))
+        .bind(&album.edition)
         .execute(&self.pool)
         .await?;
 
+        self.events.emit(LibraryEvent::AlbumAdded {
+            album_id: album.id.clone(),
+        });
+
         Ok(album.id.clone())
     }
 
@@ -305,6 +875,7 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the album doesn't exist or the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn update_album(&self, album: &Album) -> DbResult<()> {
         let id_str = album.id.0.to_string();
         let genres_json = serde_json::to_string(&album.genres)
@@ -314,7 +885,11 @@ impl SqliteLibrary {
         let result = sqlx::query(
             r"UPDATE albums SET
                 title = ?, artist = ?, year = ?, genres = ?, track_count = ?,
-                disc_count = ?, musicbrainz_id = ?, modified_at = ?
+                disc_count = ?, musicbrainz_id = ?, album_type = ?, release_date = ?,
+                country = ?, label = ?, catalog_number = ?, is_compilation = ?,
+                barcode = ?, discogs_id = ?, rg_album_gain = ?, rg_album_peak = ?,
+                loudness_lufs = ?, disambiguation = ?, media = ?, edition = ?,
+                modified_at = ?
               WHERE id = ?",
         )
         .bind(&album.title)
@@ -324,6 +899,20 @@ impl SqliteLibrary {
         .bind(album.track_count as i32)
         .bind(album.disc_count as i32)
         .bind(&album.musicbrainz_id)
+        .bind(album.album_type.map(AlbumType::as_str))
+        .bind(album.release_date.map(|d| d.to_string()))
+        .bind(&album.country)
+        .bind(&album.label)
+        .bind(&album.catalog_number)
+        .bind(album.is_compilation)
+        .bind(&album.barcode)
+        .bind(album.discogs_id.map(|id| id as i64))
+        .bind(album.rg_album_gain)
+        .bind(album.rg_album_peak)
+        .bind(album.loudness_lufs)
+        .bind(&album.disambiguation)
+        .bind(album.media.map(Media::as_str))
+        .bind(&album.edition)
         .bind(&modified_at_str)
         .bind(&id_str)
         .execute(&self.pool)
@@ -333,6 +922,10 @@ impl SqliteLibrary {
             return Err(DbError::NotFound(format!("album {id_str}")));
         }
 
+        self.events.emit(LibraryEvent::AlbumUpdated {
+            album_id: album.id.clone(),
+        });
+
         Ok(())
     }
 
@@ -353,6 +946,10 @@ impl SqliteLibrary {
             return Err(DbError::NotFound(format!("album {id_str}")));
         }
 
+        self.events.emit(LibraryEvent::AlbumRemoved {
+            album_id: id.clone(),
+        });
+
         Ok(())
     }
 
@@ -361,12 +958,14 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn search_tracks(&self, query: &str) -> DbResult<Vec<Track>> {
         let rows = sqlx::query(
             r"SELECT t.id, t.path, t.title, t.artist, t.album_artist, t.album_id, t.album_title,
                      t.track_number, t.track_total, t.disc_number, t.disc_total, t.year,
-                     t.genres, t.duration_ms, t.bitrate, t.sample_rate, t.channels, t.format,
-                     t.musicbrainz_id, t.acoustid, t.added_at, t.modified_at, t.file_hash
+                     t.genres, t.duration_ms, t.bitrate, t.sample_rate, t.bit_depth, t.channels, t.format,
+                     t.musicbrainz_id, t.acoustid, t.added_at, t.modified_at, t.file_hash,
+                     t.isrc, t.rg_track_gain, t.rg_track_peak, t.rg_album_gain, t.rg_album_peak, t.loudness_lufs
               FROM tracks t
               JOIN tracks_fts fts ON t.rowid = fts.rowid
               WHERE tracks_fts MATCH ?
@@ -379,76 +978,431 @@ impl SqliteLibrary {
         rows.iter().map(row_to_track).collect()
     }
 
-    /// List all tracks in the library.
+    /// Search tracks using full-text search, in the given sort order.
+    ///
+    /// An empty sort specification orders results by relevance.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn list_tracks(&self, limit: u32, offset: u32) -> DbResult<Vec<Track>> {
-        let rows = sqlx::query(
+    #[instrument(level = "debug", skip_all)]
+    pub async fn search_tracks_sorted(&self, query: &str, sort: &SortSpec) -> DbResult<Vec<Track>> {
+        if sort.is_empty() {
+            return self.search_tracks(query).await;
+        }
+
+        let sql = format!(
             r"SELECT id, path, title, artist, album_artist, album_id, album_title,
                      track_number, track_total, disc_number, disc_total, year,
-                     genres, duration_ms, bitrate, sample_rate, channels, format,
-                     musicbrainz_id, acoustid, added_at, modified_at, file_hash
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
               FROM tracks
-              ORDER BY artist, album_title, disc_number, track_number
-              LIMIT ? OFFSET ?",
-        )
-        .bind(limit as i32)
-        .bind(offset as i32)
-        .fetch_all(&self.pool)
-        .await?;
+              WHERE rowid IN (SELECT rowid FROM tracks_fts WHERE tracks_fts MATCH ?)
+              ORDER BY {}",
+            sort_to_sql(sort)
+        );
+        let rows = sqlx::query(&sql).bind(query).fetch_all(&self.pool).await?;
 
         rows.iter().map(row_to_track).collect()
     }
 
-    /// List all albums in the library.
+    /// Search albums by title and artist using full-text search.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn list_albums(&self, limit: u32, offset: u32) -> DbResult<Vec<Album>> {
+    pub async fn search_albums(&self, query: &str) -> DbResult<Vec<Album>> {
         let rows = sqlx::query(
-            r"SELECT id, title, artist, year, genres, track_count, disc_count,
-                     musicbrainz_id, added_at, modified_at
-              FROM albums
-              ORDER BY artist, year, title
-              LIMIT ? OFFSET ?",
+            r"SELECT a.id, a.title, a.artist, a.year, a.genres, a.track_count, a.disc_count,
+                     a.musicbrainz_id, a.album_type, a.release_date, a.country, a.label,
+                     a.catalog_number, a.is_compilation, a.added_at, a.modified_at,
+                     a.barcode, a.discogs_id, a.rg_album_gain, a.rg_album_peak, a.loudness_lufs,
+                     a.disambiguation, a.media, a.edition
+              FROM albums a
+              JOIN albums_fts fts ON a.rowid = fts.rowid
+              WHERE albums_fts MATCH ?
+              ORDER BY rank",
         )
-        .bind(limit as i32)
-        .bind(offset as i32)
+        .bind(query)
         .fetch_all(&self.pool)
         .await?;
 
         rows.iter().map(row_to_album).collect()
     }
 
-    /// Count total tracks in the library.
+    /// Search artists by name using full-text search.
+    ///
+    /// Both track and album artists are searched. Artists with the most
+    /// tracks are listed first.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn count_tracks(&self) -> DbResult<u64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM tracks")
-            .fetch_one(&self.pool)
-            .await?;
+    pub async fn search_artists(&self, query: &str) -> DbResult<Vec<ArtistSummary>> {
+        let rows = sqlx::query(
+            r"WITH matches AS (
+                  SELECT rowid, artist AS name FROM tracks
+                  WHERE rowid IN (SELECT rowid FROM tracks_fts WHERE tracks_fts MATCH '{artist} : (' || ?1 || ')')
+                  UNION
+                  SELECT rowid, album_artist FROM tracks
+                  WHERE rowid IN (SELECT rowid FROM tracks_fts WHERE tracks_fts MATCH '{album_artist} : (' || ?1 || ')')
+              )
+              SELECT m.name, COUNT(*) AS track_count, COUNT(DISTINCT t.album_id) AS album_count,
+                     COALESCE(SUM(t.duration_ms), 0) / 1000 AS total_secs
+              FROM matches m
+              JOIN tracks t ON t.rowid = m.rowid
+              GROUP BY m.name
+              ORDER BY track_count DESC, m.name",
+        )
+        .bind(query)
+        .fetch_all(&self.pool)
+        .await?;
 
-        Ok(row.get::<i64, _>("count") as u64)
+        Ok(rows
+            .iter()
+            .map(|row| ArtistSummary {
+                name: row.get("name"),
+                track_count: row.get::<i64, _>("track_count") as u64,
+                album_count: row.get::<i64, _>("album_count") as u64,
+                total_duration_secs: row.get::<i64, _>("total_secs") as u64,
+            })
+            .collect())
     }
 
-    /// Count total albums in the library.
+    /// Search playlists by name and description using full-text search.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn count_albums(&self) -> DbResult<u64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM albums")
-            .fetch_one(&self.pool)
-            .await?;
+    pub async fn search_playlists(&self, query: &str) -> DbResult<Vec<Playlist>> {
+        let rows = sqlx::query(
+            r"SELECT p.id, p.name, p.description, p.kind, p.query, p.sort, p.max_tracks,
+                     p.max_duration_secs, p.created_at, p.modified_at
+              FROM playlists p
+              JOIN playlists_fts fts ON p.rowid = fts.rowid
+              WHERE playlists_fts MATCH ?
+              ORDER BY rank",
+        )
+        .bind(query)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut playlists = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let mut playlist = row_to_playlist(row)?;
+
+            // Load track IDs for static playlists
+            if playlist.kind == PlaylistKind::Static {
+                playlist.track_ids = self.get_playlist_track_ids(&playlist.id).await?;
+            }
+
+            playlists.push(playlist);
+        }
+
+        Ok(playlists)
+    }
+
+    /// Suggest artist, album, and track names that start with the words of
+    /// a partly typed search, for type-ahead.
+    ///
+    /// Only names are matched, so that a suggestion reads like what was
+    /// typed: "bo" suggests the artist Bob Dylan and the track Bohemian
+    /// Rhapsody, but not the albums of Bob Dylan. At most `limit` names of
+    /// each kind are suggested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn suggest(&self, text: &str, limit: u32) -> DbResult<Suggestions> {
+        let Some(prefix) = fts_prefix_query(text) else {
+            return Ok(Suggestions::default());
+        };
+
+        let artists = sqlx::query(
+            r"SELECT name, COUNT(*) AS track_count FROM (
+                  SELECT artist AS name FROM tracks
+                  WHERE rowid IN (SELECT rowid FROM tracks_fts WHERE tracks_fts MATCH '{artist} : (' || ?1 || ')')
+                  UNION ALL
+                  SELECT album_artist FROM tracks
+                  WHERE rowid IN (SELECT rowid FROM tracks_fts WHERE tracks_fts MATCH '{album_artist} : (' || ?1 || ')')
+              )
+              GROUP BY name
+              ORDER BY track_count DESC, name
+              LIMIT ?2",
+        )
+        .bind(&prefix)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            let name: String = row.get("name");
+            Suggestion {
+                id: ArtistId::from_name(&name).to_string(),
+                name,
+                artist: None,
+            }
+        })
+        .collect();
+
+        let albums = sqlx::query(
+            r"SELECT a.id, a.title AS name, a.artist
+              FROM albums a
+              JOIN albums_fts fts ON a.rowid = fts.rowid
+              WHERE albums_fts MATCH '{title} : (' || ?1 || ')'
+              ORDER BY rank
+              LIMIT ?2",
+        )
+        .bind(&prefix)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(row_to_suggestion)
+        .collect();
+
+        let tracks = sqlx::query(
+            r"SELECT t.id, t.title AS name, t.artist
+              FROM tracks t
+              JOIN tracks_fts fts ON t.rowid = fts.rowid
+              WHERE tracks_fts MATCH '{title} : (' || ?1 || ')'
+              ORDER BY rank
+              LIMIT ?2",
+        )
+        .bind(&prefix)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(row_to_suggestion)
+        .collect();
+
+        Ok(Suggestions {
+            artists,
+            albums,
+            tracks,
+        })
+    }
+
+    /// Get tracks matching a query, in the given sort order.
+    ///
+    /// An empty sort specification orders results by artist and album.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn query_tracks(
+        &self,
+        query: &apollo_core::query::Query,
+        sort: &SortSpec,
+    ) -> DbResult<Vec<Track>> {
+        self.select_tracks(query, sort, None, 0, &[]).await
+    }
+
+    /// List all tracks in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn list_tracks(&self, limit: u32, offset: u32) -> DbResult<Vec<Track>> {
+        let rows = sqlx::query(
+            r"SELECT id, path, title, artist, album_artist, album_id, album_title,
+                     track_number, track_total, disc_number, disc_total, year,
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
+              FROM tracks
+              ORDER BY artist, album_title, disc_number, track_number
+              LIMIT ? OFFSET ?",
+        )
+        .bind(limit as i32)
+        .bind(offset as i32)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_track).collect()
+    }
+
+    /// List all albums in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn list_albums(&self, limit: u32, offset: u32) -> DbResult<Vec<Album>> {
+        let rows = sqlx::query(
+            r"SELECT id, title, artist, year, genres, track_count, disc_count,
+                     musicbrainz_id, album_type, release_date, country, label,
+                     catalog_number, is_compilation, added_at, modified_at,
+                     barcode, discogs_id, rg_album_gain, rg_album_peak, loudness_lufs,
+                     disambiguation, media, edition
+              FROM albums
+              ORDER BY artist, year, title
+              LIMIT ? OFFSET ?",
+        )
+        .bind(limit as i32)
+        .bind(offset as i32)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_album).collect()
+    }
+
+    /// Count total tracks in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn count_tracks(&self) -> DbResult<u64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM tracks")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.get::<i64, _>("count") as u64)
+    }
+
+    /// Count total albums in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn count_albums(&self) -> DbResult<u64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM albums")
+            .fetch_one(&self.pool)
+            .await?;
 
         Ok(row.get::<i64, _>("count") as u64)
     }
 
+    /// Get summary statistics for the whole library.
+    ///
+    /// File sizes are not stored in the database, so `total_size_bytes` is
+    /// always zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn library_stats(&self) -> DbResult<LibraryStats> {
+        let row = sqlx::query(
+            r"SELECT COUNT(*) AS track_count,
+                     COUNT(DISTINCT artist) AS artist_count,
+                     COALESCE(SUM(duration_ms), 0) AS duration_ms,
+                     (SELECT COUNT(*) FROM albums) AS album_count
+              FROM tracks",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(LibraryStats {
+            track_count: row.get::<i64, _>("track_count") as u64,
+            album_count: row.get::<i64, _>("album_count") as u64,
+            artist_count: row.get::<i64, _>("artist_count") as u64,
+            total_duration_secs: row.get::<i64, _>("duration_ms") as u64 / 1000,
+            total_size_bytes: 0,
+        })
+    }
+
+    /// Count tracks and their total duration, grouped by a field.
+    ///
+    /// Decades are listed in chronological order, all other groups by
+    /// descending track count. A track with several genres is counted once
+    /// for each of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn stats_by(&self, breakdown: StatsBreakdown) -> DbResult<Vec<StatsGroup>> {
+        let (name, from, order) = match breakdown {
+            StatsBreakdown::Format => ("format", "tracks", "track_count DESC, name"),
+            StatsBreakdown::Artist => ("artist", "tracks", "track_count DESC, name"),
+            StatsBreakdown::Genre => (
+                "genre.value",
+                "tracks LEFT JOIN json_each(tracks.genres) AS genre",
+                "track_count DESC, name",
+            ),
+            StatsBreakdown::Decade => (
+                "CAST(year / 10 * 10 AS TEXT) || 's'",
+                "tracks",
+                "name IS NULL, name",
+            ),
+        };
+        let rows = sqlx::query(&format!(
+            r"SELECT {name} AS name, COUNT(*) AS track_count,
+                     COALESCE(SUM(duration_ms), 0) AS duration_ms
+              FROM {from}
+              GROUP BY 1
+              ORDER BY {order}"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| StatsGroup {
+                name: row
+                    .get::<Option<String>, _>("name")
+                    .filter(|s| !s.is_empty()),
+                track_count: row.get::<i64, _>("track_count") as u64,
+                total_duration_secs: row.get::<i64, _>("duration_ms") as u64 / 1000,
+            })
+            .collect())
+    }
+
+    /// List the most recently added tracks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn newest_tracks(&self, limit: u32) -> DbResult<Vec<Track>> {
+        let rows = sqlx::query(
+            r"SELECT id, path, title, artist, album_artist, album_id, album_title,
+                     track_number, track_total, disc_number, disc_total, year,
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
+              FROM tracks
+              ORDER BY added_at DESC, title
+              LIMIT ?",
+        )
+        .bind(limit as i32)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_track).collect()
+    }
+
+    /// List the most played tracks with their play counts.
+    ///
+    /// Tracks that were never played are not included.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn most_played_tracks(&self, limit: u32) -> DbResult<Vec<(Track, u64)>> {
+        let rows = sqlx::query(
+            r"SELECT id, path, title, artist, album_artist, album_id, album_title,
+                     track_number, track_total, disc_number, disc_total, year,
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs,
+                     (SELECT COUNT(*) FROM plays WHERE track_id = tracks.id) AS play_count
+              FROM tracks
+              WHERE EXISTS (SELECT 1 FROM plays WHERE track_id = tracks.id)
+              ORDER BY play_count DESC, title
+              LIMIT ?",
+        )
+        .bind(limit as i32)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok((row_to_track(row)?, row.get::<i64, _>("play_count") as u64)))
+            .collect()
+    }
+
     /// Find tracks with duplicate file hashes (exact byte-for-byte duplicates).
     ///
     /// Returns groups of tracks that have the same file hash.
@@ -479,8 +1433,9 @@ impl SqliteLibrary {
             let track_rows = sqlx::query(
                 r"SELECT id, path, title, artist, album_artist, album_id, album_title,
                          track_number, track_total, disc_number, disc_total, year,
-                         genres, duration_ms, bitrate, sample_rate, channels, format,
-                         musicbrainz_id, acoustid, added_at, modified_at, file_hash
+                         genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                         musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                         isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
                   FROM tracks WHERE file_hash = ?
                   ORDER BY added_at ASC",
             )
@@ -518,8 +1473,9 @@ impl SqliteLibrary {
         let rows = sqlx::query(
             r"SELECT t1.id, t1.path, t1.title, t1.artist, t1.album_artist, t1.album_id, t1.album_title,
                      t1.track_number, t1.track_total, t1.disc_number, t1.disc_total, t1.year,
-                     t1.genres, t1.duration_ms, t1.bitrate, t1.sample_rate, t1.channels, t1.format,
-                     t1.musicbrainz_id, t1.acoustid, t1.added_at, t1.modified_at, t1.file_hash
+                     t1.genres, t1.duration_ms, t1.bitrate, t1.sample_rate, t1.bit_depth, t1.channels, t1.format,
+                     t1.musicbrainz_id, t1.acoustid, t1.added_at, t1.modified_at, t1.file_hash,
+                     t1.isrc, t1.rg_track_gain, t1.rg_track_peak, t1.rg_album_gain, t1.rg_album_peak, t1.loudness_lufs
               FROM tracks t1
               JOIN tracks t2 ON t1.title = t2.title
                             AND t1.artist = t2.artist
@@ -555,6 +1511,7 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn track_exists_by_hash(&self, file_hash: &str) -> DbResult<bool> {
         let row = sqlx::query("SELECT COUNT(*) as count FROM tracks WHERE file_hash = ?")
             .bind(file_hash)
@@ -569,12 +1526,14 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn get_track_by_hash(&self, file_hash: &str) -> DbResult<Option<Track>> {
         let row = sqlx::query(
             r"SELECT id, path, title, artist, album_artist, album_id, album_title,
                      track_number, track_total, disc_number, disc_total, year,
-                     genres, duration_ms, bitrate, sample_rate, channels, format,
-                     musicbrainz_id, acoustid, added_at, modified_at, file_hash
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
               FROM tracks WHERE file_hash = ?
               LIMIT 1",
         )
@@ -590,14 +1549,16 @@ impl SqliteLibrary {
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
     pub async fn get_track_by_path(&self, path: &std::path::Path) -> DbResult<Option<Track>> {
         let path_str = path.to_string_lossy().to_string();
 
         let row = sqlx::query(
             r"SELECT id, path, title, artist, album_artist, album_id, album_title,
                      track_number, track_total, disc_number, disc_total, year,
-                     genres, duration_ms, bitrate, sample_rate, channels, format,
-                     musicbrainz_id, acoustid, added_at, modified_at, file_hash
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
               FROM tracks WHERE path = ?",
         )
         .bind(&path_str)
@@ -607,1005 +1568,4221 @@ impl SqliteLibrary {
         row.map(|r| row_to_track(&r)).transpose()
     }
 
-    // ========================================================================
-    // Playlist operations
-    // ========================================================================
-
-    /// Get a playlist by its ID.
+    /// Point a track at its file's new location.
+    ///
+    /// The track is no longer marked as missing afterwards.
     ///
     /// # Errors
     ///
-    /// Returns an error if the database operation fails.
-    pub async fn get_playlist(&self, id: &PlaylistId) -> DbResult<Option<Playlist>> {
-        let id_str = id.0.to_string();
-
-        let row = sqlx::query(
-            r"SELECT id, name, description, kind, query, sort, max_tracks, max_duration_secs,
-                     created_at, modified_at
-              FROM playlists WHERE id = ?",
+    /// Returns an error if the track doesn't exist, another track already
+    /// has the path, or the database operation fails.
+    pub async fn set_track_path(&self, id: &TrackId, path: &std::path::Path) -> DbResult<()> {
+        let result = sqlx::query(
+            "UPDATE tracks SET path = ?, missing_since = NULL, modified_at = ? WHERE id = ?",
         )
-        .bind(&id_str)
-        .fetch_optional(&self.pool)
+        .bind(path.to_string_lossy().to_string())
+        .bind(Utc::now().to_rfc3339())
+        .bind(id.0.to_string())
+        .execute(&self.pool)
         .await?;
 
-        match row {
-            Some(r) => {
-                let mut playlist = row_to_playlist(&r)?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound(format!("track {id}")));
+        }
 
-                // Load track IDs for static playlists
-                if playlist.kind == PlaylistKind::Static {
-                    playlist.track_ids = self.get_playlist_track_ids(&playlist.id).await?;
-                }
+        self.events.emit(LibraryEvent::TrackUpdated {
+            track_id: id.clone(),
+        });
 
-                Ok(Some(playlist))
-            }
-            None => Ok(None),
-        }
+        Ok(())
     }
 
-    /// Get the track IDs for a playlist.
-    async fn get_playlist_track_ids(&self, playlist_id: &PlaylistId) -> DbResult<Vec<TrackId>> {
-        let id_str = playlist_id.0.to_string();
-
-        let rows = sqlx::query(
-            r"SELECT track_id FROM playlist_tracks
-              WHERE playlist_id = ?
-              ORDER BY position",
+    /// Mark a track's file as missing, or as found again.
+    ///
+    /// A track that is already marked as missing keeps the time it was first
+    /// found to be missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track doesn't exist or the database operation fails.
+    pub async fn set_track_missing(&self, id: &TrackId, missing: bool) -> DbResult<()> {
+        let result = sqlx::query(
+            r"UPDATE tracks SET
+                missing_since = CASE WHEN ? THEN COALESCE(missing_since, ?) ELSE NULL END
+              WHERE id = ?",
         )
-        .bind(&id_str)
-        .fetch_all(&self.pool)
+        .bind(missing)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id.0.to_string())
+        .execute(&self.pool)
         .await?;
 
-        let mut track_ids = Vec::with_capacity(rows.len());
-        for row in rows {
-            let track_id_str: String = row.get("track_id");
-            let track_id =
-                Uuid::parse_str(&track_id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
-            track_ids.push(TrackId(track_id));
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound(format!("track {id}")));
         }
 
-        Ok(track_ids)
+        self.events.emit(LibraryEvent::TrackUpdated {
+            track_id: id.clone(),
+        });
+
+        Ok(())
     }
 
-    /// Add a playlist to the library.
+    /// List tracks whose files are marked as missing, with the time each was
+    /// found to be missing.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn add_playlist(&self, playlist: &Playlist) -> DbResult<PlaylistId> {
-        let id_str = playlist.id.0.to_string();
-        let kind_str = format!("{}", playlist.kind);
-        let query_json = playlist
-            .query
-            .as_ref()
-            .map(serde_json::to_string)
-            .transpose()
-            .map_err(|e| DbError::Serialization(e.to_string()))?;
-        let sort_str = format!("{:?}", playlist.sort).to_lowercase();
-        let created_at_str = playlist.created_at.to_rfc3339();
-        let modified_at_str = playlist.modified_at.to_rfc3339();
+    pub async fn list_missing_tracks(&self) -> DbResult<Vec<(Track, DateTime<Utc>)>> {
+        let rows = sqlx::query(
+            r"SELECT id, path, title, artist, album_artist, album_id, album_title,
+                     track_number, track_total, disc_number, disc_total, year,
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs,
+                     missing_since
+              FROM tracks
+              WHERE missing_since IS NOT NULL
+              ORDER BY path",
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-        let max_tracks = playlist.limit.as_ref().and_then(|l| l.max_tracks);
-        let max_duration_secs = playlist
-            .limit
-            .as_ref()
-            .and_then(|l| l.max_duration_secs)
-            .map(|d| d as i64);
+        rows.iter()
+            .map(|row| {
+                let missing_since: String = row.get("missing_since");
+                let missing_since = DateTime::parse_from_rfc3339(&missing_since)
+                    .map_err(|e| DbError::InvalidData(e.to_string()))?
+                    .with_timezone(&Utc);
+                Ok((row_to_track(row)?, missing_since))
+            })
+            .collect()
+    }
 
-        sqlx::query(
-            r"INSERT INTO playlists (id, name, description, kind, query, sort, max_tracks,
-                                     max_duration_secs, created_at, modified_at)
-              VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-        )
-        .bind(&id_str)
-        .bind(&playlist.name)
-        .bind(&playlist.description)
-        .bind(&kind_str)
-        .bind(&query_json)
-        .bind(&sort_str)
-        .bind(max_tracks.map(|n| n as i32))
-        .bind(max_duration_secs)
-        .bind(&created_at_str)
-        .bind(&modified_at_str)
-        .execute(&self.pool)
-        .await?;
-
-        // Add track IDs for static playlists
-        if playlist.kind == PlaylistKind::Static {
-            self.set_playlist_tracks(&playlist.id, &playlist.track_ids)
-                .await?;
-        }
-
-        Ok(playlist.id.clone())
-    }
-
-    /// Set the tracks for a static playlist.
-    async fn set_playlist_tracks(
-        &self,
-        playlist_id: &PlaylistId,
-        track_ids: &[TrackId],
-    ) -> DbResult<()> {
-        let playlist_id_str = playlist_id.0.to_string();
-        let now = Utc::now().to_rfc3339();
+    // ========================================================================
+    // Pending tag writes
+    // ========================================================================
 
-        // Delete existing tracks
-        sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ?")
-            .bind(&playlist_id_str)
-            .execute(&self.pool)
+    /// Queue a track's tags to be written to its file.
+    ///
+    /// The `cleared` fields are removed from the file, along with those of
+    /// earlier queued changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database
+    /// operation fails.
+    pub async fn queue_write(&self, id: &TrackId, cleared: &[EditField]) -> DbResult<()> {
+        let id_str = id.0.to_string();
+        let row = sqlx::query("SELECT COUNT(*) as count FROM tracks WHERE id = ?")
+            .bind(&id_str)
+            .fetch_one(&self.pool)
             .await?;
+        let count: i64 = row.get("count");
+        if count == 0 {
+            return Err(DbError::NotFound(format!("track {id}")));
+        }
 
-        // Insert new tracks
-        for (position, track_id) in track_ids.iter().enumerate() {
-            let track_id_str = track_id.0.to_string();
-            sqlx::query(
-                r"INSERT INTO playlist_tracks (playlist_id, track_id, position, added_at)
-                  VALUES (?, ?, ?, ?)",
-            )
-            .bind(&playlist_id_str)
-            .bind(&track_id_str)
-            .bind(position as i32)
-            .bind(&now)
-            .execute(&self.pool)
-            .await?;
+        let queued: Option<String> =
+            sqlx::query_scalar("SELECT cleared FROM pending_writes WHERE track_id = ?")
+                .bind(&id_str)
+                .fetch_optional(&self.pool)
+                .await?;
+        let mut fields = queued.as_deref().map(parse_fields).unwrap_or_default();
+        for field in cleared {
+            if !fields.contains(field) {
+                fields.push(*field);
+            }
         }
+        let fields: Vec<&str> = fields.iter().map(|field| field.name()).collect();
+
+        sqlx::query(
+            r"INSERT INTO pending_writes (track_id, cleared, queued_at)
+              VALUES (?, ?, ?)
+              ON CONFLICT(track_id) DO UPDATE SET
+                cleared = excluded.cleared, queued_at = excluded.queued_at",
+        )
+        .bind(&id_str)
+        .bind(fields.join(","))
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
 
         Ok(())
     }
 
-    /// Update an existing playlist.
+    /// List tracks whose tags are queued to be written, with the fields to
+    /// remove from each file, oldest first.
     ///
     /// # Errors
     ///
-    /// Returns an error if the playlist doesn't exist or the database operation fails.
-    pub async fn update_playlist(&self, playlist: &Playlist) -> DbResult<()> {
-        let id_str = playlist.id.0.to_string();
-        let kind_str = format!("{}", playlist.kind);
-        let query_json = playlist
-            .query
-            .as_ref()
-            .map(serde_json::to_string)
-            .transpose()
-            .map_err(|e| DbError::Serialization(e.to_string()))?;
-        let sort_str = format!("{:?}", playlist.sort).to_lowercase();
-        let modified_at_str = Utc::now().to_rfc3339();
-
-        let max_tracks = playlist.limit.as_ref().and_then(|l| l.max_tracks);
-        let max_duration_secs = playlist
-            .limit
-            .as_ref()
-            .and_then(|l| l.max_duration_secs)
-            .map(|d| d as i64);
-
-        let result = sqlx::query(
-            r"UPDATE playlists SET
-                name = ?, description = ?, kind = ?, query = ?, sort = ?,
-                max_tracks = ?, max_duration_secs = ?, modified_at = ?
-              WHERE id = ?",
+    /// Returns an error if the database operation fails.
+    pub async fn list_pending_writes(&self) -> DbResult<Vec<(Track, Vec<EditField>)>> {
+        let rows = sqlx::query(
+            r"SELECT t.id, t.path, t.title, t.artist, t.album_artist, t.album_id, t.album_title,
+                     t.track_number, t.track_total, t.disc_number, t.disc_total, t.year,
+                     t.genres, t.duration_ms, t.bitrate, t.sample_rate, t.bit_depth, t.channels,
+                     t.format, t.musicbrainz_id, t.acoustid, t.added_at, t.modified_at,
+                     t.file_hash, t.isrc, t.rg_track_gain, t.rg_track_peak, t.rg_album_gain,
+                     t.rg_album_peak, t.loudness_lufs, p.cleared
+              FROM pending_writes p
+              JOIN tracks t ON t.id = p.track_id
+              ORDER BY p.queued_at",
         )
-        .bind(&playlist.name)
-        .bind(&playlist.description)
-        .bind(&kind_str)
-        .bind(&query_json)
-        .bind(&sort_str)
-        .bind(max_tracks.map(|n| n as i32))
-        .bind(max_duration_secs)
-        .bind(&modified_at_str)
-        .bind(&id_str)
-        .execute(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        if result.rows_affected() == 0 {
-            return Err(DbError::NotFound(format!("playlist {id_str}")));
-        }
-
-        // Update track IDs for static playlists
-        if playlist.kind == PlaylistKind::Static {
-            self.set_playlist_tracks(&playlist.id, &playlist.track_ids)
-                .await?;
-        }
+        rows.iter()
+            .map(|row| {
+                let cleared: String = row.get("cleared");
+                Ok((row_to_track(row)?, parse_fields(&cleared)))
+            })
+            .collect()
+    }
 
+    /// Remove a track from the queue of tags to write.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn remove_pending_write(&self, id: &TrackId) -> DbResult<()> {
+        sqlx::query("DELETE FROM pending_writes WHERE track_id = ?")
+            .bind(id.0.to_string())
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
-    /// Remove a playlist from the library.
+    // ========================================================================
+    // Queued scrobbles
+    // ========================================================================
+
+    /// Queue a play of a track to be scrobbled to a service.
     ///
     /// # Errors
     ///
-    /// Returns an error if the playlist doesn't exist or the database operation fails.
-    pub async fn remove_playlist(&self, id: &PlaylistId) -> DbResult<()> {
-        let id_str = id.0.to_string();
+    /// Returns an error if the track does not exist or the database
+    /// operation fails.
+    pub async fn queue_scrobble(
+        &self,
+        id: &TrackId,
+        service: &str,
+        played_at: DateTime<Utc>,
+    ) -> DbResult<()> {
+        self.ensure_track_exists(id).await?;
 
-        // The playlist_tracks entries are deleted automatically via ON DELETE CASCADE
-        let result = sqlx::query("DELETE FROM playlists WHERE id = ?")
-            .bind(&id_str)
+        sqlx::query("INSERT INTO scrobbles (track_id, service, played_at) VALUES (?, ?, ?)")
+            .bind(id.0.to_string())
+            .bind(service)
+            .bind(played_at.to_rfc3339())
             .execute(&self.pool)
             .await?;
 
-        if result.rows_affected() == 0 {
-            return Err(DbError::NotFound(format!("playlist {id_str}")));
-        }
-
         Ok(())
     }
 
-    /// List all playlists in the library.
+    /// List plays queued to be scrobbled, oldest first.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn list_playlists(&self) -> DbResult<Vec<Playlist>> {
+    pub async fn list_scrobbles(&self) -> DbResult<Vec<QueuedScrobble>> {
         let rows = sqlx::query(
-            r"SELECT id, name, description, kind, query, sort, max_tracks, max_duration_secs,
-                     created_at, modified_at
-              FROM playlists
-              ORDER BY name",
+            r"SELECT t.id, t.path, t.title, t.artist, t.album_artist, t.album_id, t.album_title,
+                     t.track_number, t.track_total, t.disc_number, t.disc_total, t.year,
+                     t.genres, t.duration_ms, t.bitrate, t.sample_rate, t.bit_depth, t.channels,
+                     t.format, t.musicbrainz_id, t.acoustid, t.added_at, t.modified_at,
+                     t.file_hash, t.isrc, t.rg_track_gain, t.rg_track_peak, t.rg_album_gain,
+                     t.rg_album_peak, t.loudness_lufs,
+                     s.id as scrobble_id, s.service, s.played_at, s.attempts, s.last_error
+              FROM scrobbles s
+              JOIN tracks t ON t.id = s.track_id
+              ORDER BY s.played_at, s.id",
         )
         .fetch_all(&self.pool)
         .await?;
 
-        let mut playlists = Vec::with_capacity(rows.len());
-        for row in &rows {
-            let mut playlist = row_to_playlist(row)?;
-
-            // Load track IDs for static playlists
-            if playlist.kind == PlaylistKind::Static {
-                playlist.track_ids = self.get_playlist_track_ids(&playlist.id).await?;
-            }
-
-            playlists.push(playlist);
-        }
-
-        Ok(playlists)
+        rows.iter()
+            .map(|row| {
+                let played_at: String = row.get("played_at");
+                let played_at = DateTime::parse_from_rfc3339(&played_at)
+                    .map_err(|e| DbError::InvalidData(e.to_string()))?
+                    .with_timezone(&Utc);
+                let attempts: i64 = row.get("attempts");
+                Ok(QueuedScrobble {
+                    id: row.get("scrobble_id"),
+                    service: row.get("service"),
+                    track: row_to_track(row)?,
+                    played_at,
+                    attempts: u32::try_from(attempts).unwrap_or(u32::MAX),
+                    last_error: row.get("last_error"),
+                })
+            })
+            .collect()
     }
 
-    /// Count total playlists in the library.
+    /// Remove a play from the queue of scrobbles.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn count_playlists(&self) -> DbResult<u64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM playlists")
-            .fetch_one(&self.pool)
+    pub async fn remove_scrobble(&self, id: i64) -> DbResult<()> {
+        sqlx::query("DELETE FROM scrobbles WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
             .await?;
+        Ok(())
+    }
 
-        Ok(row.get::<i64, _>("count") as u64)
+    /// Note a failed attempt to send a queued scrobble.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn fail_scrobble(&self, id: i64, error: &str) -> DbResult<()> {
+        sqlx::query("UPDATE scrobbles SET attempts = attempts + 1, last_error = ? WHERE id = ?")
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
-    /// Add a track to a static playlist.
+    // ========================================================================
+    // Operation log
+    // ========================================================================
+
+    /// Record an operation in the log.
+    ///
+    /// Returns the ID of the recorded operation.
     ///
     /// # Errors
     ///
-    /// Returns an error if the playlist doesn't exist or the database operation fails.
-    pub async fn add_track_to_playlist(
-        &self,
-        playlist_id: &PlaylistId,
-        track_id: &TrackId,
-    ) -> DbResult<()> {
-        let playlist_id_str = playlist_id.0.to_string();
-        let track_id_str = track_id.0.to_string();
-        let now = Utc::now().to_rfc3339();
+    /// Returns an error if the database operation fails.
+    pub async fn record_operation(&self, operation: &Operation) -> DbResult<i64> {
+        let steps_json = serde_json::to_string(&operation.steps)
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
 
-        // Get the next position
-        let row = sqlx::query(
-            "SELECT COALESCE(MAX(position), -1) + 1 as next_pos FROM playlist_tracks WHERE playlist_id = ?",
+        let result = sqlx::query(
+            "INSERT INTO operations (kind, description, steps, created_at, undone_at)
+             VALUES (?, ?, ?, ?, ?)",
         )
-        .bind(&playlist_id_str)
-        .fetch_one(&self.pool)
+        .bind(operation.kind.as_str())
+        .bind(&operation.description)
+        .bind(steps_json)
+        .bind(operation.created_at.to_rfc3339())
+        .bind(operation.undone_at.map(|at| at.to_rfc3339()))
+        .execute(&self.pool)
         .await?;
 
-        let next_pos: i32 = row.get("next_pos");
+        Ok(result.last_insert_rowid())
+    }
 
-        sqlx::query(
-            r"INSERT OR REPLACE INTO playlist_tracks (playlist_id, track_id, position, added_at)
-              VALUES (?, ?, ?, ?)",
+    /// List the most recent operations, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn list_operations(&self, limit: u32) -> DbResult<Vec<Operation>> {
+        let rows = sqlx::query(
+            "SELECT id, kind, description, steps, created_at, undone_at
+             FROM operations
+             ORDER BY id DESC
+             LIMIT ?",
         )
-        .bind(&playlist_id_str)
-        .bind(&track_id_str)
-        .bind(next_pos)
-        .bind(&now)
-        .execute(&self.pool)
+        .bind(i64::from(limit))
+        .fetch_all(&self.pool)
         .await?;
 
-        // Update playlist modified_at
-        let modified_at = Utc::now().to_rfc3339();
-        sqlx::query("UPDATE playlists SET modified_at = ? WHERE id = ?")
-            .bind(&modified_at)
-            .bind(&playlist_id_str)
-            .execute(&self.pool)
-            .await?;
-
-        Ok(())
+        rows.iter().map(row_to_operation).collect()
     }
 
-    /// Remove a track from a static playlist.
+    /// Get an operation by its ID.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn remove_track_from_playlist(
-        &self,
-        playlist_id: &PlaylistId,
-        track_id: &TrackId,
-    ) -> DbResult<()> {
-        let playlist_id_str = playlist_id.0.to_string();
-        let track_id_str = track_id.0.to_string();
+    pub async fn get_operation(&self, id: i64) -> DbResult<Option<Operation>> {
+        let row = sqlx::query(
+            "SELECT id, kind, description, steps, created_at, undone_at
+             FROM operations
+             WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
 
-        sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ? AND track_id = ?")
-            .bind(&playlist_id_str)
-            .bind(&track_id_str)
-            .execute(&self.pool)
-            .await?;
+        row.as_ref().map(row_to_operation).transpose()
+    }
 
-        // Update playlist modified_at
-        let modified_at = Utc::now().to_rfc3339();
-        sqlx::query("UPDATE playlists SET modified_at = ? WHERE id = ?")
-            .bind(&modified_at)
-            .bind(&playlist_id_str)
+    /// Note that an operation has been undone.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the operation does not exist or the database
+    /// operation fails.
+    pub async fn mark_operation_undone(&self, id: i64, undone_at: DateTime<Utc>) -> DbResult<()> {
+        let result = sqlx::query("UPDATE operations SET undone_at = ? WHERE id = ?")
+            .bind(undone_at.to_rfc3339())
+            .bind(id)
             .execute(&self.pool)
             .await?;
 
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound(format!("Operation {id}")));
+        }
         Ok(())
     }
 
-    /// Get all tracks in a playlist.
+    // ========================================================================
+    // Import history
+    // ========================================================================
+
+    /// Record a finished import in the import history.
     ///
-    /// For static playlists, returns the stored tracks in order.
-    /// For smart playlists, evaluates the query and returns matching tracks.
+    /// Returns the ID of the recorded run.
     ///
     /// # Errors
     ///
     /// Returns an error if the database operation fails.
-    pub async fn get_playlist_tracks(&self, playlist_id: &PlaylistId) -> DbResult<Vec<Track>> {
-        let id_str = playlist_id.0.to_string();
+    pub async fn record_import_run(&self, run: &ImportRun) -> DbResult<i64> {
+        let to_json = |value: Result<String, serde_json::Error>| {
+            value.map_err(|e| DbError::Serialization(e.to_string()))
+        };
+        let options_json = to_json(serde_json::to_string(&run.options))?;
+        let errors_json = to_json(serde_json::to_string(&run.errors))?;
+        let failed_files_json = to_json(serde_json::to_string(&run.failed_files))?;
 
-        // First, get the playlist to check its type
-        let playlist = self
-            .get_playlist(playlist_id)
-            .await?
-            .ok_or_else(|| DbError::NotFound(format!("playlist {id_str}")))?;
+        let result = sqlx::query(
+            "INSERT INTO import_runs (
+                source_path, started_at, duration_ms, options, tracks_found,
+                tracks_imported, tracks_skipped, tracks_duplicate, tracks_failed,
+                albums_created, cancelled, errors, failed_files
+             ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(run.source_path.to_string_lossy().to_string())
+        .bind(run.started_at.to_rfc3339())
+        .bind(run.duration_ms as i64)
+        .bind(options_json)
+        .bind(run.tracks_found as i64)
+        .bind(run.tracks_imported as i64)
+        .bind(run.tracks_skipped as i64)
+        .bind(run.tracks_duplicate as i64)
+        .bind(run.tracks_failed as i64)
+        .bind(run.albums_created as i64)
+        .bind(run.cancelled)
+        .bind(errors_json)
+        .bind(failed_files_json)
+        .execute(&self.pool)
+        .await?;
 
-        match playlist.kind {
-            PlaylistKind::Static => {
-                // Get tracks in playlist order
-                let rows = sqlx::query(
-                    r"SELECT t.id, t.path, t.title, t.artist, t.album_artist, t.album_id, t.album_title,
-                             t.track_number, t.track_total, t.disc_number, t.disc_total, t.year,
-                             t.genres, t.duration_ms, t.bitrate, t.sample_rate, t.channels, t.format,
-                             t.musicbrainz_id, t.acoustid, t.added_at, t.modified_at, t.file_hash
-                      FROM tracks t
-                      JOIN playlist_tracks pt ON t.id = pt.track_id
-                      WHERE pt.playlist_id = ?
-                      ORDER BY pt.position",
-                )
-                .bind(&id_str)
-                .fetch_all(&self.pool)
+        Ok(result.last_insert_rowid())
+    }
+
+    /// List the most recent imports, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn list_import_runs(&self, limit: u32) -> DbResult<Vec<ImportRun>> {
+        let rows = sqlx::query("SELECT * FROM import_runs ORDER BY id DESC LIMIT ?")
+            .bind(i64::from(limit))
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(row_to_import_run).collect()
+    }
+
+    // ========================================================================
+    // Change log
+    // ========================================================================
+
+    /// List the changes to tracks and playlists made after sequence number
+    /// `since`, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn list_changes(&self, since: i64) -> DbResult<Vec<Change>> {
+        let rows = sqlx::query(
+            "SELECT seq, item, item_id, kind, changed_at
+             FROM changes
+             WHERE seq > ?
+             ORDER BY seq",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_change).collect()
+    }
+
+    /// Get the sequence number of the last change made at or before a time,
+    /// or 0 if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn change_seq_at(&self, at: DateTime<Utc>) -> DbResult<i64> {
+        // Stored in the same format, so the timestamps compare as text
+        let seq: i64 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(seq), 0) FROM changes WHERE changed_at <= ?")
+                .bind(at.to_rfc3339_opts(SecondsFormat::Millis, true))
+                .fetch_one(&self.pool)
                 .await?;
+        Ok(seq)
+    }
 
-                rows.iter().map(row_to_track).collect()
-            }
-            PlaylistKind::Smart => {
-                // Evaluate the query
-                self.evaluate_smart_playlist(&playlist).await
-            }
+    // ========================================================================
+    // Ratings and play history
+    // ========================================================================
+
+    /// Set or clear the rating of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rating is not between 1 and 5, the track does
+    /// not exist, or the database operation fails.
+    pub async fn set_track_rating(&self, id: &TrackId, rating: Option<u8>) -> DbResult<()> {
+        if let Some(rating) = rating
+            && !(1..=5).contains(&rating)
+        {
+            return Err(DbError::InvalidData(format!(
+                "rating must be between 1 and 5, got {rating}"
+            )));
         }
+        self.ensure_track_exists(id).await?;
+
+        sqlx::query(
+            r"INSERT INTO track_ratings (track_id, rating, modified_at) VALUES (?, ?, ?)
+              ON CONFLICT(track_id) DO UPDATE SET
+                rating = excluded.rating, modified_at = excluded.modified_at",
+        )
+        .bind(id.0.to_string())
+        .bind(rating)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        self.events.emit(LibraryEvent::TrackRated {
+            track_id: id.clone(),
+        });
+
+        Ok(())
     }
 
-    /// Evaluate a smart playlist query and return matching tracks.
-    async fn evaluate_smart_playlist(&self, playlist: &Playlist) -> DbResult<Vec<Track>> {
-        let query = playlist
-            .query
-            .as_ref()
-            .ok_or_else(|| DbError::InvalidData("Smart playlist has no query".to_string()))?;
+    /// Mark or unmark a track as a favorite.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    pub async fn set_track_favorite(&self, id: &TrackId, favorite: bool) -> DbResult<()> {
+        self.ensure_track_exists(id).await?;
 
-        // Build the SQL WHERE clause from the query
-        let (where_clause, bindings) = query_to_sql(query);
-
-        // Build the ORDER BY clause
-        let order_by = match playlist.sort {
-            PlaylistSort::Artist => "artist, album_title, disc_number, track_number",
-            PlaylistSort::Album => "album_title, disc_number, track_number",
-            PlaylistSort::Title => "title",
-            PlaylistSort::AddedDesc => "added_at DESC",
-            PlaylistSort::AddedAsc => "added_at ASC",
-            PlaylistSort::YearDesc => "year DESC, album_title, disc_number, track_number",
-            PlaylistSort::YearAsc => "year ASC, album_title, disc_number, track_number",
-            PlaylistSort::Random => "RANDOM()",
-        };
+        sqlx::query(
+            r"INSERT INTO track_ratings (track_id, favorite, modified_at) VALUES (?, ?, ?)
+              ON CONFLICT(track_id) DO UPDATE SET
+                favorite = excluded.favorite, modified_at = excluded.modified_at",
+        )
+        .bind(id.0.to_string())
+        .bind(favorite)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
 
-        // Build LIMIT clause
-        let limit_clause = playlist
-            .limit
-            .as_ref()
-            .and_then(|l| l.max_tracks)
-            .map(|n| format!("LIMIT {n}"))
-            .unwrap_or_default();
+        self.events.emit(LibraryEvent::TrackRated {
+            track_id: id.clone(),
+        });
 
-        let sql = format!(
-            r"SELECT id, path, title, artist, album_artist, album_id, album_title,
-                     track_number, track_total, disc_number, disc_total, year,
-                     genres, duration_ms, bitrate, sample_rate, channels, format,
-                     musicbrainz_id, acoustid, added_at, modified_at, file_hash
-              FROM tracks
-              WHERE {where_clause}
-              ORDER BY {order_by}
-              {limit_clause}"
-        );
+        Ok(())
+    }
 
-        // Build the query with bindings
-        let mut query = sqlx::query(&sql);
-        for binding in bindings {
-            query = query.bind(binding);
-        }
+    /// Record a play of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    pub async fn record_play(&self, id: &TrackId, played_at: DateTime<Utc>) -> DbResult<()> {
+        self.ensure_track_exists(id).await?;
+
+        sqlx::query("INSERT INTO plays (track_id, played_at) VALUES (?, ?)")
+            .bind(id.0.to_string())
+            .bind(played_at.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
 
-        let rows = query.fetch_all(&self.pool).await?;
+        self.events.emit(LibraryEvent::TrackPlayed {
+            track_id: id.clone(),
+            played_at,
+        });
 
-        let mut tracks: Vec<Track> = rows.iter().map(row_to_track).collect::<DbResult<_>>()?;
+        Ok(())
+    }
 
-        // Apply max_duration_secs limit if set
-        if let Some(limit) = &playlist.limit
-            && let Some(max_secs) = limit.max_duration_secs
-        {
-            let max_ms = max_secs * 1000;
-            let mut total_ms = 0u64;
-            tracks.retain(|track| {
-                let track_ms = track.duration.as_millis() as u64;
-                if total_ms + track_ms <= max_ms {
-                    total_ms += track_ms;
-                    true
-                } else {
-                    false
-                }
-            });
+    /// Import plays of a track made in another music player.
+    ///
+    /// Unlike [`Self::record_play`], no events are emitted, since the plays
+    /// did not happen now.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails, in which case no plays are imported.
+    pub async fn import_plays(&self, id: &TrackId, played_at: &[DateTime<Utc>]) -> DbResult<()> {
+        self.ensure_track_exists(id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        for played_at in played_at {
+            sqlx::query("INSERT INTO plays (track_id, played_at) VALUES (?, ?)")
+                .bind(id.0.to_string())
+                .bind(played_at.to_rfc3339())
+                .execute(&mut *tx)
+                .await?;
         }
+        tx.commit().await?;
 
-        Ok(tracks)
+        Ok(())
     }
-}
 
-/// Convert a Query to a SQL WHERE clause.
-fn query_to_sql(query: &apollo_core::query::Query) -> (String, Vec<String>) {
-    use apollo_core::query::{Field, Query};
+    /// Set when a track was added to the library, such as when migrating
+    /// from another music manager.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    pub async fn set_track_added_at(&self, id: &TrackId, added_at: DateTime<Utc>) -> DbResult<()> {
+        let result = sqlx::query("UPDATE tracks SET added_at = ? WHERE id = ?")
+            .bind(added_at.to_rfc3339())
+            .bind(id.0.to_string())
+            .execute(&self.pool)
+            .await?;
 
-    match query {
-        Query::All => ("1 = 1".to_string(), vec![]),
-        Query::Text(text) => {
-            let pattern = format!("%{text}%");
-            (
-                "(title LIKE ? OR artist LIKE ? OR album_title LIKE ?)".to_string(),
-                vec![pattern.clone(), pattern.clone(), pattern],
-            )
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound(format!("track {id}")));
         }
-        Query::Field { field, value } => {
-            let column = match field {
-                Field::Artist => "artist",
-                Field::AlbumArtist => "album_artist",
-                Field::Album => "album_title",
-                Field::Title => "title",
-                Field::Year => "year",
-                Field::Genre => "genres",
-                Field::Path => "path",
-            };
 
-            if *field == Field::Genre {
-                // Genres are stored as JSON array
-                let pattern = format!("%\"{value}\"%");
-                (format!("{column} LIKE ?"), vec![pattern])
-            } else if *field == Field::Path {
-                // Path uses prefix matching
-                let pattern = format!("{value}%");
-                (format!("{column} LIKE ?"), vec![pattern])
-            } else if *field == Field::Year {
-                // Year uses exact match
-                (format!("{column} = ?"), vec![value.clone()])
-            } else {
-                // Other fields use LIKE for partial matching
-                let pattern = format!("%{value}%");
-                (format!("{column} LIKE ?"), vec![pattern])
-            }
-        }
-        Query::YearRange { start, end } => (
-            "year BETWEEN ? AND ?".to_string(),
-            vec![start.to_string(), end.to_string()],
-        ),
-        Query::And(queries) => {
-            let mut clauses = Vec::new();
-            let mut all_bindings = Vec::new();
-            for q in queries {
-                let (clause, bindings) = query_to_sql(q);
-                clauses.push(format!("({clause})"));
-                all_bindings.extend(bindings);
-            }
-            (clauses.join(" AND "), all_bindings)
-        }
-        Query::Or(queries) => {
-            let mut clauses = Vec::new();
-            let mut all_bindings = Vec::new();
-            for q in queries {
-                let (clause, bindings) = query_to_sql(q);
-                clauses.push(format!("({clause})"));
-                all_bindings.extend(bindings);
-            }
-            (clauses.join(" OR "), all_bindings)
-        }
-        Query::Not(inner) => {
-            let (clause, bindings) = query_to_sql(inner);
-            (format!("NOT ({clause})"), bindings)
+        self.events.emit(LibraryEvent::TrackUpdated {
+            track_id: id.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Get the rating and play statistics of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn get_track_stats(&self, id: &TrackId) -> DbResult<TrackStats> {
+        let row = sqlx::query(
+            r"SELECT r.rating, COALESCE(r.favorite, 0) as favorite,
+                     (SELECT COUNT(*) FROM plays WHERE track_id = t.id) as play_count,
+                     (SELECT MAX(played_at) FROM plays WHERE track_id = t.id) as last_played
+              FROM tracks t
+              LEFT JOIN track_ratings r ON r.track_id = t.id
+              WHERE t.id = ?",
+        )
+        .bind(id.0.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| DbError::NotFound(format!("track {id}")))?;
+
+        let last_played: Option<String> = row.get("last_played");
+        let last_played = last_played
+            .map(|s| {
+                DateTime::parse_from_rfc3339(&s)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .map_err(|e| DbError::InvalidData(e.to_string()))
+            })
+            .transpose()?;
+        let play_count: i64 = row.get("play_count");
+
+        Ok(TrackStats {
+            rating: row.get::<Option<u8>, _>("rating"),
+            favorite: row.get("favorite"),
+            play_count: u32::try_from(play_count).unwrap_or(u32::MAX),
+            last_played,
+        })
+    }
+
+    /// Return a not found error if a track does not exist.
+    async fn ensure_track_exists(&self, id: &TrackId) -> DbResult<()> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM tracks WHERE id = ?")
+            .bind(id.0.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        let count: i64 = row.get("count");
+        if count == 0 {
+            return Err(DbError::NotFound(format!("track {id}")));
         }
+        Ok(())
     }
-}
 
-/// Convert a database row to a Playlist.
-fn row_to_playlist(row: &sqlx::sqlite::SqliteRow) -> DbResult<Playlist> {
-    let id_str: String = row.get("id");
-    let id = Uuid::parse_str(&id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+    // ========================================================================
+    // Artwork operations
+    // ========================================================================
 
-    let kind_str: String = row.get("kind");
-    let kind = match kind_str.as_str() {
-        "static" => PlaylistKind::Static,
-        "smart" => PlaylistKind::Smart,
-        _ => {
-            return Err(DbError::InvalidData(format!(
-                "Unknown playlist kind: {kind_str}"
-            )));
+    /// Set the cover art of an album, replacing any it has.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the album does not exist or the database operation
+    /// fails.
+    pub async fn set_album_artwork(&self, id: &AlbumId, artwork: &Artwork) -> DbResult<()> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM albums WHERE id = ?")
+            .bind(id.0.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        let count: i64 = row.get("count");
+        if count == 0 {
+            return Err(DbError::NotFound(format!("album {id}")));
         }
-    };
 
-    let query_json: Option<String> = row.get("query");
-    let query = query_json
-        .map(|s| serde_json::from_str(&s))
-        .transpose()
-        .map_err(|e| DbError::Serialization(e.to_string()))?;
+        sqlx::query(
+            r"INSERT INTO artwork (album_id, mime_type, data, source, modified_at)
+              VALUES (?, ?, ?, ?, ?)
+              ON CONFLICT(album_id) DO UPDATE SET
+                mime_type = excluded.mime_type, data = excluded.data,
+                source = excluded.source, modified_at = excluded.modified_at",
+        )
+        .bind(id.0.to_string())
+        .bind(&artwork.mime_type)
+        .bind(&artwork.data)
+        .bind(&artwork.source)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
 
-    let sort_str: String = row.get("sort");
-    let sort = parse_playlist_sort(&sort_str);
+        self.events.emit(LibraryEvent::AlbumUpdated {
+            album_id: id.clone(),
+        });
 
-    let max_tracks: Option<i32> = row.get("max_tracks");
-    let max_duration_secs: Option<i64> = row.get("max_duration_secs");
-    let limit = if max_tracks.is_some() || max_duration_secs.is_some() {
-        Some(PlaylistLimit {
-            max_tracks: max_tracks.map(|n| n as u32),
-            max_duration_secs: max_duration_secs.map(|n| n as u64),
-        })
-    } else {
-        None
-    };
+        Ok(())
+    }
+
+    /// Get the cover art of an album.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn get_album_artwork(&self, id: &AlbumId) -> DbResult<Option<Artwork>> {
+        let row = sqlx::query("SELECT mime_type, data, source FROM artwork WHERE album_id = ?")
+            .bind(id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| Artwork {
+            data: row.get("data"),
+            mime_type: row.get("mime_type"),
+            source: row.get("source"),
+        }))
+    }
+
+    /// Get when the cover art of an album was last stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn get_album_artwork_modified(
+        &self,
+        id: &AlbumId,
+    ) -> DbResult<Option<DateTime<Utc>>> {
+        let row = sqlx::query("SELECT modified_at FROM artwork WHERE album_id = ?")
+            .bind(id.0.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| {
+            let modified_at: String = row.get("modified_at");
+            Ok(DateTime::parse_from_rfc3339(&modified_at)
+                .map_err(|e| DbError::InvalidData(e.to_string()))?
+                .with_timezone(&Utc))
+        })
+        .transpose()
+    }
+
+    /// Store the audio fingerprint of a track, replacing any it has.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails.
+    pub async fn set_fingerprint(&self, id: &TrackId, fingerprint: &Fingerprint) -> DbResult<()> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM tracks WHERE id = ?")
+            .bind(id.0.to_string())
+            .fetch_one(&self.pool)
+            .await?;
+        let count: i64 = row.get("count");
+        if count == 0 {
+            return Err(DbError::NotFound(format!("track {id}")));
+        }
+
+        sqlx::query(
+            r"INSERT INTO fingerprints (track_id, fingerprint, duration_secs, computed_at)
+              VALUES (?, ?, ?, ?)
+              ON CONFLICT(track_id) DO UPDATE SET
+                fingerprint = excluded.fingerprint, duration_secs = excluded.duration_secs,
+                computed_at = excluded.computed_at",
+        )
+        .bind(id.0.to_string())
+        .bind(&fingerprint.fingerprint)
+        .bind(i64::from(fingerprint.duration_secs))
+        .bind(fingerprint.computed_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the stored audio fingerprint of a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn get_fingerprint(&self, id: &TrackId) -> DbResult<Option<Fingerprint>> {
+        let row = sqlx::query(
+            "SELECT fingerprint, duration_secs, computed_at FROM fingerprints WHERE track_id = ?",
+        )
+        .bind(id.0.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| {
+            let computed_at: String = row.get("computed_at");
+            let computed_at = DateTime::parse_from_rfc3339(&computed_at)
+                .map_err(|e| DbError::InvalidData(e.to_string()))?
+                .with_timezone(&Utc);
+            Ok(Fingerprint {
+                fingerprint: row.get("fingerprint"),
+                duration_secs: row.get::<i64, _>("duration_secs") as u32,
+                computed_at,
+            })
+        })
+        .transpose()
+    }
+
+    /// Record where fields of a track came from.
+    ///
+    /// The fields in `provenance` replace those recorded before; other
+    /// fields keep their source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails, in which case nothing is recorded.
+    pub async fn record_provenance(&self, id: &TrackId, provenance: &Provenance) -> DbResult<()> {
+        self.ensure_track_exists(id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        for (field, source) in &provenance.fields {
+            sqlx::query(
+                r"INSERT INTO field_sources (track_id, field, source, updated_at)
+                  VALUES (?, ?, ?, ?)
+                  ON CONFLICT(track_id, field) DO UPDATE SET
+                    source = excluded.source, updated_at = excluded.updated_at",
+            )
+            .bind(id.0.to_string())
+            .bind(field)
+            .bind(source.source.as_str())
+            .bind(source.updated_at.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Get where the fields of a track came from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn get_track_provenance(&self, id: &TrackId) -> DbResult<Provenance> {
+        let rows = sqlx::query(
+            "SELECT field, source, updated_at FROM field_sources WHERE track_id = ? ORDER BY field",
+        )
+        .bind(id.0.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut provenance = Provenance::default();
+        for row in rows {
+            let source: String = row.get("source");
+            let source = MetadataSource::from_name(&source)
+                .ok_or_else(|| DbError::InvalidData(format!("unknown field source: {source}")))?;
+            let updated_at: String = row.get("updated_at");
+            let updated_at = DateTime::parse_from_rfc3339(&updated_at)
+                .map_err(|e| DbError::InvalidData(e.to_string()))?
+                .with_timezone(&Utc);
+            provenance
+                .fields
+                .insert(row.get("field"), FieldSource { source, updated_at });
+        }
+        Ok(provenance)
+    }
+
+    /// Set the locked fields of a track, replacing those it had.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the database operation
+    /// fails, in which case the locks are not changed.
+    pub async fn set_field_locks(&self, id: &TrackId, fields: &[EditField]) -> DbResult<()> {
+        self.ensure_track_exists(id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM field_locks WHERE track_id = ?")
+            .bind(id.0.to_string())
+            .execute(&mut *tx)
+            .await?;
+        for field in fields {
+            sqlx::query("INSERT OR IGNORE INTO field_locks (track_id, field) VALUES (?, ?)")
+                .bind(id.0.to_string())
+                .bind(field.name())
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Get the locked fields of a track, in the order of [`EditField::ALL`].
+    ///
+    /// Unknown field names are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn get_field_locks(&self, id: &TrackId) -> DbResult<Vec<EditField>> {
+        let names: Vec<String> =
+            sqlx::query_scalar("SELECT field FROM field_locks WHERE track_id = ?")
+                .bind(id.0.to_string())
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(EditField::ALL
+            .iter()
+            .copied()
+            .filter(|field| names.iter().any(|name| name == field.name()))
+            .collect())
+    }
+
+    // ========================================================================
+    // Playlist operations
+    // ========================================================================
+
+    /// Get a playlist by its ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn get_playlist(&self, id: &PlaylistId) -> DbResult<Option<Playlist>> {
+        let id_str = id.0.to_string();
+
+        let row = sqlx::query(
+            r"SELECT id, name, description, kind, query, sort, max_tracks, max_duration_secs,
+                     created_at, modified_at
+              FROM playlists WHERE id = ?",
+        )
+        .bind(&id_str)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(r) => {
+                let mut playlist = row_to_playlist(&r)?;
+
+                // Load track IDs for static playlists
+                if playlist.kind == PlaylistKind::Static {
+                    playlist.track_ids = self.get_playlist_track_ids(&playlist.id).await?;
+                }
+
+                Ok(Some(playlist))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Get the track IDs for a playlist.
+    async fn get_playlist_track_ids(&self, playlist_id: &PlaylistId) -> DbResult<Vec<TrackId>> {
+        let id_str = playlist_id.0.to_string();
+
+        let rows = sqlx::query(
+            r"SELECT track_id FROM playlist_tracks
+              WHERE playlist_id = ?
+              ORDER BY position",
+        )
+        .bind(&id_str)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut track_ids = Vec::with_capacity(rows.len());
+        for row in rows {
+            let track_id_str: String = row.get("track_id");
+            let track_id =
+                Uuid::parse_str(&track_id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+            track_ids.push(TrackId(track_id));
+        }
+
+        Ok(track_ids)
+    }
+
+    /// Add a playlist to the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist has a track more than once, or the
+    /// database operation fails.
+    pub async fn add_playlist(&self, playlist: &Playlist) -> DbResult<PlaylistId> {
+        ensure_unique_tracks(playlist)?;
+        let id_str = playlist.id.0.to_string();
+        let kind_str = format!("{}", playlist.kind);
+        let query_json = playlist
+            .query
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+        let sort_str = playlist_sort_to_str(playlist);
+        let created_at_str = playlist.created_at.to_rfc3339();
+        let modified_at_str = playlist.modified_at.to_rfc3339();
+
+        let max_tracks = playlist.limit.as_ref().and_then(|l| l.max_tracks);
+        let max_duration_secs = playlist
+            .limit
+            .as_ref()
+            .and_then(|l| l.max_duration_secs)
+            .map(|d| d as i64);
+
+        sqlx::query(
+            r"INSERT INTO playlists (id, name, description, kind, query, sort, max_tracks,
+                                     max_duration_secs, created_at, modified_at)
+              VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&id_str)
+        .bind(&playlist.name)
+        .bind(&playlist.description)
+        .bind(&kind_str)
+        .bind(&query_json)
+        .bind(&sort_str)
+        .bind(max_tracks.map(|n| n as i32))
+        .bind(max_duration_secs)
+        .bind(&created_at_str)
+        .bind(&modified_at_str)
+        .execute(&self.pool)
+        .await?;
+
+        // Add track IDs for static playlists
+        if playlist.kind == PlaylistKind::Static {
+            self.set_playlist_tracks(&playlist.id, &playlist.track_ids)
+                .await?;
+        }
+
+        self.events.emit(LibraryEvent::PlaylistCreated {
+            playlist_id: playlist.id.clone(),
+        });
+
+        Ok(playlist.id.clone())
+    }
+
+    /// Set the tracks for a static playlist.
+    async fn set_playlist_tracks(
+        &self,
+        playlist_id: &PlaylistId,
+        track_ids: &[TrackId],
+    ) -> DbResult<()> {
+        let playlist_id_str = playlist_id.0.to_string();
+        let now = Utc::now().to_rfc3339();
+
+        let current = self.get_playlist_track_ids(playlist_id).await?;
+        if current == track_ids {
+            return Ok(());
+        }
+        self.record_playlist_version(playlist_id, &current).await?;
+
+        // Delete existing tracks
+        sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ?")
+            .bind(&playlist_id_str)
+            .execute(&self.pool)
+            .await?;
+
+        // Insert new tracks
+        for (position, track_id) in track_ids.iter().enumerate() {
+            let track_id_str = track_id.0.to_string();
+            sqlx::query(
+                r"INSERT INTO playlist_tracks (playlist_id, track_id, position, added_at)
+                  VALUES (?, ?, ?, ?)",
+            )
+            .bind(&playlist_id_str)
+            .bind(&track_id_str)
+            .bind(position as i32)
+            .bind(&now)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Update an existing playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist, has a track more than
+    /// once, or the database operation fails.
+    pub async fn update_playlist(&self, playlist: &Playlist) -> DbResult<()> {
+        ensure_unique_tracks(playlist)?;
+        let id_str = playlist.id.0.to_string();
+        let kind_str = format!("{}", playlist.kind);
+        let query_json = playlist
+            .query
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+        let sort_str = playlist_sort_to_str(playlist);
+        let modified_at_str = Utc::now().to_rfc3339();
+
+        let max_tracks = playlist.limit.as_ref().and_then(|l| l.max_tracks);
+        let max_duration_secs = playlist
+            .limit
+            .as_ref()
+            .and_then(|l| l.max_duration_secs)
+            .map(|d| d as i64);
+
+        let result = sqlx::query(
+            r"UPDATE playlists SET
+                name = ?, description = ?, kind = ?, query = ?, sort = ?,
+                max_tracks = ?, max_duration_secs = ?, modified_at = ?
+              WHERE id = ?",
+        )
+        .bind(&playlist.name)
+        .bind(&playlist.description)
+        .bind(&kind_str)
+        .bind(&query_json)
+        .bind(&sort_str)
+        .bind(max_tracks.map(|n| n as i32))
+        .bind(max_duration_secs)
+        .bind(&modified_at_str)
+        .bind(&id_str)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound(format!("playlist {id_str}")));
+        }
+
+        // Update track IDs for static playlists
+        if playlist.kind == PlaylistKind::Static {
+            self.set_playlist_tracks(&playlist.id, &playlist.track_ids)
+                .await?;
+        }
+
+        self.events.emit(LibraryEvent::PlaylistChanged {
+            playlist_id: playlist.id.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Remove a playlist from the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist or the database operation fails.
+    pub async fn remove_playlist(&self, id: &PlaylistId) -> DbResult<()> {
+        let id_str = id.0.to_string();
+
+        // The playlist_tracks entries are deleted automatically via ON DELETE CASCADE
+        let result = sqlx::query("DELETE FROM playlists WHERE id = ?")
+            .bind(&id_str)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound(format!("playlist {id_str}")));
+        }
+
+        self.events.emit(LibraryEvent::PlaylistRemoved {
+            playlist_id: id.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// List all playlists in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn list_playlists(&self) -> DbResult<Vec<Playlist>> {
+        let rows = sqlx::query(
+            r"SELECT id, name, description, kind, query, sort, max_tracks, max_duration_secs,
+                     created_at, modified_at
+              FROM playlists
+              ORDER BY name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut playlists = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let mut playlist = row_to_playlist(row)?;
+
+            // Load track IDs for static playlists
+            if playlist.kind == PlaylistKind::Static {
+                playlist.track_ids = self.get_playlist_track_ids(&playlist.id).await?;
+            }
+
+            playlists.push(playlist);
+        }
+
+        Ok(playlists)
+    }
+
+    /// Count total playlists in the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn count_playlists(&self) -> DbResult<u64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM playlists")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.get::<i64, _>("count") as u64)
+    }
+
+    /// Add a track to the end of a static playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist, already has the
+    /// track, or the database operation fails.
+    pub async fn add_track_to_playlist(
+        &self,
+        playlist_id: &PlaylistId,
+        track_id: &TrackId,
+    ) -> DbResult<()> {
+        let playlist_id_str = playlist_id.0.to_string();
+        let track_id_str = track_id.0.to_string();
+        let now = Utc::now().to_rfc3339();
+
+        let current = self.get_playlist_track_ids(playlist_id).await?;
+        if current.contains(track_id) {
+            return Err(DbError::InvalidData(format!(
+                "playlist {playlist_id_str} already has track {track_id_str}"
+            )));
+        }
+        self.record_playlist_version(playlist_id, &current).await?;
+
+        // Get the next position
+        let row = sqlx::query(
+            "SELECT COALESCE(MAX(position), -1) + 1 as next_pos FROM playlist_tracks WHERE playlist_id = ?",
+        )
+        .bind(&playlist_id_str)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let next_pos: i32 = row.get("next_pos");
+
+        sqlx::query(
+            r"INSERT INTO playlist_tracks (playlist_id, track_id, position, added_at)
+              VALUES (?, ?, ?, ?)",
+        )
+        .bind(&playlist_id_str)
+        .bind(&track_id_str)
+        .bind(next_pos)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+
+        // Update playlist modified_at
+        let modified_at = Utc::now().to_rfc3339();
+        sqlx::query("UPDATE playlists SET modified_at = ? WHERE id = ?")
+            .bind(&modified_at)
+            .bind(&playlist_id_str)
+            .execute(&self.pool)
+            .await?;
+
+        self.events.emit(LibraryEvent::PlaylistChanged {
+            playlist_id: playlist_id.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Remove a track from a static playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn remove_track_from_playlist(
+        &self,
+        playlist_id: &PlaylistId,
+        track_id: &TrackId,
+    ) -> DbResult<()> {
+        let playlist_id_str = playlist_id.0.to_string();
+        let track_id_str = track_id.0.to_string();
+
+        let current = self.get_playlist_track_ids(playlist_id).await?;
+        if current.contains(track_id) {
+            self.record_playlist_version(playlist_id, &current).await?;
+        }
+
+        sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ? AND track_id = ?")
+            .bind(&playlist_id_str)
+            .bind(&track_id_str)
+            .execute(&self.pool)
+            .await?;
+
+        // Update playlist modified_at
+        let modified_at = Utc::now().to_rfc3339();
+        sqlx::query("UPDATE playlists SET modified_at = ? WHERE id = ?")
+            .bind(&modified_at)
+            .bind(&playlist_id_str)
+            .execute(&self.pool)
+            .await?;
+
+        self.events.emit(LibraryEvent::PlaylistChanged {
+            playlist_id: playlist_id.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// List the versions of a static playlist, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn list_playlist_versions(
+        &self,
+        playlist_id: &PlaylistId,
+    ) -> DbResult<Vec<PlaylistVersion>> {
+        let rows = sqlx::query(
+            r"SELECT id, playlist_id, created_at, track_ids FROM playlist_versions
+              WHERE playlist_id = ?
+              ORDER BY id DESC",
+        )
+        .bind(playlist_id.0.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_playlist_version).collect()
+    }
+
+    /// Give a static playlist the tracks of one of its versions, and return
+    /// the playlist.
+    ///
+    /// The tracks it has are kept as a new version first, so a restore can
+    /// be undone too. Tracks no longer in the library are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist or version doesn't exist, the
+    /// playlist is a smart playlist, or the database operation fails.
+    pub async fn restore_playlist_version(
+        &self,
+        playlist_id: &PlaylistId,
+        version_id: i64,
+    ) -> DbResult<Playlist> {
+        let mut playlist = self
+            .get_playlist(playlist_id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("playlist {playlist_id}")))?;
+        if playlist.kind != PlaylistKind::Static {
+            return Err(DbError::InvalidData(format!(
+                "playlist {playlist_id} is a smart playlist"
+            )));
+        }
+
+        let row = sqlx::query(
+            r"SELECT id, playlist_id, created_at, track_ids FROM playlist_versions
+              WHERE id = ? AND playlist_id = ?",
+        )
+        .bind(version_id)
+        .bind(playlist_id.0.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            DbError::NotFound(format!("version {version_id} of playlist {playlist_id}"))
+        })?;
+        let version = row_to_playlist_version(&row)?;
+
+        let track_ids_json = serde_json::to_string(&version.track_ids)
+            .map_err(|e| DbError::Serialization(e.to_string()))?;
+        let existing: HashSet<String> = sqlx::query_scalar(
+            "SELECT id FROM tracks WHERE id IN (SELECT value FROM json_each(?))",
+        )
+        .bind(track_ids_json)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .collect();
+        playlist.track_ids = version
+            .track_ids
+            .into_iter()
+            .filter(|track_id| existing.contains(&track_id.0.to_string()))
+            .collect();
+        self.update_playlist(&playlist).await?;
+
+        self.get_playlist(playlist_id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("playlist {playlist_id}")))
+    }
+
+    /// Keep the tracks a static playlist has as a version, before they
+    /// change.
+    ///
+    /// Nothing is kept for a playlist without tracks, or with the same
+    /// tracks as its newest version. Only the newest
+    /// [`MAX_PLAYLIST_VERSIONS`] versions are kept.
+    async fn record_playlist_version(
+        &self,
+        playlist_id: &PlaylistId,
+        track_ids: &[TrackId],
+    ) -> DbResult<()> {
+        if track_ids.is_empty() {
+            return Ok(());
+        }
+        let playlist_id_str = playlist_id.0.to_string();
+        let track_ids_json =
+            serde_json::to_string(track_ids).map_err(|e| DbError::Serialization(e.to_string()))?;
+
+        let newest: Option<String> = sqlx::query_scalar(
+            r"SELECT track_ids FROM playlist_versions
+              WHERE playlist_id = ?
+              ORDER BY id DESC
+              LIMIT 1",
+        )
+        .bind(&playlist_id_str)
+        .fetch_optional(&self.pool)
+        .await?;
+        if newest.as_deref() == Some(track_ids_json.as_str()) {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r"INSERT INTO playlist_versions (playlist_id, created_at, track_ids)
+              VALUES (?, ?, ?)",
+        )
+        .bind(&playlist_id_str)
+        .bind(Utc::now().to_rfc3339())
+        .bind(&track_ids_json)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r"DELETE FROM playlist_versions
+              WHERE playlist_id = ?1 AND id NOT IN (
+                  SELECT id FROM playlist_versions
+                  WHERE playlist_id = ?1
+                  ORDER BY id DESC
+                  LIMIT ?2
+              )",
+        )
+        .bind(&playlist_id_str)
+        .bind(MAX_PLAYLIST_VERSIONS)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get all tracks in a playlist.
+    ///
+    /// For static playlists, returns the stored tracks in order.
+    /// For smart playlists, evaluates the query and returns matching tracks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_playlist_tracks(&self, playlist_id: &PlaylistId) -> DbResult<Vec<Track>> {
+        let id_str = playlist_id.0.to_string();
+
+        // First, get the playlist to check its type
+        let playlist = self
+            .get_playlist(playlist_id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("playlist {id_str}")))?;
+
+        match playlist.kind {
+            PlaylistKind::Static => {
+                // Get tracks in playlist order
+                let rows = sqlx::query(
+                    r"SELECT t.id, t.path, t.title, t.artist, t.album_artist, t.album_id, t.album_title,
+                             t.track_number, t.track_total, t.disc_number, t.disc_total, t.year,
+                             t.genres, t.duration_ms, t.bitrate, t.sample_rate, t.bit_depth, t.channels, t.format,
+                             t.musicbrainz_id, t.acoustid, t.added_at, t.modified_at, t.file_hash,
+                             t.isrc, t.rg_track_gain, t.rg_track_peak, t.rg_album_gain, t.rg_album_peak, t.loudness_lufs
+                      FROM tracks t
+                      JOIN playlist_tracks pt ON t.id = pt.track_id
+                      WHERE pt.playlist_id = ?
+                      ORDER BY pt.position",
+                )
+                .bind(&id_str)
+                .fetch_all(&self.pool)
+                .await?;
+
+                rows.iter().map(row_to_track).collect()
+            }
+            PlaylistKind::Smart => {
+                // Evaluate the query
+                self.evaluate_smart_playlist(&playlist, &[]).await
+            }
+        }
+    }
+
+    /// Get a page of the tracks in a playlist.
+    ///
+    /// Like [`Self::get_playlist_tracks`], but only the tracks in the page
+    /// are read. Smart playlists limited by duration are evaluated in full,
+    /// since which tracks fit depends on all tracks before them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist or the database
+    /// operation fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn get_playlist_tracks_page(
+        &self,
+        playlist_id: &PlaylistId,
+        limit: u32,
+        offset: u32,
+    ) -> DbResult<Vec<Track>> {
+        let id_str = playlist_id.0.to_string();
+        let playlist = self
+            .get_playlist(playlist_id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("playlist {id_str}")))?;
+
+        match (&playlist.kind, &playlist.query) {
+            (PlaylistKind::Static, _) => {
+                let rows = sqlx::query(
+                    r"SELECT t.id, t.path, t.title, t.artist, t.album_artist, t.album_id, t.album_title,
+                             t.track_number, t.track_total, t.disc_number, t.disc_total, t.year,
+                             t.genres, t.duration_ms, t.bitrate, t.sample_rate, t.bit_depth, t.channels, t.format,
+                             t.musicbrainz_id, t.acoustid, t.added_at, t.modified_at, t.file_hash,
+                             t.isrc, t.rg_track_gain, t.rg_track_peak, t.rg_album_gain, t.rg_album_peak, t.loudness_lufs
+                      FROM tracks t
+                      JOIN playlist_tracks pt ON t.id = pt.track_id
+                      WHERE pt.playlist_id = ?
+                      ORDER BY pt.position
+                      LIMIT ? OFFSET ?",
+                )
+                .bind(&id_str)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(&self.pool)
+                .await?;
+
+                rows.iter().map(row_to_track).collect()
+            }
+            (PlaylistKind::Smart, Some(query))
+                if playlist
+                    .limit
+                    .as_ref()
+                    .is_none_or(|l| l.max_duration_secs.is_none()) =>
+            {
+                // Stop the page at the playlist's own limit
+                let max_tracks = playlist.limit.as_ref().and_then(|l| l.max_tracks);
+                let limit = max_tracks.map_or(limit, |max| limit.min(max.saturating_sub(offset)));
+                if limit == 0 {
+                    return Ok(Vec::new());
+                }
+                self.select_tracks(
+                    query,
+                    &playlist.effective_sort(),
+                    Some(limit),
+                    offset,
+                    std::slice::from_ref(&playlist.id),
+                )
+                .await
+            }
+            (PlaylistKind::Smart, _) => Ok(self
+                .evaluate_smart_playlist(&playlist, &[])
+                .await?
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect()),
+        }
+    }
+
+    /// Count the tracks in a playlist.
+    ///
+    /// For smart playlists, the tracks that match the query are counted,
+    /// up to the playlist's limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist doesn't exist or the database
+    /// operation fails.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn count_playlist_tracks(&self, playlist_id: &PlaylistId) -> DbResult<u64> {
+        let id_str = playlist_id.0.to_string();
+        let playlist = self
+            .get_playlist(playlist_id)
+            .await?
+            .ok_or_else(|| DbError::NotFound(format!("playlist {id_str}")))?;
+
+        match (&playlist.kind, &playlist.query) {
+            (PlaylistKind::Static, _) => {
+                let count: i64 = sqlx::query_scalar(
+                    r"SELECT COUNT(*)
+                      FROM playlist_tracks pt
+                      JOIN tracks t ON t.id = pt.track_id
+                      WHERE pt.playlist_id = ?",
+                )
+                .bind(&id_str)
+                .fetch_one(&self.pool)
+                .await?;
+                Ok(count as u64)
+            }
+            (PlaylistKind::Smart, Some(query))
+                if playlist
+                    .limit
+                    .as_ref()
+                    .is_none_or(|l| l.max_duration_secs.is_none()) =>
+            {
+                let (where_clause, bindings) = self
+                    .query_sql(query, std::slice::from_ref(&playlist.id))
+                    .await?;
+                let sql = format!("SELECT COUNT(*) FROM tracks WHERE {where_clause}");
+                let mut count = sqlx::query_scalar::<_, i64>(&sql);
+                for binding in bindings {
+                    count = count.bind(binding);
+                }
+                let count = count.fetch_one(&self.pool).await? as u64;
+                let max_tracks = playlist.limit.as_ref().and_then(|l| l.max_tracks);
+                Ok(max_tracks.map_or(count, |max| count.min(u64::from(max))))
+            }
+            (PlaylistKind::Smart, _) => {
+                Ok(self.evaluate_smart_playlist(&playlist, &[]).await?.len() as u64)
+            }
+        }
+    }
+
+    /// Evaluate a smart playlist query and return matching tracks.
+    ///
+    /// `parents` are the smart playlists whose evaluation refers to this
+    /// one, if any.
+    async fn evaluate_smart_playlist(
+        &self,
+        playlist: &Playlist,
+        parents: &[PlaylistId],
+    ) -> DbResult<Vec<Track>> {
+        let query = playlist
+            .query
+            .as_ref()
+            .ok_or_else(|| DbError::InvalidData("Smart playlist has no query".to_string()))?;
+
+        let max_tracks = playlist.limit.as_ref().and_then(|l| l.max_tracks);
+        let parents = [parents, std::slice::from_ref(&playlist.id)].concat();
+        let mut tracks = self
+            .select_tracks(query, &playlist.effective_sort(), max_tracks, 0, &parents)
+            .await?;
+
+        // Apply max_duration_secs limit if set
+        if let Some(limit) = &playlist.limit
+            && let Some(max_secs) = limit.max_duration_secs
+        {
+            let max_ms = max_secs * 1000;
+            let mut total_ms = 0u64;
+            tracks.retain(|track| {
+                let track_ms = track.duration.as_millis() as u64;
+                if total_ms + track_ms <= max_ms {
+                    total_ms += track_ms;
+                    true
+                } else {
+                    false
+                }
+            });
+        }
+
+        Ok(tracks)
+    }
+
+    /// Get tracks matching a query, in sort order, up to `limit` tracks
+    /// after skipping `offset`.
+    ///
+    /// `parents` are the smart playlists being evaluated, as for
+    /// [`Self::query_sql`].
+    async fn select_tracks(
+        &self,
+        query: &apollo_core::query::Query,
+        sort: &SortSpec,
+        limit: Option<u32>,
+        offset: u32,
+        parents: &[PlaylistId],
+    ) -> DbResult<Vec<Track>> {
+        let (where_clause, bindings) = self.query_sql(query, parents).await?;
+        let limit_clause = match (limit, offset) {
+            (Some(n), _) => format!("LIMIT {n} OFFSET {offset}"),
+            (None, 0) => String::new(),
+            (None, _) => format!("LIMIT -1 OFFSET {offset}"),
+        };
+
+        let sql = format!(
+            r"SELECT id, path, title, artist, album_artist, album_id, album_title,
+                     track_number, track_total, disc_number, disc_total, year,
+                     genres, duration_ms, bitrate, sample_rate, bit_depth, channels, format,
+                     musicbrainz_id, acoustid, added_at, modified_at, file_hash,
+                     isrc, rg_track_gain, rg_track_peak, rg_album_gain, rg_album_peak, loudness_lufs
+              FROM tracks
+              WHERE {where_clause}
+              ORDER BY {}
+              {limit_clause}",
+            sort_to_sql(sort)
+        );
+
+        let mut query = sqlx::query(&sql);
+        for binding in bindings {
+            query = query.bind(binding);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_track).collect()
+    }
+
+    /// Convert a query to a SQL WHERE clause, with the playlists that it
+    /// refers to looked up.
+    ///
+    /// `parents` are the smart playlists being evaluated that led to the
+    /// query. A playlist that refers back to one of them, or to a playlist
+    /// that doesn't exist, matches no tracks.
+    fn query_sql<'a>(
+        &'a self,
+        query: &'a apollo_core::query::Query,
+        parents: &'a [PlaylistId],
+    ) -> BoxFuture<'a, DbResult<(String, Vec<String>)>> {
+        // Boxed, since smart playlists are evaluated with their own queries
+        Box::pin(async move {
+            let mut playlists = HashMap::new();
+            for name in query.playlists() {
+                let key = name.to_lowercase();
+                if playlists.contains_key(&key) {
+                    continue;
+                }
+                let clause = match self.find_playlist_by_name(name).await? {
+                    Some(playlist) if !parents.contains(&playlist.id) => {
+                        self.playlist_clause(&playlist, parents).await?
+                    }
+                    _ => ("0 = 1".to_string(), vec![]),
+                };
+                playlists.insert(key, clause);
+            }
+            Ok(query_to_sql(query, &playlists))
+        })
+    }
+
+    /// Get a SQL WHERE clause matching the tracks of a playlist.
+    async fn playlist_clause(
+        &self,
+        playlist: &Playlist,
+        parents: &[PlaylistId],
+    ) -> DbResult<(String, Vec<String>)> {
+        match (&playlist.kind, &playlist.query) {
+            (PlaylistKind::Static, _) => Ok((
+                "tracks.id IN (SELECT track_id FROM playlist_tracks WHERE playlist_id = ?)"
+                    .to_string(),
+                vec![playlist.id.0.to_string()],
+            )),
+            (PlaylistKind::Smart, Some(query)) if playlist.limit.is_none() => {
+                let parents = [parents, std::slice::from_ref(&playlist.id)].concat();
+                self.query_sql(query, &parents).await
+            }
+            (PlaylistKind::Smart, _) => {
+                // Which tracks fit a limit depends on the others, so evaluate it
+                let track_ids: Vec<String> = self
+                    .evaluate_smart_playlist(playlist, parents)
+                    .await?
+                    .iter()
+                    .map(|track| track.id.0.to_string())
+                    .collect();
+                let track_ids = serde_json::to_string(&track_ids)
+                    .map_err(|e| DbError::Serialization(e.to_string()))?;
+                Ok((
+                    "tracks.id IN (SELECT value FROM json_each(?))".to_string(),
+                    vec![track_ids],
+                ))
+            }
+        }
+    }
+
+    /// Find a playlist by its name, ignoring case.
+    ///
+    /// Names are compared lowercased as queries key them, since `NOCASE` in
+    /// `SQLite` only folds ASCII letters. If several playlists have the name,
+    /// the oldest is returned. Track IDs of static playlists are not loaded.
+    async fn find_playlist_by_name(&self, name: &str) -> DbResult<Option<Playlist>> {
+        let key = name.to_lowercase();
+        let rows = sqlx::query(
+            r"SELECT id, name, description, kind, query, sort, max_tracks, max_duration_secs,
+                     created_at, modified_at
+              FROM playlists
+              ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .find(|row| row.get::<String, _>("name").to_lowercase() == key)
+            .map(row_to_playlist)
+            .transpose()
+    }
+}
+
+/// Turn typed text into a full-text query for names with words that start
+/// with each of its words, such as `"bohemian"* "rh"*`.
+///
+/// Each word is quoted, so that characters with a meaning in queries are
+/// matched as they are. Returns `None` if there are no words.
+fn fts_prefix_query(text: &str) -> Option<String> {
+    let query = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!query.is_empty()).then_some(query)
+}
+
+fn row_to_suggestion(row: &sqlx::sqlite::SqliteRow) -> Suggestion {
+    Suggestion {
+        id: row.get("id"),
+        name: row.get("name"),
+        artist: row.get("artist"),
+    }
+}
+
+/// Convert a row of the changes table to a [`Change`].
+fn row_to_change(row: &sqlx::sqlite::SqliteRow) -> DbResult<Change> {
+    let item: String = row.get("item");
+    let item = ChangedItem::from_name(&item)
+        .ok_or_else(|| DbError::InvalidData(format!("unknown changed item: {item}")))?;
+    let kind: String = row.get("kind");
+    let kind = ChangeKind::from_name(&kind)
+        .ok_or_else(|| DbError::InvalidData(format!("unknown change kind: {kind}")))?;
+    let changed_at: String = row.get("changed_at");
+    let changed_at = DateTime::parse_from_rfc3339(&changed_at)
+        .map(|at| at.with_timezone(&Utc))
+        .map_err(|e| DbError::InvalidData(e.to_string()))?;
+
+    Ok(Change {
+        seq: row.get("seq"),
+        item,
+        id: row.get("item_id"),
+        kind,
+        changed_at,
+    })
+}
+
+/// Convert a row of the operations table to an [`Operation`].
+fn row_to_operation(row: &sqlx::sqlite::SqliteRow) -> DbResult<Operation> {
+    let kind: String = row.get("kind");
+    let kind = OperationKind::from_name(&kind)
+        .ok_or_else(|| DbError::InvalidData(format!("unknown operation kind: {kind}")))?;
+    let steps: String = row.get("steps");
+    let steps = serde_json::from_str(&steps).map_err(|e| DbError::Serialization(e.to_string()))?;
+    let parse_time = |value: &str| {
+        DateTime::parse_from_rfc3339(value)
+            .map(|at| at.with_timezone(&Utc))
+            .map_err(|e| DbError::InvalidData(e.to_string()))
+    };
+    let created_at: String = row.get("created_at");
+    let undone_at: Option<String> = row.get("undone_at");
+
+    Ok(Operation {
+        id: row.get("id"),
+        kind,
+        description: row.get("description"),
+        created_at: parse_time(&created_at)?,
+        undone_at: undone_at.as_deref().map(parse_time).transpose()?,
+        steps,
+    })
+}
+
+/// Convert a row of the playlist versions table to a [`PlaylistVersion`].
+fn row_to_playlist_version(row: &sqlx::sqlite::SqliteRow) -> DbResult<PlaylistVersion> {
+    let playlist_id: String = row.get("playlist_id");
+    let playlist_id =
+        Uuid::parse_str(&playlist_id).map_err(|e| DbError::InvalidData(e.to_string()))?;
+    let created_at: String = row.get("created_at");
+    let created_at = DateTime::parse_from_rfc3339(&created_at)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+    let track_ids: String = row.get("track_ids");
+    let track_ids =
+        serde_json::from_str(&track_ids).map_err(|e| DbError::Serialization(e.to_string()))?;
+
+    Ok(PlaylistVersion {
+        id: row.get("id"),
+        playlist_id: PlaylistId(playlist_id),
+        created_at,
+        track_ids,
+    })
+}
+
+fn row_to_import_run(row: &sqlx::sqlite::SqliteRow) -> DbResult<ImportRun> {
+    let json = |column: &str| -> DbResult<serde_json::Value> {
+        serde_json::from_str(row.get(column)).map_err(|e| DbError::Serialization(e.to_string()))
+    };
+    let started_at: String = row.get("started_at");
+    let started_at = DateTime::parse_from_rfc3339(&started_at)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+    let count = |column: &str| row.get::<i64, _>(column) as usize;
+
+    Ok(ImportRun {
+        id: row.get("id"),
+        source_path: PathBuf::from(row.get::<String, _>("source_path")),
+        started_at,
+        duration_ms: row.get::<i64, _>("duration_ms") as u64,
+        options: json("options")?,
+        tracks_found: count("tracks_found"),
+        tracks_imported: count("tracks_imported"),
+        tracks_skipped: count("tracks_skipped"),
+        tracks_duplicate: count("tracks_duplicate"),
+        tracks_failed: count("tracks_failed"),
+        albums_created: count("albums_created"),
+        cancelled: row.get("cancelled"),
+        errors: serde_json::from_value(json("errors")?)
+            .map_err(|e| DbError::Serialization(e.to_string()))?,
+        failed_files: serde_json::from_value(json("failed_files")?)
+            .map_err(|e| DbError::Serialization(e.to_string()))?,
+    })
+}
+
+/// Read the schema version from `PRAGMA user_version`.
+async fn schema_version(pool: &SqlitePool) -> DbResult<u32> {
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(pool)
+        .await?;
+    u32::try_from(version)
+        .map_err(|_| DbError::InvalidData(format!("invalid schema version: {version}")))
+}
+
+/// Check that a static playlist has each of its tracks only once.
+fn ensure_unique_tracks(playlist: &Playlist) -> DbResult<()> {
+    let mut seen = HashSet::new();
+    playlist
+        .track_ids
+        .iter()
+        .find(|id| !seen.insert(*id))
+        .map_or(Ok(()), |track_id| {
+            Err(DbError::InvalidData(format!(
+                "playlist {} has track {track_id} more than once",
+                playlist.id
+            )))
+        })
+}
+
+/// Run `SQLite`'s integrity and foreign key checks.
+async fn integrity_problems(pool: &SqlitePool) -> DbResult<Vec<String>> {
+    let mut problems: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .filter(|result: &String| result != "ok")
+        .collect();
+
+    for row in sqlx::query("PRAGMA foreign_key_check")
+        .fetch_all(pool)
+        .await?
+    {
+        let table: String = row.try_get("table")?;
+        let rowid: Option<i64> = row.try_get("rowid")?;
+        let parent: String = row.try_get("parent")?;
+        problems.push(format!(
+            "row {} of {table} refers to a missing row of {parent}",
+            rowid.map_or_else(|| "?".to_string(), |rowid| rowid.to_string())
+        ));
+    }
+
+    Ok(problems)
+}
+
+/// Convert a Query to a SQL WHERE clause.
+///
+/// `playlists` has the clauses for the playlists that the query refers to,
+/// by lowercase name; others match no tracks.
+fn query_to_sql(
+    query: &apollo_core::query::Query,
+    playlists: &HashMap<String, (String, Vec<String>)>,
+) -> (String, Vec<String>) {
+    use apollo_core::query::{CompareOp, Field, Query};
+
+    match query {
+        Query::All => ("1 = 1".to_string(), vec![]),
+        Query::Text(text) => {
+            let pattern = format!("%{text}%");
+            (
+                "(title LIKE ? OR artist LIKE ? OR album_title LIKE ?)".to_string(),
+                vec![pattern.clone(), pattern.clone(), pattern],
+            )
+        }
+        Query::Field { field, value } => {
+            let column = field_column(*field);
+
+            if field.is_numeric() && *field != Field::Year {
+                // Other numeric fields use exact match
+                compare_to_sql(*field, CompareOp::Eq, value.clone())
+            } else if *field == Field::Format {
+                // Formats are stored lowercase; "lossless" and "lossy" match groups
+                match value.to_lowercase().as_str() {
+                    "lossless" => (format!("{column} IN ('flac', 'wav', 'aiff')"), vec![]),
+                    "lossy" => (format!("{column} IN ('mp3', 'ogg', 'opus', 'aac')"), vec![]),
+                    format => (format!("{column} = ?"), vec![format.to_string()]),
+                }
+            } else if *field == Field::Genre {
+                // Genres are stored as JSON array
+                let pattern = format!("%\"{value}\"%");
+                (format!("{column} LIKE ?"), vec![pattern])
+            } else if *field == Field::Path {
+                // Path uses prefix matching
+                let pattern = format!("{value}%");
+                (format!("{column} LIKE ?"), vec![pattern])
+            } else if *field == Field::Year {
+                // Year uses exact match
+                (format!("{column} = ?"), vec![value.clone()])
+            } else {
+                // Other fields use LIKE for partial matching
+                let pattern = format!("%{value}%");
+                (format!("{column} LIKE ?"), vec![pattern])
+            }
+        }
+        Query::YearRange { start, end } => (
+            "year BETWEEN ? AND ?".to_string(),
+            vec![start.to_string(), end.to_string()],
+        ),
+        Query::Empty { field } => {
+            let column = field_column(*field);
+            let clause = if *field == Field::Genre {
+                format!("({column} IS NULL OR {column} IN ('', '[]'))")
+            } else if field.is_numeric() {
+                format!("({column} IS NULL OR {column} = 0)")
+            } else {
+                format!("({column} IS NULL OR {column} = '')")
+            };
+            (clause, vec![])
+        }
+        Query::Regex { field, pattern } => match_to_sql(*field, "REGEXP ?", pattern.clone()),
+        Query::Exact { field, value } => match_to_sql(*field, "= ? COLLATE NOCASE", value.clone()),
+        Query::Compare { field, op, value } => compare_to_sql(*field, *op, value.to_string()),
+        Query::DateRange { field, start, end } => date_range_to_sql(*field, *start, *end),
+        Query::And(queries) => {
+            let mut clauses = Vec::new();
+            let mut all_bindings = Vec::new();
+            for q in queries {
+                let (clause, bindings) = query_to_sql(q, playlists);
+                clauses.push(format!("({clause})"));
+                all_bindings.extend(bindings);
+            }
+            (clauses.join(" AND "), all_bindings)
+        }
+        Query::Or(queries) => {
+            let mut clauses = Vec::new();
+            let mut all_bindings = Vec::new();
+            for q in queries {
+                let (clause, bindings) = query_to_sql(q, playlists);
+                clauses.push(format!("({clause})"));
+                all_bindings.extend(bindings);
+            }
+            (clauses.join(" OR "), all_bindings)
+        }
+        Query::Not(inner) => {
+            let (clause, bindings) = query_to_sql(inner, playlists);
+            // Treat NULL as a non-match so negating a missing field matches
+            (format!("NOT COALESCE(({clause}), 0)"), bindings)
+        }
+        Query::Playlist { name } => playlists
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| ("0 = 1".to_string(), vec![])),
+    }
+}
+
+/// SQL expression for when a track was last played.
+const LAST_PLAYED_COLUMN: &str = "(SELECT MAX(played_at) FROM plays WHERE track_id = tracks.id)";
+
+/// Convert a sort specification to a SQL ORDER BY clause.
+///
+/// An empty spec sorts by artist.
+fn sort_to_sql(spec: &SortSpec) -> String {
+    use apollo_core::query::SortField;
+
+    if spec.is_empty() {
+        return sort_to_sql(&SortSpec::from(PlaylistSort::Artist));
+    }
+
+    spec.keys
+        .iter()
+        .map(|key| {
+            let column = match key.field {
+                SortField::Artist => "artist",
+                SortField::AlbumArtist => "album_artist",
+                SortField::Album => "album_title",
+                SortField::Title => "title",
+                SortField::Year => "year",
+                SortField::Track => "track_number",
+                SortField::Disc => "disc_number",
+                SortField::Duration => "duration_ms",
+                SortField::Bitrate => "bitrate",
+                SortField::Path => "path",
+                SortField::Added => "added_at",
+                SortField::Modified => "modified_at",
+                SortField::Rating => field_column(apollo_core::query::Field::Rating),
+                SortField::Plays => field_column(apollo_core::query::Field::Plays),
+                SortField::LastPlayed => LAST_PLAYED_COLUMN,
+                SortField::Random => return "RANDOM()".to_string(),
+            };
+            let direction = if key.descending { "DESC" } else { "ASC" };
+            format!("{column} {direction}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Get the SQL column expression for a query field.
+///
+/// Durations are expressed in whole seconds.
+const fn field_column(field: apollo_core::query::Field) -> &'static str {
+    use apollo_core::query::Field;
+
+    match field {
+        Field::Artist => "artist",
+        Field::AlbumArtist => "album_artist",
+        Field::Album => "album_title",
+        Field::Title => "title",
+        Field::Year => "year",
+        Field::Genre => "genres",
+        Field::Path => "path",
+        Field::Duration => "duration_ms / 1000",
+        Field::Bitrate => "bitrate",
+        Field::Track => "track_number",
+        Field::Format => "format",
+        Field::SampleRate => "sample_rate",
+        Field::Channels => "channels",
+        Field::Rating => "(SELECT rating FROM track_ratings WHERE track_id = tracks.id)",
+        Field::Plays => "(SELECT COUNT(*) FROM plays WHERE track_id = tracks.id)",
+        Field::Favorite => {
+            "COALESCE((SELECT favorite FROM track_ratings WHERE track_id = tracks.id), 0)"
+        }
+        Field::AlbumType => "(SELECT album_type FROM albums WHERE albums.id = tracks.album_id)",
+        Field::Country => "(SELECT country FROM albums WHERE albums.id = tracks.album_id)",
+        Field::Label => "(SELECT label FROM albums WHERE albums.id = tracks.album_id)",
+        Field::CatalogNumber => {
+            "(SELECT catalog_number FROM albums WHERE albums.id = tracks.album_id)"
+        }
+        Field::Compilation => {
+            "COALESCE((SELECT is_compilation FROM albums WHERE albums.id = tracks.album_id), 0)"
+        }
+    }
+}
+
+/// Convert a regex or exact field match to a SQL WHERE clause.
+///
+/// Genres are matched individually against each entry of the JSON array.
+fn match_to_sql(
+    field: apollo_core::query::Field,
+    condition: &str,
+    value: String,
+) -> (String, Vec<String>) {
+    let column = field_column(field);
+    let clause = if field == apollo_core::query::Field::Genre {
+        format!("EXISTS (SELECT 1 FROM json_each({column}) WHERE value {condition})")
+    } else {
+        // NULL values would make REGEXP fail
+        format!("COALESCE({column}, '') {condition}")
+    };
+    (clause, vec![value])
+}
+
+/// Convert a date range to a SQL WHERE clause.
+///
+/// Timestamps are stored as RFC3339 in UTC, so they compare as strings.
+fn date_range_to_sql(
+    field: apollo_core::query::DateField,
+    start: Option<apollo_core::query::DateBound>,
+    end: Option<apollo_core::query::DateBound>,
+) -> (String, Vec<String>) {
+    use apollo_core::query::DateField;
+
+    let column = match field {
+        DateField::Added => "added_at",
+        DateField::Modified => "modified_at",
+        DateField::LastPlayed => LAST_PLAYED_COLUMN,
+        DateField::Released => {
+            "(SELECT release_date FROM albums WHERE albums.id = tracks.album_id)"
+        }
+    };
+    // Release dates are stored as plain dates, timestamps as RFC 3339
+    let format = |bound: apollo_core::query::DateBound| {
+        let time = bound.resolve(Utc::now());
+        if field == DateField::Released {
+            time.date_naive().to_string()
+        } else {
+            time.to_rfc3339()
+        }
+    };
+    let mut clauses = Vec::new();
+    let mut bindings = Vec::new();
+    if let Some(start) = start {
+        clauses.push(format!("{column} >= ?"));
+        bindings.push(format(start));
+    }
+    if let Some(end) = end {
+        clauses.push(format!("{column} < ?"));
+        bindings.push(format(end));
+    }
+    if clauses.is_empty() {
+        ("1 = 1".to_string(), bindings)
+    } else {
+        (clauses.join(" AND "), bindings)
+    }
+}
+
+/// Convert a numeric field comparison to a SQL WHERE clause.
+fn compare_to_sql(
+    field: apollo_core::query::Field,
+    op: apollo_core::query::CompareOp,
+    value: String,
+) -> (String, Vec<String>) {
+    let column = field_column(field);
+    // Bindings are strings, and expressions have no type affinity to convert them
+    (
+        format!("{column} {} CAST(? AS INTEGER)", op.symbol()),
+        vec![value],
+    )
+}
+
+/// Convert a database row to a Playlist.
+fn row_to_playlist(row: &sqlx::sqlite::SqliteRow) -> DbResult<Playlist> {
+    let id_str: String = row.get("id");
+    let id = Uuid::parse_str(&id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+
+    let kind_str: String = row.get("kind");
+    let kind = match kind_str.as_str() {
+        "static" => PlaylistKind::Static,
+        "smart" => PlaylistKind::Smart,
+        _ => {
+            return Err(DbError::InvalidData(format!(
+                "Unknown playlist kind: {kind_str}"
+            )));
+        }
+    };
+
+    let query_json: Option<String> = row.get("query");
+    let query = query_json
+        .map(|s| serde_json::from_str(&s))
+        .transpose()
+        .map_err(|e| DbError::Serialization(e.to_string()))?;
+
+    let sort_str: String = row.get("sort");
+    let (sort, sort_spec) = if sort_str.starts_with("sort:") {
+        let spec = SortSpec::parse(&sort_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+        (PlaylistSort::default(), Some(spec))
+    } else {
+        (parse_playlist_sort(&sort_str), None)
+    };
+
+    let max_tracks: Option<i32> = row.get("max_tracks");
+    let max_duration_secs: Option<i64> = row.get("max_duration_secs");
+    let limit = if max_tracks.is_some() || max_duration_secs.is_some() {
+        Some(PlaylistLimit {
+            max_tracks: max_tracks.map(|n| n as u32),
+            max_duration_secs: max_duration_secs.map(|n| n as u64),
+        })
+    } else {
+        None
+    };
+
+    let created_at_str: String = row.get("created_at");
+    let created_at = DateTime::parse_from_rfc3339(&created_at_str)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+
+    let modified_at_str: String = row.get("modified_at");
+    let modified_at = DateTime::parse_from_rfc3339(&modified_at_str)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+
+    Ok(Playlist {
+        id: PlaylistId(id),
+        name: row.get("name"),
+        description: row.get("description"),
+        kind,
+        query,
+        sort,
+        sort_spec,
+        limit,
+        track_ids: Vec::new(), // Loaded separately
+        created_at,
+        modified_at,
+    })
+}
+
+/// Convert a playlist's sort order to its stored form.
+///
+/// Custom sort specs are stored in the same column as presets, in their
+/// `sort:` query form.
+fn playlist_sort_to_str(playlist: &Playlist) -> String {
+    playlist.sort_spec.as_ref().map_or_else(
+        || format!("{:?}", playlist.sort).to_lowercase(),
+        ToString::to_string,
+    )
+}
+
+/// Parse playlist sort from string.
+fn parse_playlist_sort(s: &str) -> PlaylistSort {
+    match s.to_lowercase().as_str() {
+        "album" => PlaylistSort::Album,
+        "title" => PlaylistSort::Title,
+        "addeddesc" => PlaylistSort::AddedDesc,
+        "addedasc" => PlaylistSort::AddedAsc,
+        "yeardesc" => PlaylistSort::YearDesc,
+        "yearasc" => PlaylistSort::YearAsc,
+        "random" => PlaylistSort::Random,
+        // Default to Artist for "artist" and any unknown values
+        _ => PlaylistSort::Artist,
+    }
+}
+
+/// Parse a comma-separated list of field names, skipping unknown ones.
+fn parse_fields(names: &str) -> Vec<EditField> {
+    names
+        .split(',')
+        .filter_map(|name| name.parse().ok())
+        .collect()
+}
+
+/// Convert a database row to a Track.
+fn row_to_track(row: &sqlx::sqlite::SqliteRow) -> DbResult<Track> {
+    let id_str: String = row.get("id");
+    let id = Uuid::parse_str(&id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+
+    let path_str: String = row.get("path");
+    let album_id_str: Option<String> = row.get("album_id");
+    let album_id = album_id_str
+        .map(|s| Uuid::parse_str(&s).map(AlbumId))
+        .transpose()
+        .map_err(|e| DbError::InvalidData(e.to_string()))?;
+
+    let genres_json: String = row.get("genres");
+    let genres: Vec<String> =
+        serde_json::from_str(&genres_json).map_err(|e| DbError::Serialization(e.to_string()))?;
+
+    let duration_ms: i64 = row.get("duration_ms");
+    let format_str: String = row.get("format");
+    let format = parse_audio_format(&format_str);
+
+    let added_at_str: String = row.get("added_at");
+    let added_at = DateTime::parse_from_rfc3339(&added_at_str)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+
+    let modified_at_str: String = row.get("modified_at");
+    let modified_at = DateTime::parse_from_rfc3339(&modified_at_str)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+
+    Ok(Track {
+        id: TrackId(id),
+        path: PathBuf::from(path_str),
+        title: row.get("title"),
+        artist: row.get("artist"),
+        album_artist: row.get("album_artist"),
+        album_id,
+        album_title: row.get("album_title"),
+        track_number: row.get::<Option<i32>, _>("track_number").map(|n| n as u32),
+        track_total: row.get::<Option<i32>, _>("track_total").map(|n| n as u32),
+        disc_number: row.get::<Option<i32>, _>("disc_number").map(|n| n as u32),
+        disc_total: row.get::<Option<i32>, _>("disc_total").map(|n| n as u32),
+        year: row.get("year"),
+        genres,
+        duration: Duration::from_millis(duration_ms as u64),
+        bitrate: row.get::<Option<i32>, _>("bitrate").map(|n| n as u32),
+        sample_rate: row.get::<Option<i32>, _>("sample_rate").map(|n| n as u32),
+        bit_depth: row.get::<Option<i32>, _>("bit_depth").map(|n| n as u8),
+        channels: row.get::<Option<i32>, _>("channels").map(|n| n as u8),
+        format,
+        musicbrainz_id: row.get("musicbrainz_id"),
+        acoustid: row.get("acoustid"),
+        isrc: row.get("isrc"),
+        rg_track_gain: row.get("rg_track_gain"),
+        rg_track_peak: row.get("rg_track_peak"),
+        rg_album_gain: row.get("rg_album_gain"),
+        rg_album_peak: row.get("rg_album_peak"),
+        loudness_lufs: row.get("loudness_lufs"),
+        added_at,
+        modified_at,
+        file_hash: row.get("file_hash"),
+    })
+}
+
+/// Convert a database row to an Album.
+fn row_to_album(row: &sqlx::sqlite::SqliteRow) -> DbResult<Album> {
+    let id_str: String = row.get("id");
+    let id = Uuid::parse_str(&id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+
+    let genres_json: String = row.get("genres");
+    let genres: Vec<String> =
+        serde_json::from_str(&genres_json).map_err(|e| DbError::Serialization(e.to_string()))?;
+
+    let added_at_str: String = row.get("added_at");
+    let added_at = DateTime::parse_from_rfc3339(&added_at_str)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+
+    let modified_at_str: String = row.get("modified_at");
+    let modified_at = DateTime::parse_from_rfc3339(&modified_at_str)
+        .map_err(|e| DbError::InvalidData(e.to_string()))?
+        .with_timezone(&Utc);
+
+    let album_type: Option<String> = row.get("album_type");
+    let media: Option<String> = row.get("media");
+    let release_date: Option<String> = row.get("release_date");
+    let release_date = release_date
+        .map(|d| NaiveDate::from_str(&d).map_err(|e| DbError::InvalidData(e.to_string())))
+        .transpose()?;
+
+    Ok(Album {
+        id: AlbumId(id),
+        title: row.get("title"),
+        artist: row.get("artist"),
+        year: row.get("year"),
+        genres,
+        track_count: row.get::<i32, _>("track_count") as u32,
+        disc_count: row.get::<i32, _>("disc_count") as u32,
+        musicbrainz_id: row.get("musicbrainz_id"),
+        album_type: album_type.as_deref().and_then(AlbumType::from_name),
+        release_date,
+        country: row.get("country"),
+        label: row.get("label"),
+        catalog_number: row.get("catalog_number"),
+        is_compilation: row.get("is_compilation"),
+        disambiguation: row.get("disambiguation"),
+        media: media.as_deref().and_then(Media::from_name),
+        edition: row.get("edition"),
+        barcode: row.get("barcode"),
+        discogs_id: row.get::<Option<i64>, _>("discogs_id").map(|id| id as u64),
+        rg_album_gain: row.get("rg_album_gain"),
+        rg_album_peak: row.get("rg_album_peak"),
+        loudness_lufs: row.get("loudness_lufs"),
+        added_at,
+        modified_at,
+    })
+}
+
+/// Parse audio format from string.
+fn parse_audio_format(s: &str) -> AudioFormat {
+    match s.to_lowercase().as_str() {
+        "mp3" => AudioFormat::Mp3,
+        "flac" => AudioFormat::Flac,
+        "ogg" => AudioFormat::Ogg,
+        "opus" => AudioFormat::Opus,
+        "aac" => AudioFormat::Aac,
+        "wav" => AudioFormat::Wav,
+        "aiff" => AudioFormat::Aiff,
+        _ => AudioFormat::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::TrackDiff;
+
+    #[tokio::test]
+    async fn test_in_memory_database() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Should start empty
+        assert_eq!(db.count_tracks().await.unwrap(), 0);
+        assert_eq!(db.count_albums().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_track_crud() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Create a track
+        let track = Track::new(
+            PathBuf::from("/music/test.mp3"),
+            "Test Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+
+        // Add the track
+        let id = db.add_track(&track).await.unwrap();
+        assert_eq!(id, track.id);
+
+        // Retrieve the track
+        let retrieved = db.get_track(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.title, "Test Song");
+        assert_eq!(retrieved.artist, "Test Artist");
+        assert_eq!(retrieved.duration.as_secs(), 180);
+
+        // Update the track
+        let mut updated_track = retrieved;
+        updated_track.title = "Updated Song".to_string();
+        db.update_track(&updated_track).await.unwrap();
+
+        // Verify update
+        let retrieved = db.get_track(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.title, "Updated Song");
+
+        // Remove the track
+        db.remove_track(&id).await.unwrap();
+
+        // Verify removal
+        let retrieved = db.get_track(&id).await.unwrap();
+        assert!(retrieved.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_album_crud() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Create an album
+        let mut album = Album::new("Test Album".to_string(), "Test Artist".to_string());
+        album.year = Some(2023);
+        album.track_count = 10;
+
+        // Add the album
+        let id = db.add_album(&album).await.unwrap();
+        assert_eq!(id, album.id);
+
+        // Retrieve the album
+        let retrieved = db.get_album(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.title, "Test Album");
+        assert_eq!(retrieved.artist, "Test Artist");
+        assert_eq!(retrieved.year, Some(2023));
+        assert_eq!(retrieved.track_count, 10);
+
+        // Update the album
+        let mut updated_album = retrieved;
+        updated_album.title = "Updated Album".to_string();
+        db.update_album(&updated_album).await.unwrap();
+
+        // Verify update
+        let retrieved = db.get_album(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.title, "Updated Album");
+
+        // Remove the album
+        db.remove_album(&id).await.unwrap();
+
+        // Verify removal
+        let retrieved = db.get_album(&id).await.unwrap();
+        assert!(retrieved.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_change_events() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut events = db.events().subscribe();
+
+        let track = Track::new(
+            PathBuf::from("/music/test.mp3"),
+            "Test Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        db.set_track_rating(&track.id, Some(4)).await.unwrap();
+        let playlist = Playlist::new_static("Mix");
+        db.add_playlist(&playlist).await.unwrap();
+        db.add_track_to_playlist(&playlist.id, &track.id)
+            .await
+            .unwrap();
+        db.remove_track(&track.id).await.unwrap();
+
+        // Failed changes are not announced
+        assert!(db.remove_track(&track.id).await.is_err());
+
+        let mut names = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            names.push(event.name());
+        }
+        assert_eq!(
+            names,
+            vec![
+                "track.added",
+                "track.rated",
+                "playlist.created",
+                "playlist.changed",
+                "track.removed"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replaygain_roundtrip() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let mut track = Track::new(
+            PathBuf::from("/music/loud.flac"),
+            "Loud Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        track.rg_track_gain = Some(-7.85);
+        track.rg_track_peak = Some(0.988_547);
+        db.add_track(&track).await.unwrap();
+
+        track.rg_album_gain = Some(-8.12);
+        track.loudness_lufs = Some(-10.15);
+        db.update_track(&track).await.unwrap();
+
+        let retrieved = db.get_track(&track.id).await.unwrap().unwrap();
+        assert_eq!(retrieved.rg_track_gain, Some(-7.85));
+        assert_eq!(retrieved.rg_track_peak, Some(0.988_547));
+        assert_eq!(retrieved.rg_album_gain, Some(-8.12));
+        assert_eq!(retrieved.rg_album_peak, None);
+        assert_eq!(retrieved.loudness_lufs, Some(-10.15));
+
+        let mut album = Album::new("Loud Album".to_string(), "Test Artist".to_string());
+        album.rg_album_gain = Some(-8.12);
+        album.rg_album_peak = Some(1.0);
+        db.add_album(&album).await.unwrap();
+
+        let retrieved = db.get_album(&album.id).await.unwrap().unwrap();
+        assert_eq!(retrieved.rg_album_gain, Some(-8.12));
+        assert_eq!(retrieved.rg_album_peak, Some(1.0));
+        assert_eq!(retrieved.loudness_lufs, None);
+    }
+
+    #[tokio::test]
+    async fn test_missing_tracks() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let track = Track::new(
+            PathBuf::from("/music/gone.mp3"),
+            "Gone".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        assert!(db.list_missing_tracks().await.unwrap().is_empty());
+
+        db.set_track_missing(&track.id, true).await.unwrap();
+        let missing = db.list_missing_tracks().await.unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].0.id, track.id);
+        let since = missing[0].1;
+
+        // Marking again keeps the original time
+        db.set_track_missing(&track.id, true).await.unwrap();
+        assert_eq!(db.list_missing_tracks().await.unwrap()[0].1, since);
+
+        db.set_track_missing(&track.id, false).await.unwrap();
+        assert!(db.list_missing_tracks().await.unwrap().is_empty());
+
+        assert!(db.set_track_missing(&TrackId::new(), true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pending_writes() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let track = Track::new(
+            PathBuf::from("/music/edited.mp3"),
+            "Edited".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        assert!(db.list_pending_writes().await.unwrap().is_empty());
+
+        db.queue_write(&track.id, &[EditField::Genre])
+            .await
+            .unwrap();
+        db.queue_write(&track.id, &[EditField::Year, EditField::Genre])
+            .await
+            .unwrap();
+        let pending = db.list_pending_writes().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0.id, track.id);
+        assert_eq!(pending[0].1, vec![EditField::Genre, EditField::Year]);
+
+        db.remove_pending_write(&track.id).await.unwrap();
+        assert!(db.list_pending_writes().await.unwrap().is_empty());
+
+        // Removing a track drops its queued write
+        db.queue_write(&track.id, &[]).await.unwrap();
+        db.remove_track(&track.id).await.unwrap();
+        assert!(db.list_pending_writes().await.unwrap().is_empty());
+
+        assert!(db.queue_write(&TrackId::new(), &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queued_scrobbles() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let track = Track::new(
+            PathBuf::from("/music/played.mp3"),
+            "Played".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        let played_at = Utc::now();
+        db.queue_scrobble(&track.id, "listenbrainz", played_at)
+            .await
+            .unwrap();
+        db.queue_scrobble(&track.id, "lastfm", played_at - chrono::TimeDelta::hours(1))
+            .await
+            .unwrap();
+
+        let queued = db.list_scrobbles().await.unwrap();
+        assert_eq!(queued.len(), 2);
+        assert_eq!(queued[0].service, "lastfm");
+        assert_eq!(queued[0].track.id, track.id);
+        assert_eq!(queued[1].played_at.timestamp(), played_at.timestamp());
+        assert_eq!(queued[1].attempts, 0);
+
+        db.fail_scrobble(queued[0].id, "offline").await.unwrap();
+        db.remove_scrobble(queued[1].id).await.unwrap();
+        let queued = db.list_scrobbles().await.unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].attempts, 1);
+        assert_eq!(queued[0].last_error.as_deref(), Some("offline"));
+
+        // Removing a track drops its queued plays
+        db.remove_track(&track.id).await.unwrap();
+        assert!(db.list_scrobbles().await.unwrap().is_empty());
+        assert!(
+            db.queue_scrobble(&TrackId::new(), "lastfm", played_at)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_operation_log() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let old = Track::new(
+            PathBuf::from("/incoming/moved.mp3"),
+            "Moved".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let mut new = old.clone();
+        new.path = PathBuf::from("/music/Test Artist/Moved.mp3");
+
+        let mut organize = Operation::new(OperationKind::Organize, "organize 1 track");
+        organize.record_file(&old.path, &new.path, true);
+        organize.record_track(&old, &new, false);
+        let organize_id = db.record_operation(&organize).await.unwrap();
+        let tag = Operation::new(OperationKind::Tag, "tag year=2000");
+        let tag_id = db.record_operation(&tag).await.unwrap();
+
+        let operations = db.list_operations(10).await.unwrap();
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].id, tag_id);
+        assert_eq!(operations[1].kind, OperationKind::Organize);
+        assert_eq!(operations[1].steps, organize.steps);
+        assert_eq!(db.list_operations(1).await.unwrap().len(), 1);
+
+        db.mark_operation_undone(organize_id, Utc::now())
+            .await
+            .unwrap();
+        let undone = db.get_operation(organize_id).await.unwrap().unwrap();
+        assert!(undone.is_undone());
+        assert!(db.get_operation(organize_id + 100).await.unwrap().is_none());
+        assert!(db.mark_operation_undone(100, Utc::now()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_history() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        assert!(db.list_import_runs(10).await.unwrap().is_empty());
+
+        let mut run = ImportRun {
+            id: 0,
+            source_path: PathBuf::from("/incoming/album"),
+            started_at: Utc::now(),
+            duration_ms: 1200,
+            options: serde_json::json!({"auto_tag": true}),
+            tracks_found: 3,
+            tracks_imported: 2,
+            tracks_skipped: 0,
+            tracks_duplicate: 0,
+            tracks_failed: 1,
+            albums_created: 1,
+            cancelled: false,
+            errors: vec!["Failed to import 03.mp3".to_string()],
+            failed_files: vec![PathBuf::from("/incoming/album/03.mp3")],
+        };
+        let first = db.record_import_run(&run).await.unwrap();
+        run.source_path = PathBuf::from("/incoming/other");
+        run.cancelled = true;
+        let second = db.record_import_run(&run).await.unwrap();
+
+        let runs = db.list_import_runs(10).await.unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, second);
+        assert!(runs[0].cancelled);
+        assert_eq!(runs[1].id, first);
+        assert_eq!(runs[1].source_path, PathBuf::from("/incoming/album"));
+        assert_eq!(runs[1].options, run.options);
+        assert_eq!(runs[1].failed_files, run.failed_files);
+        assert_eq!(runs[1].errors, run.errors);
+        assert_eq!(runs[1].tracks_imported, 2);
+        assert_eq!(db.list_import_runs(1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_change_log() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let before = Utc::now() - chrono::Duration::seconds(1);
+        assert_eq!(db.change_seq_at(before).await.unwrap(), 0);
+
+        let mut track = Track::new(
+            PathBuf::from("/music/changed.mp3"),
+            "Changed".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        track.title = "Changed Again".to_string();
+        db.update_track(&track).await.unwrap();
+        let playlist = Playlist::new_static("Mix");
+        db.add_playlist(&playlist).await.unwrap();
+        db.add_track_to_playlist(&playlist.id, &track.id)
+            .await
+            .unwrap();
+
+        let changes = db.list_changes(0).await.unwrap();
+        let id = track.id.0.to_string();
+        assert_eq!(changes[0].item, ChangedItem::Track);
+        assert_eq!(changes[0].id, id);
+        assert_eq!(changes[0].kind, ChangeKind::Created);
+        assert_eq!(changes[1].kind, ChangeKind::Updated);
+        assert!(changes.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+        let last = changes.last().unwrap();
+        assert_eq!(last.item, ChangedItem::Playlist);
+        assert_eq!(last.kind, ChangeKind::Updated);
+        assert_eq!(db.change_seq_at(Utc::now()).await.unwrap(), last.seq);
+
+        // Deleting a playlist does not also log its tracks being removed
+        db.remove_playlist(&playlist.id).await.unwrap();
+        db.remove_track(&track.id).await.unwrap();
+        let changes = db.list_changes(last.seq).await.unwrap();
+        let kinds: Vec<_> = changes
+            .iter()
+            .map(|change| (change.item, change.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ChangedItem::Playlist, ChangeKind::Deleted),
+                (ChangedItem::Track, ChangeKind::Deleted),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_track_path() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let track = Track::new(
+            PathBuf::from("/music/old.mp3"),
+            "Moved".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let other = Track::new(
+            PathBuf::from("/music/other.mp3"),
+            "Other".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        db.add_track(&other).await.unwrap();
+        db.set_track_missing(&track.id, true).await.unwrap();
+
+        let new_path = Path::new("/music/Test Artist/new.mp3");
+        db.set_track_path(&track.id, new_path).await.unwrap();
+        let retrieved = db.get_track_by_path(new_path).await.unwrap().unwrap();
+        assert_eq!(retrieved.id, track.id);
+        assert!(db.list_missing_tracks().await.unwrap().is_empty());
+
+        // Paths stay unique
+        assert!(db.set_track_path(&other.id, new_path).await.is_err());
+        assert!(
+            db.set_track_path(&TrackId::new(), Path::new("/music/x.mp3"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_duplicate_track() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let mut tracks = Vec::new();
+        for name in ["kept.flac", "duplicate.mp3", "other.mp3"] {
+            let track = Track::new(
+                PathBuf::from(format!("/music/{name}")),
+                "Creep".to_string(),
+                "Radiohead".to_string(),
+                Duration::from_mins(4),
+            );
+            db.add_track(&track).await.unwrap();
+            tracks.push(track.id);
+        }
+        let [kept, duplicate, other] = &tracks[..] else {
+            unreachable!()
+        };
+
+        // One playlist with only the duplicate, one with both
+        let only = Playlist::new_static("Only duplicate".to_string());
+        db.add_playlist(&only).await.unwrap();
+        db.add_track_to_playlist(&only.id, duplicate).await.unwrap();
+        db.add_track_to_playlist(&only.id, other).await.unwrap();
+        let both = Playlist::new_static("Both".to_string());
+        db.add_playlist(&both).await.unwrap();
+        db.add_track_to_playlist(&both.id, kept).await.unwrap();
+        db.add_track_to_playlist(&both.id, duplicate).await.unwrap();
+
+        db.record_play(duplicate, Utc::now()).await.unwrap();
+        db.set_track_rating(duplicate, Some(4)).await.unwrap();
+
+        db.merge_duplicate_track(duplicate, kept).await.unwrap();
+
+        assert!(db.get_track(duplicate).await.unwrap().is_none());
+        let ids = |tracks: Vec<Track>| tracks.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(db.get_playlist_tracks(&only.id).await.unwrap()),
+            vec![kept.clone(), other.clone()]
+        );
+        assert_eq!(
+            ids(db.get_playlist_tracks(&both.id).await.unwrap()),
+            vec![kept.clone()]
+        );
+        let stats = db.get_track_stats(kept).await.unwrap();
+        assert_eq!(stats.play_count, 1);
+        assert_eq!(stats.rating, Some(4));
+
+        assert!(db.merge_duplicate_track(other, other).await.is_err());
+        assert!(db.merge_duplicate_track(duplicate, kept).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_album_tracks() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Create an album
+        let album = Album::new("Test Album".to_string(), "Test Artist".to_string());
+        db.add_album(&album).await.unwrap();
+
+        // Create tracks for the album
+        for i in 1..=3 {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/track{i}.mp3")),
+                format!("Track {i}"),
+                "Test Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track.album_id = Some(album.id.clone());
+            track.album_title = Some("Test Album".to_string());
+            track.track_number = Some(i);
+            db.add_track(&track).await.unwrap();
+        }
+
+        // Get album tracks
+        let tracks = db.get_album_tracks(&album.id).await.unwrap();
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[0].title, "Track 1");
+        assert_eq!(tracks[1].title, "Track 2");
+        assert_eq!(tracks[2].title, "Track 3");
+    }
+
+    #[tokio::test]
+    async fn test_get_artist() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut track = Track::new(
+            PathBuf::from("/music/track.mp3"),
+            "Under Pressure".to_string(),
+            "Queen & David Bowie".to_string(),
+            Duration::from_mins(4),
+        );
+        track.album_artist = Some("Queen".to_string());
+        db.add_track(&track).await.unwrap();
+
+        let artist = db
+            .get_artist(&ArtistId::from_name("queen"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(artist.name, "Queen");
+        assert!(db.get_artist(&track.artist_id()).await.unwrap().is_some());
+        assert!(
+            db.get_artist(&ArtistId::from_name("Bowie"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_tracks_and_albums() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Add some tracks
+        for i in 1..=5 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/track{i}.mp3")),
+                format!("Track {i}"),
+                "Test Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            db.add_track(&track).await.unwrap();
+        }
+
+        // Add some albums
+        for i in 1..=3 {
+            let album = Album::new(format!("Album {i}"), "Test Artist".to_string());
+            db.add_album(&album).await.unwrap();
+        }
+
+        // List with pagination
+        let tracks = db.list_tracks(2, 0).await.unwrap();
+        assert_eq!(tracks.len(), 2);
+
+        let tracks = db.list_tracks(10, 3).await.unwrap();
+        assert_eq!(tracks.len(), 2); // Only 2 remaining after offset 3
+
+        let albums = db.list_albums(10, 0).await.unwrap();
+        assert_eq!(albums.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_static_playlist_crud() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Create a static playlist
+        let playlist = Playlist::new_static("My Favorites").with_description("My favorite songs");
+
+        // Add the playlist
+        let id = db.add_playlist(&playlist).await.unwrap();
+        assert_eq!(id, playlist.id);
+
+        // Retrieve the playlist
+        let retrieved = db.get_playlist(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.name, "My Favorites");
+        assert_eq!(retrieved.description, Some("My favorite songs".to_string()));
+        assert!(retrieved.is_static());
+
+        // Update the playlist
+        let mut updated_playlist = retrieved;
+        updated_playlist.name = "Updated Favorites".to_string();
+        db.update_playlist(&updated_playlist).await.unwrap();
+
+        // Verify update
+        let retrieved = db.get_playlist(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.name, "Updated Favorites");
+
+        // Remove the playlist
+        db.remove_playlist(&id).await.unwrap();
+
+        // Verify removal
+        let retrieved = db.get_playlist(&id).await.unwrap();
+        assert!(retrieved.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_smart_playlist_crud() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Create a smart playlist
+        let query = apollo_core::query::Query::parse("artist:Beatles").unwrap();
+        let playlist = Playlist::new_smart("Beatles Songs", query)
+            .with_sort(PlaylistSort::YearDesc)
+            .with_max_tracks(100);
+
+        // Add the playlist
+        let id = db.add_playlist(&playlist).await.unwrap();
+
+        // Retrieve the playlist
+        let retrieved = db.get_playlist(&id).await.unwrap().unwrap();
+        assert_eq!(retrieved.name, "Beatles Songs");
+        assert!(retrieved.is_smart());
+        assert_eq!(retrieved.sort, PlaylistSort::YearDesc);
+        assert!(retrieved.query.is_some());
+        assert!(retrieved.limit.is_some());
+        assert_eq!(retrieved.limit.unwrap().max_tracks, Some(100));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::similar_names)]
+    async fn test_playlist_tracks() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Create some tracks
+        let track1 = Track::new(
+            PathBuf::from("/music/track1.mp3"),
+            "Track 1".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let track2 = Track::new(
+            PathBuf::from("/music/track2.mp3"),
+            "Track 2".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(4),
+        );
+        db.add_track(&track1).await.unwrap();
+        db.add_track(&track2).await.unwrap();
+
+        // Create a static playlist
+        let playlist = Playlist::new_static("Test Playlist");
+        let playlist_id = db.add_playlist(&playlist).await.unwrap();
+
+        // Add tracks to playlist
+        db.add_track_to_playlist(&playlist_id, &track1.id)
+            .await
+            .unwrap();
+        db.add_track_to_playlist(&playlist_id, &track2.id)
+            .await
+            .unwrap();
+
+        // Get playlist tracks
+        let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].title, "Track 1");
+        assert_eq!(tracks[1].title, "Track 2");
+
+        // Remove a track from playlist
+        db.remove_track_from_playlist(&playlist_id, &track1.id)
+            .await
+            .unwrap();
+        let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].title, "Track 2");
+    }
+
+    #[tokio::test]
+    async fn test_playlist_duplicate_tracks() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let mut track_ids = Vec::new();
+        for i in 0..3 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track_ids.push(db.add_track(&track).await.unwrap());
+        }
+
+        let mut playlist = Playlist::new_static("Mix");
+        playlist.track_ids = vec![
+            track_ids[0].clone(),
+            track_ids[1].clone(),
+            track_ids[0].clone(),
+        ];
+        assert!(matches!(
+            db.add_playlist(&playlist).await,
+            Err(DbError::InvalidData(_))
+        ));
+        assert!(db.get_playlist(&playlist.id).await.unwrap().is_none());
+
+        playlist.dedupe();
+        db.add_playlist(&playlist).await.unwrap();
+        assert!(matches!(
+            db.add_track_to_playlist(&playlist.id, &track_ids[0]).await,
+            Err(DbError::InvalidData(_))
+        ));
+        db.add_track_to_playlist(&playlist.id, &track_ids[2])
+            .await
+            .unwrap();
+        let stored = db.get_playlist(&playlist.id).await.unwrap().unwrap();
+        assert_eq!(stored.track_ids, track_ids);
+
+        // Positions left repeated by older versions are numbered again
+        sqlx::query("DROP INDEX idx_playlist_tracks_position")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        for (track_id, position) in track_ids.iter().zip([5, 2, 2]) {
+            sqlx::query("UPDATE playlist_tracks SET position = ? WHERE track_id = ?")
+                .bind(position)
+                .bind(track_id.0.to_string())
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        db.run_migrations().await.unwrap();
+
+        let positions: Vec<i64> = sqlx::query_scalar(
+            "SELECT position FROM playlist_tracks WHERE playlist_id = ? ORDER BY position",
+        )
+        .bind(playlist.id.0.to_string())
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(positions, vec![0, 1, 2]);
+        let stored = db.get_playlist(&playlist.id).await.unwrap().unwrap();
+        assert_eq!(
+            stored.track_ids,
+            vec![
+                track_ids[1].clone(),
+                track_ids[2].clone(),
+                track_ids[0].clone()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_playlist_versions() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        let mut track_ids = Vec::new();
+        for i in 0..4 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track_ids.push(db.add_track(&track).await.unwrap());
+        }
+
+        let mut playlist = Playlist::new_static("Road Trip");
+        playlist.track_ids = track_ids[..3].to_vec();
+        let playlist_id = db.add_playlist(&playlist).await.unwrap();
+        assert!(
+            db.list_playlist_versions(&playlist_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        db.add_track_to_playlist(&playlist_id, &track_ids[3])
+            .await
+            .unwrap();
+        playlist.track_ids.clear();
+        db.update_playlist(&playlist).await.unwrap();
+        // Renaming leaves the tracks, so keeps no version
+        playlist.name = "Road Trip 2".to_string();
+        db.update_playlist(&playlist).await.unwrap();
+
+        let versions = db.list_playlist_versions(&playlist_id).await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].track_ids, track_ids);
+        assert_eq!(versions[1].track_ids, &track_ids[..3]);
+        assert_eq!(versions[0].playlist_id, playlist_id);
+
+        // Tracks removed from the library are left out
+        db.remove_track(&track_ids[0]).await.unwrap();
+        let restored = db
+            .restore_playlist_version(&playlist_id, versions[0].id)
+            .await
+            .unwrap();
+        assert_eq!(restored.track_ids, &track_ids[1..]);
+        assert_eq!(restored.name, "Road Trip 2");
+
+        // Restoring keeps the tracks it replaces
+        db.restore_playlist_version(&playlist_id, versions[1].id)
+            .await
+            .unwrap();
+        let versions = db.list_playlist_versions(&playlist_id).await.unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0].track_ids, &track_ids[1..]);
+        assert_eq!(db.count_playlist_tracks(&playlist_id).await.unwrap(), 2);
+
+        assert!(matches!(
+            db.restore_playlist_version(&playlist_id, 1000).await,
+            Err(DbError::NotFound(_))
+        ));
+        let smart_id = db
+            .add_playlist(&Playlist::new_smart("All", apollo_core::query::Query::All))
+            .await
+            .unwrap();
+        assert!(
+            db.restore_playlist_version(&smart_id, versions[0].id)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_smart_playlist_evaluation() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+
+        // Create tracks with different artists
+        for i in 1..=3 {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/beatles_{i}.mp3")),
+                format!("Song {i}"),
+                "Beatles".to_string(),
+                Duration::from_mins(3),
+            );
+            track.year = Some(1965 + i);
+            db.add_track(&track).await.unwrap();
+        }
+
+        for i in 1..=2 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/stones_{i}.mp3")),
+                format!("Stone Song {i}"),
+                "Rolling Stones".to_string(),
+                Duration::from_secs(200),
+            );
+            db.add_track(&track).await.unwrap();
+        }
+
+        // Create a smart playlist for Beatles
+        let query = apollo_core::query::Query::parse("artist:Beatles").unwrap();
+        let playlist = Playlist::new_smart("Beatles", query).with_sort(PlaylistSort::YearAsc);
+        let playlist_id = db.add_playlist(&playlist).await.unwrap();
+
+        // Evaluate the playlist
+        let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+        assert_eq!(tracks.len(), 3);
+        assert!(tracks.iter().all(|t| t.artist == "Beatles"));
+        // Should be sorted by year ascending
+        assert!(tracks[0].year <= tracks[1].year);
+        assert!(tracks[1].year <= tracks[2].year);
+    }
+
+    #[tokio::test]
+    async fn test_playlist_tracks_page() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut mix = Playlist::new_static("Mix");
+        for (i, secs) in [(1, 100), (2, 300), (3, 100), (4, 100)] {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/{i}.mp3")),
+                format!("Song {i}"),
+                "Beatles".to_string(),
+                Duration::from_secs(secs),
+            );
+            track.year = Some(1960 + i);
+            db.add_track(&track).await.unwrap();
+            mix.track_ids.push(track.id.clone());
+        }
+        let mix_id = db.add_playlist(&mix).await.unwrap();
 
-    let created_at_str: String = row.get("created_at");
-    let created_at = DateTime::parse_from_rfc3339(&created_at_str)
-        .map_err(|e| DbError::InvalidData(e.to_string()))?
-        .with_timezone(&Utc);
+        let page = db.get_playlist_tracks_page(&mix_id, 2, 1).await.unwrap();
+        let titles: Vec<_> = page.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["Song 2", "Song 3"]);
+        assert_eq!(db.count_playlist_tracks(&mix_id).await.unwrap(), 4);
 
-    let modified_at_str: String = row.get("modified_at");
-    let modified_at = DateTime::parse_from_rfc3339(&modified_at_str)
-        .map_err(|e| DbError::InvalidData(e.to_string()))?
-        .with_timezone(&Utc);
+        let query = apollo_core::query::Query::parse("artist:Beatles").unwrap();
+        let mut smart = Playlist::new_smart("Beatles", query).with_sort(PlaylistSort::YearAsc);
+        smart.limit = Some(PlaylistLimit {
+            max_tracks: Some(3),
+            max_duration_secs: None,
+        });
+        let smart_id = db.add_playlist(&smart).await.unwrap();
+        let page = db.get_playlist_tracks_page(&smart_id, 5, 2).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].title, "Song 3");
+        assert_eq!(db.count_playlist_tracks(&smart_id).await.unwrap(), 3);
+
+        // Songs that do not fit the duration are skipped, later ones still fit
+        smart.limit = Some(PlaylistLimit {
+            max_tracks: None,
+            max_duration_secs: Some(300),
+        });
+        db.update_playlist(&smart).await.unwrap();
+        let page = db.get_playlist_tracks_page(&smart_id, 5, 1).await.unwrap();
+        let titles: Vec<_> = page.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["Song 3", "Song 4"]);
+        assert_eq!(db.count_playlist_tracks(&smart_id).await.unwrap(), 3);
+    }
 
-    Ok(Playlist {
-        id: PlaylistId(id),
-        name: row.get("name"),
-        description: row.get("description"),
-        kind,
-        query,
-        sort,
-        limit,
-        track_ids: Vec::new(), // Loaded separately
-        created_at,
-        modified_at,
-    })
-}
+    #[tokio::test]
+    async fn test_smart_playlist_numeric_comparisons() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
 
-/// Parse playlist sort from string.
-fn parse_playlist_sort(s: &str) -> PlaylistSort {
-    match s.to_lowercase().as_str() {
-        "album" => PlaylistSort::Album,
-        "title" => PlaylistSort::Title,
-        "addeddesc" => PlaylistSort::AddedDesc,
-        "addedasc" => PlaylistSort::AddedAsc,
-        "yeardesc" => PlaylistSort::YearDesc,
-        "yearasc" => PlaylistSort::YearAsc,
-        "random" => PlaylistSort::Random,
-        // Default to Artist for "artist" and any unknown values
-        _ => PlaylistSort::Artist,
+        for (i, (year, secs, bitrate)) in [(1985, 180, 128), (1990, 240, 320), (2001, 300, 320)]
+            .into_iter()
+            .enumerate()
+        {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                "Artist".to_string(),
+                Duration::from_secs(secs),
+            );
+            track.year = Some(year);
+            track.bitrate = Some(bitrate);
+            track.track_number = Some(u32::try_from(i).unwrap() + 1);
+            db.add_track(&track).await.unwrap();
+        }
+
+        let cases = [
+            ("year>=1990", 2),
+            ("year<1990", 1),
+            ("duration<240", 1),
+            ("duration>=4:00", 2),
+            ("duration:240", 1),
+            ("bitrate>=320", 2),
+            ("track<3", 2),
+            ("track:3", 1),
+        ];
+
+        for (query_str, expected) in cases {
+            let query = apollo_core::query::Query::parse(query_str).unwrap();
+            let playlist_id = db
+                .add_playlist(&Playlist::new_smart(query_str, query))
+                .await
+                .unwrap();
+            let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+            assert_eq!(tracks.len(), expected, "{query_str}");
+        }
     }
-}
 
-/// Convert a database row to a Track.
-fn row_to_track(row: &sqlx::sqlite::SqliteRow) -> DbResult<Track> {
-    let id_str: String = row.get("id");
-    let id = Uuid::parse_str(&id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+    #[tokio::test]
+    async fn test_smart_playlist_audio_properties() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
 
-    let path_str: String = row.get("path");
-    let album_id_str: Option<String> = row.get("album_id");
-    let album_id = album_id_str
-        .map(|s| Uuid::parse_str(&s).map(AlbumId))
-        .transpose()
-        .map_err(|e| DbError::InvalidData(e.to_string()))?;
+        let files = [
+            (AudioFormat::Flac, 1411, 44_100, 2),
+            (AudioFormat::Flac, 2304, 48_000, 2),
+            (AudioFormat::Mp3, 128, 44_100, 2),
+            (AudioFormat::Ogg, 160, 44_100, 1),
+        ];
+        for (i, (format, bitrate, sample_rate, channels)) in files.into_iter().enumerate() {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/song_{i}")),
+                format!("Song {i}"),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track.format = format;
+            track.bitrate = Some(bitrate);
+            track.sample_rate = Some(sample_rate);
+            track.channels = Some(channels);
+            db.add_track(&track).await.unwrap();
+        }
 
-    let genres_json: String = row.get("genres");
-    let genres: Vec<String> =
-        serde_json::from_str(&genres_json).map_err(|e| DbError::Serialization(e.to_string()))?;
+        let cases = [
+            ("format:flac", 2),
+            ("format:MP3", 1),
+            ("format:lossless", 2),
+            ("format:lossy", 2),
+            ("bitrate<192", 2),
+            ("samplerate:44100", 3),
+            ("samplerate>44100", 1),
+            ("channels:2", 3),
+            ("channels<2", 1),
+        ];
+
+        for (query_str, expected) in cases {
+            let query = apollo_core::query::Query::parse(query_str).unwrap();
+            let playlist_id = db
+                .add_playlist(&Playlist::new_smart(query_str, query))
+                .await
+                .unwrap();
+            let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+            assert_eq!(tracks.len(), expected, "{query_str}");
+        }
+    }
 
-    let duration_ms: i64 = row.get("duration_ms");
-    let format_str: String = row.get("format");
-    let format = parse_audio_format(&format_str);
+    #[tokio::test]
+    async fn test_smart_playlist_regex_and_exact() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
 
-    let added_at_str: String = row.get("added_at");
-    let added_at = DateTime::parse_from_rfc3339(&added_at_str)
-        .map_err(|e| DbError::InvalidData(e.to_string()))?
-        .with_timezone(&Utc);
+        let tracks = [
+            ("The Beatles", "Live at the BBC", "rock"),
+            ("Beatles Tribute", "Live", "pop"),
+            ("Them", "The Angry Young Them", "Rock and Roll"),
+        ];
+        for (i, (artist, album, genre)) in tracks.into_iter().enumerate() {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                artist.to_string(),
+                Duration::from_mins(3),
+            );
+            track.album_title = Some(album.to_string());
+            track.genres = vec![genre.to_string()];
+            db.add_track(&track).await.unwrap();
+        }
 
-    let modified_at_str: String = row.get("modified_at");
-    let modified_at = DateTime::parse_from_rfc3339(&modified_at_str)
-        .map_err(|e| DbError::InvalidData(e.to_string()))?
-        .with_timezone(&Utc);
+        let cases = [
+            (r"artist::^The\s", 1),
+            ("artist::Beatles$", 1),
+            ("artist::(?i)^the", 2),
+            (r#"album:="Live""#, 1),
+            ("album:=live", 1),
+            ("genre:=rock", 1),
+            ("genre::^Rock", 1),
+            ("albumartist::.*", 3),
+        ];
+
+        for (query_str, expected) in cases {
+            let query = apollo_core::query::Query::parse(query_str).unwrap();
+            let playlist_id = db
+                .add_playlist(&Playlist::new_smart(query_str, query))
+                .await
+                .unwrap();
+            let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+            assert_eq!(tracks.len(), expected, "{query_str}");
+        }
+    }
 
-    Ok(Track {
-        id: TrackId(id),
-        path: PathBuf::from(path_str),
-        title: row.get("title"),
-        artist: row.get("artist"),
-        album_artist: row.get("album_artist"),
-        album_id,
-        album_title: row.get("album_title"),
-        track_number: row.get::<Option<i32>, _>("track_number").map(|n| n as u32),
-        track_total: row.get::<Option<i32>, _>("track_total").map(|n| n as u32),
-        disc_number: row.get::<Option<i32>, _>("disc_number").map(|n| n as u32),
-        disc_total: row.get::<Option<i32>, _>("disc_total").map(|n| n as u32),
-        year: row.get("year"),
-        genres,
-        duration: Duration::from_millis(duration_ms as u64),
-        bitrate: row.get::<Option<i32>, _>("bitrate").map(|n| n as u32),
-        sample_rate: row.get::<Option<i32>, _>("sample_rate").map(|n| n as u32),
-        channels: row.get::<Option<i32>, _>("channels").map(|n| n as u8),
-        format,
-        musicbrainz_id: row.get("musicbrainz_id"),
-        acoustid: row.get("acoustid"),
-        added_at,
-        modified_at,
-        file_hash: row.get("file_hash"),
-    })
-}
+    #[tokio::test]
+    async fn test_smart_playlist_empty_and_negated() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
 
-/// Convert a database row to an Album.
-fn row_to_album(row: &sqlx::sqlite::SqliteRow) -> DbResult<Album> {
-    let id_str: String = row.get("id");
-    let id = Uuid::parse_str(&id_str).map_err(|e| DbError::InvalidData(e.to_string()))?;
+        let tracks = [
+            (Some("Album"), Some(1990), vec!["Rock"]),
+            (None, Some(2001), vec!["Podcast"]),
+            (Some(""), None, vec![]),
+        ];
+        for (i, (album, year, genres)) in tracks.into_iter().enumerate() {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track.album_title = album.map(String::from);
+            track.year = year;
+            track.genres = genres.into_iter().map(String::from).collect();
+            db.add_track(&track).await.unwrap();
+        }
 
-    let genres_json: String = row.get("genres");
-    let genres: Vec<String> =
-        serde_json::from_str(&genres_json).map_err(|e| DbError::Serialization(e.to_string()))?;
+        let cases = [
+            ("album:", 2),
+            ("year:", 1),
+            ("genre:", 1),
+            ("-album:", 1),
+            ("-genre:Podcast", 2),
+            ("-album:Album", 2),
+            ("-year>=2000", 2),
+        ];
+
+        for (query_str, expected) in cases {
+            let query = apollo_core::query::Query::parse(query_str).unwrap();
+            let playlist_id = db
+                .add_playlist(&Playlist::new_smart(query_str, query))
+                .await
+                .unwrap();
+            let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+            assert_eq!(tracks.len(), expected, "{query_str}");
+        }
+    }
 
-    let added_at_str: String = row.get("added_at");
-    let added_at = DateTime::parse_from_rfc3339(&added_at_str)
-        .map_err(|e| DbError::InvalidData(e.to_string()))?
-        .with_timezone(&Utc);
+    #[tokio::test]
+    async fn test_track_stats() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/song.mp3"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let id = db.add_track(&track).await.unwrap();
 
-    let modified_at_str: String = row.get("modified_at");
-    let modified_at = DateTime::parse_from_rfc3339(&modified_at_str)
-        .map_err(|e| DbError::InvalidData(e.to_string()))?
-        .with_timezone(&Utc);
+        assert_eq!(
+            db.get_track_stats(&id).await.unwrap(),
+            TrackStats::default()
+        );
 
-    Ok(Album {
-        id: AlbumId(id),
-        title: row.get("title"),
-        artist: row.get("artist"),
-        year: row.get("year"),
-        genres,
-        track_count: row.get::<i32, _>("track_count") as u32,
-        disc_count: row.get::<i32, _>("disc_count") as u32,
-        musicbrainz_id: row.get("musicbrainz_id"),
-        added_at,
-        modified_at,
-    })
-}
+        db.set_track_rating(&id, Some(4)).await.unwrap();
+        db.set_track_favorite(&id, true).await.unwrap();
+        let played_at = Utc::now();
+        db.record_play(&id, played_at - chrono::TimeDelta::days(1))
+            .await
+            .unwrap();
+        db.record_play(&id, played_at).await.unwrap();
+
+        let stats = db.get_track_stats(&id).await.unwrap();
+        assert_eq!(stats.rating, Some(4));
+        assert!(stats.favorite);
+        assert_eq!(stats.play_count, 2);
+        assert_eq!(
+            stats.last_played.map(|dt| dt.timestamp()),
+            Some(played_at.timestamp())
+        );
 
-/// Parse audio format from string.
-fn parse_audio_format(s: &str) -> AudioFormat {
-    match s.to_lowercase().as_str() {
-        "mp3" => AudioFormat::Mp3,
-        "flac" => AudioFormat::Flac,
-        "ogg" => AudioFormat::Ogg,
-        "opus" => AudioFormat::Opus,
-        "aac" => AudioFormat::Aac,
-        "wav" => AudioFormat::Wav,
-        "aiff" => AudioFormat::Aiff,
-        _ => AudioFormat::Unknown,
+        db.set_track_rating(&id, None).await.unwrap();
+        assert_eq!(db.get_track_stats(&id).await.unwrap().rating, None);
+        assert!(db.set_track_rating(&id, Some(6)).await.is_err());
+        assert!(matches!(
+            db.record_play(&TrackId::new(), played_at).await,
+            Err(DbError::NotFound(_))
+        ));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[tokio::test]
-    async fn test_in_memory_database() {
+    async fn test_album_artwork() {
         let db = SqliteLibrary::in_memory().await.unwrap();
+        let album = Album::new("Album".to_string(), "Artist".to_string());
+        let id = db.add_album(&album).await.unwrap();
+        assert_eq!(db.get_album_artwork(&id).await.unwrap(), None);
+        assert_eq!(db.get_album_artwork_modified(&id).await.unwrap(), None);
 
-        // Should start empty
-        assert_eq!(db.count_tracks().await.unwrap(), 0);
-        assert_eq!(db.count_albums().await.unwrap(), 0);
+        let mut artwork = Artwork {
+            data: vec![0xFF, 0xD8, 0xFF, 0xE0],
+            mime_type: "image/jpeg".to_string(),
+            source: Some("https://coverartarchive.org/release/x/front".to_string()),
+        };
+        let before = Utc::now();
+        db.set_album_artwork(&id, &artwork).await.unwrap();
+        assert_eq!(
+            db.get_album_artwork(&id).await.unwrap(),
+            Some(artwork.clone())
+        );
+        let modified = db.get_album_artwork_modified(&id).await.unwrap().unwrap();
+        assert!(modified >= before);
+
+        artwork.data = b"\x89PNG\r\n\x1a\n".to_vec();
+        artwork.mime_type = "image/png".to_string();
+        artwork.source = None;
+        db.set_album_artwork(&id, &artwork).await.unwrap();
+        assert_eq!(
+            db.get_album_artwork(&id).await.unwrap(),
+            Some(artwork.clone())
+        );
+
+        assert!(matches!(
+            db.set_album_artwork(&AlbumId::new(), &artwork).await,
+            Err(DbError::NotFound(_))
+        ));
+        db.remove_album(&id).await.unwrap();
+        assert_eq!(db.get_album_artwork(&id).await.unwrap(), None);
     }
 
     #[tokio::test]
-    async fn test_track_crud() {
+    async fn test_fingerprint() {
         let db = SqliteLibrary::in_memory().await.unwrap();
-
-        // Create a track
         let track = Track::new(
-            PathBuf::from("/music/test.mp3"),
-            "Test Song".to_string(),
-            "Test Artist".to_string(),
-            Duration::from_secs(180),
+            PathBuf::from("/music/song.flac"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
         );
-
-        // Add the track
         let id = db.add_track(&track).await.unwrap();
-        assert_eq!(id, track.id);
-
-        // Retrieve the track
-        let retrieved = db.get_track(&id).await.unwrap().unwrap();
-        assert_eq!(retrieved.title, "Test Song");
-        assert_eq!(retrieved.artist, "Test Artist");
-        assert_eq!(retrieved.duration.as_secs(), 180);
+        assert_eq!(db.get_fingerprint(&id).await.unwrap(), None);
 
-        // Update the track
-        let mut updated_track = retrieved;
-        updated_track.title = "Updated Song".to_string();
-        db.update_track(&updated_track).await.unwrap();
+        let mut fingerprint = Fingerprint {
+            fingerprint: "AQADtMkSJYkSZQmS".to_string(),
+            duration_secs: 180,
+            computed_at: Utc::now(),
+        };
+        db.set_fingerprint(&id, &fingerprint).await.unwrap();
+        assert_eq!(
+            db.get_fingerprint(&id).await.unwrap(),
+            Some(fingerprint.clone())
+        );
 
-        // Verify update
-        let retrieved = db.get_track(&id).await.unwrap().unwrap();
-        assert_eq!(retrieved.title, "Updated Song");
+        fingerprint.fingerprint = "AQADtEmUaEkS".to_string();
+        fingerprint.duration_secs = 179;
+        db.set_fingerprint(&id, &fingerprint).await.unwrap();
+        assert_eq!(
+            db.get_fingerprint(&id).await.unwrap(),
+            Some(fingerprint.clone())
+        );
 
-        // Remove the track
+        assert!(matches!(
+            db.set_fingerprint(&TrackId::new(), &fingerprint).await,
+            Err(DbError::NotFound(_))
+        ));
         db.remove_track(&id).await.unwrap();
-
-        // Verify removal
-        let retrieved = db.get_track(&id).await.unwrap();
-        assert!(retrieved.is_none());
+        assert_eq!(db.get_fingerprint(&id).await.unwrap(), None);
     }
 
     #[tokio::test]
-    async fn test_album_crud() {
+    async fn test_provenance() {
         let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/song.flac"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let id = db.add_track(&track).await.unwrap();
+        assert!(db.get_track_provenance(&id).await.unwrap().is_empty());
+
+        let mut tagged = track.clone();
+        tagged.title = "Song (Live)".to_string();
+        tagged.year = Some(1999);
+        let diff = TrackDiff::between(&track, &tagged);
+        db.record_provenance(
+            &id,
+            &Provenance::from_diff(&diff, MetadataSource::MusicBrainz),
+        )
+        .await
+        .unwrap();
+
+        let mut edited = tagged.clone();
+        edited.title = "Song".to_string();
+        let diff = TrackDiff::between(&tagged, &edited);
+        let manual = Provenance::from_diff(&diff, MetadataSource::Manual);
+        db.record_provenance(&id, &manual).await.unwrap();
+
+        let provenance = db.get_track_provenance(&id).await.unwrap();
+        assert_eq!(provenance.fields.len(), 2);
+        assert_eq!(provenance.fields["title"], manual.fields["title"]);
+        assert_eq!(provenance.source("year"), MetadataSource::MusicBrainz);
+        assert_eq!(provenance.source("artist"), MetadataSource::File);
+
+        assert!(matches!(
+            db.record_provenance(&TrackId::new(), &manual).await,
+            Err(DbError::NotFound(_))
+        ));
+        db.remove_track(&id).await.unwrap();
+        assert!(db.get_track_provenance(&id).await.unwrap().is_empty());
+    }
 
-        // Create an album
-        let mut album = Album::new("Test Album".to_string(), "Test Artist".to_string());
-        album.year = Some(2023);
-        album.track_count = 10;
-
-        // Add the album
-        let id = db.add_album(&album).await.unwrap();
-        assert_eq!(id, album.id);
+    #[tokio::test]
+    async fn test_field_locks() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/song.flac"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let id = db.add_track(&track).await.unwrap();
+        assert!(db.get_field_locks(&id).await.unwrap().is_empty());
 
-        // Retrieve the album
-        let retrieved = db.get_album(&id).await.unwrap().unwrap();
-        assert_eq!(retrieved.title, "Test Album");
-        assert_eq!(retrieved.artist, "Test Artist");
-        assert_eq!(retrieved.year, Some(2023));
-        assert_eq!(retrieved.track_count, 10);
+        db.set_field_locks(&id, &[EditField::Year, EditField::Genre, EditField::Year])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_field_locks(&id).await.unwrap(),
+            vec![EditField::Year, EditField::Genre]
+        );
+        db.set_field_locks(&id, &[EditField::Title]).await.unwrap();
+        assert_eq!(
+            db.get_field_locks(&id).await.unwrap(),
+            vec![EditField::Title]
+        );
 
-        // Update the album
-        let mut updated_album = retrieved;
-        updated_album.title = "Updated Album".to_string();
-        db.update_album(&updated_album).await.unwrap();
+        assert!(matches!(
+            db.set_field_locks(&TrackId::new(), &[EditField::Title])
+                .await,
+            Err(DbError::NotFound(_))
+        ));
+        db.remove_track(&id).await.unwrap();
+        assert!(db.get_field_locks(&id).await.unwrap().is_empty());
+    }
 
-        // Verify update
-        let retrieved = db.get_album(&id).await.unwrap().unwrap();
-        assert_eq!(retrieved.title, "Updated Album");
+    #[tokio::test]
+    async fn test_library_stats() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut ids = Vec::new();
+        for (title, artist, year, genres) in [
+            ("One", "Alpha", Some(1994), vec!["Rock", "Pop"]),
+            ("Two", "Alpha", Some(1999), vec!["Rock"]),
+            ("Three", "Beta", None, vec![]),
+        ] {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/{title}.flac")),
+                title.to_string(),
+                artist.to_string(),
+                Duration::from_mins(2),
+            );
+            track.year = year;
+            track.genres = genres.into_iter().map(String::from).collect();
+            ids.push(db.add_track(&track).await.unwrap());
+        }
 
-        // Remove the album
-        db.remove_album(&id).await.unwrap();
+        let stats = db.library_stats().await.unwrap();
+        assert_eq!(stats.track_count, 3);
+        assert_eq!(stats.artist_count, 2);
+        assert_eq!(stats.total_duration_secs, 360);
+
+        let decades = db.stats_by(StatsBreakdown::Decade).await.unwrap();
+        assert_eq!(decades.len(), 2);
+        assert_eq!(decades[0].name.as_deref(), Some("1990s"));
+        assert_eq!(decades[0].track_count, 2);
+        assert_eq!(decades[1].name, None);
+
+        let genres = db.stats_by(StatsBreakdown::Genre).await.unwrap();
+        let genre_counts: Vec<_> = genres
+            .iter()
+            .map(|g| (g.name.as_deref(), g.track_count))
+            .collect();
+        assert_eq!(
+            genre_counts,
+            vec![(Some("Rock"), 2), (None, 1), (Some("Pop"), 1)]
+        );
 
-        // Verify removal
-        let retrieved = db.get_album(&id).await.unwrap();
-        assert!(retrieved.is_none());
+        let artists = db.stats_by(StatsBreakdown::Artist).await.unwrap();
+        assert_eq!(artists[0].name.as_deref(), Some("Alpha"));
+        assert_eq!(artists[0].total_duration_secs, 240);
+
+        assert_eq!(db.newest_tracks(2).await.unwrap().len(), 2);
+
+        assert!(db.most_played_tracks(5).await.unwrap().is_empty());
+        db.record_play(&ids[1], Utc::now()).await.unwrap();
+        db.record_play(&ids[1], Utc::now()).await.unwrap();
+        db.record_play(&ids[0], Utc::now()).await.unwrap();
+        let played = db.most_played_tracks(5).await.unwrap();
+        let played: Vec<_> = played
+            .iter()
+            .map(|(track, count)| (track.title.as_str(), *count))
+            .collect();
+        assert_eq!(played, vec![("Two", 2), ("One", 1)]);
     }
 
     #[tokio::test]
-    async fn test_album_tracks() {
+    async fn test_search_albums_artists_playlists() {
         let db = SqliteLibrary::in_memory().await.unwrap();
-
-        // Create an album
-        let album = Album::new("Test Album".to_string(), "Test Artist".to_string());
+        let mut album = Album::new("Abbey Road".to_string(), "The Beatles".to_string());
         db.add_album(&album).await.unwrap();
-
-        // Create tracks for the album
-        for i in 1..=3 {
+        db.add_album(&Album::new("Help!".to_string(), "The Beatles".to_string()))
+            .await
+            .unwrap();
+        for (title, artist, album_artist) in [
+            ("Come Together", "The Beatles", None),
+            ("Something", "The Beatles", None),
+            ("Imagine", "John Lennon", Some("Various Artists")),
+        ] {
             let mut track = Track::new(
-                PathBuf::from(format!("/music/track{i}.mp3")),
-                format!("Track {i}"),
-                "Test Artist".to_string(),
-                Duration::from_secs(180),
+                PathBuf::from(format!("/music/{title}.flac")),
+                title.to_string(),
+                artist.to_string(),
+                Duration::from_mins(3),
             );
-            track.album_id = Some(album.id.clone());
-            track.album_title = Some("Test Album".to_string());
-            track.track_number = Some(i);
+            track.album_artist = album_artist.map(String::from);
             db.add_track(&track).await.unwrap();
         }
-
-        // Get album tracks
-        let tracks = db.get_album_tracks(&album.id).await.unwrap();
-        assert_eq!(tracks.len(), 3);
-        assert_eq!(tracks[0].title, "Track 1");
-        assert_eq!(tracks[1].title, "Track 2");
-        assert_eq!(tracks[2].title, "Track 3");
+        let mut playlist = Playlist::new_static("Road Trip");
+        playlist.description = Some("Songs for the car".to_string());
+        db.add_playlist(&playlist).await.unwrap();
+
+        let albums = db.search_albums("abbey").await.unwrap();
+        assert_eq!(albums.len(), 1);
+        assert_eq!(db.search_albums("beatles").await.unwrap().len(), 2);
+
+        // Renamed albums are found by their new title
+        album.title = "Let It Be".to_string();
+        db.update_album(&album).await.unwrap();
+        assert!(db.search_albums("abbey").await.unwrap().is_empty());
+        assert_eq!(db.search_albums("let").await.unwrap().len(), 1);
+
+        let artists = db.search_artists("beatles").await.unwrap();
+        assert_eq!(artists.len(), 1);
+        assert_eq!(artists[0].name, "The Beatles");
+        assert_eq!(artists[0].track_count, 2);
+        assert_eq!(artists[0].total_duration_secs, 360);
+        let artists = db.search_artists("various").await.unwrap();
+        assert_eq!(artists[0].name, "Various Artists");
+        // Titles are not artists
+        assert!(db.search_artists("imagine").await.unwrap().is_empty());
+
+        assert_eq!(db.search_playlists("car").await.unwrap().len(), 1);
+        assert_eq!(
+            db.search_playlists("road").await.unwrap()[0].name,
+            "Road Trip"
+        );
+        assert!(db.search_playlists("beatles").await.unwrap().is_empty());
     }
 
     #[tokio::test]
-    async fn test_list_tracks_and_albums() {
+    async fn test_suggest() {
         let db = SqliteLibrary::in_memory().await.unwrap();
-
-        // Add some tracks
-        for i in 1..=5 {
+        db.add_album(&Album::new(
+            "Blonde on Blonde".to_string(),
+            "Bob Dylan".to_string(),
+        ))
+        .await
+        .unwrap();
+        for (title, artist) in [
+            ("Visions of Johanna", "Bob Dylan"),
+            ("Just Like a Woman", "Bob Dylan"),
+            ("Bohemian Rhapsody", "Queen"),
+            ("Redemption Song", "Bob Marley"),
+        ] {
             let track = Track::new(
-                PathBuf::from(format!("/music/track{i}.mp3")),
-                format!("Track {i}"),
-                "Test Artist".to_string(),
-                Duration::from_secs(180),
+                PathBuf::from(format!("/music/{title}.flac")),
+                title.to_string(),
+                artist.to_string(),
+                Duration::from_mins(3),
             );
             db.add_track(&track).await.unwrap();
         }
 
-        // Add some albums
-        for i in 1..=3 {
-            let album = Album::new(format!("Album {i}"), "Test Artist".to_string());
-            db.add_album(&album).await.unwrap();
-        }
-
-        // List with pagination
-        let tracks = db.list_tracks(2, 0).await.unwrap();
-        assert_eq!(tracks.len(), 2);
-
-        let tracks = db.list_tracks(10, 3).await.unwrap();
-        assert_eq!(tracks.len(), 2); // Only 2 remaining after offset 3
+        let suggestions = db.suggest("bo", 5).await.unwrap();
+        let names = |list: &[Suggestion]| list.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&suggestions.artists), vec!["Bob Dylan", "Bob Marley"]);
+        assert_eq!(
+            suggestions.artists[0].id,
+            ArtistId::from_name("Bob Dylan").to_string()
+        );
+        assert_eq!(names(&suggestions.tracks), vec!["Bohemian Rhapsody"]);
+        assert_eq!(suggestions.tracks[0].artist.as_deref(), Some("Queen"));
+        // Albums match by title, not by artist
+        assert!(suggestions.albums.is_empty());
+        assert_eq!(
+            names(&db.suggest("blonde b", 5).await.unwrap().albums),
+            vec!["Blonde on Blonde"]
+        );
 
-        let albums = db.list_albums(10, 0).await.unwrap();
-        assert_eq!(albums.len(), 3);
+        assert_eq!(db.suggest("bo", 1).await.unwrap().artists.len(), 1);
+        // Query syntax is matched as typed
+        assert!(db.suggest("\"bo AND", 5).await.is_ok());
+        assert_eq!(db.suggest("  ", 5).await.unwrap(), Suggestions::default());
     }
 
     #[tokio::test]
-    async fn test_static_playlist_crud() {
+    async fn test_migrated_history() {
         let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/song.mp3"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let id = db.add_track(&track).await.unwrap();
 
-        // Create a static playlist
-        let playlist = Playlist::new_static("My Favorites").with_description("My favorite songs");
+        let added_at = track.added_at - chrono::TimeDelta::days(365);
+        db.set_track_added_at(&id, added_at).await.unwrap();
+        let played_at = Utc::now() - chrono::TimeDelta::days(7);
+        db.import_plays(&id, &[played_at, played_at]).await.unwrap();
+
+        let stored = db.get_track(&id).await.unwrap().unwrap();
+        assert_eq!(stored.added_at.timestamp(), added_at.timestamp());
+        let stats = db.get_track_stats(&id).await.unwrap();
+        assert_eq!(stats.play_count, 2);
+        assert_eq!(
+            stats.last_played.map(|dt| dt.timestamp()),
+            Some(played_at.timestamp())
+        );
 
-        // Add the playlist
-        let id = db.add_playlist(&playlist).await.unwrap();
-        assert_eq!(id, playlist.id);
+        assert!(matches!(
+            db.set_track_added_at(&TrackId::new(), added_at).await,
+            Err(DbError::NotFound(_))
+        ));
+        assert!(
+            db.import_plays(&TrackId::new(), &[played_at])
+                .await
+                .is_err()
+        );
+    }
 
-        // Retrieve the playlist
-        let retrieved = db.get_playlist(&id).await.unwrap().unwrap();
-        assert_eq!(retrieved.name, "My Favorites");
-        assert_eq!(retrieved.description, Some("My favorite songs".to_string()));
-        assert!(retrieved.is_static());
+    #[tokio::test]
+    async fn test_smart_playlist_ratings_and_plays() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
 
-        // Update the playlist
-        let mut updated_playlist = retrieved;
-        updated_playlist.name = "Updated Favorites".to_string();
-        db.update_playlist(&updated_playlist).await.unwrap();
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            ids.push(db.add_track(&track).await.unwrap());
+        }
 
-        // Verify update
-        let retrieved = db.get_playlist(&id).await.unwrap().unwrap();
-        assert_eq!(retrieved.name, "Updated Favorites");
+        let now = Utc::now();
+        db.set_track_rating(&ids[0], Some(5)).await.unwrap();
+        db.set_track_favorite(&ids[0], true).await.unwrap();
+        db.record_play(&ids[0], now - chrono::TimeDelta::days(200))
+            .await
+            .unwrap();
+        db.set_track_rating(&ids[1], Some(3)).await.unwrap();
+        db.record_play(&ids[1], now).await.unwrap();
+        db.record_play(&ids[1], now).await.unwrap();
+
+        let cases = [
+            ("rating>=4", 1),
+            ("rating:", 1),
+            ("plays=0", 1),
+            ("plays>=1", 2),
+            ("favorite:true", 1),
+            ("favorite:false", 2),
+            ("lastplayed<-90d", 1),
+            ("lastplayed:-7d", 1),
+        ];
+
+        for (query_str, expected) in cases {
+            let query = apollo_core::query::Query::parse(query_str).unwrap();
+            let playlist_id = db
+                .add_playlist(&Playlist::new_smart(query_str, query))
+                .await
+                .unwrap();
+            let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+            assert_eq!(tracks.len(), expected, "{query_str}");
+        }
+    }
 
-        // Remove the playlist
-        db.remove_playlist(&id).await.unwrap();
+    #[tokio::test]
+    async fn test_smart_playlist_album_release() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
 
-        // Verify removal
-        let retrieved = db.get_playlist(&id).await.unwrap();
-        assert!(retrieved.is_none());
+        let mut live = Album::new("Live Killers".to_string(), "Queen".to_string());
+        live.album_type = Some(AlbumType::Live);
+        live.release_date = NaiveDate::from_ymd_opt(1979, 6, 22);
+        live.country = Some("GB".to_string());
+        live.label = Some("EMI".to_string());
+        live.catalog_number = Some("EMSP 330".to_string());
+        live.media = Some(Media::Vinyl);
+        live.edition = Some("Original Pressing".to_string());
+        db.add_album(&live).await.unwrap();
+
+        let stored = db.get_album(&live.id).await.unwrap().unwrap();
+        assert_eq!(stored.album_type, Some(AlbumType::Live));
+        assert_eq!(stored.release_date, live.release_date);
+        assert_eq!(stored.catalog_number.as_deref(), Some("EMSP 330"));
+        assert_eq!(stored.media, Some(Media::Vinyl));
+        assert_eq!(stored.edition.as_deref(), Some("Original Pressing"));
+        assert_eq!(stored.disambiguation, None);
+
+        let mut remaster = stored.clone();
+        remaster.media = Some(Media::Cd);
+        remaster.disambiguation = Some("2001 remaster".to_string());
+        db.update_album(&remaster).await.unwrap();
+        let stored = db.get_album(&live.id).await.unwrap().unwrap();
+        assert_eq!(stored.media, Some(Media::Cd));
+        assert_eq!(stored.disambiguation.as_deref(), Some("2001 remaster"));
+
+        let mut ep = Album::new("Five Live".to_string(), "Various Artists".to_string());
+        ep.is_compilation = true;
+        db.add_album(&ep).await.unwrap();
+
+        for (i, album) in [&live, &ep].into_iter().enumerate() {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                "Queen".to_string(),
+                Duration::from_mins(3),
+            );
+            track.album_id = Some(album.id.clone());
+            db.add_track(&track).await.unwrap();
+        }
+
+        let cases = [
+            ("albumtype:live", 1),
+            ("country:GB", 1),
+            ("label:emi", 1),
+            ("catalog:=\"EMSP 330\"", 1),
+            ("released:1979", 1),
+            ("released:1979-06-22", 1),
+            ("released:1980..", 0),
+            ("-albumtype:live", 1),
+            ("compilation:true", 1),
+            ("compilation:false", 1),
+        ];
+
+        for (query_str, expected) in cases {
+            let query = apollo_core::query::Query::parse(query_str).unwrap();
+            let playlist_id = db
+                .add_playlist(&Playlist::new_smart(query_str, query))
+                .await
+                .unwrap();
+            let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+            assert_eq!(tracks.len(), expected, "{query_str}");
+        }
     }
 
     #[tokio::test]
-    async fn test_smart_playlist_crud() {
+    async fn test_smart_playlist_date_ranges() {
         let db = SqliteLibrary::in_memory().await.unwrap();
 
-        // Create a smart playlist
-        let query = apollo_core::query::Query::parse("artist:Beatles").unwrap();
-        let playlist = Playlist::new_smart("Beatles Songs", query)
-            .with_sort(PlaylistSort::YearDesc)
-            .with_max_tracks(100);
-
-        // Add the playlist
-        let id = db.add_playlist(&playlist).await.unwrap();
+        let now = Utc::now();
+        for (i, days_ago) in [1, 45, 400].into_iter().enumerate() {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                format!("Song {i}"),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track.added_at = now - chrono::TimeDelta::days(days_ago);
+            db.add_track(&track).await.unwrap();
+        }
 
-        // Retrieve the playlist
-        let retrieved = db.get_playlist(&id).await.unwrap().unwrap();
-        assert_eq!(retrieved.name, "Beatles Songs");
-        assert!(retrieved.is_smart());
-        assert_eq!(retrieved.sort, PlaylistSort::YearDesc);
-        assert!(retrieved.query.is_some());
-        assert!(retrieved.limit.is_some());
-        assert_eq!(retrieved.limit.unwrap().max_tracks, Some(100));
+        let last_year = (now - chrono::TimeDelta::days(400)).format("%Y-%m-%d");
+        let cases = [
+            ("added:-30d".to_string(), 1),
+            ("added:-1y".to_string(), 2),
+            ("added<-30d".to_string(), 2),
+            (format!("added:{last_year}"), 1),
+            (format!("added>{last_year}"), 2),
+            ("modified:-1d".to_string(), 3),
+        ];
+
+        for (query_str, expected) in cases {
+            let query = apollo_core::query::Query::parse(&query_str).unwrap();
+            let playlist_id = db
+                .add_playlist(&Playlist::new_smart(&query_str, query))
+                .await
+                .unwrap();
+            let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
+            assert_eq!(tracks.len(), expected, "{query_str}");
+        }
     }
 
     #[tokio::test]
-    async fn test_playlist_tracks() {
+    async fn test_smart_playlist_sort_spec() {
         let db = SqliteLibrary::in_memory().await.unwrap();
 
-        // Create some tracks
-        let track1 = Track::new(
-            PathBuf::from("/music/track1.mp3"),
-            "Track 1".to_string(),
-            "Artist".to_string(),
-            Duration::from_secs(180),
-        );
-        let track2 = Track::new(
-            PathBuf::from("/music/track2.mp3"),
-            "Track 2".to_string(),
-            "Artist".to_string(),
-            Duration::from_secs(240),
-        );
-        db.add_track(&track1).await.unwrap();
-        db.add_track(&track2).await.unwrap();
+        let tracks = [("B", 1990, "X"), ("A", 2000, "Y"), ("C", 2000, "Z")];
+        for (i, (album, year, title)) in tracks.into_iter().enumerate() {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                title.to_string(),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track.album_title = Some(album.to_string());
+            track.year = Some(year);
+            db.add_track(&track).await.unwrap();
+        }
 
-        // Create a static playlist
-        let playlist = Playlist::new_static("Test Playlist");
+        let (query, sort) =
+            apollo_core::query::Query::parse_sorted("artist:Artist sort:year- sort:album+")
+                .unwrap();
+        let playlist = Playlist::new_smart("Sorted", query).with_sort_spec(sort.clone());
         let playlist_id = db.add_playlist(&playlist).await.unwrap();
 
-        // Add tracks to playlist
-        db.add_track_to_playlist(&playlist_id, &track1.id)
+        let retrieved = db.get_playlist(&playlist_id).await.unwrap().unwrap();
+        assert_eq!(retrieved.sort_spec, Some(sort.clone()));
+
+        let titles: Vec<String> = db
+            .get_playlist_tracks(&playlist_id)
             .await
-            .unwrap();
-        db.add_track_to_playlist(&playlist_id, &track2.id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["Y", "Z", "X"]);
+
+        let titles: Vec<String> = db
+            .search_tracks_sorted("Artist", &sort)
             .await
-            .unwrap();
-
-        // Get playlist tracks
-        let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
-        assert_eq!(tracks.len(), 2);
-        assert_eq!(tracks[0].title, "Track 1");
-        assert_eq!(tracks[1].title, "Track 2");
-
-        // Remove a track from playlist
-        db.remove_track_from_playlist(&playlist_id, &track1.id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["Y", "Z", "X"]);
+
+        let query = apollo_core::query::Query::parse("year:2000").unwrap();
+        let titles: Vec<String> = db
+            .query_tracks(&query, &sort)
             .await
-            .unwrap();
-        let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
-        assert_eq!(tracks.len(), 1);
-        assert_eq!(tracks[0].title, "Track 2");
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["Y", "Z"]);
     }
 
     #[tokio::test]
-    async fn test_smart_playlist_evaluation() {
+    async fn test_smart_playlist_of_playlists() {
+        use apollo_core::query::Query;
+
         let db = SqliteLibrary::in_memory().await.unwrap();
 
-        // Create tracks with different artists
-        for i in 1..=3 {
+        let mut track_ids = Vec::new();
+        let tracks = [("A", 1990), ("B", 1991), ("C", 1992), ("D", 1993)];
+        for (i, (title, year)) in tracks.into_iter().enumerate() {
             let mut track = Track::new(
-                PathBuf::from(format!("/music/beatles_{i}.mp3")),
-                format!("Song {i}"),
-                "Beatles".to_string(),
-                Duration::from_secs(180),
+                PathBuf::from(format!("/music/song_{i}.mp3")),
+                title.to_string(),
+                "Artist".to_string(),
+                Duration::from_mins(3),
             );
-            track.year = Some(1965 + i as i32);
-            db.add_track(&track).await.unwrap();
+            track.year = Some(year);
+            track_ids.push(db.add_track(&track).await.unwrap());
         }
 
-        for i in 1..=2 {
-            let track = Track::new(
-                PathBuf::from(format!("/music/stones_{i}.mp3")),
-                format!("Stone Song {i}"),
-                "Rolling Stones".to_string(),
-                Duration::from_secs(200),
-            );
-            db.add_track(&track).await.unwrap();
+        let mut workout = Playlist::new_static("Workout");
+        for track_id in &track_ids[..3] {
+            workout.add_track(track_id.clone());
         }
+        db.add_playlist(&workout).await.unwrap();
+        let recent = Playlist::new_smart("Heard recently", Query::parse("year>=1991").unwrap())
+            .with_sort(PlaylistSort::YearDesc)
+            .with_max_tracks(2);
+        db.add_playlist(&recent).await.unwrap();
+
+        let mix = Query::parse(r#"playlist:"workout""#)
+            .unwrap()
+            .and(Query::parse(r#"-playlist:"Heard recently""#).unwrap());
+        let mix_id = db
+            .add_playlist(&Playlist::new_smart("Mix", mix).with_sort(PlaylistSort::YearAsc))
+            .await
+            .unwrap();
 
-        // Create a smart playlist for Beatles
-        let query = apollo_core::query::Query::parse("artist:Beatles").unwrap();
-        let playlist = Playlist::new_smart("Beatles", query).with_sort(PlaylistSort::YearAsc);
-        let playlist_id = db.add_playlist(&playlist).await.unwrap();
+        let cases = [
+            (Query::playlist("Mix"), vec!["A", "B"]),
+            (Query::playlist("Heard recently"), vec!["C", "D"]),
+            (!Query::playlist("Workout"), vec!["D"]),
+            (Query::playlist("Missing"), vec![]),
+            // References back to the playlist itself match nothing
+            (
+                Query::playlist("Loop").or(Query::parse("year:1990").unwrap()),
+                vec!["A"],
+            ),
+        ];
+        for (query, expected) in cases {
+            let playlist =
+                Playlist::new_smart("Loop", query.clone()).with_sort(PlaylistSort::YearAsc);
+            let playlist_id = db.add_playlist(&playlist).await.unwrap();
+            let titles: Vec<String> = db
+                .get_playlist_tracks(&playlist_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|t| t.title)
+                .collect();
+            assert_eq!(titles, expected, "{query}");
+            db.remove_playlist(&playlist_id).await.unwrap();
+        }
 
-        // Evaluate the playlist
-        let tracks = db.get_playlist_tracks(&playlist_id).await.unwrap();
-        assert_eq!(tracks.len(), 3);
-        assert!(tracks.iter().all(|t| t.artist == "Beatles"));
-        // Should be sorted by year ascending
-        assert!(tracks[0].year <= tracks[1].year);
-        assert!(tracks[1].year <= tracks[2].year);
+        assert_eq!(db.count_playlist_tracks(&mix_id).await.unwrap(), 2);
+        let tracks = db
+            .query_tracks(&Query::playlist("mix"), &SortSpec::default())
+            .await
+            .unwrap();
+        assert_eq!(tracks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_playlist_query_folds_non_ascii_case() {
+        use apollo_core::query::Query;
+
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/song.mp3"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let mut playlist = Playlist::new_static("Ärger");
+        playlist.add_track(db.add_track(&track).await.unwrap());
+        db.add_playlist(&playlist).await.unwrap();
+
+        let tracks = db
+            .query_tracks(
+                &Query::parse(r#"playlist:"ärger""#).unwrap(),
+                &SortSpec::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tracks.len(), 1);
+
+        // A smart playlist that names itself with different case is a cycle
+        let query = Query::playlist("ÄRGER 2").or(Query::parse("title:Song").unwrap());
+        let id = db
+            .add_playlist(&Playlist::new_smart("Ärger 2", query))
+            .await
+            .unwrap();
+        assert_eq!(db.get_playlist_tracks(&id).await.unwrap().len(), 1);
     }
 
     #[tokio::test]
@@ -1633,4 +5810,85 @@ mod tests {
         let count = db.count_playlists().await.unwrap();
         assert_eq!(count, 3);
     }
+
+    #[tokio::test]
+    async fn test_schema_version_and_check() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let url = format!(
+            "sqlite:{}?mode=rwc",
+            dir.path().join("library.db").display()
+        );
+
+        let db = SqliteLibrary::connect(&url).await.unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), 0);
+        db.migrate().await.unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), SCHEMA_VERSION);
+        assert!(db.check().await.unwrap().is_empty());
+        let track = Track::new(
+            PathBuf::from("/music/test.mp3"),
+            "Test Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        db.vacuum().await.unwrap();
+
+        let db = SqliteLibrary::new(&url).await.unwrap();
+        db.remove_track(&track.id).await.unwrap();
+
+        sqlx::query("PRAGMA user_version = 1000")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(matches!(db.migrate().await, Err(DbError::InvalidData(_))));
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("library.db");
+        let backup = dir.path().join("backup.db");
+        let db = SqliteLibrary::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+
+        let track = Track::new(
+            PathBuf::from("/music/test.mp3"),
+            "Test Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        db.backup(&backup).await.unwrap();
+        assert!(matches!(db.backup(&backup).await, Err(DbError::Io(_))));
+
+        db.remove_track(&track.id).await.unwrap();
+        db.pool.close().await;
+
+        SqliteLibrary::restore(&backup, &path).await.unwrap();
+        let db = SqliteLibrary::new(&format!("sqlite:{}", path.display()))
+            .await
+            .unwrap();
+        assert_eq!(db.count_tracks().await.unwrap(), 1);
+        assert!(db.get_track(&track.id).await.unwrap().is_some());
+
+        // Only Apollo libraries can be restored
+        let other = dir.path().join("other.db");
+        let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", other.display()))
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE notes (text TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        assert!(matches!(
+            SqliteLibrary::restore(&other, &path).await,
+            Err(DbError::InvalidData(_))
+        ));
+        assert!(matches!(
+            SqliteLibrary::restore(&dir.path().join("missing.db"), &path).await,
+            Err(DbError::NotFound(_))
+        ));
+    }
 }
@@ -0,0 +1,96 @@
+//! Lua bindings for audio file analysis.
+//!
+//! This module exposes fingerprinting and hashing under the `apollo.audio`
+//! table, so plugins can implement custom identification or dedup logic.
+//!
+//! # Available Functions
+//!
+//! - `apollo.audio.fingerprint(path)` - returns `{ fingerprint = "...", duration = 180 }`
+//! - `apollo.audio.hash(path)` - returns the SHA-256 hash of the file contents
+
+use apollo_audio::{compute_file_hash, generate_fingerprint};
+use mlua::{Lua, Result, Table};
+use std::path::Path;
+
+/// Register the `apollo.audio` table.
+///
+/// # Errors
+///
+/// Returns an error if the `apollo` module is not registered.
+pub fn register_audio_module(lua: &Lua) -> Result<()> {
+    let apollo: Table = lua.globals().get("apollo")?;
+    let audio = lua.create_table()?;
+
+    // apollo.audio.fingerprint(path)
+    audio.set(
+        "fingerprint",
+        lua.create_function(|lua, path: String| {
+            let result = generate_fingerprint(Path::new(&path)).map_err(mlua::Error::external)?;
+            let table = lua.create_table()?;
+            table.set("fingerprint", result.fingerprint)?;
+            table.set("duration", result.duration)?;
+            Ok(table)
+        })?,
+    )?;
+
+    // apollo.audio.hash(path)
+    audio.set(
+        "hash",
+        lua.create_function(|_, path: String| {
+            compute_file_hash(Path::new(&path)).map_err(mlua::Error::external)
+        })?,
+    )?;
+
+    apollo.set("audio", audio)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::register_apollo_module;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn create_lua() -> Lua {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+        register_audio_module(&lua).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_hash() {
+        let lua = create_lua();
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"Hello, World!").unwrap();
+        file.flush().unwrap();
+        lua.globals()
+            .set("path", file.path().to_string_lossy().to_string())
+            .unwrap();
+
+        let hash: String = lua.load("return apollo.audio.hash(path)").eval().unwrap();
+        assert_eq!(
+            hash,
+            "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+        );
+    }
+
+    #[test]
+    fn test_missing_file_raises_error() {
+        let lua = create_lua();
+
+        let ok: bool = lua
+            .load(
+                r#"
+                local ok_hash = pcall(apollo.audio.hash, "/nonexistent/file.mp3")
+                local ok_fp = pcall(apollo.audio.fingerprint, "/nonexistent/file.mp3")
+                return ok_hash or ok_fp
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert!(!ok);
+    }
+}
@@ -8,7 +8,7 @@
 #![allow(clippy::significant_drop_tightening)]
 #![allow(clippy::missing_const_for_fn)]
 
-use apollo_core::{Album, Track};
+use apollo_core::{Album, AlbumType, Media, Track};
 use mlua::{FromLua, IntoLua, Lua, MetaMethod, Result, UserData, UserDataMethods, Value};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
@@ -64,31 +64,7 @@ impl UserData for LuaTrack {
                 .0
                 .read()
                 .map_err(|_| mlua::Error::runtime("lock poisoned"))?;
-            match key.as_str() {
-                "id" => track.id.to_string().into_lua(lua),
-                "path" => track.path.to_string_lossy().to_string().into_lua(lua),
-                "title" => track.title.clone().into_lua(lua),
-                "artist" => track.artist.clone().into_lua(lua),
-                "album_artist" => track.album_artist.clone().into_lua(lua),
-                "album_title" => track.album_title.clone().into_lua(lua),
-                "track_number" => track.track_number.into_lua(lua),
-                "track_total" => track.track_total.into_lua(lua),
-                "disc_number" => track.disc_number.into_lua(lua),
-                "disc_total" => track.disc_total.into_lua(lua),
-                "year" => track.year.into_lua(lua),
-                "genres" => track.genres.clone().into_lua(lua),
-                "duration" => (track.duration.as_secs_f64()).into_lua(lua),
-                #[allow(clippy::cast_possible_truncation)] // 584 million years before truncation
-                "duration_ms" => (track.duration.as_millis() as u64).into_lua(lua),
-                "bitrate" => track.bitrate.into_lua(lua),
-                "sample_rate" => track.sample_rate.into_lua(lua),
-                "channels" => track.channels.into_lua(lua),
-                "format" => track.format.to_string().into_lua(lua),
-                "musicbrainz_id" => track.musicbrainz_id.clone().into_lua(lua),
-                "acoustid" => track.acoustid.clone().into_lua(lua),
-                "file_hash" => track.file_hash.clone().into_lua(lua),
-                _ => Ok(Value::Nil),
-            }
+            track_property(lua, &track, &key)
         });
 
         // Mutable properties
@@ -99,50 +75,7 @@ impl UserData for LuaTrack {
                     .0
                     .write()
                     .map_err(|_| mlua::Error::runtime("lock poisoned"))?;
-                match key.as_str() {
-                    "title" => {
-                        track.title = String::from_lua(value, lua)?;
-                    }
-                    "artist" => {
-                        track.artist = String::from_lua(value, lua)?;
-                    }
-                    "album_artist" => {
-                        track.album_artist = Option::<String>::from_lua(value, lua)?;
-                    }
-                    "album_title" => {
-                        track.album_title = Option::<String>::from_lua(value, lua)?;
-                    }
-                    "track_number" => {
-                        track.track_number = Option::<u32>::from_lua(value, lua)?;
-                    }
-                    "track_total" => {
-                        track.track_total = Option::<u32>::from_lua(value, lua)?;
-                    }
-                    "disc_number" => {
-                        track.disc_number = Option::<u32>::from_lua(value, lua)?;
-                    }
-                    "disc_total" => {
-                        track.disc_total = Option::<u32>::from_lua(value, lua)?;
-                    }
-                    "year" => {
-                        track.year = Option::<i32>::from_lua(value, lua)?;
-                    }
-                    "genres" => {
-                        track.genres = Vec::<String>::from_lua(value, lua)?;
-                    }
-                    "musicbrainz_id" => {
-                        track.musicbrainz_id = Option::<String>::from_lua(value, lua)?;
-                    }
-                    "acoustid" => {
-                        track.acoustid = Option::<String>::from_lua(value, lua)?;
-                    }
-                    _ => {
-                        return Err(mlua::Error::runtime(format!(
-                            "cannot set property '{key}' (read-only or unknown)"
-                        )));
-                    }
-                }
-                Ok(())
+                set_track_property(lua, &mut track, &key, value)
             },
         );
 
@@ -157,6 +90,122 @@ impl UserData for LuaTrack {
     }
 }
 
+/// Read a property of a track, or `nil` if there is no such property.
+fn track_property<'lua>(lua: &'lua Lua, track: &Track, key: &str) -> Result<Value<'lua>> {
+    match key {
+        "id" => track.id.to_string().into_lua(lua),
+        "path" => track.path.to_string_lossy().to_string().into_lua(lua),
+        "title" => track.title.clone().into_lua(lua),
+        "artist" => track.artist.clone().into_lua(lua),
+        "album_artist" => track.album_artist.clone().into_lua(lua),
+        "album_title" => track.album_title.clone().into_lua(lua),
+        "track_number" => track.track_number.into_lua(lua),
+        "track_total" => track.track_total.into_lua(lua),
+        "disc_number" => track.disc_number.into_lua(lua),
+        "disc_total" => track.disc_total.into_lua(lua),
+        "year" => track.year.into_lua(lua),
+        "genres" => track.genres.clone().into_lua(lua),
+        "duration" => (track.duration.as_secs_f64()).into_lua(lua),
+        #[allow(clippy::cast_possible_truncation)] // 584 million years before truncation
+        "duration_ms" => (track.duration.as_millis() as u64).into_lua(lua),
+        "bitrate" => track.bitrate.into_lua(lua),
+        "sample_rate" => track.sample_rate.into_lua(lua),
+        "bit_depth" => track.bit_depth.into_lua(lua),
+        "channels" => track.channels.into_lua(lua),
+        "format" => track.format.to_string().into_lua(lua),
+        "album_id" => track
+            .album_id
+            .as_ref()
+            .map(ToString::to_string)
+            .into_lua(lua),
+        "musicbrainz_id" => track.musicbrainz_id.clone().into_lua(lua),
+        "acoustid" => track.acoustid.clone().into_lua(lua),
+        "isrc" => track.isrc.clone().into_lua(lua),
+        "rg_track_gain" => track.rg_track_gain.into_lua(lua),
+        "rg_track_peak" => track.rg_track_peak.into_lua(lua),
+        "rg_album_gain" => track.rg_album_gain.into_lua(lua),
+        "rg_album_peak" => track.rg_album_peak.into_lua(lua),
+        "loudness_lufs" => track.loudness_lufs.into_lua(lua),
+        "added_at" => track.added_at.to_rfc3339().into_lua(lua),
+        "modified_at" => track.modified_at.to_rfc3339().into_lua(lua),
+        "file_hash" => track.file_hash.clone().into_lua(lua),
+        _ => Ok(Value::Nil),
+    }
+}
+
+/// Set a property of a track.
+///
+/// Fails for properties that are read-only or unknown.
+fn set_track_property<'lua>(
+    lua: &'lua Lua,
+    track: &mut Track,
+    key: &str,
+    value: Value<'lua>,
+) -> Result<()> {
+    match key {
+        "title" => {
+            track.title = String::from_lua(value, lua)?;
+        }
+        "artist" => {
+            track.artist = String::from_lua(value, lua)?;
+        }
+        "album_artist" => {
+            track.album_artist = Option::<String>::from_lua(value, lua)?;
+        }
+        "album_title" => {
+            track.album_title = Option::<String>::from_lua(value, lua)?;
+        }
+        "track_number" => {
+            track.track_number = Option::<u32>::from_lua(value, lua)?;
+        }
+        "track_total" => {
+            track.track_total = Option::<u32>::from_lua(value, lua)?;
+        }
+        "disc_number" => {
+            track.disc_number = Option::<u32>::from_lua(value, lua)?;
+        }
+        "disc_total" => {
+            track.disc_total = Option::<u32>::from_lua(value, lua)?;
+        }
+        "year" => {
+            track.year = Option::<i32>::from_lua(value, lua)?;
+        }
+        "genres" => {
+            track.genres = Vec::<String>::from_lua(value, lua)?;
+        }
+        "musicbrainz_id" => {
+            track.musicbrainz_id = Option::<String>::from_lua(value, lua)?;
+        }
+        "acoustid" => {
+            track.acoustid = Option::<String>::from_lua(value, lua)?;
+        }
+        "isrc" => {
+            track.isrc = Option::<String>::from_lua(value, lua)?;
+        }
+        "rg_track_gain" => {
+            track.rg_track_gain = Option::<f64>::from_lua(value, lua)?;
+        }
+        "rg_track_peak" => {
+            track.rg_track_peak = Option::<f64>::from_lua(value, lua)?;
+        }
+        "rg_album_gain" => {
+            track.rg_album_gain = Option::<f64>::from_lua(value, lua)?;
+        }
+        "rg_album_peak" => {
+            track.rg_album_peak = Option::<f64>::from_lua(value, lua)?;
+        }
+        "loudness_lufs" => {
+            track.loudness_lufs = Option::<f64>::from_lua(value, lua)?;
+        }
+        _ => {
+            return Err(mlua::Error::runtime(format!(
+                "cannot set property '{key}' (read-only or unknown)"
+            )));
+        }
+    }
+    Ok(())
+}
+
 /// A wrapper around [`Album`] that can be shared with Lua.
 #[derive(Clone)]
 pub struct LuaAlbum(pub Arc<RwLock<Album>>);
@@ -204,17 +253,7 @@ impl UserData for LuaAlbum {
                 .0
                 .read()
                 .map_err(|_| mlua::Error::runtime("lock poisoned"))?;
-            match key.as_str() {
-                "id" => album.id.to_string().into_lua(lua),
-                "title" => album.title.clone().into_lua(lua),
-                "artist" => album.artist.clone().into_lua(lua),
-                "year" => album.year.into_lua(lua),
-                "genres" => album.genres.clone().into_lua(lua),
-                "track_count" => album.track_count.into_lua(lua),
-                "disc_count" => album.disc_count.into_lua(lua),
-                "musicbrainz_id" => album.musicbrainz_id.clone().into_lua(lua),
-                _ => Ok(Value::Nil),
-            }
+            album_property(lua, &album, &key)
         });
 
         // Mutable properties
@@ -225,35 +264,7 @@ impl UserData for LuaAlbum {
                     .0
                     .write()
                     .map_err(|_| mlua::Error::runtime("lock poisoned"))?;
-                match key.as_str() {
-                    "title" => {
-                        album.title = String::from_lua(value, lua)?;
-                    }
-                    "artist" => {
-                        album.artist = String::from_lua(value, lua)?;
-                    }
-                    "year" => {
-                        album.year = Option::<i32>::from_lua(value, lua)?;
-                    }
-                    "genres" => {
-                        album.genres = Vec::<String>::from_lua(value, lua)?;
-                    }
-                    "track_count" => {
-                        album.track_count = u32::from_lua(value, lua)?;
-                    }
-                    "disc_count" => {
-                        album.disc_count = u32::from_lua(value, lua)?;
-                    }
-                    "musicbrainz_id" => {
-                        album.musicbrainz_id = Option::<String>::from_lua(value, lua)?;
-                    }
-                    _ => {
-                        return Err(mlua::Error::runtime(format!(
-                            "cannot set property '{key}' (read-only or unknown)"
-                        )));
-                    }
-                }
-                Ok(())
+                set_album_property(lua, &mut album, &key, value)
             },
         );
 
@@ -268,6 +279,140 @@ impl UserData for LuaAlbum {
     }
 }
 
+/// Read a property of a album, or `nil` if there is no such property.
+fn album_property<'lua>(lua: &'lua Lua, album: &Album, key: &str) -> Result<Value<'lua>> {
+    match key {
+        "id" => album.id.to_string().into_lua(lua),
+        "title" => album.title.clone().into_lua(lua),
+        "artist" => album.artist.clone().into_lua(lua),
+        "year" => album.year.into_lua(lua),
+        "genres" => album.genres.clone().into_lua(lua),
+        "track_count" => album.track_count.into_lua(lua),
+        "disc_count" => album.disc_count.into_lua(lua),
+        "musicbrainz_id" => album.musicbrainz_id.clone().into_lua(lua),
+        "album_type" => album.album_type.map(AlbumType::as_str).into_lua(lua),
+        "release_date" => album
+            .release_date
+            .map(|date| date.to_string())
+            .into_lua(lua),
+        "country" => album.country.clone().into_lua(lua),
+        "label" => album.label.clone().into_lua(lua),
+        "catalog_number" => album.catalog_number.clone().into_lua(lua),
+        "is_compilation" => album.is_compilation.into_lua(lua),
+        "disambiguation" => album.disambiguation.clone().into_lua(lua),
+        "media" => album.media.map(Media::as_str).into_lua(lua),
+        "edition" => album.edition.clone().into_lua(lua),
+        "barcode" => album.barcode.clone().into_lua(lua),
+        "discogs_id" => album.discogs_id.into_lua(lua),
+        "rg_album_gain" => album.rg_album_gain.into_lua(lua),
+        "rg_album_peak" => album.rg_album_peak.into_lua(lua),
+        "loudness_lufs" => album.loudness_lufs.into_lua(lua),
+        "added_at" => album.added_at.to_rfc3339().into_lua(lua),
+        "modified_at" => album.modified_at.to_rfc3339().into_lua(lua),
+        _ => Ok(Value::Nil),
+    }
+}
+
+/// Set a property of a album.
+///
+/// Fails for properties that are read-only or unknown.
+fn set_album_property<'lua>(
+    lua: &'lua Lua,
+    album: &mut Album,
+    key: &str,
+    value: Value<'lua>,
+) -> Result<()> {
+    match key {
+        "title" => {
+            album.title = String::from_lua(value, lua)?;
+        }
+        "artist" => {
+            album.artist = String::from_lua(value, lua)?;
+        }
+        "year" => {
+            album.year = Option::<i32>::from_lua(value, lua)?;
+        }
+        "genres" => {
+            album.genres = Vec::<String>::from_lua(value, lua)?;
+        }
+        "track_count" => {
+            album.track_count = u32::from_lua(value, lua)?;
+        }
+        "disc_count" => {
+            album.disc_count = u32::from_lua(value, lua)?;
+        }
+        "musicbrainz_id" => {
+            album.musicbrainz_id = Option::<String>::from_lua(value, lua)?;
+        }
+        "album_type" => {
+            album.album_type = Option::<String>::from_lua(value, lua)?
+                .map(|name| {
+                    AlbumType::from_name(&name)
+                        .ok_or_else(|| mlua::Error::runtime(format!("unknown album type '{name}'")))
+                })
+                .transpose()?;
+        }
+        "release_date" => {
+            album.release_date = Option::<String>::from_lua(value, lua)?
+                .map(|date| {
+                    date.parse().map_err(|_| {
+                        mlua::Error::runtime(format!(
+                            "invalid release date '{date}' (expected YYYY-MM-DD)"
+                        ))
+                    })
+                })
+                .transpose()?;
+        }
+        "country" => {
+            album.country = Option::<String>::from_lua(value, lua)?;
+        }
+        "label" => {
+            album.label = Option::<String>::from_lua(value, lua)?;
+        }
+        "catalog_number" => {
+            album.catalog_number = Option::<String>::from_lua(value, lua)?;
+        }
+        "is_compilation" => {
+            album.is_compilation = bool::from_lua(value, lua)?;
+        }
+        "disambiguation" => {
+            album.disambiguation = Option::<String>::from_lua(value, lua)?;
+        }
+        "media" => {
+            album.media = Option::<String>::from_lua(value, lua)?
+                .map(|name| {
+                    Media::from_name(&name)
+                        .ok_or_else(|| mlua::Error::runtime(format!("unknown media '{name}'")))
+                })
+                .transpose()?;
+        }
+        "edition" => {
+            album.edition = Option::<String>::from_lua(value, lua)?;
+        }
+        "barcode" => {
+            album.barcode = Option::<String>::from_lua(value, lua)?;
+        }
+        "discogs_id" => {
+            album.discogs_id = Option::<u64>::from_lua(value, lua)?;
+        }
+        "rg_album_gain" => {
+            album.rg_album_gain = Option::<f64>::from_lua(value, lua)?;
+        }
+        "rg_album_peak" => {
+            album.rg_album_peak = Option::<f64>::from_lua(value, lua)?;
+        }
+        "loudness_lufs" => {
+            album.loudness_lufs = Option::<f64>::from_lua(value, lua)?;
+        }
+        _ => {
+            return Err(mlua::Error::runtime(format!(
+                "cannot set property '{key}' (read-only or unknown)"
+            )));
+        }
+    }
+    Ok(())
+}
+
 /// Register the Apollo module with the Lua runtime.
 ///
 /// This creates the `apollo` global table with factory functions for creating
@@ -374,7 +519,7 @@ mod tests {
             PathBuf::from("/music/test.mp3"),
             "Test Song".to_string(),
             "Test Artist".to_string(),
-            Duration::from_secs(180),
+            Duration::from_mins(3),
         );
 
         lua.scope(|scope| {
@@ -404,7 +549,7 @@ mod tests {
             PathBuf::from("/music/test.mp3"),
             "Original Title".to_string(),
             "Original Artist".to_string(),
-            Duration::from_secs(180),
+            Duration::from_mins(3),
         );
 
         let lua_track = LuaTrack::new(track);
@@ -437,7 +582,7 @@ mod tests {
             PathBuf::from("/music/test.mp3"),
             "My Song".to_string(),
             "My Artist".to_string(),
-            Duration::from_secs(180),
+            Duration::from_mins(3),
         );
 
         lua.globals().set("track", LuaTrack::new(track)).unwrap();
@@ -493,6 +638,137 @@ mod tests {
         assert_eq!(modified.year, Some(2023));
     }
 
+    #[test]
+    fn test_lua_track_reads_every_field() {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+
+        let mut track = Track::new(
+            PathBuf::from("/music/test.flac"),
+            "Test Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        track.album_artist = Some("Test Artist".to_string());
+        track.album_id = Some(apollo_core::AlbumId::new());
+        track.album_title = Some("Test Album".to_string());
+        track.track_number = Some(1);
+        track.track_total = Some(10);
+        track.disc_number = Some(1);
+        track.disc_total = Some(1);
+        track.year = Some(2024);
+        track.bitrate = Some(900);
+        track.sample_rate = Some(44100);
+        track.bit_depth = Some(16);
+        track.channels = Some(2);
+        track.musicbrainz_id = Some("mbid".to_string());
+        track.acoustid = Some("acoustid".to_string());
+        track.isrc = Some("GBUM71029604".to_string());
+        track.rg_track_gain = Some(-7.5);
+        track.rg_track_peak = Some(0.98);
+        track.rg_album_gain = Some(-8.1);
+        track.rg_album_peak = Some(1.0);
+        track.loudness_lufs = Some(-10.2);
+
+        // Every field a track has must be readable, so new ones are not missed
+        let fields = serde_json::to_value(&track).unwrap();
+        lua.globals().set("track", LuaTrack::new(track)).unwrap();
+        for (field, value) in fields.as_object().unwrap() {
+            assert!(!value.is_null(), "set {field} in this test");
+            let value: Value = lua.load(format!("return track.{field}")).eval().unwrap();
+            assert!(!value.is_nil(), "track.{field} is not readable from Lua");
+        }
+    }
+
+    #[test]
+    fn test_lua_album_reads_every_field() {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+
+        let mut album = Album::new("Test Album".to_string(), "Test Artist".to_string());
+        album.year = Some(1975);
+        album.musicbrainz_id = Some("mbid".to_string());
+        album.album_type = Some(AlbumType::Album);
+        album.release_date = Some("1975-11-21".parse().unwrap());
+        album.country = Some("GB".to_string());
+        album.label = Some("EMI".to_string());
+        album.catalog_number = Some("EMTC 103".to_string());
+        album.disambiguation = Some("2011 remaster".to_string());
+        album.media = Some(Media::Cd);
+        album.edition = Some("Deluxe Edition".to_string());
+        album.barcode = Some("077774600125".to_string());
+        album.discogs_id = Some(367_084);
+        album.rg_album_gain = Some(-8.1);
+        album.rg_album_peak = Some(1.0);
+        album.loudness_lufs = Some(-10.4);
+
+        let fields = serde_json::to_value(&album).unwrap();
+        lua.globals().set("album", LuaAlbum::new(album)).unwrap();
+        for (field, value) in fields.as_object().unwrap() {
+            assert!(!value.is_null(), "set {field} in this test");
+            let value: Value = lua.load(format!("return album.{field}")).eval().unwrap();
+            assert!(!value.is_nil(), "album.{field} is not readable from Lua");
+        }
+
+        let release_date: String = lua.load("return album.release_date").eval().unwrap();
+        assert_eq!(release_date, "1975-11-21");
+        let album_type: String = lua.load("return album.album_type").eval().unwrap();
+        assert_eq!(album_type, "album");
+    }
+
+    #[test]
+    fn test_lua_write_identifiers_and_replay_gain() {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+
+        let track = Track::new(
+            PathBuf::from("/music/test.mp3"),
+            "Test Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        let album = Album::new("Test Album".to_string(), "Test Artist".to_string());
+        let lua_track = LuaTrack::new(track);
+        let lua_album = LuaAlbum::new(album);
+        lua.globals().set("track", lua_track.clone()).unwrap();
+        lua.globals().set("album", lua_album.clone()).unwrap();
+
+        lua.load(
+            r#"
+            track.isrc = "GBUM71029604"
+            track.rg_track_gain = -6.5
+            track.loudness_lufs = -11.5
+            album.album_type = "EP"
+            album.release_date = "2024-03-01"
+            album.label = "Sub Pop"
+            album.is_compilation = true
+            album.discogs_id = 12345
+            album.media = "Vinyl"
+            album.edition = "Deluxe Edition"
+        "#,
+        )
+        .exec()
+        .unwrap();
+
+        let track = lua_track.get();
+        assert_eq!(track.isrc.as_deref(), Some("GBUM71029604"));
+        assert_eq!(track.rg_track_gain, Some(-6.5));
+        assert_eq!(track.loudness_lufs, Some(-11.5));
+        let album = lua_album.get();
+        assert_eq!(album.album_type, Some(AlbumType::Ep));
+        assert_eq!(album.release_date.unwrap().to_string(), "2024-03-01");
+        assert_eq!(album.label.as_deref(), Some("Sub Pop"));
+        assert!(album.is_compilation);
+        assert_eq!(album.discogs_id, Some(12345));
+        assert_eq!(album.media, Some(Media::Vinyl));
+        assert_eq!(album.edition.as_deref(), Some("Deluxe Edition"));
+
+        assert!(lua.load("album.album_type = 'bootleg'").exec().is_err());
+        assert!(lua.load("album.release_date = 'March'").exec().is_err());
+        lua.load("album.album_type = nil").exec().unwrap();
+        assert_eq!(lua_album.get().album_type, None);
+    }
+
     #[test]
     fn test_apollo_module_new_track() {
         let lua = Lua::new();
@@ -546,7 +822,7 @@ mod tests {
             PathBuf::from("/music/test.mp3"),
             "Test Song".to_string(),
             "Test Artist".to_string(),
-            Duration::from_secs(180),
+            Duration::from_mins(3),
         );
 
         lua.globals().set("track", LuaTrack::new(track)).unwrap();
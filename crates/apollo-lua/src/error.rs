@@ -26,6 +26,13 @@ pub enum Error {
         reason: String,
     },
 
+    /// No plugin with the given name is loaded.
+    #[error("Plugin not loaded: {name}")]
+    PluginNotLoaded {
+        /// Name of the plugin.
+        name: String,
+    },
+
     /// Hook execution failed.
     #[error("Hook '{hook}' failed: {reason}")]
     HookFailed {
@@ -42,6 +49,48 @@ pub enum Error {
         reason: String,
     },
 
+    /// Plugin command not found.
+    #[error("Command not found: {name}")]
+    CommandNotFound {
+        /// Name of the command.
+        name: String,
+    },
+
+    /// An event handler failed.
+    #[error("Event '{event}' handler failed: {reason}")]
+    EventFailed {
+        /// Name of the event.
+        event: String,
+        /// Reason for the failure.
+        reason: String,
+    },
+
+    /// Plugin command execution failed.
+    #[error("Command '{command}' failed: {reason}")]
+    CommandFailed {
+        /// Name of the command.
+        command: String,
+        /// Reason for the failure.
+        reason: String,
+    },
+
+    /// Scheduled task execution failed.
+    #[error("Scheduled task '{task}' failed: {reason}")]
+    TaskFailed {
+        /// Name of the task.
+        task: String,
+        /// Reason for the failure.
+        reason: String,
+    },
+
+    /// No Lua worker is available to run a job.
+    #[error("No Lua worker available")]
+    WorkerUnavailable,
+
+    /// Error creating a metadata source client.
+    #[error("Source error: {0}")]
+    Source(#[from] apollo_sources::SourceError),
+
     /// I/O error.
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -0,0 +1,170 @@
+//! Event subscriptions for Lua plugins.
+//!
+//! Unlike hooks, events are identified by name, so new event types can be
+//! emitted without adding a [`HookType`](crate::hooks::HookType) variant.
+//! Handlers receive the event payload as a plain Lua table.
+//!
+//! # Available Functions
+//!
+//! - `apollo.events.subscribe(event, handler)` - call `handler(payload)` whenever `event` is emitted
+//! - `apollo.events.emit(event, payload?)` - emit an event to all subscribers
+//!
+//! ```lua
+//! apollo.events.subscribe("track.imported", function(track)
+//!     apollo.info("Imported " .. track.title)
+//! end)
+//! ```
+//!
+//! Library changes are emitted with [`LuaRuntime::emit_library_event`] under
+//! names such as `track.added` and `playlist.changed` (see
+//! [`LibraryEvent::NAMES`]). Their payload has a `type` field and the IDs of
+//! the changed items:
+//!
+//! ```lua
+//! apollo.events.subscribe("track.played", function(event)
+//!     apollo.info("Played " .. event.track_id .. " at " .. event.played_at)
+//! end)
+//! ```
+//!
+//! [`LuaRuntime::emit_library_event`]: crate::LuaRuntime::emit_library_event
+//! [`LibraryEvent::NAMES`]: apollo_core::LibraryEvent::NAMES
+
+use mlua::{Function, Lua, Result, Table, Value};
+
+/// Name of the global table holding event subscribers.
+const SUBSCRIBERS: &str = "_events";
+
+/// Call all handlers subscribed to an event.
+///
+/// Returns the number of handlers that were called. Stops at the first
+/// handler that fails.
+///
+/// # Errors
+///
+/// Returns an error if a handler fails.
+pub fn dispatch_event<'lua>(lua: &'lua Lua, event: &str, payload: &Value<'lua>) -> Result<usize> {
+    let subscribers: Table = lua.globals().get(SUBSCRIBERS)?;
+    let Some(handlers) = subscribers.get::<_, Option<Table>>(event)? else {
+        return Ok(0);
+    };
+
+    let mut count = 0;
+    for handler in handlers.sequence_values::<Function>() {
+        handler?.call::<_, ()>(payload.clone())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Check if any handlers are subscribed to an event.
+///
+/// # Errors
+///
+/// Returns an error if the subscriber table cannot be read.
+pub fn has_subscribers(lua: &Lua, event: &str) -> Result<bool> {
+    let subscribers: Table = lua.globals().get(SUBSCRIBERS)?;
+    Ok(subscribers
+        .get::<_, Option<Table>>(event)?
+        .is_some_and(|handlers| handlers.raw_len() > 0))
+}
+
+/// Register the `apollo.events` table.
+///
+/// # Errors
+///
+/// Returns an error if the `apollo` module is not registered.
+pub fn register_events_module(lua: &Lua) -> Result<()> {
+    let apollo: Table = lua.globals().get("apollo")?;
+    lua.globals().set(SUBSCRIBERS, lua.create_table()?)?;
+
+    let events = lua.create_table()?;
+
+    // apollo.events.subscribe(event, handler)
+    events.set(
+        "subscribe",
+        lua.create_function(|lua, (event, handler): (String, Function)| {
+            let subscribers: Table = lua.globals().get(SUBSCRIBERS)?;
+            let handlers =
+                if let Some(handlers) = subscribers.get::<_, Option<Table>>(event.as_str())? {
+                    handlers
+                } else {
+                    let handlers = lua.create_table()?;
+                    subscribers.set(event.as_str(), handlers.clone())?;
+                    handlers
+                };
+            handlers.push(handler)?;
+            tracing::debug!("Subscribed to event: {}", event);
+            Ok(())
+        })?,
+    )?;
+
+    // apollo.events.emit(event, payload?)
+    events.set(
+        "emit",
+        lua.create_function(|lua, (event, payload): (String, Value)| {
+            dispatch_event(lua, &event, &payload)
+        })?,
+    )?;
+
+    apollo.set("events", events)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::register_apollo_module;
+
+    fn create_lua() -> Lua {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+        register_events_module(&lua).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_subscribe_and_emit() {
+        let lua = create_lua();
+
+        let result: String = lua
+            .load(
+                r#"
+                local seen = {}
+                apollo.events.subscribe("track.imported", function(t) table.insert(seen, "a:" .. t.title) end)
+                apollo.events.subscribe("track.imported", function(t) table.insert(seen, "b:" .. t.title) end)
+                local count = apollo.events.emit("track.imported", { title = "Song" })
+                apollo.events.emit("other.event")
+                return count .. " " .. table.concat(seen, ",")
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(result, "2 a:Song,b:Song");
+    }
+
+    #[test]
+    fn test_has_subscribers() {
+        let lua = create_lua();
+        assert!(!has_subscribers(&lua, "track.imported").unwrap());
+
+        lua.load(r#"apollo.events.subscribe("track.imported", function() end)"#)
+            .exec()
+            .unwrap();
+        assert!(has_subscribers(&lua, "track.imported").unwrap());
+    }
+
+    #[test]
+    fn test_dispatch_without_subscribers() {
+        let lua = create_lua();
+        assert_eq!(dispatch_event(&lua, "nothing", &Value::Nil).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_failing_handler() {
+        let lua = create_lua();
+        lua.load(r#"apollo.events.subscribe("boom", function() error("failed") end)"#)
+            .exec()
+            .unwrap();
+        assert!(dispatch_event(&lua, "boom", &Value::Nil).is_err());
+    }
+}
@@ -7,6 +7,15 @@
 //! to Lua, allowing users to write custom import hooks, metadata processors,
 //! and other extensions.
 //!
+//! Plugins can use the `apollo.json` and `apollo.path` helpers, fingerprint
+//! and hash audio files through the `apollo.audio` table, and call Apollo's
+//! metadata source clients through the `apollo.sources` table once enabled
+//! with [`LuaRuntime::enable_sources`].
+//! Playlists can be created and maintained through the `apollo.playlists`
+//! table once a library is attached with [`LuaRuntime::enable_library`].
+//! Besides the fixed hooks, plugins can subscribe to named events with
+//! `apollo.events.subscribe`, which are emitted with [`LuaRuntime::emit_event`].
+//!
 //! # Example
 //!
 //! ```no_run
@@ -27,7 +36,7 @@
 //!     PathBuf::from("/music/song.mp3"),
 //!     "My Song".to_string(),
 //!     "Artist".to_string(),
-//!     Duration::from_secs(180),
+//!     Duration::from_mins(3),
 //! );
 //!
 //! // Run the on_import hook
@@ -36,13 +45,23 @@
 //! # }
 //! ```
 
+mod audio;
 mod bindings;
 mod error;
+mod events;
 mod hooks;
+mod playlists;
 mod plugin;
+mod pool;
 mod runtime;
+mod sandbox;
+mod scheduler;
+mod sources;
+mod util;
 
 pub use error::Error;
-pub use hooks::{HookResult, Hooks};
-pub use plugin::Plugin;
+pub use hooks::{HookResult, HookType, Hooks};
+pub use plugin::{Plugin, PluginCommand, ScheduledTask, load_plugin_metadata};
+pub use pool::LuaWorkerPool;
 pub use runtime::LuaRuntime;
+pub use scheduler::{Scheduler, SchedulerHandle, spawn_scheduler};
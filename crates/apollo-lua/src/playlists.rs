@@ -0,0 +1,361 @@
+//! Lua bindings for playlist management.
+//!
+//! This module exposes the library's playlists under the `apollo.playlists`
+//! table, so plugins can maintain playlists automatically (e.g. "every track
+//! imported this month").
+//!
+//! # Available Functions
+//!
+//! - `apollo.playlists.create(name, description?)` - create a static playlist
+//! - `apollo.playlists.create_smart(name, query, options?)` - create a smart playlist;
+//!   `options` may contain `description`, `sort`, `max_tracks` and `max_duration_secs`;
+//!   `sort` is one of `artist`, `album`, `title`, `added_desc`, `added_asc`,
+//!   `year_desc`, `year_asc` and `random`
+//! - `apollo.playlists.list()` - list all playlists
+//! - `apollo.playlists.get(playlist)` - get a playlist by ID or name, or `nil`
+//! - `apollo.playlists.tracks(playlist)` - get the tracks in a playlist
+//! - `apollo.playlists.add_track(playlist, track_id)` - add a track to a static playlist
+//! - `apollo.playlists.remove_track(playlist, track_id)` - remove a track from a static playlist
+//! - `apollo.playlists.delete(playlist)` - delete a playlist
+//!
+//! Playlists are returned as tables with `id`, `name`, `description`, `kind`,
+//! `query`, `sort`, and `track_ids` fields. Wherever a playlist is expected,
+//! either its ID or its name can be passed.
+
+// Allow these clippy lints for the bindings module since they're mostly noise
+// for the types of closures used in mlua function implementations.
+#![allow(clippy::significant_drop_tightening)]
+#![allow(clippy::needless_pass_by_value)]
+
+use crate::sources::Executor;
+use crate::util::to_lua_value;
+use apollo_core::TrackId;
+use apollo_core::library::Library;
+use apollo_core::playlist::{Playlist, PlaylistId, PlaylistLimit, PlaylistSort};
+use apollo_core::query::Query;
+use mlua::{Lua, Result, Table};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Names of the playlist sort orders, as plugins give them.
+const SORT_NAMES: &str = "artist, album, title, added_desc, added_asc, year_desc, year_asc, random";
+
+/// Parse a sort name into a playlist sort order.
+fn parse_sort(s: &str) -> Result<PlaylistSort> {
+    match s.to_lowercase().replace('_', "").as_str() {
+        "artist" => Ok(PlaylistSort::Artist),
+        "album" => Ok(PlaylistSort::Album),
+        "title" => Ok(PlaylistSort::Title),
+        "addeddesc" => Ok(PlaylistSort::AddedDesc),
+        "addedasc" => Ok(PlaylistSort::AddedAsc),
+        "yeardesc" => Ok(PlaylistSort::YearDesc),
+        "yearasc" => Ok(PlaylistSort::YearAsc),
+        "random" => Ok(PlaylistSort::Random),
+        _ => Err(mlua::Error::runtime(format!(
+            "unknown playlist sort: {s} (expected one of {SORT_NAMES})"
+        ))),
+    }
+}
+
+/// Convert a playlist into a Lua table.
+fn playlist_to_table<'lua>(lua: &'lua Lua, playlist: &Playlist) -> Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set("id", playlist.id.to_string())?;
+    table.set("name", playlist.name.clone())?;
+    table.set("description", playlist.description.clone())?;
+    table.set("kind", playlist.kind.to_string())?;
+    table.set("query", playlist.query.as_ref().map(ToString::to_string))?;
+    table.set("sort", playlist.sort_label())?;
+    table.set(
+        "track_ids",
+        lua.create_sequence_from(playlist.track_ids.iter().map(ToString::to_string))?,
+    )?;
+    Ok(table)
+}
+
+/// Parse a track ID string.
+fn parse_track_id(id: &str) -> Result<TrackId> {
+    Uuid::parse_str(id)
+        .map(TrackId)
+        .map_err(|_| mlua::Error::runtime(format!("invalid track ID: {id}")))
+}
+
+/// Find a playlist by ID or name.
+async fn find_playlist(db: &dyn Library, name_or_id: &str) -> Result<Option<Playlist>> {
+    if let Ok(uuid) = Uuid::parse_str(name_or_id) {
+        return db
+            .get_playlist(&PlaylistId(uuid))
+            .await
+            .map_err(mlua::Error::external);
+    }
+
+    let playlists = db.list_playlists().await.map_err(mlua::Error::external)?;
+    Ok(playlists
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name_or_id)))
+}
+
+/// Find a playlist by ID or name, raising an error if it doesn't exist.
+async fn require_playlist(db: &dyn Library, name_or_id: &str) -> Result<Playlist> {
+    find_playlist(db, name_or_id)
+        .await?
+        .ok_or_else(|| mlua::Error::runtime(format!("playlist not found: {name_or_id}")))
+}
+
+/// Register the `apollo.playlists` table.
+///
+/// # Errors
+///
+/// Returns an error if the `apollo` module is not registered.
+#[allow(clippy::too_many_lines)]
+pub fn register_playlists_module(
+    lua: &Lua,
+    db: Arc<dyn Library>,
+    executor: &Executor,
+) -> Result<()> {
+    let apollo: Table = lua.globals().get("apollo")?;
+    let playlists = lua.create_table()?;
+
+    // apollo.playlists.create(name, description?)
+    let (d, ex) = (Arc::clone(&db), executor.clone());
+    playlists.set(
+        "create",
+        lua.create_function(move |lua, (name, description): (String, Option<String>)| {
+            let mut playlist = Playlist::new_static(name);
+            playlist.description = description;
+            ex.block_on(d.add_playlist(&playlist))?
+                .map_err(mlua::Error::external)?;
+            playlist_to_table(lua, &playlist)
+        })?,
+    )?;
+
+    // apollo.playlists.create_smart(name, query, options?)
+    let (d, ex) = (Arc::clone(&db), executor.clone());
+    playlists.set(
+        "create_smart",
+        lua.create_function(
+            move |lua, (name, query, options): (String, String, Option<Table>)| {
+                let (query, sort_spec) =
+                    Query::parse_sorted(&query).map_err(mlua::Error::external)?;
+                let mut playlist = Playlist::new_smart(name, query).with_sort_spec(sort_spec);
+
+                if let Some(options) = options {
+                    playlist.description = options.get("description")?;
+                    if let Some(sort) = options.get::<_, Option<String>>("sort")? {
+                        playlist.sort = parse_sort(&sort)?;
+                    }
+                    let max_tracks: Option<u32> = options.get("max_tracks")?;
+                    let max_duration_secs: Option<u64> = options.get("max_duration_secs")?;
+                    if max_tracks.is_some() || max_duration_secs.is_some() {
+                        playlist.limit = Some(PlaylistLimit {
+                            max_tracks,
+                            max_duration_secs,
+                        });
+                    }
+                }
+
+                ex.block_on(d.add_playlist(&playlist))?
+                    .map_err(mlua::Error::external)?;
+                playlist_to_table(lua, &playlist)
+            },
+        )?,
+    )?;
+
+    // apollo.playlists.list()
+    let (d, ex) = (Arc::clone(&db), executor.clone());
+    playlists.set(
+        "list",
+        lua.create_function(move |lua, ()| {
+            let all = ex
+                .block_on(d.list_playlists())?
+                .map_err(mlua::Error::external)?;
+            let tables = all
+                .iter()
+                .map(|p| playlist_to_table(lua, p))
+                .collect::<Result<Vec<_>>>()?;
+            lua.create_sequence_from(tables)
+        })?,
+    )?;
+
+    // apollo.playlists.get(playlist)
+    let (d, ex) = (Arc::clone(&db), executor.clone());
+    playlists.set(
+        "get",
+        lua.create_function(move |lua, name_or_id: String| {
+            ex.block_on(find_playlist(d.as_ref(), &name_or_id))??
+                .map(|p| playlist_to_table(lua, &p))
+                .transpose()
+        })?,
+    )?;
+
+    // apollo.playlists.tracks(playlist)
+    let (d, ex) = (Arc::clone(&db), executor.clone());
+    playlists.set(
+        "tracks",
+        lua.create_function(move |lua, name_or_id: String| {
+            let tracks = ex.block_on(async {
+                let playlist = require_playlist(d.as_ref(), &name_or_id).await?;
+                d.get_playlist_tracks(&playlist.id)
+                    .await
+                    .map_err(mlua::Error::external)
+            })??;
+            to_lua_value(lua, &tracks)
+        })?,
+    )?;
+
+    // apollo.playlists.add_track(playlist, track_id)
+    let (d, ex) = (Arc::clone(&db), executor.clone());
+    playlists.set(
+        "add_track",
+        lua.create_function(move |_, (name_or_id, track_id): (String, String)| {
+            let track_id = parse_track_id(&track_id)?;
+            ex.block_on(async {
+                let playlist = require_playlist(d.as_ref(), &name_or_id).await?;
+                if playlist.is_smart() {
+                    return Err(mlua::Error::runtime(
+                        "cannot add tracks to a smart playlist",
+                    ));
+                }
+                d.add_track_to_playlist(&playlist.id, &track_id)
+                    .await
+                    .map_err(mlua::Error::external)
+            })?
+        })?,
+    )?;
+
+    // apollo.playlists.remove_track(playlist, track_id)
+    let (d, ex) = (Arc::clone(&db), executor.clone());
+    playlists.set(
+        "remove_track",
+        lua.create_function(move |_, (name_or_id, track_id): (String, String)| {
+            let track_id = parse_track_id(&track_id)?;
+            ex.block_on(async {
+                let playlist = require_playlist(d.as_ref(), &name_or_id).await?;
+                d.remove_track_from_playlist(&playlist.id, &track_id)
+                    .await
+                    .map_err(mlua::Error::external)
+            })?
+        })?,
+    )?;
+
+    // apollo.playlists.delete(playlist)
+    let (d, ex) = (db, executor.clone());
+    playlists.set(
+        "delete",
+        lua.create_function(move |_, name_or_id: String| {
+            ex.block_on(async {
+                let playlist = require_playlist(d.as_ref(), &name_or_id).await?;
+                d.remove_playlist(&playlist.id)
+                    .await
+                    .map_err(mlua::Error::external)
+            })?
+        })?,
+    )?;
+
+    apollo.set("playlists", playlists)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::register_apollo_module;
+    use apollo_core::Track;
+    use apollo_db::SqliteLibrary;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    async fn create_lua() -> (Lua, Arc<SqliteLibrary>) {
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+        register_playlists_module(&lua, db.clone(), &Executor::default()).unwrap();
+        (lua, db)
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        assert_eq!(parse_sort("year_desc").unwrap(), PlaylistSort::YearDesc);
+        assert_eq!(parse_sort("AddedAsc").unwrap(), PlaylistSort::AddedAsc);
+        assert_eq!(parse_sort("artist").unwrap(), PlaylistSort::Artist);
+        for typo in ["year", "added", "unknown"] {
+            let error = parse_sort(typo).unwrap_err().to_string();
+            assert!(error.contains("year_desc"), "{error}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_and_list_playlists() {
+        let (lua, db) = create_lua().await;
+
+        let track = Track::new(
+            PathBuf::from("/music/song.mp3"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        lua.globals().set("track_id", track.id.to_string()).unwrap();
+
+        let count: usize = lua
+            .load(
+                r#"
+                local p = apollo.playlists.create("Favorites", "My favorites")
+                apollo.playlists.add_track(p.id, track_id)
+                apollo.playlists.create_smart("Queen", "artist:Queen", { sort = "year_desc", max_tracks = 10 })
+                return #apollo.playlists.list()
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let title: String = lua
+            .load(r#"return apollo.playlists.tracks("favorites")[1].title"#)
+            .eval()
+            .unwrap();
+        assert_eq!(title, "Song");
+
+        let playlists = db.list_playlists().await.unwrap();
+        let smart = playlists.iter().find(|p| p.is_smart()).unwrap();
+        assert_eq!(smart.sort, PlaylistSort::YearDesc);
+        assert_eq!(smart.limit.as_ref().unwrap().max_tracks, Some(10));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_and_delete_playlist() {
+        let (lua, db) = create_lua().await;
+
+        let missing: bool = lua
+            .load(r#"return apollo.playlists.get("Nope") == nil"#)
+            .eval()
+            .unwrap();
+        assert!(missing);
+
+        lua.load(
+            r#"
+            apollo.playlists.create("Temporary")
+            apollo.playlists.delete("Temporary")
+            "#,
+        )
+        .exec()
+        .unwrap();
+        assert!(db.list_playlists().await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_add_track_to_smart_playlist_fails() {
+        let (lua, _db) = create_lua().await;
+
+        let ok: bool = lua
+            .load(
+                r#"
+                apollo.playlists.create_smart("Smart", "artist:Queen")
+                return (pcall(apollo.playlists.add_track, "Smart", "550e8400-e29b-41d4-a716-446655440000"))
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert!(!ok);
+    }
+}
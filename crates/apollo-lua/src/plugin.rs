@@ -24,10 +24,50 @@
 //!
 //! return plugin
 //! ```
+//!
+//! Each plugin runs in its own environment, so globals defined by one plugin
+//! are not visible to others. Only the `apollo` API and a safe subset of the
+//! Lua standard library are available.
+//!
+//! # Commands
+//!
+//! Plugins can also register commands that are run from the command line
+//! with `apollo plugin run <name> [args...]`. The handler receives the
+//! remaining arguments as a list of strings.
+//!
+//! ```lua
+//! plugin.commands = {
+//!     {
+//!         name = "hello",
+//!         description = "Say hello",
+//!         handler = function(args)
+//!             apollo.info("Hello, " .. (args[1] or "world"))
+//!         end,
+//!     },
+//! }
+//! ```
+//!
+//! # Scheduled Tasks
+//!
+//! Plugins can register periodic jobs that are run by long-running processes
+//! such as the web server. The handler is either the name of a function in
+//! the plugin table or a function. Intervals combine a number with a unit
+//! of `s`, `m`, `h`, or `d` (e.g. `"90s"`, `"6h"`, `"1h30m"`).
+//!
+//! ```lua
+//! plugin.schedule = { every = "6h", handler = "refresh_lyrics" }
+//!
+//! function plugin.refresh_lyrics()
+//!     apollo.info("Refreshing lyrics")
+//! end
+//! ```
+//!
+//! Multiple tasks can be registered by making `schedule` a list of tables.
 
 use crate::error::{Error, Result};
 use crate::hooks::HookType;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// Metadata about a loaded plugin.
 #[derive(Debug, Clone)]
@@ -44,6 +84,89 @@ pub struct Plugin {
     pub path: PathBuf,
     /// Which hooks this plugin provides.
     pub hooks: Vec<HookType>,
+    /// Commands registered by this plugin.
+    pub commands: Vec<PluginCommand>,
+    /// Periodic tasks registered by this plugin.
+    pub schedules: Vec<ScheduledTask>,
+}
+
+/// A command registered by a plugin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginCommand {
+    /// Name used to invoke the command.
+    pub name: String,
+    /// Short description of what the command does.
+    pub description: String,
+    /// Name of the plugin that registered the command.
+    pub plugin: String,
+}
+
+/// A periodic task registered by a plugin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledTask {
+    /// Name of the handler, unique within the plugin.
+    pub handler: String,
+    /// Name of the plugin that registered the task.
+    pub plugin: String,
+    /// How often the task runs.
+    pub interval: Duration,
+}
+
+impl ScheduledTask {
+    /// Get the key identifying this task in the Lua task registry.
+    #[must_use]
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.plugin, self.handler)
+    }
+}
+
+impl std::fmt::Display for ScheduledTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{} (every {}s)",
+            self.plugin,
+            self.handler,
+            self.interval.as_secs()
+        )
+    }
+}
+
+/// Parse a schedule interval such as `"30m"`, `"6h"`, or `"1h30m"`.
+///
+/// A bare number is interpreted as seconds. Returns `None` for invalid or
+/// zero intervals.
+#[must_use]
+pub fn parse_interval(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    if let Ok(secs) = s.parse::<u64>() {
+        return (secs > 0).then(|| Duration::from_secs(secs));
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let value: u64 = number.parse().ok()?;
+        number.clear();
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return None,
+        };
+        total = total.checked_add(value.checked_mul(unit)?)?;
+    }
+
+    if !number.is_empty() || total == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(total))
 }
 
 impl Plugin {
@@ -57,6 +180,8 @@ impl Plugin {
             author: None,
             path,
             hooks: Vec::new(),
+            commands: Vec::new(),
+            schedules: Vec::new(),
         }
     }
 
@@ -66,6 +191,12 @@ impl Plugin {
         self.hooks.contains(&hook_type)
     }
 
+    /// Check if this plugin registers a command with the given name.
+    #[must_use]
+    pub fn has_command(&self, name: &str) -> bool {
+        self.commands.iter().any(|c| c.name == name)
+    }
+
     /// Get the Lua global table name for this plugin.
     ///
     /// This is used to store the plugin's functions in Lua's global namespace.
@@ -180,6 +311,24 @@ mod tests {
         assert_eq!(plugin.version, "1.0.0");
         assert_eq!(plugin.description, "A test plugin");
         assert!(plugin.hooks.is_empty());
+        assert!(plugin.commands.is_empty());
+        assert!(plugin.schedules.is_empty());
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_interval("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_interval("15m"), Some(Duration::from_mins(15)));
+        assert_eq!(parse_interval("6h"), Some(Duration::from_hours(6)));
+        assert_eq!(parse_interval("1d"), Some(Duration::from_hours(24)));
+        assert_eq!(parse_interval("1h30m"), Some(Duration::from_mins(90)));
+        assert_eq!(parse_interval("0"), None);
+        assert_eq!(parse_interval("6"), Some(Duration::from_secs(6)));
+        assert_eq!(parse_interval("h"), None);
+        assert_eq!(parse_interval("6x"), None);
+        assert_eq!(parse_interval("1h30"), None);
+        assert_eq!(parse_interval(""), None);
     }
 
     #[test]
@@ -0,0 +1,296 @@
+//! Running hooks from async code.
+//!
+//! [`LuaRuntime`] is not `Send` and hooks run synchronously, so calling them
+//! from an async task blocks the executor. [`LuaWorkerPool`] owns one runtime
+//! per dedicated worker thread and accepts jobs over a channel, so async code
+//! can await hook results without blocking.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use apollo_lua::{LuaRuntime, LuaWorkerPool};
+//! use apollo_core::Track;
+//!
+//! # async fn example(track: Track) -> Result<(), apollo_lua::Error> {
+//! let pool = LuaWorkerPool::new(2, || {
+//!     let mut runtime = LuaRuntime::new()?;
+//!     runtime.load_plugins_from_directory("plugins");
+//!     Ok::<_, apollo_lua::Error>(runtime)
+//! })?;
+//!
+//! let (result, track) = pool.run_on_import_async(track).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::hooks::HookResult;
+use crate::runtime::LuaRuntime;
+use apollo_core::{Album, Track};
+use std::fmt::Display;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+/// A unit of work run on a worker's runtime.
+type Job = Box<dyn FnOnce(&LuaRuntime) + Send>;
+
+/// A pool of threads that each own a [`LuaRuntime`].
+///
+/// Every worker creates its own runtime, so plugin state is not shared
+/// between workers. Use a single worker if plugins rely on state persisting
+/// between hook invocations.
+pub struct LuaWorkerPool {
+    /// Channel for submitting jobs.
+    sender: Option<Sender<Job>>,
+    /// Worker threads.
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl LuaWorkerPool {
+    /// Create a pool with the given number of workers.
+    ///
+    /// Each worker calls `init` on its own thread to create its runtime,
+    /// which should load plugins and enable any bindings they need. Workers
+    /// whose initialization fails log the error and exit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a worker thread cannot be spawned.
+    pub fn new<F, E>(workers: usize, init: F) -> Result<Self>
+    where
+        F: Fn() -> std::result::Result<LuaRuntime, E> + Send + Sync + 'static,
+        E: Display,
+    {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let init = Arc::new(init);
+
+        let workers = (0..workers.max(1))
+            .map(|index| {
+                let receiver = Arc::clone(&receiver);
+                let init = Arc::clone(&init);
+                thread::Builder::new()
+                    .name(format!("apollo-lua-{index}"))
+                    .spawn(move || run_worker(index, &receiver, init.as_ref()))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            sender: Some(sender),
+            workers,
+        })
+    }
+
+    /// Run a closure on a worker's runtime and await its result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no worker is available to run the job.
+    pub async fn run<R, F>(&self, f: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&LuaRuntime) -> R + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job = Box::new(move |runtime| {
+            let _ = tx.send(f(runtime));
+        });
+
+        self.sender
+            .as_ref()
+            .ok_or(Error::WorkerUnavailable)?
+            .send(job)
+            .map_err(|_| Error::WorkerUnavailable)?;
+        rx.await.map_err(|_| Error::WorkerUnavailable)
+    }
+
+    /// Run the `on_import` hook for a track.
+    ///
+    /// Returns the hook result together with the possibly modified track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a hook fails or no worker is available.
+    pub async fn run_on_import_async(&self, track: Track) -> Result<(HookResult, Track)> {
+        self.run(move |runtime| {
+            let mut track = track;
+            let result = runtime.run_on_import(&mut track)?;
+            Ok((result, track))
+        })
+        .await?
+    }
+
+    /// Run the `post_import` hook for a track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a hook fails or no worker is available.
+    pub async fn run_post_import_async(&self, track: Track) -> Result<HookResult> {
+        self.run(move |runtime| runtime.run_post_import(&track))
+            .await?
+    }
+
+    /// Run the `on_album_import` hook for an album.
+    ///
+    /// Returns the hook result together with the possibly modified album.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a hook fails or no worker is available.
+    pub async fn run_on_album_import_async(&self, album: Album) -> Result<(HookResult, Album)> {
+        self.run(move |runtime| {
+            let mut album = album;
+            let result = runtime.run_on_album_import(&mut album)?;
+            Ok((result, album))
+        })
+        .await?
+    }
+
+    /// Run the `post_album_import` hook for an album.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a hook fails or no worker is available.
+    pub async fn run_post_album_import_async(&self, album: Album) -> Result<HookResult> {
+        self.run(move |runtime| runtime.run_post_album_import(&album))
+            .await?
+    }
+
+    /// Stop accepting jobs and wait for the workers to finish queued work.
+    pub fn shutdown(mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                warn!("Lua worker thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for LuaWorkerPool {
+    fn drop(&mut self) {
+        self.sender.take();
+    }
+}
+
+/// Create a runtime and process jobs until the channel is closed.
+fn run_worker<F, E>(index: usize, receiver: &Mutex<Receiver<Job>>, init: &F)
+where
+    F: Fn() -> std::result::Result<LuaRuntime, E>,
+    E: Display,
+{
+    let runtime = match init() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            warn!("Failed to start Lua worker {}: {}", index, e);
+            return;
+        }
+    };
+    debug!("Started Lua worker {}", index);
+
+    loop {
+        // Only hold the lock while waiting for the next job
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => break,
+        };
+        match job {
+            Ok(job) => job(&runtime),
+            Err(_) => break,
+        }
+    }
+
+    debug!("Stopped Lua worker {}", index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+
+    fn create_pool(workers: usize) -> (LuaWorkerPool, NamedTempFile) {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"
+            local plugin = {{ name = "upper", version = "1.0.0" }}
+
+            function plugin.on_import(track)
+                track.title = string.upper(track.title)
+                if track.artist == "Skip" then
+                    return "skip"
+                end
+                return "continue"
+            end
+
+            return plugin
+            "#
+        )
+        .unwrap();
+        let path = file.path().to_path_buf();
+
+        let pool = LuaWorkerPool::new(workers, move || {
+            let mut runtime = LuaRuntime::new()?;
+            runtime.load_plugin(&path)?;
+            Ok::<_, Error>(runtime)
+        })
+        .unwrap();
+        (pool, file)
+    }
+
+    fn track(title: &str, artist: &str) -> Track {
+        Track::new(
+            PathBuf::from("/music/song.mp3"),
+            title.to_string(),
+            artist.to_string(),
+            Duration::from_mins(3),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_run_on_import_async() {
+        let (pool, _file) = create_pool(2);
+
+        let (result, modified) = pool
+            .run_on_import_async(track("song", "Artist"))
+            .await
+            .unwrap();
+        assert_eq!(result, HookResult::Continue);
+        assert_eq!(modified.title, "SONG");
+
+        let (result, _) = pool
+            .run_on_import_async(track("song", "Skip"))
+            .await
+            .unwrap();
+        assert_eq!(result, HookResult::Skip);
+
+        pool.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_jobs() {
+        let (pool, _file) = create_pool(2);
+
+        let (a, b, c) = tokio::join!(
+            pool.run_on_import_async(track("a", "X")),
+            pool.run_on_import_async(track("b", "X")),
+            pool.run_on_import_async(track("c", "X")),
+        );
+        assert_eq!(a.unwrap().1.title, "A");
+        assert_eq!(b.unwrap().1.title, "B");
+        assert_eq!(c.unwrap().1.title, "C");
+    }
+
+    #[tokio::test]
+    async fn test_failed_initialization() {
+        let pool = LuaWorkerPool::new(1, || Err::<LuaRuntime, _>("broken")).unwrap();
+        let result = pool.run(|_| ()).await;
+        assert!(matches!(result, Err(Error::WorkerUnavailable)));
+    }
+}
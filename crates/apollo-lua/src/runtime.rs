@@ -1,14 +1,25 @@
 //! The Lua runtime for executing plugins and hooks.
 
+use crate::audio::register_audio_module;
 use crate::bindings::{LuaAlbum, LuaTrack, register_apollo_module};
 use crate::error::{Error, Result};
+use crate::events::{dispatch_event, has_subscribers, register_events_module};
 use crate::hooks::{HookResult, HookType, Hooks};
-use crate::plugin::{Plugin, load_plugin_metadata};
-use apollo_core::{Album, Track};
-use mlua::{Function, Lua, Value};
+use crate::playlists::register_playlists_module;
+use crate::plugin::{Plugin, PluginCommand, ScheduledTask, load_plugin_metadata, parse_interval};
+use crate::sandbox::create_plugin_environment;
+use crate::sources::{Executor, register_coverart, register_musicbrainz, register_sources_module};
+use crate::util::{register_util_modules, to_lua_value};
+use apollo_core::library::Library;
+use apollo_core::{Album, Config, LibraryEvent, Track};
+use apollo_sources::coverart::CoverArtClient;
+use apollo_sources::musicbrainz::CachedMusicBrainzClient;
+use mlua::{Function, Lua, Table, Value, Variadic};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use tracing::{debug, info, warn};
 
 /// The Lua runtime for Apollo plugins.
@@ -21,6 +32,8 @@ pub struct LuaRuntime {
     plugins: HashMap<String, Plugin>,
     /// Registered hooks.
     hooks: Hooks,
+    /// Executor for async calls made from Lua callbacks.
+    executor: Executor,
 }
 
 impl LuaRuntime {
@@ -34,17 +47,65 @@ impl LuaRuntime {
 
         // Register the apollo module
         register_apollo_module(&lua)?;
+        register_audio_module(&lua)?;
+        register_util_modules(&lua)?;
+        register_sources_module(&lua)?;
+        register_events_module(&lua)?;
 
-        // Set up the plugins table
+        // Set up the plugins and commands tables
         lua.globals().set("_plugins", lua.create_table()?)?;
+        lua.globals().set("_commands", lua.create_table()?)?;
+        lua.globals().set("_tasks", lua.create_table()?)?;
+        lua.globals().set("_environments", lua.create_table()?)?;
 
         Ok(Self {
             lua,
             plugins: HashMap::new(),
             hooks: Hooks::new(),
+            executor: Executor::default(),
         })
     }
 
+    /// Enable the network-backed `apollo.sources` functions.
+    ///
+    /// This creates cached, rate-limited clients using the application
+    /// identity from the `MusicBrainz` configuration. The `MusicBrainz`
+    /// functions are only registered if `MusicBrainz` is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a client cannot be created.
+    pub fn enable_sources(&self, config: &Config) -> Result<()> {
+        let mb = &config.musicbrainz;
+
+        if mb.enabled {
+            let client = CachedMusicBrainzClient::with_defaults(
+                &mb.app_name,
+                &mb.app_version,
+                &mb.contact_email,
+            )?;
+            register_musicbrainz(&self.lua, Arc::new(client), &self.executor)?;
+        }
+
+        let client = CoverArtClient::new(&mb.app_name, &mb.app_version)?;
+        register_coverart(&self.lua, Arc::new(client), &self.executor)?;
+
+        debug!("Enabled Lua source bindings");
+        Ok(())
+    }
+
+    /// Enable the `apollo.playlists` functions backed by the given library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the functions cannot be registered.
+    pub fn enable_library(&self, db: Arc<dyn Library>) -> Result<()> {
+        register_playlists_module(&self.lua, db, &self.executor)?;
+
+        debug!("Enabled Lua library bindings");
+        Ok(())
+    }
+
     /// Load a plugin from a file.
     ///
     /// The plugin script should return a table with metadata and hook functions.
@@ -60,21 +121,33 @@ impl LuaRuntime {
         let path = path.as_ref();
 
         // Load metadata first (without executing)
-        let plugin = load_plugin_metadata(path)?;
+        let mut plugin = load_plugin_metadata(path)?;
         let plugin_name = plugin.name.clone();
 
         info!("Loading plugin: {} v{}", plugin.name, plugin.version);
 
-        // Read and execute the plugin script
+        // Read and execute the plugin script in its own environment
         let script = fs::read_to_string(path)?;
-        let plugin_table: mlua::Table =
-            self.lua
-                .load(&script)
-                .eval()
-                .map_err(|e| Error::PluginLoad {
-                    name: plugin_name.clone(),
-                    reason: e.to_string(),
-                })?;
+        let env = create_plugin_environment(&self.lua)?;
+        let plugin_table: Table = self
+            .lua
+            .load(&script)
+            .set_name(path.to_string_lossy().to_string())
+            .set_environment(env.clone())
+            .eval()
+            .map_err(|e| Error::PluginLoad {
+                name: plugin_name.clone(),
+                reason: e.to_string(),
+            })?;
+
+        // Register commands
+        plugin.commands = self.register_commands(&plugin_name, &plugin_table)?;
+
+        // Register scheduled tasks
+        plugin.schedules = self.register_schedules(&plugin_name, &plugin_table)?;
+
+        let environments: Table = self.lua.globals().get("_environments")?;
+        environments.set(plugin_name.as_str(), env)?;
 
         // Store the plugin table in globals
         let table_name = plugin.lua_table_name();
@@ -142,6 +215,121 @@ impl LuaRuntime {
         self.plugins.values().collect()
     }
 
+    /// Get all commands registered by loaded plugins, sorted by name.
+    #[must_use]
+    pub fn commands(&self) -> Vec<&PluginCommand> {
+        let mut commands: Vec<&PluginCommand> = self
+            .plugins
+            .values()
+            .flat_map(|p| p.commands.iter())
+            .collect();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+
+    /// Get a registered command by name.
+    #[must_use]
+    pub fn get_command(&self, name: &str) -> Option<&PluginCommand> {
+        self.plugins
+            .values()
+            .flat_map(|p| p.commands.iter())
+            .find(|c| c.name == name)
+    }
+
+    /// Run a plugin command with the given arguments.
+    ///
+    /// The arguments are passed to the handler as a Lua list of strings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command is not registered or the handler fails.
+    pub fn run_command(&self, name: &str, args: &[String]) -> Result<()> {
+        let commands: Table = self.lua.globals().get("_commands")?;
+        let handler: Option<Function> = commands.get(name)?;
+        let handler = handler.ok_or_else(|| Error::CommandNotFound {
+            name: name.to_string(),
+        })?;
+
+        let lua_args = self
+            .lua
+            .create_sequence_from(args.iter().map(String::as_str))?;
+        handler
+            .call::<_, ()>(lua_args)
+            .map_err(|e| Error::CommandFailed {
+                command: name.to_string(),
+                reason: e.to_string(),
+            })
+    }
+
+    /// Emit an event to all plugins subscribed with `apollo.events.subscribe`.
+    ///
+    /// The payload is converted to a plain Lua value. Returns the number of
+    /// handlers that were called.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload cannot be converted or a handler fails.
+    pub fn emit_event<T: Serialize>(&self, event: &str, payload: &T) -> Result<usize> {
+        let payload = to_lua_value(&self.lua, payload)?;
+        dispatch_event(&self.lua, event, &payload).map_err(|e| Error::EventFailed {
+            event: event.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Emit a library change event to subscribed plugins.
+    ///
+    /// The event is emitted under its [name](LibraryEvent::name), such as
+    /// `track.added`, with the event as payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a handler fails.
+    pub fn emit_library_event(&self, event: &LibraryEvent) -> Result<usize> {
+        self.emit_event(event.name(), event)
+    }
+
+    /// Check if any plugin is subscribed to an event.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the subscriber table cannot be read.
+    pub fn has_subscribers(&self, event: &str) -> Result<bool> {
+        Ok(has_subscribers(&self.lua, event)?)
+    }
+
+    /// Get all scheduled tasks registered by loaded plugins.
+    #[must_use]
+    pub fn scheduled_tasks(&self) -> Vec<&ScheduledTask> {
+        let mut tasks: Vec<&ScheduledTask> = self
+            .plugins
+            .values()
+            .flat_map(|p| p.schedules.iter())
+            .collect();
+        tasks.sort_by_key(|t| t.key());
+        tasks
+    }
+
+    /// Run a scheduled task once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the task is not registered or the handler fails.
+    pub fn run_scheduled_task(&self, task: &ScheduledTask) -> Result<()> {
+        let key = task.key();
+        let tasks: Table = self.lua.globals().get("_tasks")?;
+        let handler: Option<Function> = tasks.get(key.as_str())?;
+        let handler = handler.ok_or_else(|| Error::TaskFailed {
+            task: key.clone(),
+            reason: "task is not registered".to_string(),
+        })?;
+
+        handler.call::<_, ()>(()).map_err(|e| Error::TaskFailed {
+            task: key,
+            reason: e.to_string(),
+        })
+    }
+
     /// Check if any hooks are registered for a hook type.
     #[must_use]
     pub fn has_hooks(&self, hook_type: HookType) -> bool {
@@ -263,6 +451,227 @@ impl LuaRuntime {
         Ok(result)
     }
 
+    /// Run a Lua script file with the given arguments.
+    ///
+    /// The arguments are available to the script as the global `arg` list
+    /// and as the chunk's varargs (`...`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script cannot be read or fails to execute.
+    pub fn run_script<P: AsRef<Path>>(&self, path: P, args: &[String]) -> Result<()> {
+        let path = path.as_ref();
+        let script = fs::read_to_string(path)?;
+
+        let arg_table = self
+            .lua
+            .create_sequence_from(args.iter().map(String::as_str))?;
+        arg_table.set(0, path.to_string_lossy().to_string())?;
+        self.lua.globals().set("arg", arg_table)?;
+
+        let chunk = self
+            .lua
+            .load(&script)
+            .set_name(path.to_string_lossy().to_string());
+        chunk.call::<_, ()>(args.iter().map(String::as_str).collect::<Variadic<_>>())?;
+        Ok(())
+    }
+
+    /// Evaluate a line of input interactively, as in a REPL.
+    ///
+    /// Input is first tried as an expression and otherwise executed as a
+    /// statement. The returned values are converted with Lua's `tostring`.
+    /// Returns `Ok(None)` if the input is an incomplete statement and more
+    /// lines are needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input is invalid or fails to execute.
+    pub fn eval_interactive(&self, input: &str) -> Result<Option<Vec<String>>> {
+        let function = match self
+            .lua
+            .load(format!("return {input}"))
+            .set_name("=stdin")
+            .into_function()
+        {
+            Ok(function) => function,
+            Err(_) => match self.lua.load(input).set_name("=stdin").into_function() {
+                Ok(function) => function,
+                Err(mlua::Error::SyntaxError {
+                    incomplete_input: true,
+                    ..
+                }) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        let values: Variadic<Value> = function.call(())?;
+        let tostring: Function = self.lua.globals().get("tostring")?;
+        let output = values
+            .into_iter()
+            .map(|value| tostring.call::<_, String>(value))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        Ok(Some(output))
+    }
+
+    /// Evaluate Lua code in a loaded plugin's environment.
+    ///
+    /// This gives access to the plugin's own globals, which is useful for
+    /// debugging plugins.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded or the code fails to execute.
+    pub fn eval_in_plugin<T: for<'a> mlua::FromLua<'a>>(
+        &self,
+        plugin: &str,
+        code: &str,
+    ) -> Result<T> {
+        let environments: Table = self.lua.globals().get("_environments")?;
+        let env: Table = environments
+            .get::<_, Option<Table>>(plugin)?
+            .ok_or_else(|| Error::PluginNotLoaded {
+                name: plugin.to_string(),
+            })?;
+        let result = self.lua.load(code).set_environment(env).eval()?;
+        Ok(result)
+    }
+
+    /// Register the commands declared in a plugin's `commands` table.
+    ///
+    /// Command names are unique: a name that another plugin declared, or
+    /// that the plugin declares twice, is an error.
+    fn register_commands(
+        &self,
+        plugin_name: &str,
+        plugin_table: &Table,
+    ) -> Result<Vec<PluginCommand>> {
+        let Some(declared) = plugin_table.get::<_, Option<Table>>("commands")? else {
+            return Ok(Vec::new());
+        };
+
+        let registry: Table = self.lua.globals().get("_commands")?;
+        let mut commands = Vec::new();
+        let mut handlers = Vec::new();
+
+        for entry in declared.sequence_values::<Table>() {
+            let entry = entry.map_err(|e| Error::InvalidMetadata {
+                reason: format!("invalid command entry: {e}"),
+            })?;
+
+            let name: String = entry.get("name").map_err(|_| Error::InvalidMetadata {
+                reason: "command must have a 'name' field".to_string(),
+            })?;
+            let handler: Function = entry.get("handler").map_err(|_| Error::InvalidMetadata {
+                reason: format!("command '{name}' must have a 'handler' function"),
+            })?;
+            let description: Option<String> = entry.get("description")?;
+
+            // A plugin that is loaded again may declare its own commands again
+            let taken = self
+                .get_command(&name)
+                .filter(|command| command.plugin != plugin_name)
+                .map(|command| command.plugin.as_str())
+                .or_else(|| {
+                    handlers
+                        .iter()
+                        .any(|(command, _)| command == &name)
+                        .then_some(plugin_name)
+                });
+            if let Some(owner) = taken {
+                return Err(Error::InvalidMetadata {
+                    reason: format!("command '{name}' is already declared by {owner}"),
+                });
+            }
+
+            commands.push(PluginCommand {
+                name: name.clone(),
+                description: description.unwrap_or_default(),
+                plugin: plugin_name.to_string(),
+            });
+            handlers.push((name, handler));
+        }
+
+        // Nothing is registered unless every command is valid
+        for (name, handler) in handlers {
+            registry.set(name.as_str(), handler)?;
+            debug!("Registered command: {} for {}", name, plugin_name);
+        }
+
+        Ok(commands)
+    }
+
+    /// Register the scheduled tasks declared in a plugin's `schedule` field.
+    fn register_schedules(
+        &self,
+        plugin_name: &str,
+        plugin_table: &Table,
+    ) -> Result<Vec<ScheduledTask>> {
+        let Some(declared) = plugin_table.get::<_, Option<Table>>("schedule")? else {
+            return Ok(Vec::new());
+        };
+
+        // Accept both a single task table and a list of task tables
+        let entries: Vec<Table> = if declared.contains_key("every")? {
+            vec![declared]
+        } else {
+            declared
+                .sequence_values::<Table>()
+                .collect::<mlua::Result<_>>()
+                .map_err(|e| Error::InvalidMetadata {
+                    reason: format!("invalid schedule entry: {e}"),
+                })?
+        };
+
+        let registry: Table = self.lua.globals().get("_tasks")?;
+        let mut tasks = Vec::new();
+
+        for (index, entry) in entries.into_iter().enumerate() {
+            let every: String = entry.get("every").map_err(|_| Error::InvalidMetadata {
+                reason: "scheduled task must have an 'every' field".to_string(),
+            })?;
+            let interval = parse_interval(&every).ok_or_else(|| Error::InvalidMetadata {
+                reason: format!("invalid schedule interval: {every}"),
+            })?;
+
+            let (handler, function) = match entry.get::<_, Value>("handler")? {
+                Value::String(name) => {
+                    let name = name.to_str()?.to_string();
+                    let function: Function =
+                        plugin_table
+                            .get(name.as_str())
+                            .map_err(|_| Error::InvalidMetadata {
+                                reason: format!("scheduled handler '{name}' is not a function"),
+                            })?;
+                    (name, function)
+                }
+                Value::Function(function) => {
+                    let name: Option<String> = entry.get("name")?;
+                    (
+                        name.unwrap_or_else(|| format!("task{}", index + 1)),
+                        function,
+                    )
+                }
+                _ => {
+                    return Err(Error::InvalidMetadata {
+                        reason: "scheduled task must have a 'handler' field".to_string(),
+                    });
+                }
+            };
+
+            let task = ScheduledTask {
+                handler,
+                plugin: plugin_name.to_string(),
+                interval,
+            };
+            registry.set(task.key(), function)?;
+            debug!("Registered scheduled task: {}", task);
+            tasks.push(task);
+        }
+
+        Ok(tasks)
+    }
+
     /// Run a hook that operates on a track.
     fn run_track_hook(&self, hook_type: HookType, track: &mut Track) -> Result<HookResult> {
         let callbacks = self.hooks.get(hook_type);
@@ -411,7 +820,9 @@ fn parse_hook_result(value: &Value) -> HookResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use apollo_db::SqliteLibrary;
     use std::io::Write;
+    use std::path::PathBuf;
     use std::time::Duration;
     use tempfile::NamedTempFile;
 
@@ -420,7 +831,7 @@ mod tests {
             std::path::PathBuf::from("/music/test.mp3"),
             "Test Song".to_string(),
             "Test Artist".to_string(),
-            Duration::from_secs(180),
+            Duration::from_mins(3),
         )
     }
 
@@ -645,6 +1056,322 @@ mod tests {
         assert_eq!(album.year, Some(2024));
     }
 
+    #[test]
+    fn test_plugin_commands() {
+        let mut runtime = LuaRuntime::new().unwrap();
+
+        let plugin_file = create_plugin_file(
+            r#"
+            local plugin = {
+                name = "command_test",
+                version = "1.0.0",
+                description = "Test plugin commands",
+            }
+
+            plugin.commands = {
+                {
+                    name = "greet",
+                    description = "Store a greeting",
+                    handler = function(args)
+                        greeting = "hello " .. table.concat(args, " ")
+                    end,
+                },
+            }
+
+            return plugin
+        "#,
+        );
+
+        let plugin = runtime.load_plugin(plugin_file.path()).unwrap();
+        assert!(plugin.has_command("greet"));
+
+        let command = runtime.get_command("greet").unwrap();
+        assert_eq!(command.plugin, "command_test");
+        assert_eq!(command.description, "Store a greeting");
+
+        runtime
+            .run_command("greet", &["big".to_string(), "world".to_string()])
+            .unwrap();
+        let greeting: String = runtime
+            .eval_in_plugin("command_test", "return greeting")
+            .unwrap();
+        assert_eq!(greeting, "hello big world");
+    }
+
+    #[test]
+    fn test_duplicate_command_names_are_rejected() {
+        let mut runtime = LuaRuntime::new().unwrap();
+
+        for (name, message) in [("first", "one"), ("second", "two")] {
+            let plugin_file = create_plugin_file(&format!(
+                r#"
+                local plugin = {{ name = "{name}", version = "1.0.0" }}
+                plugin.commands = {{
+                    {{ name = "{name}-only", handler = function() end }},
+                    {{ name = "shared", handler = function() message = "{message}" end }},
+                }}
+                return plugin
+            "#
+            ));
+            let result = runtime.load_plugin(plugin_file.path());
+            if name == "first" {
+                result.unwrap();
+            } else {
+                assert!(matches!(result, Err(Error::InvalidMetadata { .. })));
+            }
+        }
+
+        assert!(runtime.get_plugin("second").is_none());
+        assert!(runtime.get_command("second-only").is_none());
+        assert!(runtime.run_command("second-only", &[]).is_err());
+        assert_eq!(runtime.get_command("shared").unwrap().plugin, "first");
+        runtime.run_command("shared", &[]).unwrap();
+        let message: String = runtime.eval_in_plugin("first", "return message").unwrap();
+        assert_eq!(message, "one");
+
+        let plugin_file = create_plugin_file(
+            r#"
+            local plugin = { name = "twice", version = "1.0.0" }
+            plugin.commands = {
+                { name = "again", handler = function() end },
+                { name = "again", handler = function() end },
+            }
+            return plugin
+        "#,
+        );
+        assert!(matches!(
+            runtime.load_plugin(plugin_file.path()),
+            Err(Error::InvalidMetadata { .. })
+        ));
+    }
+
+    #[test]
+    fn test_plugins_are_isolated() {
+        let mut runtime = LuaRuntime::new().unwrap();
+
+        for (name, value) in [("first", 1), ("second", 2)] {
+            let plugin_file = create_plugin_file(&format!(
+                r#"
+                local plugin = {{ name = "{name}", version = "1.0.0" }}
+                function helper() return {value} end
+                plugin.commands = {{
+                    {{ name = "{name}", handler = function() result = helper() end }},
+                }}
+                return plugin
+            "#
+            ));
+            runtime.load_plugin(plugin_file.path()).unwrap();
+        }
+
+        runtime.run_command("first", &[]).unwrap();
+        runtime.run_command("second", &[]).unwrap();
+
+        let first: i64 = runtime.eval_in_plugin("first", "return result").unwrap();
+        let second: i64 = runtime.eval_in_plugin("second", "return result").unwrap();
+        assert_eq!((first, second), (1, 2));
+
+        let leaked: bool = runtime.eval("return helper ~= nil").unwrap();
+        assert!(!leaked);
+
+        let result = runtime.eval_in_plugin::<i64>("missing", "return 1");
+        assert!(matches!(result, Err(Error::PluginNotLoaded { .. })));
+    }
+
+    #[test]
+    fn test_plugin_events() {
+        let mut runtime = LuaRuntime::new().unwrap();
+
+        let plugin_file = create_plugin_file(
+            r#"
+            local plugin = { name = "event_test", version = "1.0.0" }
+
+            apollo.events.subscribe("track.imported", function(track)
+                imported = track.title .. " by " .. track.artist
+            end)
+
+            return plugin
+        "#,
+        );
+        runtime.load_plugin(plugin_file.path()).unwrap();
+        assert!(runtime.has_subscribers("track.imported").unwrap());
+        assert!(!runtime.has_subscribers("track.removed").unwrap());
+
+        let track = Track::new(
+            PathBuf::from("/music/song.mp3"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        assert_eq!(runtime.emit_event("track.imported", &track).unwrap(), 1);
+        assert_eq!(runtime.emit_event("track.removed", &track).unwrap(), 0);
+
+        let imported: String = runtime
+            .eval_in_plugin("event_test", "return imported")
+            .unwrap();
+        assert_eq!(imported, "Song by Artist");
+    }
+
+    #[test]
+    fn test_plugin_schedules() {
+        let mut runtime = LuaRuntime::new().unwrap();
+
+        let plugin_file = create_plugin_file(
+            r#"
+            local plugin = {
+                name = "schedule_test",
+                version = "1.0.0",
+            }
+
+            plugin.schedule = {
+                { every = "6h", handler = "refresh" },
+                { every = "30m", handler = function() cleaned = true end },
+            }
+
+            function plugin.refresh()
+                refreshed = true
+            end
+
+            return plugin
+        "#,
+        );
+
+        let plugin = runtime.load_plugin(plugin_file.path()).unwrap();
+        assert_eq!(plugin.schedules.len(), 2);
+
+        let tasks: Vec<ScheduledTask> = runtime.scheduled_tasks().into_iter().cloned().collect();
+        assert_eq!(tasks[0].handler, "refresh");
+        assert_eq!(tasks[0].interval, std::time::Duration::from_hours(6));
+        assert_eq!(tasks[1].handler, "task2");
+
+        for task in &tasks {
+            runtime.run_scheduled_task(task).unwrap();
+        }
+        let done: bool = runtime
+            .eval_in_plugin("schedule_test", "return refreshed and cleaned")
+            .unwrap();
+        assert!(done);
+    }
+
+    #[test]
+    fn test_plugin_schedule_invalid_interval() {
+        let mut runtime = LuaRuntime::new().unwrap();
+
+        let plugin_file = create_plugin_file(
+            r#"
+            local plugin = {
+                name = "bad_schedule",
+                version = "1.0.0",
+                schedule = { every = "often", handler = function() end },
+            }
+            return plugin
+        "#,
+        );
+
+        let result = runtime.load_plugin(plugin_file.path());
+        assert!(matches!(result, Err(Error::InvalidMetadata { .. })));
+    }
+
+    #[test]
+    fn test_plugin_command_errors() {
+        let mut runtime = LuaRuntime::new().unwrap();
+
+        let plugin_file = create_plugin_file(
+            r#"
+            local plugin = {
+                name = "failing_command",
+                version = "1.0.0",
+            }
+
+            plugin.commands = {
+                { name = "fail", handler = function(args) error("boom") end },
+            }
+
+            return plugin
+        "#,
+        );
+
+        runtime.load_plugin(plugin_file.path()).unwrap();
+
+        let result = runtime.run_command("fail", &[]);
+        assert!(matches!(result, Err(Error::CommandFailed { .. })));
+
+        let result = runtime.run_command("missing", &[]);
+        assert!(matches!(result, Err(Error::CommandNotFound { .. })));
+    }
+
+    #[test]
+    fn test_enable_sources() {
+        let runtime = LuaRuntime::new().unwrap();
+
+        let mut config = Config::default();
+        config.musicbrainz.enabled = false;
+        runtime.enable_sources(&config).unwrap();
+
+        let has_search: bool = runtime
+            .eval("return apollo.sources.musicbrainz.search_recordings ~= nil")
+            .unwrap();
+        assert!(!has_search);
+        let has_art: bool = runtime
+            .eval("return apollo.sources.coverart.release_art ~= nil")
+            .unwrap();
+        assert!(has_art);
+
+        config.musicbrainz.enabled = true;
+        runtime.enable_sources(&config).unwrap();
+        let has_search: bool = runtime
+            .eval("return apollo.sources.musicbrainz.search_recordings ~= nil")
+            .unwrap();
+        assert!(has_search);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_enable_library() {
+        let runtime = LuaRuntime::new().unwrap();
+        let has_playlists: bool = runtime.eval("return apollo.playlists ~= nil").unwrap();
+        assert!(!has_playlists);
+
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        runtime.enable_library(db.clone()).unwrap();
+
+        runtime.exec(r#"apollo.playlists.create("Mix")"#).unwrap();
+        assert_eq!(db.list_playlists().await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_run_script() {
+        let runtime = LuaRuntime::new().unwrap();
+
+        let script = create_plugin_file(
+            r#"
+            local first = ...
+            joined = first .. ":" .. arg[2] .. ":" .. #arg
+        "#,
+        );
+
+        runtime
+            .run_script(script.path(), &["a".to_string(), "b".to_string()])
+            .unwrap();
+        let joined: String = runtime.eval("return joined").unwrap();
+        assert_eq!(joined, "a:b:2");
+    }
+
+    #[test]
+    fn test_eval_interactive() {
+        let runtime = LuaRuntime::new().unwrap();
+
+        let output = runtime.eval_interactive("1 + 2, 'x'").unwrap();
+        assert_eq!(output, Some(vec!["3".to_string(), "x".to_string()]));
+
+        let output = runtime.eval_interactive("answer = 42").unwrap();
+        assert_eq!(output, Some(vec![]));
+        let answer: i64 = runtime.eval("return answer").unwrap();
+        assert_eq!(answer, 42);
+
+        assert_eq!(runtime.eval_interactive("for i = 1, 3 do").unwrap(), None);
+        assert!(runtime.eval_interactive("1 +* 2").is_err());
+        assert!(runtime.eval_interactive("error('boom')").is_err());
+    }
+
     #[test]
     fn test_parse_hook_result() {
         assert_eq!(parse_hook_result(&Value::Nil), HookResult::Continue);
@@ -0,0 +1,201 @@
+//! Isolated environments for plugins.
+//!
+//! Each plugin is loaded with its own environment table instead of the shared
+//! globals, so helpers defined by one plugin cannot clobber another's. The
+//! environment contains the `apollo` API and a whitelisted subset of the Lua
+//! standard library. Modules that can touch the file system or load code
+//! (`io`, `package`, `require`, `load`, `dofile`, `debug`) are not available.
+//! `getmetatable` only sees the metatables of tables, as the one shared by
+//! all strings leads to the real `string` library.
+
+use mlua::{Function, Lua, Result, Table, Value};
+
+/// Global functions and values copied into plugin environments.
+const SAFE_GLOBALS: &[&str] = &[
+    "assert",
+    "error",
+    "ipairs",
+    "next",
+    "pairs",
+    "pcall",
+    "print",
+    "rawequal",
+    "rawget",
+    "rawlen",
+    "rawset",
+    "select",
+    "setmetatable",
+    "tonumber",
+    "tostring",
+    "type",
+    "xpcall",
+    "_VERSION",
+];
+
+/// Standard library tables copied into plugin environments.
+const SAFE_LIBRARIES: &[&str] = &["coroutine", "math", "string", "table", "utf8"];
+
+/// Functions from the `os` library available to plugins.
+const SAFE_OS_FUNCTIONS: &[&str] = &["clock", "date", "difftime", "getenv", "time"];
+
+/// Create a shallow copy of a table.
+fn copy_table<'lua>(lua: &'lua Lua, source: &Table<'lua>) -> Result<Table<'lua>> {
+    let copy = lua.create_table()?;
+    for pair in source.clone().pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        copy.set(key, value)?;
+    }
+    Ok(copy)
+}
+
+/// Create a `getmetatable` that gives the metatables of tables only.
+///
+/// Strings share one metatable whose `__index` is the global `string`
+/// table, so handing it out would let a plugin change string methods for
+/// every other plugin.
+fn create_getmetatable(lua: &Lua) -> Result<Function<'_>> {
+    lua.create_function(|_, value: Value| {
+        let Value::Table(table) = value else {
+            return Ok(Value::Nil);
+        };
+        let Some(metatable) = table.get_metatable() else {
+            return Ok(Value::Nil);
+        };
+        match metatable.raw_get::<_, Value>("__metatable")? {
+            Value::Nil => Ok(Value::Table(metatable)),
+            protected => Ok(protected),
+        }
+    })
+}
+
+/// Create a new isolated environment for a plugin.
+///
+/// Standard library tables are copied, so a plugin modifying e.g. `string`
+/// does not affect other plugins, and the metatable shared by strings is out
+/// of reach. The `apollo` table is shared.
+///
+/// # Errors
+///
+/// Returns an error if the environment cannot be created.
+pub fn create_plugin_environment(lua: &Lua) -> Result<Table<'_>> {
+    let globals = lua.globals();
+    let env = lua.create_table()?;
+
+    for name in SAFE_GLOBALS {
+        env.set(*name, globals.get::<_, Value>(*name)?)?;
+    }
+    env.set("getmetatable", create_getmetatable(lua)?)?;
+
+    for name in SAFE_LIBRARIES {
+        if let Some(library) = globals.get::<_, Option<Table>>(*name)? {
+            env.set(*name, copy_table(lua, &library)?)?;
+        }
+    }
+
+    if let Some(os) = globals.get::<_, Option<Table>>("os")? {
+        let safe_os = lua.create_table()?;
+        for name in SAFE_OS_FUNCTIONS {
+            safe_os.set(*name, os.get::<_, Value>(*name)?)?;
+        }
+        env.set("os", safe_os)?;
+    }
+
+    env.set("apollo", globals.get::<_, Value>("apollo")?)?;
+    env.set("_G", env.clone())?;
+
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::register_apollo_module;
+
+    fn create_lua() -> Lua {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_environment_contents() {
+        let lua = create_lua();
+        let env = create_plugin_environment(&lua).unwrap();
+
+        let result: bool = lua
+            .load(
+                r#"
+                return apollo ~= nil
+                    and string.upper("a") == "A"
+                    and type(os.time()) == "number"
+                    and io == nil
+                    and require == nil
+                    and load == nil
+                    and os.execute == nil
+                "#,
+            )
+            .set_environment(env)
+            .eval()
+            .unwrap();
+        assert!(result);
+    }
+
+    #[test]
+    fn test_environments_are_isolated() {
+        let lua = create_lua();
+        let first = create_plugin_environment(&lua).unwrap();
+        let second = create_plugin_environment(&lua).unwrap();
+
+        lua.load("helper = 1; string.shout = string.upper")
+            .set_environment(first)
+            .exec()
+            .unwrap();
+
+        let leaked: bool = lua
+            .load("return helper ~= nil or string.shout ~= nil")
+            .set_environment(second)
+            .eval()
+            .unwrap();
+        assert!(!leaked);
+
+        let global: Value = lua.globals().get("helper").unwrap();
+        assert!(global.is_nil());
+    }
+
+    #[test]
+    fn test_string_methods_are_isolated() {
+        let lua = create_lua();
+        let first = create_plugin_environment(&lua).unwrap();
+        let second = create_plugin_environment(&lua).unwrap();
+
+        let reached: bool = lua
+            .load(
+                r#"
+                local reached = getmetatable("") ~= nil
+                string.upper = function() return "clobbered" end
+                local ok = pcall(function()
+                    getmetatable("").__index.upper = function() return "clobbered" end
+                end)
+                return reached or ok
+                "#,
+            )
+            .set_environment(first)
+            .eval()
+            .unwrap();
+        assert!(!reached);
+
+        let upper: String = lua
+            .load(r#"return ("x"):upper() .. string.upper("y")"#)
+            .set_environment(second)
+            .eval()
+            .unwrap();
+        assert_eq!(upper, "XY");
+
+        let protected: String = lua
+            .load(r#"return getmetatable(setmetatable({}, { __metatable = "locked" }))"#)
+            .set_environment(create_plugin_environment(&lua).unwrap())
+            .eval()
+            .unwrap();
+        assert_eq!(protected, "locked");
+    }
+}
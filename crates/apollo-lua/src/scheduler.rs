@@ -0,0 +1,392 @@
+//! Periodic execution of plugin scheduled tasks.
+//!
+//! The Lua VM is not thread-safe, so the scheduler runs on a dedicated thread
+//! that owns its own [`LuaRuntime`]. Long-running processes like the web
+//! server start it with [`spawn_scheduler`] and stop it through the returned
+//! [`SchedulerHandle`].
+//!
+//! The same thread delivers library change events to plugins subscribed
+//! with `apollo.events.subscribe`, and runs the `on_close` hooks when the
+//! scheduler is stopped.
+
+use crate::hooks::HookType;
+use crate::plugin::ScheduledTask;
+use crate::runtime::LuaRuntime;
+use apollo_core::event::{EventReceiver, LibraryEvent};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::TryRecvError;
+use tracing::{debug, info, warn};
+
+/// How often to check for library events while waiting for tasks.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Tracks when each scheduled task is next due.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    /// Tasks paired with their next run time.
+    entries: Vec<(ScheduledTask, Instant)>,
+}
+
+impl Scheduler {
+    /// Create a scheduler whose tasks first run one interval after `now`.
+    #[must_use]
+    pub fn new(tasks: impl IntoIterator<Item = ScheduledTask>, now: Instant) -> Self {
+        let entries = tasks
+            .into_iter()
+            .map(|task| {
+                let due = now + task.interval;
+                (task, due)
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Check if there are no tasks to schedule.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the time at which the next task is due.
+    #[must_use]
+    pub fn next_due(&self) -> Option<Instant> {
+        self.entries.iter().map(|(_, due)| *due).min()
+    }
+
+    /// Take all tasks that are due at `now` and reschedule them.
+    pub fn take_due(&mut self, now: Instant) -> Vec<ScheduledTask> {
+        let mut due_tasks = Vec::new();
+        for (task, due) in &mut self.entries {
+            if *due <= now {
+                due_tasks.push(task.clone());
+                *due = now + task.interval;
+            }
+        }
+        due_tasks
+    }
+}
+
+/// Handle to a running scheduler thread.
+///
+/// Dropping the handle stops the scheduler without waiting for it.
+pub struct SchedulerHandle {
+    /// Channel used to signal shutdown.
+    stop: Option<Sender<()>>,
+    /// The scheduler thread.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SchedulerHandle {
+    /// Stop the scheduler and wait for a running task to finish.
+    pub fn stop(mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            warn!("Scheduler thread panicked");
+        }
+    }
+}
+
+impl Drop for SchedulerHandle {
+    fn drop(&mut self) {
+        self.stop.take();
+    }
+}
+
+/// Spawn a thread that runs plugin scheduled tasks.
+///
+/// The runtime is created on the scheduler thread by calling `init`, which
+/// should load plugins and enable any bindings they need. If `events` is
+/// given, library events received on it are emitted to subscribed plugins.
+/// The thread exits immediately if no tasks are registered, no plugin
+/// subscribes to library events, and no plugin has an `on_close` hook. Task
+/// and handler failures are logged and do not stop the scheduler.
+///
+/// # Errors
+///
+/// Returns an error if the thread cannot be spawned.
+pub fn spawn_scheduler<F, E>(
+    init: F,
+    events: Option<EventReceiver>,
+) -> std::io::Result<SchedulerHandle>
+where
+    F: FnOnce() -> std::result::Result<LuaRuntime, E> + Send + 'static,
+    E: std::fmt::Display,
+{
+    let (stop, stopped) = mpsc::channel::<()>();
+
+    let thread = thread::Builder::new()
+        .name("apollo-scheduler".to_string())
+        .spawn(move || {
+            let runtime = match init() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    warn!("Failed to start plugin scheduler: {}", e);
+                    return;
+                }
+            };
+
+            let tasks = runtime.scheduled_tasks().into_iter().cloned();
+            let mut scheduler = Scheduler::new(tasks, Instant::now());
+            let mut events = events.filter(|_| has_event_subscribers(&runtime));
+            let close_hooks = runtime.has_hooks(HookType::OnClose);
+            if scheduler.is_empty() && events.is_none() && !close_hooks {
+                debug!("No scheduled plugin tasks or event subscriptions");
+                return;
+            }
+            info!("Started plugin scheduler");
+
+            loop {
+                let next_task = scheduler
+                    .next_due()
+                    .map(|next| next.saturating_duration_since(Instant::now()));
+                let timeout = match (next_task, events.is_some()) {
+                    (Some(timeout), true) => timeout.min(EVENT_POLL_INTERVAL),
+                    (Some(timeout), false) => timeout,
+                    (None, true) => EVENT_POLL_INTERVAL,
+                    (None, false) => {
+                        // Only the on_close hooks are left to run
+                        if close_hooks {
+                            let _ = stopped.recv();
+                        }
+                        break;
+                    }
+                };
+                match stopped.recv_timeout(timeout) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                if let Some(receiver) = events.as_mut()
+                    && !dispatch_library_events(&runtime, receiver)
+                {
+                    events = None;
+                }
+
+                for task in scheduler.take_due(Instant::now()) {
+                    debug!("Running scheduled task: {}", task);
+                    if let Err(e) = runtime.run_scheduled_task(&task) {
+                        warn!("{}", e);
+                    }
+                }
+            }
+
+            if let Err(e) = runtime.run_on_close() {
+                warn!("{}", e);
+            }
+            debug!("Stopped plugin scheduler");
+        })?;
+
+    Ok(SchedulerHandle {
+        stop: Some(stop),
+        thread: Some(thread),
+    })
+}
+
+/// Check if any plugin subscribes to a library event.
+fn has_event_subscribers(runtime: &LuaRuntime) -> bool {
+    LibraryEvent::NAMES
+        .iter()
+        .any(|name| runtime.has_subscribers(name).unwrap_or(false))
+}
+
+/// Emit all pending library events to plugins.
+///
+/// Returns `false` once no more events can be received.
+fn dispatch_library_events(runtime: &LuaRuntime, events: &mut EventReceiver) -> bool {
+    loop {
+        match events.try_recv() {
+            Ok(event) => {
+                if let Err(e) = runtime.emit_library_event(&event) {
+                    warn!("{}", e);
+                }
+            }
+            Err(TryRecvError::Lagged(missed)) => {
+                warn!("Plugins fell behind on library events, skipped {missed}");
+            }
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Closed) => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::TrackId;
+    use apollo_core::event::EventBus;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn task(handler: &str, secs: u64) -> ScheduledTask {
+        ScheduledTask {
+            handler: handler.to_string(),
+            plugin: "test".to_string(),
+            interval: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn test_scheduler_take_due() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new([task("fast", 10), task("slow", 60)], start);
+
+        assert_eq!(scheduler.next_due(), Some(start + Duration::from_secs(10)));
+        assert!(scheduler.take_due(start).is_empty());
+
+        let due = scheduler.take_due(start + Duration::from_secs(10));
+        assert_eq!(due, vec![task("fast", 10)]);
+        assert_eq!(scheduler.next_due(), Some(start + Duration::from_secs(20)));
+
+        let due = scheduler.take_due(start + Duration::from_mins(1));
+        assert_eq!(due.len(), 2);
+    }
+
+    #[test]
+    fn test_spawn_scheduler_runs_tasks() {
+        let output = NamedTempFile::new().unwrap();
+        let mut file = NamedTempFile::with_suffix(".lua").unwrap();
+        writeln!(
+            file,
+            r#"
+            local plugin = {{
+                name = "ticker",
+                version = "1.0.0",
+                schedule = {{ every = "1s", handler = "tick" }},
+            }}
+
+            function plugin.tick()
+                apollo.record_tick()
+            end
+
+            return plugin
+            "#
+        )
+        .unwrap();
+        let path = file.path().to_path_buf();
+        let output_path = output.path().display().to_string();
+
+        let handle = spawn_scheduler(
+            move || {
+                let mut runtime = LuaRuntime::new()?;
+                // Plugins have no file access, so record ticks through the shared API table
+                runtime.exec(&format!(
+                    r#"apollo.record_tick = function()
+                    local f = io.open("{output_path}", "a")
+                    f:write("tick\n")
+                    f:close()
+                end"#
+                ))?;
+                runtime.load_plugin(&path)?;
+                Ok::<_, crate::Error>(runtime)
+            },
+            None,
+        )
+        .unwrap();
+
+        thread::sleep(Duration::from_millis(1500));
+        handle.stop();
+
+        let ticks = std::fs::read_to_string(output.path()).unwrap();
+        assert_eq!(ticks.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_spawn_scheduler_emits_library_events() {
+        let output = NamedTempFile::new().unwrap();
+        let mut file = NamedTempFile::with_suffix(".lua").unwrap();
+        writeln!(
+            file,
+            r#"
+            local plugin = {{ name = "listener", version = "1.0.0" }}
+
+            apollo.events.subscribe("track.added", function(event)
+                apollo.record(event.type)
+            end)
+
+            return plugin
+            "#
+        )
+        .unwrap();
+        let path = file.path().to_path_buf();
+        let output_path = output.path().display().to_string();
+
+        let bus = EventBus::new();
+        let handle = spawn_scheduler(
+            move || {
+                let mut runtime = LuaRuntime::new()?;
+                runtime.exec(&format!(
+                    r#"apollo.record = function(line)
+                        local f = io.open("{output_path}", "a")
+                        f:write(line .. "\n")
+                        f:close()
+                    end"#
+                ))?;
+                runtime.load_plugin(&path)?;
+                Ok::<_, crate::Error>(runtime)
+            },
+            Some(bus.subscribe()),
+        )
+        .unwrap();
+
+        bus.emit(LibraryEvent::TrackAdded {
+            track_id: TrackId::new(),
+        });
+        bus.emit(LibraryEvent::TrackRemoved {
+            track_id: TrackId::new(),
+        });
+        thread::sleep(Duration::from_millis(500));
+        handle.stop();
+
+        let events = std::fs::read_to_string(output.path()).unwrap();
+        assert_eq!(events, "track_added\n");
+    }
+
+    #[test]
+    fn test_spawn_scheduler_runs_on_close() {
+        let output = NamedTempFile::new().unwrap();
+        let mut file = NamedTempFile::with_suffix(".lua").unwrap();
+        writeln!(
+            file,
+            r#"
+            local plugin = {{ name = "closer", version = "1.0.0" }}
+
+            function plugin.on_close()
+                apollo.record("closed")
+            end
+
+            return plugin
+            "#
+        )
+        .unwrap();
+        let path = file.path().to_path_buf();
+        let output_path = output.path().display().to_string();
+
+        let handle = spawn_scheduler(
+            move || {
+                let mut runtime = LuaRuntime::new()?;
+                runtime.exec(&format!(
+                    r#"apollo.record = function(line)
+                        local f = io.open("{output_path}", "a")
+                        f:write(line .. "\n")
+                        f:close()
+                    end"#
+                ))?;
+                runtime.load_plugin(&path)?;
+                Ok::<_, crate::Error>(runtime)
+            },
+            None,
+        )
+        .unwrap();
+
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(std::fs::read_to_string(output.path()).unwrap(), "");
+        handle.stop();
+
+        let lines = std::fs::read_to_string(output.path()).unwrap();
+        assert_eq!(lines, "closed\n");
+    }
+}
@@ -0,0 +1,355 @@
+//! Lua bindings for external metadata sources.
+//!
+//! This module exposes Apollo's rate-limited, cached source clients to Lua
+//! under the `apollo.sources` table, so plugins can do their own enrichment.
+//!
+//! # Available Functions
+//!
+//! - `apollo.sources.musicbrainz.search_recordings(title, artist?, limit?)`
+//! - `apollo.sources.musicbrainz.search_releases(title, artist?, limit?)`
+//! - `apollo.sources.musicbrainz.lookup_recording(mbid, include?)`
+//! - `apollo.sources.musicbrainz.lookup_release(mbid, include?)`
+//! - `apollo.sources.musicbrainz.find_best_recording(title, artist, album?, duration_secs?, min_score?)`
+//! - `apollo.sources.coverart.front_url(mbid, size?)`
+//! - `apollo.sources.coverart.back_url(mbid, size?)`
+//! - `apollo.sources.coverart.release_art(mbid)`
+//!
+//! The URL helpers are always available. The network functions are only
+//! registered once sources are enabled with
+//! [`LuaRuntime::enable_sources`](crate::LuaRuntime::enable_sources).
+//! Results are returned as plain Lua tables.
+
+// Allow these clippy lints for the bindings module since they're mostly noise
+// for the types of closures used in mlua function implementations.
+#![allow(clippy::significant_drop_tightening)]
+#![allow(clippy::needless_pass_by_value)]
+
+use crate::util::to_lua_value;
+use apollo_sources::coverart::{CoverArtClient, ImageSize};
+use apollo_sources::musicbrainz::CachedMusicBrainzClient;
+use mlua::{Lua, Result, Table};
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+
+/// Default number of search results returned to Lua.
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+
+/// Default minimum score for `find_best_recording`.
+const DEFAULT_MIN_SCORE: u8 = 80;
+
+/// Runs source client futures from synchronous Lua callbacks.
+///
+/// Inside a multi-threaded Tokio runtime the future is driven on the current
+/// worker. Outside of any runtime a dedicated runtime is created lazily and
+/// reused, so HTTP connection pools stay valid between calls.
+#[derive(Clone, Default)]
+pub struct Executor {
+    fallback: Arc<OnceLock<Runtime>>,
+}
+
+impl Executor {
+    /// Block on a future and return its output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when called from a single-threaded Tokio runtime,
+    /// where blocking would deadlock, or if a runtime cannot be created.
+    pub fn block_on<F: Future>(&self, future: F) -> Result<F::Output> {
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                Ok(tokio::task::block_in_place(|| handle.block_on(future)))
+            }
+            Ok(_) => Err(mlua::Error::runtime(
+                "source lookups require a multi-threaded runtime",
+            )),
+            Err(_) => Ok(self.fallback_runtime()?.block_on(future)),
+        }
+    }
+
+    /// Get the runtime for use outside of any runtime, creating it first.
+    fn fallback_runtime(&self) -> Result<&Runtime> {
+        if let Some(runtime) = self.fallback.get() {
+            return Ok(runtime);
+        }
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(mlua::Error::external)?;
+        // Another thread may have created one meanwhile, which is kept
+        Ok(self.fallback.get_or_init(|| runtime))
+    }
+}
+
+/// Parse an image size name, defaulting to large.
+fn parse_image_size(size: Option<&str>) -> ImageSize {
+    match size.map(str::to_lowercase).as_deref() {
+        Some("small") => ImageSize::Small,
+        Some("medium") => ImageSize::Medium,
+        Some("original") => ImageSize::Original,
+        _ => ImageSize::Large,
+    }
+}
+
+/// Get the `apollo.sources` table, creating it if needed.
+fn sources_table(lua: &Lua) -> Result<Table<'_>> {
+    let apollo: Table = lua.globals().get("apollo")?;
+    if let Some(sources) = apollo.get::<_, Option<Table>>("sources")? {
+        return Ok(sources);
+    }
+
+    let sources = lua.create_table()?;
+    sources.set("musicbrainz", lua.create_table()?)?;
+    sources.set("coverart", lua.create_table()?)?;
+    apollo.set("sources", sources.clone())?;
+    Ok(sources)
+}
+
+/// Register the `apollo.sources` table with the offline helpers.
+///
+/// # Errors
+///
+/// Returns an error if the `apollo` module is not registered.
+pub fn register_sources_module(lua: &Lua) -> Result<()> {
+    let coverart: Table = sources_table(lua)?.get("coverart")?;
+
+    // apollo.sources.coverart.front_url(mbid, size?)
+    coverart.set(
+        "front_url",
+        lua.create_function(|_, (mbid, size): (String, Option<String>)| {
+            Ok(CoverArtClient::front_cover_url(
+                &mbid,
+                parse_image_size(size.as_deref()),
+            ))
+        })?,
+    )?;
+
+    // apollo.sources.coverart.back_url(mbid, size?)
+    coverart.set(
+        "back_url",
+        lua.create_function(|_, (mbid, size): (String, Option<String>)| {
+            Ok(CoverArtClient::back_cover_url(
+                &mbid,
+                parse_image_size(size.as_deref()),
+            ))
+        })?,
+    )?;
+
+    Ok(())
+}
+
+/// Register the network-backed `MusicBrainz` functions.
+///
+/// # Errors
+///
+/// Returns an error if the functions cannot be registered.
+#[allow(clippy::too_many_lines)]
+pub fn register_musicbrainz(
+    lua: &Lua,
+    client: Arc<CachedMusicBrainzClient>,
+    executor: &Executor,
+) -> Result<()> {
+    let musicbrainz: Table = sources_table(lua)?.get("musicbrainz")?;
+
+    // apollo.sources.musicbrainz.search_recordings(title, artist?, limit?)
+    let (c, ex) = (Arc::clone(&client), executor.clone());
+    musicbrainz.set(
+        "search_recordings",
+        lua.create_function(
+            move |lua, (title, artist, limit): (String, Option<String>, Option<u32>)| {
+                let recordings = ex
+                    .block_on(c.search_recordings(
+                        &title,
+                        artist.as_deref(),
+                        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+                    ))?
+                    .map_err(mlua::Error::external)?;
+                to_lua_value(lua, &recordings)
+            },
+        )?,
+    )?;
+
+    // apollo.sources.musicbrainz.search_releases(title, artist?, limit?)
+    let (c, ex) = (Arc::clone(&client), executor.clone());
+    musicbrainz.set(
+        "search_releases",
+        lua.create_function(
+            move |lua, (title, artist, limit): (String, Option<String>, Option<u32>)| {
+                let releases = ex
+                    .block_on(c.search_releases(
+                        &title,
+                        artist.as_deref(),
+                        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+                    ))?
+                    .map_err(mlua::Error::external)?;
+                to_lua_value(lua, &releases)
+            },
+        )?,
+    )?;
+
+    // apollo.sources.musicbrainz.lookup_recording(mbid, include?)
+    let (c, ex) = (Arc::clone(&client), executor.clone());
+    musicbrainz.set(
+        "lookup_recording",
+        lua.create_function(move |lua, (mbid, include): (String, Option<Vec<String>>)| {
+            let include = include.unwrap_or_default();
+            let include: Vec<&str> = include.iter().map(String::as_str).collect();
+            let recording = ex
+                .block_on(c.lookup_recording(&mbid, &include))?
+                .map_err(mlua::Error::external)?;
+            to_lua_value(lua, &recording)
+        })?,
+    )?;
+
+    // apollo.sources.musicbrainz.lookup_release(mbid, include?)
+    let (c, ex) = (Arc::clone(&client), executor.clone());
+    musicbrainz.set(
+        "lookup_release",
+        lua.create_function(move |lua, (mbid, include): (String, Option<Vec<String>>)| {
+            let include = include.unwrap_or_default();
+            let include: Vec<&str> = include.iter().map(String::as_str).collect();
+            let release = ex
+                .block_on(c.lookup_release(&mbid, &include))?
+                .map_err(mlua::Error::external)?;
+            to_lua_value(lua, &release)
+        })?,
+    )?;
+
+    // apollo.sources.musicbrainz.find_best_recording(title, artist, album?, duration_secs?, min_score?)
+    let (c, ex) = (client, executor.clone());
+    musicbrainz.set(
+        "find_best_recording",
+        lua.create_function(
+            move |lua,
+                  (title, artist, album, duration_secs, min_score): (
+                String,
+                String,
+                Option<String>,
+                Option<f64>,
+                Option<u8>,
+            )| {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let duration_ms = duration_secs.map(|secs| (secs * 1000.0) as u64);
+                let recording = ex
+                    .block_on(c.find_best_recording(
+                        &title,
+                        &artist,
+                        album.as_deref(),
+                        duration_ms,
+                        min_score.unwrap_or(DEFAULT_MIN_SCORE),
+                    ))?
+                    .map_err(mlua::Error::external)?;
+                to_lua_value(lua, &recording)
+            },
+        )?,
+    )?;
+
+    Ok(())
+}
+
+/// Register the network-backed Cover Art Archive functions.
+///
+/// # Errors
+///
+/// Returns an error if the functions cannot be registered.
+pub fn register_coverart(
+    lua: &Lua,
+    client: Arc<CoverArtClient>,
+    executor: &Executor,
+) -> Result<()> {
+    let coverart: Table = sources_table(lua)?.get("coverart")?;
+
+    // apollo.sources.coverart.release_art(mbid)
+    let ex = executor.clone();
+    coverart.set(
+        "release_art",
+        lua.create_function(move |lua, mbid: String| {
+            let images = ex
+                .block_on(client.get_release_art(&mbid))?
+                .map_err(mlua::Error::external)?;
+            to_lua_value(lua, &images)
+        })?,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::register_apollo_module;
+
+    fn create_lua() -> Lua {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+        register_sources_module(&lua).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_parse_image_size() {
+        assert_eq!(parse_image_size(Some("small")), ImageSize::Small);
+        assert_eq!(parse_image_size(Some("ORIGINAL")), ImageSize::Original);
+        assert_eq!(parse_image_size(Some("unknown")), ImageSize::Large);
+        assert_eq!(parse_image_size(None), ImageSize::Large);
+    }
+
+    #[test]
+    fn test_coverart_urls() {
+        let lua = create_lua();
+
+        let url: String = lua
+            .load(r#"return apollo.sources.coverart.front_url("test-mbid", "small")"#)
+            .eval()
+            .unwrap();
+        assert!(url.ends_with("/release/test-mbid/front-250"));
+
+        let url: String = lua
+            .load(r#"return apollo.sources.coverart.back_url("test-mbid")"#)
+            .eval()
+            .unwrap();
+        assert!(url.ends_with("/release/test-mbid/back-500"));
+    }
+
+    #[test]
+    fn test_network_functions_not_registered_by_default() {
+        let lua = create_lua();
+
+        let registered: bool = lua
+            .load("return apollo.sources.musicbrainz.search_recordings ~= nil")
+            .eval()
+            .unwrap();
+        assert!(!registered);
+    }
+
+    #[test]
+    fn test_register_network_functions() {
+        let lua = create_lua();
+        let executor = Executor::default();
+
+        let mb = CachedMusicBrainzClient::with_defaults("Test", "0.1", "test@example.com").unwrap();
+        register_musicbrainz(&lua, Arc::new(mb), &executor).unwrap();
+        let art = CoverArtClient::new("Test", "0.1").unwrap();
+        register_coverart(&lua, Arc::new(art), &executor).unwrap();
+
+        let registered: bool = lua
+            .load(
+                r#"
+                local mb = apollo.sources.musicbrainz
+                return type(mb.search_recordings) == "function"
+                    and type(mb.lookup_release) == "function"
+                    and type(apollo.sources.coverart.release_art) == "function"
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert!(registered);
+    }
+
+    #[test]
+    fn test_executor_outside_runtime() {
+        let executor = Executor::default();
+        assert_eq!(executor.block_on(async { 1 + 2 }).unwrap(), 3);
+        assert_eq!(executor.block_on(async { 4 }).unwrap(), 4);
+    }
+}
@@ -0,0 +1,210 @@
+//! Utility modules for Lua plugins.
+//!
+//! This module provides helpers that nearly every non-trivial plugin needs,
+//! so they don't have to be reimplemented in Lua.
+//!
+//! # Available Functions
+//!
+//! - `apollo.json.encode(value, pretty?)` - encode a Lua value as JSON
+//! - `apollo.json.decode(text)` - decode JSON into Lua values
+//! - `apollo.path.sanitize(name)` - make a string safe for use as a path component
+//! - `apollo.path.join(...)` - join path components
+//! - `apollo.path.ext(path)` - get the file extension, or `nil`
+
+use apollo_core::template::sanitize_path_component;
+use mlua::{Lua, LuaSerdeExt, Result, SerializeOptions, Table, Value, Variadic};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Convert a serializable value into a Lua value, mapping `None` to `nil`.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be converted.
+pub fn to_lua_value<'lua, T: Serialize>(lua: &'lua Lua, value: &T) -> Result<Value<'lua>> {
+    let options = SerializeOptions::new()
+        .serialize_none_to_null(false)
+        .serialize_unit_to_null(false);
+    lua.to_value_with(value, options)
+}
+
+/// Register the `apollo.json` and `apollo.path` tables.
+///
+/// # Errors
+///
+/// Returns an error if the `apollo` module is not registered.
+pub fn register_util_modules(lua: &Lua) -> Result<()> {
+    let apollo: Table = lua.globals().get("apollo")?;
+
+    let json = lua.create_table()?;
+
+    // apollo.json.encode(value, pretty?)
+    json.set(
+        "encode",
+        lua.create_function(|lua, (value, pretty): (Value, Option<bool>)| {
+            let value: serde_json::Value = lua.from_value(value)?;
+            let encoded = if pretty.unwrap_or(false) {
+                serde_json::to_string_pretty(&value)
+            } else {
+                serde_json::to_string(&value)
+            };
+            encoded.map_err(mlua::Error::external)
+        })?,
+    )?;
+
+    // apollo.json.decode(text)
+    json.set(
+        "decode",
+        lua.create_function(|lua, text: String| {
+            let value: serde_json::Value =
+                serde_json::from_str(&text).map_err(mlua::Error::external)?;
+            to_lua_value(lua, &value)
+        })?,
+    )?;
+
+    apollo.set("json", json)?;
+
+    let path = lua.create_table()?;
+
+    // apollo.path.sanitize(name)
+    path.set(
+        "sanitize",
+        lua.create_function(|_, name: String| Ok(sanitize_path_component(&name)))?,
+    )?;
+
+    // apollo.path.join(...)
+    path.set(
+        "join",
+        lua.create_function(|_, parts: Variadic<String>| {
+            let joined: PathBuf = parts.iter().collect();
+            Ok(joined.to_string_lossy().to_string())
+        })?,
+    )?;
+
+    // apollo.path.ext(path)
+    path.set(
+        "ext",
+        lua.create_function(|_, path: String| {
+            Ok(Path::new(&path)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_string()))
+        })?,
+    )?;
+
+    apollo.set("path", path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::register_apollo_module;
+
+    fn create_lua() -> Lua {
+        let lua = Lua::new();
+        register_apollo_module(&lua).unwrap();
+        register_util_modules(&lua).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_to_lua_value_maps_none_to_nil() {
+        #[derive(Serialize)]
+        struct Sample {
+            present: Option<u32>,
+            missing: Option<u32>,
+        }
+
+        let lua = Lua::new();
+        let value = to_lua_value(
+            &lua,
+            &Sample {
+                present: Some(1),
+                missing: None,
+            },
+        )
+        .unwrap();
+        let table = value.as_table().unwrap();
+        assert_eq!(table.get::<_, u32>("present").unwrap(), 1);
+        assert!(matches!(
+            table.get::<_, Value>("missing").unwrap(),
+            Value::Nil
+        ));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let lua = create_lua();
+
+        let result: String = lua
+            .load(
+                r#"
+                local data = apollo.json.decode('{"name": "Queen", "albums": [1, 2, 3], "active": false}')
+                return data.name .. ":" .. #data.albums .. ":" .. tostring(data.active)
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(result, "Queen:3:false");
+
+        let encoded: String = lua
+            .load(r#"return apollo.json.encode({ title = "Song", year = 1975 })"#)
+            .eval()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(value["title"], "Song");
+        assert_eq!(value["year"], 1975);
+    }
+
+    #[test]
+    fn test_json_decode_invalid() {
+        let lua = create_lua();
+
+        let ok: bool = lua
+            .load(r#"return (pcall(apollo.json.decode, "{not json"))"#)
+            .eval()
+            .unwrap();
+        assert!(!ok);
+    }
+
+    #[test]
+    fn test_json_decode_null_is_nil() {
+        let lua = create_lua();
+
+        let is_nil: bool = lua
+            .load(r#"return apollo.json.decode('{"a": null}').a == nil"#)
+            .eval()
+            .unwrap();
+        assert!(is_nil);
+    }
+
+    #[test]
+    fn test_path_helpers() {
+        let lua = create_lua();
+
+        let sanitized: String = lua
+            .load(r#"return apollo.path.sanitize("AC/DC: Live?")"#)
+            .eval()
+            .unwrap();
+        assert_eq!(sanitized, "AC DC_ Live_");
+
+        let joined: String = lua
+            .load(r#"return apollo.path.join("Music", "Queen", "song.mp3")"#)
+            .eval()
+            .unwrap();
+        assert_eq!(joined, "Music/Queen/song.mp3");
+
+        let ext: String = lua
+            .load(r#"return apollo.path.ext("/music/song.flac")"#)
+            .eval()
+            .unwrap();
+        assert_eq!(ext, "flac");
+
+        let no_ext: bool = lua
+            .load(r#"return apollo.path.ext("/music/README") == nil"#)
+            .eval()
+            .unwrap();
+        assert!(no_ext);
+    }
+}
@@ -6,7 +6,7 @@ use reqwest::Client;
 use reqwest::header::{ACCEPT, HeaderMap, HeaderValue, USER_AGENT};
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use tracing::{debug, warn};
+use tracing::{debug, instrument, warn};
 
 /// API base URL.
 const API_BASE: &str = "https://api.acoustid.org/v2";
@@ -24,6 +24,8 @@ pub struct AcoustIdClient {
     client: Client,
     /// API key.
     api_key: String,
+    /// API base URL.
+    base_url: String,
     /// Last request time for rate limiting.
     last_request: Mutex<Instant>,
 }
@@ -54,6 +56,7 @@ impl AcoustIdClient {
         Ok(Self {
             client,
             api_key: api_key.into(),
+            base_url: API_BASE.to_string(),
             last_request: Mutex::new(
                 Instant::now()
                     .checked_sub(MIN_REQUEST_INTERVAL)
@@ -62,13 +65,20 @@ impl AcoustIdClient {
         })
     }
 
+    /// Use another API endpoint, such as a mock server.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
     /// Wait for rate limiting before making a request.
     async fn wait_for_rate_limit(&self) {
         let mut last = self.last_request.lock().await;
         let elapsed = last.elapsed();
 
         if elapsed < MIN_REQUEST_INTERVAL {
-            let wait = MIN_REQUEST_INTERVAL - elapsed;
+            let wait = MIN_REQUEST_INTERVAL.saturating_sub(elapsed);
             debug!("Rate limiting: waiting {:?}", wait);
             tokio::time::sleep(wait).await;
         }
@@ -110,6 +120,7 @@ impl AcoustIdClient {
     /// # Errors
     ///
     /// Returns an error if the API request fails.
+    #[instrument(name = "acoustid_lookup", level = "debug", skip(self, fingerprint))]
     pub async fn lookup_with_meta(
         &self,
         fingerprint: &str,
@@ -120,7 +131,8 @@ impl AcoustIdClient {
 
         let meta_str = meta.join("+");
         let url = format!(
-            "{API_BASE}/lookup?client={}&duration={}&fingerprint={}&meta={}",
+            "{}/lookup?client={}&duration={}&fingerprint={}&meta={}",
+            self.base_url,
             urlencoding::encode(&self.api_key),
             duration,
             urlencoding::encode(fingerprint),
@@ -128,6 +140,7 @@ impl AcoustIdClient {
         );
 
         debug!(
+            monotonic_counter.acoustid_requests = 1_u64,
             "AcoustID lookup: duration={}s, fingerprint_len={}",
             duration,
             fingerprint.len()
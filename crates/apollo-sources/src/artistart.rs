@@ -0,0 +1,513 @@
+//! Artist images from [fanart.tv](https://fanart.tv/) and
+//! [Wikidata](https://www.wikidata.org/).
+//!
+//! Artists are found on [MusicBrainz](https://musicbrainz.org/) by name. With
+//! an API key, the most liked artist thumbnail on fanart.tv is used;
+//! otherwise, or when fanart.tv has none, the image of the artist's Wikidata
+//! item, which is hosted on [Wikimedia Commons](https://commons.wikimedia.org/).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use apollo_sources::artistart::ArtistImageClient;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = ArtistImageClient::new("MyApp", "1.0", "contact@example.com")?
+//!     .with_fanart_api_key("your-api-key");
+//!
+//! let image = client.find_image("Nina Simone").await?;
+//! println!("{} bytes of {}", image.data.len(), image.mime_type);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{SourceError, SourceResult};
+use crate::musicbrainz::escape_lucene;
+use reqwest::Client;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, instrument, warn};
+
+/// [MusicBrainz](https://musicbrainz.org/) API base URL.
+const MUSICBRAINZ_BASE: &str = "https://musicbrainz.org";
+
+/// fanart.tv API base URL.
+const FANART_BASE: &str = "https://webservice.fanart.tv";
+
+/// Wikidata base URL.
+const WIKIDATA_BASE: &str = "https://www.wikidata.org";
+
+/// Wikimedia Commons base URL.
+const COMMONS_BASE: &str = "https://commons.wikimedia.org";
+
+/// Minimum delay between [MusicBrainz](https://musicbrainz.org/) requests.
+const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(1100);
+
+/// Lowest search score of an artist to be taken as the one searched for.
+const MIN_SCORE: u8 = 90;
+
+/// Width in pixels of images from Wikimedia Commons.
+const COMMONS_WIDTH: u32 = 500;
+
+/// A downloaded artist image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtistImage {
+    /// Image data.
+    pub data: Vec<u8>,
+    /// MIME type, such as `image/jpeg`.
+    pub mime_type: String,
+}
+
+/// Client for finding and downloading artist images.
+pub struct ArtistImageClient {
+    client: Client,
+    fanart_api_key: Option<String>,
+    musicbrainz_url: String,
+    fanart_url: String,
+    wikidata_url: String,
+    commons_url: String,
+    last_request: Mutex<Instant>,
+}
+
+impl ArtistImageClient {
+    /// Create a client that uses Wikidata only.
+    ///
+    /// # Arguments
+    ///
+    /// * `app_name` - Name of your application
+    /// * `app_version` - Version of your application
+    /// * `contact` - Contact email or URL, sent to [MusicBrainz](https://musicbrainz.org/)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new(app_name: &str, app_version: &str, contact: &str) -> SourceResult<Self> {
+        let user_agent = format!("{app_name}/{app_version} ( {contact} )");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(&user_agent)
+                .map_err(|e| SourceError::InvalidInput(e.to_string()))?,
+        );
+
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            fanart_api_key: None,
+            musicbrainz_url: MUSICBRAINZ_BASE.to_string(),
+            fanart_url: FANART_BASE.to_string(),
+            wikidata_url: WIKIDATA_BASE.to_string(),
+            commons_url: COMMONS_BASE.to_string(),
+            last_request: Mutex::new(
+                Instant::now()
+                    .checked_sub(MIN_REQUEST_INTERVAL)
+                    .unwrap_or_else(Instant::now),
+            ),
+        })
+    }
+
+    /// Look for images on fanart.tv first, with a personal API key (get one
+    /// at <https://fanart.tv/get-an-api-key/>). An empty key is ignored.
+    #[must_use]
+    pub fn with_fanart_api_key(mut self, api_key: impl Into<String>) -> Self {
+        let api_key = api_key.into();
+        self.fanart_api_key = (!api_key.is_empty()).then_some(api_key);
+        self
+    }
+
+    /// Use one endpoint for all services, such as a mock server.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        self.musicbrainz_url.clone_from(&base_url);
+        self.fanart_url.clone_from(&base_url);
+        self.wikidata_url.clone_from(&base_url);
+        self.commons_url = base_url;
+        self
+    }
+
+    /// Wait for rate limiting before making a [MusicBrainz](https://musicbrainz.org/) request.
+    async fn wait_for_rate_limit(&self) {
+        let mut last = self.last_request.lock().await;
+        let elapsed = last.elapsed();
+
+        if elapsed < MIN_REQUEST_INTERVAL {
+            let wait = MIN_REQUEST_INTERVAL.saturating_sub(elapsed);
+            debug!("Rate limiting: waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+
+        *last = Instant::now();
+    }
+
+    /// Find and download an image of an artist.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SourceError::NotFound`] if the artist or an image of them
+    /// cannot be found, or another error if a request fails.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn find_image(&self, name: &str) -> SourceResult<ArtistImage> {
+        let url = self.find_image_url(name).await?;
+        self.download(&url).await
+    }
+
+    /// Find the URL of an image of an artist.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SourceError::NotFound`] if the artist or an image of them
+    /// cannot be found, or another error if a request fails.
+    pub async fn find_image_url(&self, name: &str) -> SourceResult<String> {
+        let mbid = self.find_artist_id(name).await?;
+
+        if let Some(api_key) = &self.fanart_api_key {
+            match self.fanart_image_url(&mbid, api_key).await {
+                Ok(url) => return Ok(url),
+                Err(SourceError::NotFound) => {}
+                Err(e) => warn!("fanart.tv lookup for {name} failed: {e}"),
+            }
+        }
+
+        let item = self.wikidata_item(&mbid).await?;
+        self.wikidata_image_url(&item).await
+    }
+
+    /// Find the [MusicBrainz](https://musicbrainz.org/) ID of an artist by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SourceError::NotFound`] if no artist matches the name well
+    /// enough, or another error if the request fails.
+    pub async fn find_artist_id(&self, name: &str) -> SourceResult<String> {
+        let query = format!("artist:\"{}\"", escape_lucene(name));
+        let url = format!(
+            "{}/ws/2/artist?query={}&limit=1",
+            self.musicbrainz_url,
+            urlencoding::encode(&query)
+        );
+
+        self.wait_for_rate_limit().await;
+        let response: ArtistSearchResponse = self.get(&url).await?;
+        response
+            .artists
+            .into_iter()
+            .find(|artist| artist.score >= MIN_SCORE)
+            .map(|artist| artist.id)
+            .ok_or(SourceError::NotFound)
+    }
+
+    /// Find the most liked artist thumbnail on fanart.tv.
+    async fn fanart_image_url(&self, mbid: &str, api_key: &str) -> SourceResult<String> {
+        let url = format!(
+            "{}/v3/music/{mbid}?api_key={}",
+            self.fanart_url,
+            urlencoding::encode(api_key)
+        );
+
+        let response: FanartResponse = self.get(&url).await?;
+        response
+            .artistthumb
+            .into_iter()
+            .max_by_key(|image| image.likes.parse::<u32>().unwrap_or(0))
+            .map(|image| image.url)
+            .ok_or(SourceError::NotFound)
+    }
+
+    /// Find the Wikidata item linked to a [MusicBrainz](https://musicbrainz.org/) artist.
+    async fn wikidata_item(&self, mbid: &str) -> SourceResult<String> {
+        let url = format!("{}/ws/2/artist/{mbid}?inc=url-rels", self.musicbrainz_url);
+
+        self.wait_for_rate_limit().await;
+        let artist: ArtistLookupResponse = self.get(&url).await?;
+        artist
+            .relations
+            .iter()
+            .filter(|relation| relation.kind == "wikidata")
+            .filter_map(|relation| relation.url.as_ref())
+            .find_map(|url| url.resource.rsplit('/').next())
+            .filter(|item| item.starts_with('Q'))
+            .map(ToString::to_string)
+            .ok_or(SourceError::NotFound)
+    }
+
+    /// Find the URL of the image of a Wikidata item.
+    async fn wikidata_image_url(&self, item: &str) -> SourceResult<String> {
+        let url = format!("{}/wiki/Special:EntityData/{item}.json", self.wikidata_url);
+
+        let response: EntityDataResponse = self.get(&url).await?;
+        let file = response
+            .entities
+            .get(item)
+            .and_then(|entity| entity.claims.get("P18"))
+            .and_then(|claims| claims.first())
+            .and_then(|claim| claim.mainsnak.datavalue.as_ref())
+            .and_then(|value| value.value.as_str())
+            .ok_or(SourceError::NotFound)?;
+
+        Ok(format!(
+            "{}/wiki/Special:FilePath/{}?width={COMMONS_WIDTH}",
+            self.commons_url,
+            urlencoding::encode(&file.replace(' ', "_"))
+        ))
+    }
+
+    /// Download an image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the download fails or is not an image.
+    pub async fn download(&self, url: &str) -> SourceResult<ArtistImage> {
+        debug!("GET {url}");
+        let response = self
+            .client
+            .get(url)
+            .header(ACCEPT, "image/*")
+            .send()
+            .await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(SourceError::NotFound);
+        }
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(SourceError::Api {
+                status: status.as_u16(),
+                message,
+            });
+        }
+
+        let mime_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map_or("image/jpeg", |value| {
+                value.split(';').next().unwrap_or(value).trim()
+            })
+            .to_string();
+        if !mime_type.starts_with("image/") {
+            return Err(SourceError::Parse(format!("not an image: {mime_type}")));
+        }
+
+        let data = response.bytes().await?.to_vec();
+        Ok(ArtistImage { data, mime_type })
+    }
+
+    /// Make a GET request for JSON.
+    async fn get<T: DeserializeOwned>(&self, url: &str) -> SourceResult<T> {
+        debug!("GET {url}");
+
+        let response = self.client.get(url).send().await?;
+        let status = response.status();
+
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(SourceError::NotFound);
+        }
+
+        if status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            let retry_after = response
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60);
+
+            warn!("Rate limited, retry after {retry_after} seconds");
+            return Err(SourceError::RateLimited { retry_after });
+        }
+
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(SourceError::Api {
+                status: status.as_u16(),
+                message,
+            });
+        }
+
+        let body = response.text().await?;
+        serde_json::from_str(&body).map_err(|e| SourceError::Parse(e.to_string()))
+    }
+}
+
+/// Artists found by a [MusicBrainz](https://musicbrainz.org/) search.
+#[derive(Debug, Deserialize)]
+struct ArtistSearchResponse {
+    #[serde(default)]
+    artists: Vec<FoundArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FoundArtist {
+    id: String,
+    #[serde(default)]
+    score: u8,
+}
+
+/// A [MusicBrainz](https://musicbrainz.org/) artist, of which only the URL
+/// relations are used.
+#[derive(Debug, Deserialize)]
+struct ArtistLookupResponse {
+    #[serde(default)]
+    relations: Vec<Relation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Relation {
+    #[serde(rename = "type")]
+    kind: String,
+    url: Option<RelationUrl>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RelationUrl {
+    resource: String,
+}
+
+/// Images of an artist on fanart.tv, of which only thumbnails are used.
+#[derive(Debug, Deserialize)]
+struct FanartResponse {
+    #[serde(default)]
+    artistthumb: Vec<FanartImage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FanartImage {
+    url: String,
+    /// Number of likes, as a string.
+    #[serde(default)]
+    likes: String,
+}
+
+/// Wikidata items by ID, of which only the claims are used.
+#[derive(Debug, Deserialize)]
+struct EntityDataResponse {
+    entities: HashMap<String, Entity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entity {
+    #[serde(default)]
+    claims: HashMap<String, Vec<Claim>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claim {
+    mainsnak: Snak,
+}
+
+#[derive(Debug, Deserialize)]
+struct Snak {
+    datavalue: Option<DataValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DataValue {
+    value: serde_json::Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const MBID: &str = "0383dadf-2a4e-4d10-a46a-e9e041da8eb3";
+
+    async fn mock_musicbrainz(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/ws/2/artist"))
+            .and(query_param("query", "artist:\"Queen\""))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "artists": [{ "id": MBID, "name": "Queen", "score": 100 }]
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/ws/2/artist/{MBID}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": MBID,
+                "relations": [
+                    { "type": "official homepage", "url": { "resource": "https://queenonline.com/" } },
+                    { "type": "wikidata", "url": { "resource": "https://www.wikidata.org/wiki/Q15862" } }
+                ]
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_image_from_wikidata() {
+        let server = MockServer::start().await;
+        mock_musicbrainz(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/wiki/Special:EntityData/Q15862.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "entities": { "Q15862": { "claims": { "P18": [
+                    { "mainsnak": { "datavalue": { "value": "Queen 1984.jpg", "type": "string" } } }
+                ] } } }
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/wiki/Special:FilePath/Queen_1984.jpg"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/jpeg")
+                    .set_body_bytes(b"jpeg".to_vec()),
+            )
+            .mount(&server)
+            .await;
+
+        let client = ArtistImageClient::new("Test", "1.0", "test@example.com")
+            .unwrap()
+            .with_base_url(server.uri());
+        let image = client.find_image("Queen").await.unwrap();
+        assert_eq!(image.data, b"jpeg");
+        assert_eq!(image.mime_type, "image/jpeg");
+
+        assert!(matches!(
+            client.find_image("Nobody").await,
+            Err(SourceError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_image_from_fanart() {
+        let server = MockServer::start().await;
+        mock_musicbrainz(&server).await;
+        Mock::given(method("GET"))
+            .and(path(format!("/v3/music/{MBID}")))
+            .and(query_param("api_key", "key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "Queen",
+                "artistthumb": [
+                    { "id": "1", "url": "https://assets.fanart.tv/a.jpg", "likes": "2" },
+                    { "id": "2", "url": "https://assets.fanart.tv/b.jpg", "likes": "7" }
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = ArtistImageClient::new("Test", "1.0", "test@example.com")
+            .unwrap()
+            .with_base_url(server.uri())
+            .with_fanart_api_key("key");
+        assert_eq!(
+            client.find_image_url("Queen").await.unwrap(),
+            "https://assets.fanart.tv/b.jpg"
+        );
+    }
+}
@@ -12,7 +12,7 @@ use tokio::sync::RwLock;
 use tracing::debug;
 
 /// Default TTL for cache entries (1 hour).
-const DEFAULT_TTL: Duration = Duration::from_secs(3600);
+const DEFAULT_TTL: Duration = Duration::from_hours(1);
 
 /// Maximum cache size (number of entries).
 const DEFAULT_MAX_SIZE: usize = 10000;
@@ -239,7 +239,7 @@ where
             .iter()
             .map(|(k, e)| (k.clone(), e.created.elapsed()))
             .collect();
-        ages.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by age descending
+        ages.sort_by_key(|(_, age)| std::cmp::Reverse(*age)); // Sort by age descending
 
         for (key, _) in ages.into_iter().take(count) {
             entries.remove(&key);
@@ -7,7 +7,7 @@ use reqwest::header::{ACCEPT, HeaderMap, HeaderValue, USER_AGENT};
 use std::path::Path;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use tracing::{debug, warn};
+use tracing::{debug, instrument, warn};
 
 /// Cover Art Archive API base URL.
 const CAA_API_BASE: &str = "https://coverartarchive.org";
@@ -89,7 +89,7 @@ impl CoverArtClient {
         let elapsed = last.elapsed();
 
         if elapsed < MIN_REQUEST_INTERVAL {
-            let wait = MIN_REQUEST_INTERVAL - elapsed;
+            let wait = MIN_REQUEST_INTERVAL.saturating_sub(elapsed);
             debug!("Rate limiting: waiting {:?}", wait);
             tokio::time::sleep(wait).await;
         }
@@ -106,11 +106,12 @@ impl CoverArtClient {
     /// # Errors
     ///
     /// Returns an error if the API request fails or no art is found.
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_release_art(&self, release_mbid: &str) -> SourceResult<Vec<CoverImage>> {
         self.wait_for_rate_limit().await;
 
         let url = format!("{CAA_API_BASE}/release/{release_mbid}");
-        debug!("GET {url}");
+        debug!(monotonic_counter.coverart_requests = 1_u64, "GET {url}");
 
         let response = self.client.get(&url).send().await?;
         let status = response.status();
@@ -166,6 +167,7 @@ impl CoverArtClient {
     /// # Errors
     ///
     /// Returns an error if the API request fails or no art is found.
+    #[instrument(level = "debug", skip(self))]
     pub async fn get_release_group_art(
         &self,
         release_group_mbid: &str,
@@ -173,7 +175,7 @@ impl CoverArtClient {
         self.wait_for_rate_limit().await;
 
         let url = format!("{CAA_API_BASE}/release-group/{release_group_mbid}");
-        debug!("GET {url}");
+        debug!(monotonic_counter.coverart_requests = 1_u64, "GET {url}");
 
         let response = self.client.get(&url).send().await?;
         let status = response.status();
@@ -262,10 +264,14 @@ impl CoverArtClient {
     /// # Errors
     ///
     /// Returns an error if the download fails.
+    #[instrument(level = "debug", skip(self))]
     pub async fn download_image(&self, url: &str) -> SourceResult<Vec<u8>> {
         self.wait_for_rate_limit().await;
 
-        debug!("Downloading image from {url}");
+        debug!(
+            monotonic_counter.coverart_requests = 1_u64,
+            "Downloading image from {url}"
+        );
 
         let response = self.client.get(url).send().await?;
         let status = response.status();
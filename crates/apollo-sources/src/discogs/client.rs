@@ -7,7 +7,7 @@ use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue, USER_AGENT}
 use std::fmt::Write;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use tracing::{debug, warn};
+use tracing::{debug, instrument, warn};
 
 /// Discogs API base URL.
 const API_BASE: &str = "https://api.discogs.com";
@@ -51,6 +51,7 @@ const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(1100);
 /// ```
 pub struct DiscogsClient {
     client: Client,
+    base_url: String,
     last_request: Mutex<Instant>,
 }
 
@@ -89,6 +90,7 @@ impl DiscogsClient {
 
         Ok(Self {
             client,
+            base_url: API_BASE.to_string(),
             last_request: Mutex::new(
                 Instant::now()
                     .checked_sub(MIN_REQUEST_INTERVAL)
@@ -97,13 +99,20 @@ impl DiscogsClient {
         })
     }
 
+    /// Use another API endpoint, such as a mock server.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
     /// Wait for rate limiting before making a request.
     async fn wait_for_rate_limit(&self) {
         let mut last = self.last_request.lock().await;
         let elapsed = last.elapsed();
 
         if elapsed < MIN_REQUEST_INTERVAL {
-            let wait = MIN_REQUEST_INTERVAL - elapsed;
+            let wait = MIN_REQUEST_INTERVAL.saturating_sub(elapsed);
             debug!("Rate limiting: waiting {:?}", wait);
             tokio::time::sleep(wait).await;
         }
@@ -112,11 +121,12 @@ impl DiscogsClient {
     }
 
     /// Make a GET request to the API.
+    #[instrument(name = "discogs_request", level = "debug", skip(self))]
     async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> SourceResult<T> {
         self.wait_for_rate_limit().await;
 
-        let url = format!("{API_BASE}{path}");
-        debug!("GET {url}");
+        let url = format!("{}{path}", self.base_url);
+        debug!(monotonic_counter.discogs_requests = 1_u64, "GET {url}");
 
         let response = self.client.get(&url).send().await?;
         let status = response.status();
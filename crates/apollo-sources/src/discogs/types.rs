@@ -1,5 +1,8 @@
 //! [Discogs](https://discogs.com/) API response types.
 
+use crate::musicbrainz::similarity;
+use apollo_core::{Album, AlbumType, Media};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 
@@ -37,6 +40,9 @@ pub struct Release {
     /// Release date.
     #[serde(default)]
     pub released: Option<String>,
+    /// Identifiers such as barcodes and matrix numbers.
+    #[serde(default)]
+    pub identifiers: Vec<Identifier>,
     /// Discogs resource URL.
     #[serde(default)]
     pub resource_url: Option<String>,
@@ -75,6 +81,168 @@ impl Release {
         }
         result
     }
+
+    /// Get the full release date, if the day is known.
+    ///
+    /// Discogs uses `00` for unknown months and days.
+    #[must_use]
+    pub fn release_date(&self) -> Option<NaiveDate> {
+        self.released
+            .as_deref()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    }
+
+    /// Get the album type from the format descriptions.
+    ///
+    /// More specific types such as "Compilation" or "EP" take precedence
+    /// over "Album".
+    #[must_use]
+    pub fn album_type(&self) -> Option<AlbumType> {
+        self.formats
+            .iter()
+            .flat_map(|format| &format.descriptions)
+            .filter_map(|description| AlbumType::from_name(description))
+            .min_by_key(|album_type| *album_type == AlbumType::Album)
+    }
+
+    /// Get the medium of the release, from its first format with a known
+    /// one.
+    #[must_use]
+    pub fn media(&self) -> Option<Media> {
+        self.formats
+            .iter()
+            .find_map(|format| Media::from_name(&format.name))
+    }
+
+    /// Get the edition of the release, from the free text of its formats,
+    /// such as "Deluxe Edition".
+    #[must_use]
+    pub fn edition(&self) -> Option<String> {
+        self.formats
+            .iter()
+            .filter_map(|format| format.text.as_deref())
+            .map(str::trim)
+            .find(|text| !text.is_empty())
+            .map(ToString::to_string)
+    }
+
+    /// Get the first barcode of the release, without spaces.
+    #[must_use]
+    pub fn barcode(&self) -> Option<String> {
+        self.identifiers
+            .iter()
+            .find(|identifier| identifier.kind.eq_ignore_ascii_case("barcode"))
+            .map(|identifier| identifier.value.replace(' ', ""))
+            .filter(|barcode| !barcode.is_empty())
+    }
+
+    /// Copy release metadata to an album.
+    ///
+    /// Fields missing from the release are left unchanged.
+    pub fn apply_to_album(&self, album: &mut Album) {
+        album.discogs_id = Some(self.id);
+        if let Some(barcode) = self.barcode() {
+            album.barcode = Some(barcode);
+        }
+        album.year = self.year.or(album.year);
+        album.album_type = self.album_type().or(album.album_type);
+        album.release_date = self.release_date().or(album.release_date);
+        album.media = self.media().or(album.media);
+        if let Some(edition) = self.edition() {
+            album.edition = Some(edition);
+        }
+        if let Some(ref country) = self.country {
+            album.country = Some(country.clone());
+        }
+        if let Some(label) = self.labels.first() {
+            album.label = Some(label.name.clone());
+            // Discogs uses "none" for releases without a catalog number
+            if let Some(ref catno) = label.catno
+                && !catno.eq_ignore_ascii_case("none")
+            {
+                album.catalog_number = Some(catno.clone());
+            }
+        }
+    }
+
+    /// Get the tracks of the release, without the headings and indexes
+    /// of its track list.
+    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
+        self.tracklist.iter().filter(|track| {
+            track
+                .track_type
+                .as_deref()
+                .is_none_or(|kind| kind == "track")
+        })
+    }
+
+    /// Find the track of the release that a track is, and score how well
+    /// the release fits it, from 0.0 to 1.0.
+    ///
+    /// The index is into [`Self::tracks`], and `None` if no track of the
+    /// release is close enough.
+    #[must_use]
+    pub fn match_track(&self, track: &apollo_core::Track) -> (Option<usize>, f64) {
+        let paired = self
+            .tracks()
+            .enumerate()
+            .map(|(index, release_track)| (index, pair_score(track, index, release_track)))
+            .filter(|(_, score)| *score >= MIN_PAIR_SCORE)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let album_score = track
+            .album_title
+            .as_deref()
+            .map_or(0.5, |album| similarity(album, &self.title));
+        let artist = track.album_artist.as_deref().unwrap_or(&track.artist);
+        let artist_score = similarity(artist, &self.artist_name());
+        let score = 0.6f64.mul_add(
+            paired.map_or(0.0, |(_, score)| score),
+            0.2f64.mul_add(album_score, 0.2 * artist_score),
+        );
+        (paired.map(|(index, _)| index), score)
+    }
+
+    /// Copy release metadata to a track, and the metadata of one of its
+    /// tracks, by index into [`Self::tracks`].
+    pub fn apply_to_track(&self, track: &mut apollo_core::Track, index: Option<usize>) {
+        track.album_title = Some(self.title.clone());
+        let album_artist = self.artist_name();
+        if !album_artist.is_empty() {
+            track.album_artist = Some(album_artist);
+        }
+        track.year = self.year.filter(|year| *year > 0).or(track.year);
+
+        let Some(release_track) = index.and_then(|index| self.tracks().nth(index)) else {
+            return;
+        };
+        track.title.clone_from(&release_track.title);
+        track.track_number = index.and_then(|index| u32::try_from(index + 1).ok());
+        track.track_total = u32::try_from(self.tracks().count()).ok();
+    }
+}
+
+/// Minimum score for a track to be paired with a track of a release.
+const MIN_PAIR_SCORE: f64 = 0.5;
+
+/// Score how well a track fits a track of a release, by title, position,
+/// and length.
+fn pair_score(track: &apollo_core::Track, index: usize, release_track: &Track) -> f64 {
+    let title = similarity(&track.title, &release_track.title);
+    let position = match track.track_number {
+        Some(number) if usize::try_from(number).is_ok_and(|number| number == index + 1) => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    };
+    let length = release_track.duration_ms().map_or(0.5, |length| {
+        #[allow(clippy::cast_possible_truncation)]
+        let duration = track.duration.as_millis() as u64;
+        // Full marks within 2 seconds, none beyond 12
+        #[allow(clippy::cast_precision_loss)]
+        let difference = duration.abs_diff(length).saturating_sub(2000) as f64;
+        1.0 - (difference / 10_000.0).min(1.0)
+    });
+    0.6f64.mul_add(title, 0.2f64.mul_add(position, 0.2 * length))
 }
 
 /// A master release from the Discogs API.
@@ -217,6 +385,19 @@ pub struct Label {
     pub resource_url: Option<String>,
 }
 
+/// An identifier printed on a release.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identifier {
+    /// Identifier type (e.g., "Barcode", "Matrix / Runout").
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The identifier value.
+    pub value: String,
+    /// Description (e.g., "Text", "Side A").
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
 /// A release format (CD, Vinyl, etc.).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Format {
@@ -403,6 +584,7 @@ mod tests {
             formats: vec![],
             country: None,
             released: None,
+            identifiers: vec![],
             resource_url: None,
             uri: None,
             master_id: None,
@@ -414,4 +596,94 @@ mod tests {
 
         assert_eq!(release.artist_name(), "Artist One & Artist Two");
     }
+
+    #[test]
+    fn test_apply_release_to_album() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": 1,
+                "title": "Greatest Hits",
+                "year": 1981,
+                "country": "UK",
+                "released": "1981-10-26",
+                "labels": [{"name": "EMI", "catno": "EMTV 30"}],
+                "identifiers": [
+                    {"type": "Matrix / Runout", "value": "EMTV 30 A-1"},
+                    {"type": "Barcode", "value": "0 77774 60012 5"}
+                ],
+                "formats": [{
+                    "name": "Vinyl",
+                    "text": "Deluxe Edition",
+                    "descriptions": ["LP", "Compilation"]
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let mut album = Album::new("Greatest Hits".to_string(), "Queen".to_string());
+        release.apply_to_album(&mut album);
+
+        assert_eq!(album.year, Some(1981));
+        assert_eq!(album.album_type, Some(AlbumType::Compilation));
+        assert_eq!(album.release_date, NaiveDate::from_ymd_opt(1981, 10, 26));
+        assert_eq!(album.country.as_deref(), Some("UK"));
+        assert_eq!(album.label.as_deref(), Some("EMI"));
+        assert_eq!(album.catalog_number.as_deref(), Some("EMTV 30"));
+        assert_eq!(album.barcode.as_deref(), Some("077774600125"));
+        assert_eq!(album.discogs_id, Some(1));
+        assert_eq!(album.media, Some(Media::Vinyl));
+        assert_eq!(album.edition.as_deref(), Some("Deluxe Edition"));
+    }
+
+    #[test]
+    fn test_match_and_apply_to_track() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": 1,
+                "title": "Pablo Honey",
+                "year": 1993,
+                "artists": [{"name": "Radiohead"}],
+                "tracklist": [
+                    {"position": "", "title": "Side A", "type_": "heading"},
+                    {"position": "A1", "title": "You", "duration": "3:28", "type_": "track"},
+                    {"position": "A2", "title": "Creep", "duration": "3:56", "type_": "track"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(release.tracks().count(), 2);
+
+        let mut track = apollo_core::Track::new(
+            std::path::PathBuf::from("/music/creep.mp3"),
+            "Creep".to_string(),
+            "Radiohead".to_string(),
+            std::time::Duration::from_secs(236),
+        );
+        let (index, score) = release.match_track(&track);
+        assert_eq!(index, Some(1));
+        assert!(score > 0.8, "score {score}");
+
+        release.apply_to_track(&mut track, index);
+        assert_eq!(track.album_title.as_deref(), Some("Pablo Honey"));
+        assert_eq!(track.album_artist.as_deref(), Some("Radiohead"));
+        assert_eq!(track.year, Some(1993));
+        assert_eq!(track.track_number, Some(2));
+        assert_eq!(track.track_total, Some(2));
+
+        let other = apollo_core::Track::new(
+            std::path::PathBuf::from("/music/other.mp3"),
+            "Something Else Entirely".to_string(),
+            "Nobody".to_string(),
+            std::time::Duration::from_secs(30),
+        );
+        assert_eq!(release.match_track(&other).0, None);
+    }
+
+    #[test]
+    fn test_release_date_unknown_day() {
+        let release: Release =
+            serde_json::from_str(r#"{"id": 1, "title": "Test", "released": "1975-00-00"}"#)
+                .unwrap();
+        assert_eq!(release.release_date(), None);
+    }
 }
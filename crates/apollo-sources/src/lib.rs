@@ -14,6 +14,22 @@
 //! - [Discogs](https://discogs.com/): Comprehensive music release database
 //! - [Cover Art Archive](https://coverartarchive.org/): Album cover art from [MusicBrainz](https://musicbrainz.org/)
 //!
+//! The [`artistart`] module finds images of artists on
+//! [fanart.tv](https://fanart.tv/) and [Wikidata](https://www.wikidata.org/).
+//!
+//! The [`scrobble`] module sends recorded plays to
+//! [Last.fm](https://www.last.fm/) and [ListenBrainz](https://listenbrainz.org/).
+//!
+//! The [`remote`] module talks to the API of another Apollo library, to
+//! copy the tracks one library is missing to the other.
+//!
+//! The [`streaming`] module reads playlists from
+//! [Spotify](https://spotify.com/) and [Deezer](https://deezer.com/), and
+//! matches their songs to local tracks.
+//!
+//! The [`migrate`] module reads the libraries of other music managers, such
+//! as [beets](https://beets.io/) and iTunes, to move them to Apollo.
+//!
 //! # Caching
 //!
 //! All clients support response caching to reduce API calls and improve performance.
@@ -117,11 +133,16 @@
 //! ```
 
 pub mod acoustid;
+pub mod artistart;
 pub mod cache;
 pub mod coverart;
 pub mod discogs;
 mod error;
+pub mod migrate;
 pub mod musicbrainz;
+pub mod remote;
+pub mod scrobble;
+pub mod streaming;
 
 pub use cache::{CacheConfig, ResponseCache};
 pub use error::{SourceError, SourceResult};
@@ -0,0 +1,257 @@
+//! Reading a [beets](https://beets.io/) library database.
+//!
+//! Track metadata comes from the `items` table. Play counts, last plays, and
+//! ratings are flexible attributes set by plugins such as `mpdstats`, or by
+//! hand with `beet modify rating=4`. Ratings that are whole numbers are taken
+//! as stars; fractions from 0 to 1, as `mpdstats` computes them, are scaled
+//! to five stars.
+//!
+//! beets keeps playlists as M3U files outside its database, so the migrated
+//! library has none.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use apollo_core::{AudioFormat, Track, TrackStats};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow};
+use sqlx::{Decode, Row, Sqlite, Type};
+use tracing::debug;
+
+use super::{MigratedLibrary, MigratedTrack, from_timestamp, split_genres};
+use crate::error::{SourceError, SourceResult};
+
+/// Read a beets library database, usually `~/.config/beets/library.db`.
+///
+/// # Errors
+///
+/// Returns an error if the file does not exist or is not a beets library.
+pub async fn read_library(path: &Path) -> SourceResult<MigratedLibrary> {
+    if !path.is_file() {
+        return Err(SourceError::InvalidInput(format!(
+            "beets library not found: {}",
+            path.display()
+        )));
+    }
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .map_err(|e| database_error(&e))?;
+
+    let items = sqlx::query(
+        r"SELECT id, CAST(path AS BLOB) AS path, title, artist, albumartist, album,
+                 track, tracktotal, disc, disctotal, year, genre, length, bitrate,
+                 samplerate, bitdepth, channels, format, mb_trackid, acoustid_id, added
+          FROM items
+          ORDER BY id",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| database_error(&e))?;
+
+    let mut attributes: HashMap<i64, HashMap<String, String>> = HashMap::new();
+    let rows =
+        sqlx::query("SELECT entity_id, key, CAST(value AS TEXT) AS value FROM item_attributes")
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| database_error(&e))?;
+    for row in rows {
+        if let (Some(id), Some(key), Some(value)) = (
+            get::<i64>(&row, "entity_id"),
+            get::<String>(&row, "key"),
+            get::<String>(&row, "value"),
+        ) {
+            attributes.entry(id).or_default().insert(key, value);
+        }
+    }
+    pool.close().await;
+
+    let tracks = items
+        .iter()
+        .filter_map(|row| {
+            let attributes = get::<i64>(row, "id").and_then(|id| attributes.get(&id));
+            read_item(row, attributes)
+        })
+        .collect::<Vec<_>>();
+    debug!("Read {} tracks from beets library", tracks.len());
+
+    Ok(MigratedLibrary {
+        tracks,
+        playlists: Vec::new(),
+    })
+}
+
+/// Read a track from a row of the `items` table.
+fn read_item(
+    row: &SqliteRow,
+    attributes: Option<&HashMap<String, String>>,
+) -> Option<MigratedTrack> {
+    let path = get::<Vec<u8>>(row, "path")?;
+    let path = PathBuf::from(String::from_utf8_lossy(&path).into_owned());
+    let text = |column: &str| get::<String>(row, column).filter(|value| !value.is_empty());
+    // beets stores zero for unknown numbers
+    let number = |column: &str| {
+        get::<i64>(row, column)
+            .and_then(|value| u32::try_from(value).ok())
+            .filter(|&value| value > 0)
+    };
+
+    let title = text("title").unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let length = get::<f64>(row, "length").unwrap_or_default();
+    let mut track = Track::new(
+        path.clone(),
+        title,
+        text("artist").unwrap_or_default(),
+        Duration::try_from_secs_f64(length).unwrap_or_default(),
+    );
+    track.album_artist = text("albumartist");
+    track.album_title = text("album");
+    track.track_number = number("track");
+    track.track_total = number("tracktotal");
+    track.disc_number = number("disc");
+    track.disc_total = number("disctotal");
+    track.year = number("year").and_then(|year| i32::try_from(year).ok());
+    track.genres = text("genre")
+        .map(|genre| split_genres(&genre))
+        .unwrap_or_default();
+    // Bitrates are in bits per second
+    track.bitrate = number("bitrate").map(|bitrate| bitrate / 1000);
+    track.sample_rate = number("samplerate");
+    track.bit_depth = number("bitdepth").and_then(|depth| u8::try_from(depth).ok());
+    track.channels = number("channels").and_then(|channels| u8::try_from(channels).ok());
+    track.format = path.extension().map_or(AudioFormat::Unknown, |ext| {
+        AudioFormat::from_extension(&ext.to_string_lossy())
+    });
+    track.musicbrainz_id = text("mb_trackid");
+    track.acoustid = text("acoustid_id");
+    if let Some(added) = get::<f64>(row, "added").and_then(from_timestamp) {
+        track.added_at = added;
+    }
+
+    let attribute = |key: &str| attributes.and_then(|attributes| attributes.get(key));
+    let stats = TrackStats {
+        rating: attribute("rating").and_then(|rating| parse_rating(rating)),
+        favorite: false,
+        play_count: attribute("play_count")
+            .and_then(|count| count.parse().ok())
+            .unwrap_or_default(),
+        last_played: attribute("last_played")
+            .and_then(|played| played.parse().ok())
+            .and_then(from_timestamp),
+    };
+
+    Some(MigratedTrack { track, stats })
+}
+
+/// Parse a rating as stars, or as a fraction of five stars.
+fn parse_rating(rating: &str) -> Option<u8> {
+    let rating = rating.trim();
+    if let Ok(stars) = rating.parse::<u8>() {
+        return (1..=5).contains(&stars).then_some(stars);
+    }
+    let fraction = rating
+        .parse::<f64>()
+        .ok()
+        .filter(|f| (0.0..=1.0).contains(f))?;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let stars = (fraction * 5.0).round() as u8;
+    (stars > 0).then_some(stars)
+}
+
+/// Get a column, treating missing columns and unexpected types as unset.
+fn get<'r, T>(row: &'r SqliteRow, column: &str) -> Option<T>
+where
+    T: Decode<'r, Sqlite> + Type<Sqlite>,
+{
+    row.try_get::<Option<T>, _>(column).ok().flatten()
+}
+
+fn database_error(e: &sqlx::Error) -> SourceError {
+    SourceError::Parse(format!("failed to read beets library: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_read_library() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("library.db");
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .unwrap();
+        for sql in [
+            r"CREATE TABLE items (id INTEGER PRIMARY KEY, path BLOB, title TEXT, artist TEXT,
+                albumartist TEXT, album TEXT, track INTEGER, tracktotal INTEGER, disc INTEGER,
+                disctotal INTEGER, year INTEGER, genre TEXT, length REAL, bitrate INTEGER,
+                samplerate INTEGER, bitdepth INTEGER, channels INTEGER, format TEXT,
+                mb_trackid TEXT, acoustid_id TEXT, added REAL)",
+            "CREATE TABLE item_attributes (id INTEGER PRIMARY KEY, entity_id INTEGER, key TEXT, value TEXT)",
+            r"INSERT INTO items VALUES (1, CAST('/music/Radiohead/02 Creep.mp3' AS BLOB), 'Creep',
+                'Radiohead', 'Radiohead', 'Pablo Honey', 2, 12, 1, 1, 1993, 'Alternative; Rock',
+                238.6, 320000, 44100, 0, 2, 'MP3', '', '', 1000000000.0)",
+            "INSERT INTO items (id, path, title, artist, length) VALUES (2, '/music/untitled.flac', '', '', 0)",
+            r"INSERT INTO item_attributes (entity_id, key, value) VALUES
+                (1, 'play_count', '7'), (1, 'last_played', '1100000000'), (1, 'rating', '0.8'),
+                (2, 'rating', '4')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        pool.close().await;
+
+        let library = read_library(&path).await.unwrap();
+        assert!(library.playlists.is_empty());
+        let [creep, untitled] = library.tracks.as_slice() else {
+            panic!("expected 2 tracks, got {}", library.tracks.len());
+        };
+
+        assert_eq!(
+            creep.track.path,
+            PathBuf::from("/music/Radiohead/02 Creep.mp3")
+        );
+        assert_eq!(creep.track.title, "Creep");
+        assert_eq!(creep.track.album_title.as_deref(), Some("Pablo Honey"));
+        assert_eq!(creep.track.track_number, Some(2));
+        assert_eq!(creep.track.year, Some(1993));
+        assert_eq!(creep.track.genres, vec!["Alternative", "Rock"]);
+        assert_eq!(creep.track.duration, Duration::from_millis(238_600));
+        assert_eq!(creep.track.bitrate, Some(320));
+        assert_eq!(creep.track.bit_depth, None);
+        assert_eq!(creep.track.format, AudioFormat::Mp3);
+        assert_eq!(creep.track.musicbrainz_id, None);
+        assert_eq!(creep.track.added_at.timestamp(), 1_000_000_000);
+        assert_eq!(creep.stats.play_count, 7);
+        assert_eq!(creep.stats.rating, Some(4));
+        assert_eq!(
+            creep.stats.last_played.map(|played| played.timestamp()),
+            Some(1_100_000_000)
+        );
+
+        assert_eq!(untitled.track.title, "untitled");
+        assert_eq!(untitled.track.format, AudioFormat::Flac);
+        assert_eq!(untitled.stats.rating, Some(4));
+        assert_eq!(untitled.stats.play_count, 0);
+    }
+
+    #[test]
+    fn test_parse_rating() {
+        assert_eq!(parse_rating("5"), Some(5));
+        assert_eq!(parse_rating("0"), None);
+        assert_eq!(parse_rating("7"), None);
+        assert_eq!(parse_rating("0.5"), Some(3));
+        assert_eq!(parse_rating("1.0"), Some(5));
+        assert_eq!(parse_rating("high"), None);
+    }
+}
@@ -0,0 +1,319 @@
+//! Reading an iTunes or Music.app library.
+//!
+//! iTunes writes its library as `iTunes Library.xml` next to the library
+//! itself. Music.app no longer does, but exports the same file with File >
+//! Library > Export Library.
+//!
+//! Only local music files are read; podcasts, videos, and streamed tracks
+//! are skipped. Ratings iTunes computed from an album's rating are ignored.
+//! Smart playlists are migrated as static playlists of the tracks they held
+//! when the library was exported, and built-in playlists such as "Music"
+//! are skipped.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use apollo_core::{AudioFormat, Track, TrackStats};
+use chrono::{DateTime, Utc};
+use plist::{Dictionary, Value};
+use tracing::debug;
+use url::Url;
+
+use super::{MigratedLibrary, MigratedPlaylist, MigratedTrack, rating_from_percent, split_genres};
+use crate::error::{SourceError, SourceResult};
+
+/// Read an iTunes or Music.app library XML file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is not an iTunes library.
+pub fn read_library(path: &Path) -> SourceResult<MigratedLibrary> {
+    let plist = Value::from_file(path)
+        .map_err(|e| SourceError::Parse(format!("failed to read iTunes library: {e}")))?;
+    let entries = plist
+        .as_dictionary()
+        .and_then(|root| root.get("Tracks"))
+        .and_then(Value::as_dictionary)
+        .ok_or_else(|| {
+            SourceError::InvalidInput(format!("not an iTunes library: {}", path.display()))
+        })?;
+
+    let mut tracks = Vec::new();
+    let mut paths: HashMap<u64, PathBuf> = HashMap::new();
+    for entry in entries.values().filter_map(Value::as_dictionary) {
+        let Some(migrated) = read_track(entry) else {
+            continue;
+        };
+        if let Some(id) = integer(entry, "Track ID") {
+            paths.insert(id, migrated.track.path.clone());
+        }
+        tracks.push(migrated);
+    }
+
+    let playlists = plist
+        .as_dictionary()
+        .and_then(|root| root.get("Playlists"))
+        .and_then(Value::as_array)
+        .map(|playlists| {
+            playlists
+                .iter()
+                .filter_map(Value::as_dictionary)
+                .filter_map(|playlist| read_playlist(playlist, &paths))
+                .collect()
+        })
+        .unwrap_or_default();
+    debug!("Read {} tracks from iTunes library", tracks.len());
+
+    Ok(MigratedLibrary { tracks, playlists })
+}
+
+/// Read a track entry, if it is a local music file.
+fn read_track(entry: &Dictionary) -> Option<MigratedTrack> {
+    let flag = |key: &str| entry.get(key).and_then(Value::as_boolean).unwrap_or(false);
+    if flag("Podcast") || flag("Has Video") || flag("Movie") {
+        return None;
+    }
+    let path = entry
+        .get("Location")
+        .and_then(Value::as_string)
+        .and_then(|location| Url::parse(location).ok())
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())?;
+
+    let text = |key: &str| {
+        entry
+            .get(key)
+            .and_then(Value::as_string)
+            .filter(|value| !value.is_empty())
+            .map(ToString::to_string)
+    };
+    let number = |key: &str| {
+        integer(entry, key)
+            .and_then(|value| u32::try_from(value).ok())
+            .filter(|&value| value > 0)
+    };
+
+    let title = text("Name").unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let mut track = Track::new(
+        path.clone(),
+        title,
+        text("Artist").unwrap_or_default(),
+        Duration::from_millis(integer(entry, "Total Time").unwrap_or_default()),
+    );
+    track.album_artist = text("Album Artist");
+    track.album_title = text("Album");
+    track.track_number = number("Track Number");
+    track.track_total = number("Track Count");
+    track.disc_number = number("Disc Number");
+    track.disc_total = number("Disc Count");
+    track.year = number("Year").and_then(|year| i32::try_from(year).ok());
+    track.genres = text("Genre")
+        .map(|genre| split_genres(&genre))
+        .unwrap_or_default();
+    track.bitrate = number("Bit Rate");
+    track.sample_rate = number("Sample Rate");
+    track.format = path.extension().map_or(AudioFormat::Unknown, |ext| {
+        AudioFormat::from_extension(&ext.to_string_lossy())
+    });
+    if let Some(added) = date(entry, "Date Added") {
+        track.added_at = added;
+    }
+
+    let stats = TrackStats {
+        rating: integer(entry, "Rating")
+            .filter(|_| !flag("Rating Computed"))
+            .and_then(rating_from_percent),
+        favorite: flag("Loved") || flag("Favorited"),
+        play_count: number("Play Count").unwrap_or_default(),
+        last_played: date(entry, "Play Date UTC"),
+    };
+
+    Some(MigratedTrack { track, stats })
+}
+
+/// Read a playlist made by the user, keeping the tracks that were read.
+fn read_playlist(playlist: &Dictionary, paths: &HashMap<u64, PathBuf>) -> Option<MigratedPlaylist> {
+    let flag = |key: &str| {
+        playlist
+            .get(key)
+            .and_then(Value::as_boolean)
+            .unwrap_or(false)
+    };
+    // The whole library, built-in playlists, and folders of playlists
+    if flag("Master") || flag("Folder") || playlist.contains_key("Distinguished Kind") {
+        return None;
+    }
+    let name = playlist.get("Name").and_then(Value::as_string)?;
+
+    let tracks = playlist
+        .get("Playlist Items")
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_dictionary)
+                .filter_map(|item| integer(item, "Track ID"))
+                .filter_map(|id| paths.get(&id).cloned())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(MigratedPlaylist {
+        name: name.to_string(),
+        tracks,
+    })
+}
+
+fn integer(dict: &Dictionary, key: &str) -> Option<u64> {
+    dict.get(key).and_then(Value::as_unsigned_integer)
+}
+
+fn date(dict: &Dictionary, key: &str) -> Option<DateTime<Utc>> {
+    dict.get(key)
+        .and_then(Value::as_date)
+        .map(|date| DateTime::from(SystemTime::from(date)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LIBRARY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple Computer//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Tracks</key>
+    <dict>
+        <key>101</key>
+        <dict>
+            <key>Track ID</key><integer>101</integer>
+            <key>Name</key><string>Creep</string>
+            <key>Artist</key><string>Radiohead</string>
+            <key>Album</key><string>Pablo Honey</string>
+            <key>Genre</key><string>Alternative</string>
+            <key>Total Time</key><integer>238640</integer>
+            <key>Track Number</key><integer>2</integer>
+            <key>Year</key><integer>1993</integer>
+            <key>Bit Rate</key><integer>256</integer>
+            <key>Date Added</key><date>2008-03-01T12:00:00Z</date>
+            <key>Play Count</key><integer>12</integer>
+            <key>Play Date UTC</key><date>2020-05-01T20:00:00Z</date>
+            <key>Rating</key><integer>80</integer>
+            <key>Loved</key><true/>
+            <key>Location</key><string>file:///Users/me/Music/Radiohead/Pablo%20Honey/02%20Creep.m4a</string>
+        </dict>
+        <key>102</key>
+        <dict>
+            <key>Track ID</key><integer>102</integer>
+            <key>Name</key><string>Episode 1</string>
+            <key>Podcast</key><true/>
+            <key>Location</key><string>file:///Users/me/Music/Podcasts/episode.mp3</string>
+        </dict>
+        <key>103</key>
+        <dict>
+            <key>Track ID</key><integer>103</integer>
+            <key>Name</key><string>Anyone Can Play Guitar</string>
+            <key>Artist</key><string>Radiohead</string>
+            <key>Rating</key><integer>60</integer>
+            <key>Rating Computed</key><true/>
+            <key>Location</key><string>file:///Users/me/Music/Radiohead/Pablo%20Honey/03.mp3</string>
+        </dict>
+        <key>104</key>
+        <dict>
+            <key>Track ID</key><integer>104</integer>
+            <key>Name</key><string>Streamed</string>
+            <key>Location</key><string>https://example.com/stream.mp3</string>
+        </dict>
+    </dict>
+    <key>Playlists</key>
+    <array>
+        <dict>
+            <key>Name</key><string>Library</string>
+            <key>Master</key><true/>
+            <key>Playlist Items</key>
+            <array><dict><key>Track ID</key><integer>101</integer></dict></array>
+        </dict>
+        <dict>
+            <key>Name</key><string>Music</string>
+            <key>Distinguished Kind</key><integer>4</integer>
+        </dict>
+        <dict>
+            <key>Name</key><string>Road Trip</string>
+            <key>Playlist Items</key>
+            <array>
+                <dict><key>Track ID</key><integer>103</integer></dict>
+                <dict><key>Track ID</key><integer>102</integer></dict>
+                <dict><key>Track ID</key><integer>101</integer></dict>
+            </array>
+        </dict>
+    </array>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn test_read_library() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("Library.xml");
+        std::fs::write(&path, LIBRARY).unwrap();
+
+        let library = read_library(&path).unwrap();
+        let [creep, guitar] = library.tracks.as_slice() else {
+            panic!("expected 2 tracks, got {}", library.tracks.len());
+        };
+
+        let creep_path = PathBuf::from("/Users/me/Music/Radiohead/Pablo Honey/02 Creep.m4a");
+        assert_eq!(creep.track.path, creep_path);
+        assert_eq!(creep.track.title, "Creep");
+        assert_eq!(creep.track.album_title.as_deref(), Some("Pablo Honey"));
+        assert_eq!(creep.track.genres, vec!["Alternative"]);
+        assert_eq!(creep.track.duration, Duration::from_millis(238_640));
+        assert_eq!(creep.track.track_number, Some(2));
+        assert_eq!(creep.track.year, Some(1993));
+        assert_eq!(creep.track.format, AudioFormat::Aac);
+        assert_eq!(
+            creep.track.added_at.to_rfc3339(),
+            "2008-03-01T12:00:00+00:00"
+        );
+        assert_eq!(creep.stats.play_count, 12);
+        assert_eq!(creep.stats.rating, Some(4));
+        assert!(creep.stats.favorite);
+        assert_eq!(
+            creep.stats.last_played.map(|played| played.to_rfc3339()),
+            Some("2020-05-01T20:00:00+00:00".to_string())
+        );
+
+        assert_eq!(guitar.stats.rating, None);
+        assert_eq!(guitar.stats.play_count, 0);
+
+        assert_eq!(
+            library.playlists,
+            vec![MigratedPlaylist {
+                name: "Road Trip".to_string(),
+                tracks: vec![guitar.track.path.clone(), creep_path],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_not_a_library() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("Library.xml");
+        std::fs::write(
+            &path,
+            r#"<?xml version="1.0" encoding="UTF-8"?><plist version="1.0"><array/></plist>"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            read_library(&path),
+            Err(SourceError::InvalidInput(_))
+        ));
+        assert!(read_library(&dir.path().join("missing.xml")).is_err());
+    }
+}
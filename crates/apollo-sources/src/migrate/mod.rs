@@ -0,0 +1,178 @@
+//! Reading other music libraries, to migrate them to Apollo.
+//!
+//! Re-importing the files of another library loses everything that only
+//! lives in its database: ratings, play counts, playlists, and when tracks
+//! were added. These readers bring that history along:
+//!
+//! - [`beets`] reads a [beets](https://beets.io/) library database, including
+//!   the play counts and ratings its plugins store as flexible attributes
+//! - [`itunes`] reads the `Library.xml` export of iTunes or Music.app
+//!
+//! Both produce a [`MigratedLibrary`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use apollo_sources::migrate::itunes;
+//! use std::path::Path;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let library = itunes::read_library(Path::new("Library.xml"))?;
+//! for migrated in &library.tracks {
+//!     println!(
+//!         "{} - {}: played {} times",
+//!         migrated.track.artist, migrated.track.title, migrated.stats.play_count
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod beets;
+pub mod itunes;
+
+use std::path::PathBuf;
+
+use apollo_core::{Track, TrackStats};
+use chrono::{DateTime, Utc};
+
+/// A track read from another library.
+#[derive(Debug, Clone)]
+pub struct MigratedTrack {
+    /// Track metadata, including the path to the file and when the track
+    /// was added.
+    ///
+    /// Audio properties such as the format and bitrate are only as accurate
+    /// as the other library recorded them; prefer reading them from the file.
+    pub track: Track,
+    /// Rating, favorite, play count, and last play.
+    pub stats: TrackStats,
+}
+
+impl MigratedTrack {
+    /// Copy the migrated metadata onto a track read from the file.
+    ///
+    /// Tags and the added date come from the other library, which may have
+    /// been edited there without being written to the file. The file's
+    /// audio properties are kept.
+    pub fn merge_into(&self, track: &mut Track) {
+        let migrated = &self.track;
+        track.title.clone_from(&migrated.title);
+        track.artist.clone_from(&migrated.artist);
+        let keep = |migrated: &Option<String>, current: &mut Option<String>| {
+            if migrated.is_some() {
+                current.clone_from(migrated);
+            }
+        };
+        keep(&migrated.album_artist, &mut track.album_artist);
+        keep(&migrated.album_title, &mut track.album_title);
+        keep(&migrated.musicbrainz_id, &mut track.musicbrainz_id);
+        keep(&migrated.acoustid, &mut track.acoustid);
+        keep(&migrated.isrc, &mut track.isrc);
+        track.track_number = migrated.track_number.or(track.track_number);
+        track.track_total = migrated.track_total.or(track.track_total);
+        track.disc_number = migrated.disc_number.or(track.disc_number);
+        track.disc_total = migrated.disc_total.or(track.disc_total);
+        track.year = migrated.year.or(track.year);
+        if !migrated.genres.is_empty() {
+            track.genres.clone_from(&migrated.genres);
+        }
+        track.added_at = migrated.added_at;
+    }
+}
+
+/// A static playlist read from another library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigratedPlaylist {
+    /// Playlist name.
+    pub name: String,
+    /// Paths of the tracks, in playlist order.
+    pub tracks: Vec<PathBuf>,
+}
+
+/// Everything read from another library.
+#[derive(Debug, Clone, Default)]
+pub struct MigratedLibrary {
+    /// Tracks, in the order the other library stores them.
+    pub tracks: Vec<MigratedTrack>,
+    /// Playlists of those tracks.
+    pub playlists: Vec<MigratedPlaylist>,
+}
+
+/// Convert a rating on a 0 to 100 scale, as used by iTunes, to stars.
+///
+/// Zero means unrated.
+fn rating_from_percent(percent: u64) -> Option<u8> {
+    // 20 points per star; round half stars up
+    let stars = (percent.min(100) + 10) / 20;
+    u8::try_from(stars).ok().filter(|&stars| stars > 0)
+}
+
+/// Convert a Unix timestamp in seconds to a date.
+fn from_timestamp(seconds: f64) -> Option<DateTime<Utc>> {
+    #[allow(clippy::cast_possible_truncation)]
+    DateTime::from_timestamp(seconds.trunc() as i64, 0).filter(|_| seconds > 0.0)
+}
+
+/// Split a genre tag holding several genres.
+fn split_genres(genre: &str) -> Vec<String> {
+    genre
+        .split([';', ','])
+        .map(str::trim)
+        .filter(|genre| !genre.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn track(title: &str) -> Track {
+        Track::new(
+            PathBuf::from("/music/track.flac"),
+            title.to_string(),
+            "Radiohead".to_string(),
+            Duration::from_secs(238),
+        )
+    }
+
+    #[test]
+    fn test_merge_into() {
+        let mut migrated = track("Creep");
+        migrated.album_title = Some("Pablo Honey".to_string());
+        migrated.genres = vec!["Alternative".to_string()];
+        migrated.added_at = DateTime::from_timestamp(1_000_000_000, 0).unwrap();
+        let migrated = MigratedTrack {
+            track: migrated,
+            stats: TrackStats::default(),
+        };
+
+        let mut file = track("creep");
+        file.year = Some(1993);
+        file.bitrate = Some(1000);
+        migrated.merge_into(&mut file);
+
+        assert_eq!(file.title, "Creep");
+        assert_eq!(file.album_title.as_deref(), Some("Pablo Honey"));
+        assert_eq!(file.year, Some(1993));
+        assert_eq!(file.bitrate, Some(1000));
+        assert_eq!(file.genres, vec!["Alternative"]);
+        assert_eq!(file.added_at, migrated.track.added_at);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(rating_from_percent(0), None);
+        assert_eq!(rating_from_percent(20), Some(1));
+        assert_eq!(rating_from_percent(50), Some(3));
+        assert_eq!(rating_from_percent(100), Some(5));
+        assert_eq!(from_timestamp(0.0), None);
+        assert_eq!(
+            from_timestamp(1_000_000_000.5),
+            DateTime::from_timestamp(1_000_000_000, 0)
+        );
+        assert_eq!(split_genres("Rock; Pop,"), vec!["Rock", "Pop"]);
+    }
+}
@@ -123,6 +123,14 @@ impl CachedMusicBrainzClient {
         self.release_lookup_cache.clear().await;
     }
 
+    /// Remove expired entries from all caches.
+    pub async fn prune_cache(&self) {
+        self.recording_search_cache.cleanup().await;
+        self.release_search_cache.cleanup().await;
+        self.recording_lookup_cache.cleanup().await;
+        self.release_lookup_cache.cleanup().await;
+    }
+
     /// Get cache statistics.
     pub async fn cache_stats(&self) -> CacheStats {
         CacheStats {
@@ -376,7 +384,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_cached_client_creation() {
-        let config = CacheConfig::new().with_ttl(Duration::from_secs(60));
+        let config = CacheConfig::new().with_ttl(Duration::from_mins(1));
         let client = CachedMusicBrainzClient::new("TestApp", "0.1", "test@example.com", config);
         assert!(client.is_ok());
     }
@@ -9,7 +9,7 @@ use reqwest::header::{ACCEPT, HeaderMap, HeaderValue, USER_AGENT};
 use std::fmt::Write;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use tracing::{debug, warn};
+use tracing::{debug, instrument, warn};
 
 /// API base URL.
 const API_BASE: &str = "https://musicbrainz.org/ws/2";
@@ -20,6 +20,7 @@ const MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(1100);
 /// API client with rate limiting.
 pub struct MusicBrainzClient {
     client: Client,
+    base_url: String,
     last_request: Mutex<Instant>,
 }
 
@@ -53,6 +54,7 @@ impl MusicBrainzClient {
 
         Ok(Self {
             client,
+            base_url: API_BASE.to_string(),
             // Initialize to past so first request goes through immediately
             last_request: Mutex::new(
                 Instant::now()
@@ -62,13 +64,20 @@ impl MusicBrainzClient {
         })
     }
 
+    /// Use another API endpoint, such as a mirror or a mock server.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
     /// Wait for rate limiting before making a request.
     async fn wait_for_rate_limit(&self) {
         let mut last = self.last_request.lock().await;
         let elapsed = last.elapsed();
 
         if elapsed < MIN_REQUEST_INTERVAL {
-            let wait = MIN_REQUEST_INTERVAL - elapsed;
+            let wait = MIN_REQUEST_INTERVAL.saturating_sub(elapsed);
             debug!("Rate limiting: waiting {:?}", wait);
             tokio::time::sleep(wait).await;
         }
@@ -77,11 +86,12 @@ impl MusicBrainzClient {
     }
 
     /// Make a GET request to the API.
+    #[instrument(name = "musicbrainz_request", level = "debug", skip(self))]
     async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> SourceResult<T> {
         self.wait_for_rate_limit().await;
 
-        let url = format!("{API_BASE}{path}");
-        debug!("GET {url}");
+        let url = format!("{}{path}", self.base_url);
+        debug!(monotonic_counter.musicbrainz_requests = 1_u64, "GET {url}");
 
         let response = self.client.get(&url).send().await?;
         let status = response.status();
@@ -278,7 +288,7 @@ impl MusicBrainzClient {
 }
 
 /// Escape special Lucene query characters.
-fn escape_lucene(s: &str) -> String {
+pub fn escape_lucene(s: &str) -> String {
     let special = [
         '+', '-', '&', '|', '!', '(', ')', '{', '}', '[', ']', '^', '"', '~', '*', '?', ':', '\\',
         '/',
@@ -0,0 +1,478 @@
+//! Matching groups of tracks to releases.
+//!
+//! [`ReleaseMatcher`] finds releases that a group of tracks, usually one
+//! album's files, could belong to and ranks them by how well they fit. Each
+//! [`ReleaseCandidate`] pairs the tracks with the release's tracks, so its
+//! metadata can be shown as a per-track diff before it is applied.
+//!
+//! The score of a candidate combines how closely the album title and artist
+//! match the tags, and how well the tracks match the release's track list by
+//! title, position, and length. Missing and extra tracks lower the score.
+
+use apollo_core::metadata::Track;
+use std::collections::HashSet;
+
+use super::cached::CachedMusicBrainzClient;
+use super::client::MusicBrainzClient;
+use super::types::Release;
+use crate::error::SourceResult;
+
+/// Related entities to fetch for each candidate release.
+const RELEASE_INCLUDES: &[&str] = &[
+    "recordings",
+    "artist-credits",
+    "isrcs",
+    "labels",
+    "release-groups",
+];
+
+/// Minimum score for two tracks to be paired.
+const MIN_PAIR_SCORE: f64 = 0.5;
+
+/// A track on a release, with its position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseTrack {
+    /// Disc number, starting at 1.
+    pub disc_number: u32,
+    /// Number of discs on the release.
+    pub disc_total: u32,
+    /// Track number on the disc, starting at 1.
+    pub track_number: u32,
+    /// Number of tracks on the disc.
+    pub track_total: u32,
+    /// Track title.
+    pub title: String,
+    /// Track artist; empty if only the release artist is known.
+    pub artist: String,
+    /// The MBID of the recording.
+    pub recording_id: Option<String>,
+    /// ISRC of the recording.
+    pub isrc: Option<String>,
+    /// Length in milliseconds.
+    pub length: Option<u64>,
+}
+
+/// A release that a group of tracks may belong to.
+#[derive(Debug, Clone)]
+pub struct ReleaseCandidate {
+    /// The release, with its track list.
+    pub release: Release,
+    /// How well the release fits the tracks, from 0.0 to 1.0.
+    pub score: f64,
+    /// The release track paired with each track, by index into
+    /// [`Self::release_tracks`].
+    pub pairs: Vec<Option<usize>>,
+    /// The tracks of the release, in order.
+    pub release_tracks: Vec<ReleaseTrack>,
+}
+
+impl ReleaseCandidate {
+    /// Score how well a release fits a group of tracks.
+    #[must_use]
+    pub fn new(release: Release, tracks: &[Track]) -> Self {
+        let release_tracks = release_tracks(&release);
+        let pairs = pair_tracks(tracks, &release_tracks);
+
+        let paired: f64 = pairs
+            .iter()
+            .zip(tracks)
+            .filter_map(|(pair, track)| pair.map(|i| pair_score(track, &release_tracks[i])))
+            .sum();
+        #[allow(clippy::cast_precision_loss)]
+        let coverage = paired / tracks.len().max(release_tracks.len()).max(1) as f64;
+
+        let album = tracks.iter().find_map(|t| t.album_title.as_deref());
+        let artist = tracks
+            .iter()
+            .find_map(|t| t.album_artist.as_deref())
+            .or_else(|| tracks.first().map(|t| t.artist.as_str()));
+        let album_score = album.map_or(0.5, |album| similarity(album, &release.title));
+        let artist_score = artist.map_or(0.5, |artist| similarity(artist, &release.artist_name()));
+
+        let score = 0.6f64.mul_add(coverage, 0.2f64.mul_add(album_score, 0.2 * artist_score));
+        Self {
+            release,
+            score,
+            pairs,
+            release_tracks,
+        }
+    }
+
+    /// Get the release track paired with a track, by index.
+    #[must_use]
+    pub fn release_track(&self, index: usize) -> Option<&ReleaseTrack> {
+        self.pairs
+            .get(index)
+            .copied()
+            .flatten()
+            .map(|i| &self.release_tracks[i])
+    }
+
+    /// Get the number of release tracks not paired with any track.
+    #[must_use]
+    pub fn missing_tracks(&self) -> usize {
+        self.release_tracks.len() - self.pairs.iter().flatten().count()
+    }
+
+    /// Copy the release metadata to the tracks it was scored against.
+    ///
+    /// Tracks without a paired release track only get the album metadata.
+    pub fn apply(&self, tracks: &mut [Track]) {
+        let album_artist = self.release.artist_name();
+        for (index, track) in tracks.iter_mut().enumerate() {
+            track.album_title = Some(self.release.title.clone());
+            if !album_artist.is_empty() {
+                track.album_artist = Some(album_artist.clone());
+            }
+            track.year = self.release.year().or(track.year);
+
+            let Some(release_track) = self.release_track(index) else {
+                continue;
+            };
+            track.title.clone_from(&release_track.title);
+            if !release_track.artist.is_empty() {
+                track.artist.clone_from(&release_track.artist);
+            } else if !album_artist.is_empty() {
+                track.artist.clone_from(&album_artist);
+            }
+            track.track_number = Some(release_track.track_number);
+            track.track_total = Some(release_track.track_total);
+            track.disc_number = Some(release_track.disc_number);
+            track.disc_total = Some(release_track.disc_total);
+            if let Some(ref recording_id) = release_track.recording_id {
+                track.musicbrainz_id = Some(recording_id.clone());
+            }
+            if let Some(ref isrc) = release_track.isrc {
+                track.isrc = Some(isrc.clone());
+            }
+        }
+    }
+}
+
+/// The client a matcher sends its requests through.
+#[derive(Clone, Copy)]
+enum Client<'a> {
+    Direct(&'a MusicBrainzClient),
+    Cached(&'a CachedMusicBrainzClient),
+}
+
+impl Client<'_> {
+    async fn search_releases(
+        self,
+        title: &str,
+        artist: Option<&str>,
+        limit: u32,
+    ) -> SourceResult<Vec<Release>> {
+        match self {
+            Self::Direct(client) => client.search_releases(title, artist, limit).await,
+            Self::Cached(client) => client.search_releases(title, artist, limit).await,
+        }
+    }
+
+    async fn lookup_release(self, mbid: &str, include: &[&str]) -> SourceResult<Release> {
+        match self {
+            Self::Direct(client) => client.lookup_release(mbid, include).await,
+            Self::Cached(client) => client.lookup_release(mbid, include).await,
+        }
+    }
+}
+
+/// Finds and ranks releases for groups of tracks.
+pub struct ReleaseMatcher<'a> {
+    client: Client<'a>,
+    max_candidates: u32,
+}
+
+impl<'a> ReleaseMatcher<'a> {
+    /// Create a matcher that looks up at most 5 candidates per search.
+    #[must_use]
+    pub const fn new(client: &'a MusicBrainzClient) -> Self {
+        Self {
+            client: Client::Direct(client),
+            max_candidates: 5,
+        }
+    }
+
+    /// Create a matcher that reuses cached responses.
+    ///
+    /// Matching many albums often looks up the same releases, such as an
+    /// artist's albums that turn up in each other's searches.
+    #[must_use]
+    pub const fn cached(client: &'a CachedMusicBrainzClient) -> Self {
+        Self {
+            client: Client::Cached(client),
+            max_candidates: 5,
+        }
+    }
+
+    /// Set the maximum number of candidates per search.
+    ///
+    /// Each candidate takes a request to fetch its track list.
+    #[must_use]
+    pub const fn with_max_candidates(mut self, max_candidates: u32) -> Self {
+        self.max_candidates = max_candidates;
+        self
+    }
+
+    /// Find candidates using the album title and artist from the tracks'
+    /// tags.
+    ///
+    /// Returns no candidates if the tracks have no album title.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an API request fails.
+    pub async fn candidates(&self, tracks: &[Track]) -> SourceResult<Vec<ReleaseCandidate>> {
+        let Some(album) = tracks.iter().find_map(|t| t.album_title.as_deref()) else {
+            return Ok(Vec::new());
+        };
+        let artist = tracks
+            .iter()
+            .find_map(|t| t.album_artist.as_deref())
+            .or_else(|| tracks.first().map(|t| t.artist.as_str()));
+        self.search(tracks, album, artist).await
+    }
+
+    /// Find candidates by album title and, optionally, artist.
+    ///
+    /// Candidates are sorted by score, best first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an API request fails.
+    pub async fn search(
+        &self,
+        tracks: &[Track],
+        album: &str,
+        artist: Option<&str>,
+    ) -> SourceResult<Vec<ReleaseCandidate>> {
+        let releases = self
+            .client
+            .search_releases(album, artist, self.max_candidates)
+            .await?;
+
+        let mut candidates = Vec::with_capacity(releases.len());
+        for release in releases {
+            candidates.push(self.lookup(tracks, &release.id).await?);
+        }
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(candidates)
+    }
+
+    /// Score a release, given its MBID, against the tracks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the API request fails or the release is not
+    /// found.
+    pub async fn lookup(
+        &self,
+        tracks: &[Track],
+        release_id: &str,
+    ) -> SourceResult<ReleaseCandidate> {
+        let release = self
+            .client
+            .lookup_release(release_id, RELEASE_INCLUDES)
+            .await?;
+        Ok(ReleaseCandidate::new(release, tracks))
+    }
+}
+
+/// List the tracks of a release, in order.
+fn release_tracks(release: &Release) -> Vec<ReleaseTrack> {
+    let disc_total = u32::try_from(release.media.len()).unwrap_or(u32::MAX);
+    let mut tracks = Vec::new();
+    for (disc_index, medium) in release.media.iter().enumerate() {
+        let disc_number = medium
+            .position
+            .unwrap_or_else(|| u32::try_from(disc_index + 1).unwrap_or(u32::MAX));
+        let track_total = medium
+            .track_count
+            .unwrap_or_else(|| u32::try_from(medium.tracks.len()).unwrap_or(u32::MAX));
+        for (track_index, track) in medium.tracks.iter().enumerate() {
+            let recording = track.recording.as_ref();
+            let title = track
+                .title
+                .clone()
+                .or_else(|| recording.map(|r| r.title.clone()))
+                .unwrap_or_default();
+            tracks.push(ReleaseTrack {
+                disc_number,
+                disc_total,
+                track_number: track
+                    .position
+                    .unwrap_or_else(|| u32::try_from(track_index + 1).unwrap_or(u32::MAX)),
+                track_total,
+                title,
+                artist: recording
+                    .map(super::types::Recording::artist_name)
+                    .unwrap_or_default(),
+                recording_id: recording.map(|r| r.id.clone()),
+                isrc: recording.and_then(|r| r.isrcs.first().cloned()),
+                length: track.length.or_else(|| recording.and_then(|r| r.length)),
+            });
+        }
+    }
+    tracks
+}
+
+/// Pair tracks with release tracks, best pairs first.
+fn pair_tracks(tracks: &[Track], release_tracks: &[ReleaseTrack]) -> Vec<Option<usize>> {
+    let mut scored = Vec::new();
+    for (i, track) in tracks.iter().enumerate() {
+        for (j, release_track) in release_tracks.iter().enumerate() {
+            let score = pair_score(track, release_track);
+            if score >= MIN_PAIR_SCORE {
+                scored.push((score, i, j));
+            }
+        }
+    }
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut pairs = vec![None; tracks.len()];
+    let mut taken = HashSet::new();
+    for (_, i, j) in scored {
+        if pairs[i].is_none() && !taken.contains(&j) {
+            pairs[i] = Some(j);
+            taken.insert(j);
+        }
+    }
+    pairs
+}
+
+/// Score how well a track matches a release track, from 0.0 to 1.0.
+fn pair_score(track: &Track, release_track: &ReleaseTrack) -> f64 {
+    let title = similarity(&track.title, &release_track.title);
+    let position = match track.track_number {
+        Some(number)
+            if number == release_track.track_number
+                && track.disc_number.unwrap_or(1) == release_track.disc_number =>
+        {
+            1.0
+        }
+        // Right number on another disc
+        Some(number) if number == release_track.track_number => 0.5,
+        Some(_) => 0.0,
+        None => 0.5,
+    };
+    let length = release_track.length.map_or(0.5, |length| {
+        #[allow(clippy::cast_possible_truncation)]
+        let duration = track.duration.as_millis() as u64;
+        // Full marks within 2 seconds, none beyond 12
+        #[allow(clippy::cast_precision_loss)]
+        let difference = duration.abs_diff(length).saturating_sub(2000) as f64;
+        1.0 - (difference / 10_000.0).min(1.0)
+    });
+    0.6f64.mul_add(title, 0.2f64.mul_add(position, 0.2 * length))
+}
+
+/// Compare two names, ignoring case, punctuation, and spacing.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    strsim::normalized_levenshtein(&normalize(a), &normalize(b))
+}
+
+fn normalize(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn release() -> Release {
+        serde_json::from_str(
+            r#"{
+                "id": "release",
+                "title": "Pablo Honey",
+                "date": "1993-02-22",
+                "artist-credit": [{"artist": {"id": "radiohead", "name": "Radiohead"}}],
+                "media": [{
+                    "position": 1,
+                    "track-count": 3,
+                    "tracks": [
+                        {"id": "t1", "position": 1, "title": "You", "length": 208000,
+                         "recording": {"id": "r1", "title": "You", "isrcs": ["GBAYE9200070"]}},
+                        {"id": "t2", "position": 2, "title": "Creep", "length": 238000,
+                         "recording": {"id": "r2", "title": "Creep"}},
+                        {"id": "t3", "position": 3, "title": "How Do You?", "length": 132000,
+                         "recording": {"id": "r3", "title": "How Do You?"}}
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn track(title: &str, number: Option<u32>, secs: u64) -> Track {
+        let mut track = Track::new(
+            PathBuf::from(format!("/music/{title}.mp3")),
+            title.to_string(),
+            "radiohead".to_string(),
+            Duration::from_secs(secs),
+        );
+        track.album_title = Some("Pablo Honey".to_string());
+        track.track_number = number;
+        track
+    }
+
+    #[test]
+    fn test_pairs_and_applies_tracks() {
+        let mut tracks = vec![
+            track("creep", None, 239),
+            track("How do you", Some(3), 130),
+            track("You", Some(1), 208),
+        ];
+        let candidate = ReleaseCandidate::new(release(), &tracks);
+
+        assert_eq!(candidate.pairs, vec![Some(1), Some(2), Some(0)]);
+        assert_eq!(candidate.missing_tracks(), 0);
+        assert!(candidate.score > 0.9, "score {}", candidate.score);
+
+        candidate.apply(&mut tracks);
+        assert_eq!(tracks[0].title, "Creep");
+        assert_eq!(tracks[0].artist, "Radiohead");
+        assert_eq!(tracks[0].track_number, Some(2));
+        assert_eq!(tracks[0].track_total, Some(3));
+        assert_eq!(tracks[0].disc_total, Some(1));
+        assert_eq!(tracks[0].musicbrainz_id.as_deref(), Some("r2"));
+        assert_eq!(tracks[0].album_artist.as_deref(), Some("Radiohead"));
+        assert_eq!(tracks[0].year, Some(1993));
+        assert_eq!(tracks[2].isrc.as_deref(), Some("GBAYE9200070"));
+    }
+
+    #[test]
+    fn test_missing_and_unknown_tracks_lower_score() {
+        let complete = ReleaseCandidate::new(
+            release(),
+            &[
+                track("You", Some(1), 208),
+                track("Creep", Some(2), 238),
+                track("How Do You?", Some(3), 132),
+            ],
+        );
+        let partial = ReleaseCandidate::new(
+            release(),
+            &[
+                track("You", Some(1), 208),
+                track("Anyone Can Play Guitar", Some(4), 218),
+            ],
+        );
+
+        assert_eq!(partial.pairs, vec![Some(0), None]);
+        assert_eq!(partial.missing_tracks(), 2);
+        assert!(partial.score < complete.score);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("How Do You?"), "how do you");
+        assert_eq!(normalize("  AC/DC "), "ac dc");
+        assert!((similarity("The Bends", "the bends!") - 1.0).abs() < f64::EPSILON);
+    }
+}
@@ -50,10 +50,14 @@
 
 mod cached;
 mod client;
+mod matcher;
 mod types;
 
 pub use cached::{CacheStats, CachedMusicBrainzClient};
 pub use client::MusicBrainzClient;
+pub(crate) use client::escape_lucene;
+pub(crate) use matcher::similarity;
+pub use matcher::{ReleaseCandidate, ReleaseMatcher, ReleaseTrack};
 pub use types::{
     Artist, ArtistCredit, Medium, Recording, RecordingSearchResponse, Release, ReleaseGroup,
     ReleaseSearchResponse, Track,
@@ -1,5 +1,7 @@
 //! [MusicBrainz](https://musicbrainz.org/) API response types.
 
+use apollo_core::{Album, AlbumType, Media};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 
@@ -43,6 +45,30 @@ impl Recording {
                 acc
             })
     }
+
+    /// Copy recording metadata to a track.
+    ///
+    /// The album title and year come from the first release of the
+    /// recording, if it was looked up with its releases.
+    pub fn apply_to_track(&self, track: &mut apollo_core::Track) {
+        track.musicbrainz_id = Some(self.id.clone());
+        track.title.clone_from(&self.title);
+        let artist = self.artist_name();
+        if !artist.is_empty() {
+            track.artist = artist;
+        }
+        if let Some(isrc) = self.isrcs.first() {
+            track.isrc = Some(isrc.clone());
+        }
+        if let Some(release) = self.releases.first() {
+            track.album_title = Some(release.title.clone());
+            track.year = release.year().or(track.year);
+            let album_artist = release.artist_name();
+            if !album_artist.is_empty() {
+                track.album_artist = Some(album_artist);
+            }
+        }
+    }
 }
 
 /// A release (album/single/EP) from the API.
@@ -76,6 +102,12 @@ pub struct Release {
     /// Media (discs/sides) on this release.
     #[serde(default)]
     pub media: Vec<Medium>,
+    /// Labels and catalog numbers (requires `inc=labels`).
+    #[serde(default, rename = "label-info")]
+    pub label_info: Vec<LabelInfo>,
+    /// Barcode (UPC/EAN); empty if the release has none.
+    #[serde(default)]
+    pub barcode: Option<String>,
     /// Score from search results (0-100).
     #[serde(default)]
     pub score: Option<u8>,
@@ -103,6 +135,85 @@ impl Release {
             .and_then(|d| d.split('-').next())
             .and_then(|y| y.parse().ok())
     }
+
+    /// Get the full release date, if the day is known.
+    #[must_use]
+    pub fn release_date(&self) -> Option<NaiveDate> {
+        self.date
+            .as_deref()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    }
+
+    /// Get the album type from the release group.
+    ///
+    /// Secondary types such as "Live" or "Compilation" take precedence over
+    /// the primary type.
+    #[must_use]
+    pub fn album_type(&self) -> Option<AlbumType> {
+        let group = self.release_group.as_ref()?;
+        group
+            .secondary_types
+            .iter()
+            .find_map(|t| AlbumType::from_name(t))
+            .or_else(|| group.primary_type.as_deref().and_then(AlbumType::from_name))
+    }
+
+    /// Get the medium of the release, from the format of its first medium
+    /// with a known one.
+    #[must_use]
+    pub fn media_format(&self) -> Option<Media> {
+        self.media
+            .iter()
+            .find_map(|medium| medium.format.as_deref().and_then(Media::from_name))
+    }
+
+    /// Copy release metadata to an album.
+    ///
+    /// Fields missing from the release are left unchanged.
+    pub fn apply_to_album(&self, album: &mut Album) {
+        album.musicbrainz_id = Some(self.id.clone());
+        album.year = self.year().or(album.year);
+        album.album_type = self.album_type().or(album.album_type);
+        album.release_date = self.release_date().or(album.release_date);
+        album.media = self.media_format().or(album.media);
+        if let Some(ref country) = self.country {
+            album.country = Some(country.clone());
+        }
+        if let Some(disambiguation) = self.disambiguation.as_deref().filter(|d| !d.is_empty()) {
+            album.disambiguation = Some(disambiguation.to_string());
+        }
+        if let Some(barcode) = self.barcode.as_deref().filter(|b| !b.is_empty()) {
+            album.barcode = Some(barcode.to_string());
+        }
+        if let Some(info) = self.label_info.first() {
+            if let Some(ref label) = info.label {
+                album.label = Some(label.name.clone());
+            }
+            if let Some(ref catalog_number) = info.catalog_number {
+                album.catalog_number = Some(catalog_number.clone());
+            }
+        }
+    }
+}
+
+/// A label and catalog number of a release.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelInfo {
+    /// The catalog number.
+    #[serde(default, rename = "catalog-number")]
+    pub catalog_number: Option<String>,
+    /// The label.
+    #[serde(default)]
+    pub label: Option<Label>,
+}
+
+/// A record label.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Label {
+    /// The MBID of the label.
+    pub id: String,
+    /// The label name.
+    pub name: String,
 }
 
 /// A release group (album, EP, single, etc.).
@@ -216,3 +327,102 @@ pub struct ReleaseSearchResponse {
     #[serde(default)]
     pub offset: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_recording_to_track() {
+        let recording: Recording = serde_json::from_str(
+            r#"{
+                "id": "b1a9c0e9-d987-4042-ae91-78d6a3267d69",
+                "title": "Bohemian Rhapsody",
+                "artist-credit": [{"artist": {"id": "queen", "name": "Queen"}}],
+                "isrcs": ["GBUM71029604"],
+                "releases": [{"id": "release", "title": "A Night at the Opera", "date": "1975-11-21"}]
+            }"#,
+        )
+        .unwrap();
+
+        let mut track = apollo_core::Track::new(
+            std::path::PathBuf::from("/music/01.mp3"),
+            "Track 01".to_string(),
+            "Unknown Artist".to_string(),
+            std::time::Duration::from_secs(354),
+        );
+        recording.apply_to_track(&mut track);
+
+        assert_eq!(track.title, "Bohemian Rhapsody");
+        assert_eq!(track.artist, "Queen");
+        assert_eq!(
+            track.musicbrainz_id.as_deref(),
+            Some("b1a9c0e9-d987-4042-ae91-78d6a3267d69")
+        );
+        assert_eq!(track.isrc.as_deref(), Some("GBUM71029604"));
+        assert_eq!(track.album_title.as_deref(), Some("A Night at the Opera"));
+        assert_eq!(track.year, Some(1975));
+        assert_eq!(track.album_artist, None);
+    }
+
+    #[test]
+    fn test_apply_release_to_album() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": "6defd963-fe91-4550-b18e-82c685603c2b",
+                "title": "A Night at the Opera",
+                "date": "1975-11-21",
+                "country": "GB",
+                "disambiguation": "30th anniversary",
+                "barcode": "077774600125",
+                "release-group": {
+                    "id": "rg",
+                    "primary-type": "Album",
+                    "secondary-types": []
+                },
+                "label-info": [
+                    {
+                        "catalog-number": "EMTC 103",
+                        "label": {"id": "label", "name": "EMI"}
+                    }
+                ],
+                "media": [{"position": 1, "format": "12\" Vinyl"}]
+            }"#,
+        )
+        .unwrap();
+
+        let mut album = Album::new("A Night at the Opera".to_string(), "Queen".to_string());
+        release.apply_to_album(&mut album);
+
+        assert_eq!(album.year, Some(1975));
+        assert_eq!(album.album_type, Some(AlbumType::Album));
+        assert_eq!(album.release_date, NaiveDate::from_ymd_opt(1975, 11, 21));
+        assert_eq!(album.country.as_deref(), Some("GB"));
+        assert_eq!(album.label.as_deref(), Some("EMI"));
+        assert_eq!(album.catalog_number.as_deref(), Some("EMTC 103"));
+        assert_eq!(album.barcode.as_deref(), Some("077774600125"));
+        assert_eq!(album.disambiguation.as_deref(), Some("30th anniversary"));
+        assert_eq!(album.media, Some(Media::Vinyl));
+    }
+
+    #[test]
+    fn test_album_type_prefers_secondary() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": "r",
+                "title": "Live Killers",
+                "date": "1979",
+                "release-group": {
+                    "id": "rg",
+                    "primary-type": "Album",
+                    "secondary-types": ["Live"]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(release.album_type(), Some(AlbumType::Live));
+        assert_eq!(release.release_date(), None);
+        assert_eq!(release.year(), Some(1979));
+    }
+}
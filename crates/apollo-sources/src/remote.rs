@@ -0,0 +1,460 @@
+//! Client for the REST API of another Apollo library.
+//!
+//! Libraries on a laptop and a home server can be kept in step by copying
+//! the tracks one is missing to the other: [`missing_tracks`] finds them,
+//! [`match_albums`] finds the albums they belong to on the other side, and
+//! [`RemoteLibrary`] reads and adds tracks, albums, and their files over the
+//! API of `apollo web`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use apollo_sources::remote::{RemoteLibrary, missing_tracks};
+//!
+//! # async fn example(local: Vec<apollo_core::Track>) -> Result<(), Box<dyn std::error::Error>> {
+//! let remote = RemoteLibrary::new("http://homeserver:8337")?;
+//! let tracks = remote.list_tracks().await?;
+//! for track in missing_tracks(&tracks, &local) {
+//!     println!("Not here yet: {} - {}", track.artist, track.title);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{SourceError, SourceResult};
+use apollo_core::{Album, AlbumId, Track, TrackId};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{Body, Client, Response};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+use tracing::debug;
+
+/// User agent sent to the other library.
+const USER_AGENT: &str = concat!("Apollo/", env!("CARGO_PKG_VERSION"));
+
+/// Most items the API returns in one page.
+const PAGE_SIZE: u32 = 500;
+
+/// One page of a list.
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    total: u64,
+}
+
+/// Error body of the API.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+/// Client for the library of another Apollo instance.
+pub struct RemoteLibrary {
+    /// HTTP client.
+    client: Client,
+    base_url: String,
+}
+
+impl RemoteLibrary {
+    /// Create a client for the library served at `url`, such as
+    /// `http://homeserver:8337`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is not an HTTP URL, or the HTTP client
+    /// cannot be created.
+    pub fn new(url: &str) -> SourceResult<Self> {
+        let parsed = url::Url::parse(url)
+            .map_err(|e| SourceError::InvalidInput(format!("invalid URL {url}: {e}")))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(SourceError::InvalidInput(format!("not an HTTP URL: {url}")));
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        headers.insert(header::USER_AGENT, HeaderValue::from_static(USER_AGENT));
+
+        // Files can take long to copy, so only connecting is timed
+        let client = Client::builder()
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// List all tracks of the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails or the response cannot be parsed.
+    pub async fn list_tracks(&self) -> SourceResult<Vec<Track>> {
+        self.list("/api/tracks").await
+    }
+
+    /// List all albums of the library.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a request fails or the response cannot be parsed.
+    pub async fn list_albums(&self) -> SourceResult<Vec<Album>> {
+        self.list("/api/albums").await
+    }
+
+    /// Add an album to the library, keeping its ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or is rejected, such as when
+    /// the library has the album already.
+    pub async fn add_album(&self, album: &Album) -> SourceResult<()> {
+        let url = format!("{}/api/albums", self.base_url);
+        debug!("POST {url}");
+        let response = self.client.post(&url).json(album).send().await?;
+        check(response).await?;
+        Ok(())
+    }
+
+    /// Add a track to the library, keeping its ID.
+    ///
+    /// The track's album must be in the library already.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or is rejected, such as when
+    /// the library has the track already.
+    pub async fn add_track(&self, track: &Track) -> SourceResult<()> {
+        let url = format!("{}/api/tracks", self.base_url);
+        debug!("POST {url}");
+        let response = self.client.post(&url).json(track).send().await?;
+        check(response).await?;
+        Ok(())
+    }
+
+    /// Download the audio file of a track to `dest`.
+    ///
+    /// The file is only put in place once it has been downloaded completely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, such as when the library has
+    /// no file for the track, or the file cannot be written.
+    pub async fn download_file(&self, id: &TrackId, dest: &Path) -> SourceResult<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let url = format!("{}/api/tracks/{id}/stream", self.base_url);
+        debug!("GET {url}");
+        let mut response = check(self.client.get(&url).send().await?).await?;
+
+        let write_error = |e: std::io::Error| {
+            SourceError::InvalidInput(format!("Failed to write {}: {e}", dest.display()))
+        };
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(write_error)?;
+        }
+        let partial = dest.with_extension("part");
+        let mut file = tokio::fs::File::create(&partial)
+            .await
+            .map_err(write_error)?;
+        let written = async {
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await.map_err(write_error)?;
+            }
+            file.flush().await.map_err(write_error)
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&partial, dest).await.map_err(write_error)
+    }
+
+    /// Upload the audio file of a track that the library has no file for.
+    ///
+    /// The library places the file in its music directory, and returns the
+    /// track with its new path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or the request fails or
+    /// is rejected, such as when the library has no music directory set.
+    pub async fn upload_file(&self, id: &TrackId, path: &Path) -> SourceResult<Track> {
+        let file = tokio::fs::File::open(path).await.map_err(|e| {
+            SourceError::InvalidInput(format!("Failed to read {}: {e}", path.display()))
+        })?;
+
+        let url = format!("{}/api/tracks/{id}/file", self.base_url);
+        debug!("PUT {url}");
+        let response = self
+            .client
+            .put(&url)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(file))
+            .send()
+            .await?;
+        parse(check(response).await?).await
+    }
+
+    /// Read every page of a list.
+    async fn list<T: DeserializeOwned>(&self, path: &str) -> SourceResult<Vec<T>> {
+        let mut items = Vec::new();
+        loop {
+            let url = format!(
+                "{}{path}?limit={PAGE_SIZE}&offset={}",
+                self.base_url,
+                items.len()
+            );
+            debug!("GET {url}");
+            let response = check(self.client.get(&url).send().await?).await?;
+            let page: Page<T> = parse(response).await?;
+            let done = page.items.is_empty();
+            items.extend(page.items);
+            if done || items.len() as u64 >= page.total {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+/// Turn an error response into an error, with the message of the library.
+async fn check(response: Response) -> SourceResult<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ErrorResponse>(&text).map_or(text, |e| e.message);
+    Err(SourceError::Api {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+/// Parse a JSON response.
+async fn parse<T: DeserializeOwned>(response: Response) -> SourceResult<T> {
+    let text = response.text().await?;
+    serde_json::from_str(&text).map_err(|e| SourceError::Parse(e.to_string()))
+}
+
+/// Find the tracks of `from` that `to` does not have.
+///
+/// Tracks are the same when they have the same ID, file hash, or
+/// [MusicBrainz](https://musicbrainz.org/) recording ID.
+#[must_use]
+pub fn missing_tracks<'a>(from: &'a [Track], to: &[Track]) -> Vec<&'a Track> {
+    let ids: HashSet<&TrackId> = to.iter().map(|track| &track.id).collect();
+    let hashes: HashSet<&str> = to
+        .iter()
+        .map(|track| track.file_hash.as_str())
+        .filter(|hash| !hash.is_empty())
+        .collect();
+    let recordings: HashSet<&str> = to
+        .iter()
+        .filter_map(|track| track.musicbrainz_id.as_deref())
+        .collect();
+
+    from.iter()
+        .filter(|track| {
+            !ids.contains(&track.id)
+                && !hashes.contains(track.file_hash.as_str())
+                && track
+                    .musicbrainz_id
+                    .as_deref()
+                    .is_none_or(|mbid| !recordings.contains(mbid))
+        })
+        .collect()
+}
+
+/// Find the albums of `from` in `to`, by the ID of each album of `from`.
+///
+/// Albums are the same when they have the same ID or
+/// [MusicBrainz](https://musicbrainz.org/) release ID, or the same title and
+/// artist regardless of case.
+#[must_use]
+pub fn match_albums(from: &[Album], to: &[Album]) -> HashMap<AlbumId, AlbumId> {
+    let key = |album: &Album| (album.title.to_lowercase(), album.artist.to_lowercase());
+    let ids: HashSet<&AlbumId> = to.iter().map(|album| &album.id).collect();
+    let releases: HashMap<&str, &AlbumId> = to
+        .iter()
+        .filter_map(|album| Some((album.musicbrainz_id.as_deref()?, &album.id)))
+        .collect();
+    let names: HashMap<(String, String), &AlbumId> =
+        to.iter().map(|album| (key(album), &album.id)).collect();
+
+    from.iter()
+        .filter_map(|album| {
+            let found = if ids.contains(&album.id) {
+                Some(&album.id)
+            } else {
+                album
+                    .musicbrainz_id
+                    .as_deref()
+                    .and_then(|mbid| releases.get(mbid).copied())
+                    .or_else(|| names.get(&key(album)).copied())
+            };
+            found.map(|id| (album.id.clone(), id.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+    use wiremock::matchers::{body_string, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn track(name: &str) -> Track {
+        let mut track = Track::new(
+            PathBuf::from(format!("/music/{name}.flac")),
+            name.to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        track.file_hash = format!("hash-{name}");
+        track
+    }
+
+    #[test]
+    fn test_missing_tracks() {
+        let same_id = track("same-id");
+        let same_hash = track("same-hash");
+        let mut same_recording = track("same-recording");
+        same_recording.musicbrainz_id = Some("recording".to_string());
+        let new = track("new");
+        let from = vec![
+            same_id.clone(),
+            same_hash.clone(),
+            same_recording,
+            new.clone(),
+        ];
+
+        let mut other_hash = track("other");
+        other_hash.file_hash.clone_from(&same_hash.file_hash);
+        let mut other_recording = track("other");
+        other_recording.musicbrainz_id = Some("recording".to_string());
+        let to = vec![same_id, other_hash, other_recording];
+
+        let missing = missing_tracks(&from, &to);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].id, new.id);
+        assert!(missing_tracks(&to, &to).is_empty());
+    }
+
+    #[test]
+    fn test_match_albums() {
+        let same = Album::new("Same".to_string(), "Artist".to_string());
+        let mut release = Album::new("Release".to_string(), "Artist".to_string());
+        release.musicbrainz_id = Some("release".to_string());
+        let named = Album::new("Named".to_string(), "Artist".to_string());
+        let new = Album::new("New".to_string(), "Artist".to_string());
+
+        let mut other_release = Album::new("Renamed".to_string(), "Artist".to_string());
+        other_release.musicbrainz_id = Some("release".to_string());
+        let other_named = Album::new("NAMED".to_string(), "artist".to_string());
+        let to = vec![same.clone(), other_release.clone(), other_named.clone()];
+
+        let matched = match_albums(&[same.clone(), release.clone(), named.clone(), new], &to);
+        assert_eq!(matched.len(), 3);
+        assert_eq!(matched[&same.id], same.id);
+        assert_eq!(matched[&release.id], other_release.id);
+        assert_eq!(matched[&named.id], other_named.id);
+    }
+
+    #[tokio::test]
+    async fn test_list_tracks() {
+        let server = MockServer::start().await;
+        let tracks: Vec<Track> = (0..3).map(|i| track(&format!("track{i}"))).collect();
+        Mock::given(method("GET"))
+            .and(path("/api/tracks"))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                json!({ "items": tracks[..2], "total": 3, "limit": 2, "offset": 0 }),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/tracks"))
+            .and(query_param("offset", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                json!({ "items": tracks[2..], "total": 3, "limit": 2, "offset": 2 }),
+            ))
+            .mount(&server)
+            .await;
+
+        let remote = RemoteLibrary::new(&server.uri()).unwrap();
+        let listed = remote.list_tracks().await.unwrap();
+        let ids: Vec<&TrackId> = listed.iter().map(|track| &track.id).collect();
+        assert_eq!(
+            ids,
+            tracks.iter().map(|track| &track.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_track_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/tracks"))
+            .respond_with(
+                ResponseTemplate::new(409).set_body_json(
+                    json!({ "error": "conflict", "message": "Track already exists" }),
+                ),
+            )
+            .mount(&server)
+            .await;
+
+        let remote = RemoteLibrary::new(&server.uri()).unwrap();
+        match remote.add_track(&track("existing")).await {
+            Err(SourceError::Api { status, message }) => {
+                assert_eq!(status, 409);
+                assert_eq!(message, "Track already exists");
+            }
+            other => panic!("expected an API error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let server = MockServer::start().await;
+        let uploaded = track("uploaded");
+        Mock::given(method("GET"))
+            .and(path(format!("/api/tracks/{}/stream", uploaded.id)))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"audio".to_vec()))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(format!("/api/tracks/{}/file", uploaded.id)))
+            .and(body_string("audio"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&uploaded))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let remote = RemoteLibrary::new(&server.uri()).unwrap();
+        let dest = dir.path().join("Artist").join("track.flac");
+        remote.download_file(&uploaded.id, &dest).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"audio");
+
+        let track = remote.upload_file(&uploaded.id, &dest).await.unwrap();
+        assert_eq!(track.id, uploaded.id);
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        assert!(RemoteLibrary::new("homeserver:8337").is_err());
+        assert!(RemoteLibrary::new("ftp://homeserver").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! [Last.fm](https://www.last.fm/) scrobbling client.
+
+use super::{Scrobble, USER_AGENT};
+use crate::error::{SourceError, SourceResult};
+use md5::{Digest, Md5};
+use reqwest::Client;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::debug;
+
+/// API base URL.
+const API_BASE: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// Most plays accepted by one `track.scrobble` call.
+pub(super) const BATCH_SIZE: usize = 50;
+
+/// Error code of too many calls in a short time.
+const RATE_LIMIT_EXCEEDED: u32 = 29;
+
+/// Error body of the API.
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: u32,
+    message: String,
+}
+
+/// Client for scrobbling to [Last.fm](https://www.last.fm/).
+pub struct LastFmClient {
+    /// HTTP client.
+    client: Client,
+    api_key: String,
+    api_secret: String,
+    session_key: String,
+    base_url: String,
+}
+
+impl LastFmClient {
+    /// Create a client that scrobbles to the account of `session_key`.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - Your API key (get one at <https://www.last.fm/api/account/create>)
+    /// * `api_secret` - The shared secret of the API key
+    /// * `session_key` - Session key of the account to scrobble to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new(
+        api_key: impl Into<String>,
+        api_secret: impl Into<String>,
+        session_key: impl Into<String>,
+    ) -> SourceResult<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        headers.insert(header::USER_AGENT, HeaderValue::from_static(USER_AGENT));
+
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            api_key: api_key.into(),
+            api_secret: api_secret.into(),
+            session_key: session_key.into(),
+            base_url: API_BASE.to_string(),
+        })
+    }
+
+    /// Use another API endpoint, such as a compatible service or a mock
+    /// server.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Scrobble plays to the account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are more than 50 plays, or the request
+    /// fails or is rejected.
+    pub async fn scrobble(&self, scrobbles: &[Scrobble]) -> SourceResult<()> {
+        if scrobbles.len() > BATCH_SIZE {
+            return Err(SourceError::InvalidInput(format!(
+                "at most {BATCH_SIZE} plays can be scrobbled at once"
+            )));
+        }
+        if scrobbles.is_empty() {
+            return Ok(());
+        }
+
+        let mut params = BTreeMap::new();
+        params.insert("method".to_string(), "track.scrobble".to_string());
+        params.insert("api_key".to_string(), self.api_key.clone());
+        params.insert("sk".to_string(), self.session_key.clone());
+        for (i, scrobble) in scrobbles.iter().enumerate() {
+            let mut param = |name: &str, value: String| {
+                params.insert(format!("{name}[{i}]"), value);
+            };
+            param("artist", scrobble.artist.clone());
+            param("track", scrobble.title.clone());
+            param("timestamp", scrobble.played_at.timestamp().to_string());
+            param("duration", scrobble.duration.as_secs().to_string());
+            if let Some(album) = &scrobble.album {
+                param("album", album.clone());
+            }
+            if let Some(album_artist) = &scrobble.album_artist {
+                param("albumArtist", album_artist.clone());
+            }
+            if let Some(number) = scrobble.track_number {
+                param("trackNumber", number.to_string());
+            }
+            if let Some(mbid) = &scrobble.musicbrainz_id {
+                param("mbid", mbid.clone());
+            }
+        }
+        let signature = self.sign(&params);
+        params.insert("api_sig".to_string(), signature);
+        params.insert("format".to_string(), "json".to_string());
+
+        debug!("Scrobbling {} plays to Last.fm", scrobbles.len());
+        let response = self
+            .client
+            .post(&self.base_url)
+            .form(&params)
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        // Errors come as a JSON body, with or without an error status
+        if let Ok(error) = serde_json::from_str::<ErrorResponse>(&body) {
+            if error.error == RATE_LIMIT_EXCEEDED {
+                return Err(SourceError::RateLimited { retry_after: 60 });
+            }
+            return Err(SourceError::Api {
+                status: status.as_u16(),
+                message: format!("{} (error {})", error.message, error.error),
+            });
+        }
+        if !status.is_success() {
+            return Err(SourceError::Api {
+                status: status.as_u16(),
+                message: body,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sign the parameters of a call: the MD5 hash of every name and value
+    /// in order of their names, followed by the shared secret.
+    fn sign(&self, params: &BTreeMap<String, String>) -> String {
+        let mut hasher = Md5::new();
+        for (name, value) in params {
+            hasher.update(name.as_bytes());
+            hasher.update(value.as_bytes());
+        }
+        hasher.update(self.api_secret.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn scrobble() -> Scrobble {
+        Scrobble {
+            artist: "Nina Simone".to_string(),
+            title: "Sinnerman".to_string(),
+            album: Some("Pastel Blues".to_string()),
+            album_artist: None,
+            track_number: Some(9),
+            duration: Duration::from_secs(622),
+            musicbrainz_id: None,
+            played_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        let client = LastFmClient::new("key", "secret", "session").unwrap();
+        let mut params = BTreeMap::new();
+        params.insert("method".to_string(), "auth.getSession".to_string());
+        params.insert("api_key".to_string(), "key".to_string());
+        // md5("api_keykeymethodauth.getSessionsecret")
+        assert_eq!(client.sign(&params), "22c8184cc52cd5d7a67f5a8b092ff8b6");
+    }
+
+    #[tokio::test]
+    async fn test_scrobble() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("method=track.scrobble"))
+            .and(body_string_contains("artist%5B0%5D=Nina+Simone"))
+            .and(body_string_contains("timestamp%5B0%5D=1700000000"))
+            .and(body_string_contains("trackNumber%5B0%5D=9"))
+            .and(body_string_contains("sk=session"))
+            .and(body_string_contains("api_sig="))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"scrobbles":{"@attr":{"accepted":1,"ignored":0},"scrobble":{}}}"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = LastFmClient::new("key", "secret", "session")
+            .unwrap()
+            .with_base_url(server.uri());
+        client.scrobble(&[scrobble()]).await.unwrap();
+        client.scrobble(&[]).await.unwrap();
+        assert!(client.scrobble(&vec![scrobble(); 51]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scrobble_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(403).set_body_string(
+                r#"{"error":9,"message":"Invalid session key - Please re-authenticate"}"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client = LastFmClient::new("key", "secret", "expired")
+            .unwrap()
+            .with_base_url(server.uri());
+        match client.scrobble(&[scrobble()]).await {
+            Err(SourceError::Api { status, message }) => {
+                assert_eq!(status, 403);
+                assert!(message.contains("Invalid session key"));
+            }
+            other => panic!("expected an API error, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! [ListenBrainz](https://listenbrainz.org/) client for submitting listens.
+
+use super::{Scrobble, USER_AGENT};
+use crate::error::{SourceError, SourceResult};
+use reqwest::Client;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde_json::{Map, Value, json};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// API base URL.
+const API_BASE: &str = "https://api.listenbrainz.org";
+
+/// Most listens submitted at once, well below the limit of the API.
+pub(super) const BATCH_SIZE: usize = 100;
+
+/// Client for submitting listens to [ListenBrainz](https://listenbrainz.org/).
+pub struct ListenBrainzClient {
+    /// HTTP client.
+    client: Client,
+    base_url: String,
+}
+
+impl ListenBrainzClient {
+    /// Create a client that submits listens for the user of `token`.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - User token (find it at <https://listenbrainz.org/settings/>)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token is not a valid header value, or the
+    /// HTTP client cannot be created.
+    pub fn new(token: &str) -> SourceResult<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        headers.insert(header::USER_AGENT, HeaderValue::from_static(USER_AGENT));
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Token {token}"))
+                .map_err(|e| SourceError::InvalidInput(e.to_string()))?,
+        );
+
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: API_BASE.to_string(),
+        })
+    }
+
+    /// Use another API endpoint, such as a self-hosted server or a mock
+    /// server.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Submit plays as listens of the user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are more than 100 plays, or the request
+    /// fails or is rejected.
+    pub async fn submit_listens(&self, scrobbles: &[Scrobble]) -> SourceResult<()> {
+        if scrobbles.len() > BATCH_SIZE {
+            return Err(SourceError::InvalidInput(format!(
+                "at most {BATCH_SIZE} listens can be submitted at once"
+            )));
+        }
+        if scrobbles.is_empty() {
+            return Ok(());
+        }
+
+        // A single listen has a type of its own; several are imported
+        let listen_type = if scrobbles.len() == 1 {
+            "single"
+        } else {
+            "import"
+        };
+        let payload: Vec<Value> = scrobbles.iter().map(listen).collect();
+        let body = json!({ "listen_type": listen_type, "payload": payload });
+
+        let url = format!("{}/1/submit-listens", self.base_url.trim_end_matches('/'));
+        debug!("POST {url} with {} listens", scrobbles.len());
+        let response = self.client.post(&url).json(&body).send().await?;
+        let status = response.status();
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get("x-ratelimit-reset-in")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60);
+
+            warn!("Rate limited, retry after {retry_after} seconds");
+            return Err(SourceError::RateLimited { retry_after });
+        }
+
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            // Errors usually come as `{"code": 400, "error": "..."}`
+            let message = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|body| body.get("error")?.as_str().map(str::to_string))
+                .unwrap_or(text);
+            return Err(SourceError::Api {
+                status: status.as_u16(),
+                message,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Describe a play as a listen.
+fn listen(scrobble: &Scrobble) -> Value {
+    let mut info = Map::new();
+    info.insert("submission_client".to_string(), json!("Apollo"));
+    info.insert(
+        "submission_client_version".to_string(),
+        json!(env!("CARGO_PKG_VERSION")),
+    );
+    info.insert(
+        "duration_ms".to_string(),
+        json!(u64::try_from(scrobble.duration.as_millis()).unwrap_or(u64::MAX)),
+    );
+    if let Some(number) = scrobble.track_number {
+        info.insert("tracknumber".to_string(), json!(number));
+    }
+    if let Some(mbid) = &scrobble.musicbrainz_id {
+        info.insert("recording_mbid".to_string(), json!(mbid));
+    }
+    if let Some(album_artist) = &scrobble.album_artist {
+        info.insert("albumartist".to_string(), json!(album_artist));
+    }
+
+    let mut metadata = Map::new();
+    metadata.insert("artist_name".to_string(), json!(scrobble.artist));
+    metadata.insert("track_name".to_string(), json!(scrobble.title));
+    if let Some(album) = &scrobble.album {
+        metadata.insert("release_name".to_string(), json!(album));
+    }
+    metadata.insert("additional_info".to_string(), Value::Object(info));
+
+    json!({
+        "listened_at": scrobble.played_at.timestamp(),
+        "track_metadata": metadata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn scrobble(title: &str) -> Scrobble {
+        Scrobble {
+            artist: "Nina Simone".to_string(),
+            title: title.to_string(),
+            album: Some("Pastel Blues".to_string()),
+            album_artist: None,
+            track_number: None,
+            duration: Duration::from_secs(622),
+            musicbrainz_id: Some("2d6ff3e0-6fa1-4bb2-9ce4-1a3cc3ad9a6b".to_string()),
+            played_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_listens() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/1/submit-listens"))
+            .and(header("authorization", "Token secret"))
+            .and(body_partial_json(json!({
+                "listen_type": "single",
+                "payload": [{
+                    "listened_at": 1_700_000_000,
+                    "track_metadata": {
+                        "artist_name": "Nina Simone",
+                        "track_name": "Sinnerman",
+                        "release_name": "Pastel Blues",
+                        "additional_info": {
+                            "duration_ms": 622_000,
+                            "recording_mbid": "2d6ff3e0-6fa1-4bb2-9ce4-1a3cc3ad9a6b"
+                        }
+                    }
+                }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"status":"ok"}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "listen_type": "import" })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"status":"ok"}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = ListenBrainzClient::new("secret")
+            .unwrap()
+            .with_base_url(server.uri());
+        client
+            .submit_listens(&[scrobble("Sinnerman")])
+            .await
+            .unwrap();
+        client
+            .submit_listens(&[scrobble("Sinnerman"), scrobble("Be My Husband")])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_submit_listens_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .set_body_string(r#"{"code":401,"error":"Invalid authorization token."}"#),
+            )
+            .mount(&server)
+            .await;
+
+        let client = ListenBrainzClient::new("wrong")
+            .unwrap()
+            .with_base_url(server.uri());
+        match client.submit_listens(&[scrobble("Sinnerman")]).await {
+            Err(SourceError::Api { status, message }) => {
+                assert_eq!(status, 401);
+                assert_eq!(message, "Invalid authorization token.");
+            }
+            other => panic!("expected an API error, got {other:?}"),
+        }
+    }
+}
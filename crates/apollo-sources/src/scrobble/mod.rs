@@ -0,0 +1,155 @@
+//! Scrobbling plays to [Last.fm](https://www.last.fm/) and
+//! [ListenBrainz](https://listenbrainz.org/).
+//!
+//! Both services keep a history of what their users listen to. A
+//! [`ScrobbleClient`] submits [`Scrobble`]s to one of them, in batches of
+//! at most [`ScrobbleClient::batch_size`] plays.
+//!
+//! # Authentication
+//!
+//! [Last.fm](https://www.last.fm/) needs an API key with its shared secret,
+//! and the session key of the account to scrobble to.
+//! [ListenBrainz](https://listenbrainz.org/) needs the user token found at
+//! <https://listenbrainz.org/settings/>.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use apollo_sources::scrobble::{ListenBrainzClient, Scrobble, ScrobbleClient};
+//! # use apollo_core::metadata::Track;
+//!
+//! # async fn example(track: &Track) -> Result<(), Box<dyn std::error::Error>> {
+//! let client = ScrobbleClient::ListenBrainz(ListenBrainzClient::new("your-token")?);
+//!
+//! client.submit(&[Scrobble::new(track, chrono::Utc::now())]).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod lastfm;
+mod listenbrainz;
+
+pub use lastfm::LastFmClient;
+pub use listenbrainz::ListenBrainzClient;
+
+use crate::error::SourceResult;
+use apollo_core::metadata::Track;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// User agent sent to the scrobble services.
+const USER_AGENT: &str = "Apollo/0.1 (https://github.com/yourusername/apollo)";
+
+/// A play of a track, as sent to a scrobble service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scrobble {
+    /// Track artist.
+    pub artist: String,
+    /// Track title.
+    pub title: String,
+    /// Album title.
+    pub album: Option<String>,
+    /// Album artist, if it differs from the track artist.
+    pub album_artist: Option<String>,
+    /// Track number on the album.
+    pub track_number: Option<u32>,
+    /// Length of the track.
+    pub duration: Duration,
+    /// [MusicBrainz](https://musicbrainz.org/) recording ID.
+    pub musicbrainz_id: Option<String>,
+    /// When the track started playing.
+    pub played_at: DateTime<Utc>,
+}
+
+impl Scrobble {
+    /// Describe a play of a track.
+    #[must_use]
+    pub fn new(track: &Track, played_at: DateTime<Utc>) -> Self {
+        Self {
+            artist: track.artist.clone(),
+            title: track.title.clone(),
+            album: track.album_title.clone(),
+            album_artist: track
+                .album_artist
+                .clone()
+                .filter(|artist| *artist != track.artist),
+            track_number: track.track_number,
+            duration: track.duration,
+            musicbrainz_id: track.musicbrainz_id.clone(),
+            played_at,
+        }
+    }
+}
+
+/// A client for one of the scrobble services.
+pub enum ScrobbleClient {
+    /// [Last.fm](https://www.last.fm/).
+    LastFm(LastFmClient),
+    /// [ListenBrainz](https://listenbrainz.org/).
+    ListenBrainz(ListenBrainzClient),
+}
+
+impl ScrobbleClient {
+    /// Get the name of the service, such as `lastfm`.
+    #[must_use]
+    pub const fn service(&self) -> &'static str {
+        match self {
+            Self::LastFm(_) => "lastfm",
+            Self::ListenBrainz(_) => "listenbrainz",
+        }
+    }
+
+    /// Get the largest number of plays submitted at once.
+    #[must_use]
+    pub const fn batch_size(&self) -> usize {
+        match self {
+            Self::LastFm(_) => lastfm::BATCH_SIZE,
+            Self::ListenBrainz(_) => listenbrainz::BATCH_SIZE,
+        }
+    }
+
+    /// Submit plays to the service.
+    ///
+    /// Plays that the service ignores, such as ones it deems too old, count
+    /// as submitted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are more plays than the batch size, or the
+    /// request fails or is rejected.
+    pub async fn submit(&self, scrobbles: &[Scrobble]) -> SourceResult<()> {
+        match self {
+            Self::LastFm(client) => client.scrobble(scrobbles).await,
+            Self::ListenBrainz(client) => client.submit_listens(scrobbles).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_scrobble_from_track() {
+        let mut track = Track::new(
+            PathBuf::from("/music/one.flac"),
+            "One".to_string(),
+            "Metallica".to_string(),
+            Duration::from_secs(446),
+        );
+        track.album_artist = Some("Metallica".to_string());
+        track.album_title = Some("...And Justice for All".to_string());
+        let played_at = Utc::now();
+
+        let scrobble = Scrobble::new(&track, played_at);
+        assert_eq!(scrobble.artist, "Metallica");
+        assert_eq!(scrobble.album.as_deref(), Some("...And Justice for All"));
+        assert_eq!(scrobble.album_artist, None);
+        assert_eq!(scrobble.played_at, played_at);
+
+        track.album_artist = Some("Various Artists".to_string());
+        let scrobble = Scrobble::new(&track, played_at);
+        assert_eq!(scrobble.album_artist.as_deref(), Some("Various Artists"));
+    }
+}
@@ -0,0 +1,241 @@
+//! [Deezer](https://deezer.com/) client for reading public playlists.
+
+use super::{StreamingPlaylist, StreamingTrack, USER_AGENT};
+use crate::error::{SourceError, SourceResult};
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+use tracing::debug;
+
+/// API base URL.
+const API_BASE: &str = "https://api.deezer.com";
+
+/// Songs requested per page of a playlist.
+const PAGE_SIZE: usize = 100;
+
+/// Client for reading [Deezer](https://deezer.com/) playlists.
+pub struct DeezerClient {
+    /// HTTP client.
+    client: Client,
+    base_url: String,
+}
+
+impl DeezerClient {
+    /// Create a client.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new() -> SourceResult<Self> {
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            base_url: API_BASE.to_string(),
+        })
+    }
+
+    /// Use another API endpoint, such as a mock server.
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Read a public playlist with all its songs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist does not exist or is private, or the
+    /// request fails.
+    pub async fn get_playlist(&self, id: &str) -> SourceResult<StreamingPlaylist> {
+        let playlist: PlaylistResponse = self.get(&format!("/playlist/{id}")).await?;
+
+        let mut tracks = Vec::new();
+        loop {
+            let page: TracksPage = self
+                .get(&format!(
+                    "/playlist/{id}/tracks?index={}&limit={PAGE_SIZE}",
+                    tracks.len()
+                ))
+                .await?;
+            let count = page.data.len();
+            tracks.extend(page.data.into_iter().map(StreamingTrack::from));
+            if page.next.is_none() || count == 0 {
+                break;
+            }
+        }
+
+        Ok(StreamingPlaylist {
+            name: playlist.title,
+            tracks,
+        })
+    }
+
+    /// Make a GET request to the API.
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> SourceResult<T> {
+        let url = format!("{}{path}", self.base_url.trim_end_matches('/'));
+        debug!("GET {url}");
+
+        let response = self.client.get(&url).send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(SourceError::Api {
+                status: status.as_u16(),
+                message: body,
+            });
+        }
+
+        // Errors come with a success status, as `{"error": {...}}`
+        let value: serde_json::Value =
+            serde_json::from_str(&body).map_err(|e| SourceError::Parse(e.to_string()))?;
+        if let Some(error) = value.get("error") {
+            let error: ApiError = serde_json::from_value(error.clone())
+                .map_err(|e| SourceError::Parse(e.to_string()))?;
+            return Err(match error.code {
+                // Too many requests within 5 seconds
+                4 => SourceError::RateLimited { retry_after: 5 },
+                800 => SourceError::NotFound,
+                code => SourceError::Api {
+                    status: code,
+                    message: error.message,
+                },
+            });
+        }
+        serde_json::from_value(value).map_err(|e| SourceError::Parse(e.to_string()))
+    }
+}
+
+/// An error reported by the API.
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    code: u16,
+}
+
+/// A playlist, of which only the title is used.
+#[derive(Debug, Deserialize)]
+struct PlaylistResponse {
+    title: String,
+}
+
+/// A page of the songs of a playlist.
+#[derive(Debug, Deserialize)]
+struct TracksPage {
+    data: Vec<DeezerTrack>,
+    next: Option<String>,
+}
+
+/// A song on a playlist.
+#[derive(Debug, Deserialize)]
+struct DeezerTrack {
+    title: String,
+    /// Length in seconds.
+    duration: Option<u64>,
+    isrc: Option<String>,
+    artist: DeezerName,
+    album: Option<DeezerTitle>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeezerName {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeezerTitle {
+    title: String,
+}
+
+impl From<DeezerTrack> for StreamingTrack {
+    fn from(track: DeezerTrack) -> Self {
+        Self {
+            title: track.title,
+            artists: vec![track.artist.name],
+            album: track.album.map(|album| album.title),
+            duration: track.duration.map(Duration::from_secs),
+            isrc: track.isrc.filter(|isrc| !isrc.is_empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn track(title: &str) -> serde_json::Value {
+        json!({
+            "title": title,
+            "duration": 245,
+            "artist": { "name": "Daft Punk" },
+            "album": { "title": "Discovery" }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_get_playlist() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/playlist/42"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "id": 42, "title": "Robots" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/playlist/42/tracks"))
+            .and(query_param("index", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [track("One More Time")],
+                "total": 2,
+                "next": format!("{}/playlist/42/tracks?index=1", server.uri())
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/playlist/42/tracks"))
+            .and(query_param("index", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [track("Digital Love")],
+                "total": 2
+            })))
+            .mount(&server)
+            .await;
+
+        let client = DeezerClient::new().unwrap().with_base_url(server.uri());
+        let playlist = client.get_playlist("42").await.unwrap();
+        assert_eq!(playlist.name, "Robots");
+        assert_eq!(playlist.tracks.len(), 2);
+        assert_eq!(playlist.tracks[1].title, "Digital Love");
+        assert_eq!(playlist.tracks[1].artists, vec!["Daft Punk".to_string()]);
+        assert_eq!(playlist.tracks[1].album.as_deref(), Some("Discovery"));
+        assert_eq!(playlist.tracks[1].duration, Some(Duration::from_secs(245)));
+    }
+
+    #[tokio::test]
+    async fn test_missing_playlist() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "error": { "type": "DataException", "message": "no data", "code": 800 }
+            })))
+            .mount(&server)
+            .await;
+
+        let client = DeezerClient::new().unwrap().with_base_url(server.uri());
+        assert!(matches!(
+            client.get_playlist("1").await,
+            Err(SourceError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,201 @@
+//! Reading playlists exported to CSV.
+//!
+//! Exports made with [Exportify](https://exportify.net/) or `TuneMyMusic` are
+//! read by their column names, so the order of the columns does not matter
+//! and unknown columns are ignored.
+
+use super::{StreamingPlaylist, StreamingTrack};
+use crate::error::{SourceError, SourceResult};
+use std::time::Duration;
+
+/// Column names for the song title.
+const TITLE_COLUMNS: &[&str] = &["track name", "title", "track", "name", "song"];
+
+/// Column names for the artists.
+const ARTIST_COLUMNS: &[&str] = &["artist name(s)", "artist name", "artists", "artist"];
+
+/// Column names for the album title.
+const ALBUM_COLUMNS: &[&str] = &["album name", "album"];
+
+/// Column names for the length in milliseconds.
+const DURATION_MS_COLUMNS: &[&str] = &["duration (ms)", "duration_ms", "track duration (ms)"];
+
+/// Column names for the length in seconds or as `m:ss`.
+const DURATION_COLUMNS: &[&str] = &["duration", "length"];
+
+/// Column names for the ISRC.
+const ISRC_COLUMNS: &[&str] = &["isrc"];
+
+/// Read a playlist from the contents of a CSV export.
+///
+/// The first row must name the columns; a title and an artist column are
+/// required. Several artists in one field are separated by `,` or `;`.
+///
+/// # Errors
+///
+/// Returns an error if the title or artist column is missing.
+pub fn read_csv_export(name: &str, contents: &str) -> SourceResult<StreamingPlaylist> {
+    let mut rows = parse_csv(contents.trim_start_matches('\u{feff}')).into_iter();
+    let header: Vec<String> = rows
+        .next()
+        .unwrap_or_default()
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+    let column = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| header.iter().position(|column| column == name))
+    };
+
+    let title_column = column(TITLE_COLUMNS)
+        .ok_or_else(|| SourceError::Parse("CSV export has no title column".to_string()))?;
+    let artist_column = column(ARTIST_COLUMNS)
+        .ok_or_else(|| SourceError::Parse("CSV export has no artist column".to_string()))?;
+    let album_column = column(ALBUM_COLUMNS);
+    let duration_ms_column = column(DURATION_MS_COLUMNS);
+    let duration_column = column(DURATION_COLUMNS);
+    let isrc_column = column(ISRC_COLUMNS);
+
+    let tracks = rows
+        .filter_map(|row| {
+            let field = |index: Option<usize>| {
+                index
+                    .and_then(|i| row.get(i))
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+            };
+
+            let title = field(Some(title_column))?.to_string();
+            let artists = field(Some(artist_column))
+                .map(split_artists)
+                .unwrap_or_default();
+            let duration = field(duration_ms_column)
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis)
+                .or_else(|| field(duration_column).and_then(parse_duration));
+
+            Some(StreamingTrack {
+                title,
+                artists,
+                album: field(album_column).map(str::to_string),
+                duration,
+                isrc: field(isrc_column).map(str::to_uppercase),
+            })
+        })
+        .collect();
+
+    Ok(StreamingPlaylist {
+        name: name.to_string(),
+        tracks,
+    })
+}
+
+/// Split a field of artists into names.
+fn split_artists(value: &str) -> Vec<String> {
+    value
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|artist| !artist.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse a length in seconds, or as `m:ss`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    match value.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes: u64 = minutes.parse().ok()?;
+            let seconds: u64 = seconds.parse().ok()?;
+            Some(Duration::from_secs(minutes * 60 + seconds))
+        }
+        None => value.parse().ok().map(Duration::from_secs),
+    }
+}
+
+/// Split CSV text into rows of fields.
+///
+/// Fields may be quoted with `"`, with `""` for a quote inside and line
+/// breaks kept. Empty lines are skipped.
+fn parse_csv(contents: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|f| !f.is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => field.push(c),
+        }
+    }
+
+    row.push(field);
+    if row.iter().any(|f| !f.is_empty()) {
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_quoting() {
+        let rows = parse_csv("a,\"b, c\",\"say \"\"hi\"\"\"\r\n\n1,\"two\nlines\",3");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["a", "b, c", "say \"hi\""],
+                vec!["1", "two\nlines", "3"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_exportify() {
+        let csv = "\u{feff}Track URI,Track Name,Artist Name(s),Album Name,Duration (ms),ISRC\n\
+                   spotify:track:1,Under Pressure,\"Queen,David Bowie\",Hot Space,248000,gbum71029604\n\
+                   spotify:track:2,,Nobody,,0,\n";
+        let playlist = read_csv_export("Mix", csv).unwrap();
+
+        assert_eq!(playlist.name, "Mix");
+        assert_eq!(playlist.tracks.len(), 1);
+        let track = &playlist.tracks[0];
+        assert_eq!(track.title, "Under Pressure");
+        assert_eq!(track.artists, vec!["Queen", "David Bowie"]);
+        assert_eq!(track.album.as_deref(), Some("Hot Space"));
+        assert_eq!(track.duration, Some(Duration::from_secs(248)));
+        assert_eq!(track.isrc.as_deref(), Some("GBUM71029604"));
+    }
+
+    #[test]
+    fn test_read_minimal_export() {
+        let csv = "Artist;Title\n";
+        assert!(read_csv_export("Mix", csv).is_err());
+
+        let csv = "Title,Artist,Duration\nYellow,Coldplay,4:26\n";
+        let playlist = read_csv_export("Mix", csv).unwrap();
+        assert_eq!(playlist.tracks[0].duration, Some(Duration::from_secs(266)));
+        assert_eq!(playlist.tracks[0].isrc, None);
+    }
+}
@@ -0,0 +1,300 @@
+//! Matching songs on streaming playlists to local tracks.
+//!
+//! Songs are matched in three passes, stopping at the first that finds a
+//! track:
+//!
+//! 1. By ISRC, when both the song and a track have one.
+//! 2. By artist and title, ignoring case, punctuation, featured artists, and
+//!    version suffixes such as `(Remastered 2011)`, with lengths at most
+//!    [`METADATA_TOLERANCE`] apart.
+//! 3. By similar artist and title, with lengths at most
+//!    [`FUZZY_TOLERANCE`] apart.
+//!
+//! When several tracks match, the one closest in length wins, preferring
+//! tracks from the same album.
+
+use apollo_core::metadata::Track;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::StreamingTrack;
+
+/// Largest difference in length for tracks matched by artist and title.
+pub const METADATA_TOLERANCE: Duration = Duration::from_secs(5);
+
+/// Largest difference in length for tracks matched by similar names.
+pub const FUZZY_TOLERANCE: Duration = Duration::from_secs(3);
+
+/// Minimum [Jaro-Winkler](https://en.wikipedia.org/wiki/Jaro%E2%80%93Winkler_distance)
+/// similarity of both artist and title for a fuzzy match.
+const FUZZY_MIN_SIMILARITY: f64 = 0.92;
+
+/// How a song was matched to a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMethod {
+    /// Same ISRC.
+    Isrc,
+    /// Same artist and title.
+    Metadata,
+    /// Similar artist and title.
+    Fuzzy,
+}
+
+/// A local track found for a song.
+#[derive(Debug, Clone, Copy)]
+pub struct PlaylistMatch<'a> {
+    /// The matched track.
+    pub track: &'a Track,
+    /// How it was matched.
+    pub method: MatchMethod,
+}
+
+/// Finds local tracks for songs on streaming playlists.
+pub struct PlaylistMatcher<'a> {
+    tracks: &'a [Track],
+    /// Tracks by upper case ISRC.
+    by_isrc: HashMap<String, Vec<&'a Track>>,
+    /// Tracks by normalized main artist and title.
+    by_name: HashMap<(String, String), Vec<&'a Track>>,
+}
+
+impl<'a> PlaylistMatcher<'a> {
+    /// Index the tracks of a library for matching.
+    #[must_use]
+    pub fn new(tracks: &'a [Track]) -> Self {
+        let mut by_isrc: HashMap<String, Vec<&Track>> = HashMap::new();
+        let mut by_name: HashMap<(String, String), Vec<&Track>> = HashMap::new();
+        for track in tracks {
+            if let Some(isrc) = track.isrc.as_deref().filter(|isrc| !isrc.is_empty()) {
+                by_isrc.entry(isrc.to_uppercase()).or_default().push(track);
+            }
+            by_name
+                .entry((artist_key(&track.artist), title_key(&track.title)))
+                .or_default()
+                .push(track);
+        }
+
+        Self {
+            tracks,
+            by_isrc,
+            by_name,
+        }
+    }
+
+    /// Find the local track for a song, if the library has it.
+    #[must_use]
+    pub fn find(&self, song: &StreamingTrack) -> Option<PlaylistMatch<'a>> {
+        if let Some(track) = song
+            .isrc
+            .as_ref()
+            .and_then(|isrc| self.by_isrc.get(&isrc.to_uppercase()))
+            .and_then(|tracks| best(song, tracks.iter().copied(), None))
+        {
+            return Some(PlaylistMatch {
+                track,
+                method: MatchMethod::Isrc,
+            });
+        }
+
+        let artist = song.artists.first().map_or("", String::as_str);
+        let (artist, title) = (artist_key(artist), title_key(&song.title));
+        if let Some(track) = self
+            .by_name
+            .get(&(artist.clone(), title.clone()))
+            .and_then(|tracks| best(song, tracks.iter().copied(), Some(METADATA_TOLERANCE)))
+        {
+            return Some(PlaylistMatch {
+                track,
+                method: MatchMethod::Metadata,
+            });
+        }
+
+        let similar = self.tracks.iter().filter(|track| {
+            strsim::jaro_winkler(&title, &title_key(&track.title)) >= FUZZY_MIN_SIMILARITY
+                && strsim::jaro_winkler(&artist, &artist_key(&track.artist)) >= FUZZY_MIN_SIMILARITY
+        });
+        best(song, similar, Some(FUZZY_TOLERANCE)).map(|track| PlaylistMatch {
+            track,
+            method: MatchMethod::Fuzzy,
+        })
+    }
+}
+
+/// Pick the track closest in length to a song, preferring the song's album.
+///
+/// With a tolerance, tracks further apart in length are left out. Lengths
+/// that are unknown always fit.
+fn best<'a>(
+    song: &StreamingTrack,
+    tracks: impl Iterator<Item = &'a Track>,
+    tolerance: Option<Duration>,
+) -> Option<&'a Track> {
+    let album = song.album.as_deref().map(title_key);
+    tracks
+        .filter_map(|track| {
+            let difference = song
+                .duration
+                .filter(|_| !track.duration.is_zero())
+                .map_or(Duration::ZERO, |duration| duration.abs_diff(track.duration));
+            if tolerance.is_some_and(|tolerance| difference > tolerance) {
+                return None;
+            }
+            let other_album =
+                album.is_some() && track.album_title.as_deref().map(title_key) != album;
+            Some(((other_album, difference), track))
+        })
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, track)| track)
+}
+
+/// Normalize the main artist of an artist credit, leaving out anyone
+/// featured or credited after them.
+fn artist_key(artist: &str) -> String {
+    let lower = artist.to_lowercase();
+    let main = [
+        " feat. ",
+        " feat ",
+        " ft. ",
+        " ft ",
+        " featuring ",
+        " with ",
+        " & ",
+        " x ",
+        ", ",
+        "; ",
+    ]
+    .iter()
+    .filter_map(|separator| lower.find(separator))
+    .min()
+    .map_or(lower.as_str(), |end| &lower[..end]);
+    normalize(main)
+}
+
+/// Normalize a title, leaving out parenthesized and bracketed parts and
+/// version suffixes such as ` - Remastered 2011`.
+fn title_key(title: &str) -> String {
+    let mut stripped = String::with_capacity(title.len());
+    let mut depth = 0usize;
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            c if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    let stripped = stripped.split(" - ").next().unwrap_or_default();
+    let key = normalize(stripped);
+    // A title that is only a parenthesized part, such as "(Untitled)"
+    if key.is_empty() {
+        normalize(title)
+    } else {
+        key
+    }
+}
+
+/// Lower case words without punctuation, separated by single spaces.
+fn normalize(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn track(artist: &str, title: &str, album: &str, secs: u64) -> Track {
+        let mut track = Track::new(
+            PathBuf::from(format!("/music/{artist}/{album}/{title}.flac")),
+            title.to_string(),
+            artist.to_string(),
+            Duration::from_secs(secs),
+        );
+        track.album_title = Some(album.to_string());
+        track
+    }
+
+    fn song(artists: &[&str], title: &str, secs: u64) -> StreamingTrack {
+        StreamingTrack {
+            title: title.to_string(),
+            artists: artists.iter().map(|a| (*a).to_string()).collect(),
+            album: None,
+            duration: Some(Duration::from_secs(secs)),
+            isrc: None,
+        }
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(artist_key("Queen & David Bowie"), "queen");
+        assert_eq!(artist_key("Mark Ronson feat. Bruno Mars"), "mark ronson");
+        assert_eq!(artist_key("AC/DC"), "ac dc");
+        assert_eq!(title_key("Let It Be - Remastered 2009"), "let it be");
+        assert_eq!(title_key("Uptown Funk (feat. Bruno Mars)"), "uptown funk");
+        assert_eq!(title_key("(Untitled)"), "untitled");
+    }
+
+    #[test]
+    fn test_match_by_isrc() {
+        let mut pressure = track("Queen", "Under Pressure (2011 Remaster)", "Hot Space", 248);
+        pressure.isrc = Some("gbum71029604".to_string());
+        let tracks = vec![pressure];
+        let matcher = PlaylistMatcher::new(&tracks);
+
+        let mut wanted = song(&["Someone Else"], "Renamed", 100);
+        wanted.isrc = Some("GBUM71029604".to_string());
+        let found = matcher.find(&wanted).unwrap();
+        assert_eq!(found.method, MatchMethod::Isrc);
+    }
+
+    #[test]
+    fn test_match_by_metadata() {
+        let tracks = vec![
+            track("Queen", "Under Pressure", "Greatest Hits II", 244),
+            track("Queen", "Under Pressure", "Hot Space", 248),
+            track("Queen", "Under Pressure (Live)", "Live at Wembley", 300),
+        ];
+        let matcher = PlaylistMatcher::new(&tracks);
+
+        let found = matcher
+            .find(&song(
+                &["Queen", "David Bowie"],
+                "Under Pressure - Remastered 2011",
+                247,
+            ))
+            .unwrap();
+        assert_eq!(found.method, MatchMethod::Metadata);
+        assert_eq!(found.track.album_title.as_deref(), Some("Hot Space"));
+
+        // Same album wins over closer length
+        let mut wanted = song(&["Queen"], "Under Pressure", 247);
+        wanted.album = Some("Greatest Hits II".to_string());
+        let found = matcher.find(&wanted).unwrap();
+        assert_eq!(found.track.album_title.as_deref(), Some("Greatest Hits II"));
+
+        // Too far apart in length
+        assert!(
+            matcher
+                .find(&song(&["Queen"], "Under Pressure", 400))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_match_fuzzy() {
+        let tracks = vec![track("Beyoncé", "Halo", "I Am... Sasha Fierce", 261)];
+        let matcher = PlaylistMatcher::new(&tracks);
+
+        let found = matcher.find(&song(&["Beyonce"], "Halo", 262)).unwrap();
+        assert_eq!(found.method, MatchMethod::Fuzzy);
+
+        assert!(matcher.find(&song(&["Beyonce"], "Halo", 270)).is_none());
+        assert!(matcher.find(&song(&["Coldplay"], "Halo", 261)).is_none());
+    }
+}
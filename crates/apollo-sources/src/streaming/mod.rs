@@ -0,0 +1,177 @@
+//! Importing playlists from [Spotify](https://spotify.com/) and
+//! [Deezer](https://deezer.com/).
+//!
+//! A streaming playlist is read as a [`StreamingPlaylist`], either from the
+//! service by its URL or from a CSV export, such as one made with
+//! [Exportify](https://exportify.net/). A [`PlaylistMatcher`] then finds the
+//! local track for each song: by ISRC where both have one, and by title,
+//! artist, and duration otherwise.
+//!
+//! # Authentication
+//!
+//! [Deezer](https://deezer.com/) playlists that are public can be read
+//! without an account. [Spotify](https://spotify.com/) needs the client ID
+//! and secret of an app registered at
+//! <https://developer.spotify.com/dashboard>.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use apollo_sources::streaming::{DeezerClient, PlaylistMatcher, PlaylistUrl};
+//! # use apollo_core::metadata::Track;
+//!
+//! # async fn example(library: &[Track]) -> Result<(), Box<dyn std::error::Error>> {
+//! let Some(PlaylistUrl::Deezer(id)) = PlaylistUrl::parse("https://www.deezer.com/playlist/908622995")
+//! else {
+//!     return Ok(());
+//! };
+//! let playlist = DeezerClient::new()?.get_playlist(&id).await?;
+//!
+//! let matcher = PlaylistMatcher::new(library);
+//! for song in &playlist.tracks {
+//!     match matcher.find(song) {
+//!         Some(found) => println!("{} -> {}", song, found.track.path.display()),
+//!         None => println!("{song}: not in the library"),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod deezer;
+mod export;
+mod matcher;
+mod spotify;
+
+pub use deezer::DeezerClient;
+pub use export::read_csv_export;
+pub use matcher::{MatchMethod, PlaylistMatch, PlaylistMatcher};
+pub use spotify::SpotifyClient;
+
+use std::fmt;
+use std::time::Duration;
+
+/// User agent sent to the streaming services.
+const USER_AGENT: &str = "Apollo/0.1 (https://github.com/yourusername/apollo)";
+
+/// A song on a streaming playlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamingTrack {
+    /// Song title.
+    pub title: String,
+    /// Credited artists, main artist first.
+    pub artists: Vec<String>,
+    /// Album title.
+    pub album: Option<String>,
+    /// Length of the song.
+    pub duration: Option<Duration>,
+    /// International Standard Recording Code.
+    pub isrc: Option<String>,
+}
+
+impl StreamingTrack {
+    /// Get the credited artists as one name, such as `A, B`.
+    #[must_use]
+    pub fn artist(&self) -> String {
+        self.artists.join(", ")
+    }
+}
+
+impl fmt::Display for StreamingTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {}", self.artist(), self.title)
+    }
+}
+
+/// A playlist read from a streaming service or an export of one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamingPlaylist {
+    /// Playlist name.
+    pub name: String,
+    /// Songs, in playlist order.
+    pub tracks: Vec<StreamingTrack>,
+}
+
+/// A playlist on a streaming service, by its ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaylistUrl {
+    /// A [Spotify](https://spotify.com/) playlist.
+    Spotify(String),
+    /// A [Deezer](https://deezer.com/) playlist.
+    Deezer(String),
+}
+
+impl PlaylistUrl {
+    /// Parse the URL of a playlist, as shared from the service.
+    ///
+    /// Accepts `https://open.spotify.com/playlist/<id>`, `spotify:playlist:<id>`,
+    /// and `https://www.deezer.com/<language>/playlist/<id>`. Returns `None`
+    /// for anything else.
+    #[must_use]
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        if let Some(id) = input.strip_prefix("spotify:playlist:") {
+            return valid_id(id).map(Self::Spotify);
+        }
+
+        let url = url::Url::parse(input).ok()?;
+        let host = url.host_str()?.trim_start_matches("www.");
+        let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty());
+        match host {
+            "open.spotify.com" => {
+                let mut segments = segments.skip_while(|segment| *segment != "playlist");
+                segments.next()?;
+                valid_id(segments.next()?).map(Self::Spotify)
+            }
+            "deezer.com" => {
+                segments.find(|segment| *segment == "playlist")?;
+                let id = segments.next()?;
+                id.chars()
+                    .all(|c| c.is_ascii_digit())
+                    .then(|| Self::Deezer(id.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Check that a playlist ID has only the characters IDs are made of.
+fn valid_id(id: &str) -> Option<String> {
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())).then(|| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_playlist_url() {
+        assert_eq!(
+            PlaylistUrl::parse("https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M?si=abc"),
+            Some(PlaylistUrl::Spotify("37i9dQZF1DXcBWIGoYBM5M".to_string()))
+        );
+        assert_eq!(
+            PlaylistUrl::parse("https://open.spotify.com/intl-de/playlist/37i9dQZF1DXcBWIGoYBM5M"),
+            Some(PlaylistUrl::Spotify("37i9dQZF1DXcBWIGoYBM5M".to_string()))
+        );
+        assert_eq!(
+            PlaylistUrl::parse("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"),
+            Some(PlaylistUrl::Spotify("37i9dQZF1DXcBWIGoYBM5M".to_string()))
+        );
+        assert_eq!(
+            PlaylistUrl::parse("https://www.deezer.com/en/playlist/908622995"),
+            Some(PlaylistUrl::Deezer("908622995".to_string()))
+        );
+        assert_eq!(
+            PlaylistUrl::parse("https://deezer.com/playlist/908622995"),
+            Some(PlaylistUrl::Deezer("908622995".to_string()))
+        );
+
+        assert_eq!(
+            PlaylistUrl::parse("https://open.spotify.com/album/4aawyAB9vmqN3uQ7FjRGTy"),
+            None
+        );
+        assert_eq!(PlaylistUrl::parse("https://example.com/playlist/1"), None);
+        assert_eq!(PlaylistUrl::parse("playlists/road-trip.csv"), None);
+    }
+}
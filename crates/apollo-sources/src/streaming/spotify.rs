@@ -0,0 +1,368 @@
+//! [Spotify](https://spotify.com/) client for reading playlists.
+
+use super::{StreamingPlaylist, StreamingTrack, USER_AGENT};
+use crate::error::{SourceError, SourceResult};
+use reqwest::{Client, Response, StatusCode};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Accounts service, which hands out access tokens.
+const ACCOUNTS_BASE: &str = "https://accounts.spotify.com";
+
+/// Web API base URL.
+const API_BASE: &str = "https://api.spotify.com";
+
+/// Songs requested per page of a playlist.
+const PAGE_SIZE: usize = 100;
+
+/// Client for reading [Spotify](https://spotify.com/) playlists.
+///
+/// Authenticates with the client credentials flow, so only playlists that
+/// are public or shared by link can be read.
+pub struct SpotifyClient {
+    /// HTTP client.
+    client: Client,
+    client_id: String,
+    client_secret: String,
+    accounts_url: String,
+    api_url: String,
+    /// Access token, once requested.
+    token: Mutex<Option<String>>,
+}
+
+impl SpotifyClient {
+    /// Create a client with the credentials of a registered app.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either credential is empty or the HTTP client
+    /// cannot be created.
+    pub fn new(client_id: &str, client_secret: &str) -> SourceResult<Self> {
+        if client_id.is_empty() || client_secret.is_empty() {
+            return Err(SourceError::InvalidInput(
+                "Spotify client ID and secret are required".to_string(),
+            ));
+        }
+
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            accounts_url: ACCOUNTS_BASE.to_string(),
+            api_url: API_BASE.to_string(),
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Use other endpoints for the accounts service and the Web API, such as
+    /// a mock server.
+    #[must_use]
+    pub fn with_base_urls(
+        mut self,
+        accounts_url: impl Into<String>,
+        api_url: impl Into<String>,
+    ) -> Self {
+        self.accounts_url = accounts_url.into();
+        self.api_url = api_url.into();
+        self
+    }
+
+    /// Read a playlist with all its songs.
+    ///
+    /// Local files and episodes on the playlist are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlist does not exist or is private, the
+    /// credentials are rejected, or the request fails.
+    pub async fn get_playlist(&self, id: &str) -> SourceResult<StreamingPlaylist> {
+        let playlist: PlaylistResponse = self
+            .get(&format!("{}/v1/playlists/{id}?fields=name", self.api()))
+            .await?;
+
+        let mut tracks = Vec::new();
+        let mut next = Some(format!(
+            "{}/v1/playlists/{id}/tracks?limit={PAGE_SIZE}&offset=0",
+            self.api()
+        ));
+        while let Some(url) = next {
+            let page: TracksPage = self.get(&url).await?;
+            tracks.extend(
+                page.items
+                    .into_iter()
+                    .filter_map(|item| item.track)
+                    .filter(|track| !track.is_local && track.kind == "track")
+                    .map(StreamingTrack::from),
+            );
+            next = page.next;
+        }
+
+        Ok(StreamingPlaylist {
+            name: playlist.name,
+            tracks,
+        })
+    }
+
+    fn api(&self) -> &str {
+        self.api_url.trim_end_matches('/')
+    }
+
+    /// Make a GET request to the Web API, requesting a new access token
+    /// once if the current one has expired.
+    async fn get<T: DeserializeOwned>(&self, url: &str) -> SourceResult<T> {
+        debug!("GET {url}");
+
+        let token = self.access_token(false).await?;
+        let mut response = self.client.get(url).bearer_auth(&token).send().await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            debug!("Spotify access token expired, requesting a new one");
+            let token = self.access_token(true).await?;
+            response = self.client.get(url).bearer_auth(&token).send().await?;
+        }
+
+        Self::handle_response(response).await
+    }
+
+    /// Get an access token, requesting one if there is none yet or `renew`
+    /// is set.
+    async fn access_token(&self, renew: bool) -> SourceResult<String> {
+        if !renew {
+            let cached = self.token.lock().await.clone();
+            if let Some(token) = cached {
+                return Ok(token);
+            }
+        }
+
+        let url = format!("{}/api/token", self.accounts_url.trim_end_matches('/'));
+        let response = self
+            .client
+            .post(&url)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await?;
+        if response.status() == StatusCode::BAD_REQUEST
+            || response.status() == StatusCode::UNAUTHORIZED
+        {
+            let message = response.text().await.unwrap_or_default();
+            warn!("Spotify rejected the client credentials: {message}");
+            return Err(SourceError::InvalidInput(
+                "Spotify rejected the client ID or secret".to_string(),
+            ));
+        }
+
+        let response: TokenResponse = Self::handle_response(response).await?;
+        *self.token.lock().await = Some(response.access_token.clone());
+        Ok(response.access_token)
+    }
+
+    /// Turn a response into its JSON body or an error.
+    async fn handle_response<T: DeserializeOwned>(response: Response) -> SourceResult<T> {
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get("Retry-After")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1);
+            return Err(SourceError::RateLimited { retry_after });
+        }
+        if status == StatusCode::NOT_FOUND {
+            return Err(SourceError::NotFound);
+        }
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(SourceError::Api {
+                status: status.as_u16(),
+                message,
+            });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| SourceError::Parse(e.to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// A playlist, of which only the name is requested.
+#[derive(Debug, Deserialize)]
+struct PlaylistResponse {
+    name: String,
+}
+
+/// A page of the items on a playlist.
+#[derive(Debug, Deserialize)]
+struct TracksPage {
+    items: Vec<PlaylistItem>,
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistItem {
+    /// Missing for songs that are no longer available.
+    track: Option<SpotifyTrack>,
+}
+
+/// A song, or a podcast episode.
+#[derive(Debug, Deserialize)]
+struct SpotifyTrack {
+    name: String,
+    #[serde(default)]
+    artists: Vec<SpotifyName>,
+    album: Option<SpotifyName>,
+    duration_ms: Option<u64>,
+    #[serde(default)]
+    external_ids: ExternalIds,
+    #[serde(rename = "type", default = "default_kind")]
+    kind: String,
+    #[serde(default)]
+    is_local: bool,
+}
+
+fn default_kind() -> String {
+    "track".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct SpotifyName {
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ExternalIds {
+    isrc: Option<String>,
+}
+
+impl From<SpotifyTrack> for StreamingTrack {
+    fn from(track: SpotifyTrack) -> Self {
+        Self {
+            title: track.name,
+            artists: track.artists.into_iter().map(|a| a.name).collect(),
+            album: track.album.map(|album| album.name),
+            duration: track.duration_ms.map(Duration::from_millis),
+            isrc: track.external_ids.isrc.filter(|isrc| !isrc.is_empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mock_token(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/api/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "token",
+                "token_type": "Bearer",
+                "expires_in": 3600
+            })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn test_requires_credentials() {
+        assert!(matches!(
+            SpotifyClient::new("", "secret"),
+            Err(SourceError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_playlist() {
+        let server = MockServer::start().await;
+        mock_token(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/v1/playlists/abc"))
+            .and(header("Authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Mix" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/playlists/abc/tracks"))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "items": [
+                    { "track": {
+                        "name": "Under Pressure",
+                        "artists": [{ "name": "Queen" }, { "name": "David Bowie" }],
+                        "album": { "name": "Hot Space" },
+                        "duration_ms": 248_000,
+                        "external_ids": { "isrc": "GBUM71029604" },
+                        "type": "track"
+                    } },
+                    { "track": null },
+                    { "track": { "name": "Episode", "type": "episode" } }
+                ],
+                "next": format!("{}/v1/playlists/abc/tracks?limit=100&offset=100", server.uri())
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/playlists/abc/tracks"))
+            .and(query_param("offset", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "items": [{ "track": {
+                    "name": "Local Demo",
+                    "artists": [{ "name": "Me" }],
+                    "is_local": true
+                } }],
+                "next": null
+            })))
+            .mount(&server)
+            .await;
+
+        let client = SpotifyClient::new("id", "secret")
+            .unwrap()
+            .with_base_urls(server.uri(), server.uri());
+        let playlist = client.get_playlist("abc").await.unwrap();
+        assert_eq!(playlist.name, "Mix");
+        assert_eq!(playlist.tracks.len(), 1);
+
+        let track = &playlist.tracks[0];
+        assert_eq!(track.artist(), "Queen, David Bowie");
+        assert_eq!(track.album.as_deref(), Some("Hot Space"));
+        assert_eq!(track.duration, Some(Duration::from_secs(248)));
+        assert_eq!(track.isrc.as_deref(), Some("GBUM71029604"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/token"))
+            .respond_with(
+                ResponseTemplate::new(400).set_body_json(json!({ "error": "invalid_client" })),
+            )
+            .mount(&server)
+            .await;
+
+        let client = SpotifyClient::new("id", "wrong")
+            .unwrap()
+            .with_base_urls(server.uri(), server.uri());
+        assert!(matches!(
+            client.get_playlist("abc").await,
+            Err(SourceError::InvalidInput(_))
+        ));
+    }
+}
@@ -16,9 +16,16 @@ export interface Track {
   format: string;
   bitrate: number | null;
   sample_rate: number | null;
+  bit_depth: number | null;
   channels: number | null;
   musicbrainz_id: string | null;
   acoustid: string | null;
+  isrc: string | null;
+  rg_track_gain: number | null;
+  rg_track_peak: number | null;
+  rg_album_gain: number | null;
+  rg_album_peak: number | null;
+  loudness_lufs: number | null;
   added_at: string;
   updated_at: string;
 }
@@ -33,6 +40,11 @@ export interface Album {
   total_discs: number | null;
   musicbrainz_id: string | null;
   cover_art_path: string | null;
+  barcode: string | null;
+  discogs_id: number | null;
+  rg_album_gain: number | null;
+  rg_album_peak: number | null;
+  loudness_lufs: number | null;
   added_at: string;
   updated_at: string;
 }
@@ -0,0 +1,106 @@
+//! Access log and request IDs.
+//!
+//! Every request is given an ID, or keeps the one a proxy in front of the
+//! server set in the `web.logging.request_id_header` header. The ID is sent
+//! back in that header and in error responses, and everything logged while
+//! the request is handled is logged in a span that carries it, so that a
+//! problem a user reports can be found in the server's log.
+
+use crate::state::AppState;
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{Instrument, info, info_span, warn};
+use uuid::Uuid;
+
+/// Header that carries the request ID, if the configured one is not valid.
+const DEFAULT_HEADER: &str = "x-request-id";
+
+/// Longest request ID that is taken over from a request.
+const MAX_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Get the ID of the request being handled.
+///
+/// Returns `None` outside of a request, such as in background jobs.
+#[must_use]
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware that gives each request an ID, and logs it once handled.
+pub async fn log_requests(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let logging = &state.config.web.logging;
+    let header = HeaderName::from_bytes(logging.request_id_header.as_bytes())
+        .unwrap_or_else(|_| HeaderName::from_static(DEFAULT_HEADER));
+    let id = request
+        .headers()
+        .get(&header)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_id(id))
+        .map_or_else(|| Uuid::new_v4().to_string(), ToString::to_string);
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let span = info_span!("request", request_id = %id, %method, %path);
+    let started = Instant::now();
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(request))
+        .instrument(span.clone())
+        .await;
+
+    if logging.access_log {
+        let status = response.status();
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        span.in_scope(|| {
+            if status.is_server_error() {
+                warn!(status = status.as_u16(), latency_ms, "{method} {path}");
+            } else {
+                info!(status = status.as_u16(), latency_ms, "{method} {path}");
+            }
+        });
+    }
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(header, value);
+    }
+    response
+}
+
+/// Check that a request ID from a client is short and printable, so that it
+/// can be logged as it is.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_ids() {
+        assert!(is_valid_id("550e8400-e29b-41d4-a716-446655440000"));
+        assert!(is_valid_id("req_42"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("two words"));
+        assert!(!is_valid_id(&"a".repeat(MAX_ID_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_current_request_id() {
+        assert_eq!(current_request_id(), None);
+        let id = REQUEST_ID
+            .scope("abc".to_string(), async { current_request_id() })
+            .await;
+        assert_eq!(id.as_deref(), Some("abc"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Artist images, cached on disk.
+//!
+//! The image of an artist is looked up with an [`ArtistImageClient`] the
+//! first time it is asked for, and kept in a directory by artist ID. Artists
+//! without an image are remembered as well, and only looked up again after
+//! [`RETRY_AFTER`], so that showing the artists of a library does not query
+//! the services for every artist each time. Where there is no image,
+//! [`placeholder`] draws the artist's initials instead.
+
+use crate::dlna;
+use apollo_core::metadata::ArtistId;
+use apollo_sources::SourceError;
+use apollo_sources::artistart::{ArtistImage, ArtistImageClient};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// How long to wait before looking up an artist without an image again.
+pub const RETRY_AFTER: Duration = Duration::from_hours(7 * 24);
+
+/// File extensions of the cached images, by MIME type.
+const IMAGE_TYPES: [(&str, &str); 4] = [
+    ("jpg", "image/jpeg"),
+    ("png", "image/png"),
+    ("webp", "image/webp"),
+    ("gif", "image/gif"),
+];
+
+/// Extension of the files that mark artists without an image.
+const MISSING_EXTENSION: &str = "none";
+
+/// Artist images, looked up once and then kept on disk.
+pub struct ArtistImages {
+    client: ArtistImageClient,
+    directory: PathBuf,
+}
+
+impl ArtistImages {
+    /// Look up images with a client, and keep them in a directory.
+    ///
+    /// The directory is created when the first image is stored.
+    #[must_use]
+    pub fn new(client: ArtistImageClient, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            client,
+            directory: directory.into(),
+        }
+    }
+
+    /// Get the image of an artist, looking it up if it is not cached yet.
+    ///
+    /// Returns `None` if the artist has no image, or the lookup failed.
+    pub async fn get(&self, id: &ArtistId, name: &str) -> Option<ArtistImage> {
+        for (extension, mime_type) in IMAGE_TYPES {
+            if let Ok(data) = tokio::fs::read(self.path(id, extension)).await {
+                return Some(ArtistImage {
+                    data,
+                    mime_type: mime_type.to_string(),
+                });
+            }
+        }
+        if self.recently_missing(id).await {
+            return None;
+        }
+
+        match self.client.find_image(name).await {
+            Ok(image) => {
+                self.store(id, &image).await;
+                Some(image)
+            }
+            Err(SourceError::NotFound) => {
+                debug!("No image found for artist {name}");
+                self.store_missing(id).await;
+                None
+            }
+            Err(e) => {
+                warn!("Failed to look up image of artist {name}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Check whether the artist was looked up without an image lately.
+    async fn recently_missing(&self, id: &ArtistId) -> bool {
+        let Ok(metadata) = tokio::fs::metadata(self.path(id, MISSING_EXTENSION)).await else {
+            return false;
+        };
+        metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age < RETRY_AFTER)
+    }
+
+    /// Keep an image, unless it is of a type that is not cached.
+    async fn store(&self, id: &ArtistId, image: &ArtistImage) {
+        let Some((extension, _)) = IMAGE_TYPES
+            .iter()
+            .find(|(_, mime_type)| *mime_type == image.mime_type)
+        else {
+            return;
+        };
+        let result = async {
+            tokio::fs::create_dir_all(&self.directory).await?;
+            tokio::fs::write(self.path(id, extension), &image.data).await?;
+            remove_if_exists(&self.path(id, MISSING_EXTENSION)).await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to cache artist image: {e}");
+        }
+    }
+
+    /// Remember that the artist has no image.
+    async fn store_missing(&self, id: &ArtistId) {
+        let result = async {
+            tokio::fs::create_dir_all(&self.directory).await?;
+            tokio::fs::write(self.path(id, MISSING_EXTENSION), b"").await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to cache missing artist image: {e}");
+        }
+    }
+
+    /// Get the path of a cache file of an artist.
+    fn path(&self, id: &ArtistId, extension: &str) -> PathBuf {
+        self.directory.join(format!("{id}.{extension}"))
+    }
+}
+
+/// Remove a file, if there is one.
+async fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Draw a placeholder image with the initials of an artist, as SVG.
+#[must_use]
+pub fn placeholder(name: &str) -> String {
+    let initials: String = name
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .flat_map(char::to_uppercase)
+        .take(2)
+        .collect();
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="500" height="500" viewBox="0 0 500 500"><rect width="500" height="500" fill="#4a4a4a"/><text x="250" y="250" dy="0.35em" text-anchor="middle" font-family="sans-serif" font-size="200" fill="#d0d0d0">{}</text></svg>"##,
+        dlna::escape(&initials)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_placeholder() {
+        assert!(placeholder("nina simone").contains(">NS</text>"));
+        assert!(placeholder("The Rolling Stones").contains(">TR</text>"));
+        assert!(placeholder("!!!").contains("></text>"));
+        assert!(placeholder("Simon & Garfunkel").contains(">SG</text>"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_images_are_remembered() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/ws/2/artist"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "artists": [] })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let dir = tempfile::TempDir::new().unwrap();
+        let client = ArtistImageClient::new("Test", "1.0", "test@example.com")
+            .unwrap()
+            .with_base_url(server.uri());
+        let images = ArtistImages::new(client, dir.path().join("artists"));
+
+        let id = ArtistId::from_name("Nobody");
+        assert!(images.get(&id, "Nobody").await.is_none());
+        assert!(dir.path().join(format!("artists/{id}.none")).exists());
+        // Not looked up again
+        assert!(images.get(&id, "Nobody").await.is_none());
+
+        // Cached images are served as they are
+        let other = ArtistId::from_name("Somebody");
+        std::fs::write(dir.path().join(format!("artists/{other}.png")), b"png").unwrap();
+        let image = images.get(&other, "Somebody").await.unwrap();
+        assert_eq!(image.data, b"png");
+        assert_eq!(image.mime_type, "image/png");
+    }
+}
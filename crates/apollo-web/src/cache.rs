@@ -0,0 +1,139 @@
+//! Cached smart playlist results.
+//!
+//! Evaluating a smart playlist runs its query against the whole library, so
+//! results are kept until a [`LibraryEvent`] says they may have changed.
+//! Changes to tracks, albums, ratings, or plays drop every cached result;
+//! changes to a playlist drop only that playlist's result.
+
+use apollo_core::event::{EventBus, EventReceiver, LibraryEvent};
+use apollo_core::metadata::Track;
+use apollo_core::playlist::PlaylistId;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use tokio::sync::broadcast::error::TryRecvError;
+
+/// Smart playlist tracks, invalidated by library events.
+pub struct PlaylistCache {
+    state: Mutex<CacheState>,
+}
+
+struct CacheState {
+    /// Events not yet applied to the cache.
+    events: EventReceiver,
+    /// Cached tracks by playlist.
+    tracks: HashMap<PlaylistId, Vec<Track>>,
+    /// Incremented whenever cached results are dropped.
+    generation: u64,
+}
+
+impl CacheState {
+    /// Drop results that pending events may have changed.
+    fn sync(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.invalidate(&event),
+                // Missed events could have changed anything
+                Err(TryRecvError::Lagged(_)) => {
+                    self.tracks.clear();
+                    self.generation += 1;
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+    }
+
+    fn invalidate(&mut self, event: &LibraryEvent) {
+        if event.affects_queries() {
+            self.tracks.clear();
+        } else if let Some(playlist_id) = event.playlist_id() {
+            self.tracks.remove(playlist_id);
+        }
+        self.generation += 1;
+    }
+}
+
+impl PlaylistCache {
+    /// Create an empty cache that is invalidated by events on `events`.
+    #[must_use]
+    pub fn new(events: &EventBus) -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                events: events.subscribe(),
+                tracks: HashMap::new(),
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Get the cached tracks of a playlist.
+    pub fn get(&self, playlist_id: &PlaylistId) -> Option<Vec<Track>> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.sync();
+        state.tracks.get(playlist_id).cloned()
+    }
+
+    /// Get the current generation, to pass to [`Self::insert`].
+    ///
+    /// Take the generation before evaluating a playlist, so that results
+    /// that were outdated by a change during evaluation are not cached.
+    pub fn generation(&self) -> u64 {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.sync();
+        state.generation
+    }
+
+    /// Cache the tracks of a playlist, evaluated at `generation`.
+    ///
+    /// Does nothing if the library changed since `generation`.
+    pub fn insert(&self, playlist_id: PlaylistId, tracks: Vec<Track>, generation: u64) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.sync();
+        if state.generation == generation {
+            state.tracks.insert(playlist_id, tracks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::metadata::TrackId;
+
+    #[test]
+    fn invalidated_by_events() {
+        let bus = EventBus::new();
+        let cache = PlaylistCache::new(&bus);
+        let mix = PlaylistId::new();
+        let other = PlaylistId::new();
+
+        let generation = cache.generation();
+        cache.insert(mix.clone(), Vec::new(), generation);
+        cache.insert(other.clone(), Vec::new(), generation);
+        assert!(cache.get(&mix).is_some());
+
+        bus.emit(LibraryEvent::PlaylistChanged {
+            playlist_id: other.clone(),
+        });
+        assert!(cache.get(&mix).is_some());
+        assert!(cache.get(&other).is_none());
+
+        bus.emit(LibraryEvent::TrackAdded {
+            track_id: TrackId::new(),
+        });
+        assert!(cache.get(&mix).is_none());
+    }
+
+    #[test]
+    fn outdated_results_not_cached() {
+        let bus = EventBus::new();
+        let cache = PlaylistCache::new(&bus);
+        let mix = PlaylistId::new();
+
+        let generation = cache.generation();
+        bus.emit(LibraryEvent::TrackRemoved {
+            track_id: TrackId::new(),
+        });
+        cache.insert(mix.clone(), Vec::new(), generation);
+        assert!(cache.get(&mix).is_none());
+    }
+}
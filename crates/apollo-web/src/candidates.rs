@@ -0,0 +1,305 @@
+//! Candidate metadata for tagging tracks by hand.
+//!
+//! Where [`identify`](crate::identify) goes by a track's audio, candidates
+//! here are found by searching for its tags: releases and recordings on
+//! [MusicBrainz], or releases on [Discogs]. Each candidate carries the track
+//! as it would be with the candidate's metadata, and the changes that makes,
+//! so that a tag editor can show them side by side and apply the one picked.
+//!
+//! [MusicBrainz]: https://musicbrainz.org/
+//! [Discogs]: https://discogs.com/
+
+use crate::error::ApiError;
+use apollo_core::diff::{FieldChange, TrackDiff};
+use apollo_core::metadata::Track;
+use apollo_core::provenance::MetadataSource;
+use apollo_sources::discogs::DiscogsClient;
+use apollo_sources::musicbrainz::{MusicBrainzClient, ReleaseMatcher};
+use apollo_sources::{SourceError, SourceResult};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Most candidates of each kind looked up for a track.
+pub const MAX_CANDIDATES: u32 = 5;
+
+/// Most releases looked up on `MusicBrainz` for a track, each of which takes
+/// a request for its track list.
+const MAX_RELEASES: u32 = 3;
+
+/// Related entities to fetch for a recording.
+const RECORDING_INCLUDES: &[&str] = &["artists", "releases", "isrcs"];
+
+/// Service that candidates come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateSource {
+    /// [MusicBrainz](https://musicbrainz.org/).
+    #[default]
+    MusicBrainz,
+    /// [Discogs](https://discogs.com/).
+    Discogs,
+}
+
+impl CandidateSource {
+    /// Get the source that metadata from the service is recorded as.
+    #[must_use]
+    pub const fn metadata_source(self) -> MetadataSource {
+        match self {
+            Self::MusicBrainz => MetadataSource::MusicBrainz,
+            Self::Discogs => MetadataSource::Discogs,
+        }
+    }
+}
+
+/// What a candidate is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateKind {
+    /// A release, with the track of it that the track is.
+    Release,
+    /// A recording, with the release it first appeared on.
+    Recording,
+}
+
+/// Metadata a track could be tagged with.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TagCandidate {
+    /// Service the candidate comes from.
+    pub source: CandidateSource,
+    /// Whether the candidate is a release or a recording.
+    pub kind: CandidateKind,
+    /// ID of the release or recording on its service.
+    pub id: String,
+    /// Title of the release or recording.
+    #[schema(example = "A Night at the Opera")]
+    pub title: String,
+    /// Artist of the release or recording.
+    #[schema(example = "Queen")]
+    pub artist: String,
+    /// How well the candidate fits the track, from 0 to 1.
+    #[schema(example = 0.92)]
+    pub score: f64,
+    /// The track with the candidate's metadata applied.
+    pub track: Track,
+    /// Fields of the track that would change.
+    pub changes: Vec<FieldChange>,
+}
+
+/// Services that candidate metadata is looked up on.
+#[derive(Default)]
+pub struct TagSources {
+    musicbrainz: Option<MusicBrainzClient>,
+    discogs: Option<DiscogsClient>,
+}
+
+impl TagSources {
+    /// Look up candidates on `MusicBrainz`.
+    #[must_use]
+    pub fn with_musicbrainz(mut self, client: MusicBrainzClient) -> Self {
+        self.musicbrainz = Some(client);
+        self
+    }
+
+    /// Look up candidates on Discogs.
+    #[must_use]
+    pub fn with_discogs(mut self, client: DiscogsClient) -> Self {
+        self.discogs = Some(client);
+        self
+    }
+
+    /// Find candidates for a track on a service, best first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service is not set up, or a request to it
+    /// fails.
+    pub async fn candidates(
+        &self,
+        track: &Track,
+        source: CandidateSource,
+    ) -> Result<Vec<TagCandidate>, ApiError> {
+        let mut candidates = match source {
+            CandidateSource::MusicBrainz => {
+                musicbrainz_candidates(self.musicbrainz()?, track).await
+            }
+            CandidateSource::Discogs => discogs_candidates(self.discogs()?, track).await,
+        }
+        .map_err(|e| source_error(source, e))?;
+        for candidate in &mut candidates {
+            candidate.changes = TrackDiff::between(track, &candidate.track).changes;
+        }
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(candidates)
+    }
+
+    /// Tag a track with a candidate, given its ID.
+    ///
+    /// The candidate is looked up again, so that only metadata from the
+    /// service is applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service is not set up, the candidate is not
+    /// found, or a request to the service fails.
+    pub async fn apply(
+        &self,
+        track: &Track,
+        source: CandidateSource,
+        kind: CandidateKind,
+        id: &str,
+    ) -> Result<Track, ApiError> {
+        let mut tagged = track.clone();
+        match (source, kind) {
+            (CandidateSource::MusicBrainz, CandidateKind::Release) => {
+                let client = self.musicbrainz()?;
+                let candidate = ReleaseMatcher::new(client)
+                    .lookup(std::slice::from_ref(track), id)
+                    .await
+                    .map_err(|e| source_error(source, e))?;
+                candidate.apply(std::slice::from_mut(&mut tagged));
+            }
+            (CandidateSource::MusicBrainz, CandidateKind::Recording) => {
+                let recording = self
+                    .musicbrainz()?
+                    .lookup_recording(id, RECORDING_INCLUDES)
+                    .await
+                    .map_err(|e| source_error(source, e))?;
+                recording.apply_to_track(&mut tagged);
+            }
+            (CandidateSource::Discogs, CandidateKind::Release) => {
+                let release_id = id.parse().map_err(|_| {
+                    ApiError::BadRequest(format!("Invalid Discogs release ID: {id}"))
+                })?;
+                let release = self
+                    .discogs()?
+                    .get_release(release_id)
+                    .await
+                    .map_err(|e| source_error(source, e))?;
+                let (index, _) = release.match_track(track);
+                release.apply_to_track(&mut tagged, index);
+            }
+            (CandidateSource::Discogs, CandidateKind::Recording) => {
+                return Err(ApiError::BadRequest(
+                    "Discogs has releases only, not recordings".to_string(),
+                ));
+            }
+        }
+        Ok(tagged)
+    }
+
+    fn musicbrainz(&self) -> Result<&MusicBrainzClient, ApiError> {
+        self.musicbrainz.as_ref().ok_or_else(|| {
+            ApiError::BadRequest(
+                "MusicBrainz is disabled; enable it with musicbrainz.enabled".to_string(),
+            )
+        })
+    }
+
+    fn discogs(&self) -> Result<&DiscogsClient, ApiError> {
+        self.discogs.as_ref().ok_or_else(|| {
+            ApiError::BadRequest("Discogs is not set up; set a token in discogs.token".to_string())
+        })
+    }
+}
+
+/// Find recordings on `MusicBrainz` by a track's title and artist, and
+/// releases by its album title if it has one.
+async fn musicbrainz_candidates(
+    client: &MusicBrainzClient,
+    track: &Track,
+) -> SourceResult<Vec<TagCandidate>> {
+    let mut candidates = Vec::new();
+    let recordings = client
+        .search_recordings(&track.title, Some(&track.artist), MAX_CANDIDATES)
+        .await?;
+    for recording in recordings {
+        let mut tagged = track.clone();
+        recording.apply_to_track(&mut tagged);
+        candidates.push(TagCandidate {
+            source: CandidateSource::MusicBrainz,
+            kind: CandidateKind::Recording,
+            id: recording.id.clone(),
+            title: recording.title.clone(),
+            artist: recording.artist_name(),
+            score: recording
+                .score
+                .map_or(0.0, |score| f64::from(score) / 100.0),
+            track: tagged,
+            changes: Vec::new(),
+        });
+    }
+
+    let releases = ReleaseMatcher::new(client)
+        .with_max_candidates(MAX_RELEASES)
+        .candidates(std::slice::from_ref(track))
+        .await?;
+    for candidate in releases {
+        let mut tagged = track.clone();
+        candidate.apply(std::slice::from_mut(&mut tagged));
+        candidates.push(TagCandidate {
+            source: CandidateSource::MusicBrainz,
+            kind: CandidateKind::Release,
+            id: candidate.release.id.clone(),
+            title: candidate.release.title.clone(),
+            artist: candidate.release.artist_name(),
+            score: candidate.score,
+            track: tagged,
+            changes: Vec::new(),
+        });
+    }
+    Ok(candidates)
+}
+
+/// Find releases on Discogs by a track's album title, or by its title if it
+/// has none.
+async fn discogs_candidates(
+    client: &DiscogsClient,
+    track: &Track,
+) -> SourceResult<Vec<TagCandidate>> {
+    let artist = track.album_artist.as_deref().unwrap_or(&track.artist);
+    let results = if let Some(album) = &track.album_title {
+        client
+            .search_releases(album, Some(artist), MAX_CANDIDATES)
+            .await?
+    } else {
+        let query = format!("{artist} {}", track.title);
+        let (results, _) = client.search(&query, MAX_CANDIDATES).await?;
+        results
+            .into_iter()
+            .filter(|result| result.result_type == "release")
+            .collect()
+    };
+
+    let mut candidates = Vec::with_capacity(results.len());
+    for result in results {
+        let release = client.get_release(result.id).await?;
+        let (index, score) = release.match_track(track);
+        let mut tagged = track.clone();
+        release.apply_to_track(&mut tagged, index);
+        candidates.push(TagCandidate {
+            source: CandidateSource::Discogs,
+            kind: CandidateKind::Release,
+            id: release.id.to_string(),
+            title: release.title.clone(),
+            artist: release.artist_name(),
+            score,
+            track: tagged,
+            changes: Vec::new(),
+        });
+    }
+    Ok(candidates)
+}
+
+/// Turn a failed request to a service into an API error.
+fn source_error(source: CandidateSource, error: SourceError) -> ApiError {
+    let name = match source {
+        CandidateSource::MusicBrainz => "MusicBrainz",
+        CandidateSource::Discogs => "Discogs",
+    };
+    match error {
+        SourceError::NotFound | SourceError::Api { status: 404, .. } => {
+            ApiError::NotFound(format!("Not found on {name}"))
+        }
+        error => ApiError::Internal(format!("{name} lookup failed: {error}")),
+    }
+}
@@ -0,0 +1,508 @@
+//! DLNA media server, for smart TVs and network receivers.
+//!
+//! With `dlna.enabled` set, the web server also acts as a `UPnP`
+//! `MediaServer`: an [`SsdpServer`] lets players on the local network find
+//! it, and the routes from [`routes`] describe the device and answer
+//! `ContentDirectory` requests to browse the library. Players stream tracks
+//! from the same endpoint as other clients of the web API.
+//!
+//! Control requests are small SOAP documents, so the few arguments they
+//! carry are picked out directly instead of parsing the XML.
+
+mod content;
+mod ssdp;
+
+pub use ssdp::SsdpServer;
+
+use crate::error::ApiError;
+use crate::state::AppState;
+use apollo_core::Config;
+use apollo_core::metadata::{AlbumId, AudioFormat};
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{any, get, post},
+};
+use content::{BrowseFlag, browse};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Device type of a media server.
+const MEDIA_SERVER: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+
+/// Service type of the content directory, which players browse.
+const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+
+/// Service type of the connection manager, which lists playable formats.
+const CONNECTION_MANAGER: &str = "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+/// Request header of players that want to know how a file can be played.
+pub(crate) const GET_CONTENT_FEATURES: &str = "getcontentfeatures.dlna.org";
+
+/// Response header that tells players how a file can be played.
+pub(crate) const CONTENT_FEATURES: &str = "contentfeatures.dlna.org";
+
+/// Response header that tells players how a file is sent.
+pub(crate) const TRANSFER_MODE: &str = "transfermode.dlna.org";
+
+/// How streamed files can be played: seeking by byte range, as a stream.
+pub(crate) const STREAM_FEATURES: &str =
+    "DLNA.ORG_OP=01;DLNA.ORG_CI=0;DLNA.ORG_FLAGS=01700000000000000000000000000000";
+
+/// Content type of `UPnP` documents.
+const XML: &str = r#"text/xml; charset="utf-8""#;
+
+/// Get the device ID of the media server.
+///
+/// The ID stays the same across restarts, so that players recognize the
+/// server, and differs between libraries and server names.
+#[must_use]
+pub fn device_uuid(config: &Config) -> Uuid {
+    let key = format!(
+        "apollo:dlna:{}:{}",
+        config.library_path().display(),
+        config.dlna.name
+    );
+    Uuid::new_v5(&Uuid::NAMESPACE_URL, key.as_bytes())
+}
+
+/// Create the routes of the media server.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/dlna/description.xml", get(description))
+        .route(
+            "/dlna/ContentDirectory.xml",
+            get(|| async { xml(StatusCode::OK, CONTENT_DIRECTORY_SCPD.to_string()) }),
+        )
+        .route(
+            "/dlna/ConnectionManager.xml",
+            get(|| async { xml(StatusCode::OK, CONNECTION_MANAGER_SCPD.to_string()) }),
+        )
+        .route(
+            "/dlna/control/ContentDirectory",
+            post(content_directory_control),
+        )
+        .route(
+            "/dlna/control/ConnectionManager",
+            post(connection_manager_control),
+        )
+        .route("/dlna/event/:service", any(subscribe))
+        .route("/dlna/art/:id", get(album_art))
+}
+
+/// Serve the device description, which players read first.
+async fn description(State(state): State<Arc<AppState>>) -> Response {
+    let config = &state.config;
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" xmlns:dlna="urn:schemas-dlna-org:device-1-0">
+<specVersion><major>1</major><minor>0</minor></specVersion>
+<device>
+<deviceType>{MEDIA_SERVER}</deviceType>
+<friendlyName>{name}</friendlyName>
+<manufacturer>Apollo</manufacturer>
+<modelName>Apollo</modelName>
+<modelNumber>{version}</modelNumber>
+<UDN>uuid:{uuid}</UDN>
+<dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>
+<serviceList>
+<service>
+<serviceType>{CONTENT_DIRECTORY}</serviceType>
+<serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId>
+<SCPDURL>/dlna/ContentDirectory.xml</SCPDURL>
+<controlURL>/dlna/control/ContentDirectory</controlURL>
+<eventSubURL>/dlna/event/ContentDirectory</eventSubURL>
+</service>
+<service>
+<serviceType>{CONNECTION_MANAGER}</serviceType>
+<serviceId>urn:upnp-org:serviceId:ConnectionManager</serviceId>
+<SCPDURL>/dlna/ConnectionManager.xml</SCPDURL>
+<controlURL>/dlna/control/ConnectionManager</controlURL>
+<eventSubURL>/dlna/event/ConnectionManager</eventSubURL>
+</service>
+</serviceList>
+</device>
+</root>
+"#,
+        name = escape(&config.dlna.name),
+        version = env!("CARGO_PKG_VERSION"),
+        uuid = device_uuid(config),
+    );
+    xml(StatusCode::OK, body)
+}
+
+/// Answer a request to the content directory.
+async fn content_directory_control(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let action = soap_action(&headers);
+    let result = match action.as_str() {
+        "Browse" => browse_action(&state, &headers, &body).await,
+        "GetSearchCapabilities" => Ok(vec![("SearchCaps", String::new())]),
+        "GetSortCapabilities" => Ok(vec![("SortCaps", String::new())]),
+        "GetSystemUpdateID" => Ok(vec![("Id", update_id(&state).to_string())]),
+        _ => return soap_fault(401, "Invalid Action"),
+    };
+    match result {
+        Ok(values) => soap_response(&action, CONTENT_DIRECTORY, &values),
+        Err(ApiError::NotFound(_)) => soap_fault(701, "No such object"),
+        Err(ApiError::BadRequest(_)) => soap_fault(402, "Invalid Args"),
+        Err(e) => {
+            warn!("DLNA browse failed: {e:?}");
+            soap_fault(501, "Action Failed")
+        }
+    }
+}
+
+/// Browse the library for a player.
+async fn browse_action(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Vec<(&'static str, String)>, ApiError> {
+    let invalid = || ApiError::BadRequest("Invalid browse arguments".to_string());
+    let object_id = soap_arg(body, "ObjectID").ok_or_else(invalid)?;
+    let flag = soap_arg(body, "BrowseFlag")
+        .and_then(|flag| BrowseFlag::parse(&flag))
+        .ok_or_else(invalid)?;
+    let number = |name: &str| {
+        soap_arg(body, name).map_or(Ok(0), |value| value.trim().parse().map_err(|_| invalid()))
+    };
+    let start = number("StartingIndex")?;
+    let count = number("RequestedCount")?;
+
+    // Links must use the address the player reached the server at
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .ok_or_else(invalid)?;
+    let base = format!("http://{host}");
+
+    let page = browse(&*state.db, &base, &object_id, flag, start, count).await?;
+    Ok(vec![
+        ("Result", page.didl),
+        ("NumberReturned", page.returned.to_string()),
+        ("TotalMatches", page.total.to_string()),
+        ("UpdateID", update_id(state).to_string()),
+    ])
+}
+
+/// Answer a request to the connection manager.
+async fn connection_manager_control(headers: HeaderMap) -> Response {
+    let action = soap_action(&headers);
+    let values = match action.as_str() {
+        "GetProtocolInfo" => vec![("Source", protocol_info()), ("Sink", String::new())],
+        "GetCurrentConnectionIDs" => vec![("ConnectionIDs", "0".to_string())],
+        "GetCurrentConnectionInfo" => vec![
+            ("RcsID", "-1".to_string()),
+            ("AVTransportID", "-1".to_string()),
+            ("ProtocolInfo", String::new()),
+            ("PeerConnectionManager", String::new()),
+            ("PeerConnectionID", "-1".to_string()),
+            ("Direction", "Output".to_string()),
+            ("Status", "OK".to_string()),
+        ],
+        _ => return soap_fault(401, "Invalid Action"),
+    };
+    soap_response(&action, CONNECTION_MANAGER, &values)
+}
+
+/// Accept subscriptions to change events.
+///
+/// Players may refuse servers that turn subscriptions down, but they also
+/// poll `GetSystemUpdateID`, so no events are sent.
+async fn subscribe(method: Method) -> Response {
+    match method.as_str() {
+        "SUBSCRIBE" => (
+            [
+                ("SID", format!("uuid:{}", Uuid::new_v4())),
+                ("TIMEOUT", "Second-1800".to_string()),
+            ],
+            StatusCode::OK,
+        )
+            .into_response(),
+        "UNSUBSCRIBE" => StatusCode::OK.into_response(),
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+/// Serve the cover of an album.
+async fn album_art(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid album ID: {id}")))?;
+    let artwork = state
+        .db
+        .get_album_artwork(&AlbumId(uuid))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No cover for album: {id}")))?;
+    let mime = HeaderValue::from_str(&artwork.mime_type)
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    Ok(([(header::CONTENT_TYPE, mime)], Body::from(artwork.data)).into_response())
+}
+
+/// Get the version of the library that players see, which changes when the
+/// library does.
+fn update_id(state: &AppState) -> u32 {
+    // The smart playlist cache counts the changes already
+    u32::try_from(state.playlists.generation() % (u64::from(u32::MAX) + 1)).unwrap_or_default()
+}
+
+/// Get the formats that players can receive.
+fn protocol_info() -> String {
+    let mut mime_types: Vec<&str> = [
+        AudioFormat::Mp3,
+        AudioFormat::Flac,
+        AudioFormat::Ogg,
+        AudioFormat::Aac,
+        AudioFormat::Wav,
+        AudioFormat::Aiff,
+    ]
+    .iter()
+    .map(|format| format.mime_type())
+    .collect();
+    mime_types.dedup();
+    mime_types
+        .iter()
+        .map(|mime| format!("http-get:*:{mime}:*"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Get the action of a SOAP request, such as `Browse`.
+fn soap_action(headers: &HeaderMap) -> String {
+    headers
+        .get("soapaction")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().trim_matches('"').rsplit_once('#'))
+        .map(|(_, action)| action.to_string())
+        .unwrap_or_default()
+}
+
+/// Get the value of an argument of a SOAP request.
+fn soap_arg(body: &str, name: &str) -> Option<String> {
+    let open = format!("<{name}");
+    let mut rest = body;
+    loop {
+        let at = rest.find(&open)?;
+        rest = &rest[at + open.len()..];
+        // Skip longer names that start the same, such as `ObjectIDs`
+        match rest.chars().next()? {
+            '>' => break,
+            c if c.is_whitespace() => {
+                let end = rest.find('>')?;
+                if rest[..end].ends_with('/') {
+                    return Some(String::new());
+                }
+                rest = &rest[end..];
+                break;
+            }
+            '/' => return Some(String::new()),
+            _ => {}
+        }
+    }
+    let value = &rest[1..];
+    let end = value.find('<')?;
+    Some(unescape(&value[..end]))
+}
+
+/// Write the response to a SOAP request.
+fn soap_response(action: &str, service: &str, values: &[(&str, String)]) -> Response {
+    let mut body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><u:{action}Response xmlns:u="{service}">"#
+    );
+    for (name, value) in values {
+        let _ = write!(body, "<{name}>{}</{name}>", escape(value));
+    }
+    let _ = write!(body, "</u:{action}Response></s:Body></s:Envelope>");
+    xml(StatusCode::OK, body)
+}
+
+/// Write a `UPnP` error.
+fn soap_fault(code: u16, description: &str) -> Response {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0"><errorCode>{code}</errorCode><errorDescription>{description}</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>"#
+    );
+    xml(StatusCode::INTERNAL_SERVER_ERROR, body)
+}
+
+/// Create a response with an XML document.
+fn xml(status: StatusCode, body: String) -> Response {
+    (status, [(header::CONTENT_TYPE, XML)], body).into_response()
+}
+
+/// Escape text for XML.
+pub(crate) fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Undo [`escape`].
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Description of the content directory service.
+const CONTENT_DIRECTORY_SCPD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+<specVersion><major>1</major><minor>0</minor></specVersion>
+<actionList>
+<action><name>Browse</name><argumentList>
+<argument><name>ObjectID</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_ObjectID</relatedStateVariable></argument>
+<argument><name>BrowseFlag</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_BrowseFlag</relatedStateVariable></argument>
+<argument><name>Filter</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Filter</relatedStateVariable></argument>
+<argument><name>StartingIndex</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Index</relatedStateVariable></argument>
+<argument><name>RequestedCount</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+<argument><name>SortCriteria</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_SortCriteria</relatedStateVariable></argument>
+<argument><name>Result</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Result</relatedStateVariable></argument>
+<argument><name>NumberReturned</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+<argument><name>TotalMatches</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+<argument><name>UpdateID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_UpdateID</relatedStateVariable></argument>
+</argumentList></action>
+<action><name>GetSearchCapabilities</name><argumentList>
+<argument><name>SearchCaps</name><direction>out</direction><relatedStateVariable>SearchCapabilities</relatedStateVariable></argument>
+</argumentList></action>
+<action><name>GetSortCapabilities</name><argumentList>
+<argument><name>SortCaps</name><direction>out</direction><relatedStateVariable>SortCapabilities</relatedStateVariable></argument>
+</argumentList></action>
+<action><name>GetSystemUpdateID</name><argumentList>
+<argument><name>Id</name><direction>out</direction><relatedStateVariable>SystemUpdateID</relatedStateVariable></argument>
+</argumentList></action>
+</actionList>
+<serviceStateTable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_ObjectID</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_BrowseFlag</name><dataType>string</dataType><allowedValueList><allowedValue>BrowseMetadata</allowedValue><allowedValue>BrowseDirectChildren</allowedValue></allowedValueList></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_Filter</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_Index</name><dataType>ui4</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_Count</name><dataType>ui4</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_SortCriteria</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_Result</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_UpdateID</name><dataType>ui4</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>SearchCapabilities</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>SortCapabilities</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="yes"><name>SystemUpdateID</name><dataType>ui4</dataType></stateVariable>
+</serviceStateTable>
+</scpd>
+"#;
+
+/// Description of the connection manager service.
+const CONNECTION_MANAGER_SCPD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+<specVersion><major>1</major><minor>0</minor></specVersion>
+<actionList>
+<action><name>GetProtocolInfo</name><argumentList>
+<argument><name>Source</name><direction>out</direction><relatedStateVariable>SourceProtocolInfo</relatedStateVariable></argument>
+<argument><name>Sink</name><direction>out</direction><relatedStateVariable>SinkProtocolInfo</relatedStateVariable></argument>
+</argumentList></action>
+<action><name>GetCurrentConnectionIDs</name><argumentList>
+<argument><name>ConnectionIDs</name><direction>out</direction><relatedStateVariable>CurrentConnectionIDs</relatedStateVariable></argument>
+</argumentList></action>
+<action><name>GetCurrentConnectionInfo</name><argumentList>
+<argument><name>ConnectionID</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_ConnectionID</relatedStateVariable></argument>
+<argument><name>RcsID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_RcsID</relatedStateVariable></argument>
+<argument><name>AVTransportID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_AVTransportID</relatedStateVariable></argument>
+<argument><name>ProtocolInfo</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ProtocolInfo</relatedStateVariable></argument>
+<argument><name>PeerConnectionManager</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ConnectionManager</relatedStateVariable></argument>
+<argument><name>PeerConnectionID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ConnectionID</relatedStateVariable></argument>
+<argument><name>Direction</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Direction</relatedStateVariable></argument>
+<argument><name>Status</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ConnectionStatus</relatedStateVariable></argument>
+</argumentList></action>
+</actionList>
+<serviceStateTable>
+<stateVariable sendEvents="yes"><name>SourceProtocolInfo</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="yes"><name>SinkProtocolInfo</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="yes"><name>CurrentConnectionIDs</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_ConnectionStatus</name><dataType>string</dataType><allowedValueList><allowedValue>OK</allowedValue><allowedValue>ContentFormatMismatch</allowedValue><allowedValue>InsufficientBandwidth</allowedValue><allowedValue>UnreliableChannel</allowedValue><allowedValue>Unknown</allowedValue></allowedValueList></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_ConnectionManager</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_Direction</name><dataType>string</dataType><allowedValueList><allowedValue>Input</allowedValue><allowedValue>Output</allowedValue></allowedValueList></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_ProtocolInfo</name><dataType>string</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_ConnectionID</name><dataType>i4</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_AVTransportID</name><dataType>i4</dataType></stateVariable>
+<stateVariable sendEvents="no"><name>A_ARG_TYPE_RcsID</name><dataType>i4</dataType></stateVariable>
+</serviceStateTable>
+</scpd>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soap_arg() {
+        let body = r#"<?xml version="1.0"?><s:Envelope><s:Body><u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1"><ObjectIDs>no</ObjectIDs><ObjectID xmlns:dt="urn:schemas-microsoft-com:datatypes" dt:dt="string">artist/Simon &amp; Garfunkel</ObjectID><BrowseFlag>BrowseDirectChildren</BrowseFlag><Filter/><StartingIndex>0</StartingIndex></u:Browse></s:Body></s:Envelope>"#;
+        assert_eq!(
+            soap_arg(body, "ObjectID").as_deref(),
+            Some("artist/Simon & Garfunkel")
+        );
+        assert_eq!(
+            soap_arg(body, "BrowseFlag").as_deref(),
+            Some("BrowseDirectChildren")
+        );
+        assert_eq!(soap_arg(body, "Filter").as_deref(), Some(""));
+        assert_eq!(soap_arg(body, "RequestedCount"), None);
+    }
+
+    #[test]
+    fn test_soap_action() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "soapaction",
+            HeaderValue::from_static(r#""urn:schemas-upnp-org:service:ContentDirectory:1#Browse""#),
+        );
+        assert_eq!(soap_action(&headers), "Browse");
+        assert_eq!(soap_action(&HeaderMap::new()), "");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("Rock"), "Rock");
+        assert_eq!(
+            escape(r#"<"Tom" & 'Jerry'>"#),
+            "&lt;&quot;Tom&quot; &amp; &apos;Jerry&apos;&gt;"
+        );
+        assert_eq!(unescape(&escape("a &lt; b & c")), "a &lt; b & c");
+    }
+
+    #[test]
+    fn test_device_uuid() {
+        let config = Config::default();
+        assert_eq!(device_uuid(&config), device_uuid(&config));
+        let mut renamed = config.clone();
+        renamed.dlna.name = "Kitchen".to_string();
+        assert_ne!(device_uuid(&config), device_uuid(&renamed));
+    }
+
+    #[test]
+    fn test_protocol_info() {
+        let info = protocol_info();
+        assert!(info.starts_with("http-get:*:audio/mpeg:*,http-get:*:audio/flac:*"));
+        assert_eq!(info.matches("audio/ogg").count(), 1);
+    }
+}
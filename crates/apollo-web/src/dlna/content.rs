@@ -0,0 +1,655 @@
+//! The tree of objects that DLNA players browse.
+//!
+//! The root holds four containers: albums, album artists, playlists, and
+//! all tracks. Object IDs name what they stand for, such as `album/<uuid>`
+//! or `artist/<name>`, so browsing needs no state beyond the library.
+//! Listings are written as [DIDL-Lite](http://www.upnp.org/schemas/av/didl-lite.xsd),
+//! with links to the stream endpoint of the web API.
+
+use super::{STREAM_FEATURES, escape};
+use crate::error::ApiError;
+use apollo_core::library::Library;
+use apollo_core::metadata::{Album, AlbumId, Track, TrackId};
+use apollo_core::playlist::{Playlist, PlaylistId};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// Start of every DIDL-Lite document.
+const DIDL_START: &str = r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">"#;
+
+/// End of every DIDL-Lite document.
+const DIDL_END: &str = "</DIDL-Lite>";
+
+/// What to browse: an object itself, or its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowseFlag {
+    /// The object itself.
+    Metadata,
+    /// The children of a container.
+    Children,
+}
+
+impl BrowseFlag {
+    /// Parse a flag as players send it.
+    pub fn parse(flag: &str) -> Option<Self> {
+        match flag {
+            "BrowseMetadata" => Some(Self::Metadata),
+            "BrowseDirectChildren" => Some(Self::Children),
+            _ => None,
+        }
+    }
+}
+
+/// A page of browsed objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    /// The objects as a DIDL-Lite document.
+    pub didl: String,
+    /// Number of objects on the page.
+    pub returned: usize,
+    /// Number of objects on all pages.
+    pub total: usize,
+}
+
+/// An object in the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Object {
+    Root,
+    Albums,
+    Artists,
+    Playlists,
+    Tracks,
+    Album(AlbumId),
+    Artist(String),
+    Playlist(PlaylistId),
+    Track(TrackId),
+}
+
+impl Object {
+    /// Parse an object ID.
+    fn parse(id: &str) -> Option<Self> {
+        let uuid = |id: &str| Uuid::parse_str(id).ok();
+        Some(match id.split_once('/') {
+            None => match id {
+                "0" => Self::Root,
+                "albums" => Self::Albums,
+                "artists" => Self::Artists,
+                "playlists" => Self::Playlists,
+                "tracks" => Self::Tracks,
+                _ => return None,
+            },
+            Some(("album", id)) => Self::Album(AlbumId(uuid(id)?)),
+            Some(("artist", name)) if !name.is_empty() => Self::Artist(name.to_string()),
+            Some(("playlist", id)) => Self::Playlist(PlaylistId(uuid(id)?)),
+            Some(("track", id)) => Self::Track(TrackId(uuid(id)?)),
+            Some(_) => return None,
+        })
+    }
+
+    /// Get the object ID.
+    fn id(&self) -> String {
+        match self {
+            Self::Root => "0".to_string(),
+            Self::Albums => "albums".to_string(),
+            Self::Artists => "artists".to_string(),
+            Self::Playlists => "playlists".to_string(),
+            Self::Tracks => "tracks".to_string(),
+            Self::Album(id) => format!("album/{id}"),
+            Self::Artist(name) => format!("artist/{name}"),
+            Self::Playlist(id) => format!("playlist/{id}"),
+            Self::Track(id) => format!("track/{id}"),
+        }
+    }
+}
+
+/// A container, as listed in DIDL-Lite.
+struct Container {
+    object: Object,
+    /// ID of the parent, which is `-1` for the root.
+    parent: String,
+    title: String,
+    class: &'static str,
+    child_count: Option<usize>,
+    artist: Option<String>,
+}
+
+/// Writes DIDL-Lite for objects, with links under a base URL.
+struct Didl<'a> {
+    base: &'a str,
+    out: String,
+    count: usize,
+}
+
+impl<'a> Didl<'a> {
+    fn new(base: &'a str) -> Self {
+        Self {
+            base,
+            out: DIDL_START.to_string(),
+            count: 0,
+        }
+    }
+
+    fn container(&mut self, container: &Container) {
+        let id = container.object.id();
+        let _ = write!(
+            self.out,
+            r#"<container id="{}" parentID="{}" restricted="1" searchable="0""#,
+            escape(&id),
+            escape(&container.parent),
+        );
+        if let Some(count) = container.child_count {
+            let _ = write!(self.out, r#" childCount="{count}""#);
+        }
+        let _ = write!(
+            self.out,
+            "><dc:title>{}</dc:title><upnp:class>{}</upnp:class>",
+            escape(&container.title),
+            container.class,
+        );
+        if let Some(artist) = &container.artist {
+            let _ = write!(
+                self.out,
+                "<upnp:artist>{0}</upnp:artist><dc:creator>{0}</dc:creator>",
+                escape(artist)
+            );
+        }
+        if let Object::Album(album_id) = &container.object {
+            let _ = write!(
+                self.out,
+                "<upnp:albumArtURI>{}/dlna/art/{album_id}</upnp:albumArtURI>",
+                self.base
+            );
+        }
+        self.out.push_str("</container>");
+        self.count += 1;
+    }
+
+    fn item(&mut self, track: &Track, parent: &Object) {
+        let _ = write!(
+            self.out,
+            r#"<item id="track/{}" parentID="{}" restricted="1"><dc:title>{}</dc:title><upnp:class>object.item.audioItem.musicTrack</upnp:class>"#,
+            track.id,
+            escape(&parent.id()),
+            escape(&track.title),
+        );
+        let _ = write!(
+            self.out,
+            "<upnp:artist>{0}</upnp:artist><dc:creator>{0}</dc:creator>",
+            escape(&track.artist)
+        );
+        if let Some(album) = &track.album_title {
+            let _ = write!(self.out, "<upnp:album>{}</upnp:album>", escape(album));
+        }
+        if let Some(album_artist) = &track.album_artist {
+            let _ = write!(
+                self.out,
+                r#"<upnp:artist role="AlbumArtist">{}</upnp:artist>"#,
+                escape(album_artist)
+            );
+        }
+        for genre in &track.genres {
+            let _ = write!(self.out, "<upnp:genre>{}</upnp:genre>", escape(genre));
+        }
+        if let Some(number) = track.track_number {
+            let _ = write!(
+                self.out,
+                "<upnp:originalTrackNumber>{number}</upnp:originalTrackNumber>"
+            );
+        }
+        if let Some(year) = track.year {
+            let _ = write!(self.out, "<dc:date>{year:04}-01-01</dc:date>");
+        }
+        if let Some(album_id) = &track.album_id {
+            let _ = write!(
+                self.out,
+                "<upnp:albumArtURI>{}/dlna/art/{album_id}</upnp:albumArtURI>",
+                self.base
+            );
+        }
+
+        let mime = track.format.mime_type();
+        let _ = write!(
+            self.out,
+            r#"<res protocolInfo="http-get:*:{mime}:{STREAM_FEATURES}" duration="{}""#,
+            format_duration(track.duration)
+        );
+        if let Ok(meta) = std::fs::metadata(&track.path) {
+            let _ = write!(self.out, r#" size="{}""#, meta.len());
+        }
+        if let Some(rate) = track.sample_rate {
+            let _ = write!(self.out, r#" sampleFrequency="{rate}""#);
+        }
+        if let Some(channels) = track.channels {
+            let _ = write!(self.out, r#" nrAudioChannels="{channels}""#);
+        }
+        let _ = write!(
+            self.out,
+            ">{}/api/tracks/{}/stream</res></item>",
+            self.base, track.id
+        );
+        self.count += 1;
+    }
+
+    fn finish(mut self, total: usize) -> Page {
+        self.out.push_str(DIDL_END);
+        Page {
+            didl: self.out,
+            returned: self.count,
+            total,
+        }
+    }
+}
+
+/// Browse an object, or a page of its children.
+///
+/// A `count` of 0 asks for all children from `start`. Links point under
+/// `base`, the address the player reached the server at.
+///
+/// # Errors
+///
+/// Returns [`ApiError::NotFound`] if there is no such object or it has no
+/// children, and an error if the library cannot be read.
+pub async fn browse(
+    db: &dyn Library,
+    base: &str,
+    object_id: &str,
+    flag: BrowseFlag,
+    start: usize,
+    count: usize,
+) -> Result<Page, ApiError> {
+    let object = Object::parse(object_id)
+        .ok_or_else(|| ApiError::NotFound(format!("No such object: {object_id}")))?;
+    let mut didl = Didl::new(base);
+
+    if flag == BrowseFlag::Metadata {
+        match &object {
+            Object::Track(id) => {
+                let track = db
+                    .get_track(id)
+                    .await?
+                    .ok_or_else(|| ApiError::NotFound(format!("No such object: {object_id}")))?;
+                let parent = track.album_id.clone().map_or(Object::Tracks, Object::Album);
+                didl.item(&track, &parent);
+            }
+            _ => didl.container(&describe(db, object).await?),
+        }
+        return Ok(didl.finish(1));
+    }
+    let total = children(db, &mut didl, object, start, count).await?;
+    Ok(didl.finish(total))
+}
+
+/// Add a page of the children of a container, and count all of them.
+async fn children(
+    db: &dyn Library,
+    didl: &mut Didl<'_>,
+    object: Object,
+    start: usize,
+    count: usize,
+) -> Result<usize, ApiError> {
+    let range = |total: usize| {
+        let end = if count == 0 {
+            total
+        } else {
+            start.saturating_add(count).min(total)
+        };
+        start.min(end)..end
+    };
+    let total = match object {
+        Object::Root => {
+            let containers = [
+                Object::Albums,
+                Object::Artists,
+                Object::Playlists,
+                Object::Tracks,
+            ];
+            for object in &containers[range(containers.len())] {
+                didl.container(&describe(db, object.clone()).await?);
+            }
+            containers.len()
+        }
+        Object::Albums => {
+            let albums = sorted_albums(db).await?;
+            for album in &albums[range(albums.len())] {
+                didl.container(&album_container(album, &Object::Albums));
+            }
+            albums.len()
+        }
+        Object::Artists => {
+            let artists = album_artists(&sorted_albums(db).await?);
+            let names: Vec<&String> = artists.keys().collect();
+            for name in &names[range(names.len())] {
+                didl.container(&artist_container(name, artists[*name]));
+            }
+            names.len()
+        }
+        Object::Artist(ref name) => {
+            let albums: Vec<Album> = sorted_albums(db)
+                .await?
+                .into_iter()
+                .filter(|album| &album.artist == name)
+                .collect();
+            for album in &albums[range(albums.len())] {
+                didl.container(&album_container(album, &object));
+            }
+            albums.len()
+        }
+        Object::Playlists => {
+            let playlists = db.list_playlists().await?;
+            for playlist in &playlists[range(playlists.len())] {
+                didl.container(&playlist_container(playlist));
+            }
+            playlists.len()
+        }
+        Object::Tracks => {
+            let total = usize::try_from(db.count_tracks().await?).unwrap_or(usize::MAX);
+            let range = range(total);
+            let limit = u32::try_from(range.len()).unwrap_or(u32::MAX);
+            let offset = u32::try_from(range.start).unwrap_or(u32::MAX);
+            for track in db.list_tracks(limit, offset).await? {
+                didl.item(&track, &object);
+            }
+            total
+        }
+        Object::Album(ref id) => {
+            let tracks = db.get_album_tracks(id).await?;
+            for track in &tracks[range(tracks.len())] {
+                didl.item(track, &object);
+            }
+            tracks.len()
+        }
+        Object::Playlist(ref id) => {
+            let tracks = db.get_playlist_tracks(id).await?;
+            for track in &tracks[range(tracks.len())] {
+                didl.item(track, &object);
+            }
+            tracks.len()
+        }
+        Object::Track(_) => {
+            return Err(ApiError::NotFound(format!(
+                "Not a container: {}",
+                object.id()
+            )));
+        }
+    };
+    Ok(total)
+}
+
+/// Describe a container.
+async fn describe(db: &dyn Library, object: Object) -> Result<Container, ApiError> {
+    let not_found = || ApiError::NotFound(format!("No such object: {}", object.id()));
+    let top = |object: Object, title: &str, class: &'static str, count: usize| Container {
+        object,
+        parent: Object::Root.id(),
+        title: title.to_string(),
+        class,
+        child_count: Some(count),
+        artist: None,
+    };
+    Ok(match &object {
+        Object::Root => Container {
+            object: Object::Root,
+            parent: "-1".to_string(),
+            title: "Apollo".to_string(),
+            class: "object.container.storageFolder",
+            child_count: Some(4),
+            artist: None,
+        },
+        Object::Albums => {
+            let count = usize::try_from(db.count_albums().await?).unwrap_or(usize::MAX);
+            top(object, "Albums", "object.container.storageFolder", count)
+        }
+        Object::Artists => {
+            let count = album_artists(&sorted_albums(db).await?).len();
+            top(object, "Artists", "object.container.storageFolder", count)
+        }
+        Object::Playlists => {
+            let count = db.list_playlists().await?.len();
+            top(object, "Playlists", "object.container.storageFolder", count)
+        }
+        Object::Tracks => {
+            let count = usize::try_from(db.count_tracks().await?).unwrap_or(usize::MAX);
+            top(
+                object,
+                "All Tracks",
+                "object.container.storageFolder",
+                count,
+            )
+        }
+        Object::Album(id) => {
+            let album = db.get_album(id).await?.ok_or_else(not_found)?;
+            album_container(&album, &Object::Albums)
+        }
+        Object::Artist(name) => {
+            let artists = album_artists(&sorted_albums(db).await?);
+            let count = *artists.get(name).ok_or_else(not_found)?;
+            artist_container(name, count)
+        }
+        Object::Playlist(id) => {
+            let playlist = db.get_playlist(id).await?.ok_or_else(not_found)?;
+            playlist_container(&playlist)
+        }
+        Object::Track(_) => return Err(not_found()),
+    })
+}
+
+/// List the albums by artist and title.
+async fn sorted_albums(db: &dyn Library) -> Result<Vec<Album>, ApiError> {
+    let mut albums = db.list_albums(u32::MAX, 0).await?;
+    albums.sort_by_cached_key(|album| (album.artist.to_lowercase(), album.title.to_lowercase()));
+    Ok(albums)
+}
+
+/// Count the albums of each album artist.
+fn album_artists(albums: &[Album]) -> BTreeMap<String, usize> {
+    let mut artists = BTreeMap::new();
+    for album in albums {
+        *artists.entry(album.artist.clone()).or_default() += 1;
+    }
+    artists
+}
+
+/// Describe an album, listed under `parent`.
+fn album_container(album: &Album, parent: &Object) -> Container {
+    Container {
+        object: Object::Album(album.id.clone()),
+        parent: parent.id(),
+        title: album.title.clone(),
+        class: "object.container.album.musicAlbum",
+        child_count: Some(album.track_count as usize),
+        artist: Some(album.artist.clone()),
+    }
+}
+
+/// Describe an album artist.
+fn artist_container(name: &str, album_count: usize) -> Container {
+    Container {
+        object: Object::Artist(name.to_string()),
+        parent: Object::Artists.id(),
+        title: name.to_string(),
+        class: "object.container.person.musicArtist",
+        child_count: Some(album_count),
+        artist: None,
+    }
+}
+
+/// Describe a playlist.
+fn playlist_container(playlist: &Playlist) -> Container {
+    Container {
+        object: Object::Playlist(playlist.id.clone()),
+        parent: Object::Playlists.id(),
+        title: playlist.name.clone(),
+        class: "object.container.playlistContainer",
+        // Smart playlists would have to be evaluated to count their tracks
+        child_count: (!playlist.is_smart()).then_some(playlist.track_ids.len()),
+        artist: None,
+    }
+}
+
+/// Format a duration as DIDL-Lite wants it, such as `0:03:25.000`.
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        duration.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::metadata::AudioFormat;
+    use apollo_db::SqliteLibrary;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    const BASE: &str = "http://192.168.1.10:8337";
+
+    async fn library() -> (SqliteLibrary, Album) {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut album = Album::new("Abbey Road".to_string(), "The Beatles".to_string());
+        album.track_count = 2;
+        db.add_album(&album).await.unwrap();
+        for (number, title) in [(1, "Come Together"), (2, "Something")] {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/{number}.flac")),
+                title.to_string(),
+                "The Beatles".to_string(),
+                Duration::from_secs(200),
+            );
+            track.album_id = Some(album.id.clone());
+            track.album_title = Some(album.title.clone());
+            track.track_number = Some(number);
+            track.format = AudioFormat::Flac;
+            db.add_track(&track).await.unwrap();
+        }
+        let mut other = Album::new("Blue & Lonesome".to_string(), "Rolling Stones".to_string());
+        other.track_count = 0;
+        db.add_album(&other).await.unwrap();
+        (db, album)
+    }
+
+    #[test]
+    fn test_object_ids() {
+        for id in [
+            "0",
+            "albums",
+            "artist/AC/DC",
+            "track/550e8400-e29b-41d4-a716-446655440000",
+        ] {
+            assert_eq!(Object::parse(id).unwrap().id(), id);
+        }
+        assert_eq!(
+            Object::parse("artist/AC/DC"),
+            Some(Object::Artist("AC/DC".to_string()))
+        );
+        assert!(Object::parse("album/nope").is_none());
+        assert!(Object::parse("artist/").is_none());
+        assert!(Object::parse("genres").is_none());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(
+            format_duration(Duration::from_millis(205_250)),
+            "0:03:25.250"
+        );
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05.000");
+    }
+
+    #[tokio::test]
+    async fn test_browse_root() {
+        let (db, _) = library().await;
+        let page = browse(&db, BASE, "0", BrowseFlag::Children, 0, 0)
+            .await
+            .unwrap();
+        assert_eq!(page.returned, 4);
+        assert_eq!(page.total, 4);
+        assert!(page.didl.starts_with("<DIDL-Lite"));
+        assert!(page.didl.contains(
+            r#"<container id="albums" parentID="0" restricted="1" searchable="0" childCount="2">"#
+        ));
+        assert!(page.didl.contains(
+            r#"<container id="tracks" parentID="0" restricted="1" searchable="0" childCount="2">"#
+        ));
+
+        let page = browse(&db, BASE, "0", BrowseFlag::Metadata, 0, 0)
+            .await
+            .unwrap();
+        assert_eq!(page.returned, 1);
+        assert!(page.didl.contains(r#"id="0" parentID="-1""#));
+    }
+
+    #[tokio::test]
+    async fn test_browse_albums_and_artists() {
+        let (db, album) = library().await;
+        let page = browse(&db, BASE, "albums", BrowseFlag::Children, 0, 1)
+            .await
+            .unwrap();
+        assert_eq!(page.returned, 1);
+        assert_eq!(page.total, 2);
+        assert!(
+            page.didl
+                .contains("<dc:title>Blue &amp; Lonesome</dc:title>")
+        );
+
+        let page = browse(&db, BASE, "artists", BrowseFlag::Children, 0, 0)
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert!(
+            page.didl
+                .contains(r#"<container id="artist/The Beatles" parentID="artists""#)
+        );
+
+        let page = browse(&db, BASE, "artist/The Beatles", BrowseFlag::Children, 0, 0)
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert!(page.didl.contains(&format!(
+            r#"<container id="album/{}" parentID="artist/The Beatles""#,
+            album.id
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_browse_album_tracks() {
+        let (db, album) = library().await;
+        let id = format!("album/{}", album.id);
+        let page = browse(&db, BASE, &id, BrowseFlag::Children, 0, 0)
+            .await
+            .unwrap();
+        assert_eq!(page.returned, 2);
+        assert!(page.didl.contains("<dc:title>Come Together</dc:title>"));
+        assert!(
+            page.didl
+                .contains("<upnp:class>object.item.audioItem.musicTrack</upnp:class>")
+        );
+        assert!(
+            page.didl
+                .contains(r#"protocolInfo="http-get:*:audio/flac:"#)
+        );
+        assert!(page.didl.contains(r#"duration="0:03:20.000""#));
+        assert!(page.didl.contains(&format!("{BASE}/dlna/art/{}", album.id)));
+
+        let tracks = db.get_album_tracks(&album.id).await.unwrap();
+        let track_id = format!("track/{}", tracks[0].id);
+        let page = browse(&db, BASE, &track_id, BrowseFlag::Metadata, 0, 0)
+            .await
+            .unwrap();
+        assert!(page.didl.contains(&format!(r#"parentID="{id}""#)));
+        assert!(
+            page.didl
+                .contains(&format!("{BASE}/api/tracks/{}/stream</res>", tracks[0].id))
+        );
+
+        let result = browse(&db, BASE, &track_id, BrowseFlag::Children, 0, 0).await;
+        assert!(matches!(result, Err(ApiError::NotFound(_))));
+        let result = browse(&db, BASE, "nothing", BrowseFlag::Metadata, 0, 0).await;
+        assert!(matches!(result, Err(ApiError::NotFound(_))));
+    }
+}
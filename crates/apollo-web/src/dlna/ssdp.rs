@@ -0,0 +1,273 @@
+//! Discovery of the media server with SSDP.
+//!
+//! Players find media servers by multicasting an `M-SEARCH` request on the
+//! local network, and learn about new ones from `NOTIFY` announcements. An
+//! [`SsdpServer`] answers the searches that match the server, announces it
+//! when it starts and again before earlier announcements expire, and says
+//! goodbye when it stops. Every message points at the device description,
+//! which is served by the web server.
+
+use super::{CONNECTION_MANAGER, CONTENT_DIRECTORY, MEDIA_SERVER};
+use crate::error::ApiError;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket as StdUdpSocket};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Multicast group of SSDP.
+const SSDP_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+
+/// Port of SSDP.
+const SSDP_PORT: u16 = 1900;
+
+/// Seconds that players may remember an announcement.
+const MAX_AGE_SECS: u64 = 1800;
+
+/// Product token sent with every message.
+const SERVER: &str = concat!("Linux/1.0 UPnP/1.0 Apollo/", env!("CARGO_PKG_VERSION"));
+
+/// Announces the media server on the local network.
+#[derive(Debug, Clone, Copy)]
+pub struct SsdpServer {
+    uuid: Uuid,
+    port: u16,
+}
+
+impl SsdpServer {
+    /// Create a server for the device `uuid`, whose description is served
+    /// on `port`.
+    #[must_use]
+    pub const fn new(uuid: Uuid, port: u16) -> Self {
+        Self { uuid, port }
+    }
+
+    /// Answer searches and announce the server until the task is cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SSDP port cannot be opened.
+    pub async fn run(self) -> Result<(), ApiError> {
+        let socket = multicast_socket()
+            .map_err(|e| ApiError::Internal(format!("Failed to open SSDP port: {e}")))?;
+        info!("Announcing DLNA media server uuid:{}", self.uuid);
+
+        // Announcements are repeated well before they expire
+        let mut ticker = tokio::time::interval(Duration::from_secs(MAX_AGE_SECS / 3));
+        let mut buf = [0u8; 2048];
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => self.notify(&socket, "ssdp:alive").await,
+                received = socket.recv_from(&mut buf) => match received {
+                    Ok((len, from)) => {
+                        let message = String::from_utf8_lossy(&buf[..len]);
+                        if let Some(target) = search_target(&message) {
+                            self.answer(&socket, &target, from).await;
+                        }
+                    }
+                    Err(e) => warn!("SSDP receive error: {e}"),
+                },
+            }
+        }
+    }
+
+    /// Tell players that the server is going away.
+    pub async fn bye(&self) {
+        match multicast_socket() {
+            Ok(socket) => self.notify(&socket, "ssdp:byebye").await,
+            Err(e) => debug!("Failed to say goodbye over SSDP: {e}"),
+        }
+    }
+
+    /// Answer a search with each matching notification type.
+    async fn answer(&self, socket: &UdpSocket, target: &str, to: SocketAddr) {
+        let Some(ip) = local_ip(to) else {
+            return;
+        };
+        for (nt, usn) in matching_targets(self.uuid, target) {
+            let response = search_response(&nt, &usn, &self.location(ip));
+            if let Err(e) = socket.send_to(response.as_bytes(), to).await {
+                debug!("Failed to answer SSDP search from {to}: {e}");
+            }
+        }
+    }
+
+    /// Send a notification of each type.
+    async fn notify(&self, socket: &UdpSocket, nts: &str) {
+        let group = SocketAddr::V4(SocketAddrV4::new(SSDP_GROUP, SSDP_PORT));
+        let Some(ip) = local_ip(group) else {
+            debug!("No network to announce the media server on");
+            return;
+        };
+        for (nt, usn) in notification_types(self.uuid) {
+            let message = notify_message(&nt, &usn, nts, &self.location(ip));
+            if let Err(e) = socket.send_to(message.as_bytes(), group).await {
+                debug!("Failed to send SSDP notification: {e}");
+            }
+        }
+    }
+
+    /// Get the URL of the device description, at an address of this host.
+    fn location(&self, ip: IpAddr) -> String {
+        format!(
+            "http://{}/dlna/description.xml",
+            SocketAddr::new(ip, self.port)
+        )
+    }
+}
+
+/// Open a socket on the SSDP port that receives the multicast group.
+///
+/// Other programs on the host may use SSDP as well, so the port is shared.
+fn multicast_socket() -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, SSDP_PORT)).into())?;
+    socket.join_multicast_v4(&SSDP_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_ttl_v4(2)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Get the address of this host that reaches `peer`.
+fn local_ip(peer: SocketAddr) -> Option<IpAddr> {
+    // Connecting a UDP socket sends nothing, but picks the route
+    let socket = StdUdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect(peer).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Get the search target of an `M-SEARCH` request.
+fn search_target(message: &str) -> Option<String> {
+    let mut lines = message.lines();
+    if !lines.next()?.starts_with("M-SEARCH ") {
+        return None;
+    }
+    let mut target = None;
+    let mut discover = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_uppercase().as_str() {
+            "ST" => target = Some(value.to_string()),
+            "MAN" => discover = value.trim_matches('"') == "ssdp:discover",
+            _ => {}
+        }
+    }
+    target.filter(|_| discover)
+}
+
+/// Get the notification types of the server, with their unique service
+/// names.
+fn notification_types(uuid: Uuid) -> Vec<(String, String)> {
+    let device = format!("uuid:{uuid}");
+    let mut types = vec![
+        (
+            "upnp:rootdevice".to_string(),
+            format!("{device}::upnp:rootdevice"),
+        ),
+        (device.clone(), device.clone()),
+    ];
+    for urn in [MEDIA_SERVER, CONTENT_DIRECTORY, CONNECTION_MANAGER] {
+        types.push((urn.to_string(), format!("{device}::{urn}")));
+    }
+    types
+}
+
+/// Get the notification types that answer a search target.
+fn matching_targets(uuid: Uuid, target: &str) -> Vec<(String, String)> {
+    notification_types(uuid)
+        .into_iter()
+        .filter(|(nt, _)| target == "ssdp:all" || nt.eq_ignore_ascii_case(target))
+        .collect()
+}
+
+/// Write the answer to a search.
+fn search_response(st: &str, usn: &str, location: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\n\
+         CACHE-CONTROL: max-age={MAX_AGE_SECS}\r\n\
+         EXT:\r\n\
+         LOCATION: {location}\r\n\
+         SERVER: {SERVER}\r\n\
+         ST: {st}\r\n\
+         USN: {usn}\r\n\
+         Content-Length: 0\r\n\r\n"
+    )
+}
+
+/// Write a notification, which is `ssdp:alive` or `ssdp:byebye`.
+fn notify_message(nt: &str, usn: &str, nts: &str, location: &str) -> String {
+    format!(
+        "NOTIFY * HTTP/1.1\r\n\
+         HOST: {SSDP_GROUP}:{SSDP_PORT}\r\n\
+         CACHE-CONTROL: max-age={MAX_AGE_SECS}\r\n\
+         LOCATION: {location}\r\n\
+         NT: {nt}\r\n\
+         NTS: {nts}\r\n\
+         SERVER: {SERVER}\r\n\
+         USN: {usn}\r\n\
+         Content-Length: 0\r\n\r\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: Uuid = Uuid::from_u128(0x1234);
+
+    #[test]
+    fn test_search_target() {
+        let search = "M-SEARCH * HTTP/1.1\r\n\
+                      HOST: 239.255.255.250:1900\r\n\
+                      MAN: \"ssdp:discover\"\r\n\
+                      MX: 2\r\n\
+                      ST: urn:schemas-upnp-org:device:MediaServer:1\r\n\r\n";
+        assert_eq!(
+            search_target(search).as_deref(),
+            Some("urn:schemas-upnp-org:device:MediaServer:1")
+        );
+
+        let without_man = "M-SEARCH * HTTP/1.1\r\nST: ssdp:all\r\n\r\n";
+        assert_eq!(search_target(without_man), None);
+        let notify = "NOTIFY * HTTP/1.1\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\n\r\n";
+        assert_eq!(search_target(notify), None);
+    }
+
+    #[test]
+    fn test_matching_targets() {
+        assert_eq!(matching_targets(UUID, "ssdp:all").len(), 5);
+        assert!(matching_targets(UUID, "urn:schemas-upnp-org:device:MediaRenderer:1").is_empty());
+
+        let device = format!("uuid:{UUID}");
+        assert_eq!(
+            matching_targets(UUID, &device),
+            vec![(device.clone(), device.clone())]
+        );
+        assert_eq!(
+            matching_targets(UUID, "upnp:rootdevice"),
+            vec![(
+                "upnp:rootdevice".to_string(),
+                format!("{device}::upnp:rootdevice")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_messages() {
+        let location = "http://192.168.1.10:8337/dlna/description.xml";
+        let response = search_response(MEDIA_SERVER, "uuid:x::urn", location);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(&format!("\r\nST: {MEDIA_SERVER}\r\n")));
+        assert!(response.contains(&format!("\r\nLOCATION: {location}\r\n")));
+        assert!(response.ends_with("\r\n\r\n"));
+
+        let notify = notify_message("upnp:rootdevice", "uuid:x", "ssdp:byebye", location);
+        assert!(notify.starts_with("NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\n"));
+        assert!(notify.contains("\r\nNTS: ssdp:byebye\r\n"));
+    }
+}
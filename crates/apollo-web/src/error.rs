@@ -14,10 +14,12 @@ pub enum ApiError {
     NotFound(String),
     /// Invalid request.
     BadRequest(String),
+    /// Resource already exists.
+    Conflict(String),
     /// Internal server error.
     Internal(String),
-    /// Database error.
-    Database(apollo_db::DbError),
+    /// Library storage error.
+    Library(apollo_core::Error),
 }
 
 /// Error response body.
@@ -25,6 +27,8 @@ pub enum ApiError {
 struct ErrorResponse {
     error: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 impl IntoResponse for ApiError {
@@ -32,9 +36,10 @@ impl IntoResponse for ApiError {
         let (status, error_type, message) = match self {
             Self::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg),
             Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, "bad_request", msg),
+            Self::Conflict(msg) => (StatusCode::CONFLICT, "conflict", msg),
             Self::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", msg),
-            Self::Database(err) => {
-                tracing::error!("Database error: {err}");
+            Self::Library(err) => {
+                tracing::error!("Library error: {err}");
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     "database_error",
@@ -46,17 +51,23 @@ impl IntoResponse for ApiError {
         let body = ErrorResponse {
             error: error_type.to_string(),
             message,
+            request_id: crate::access_log::current_request_id(),
         };
 
         (status, Json(body)).into_response()
     }
 }
 
-impl From<apollo_db::DbError> for ApiError {
-    fn from(err: apollo_db::DbError) -> Self {
-        match &err {
-            apollo_db::DbError::NotFound(resource) => Self::NotFound(resource.clone()),
-            _ => Self::Database(err),
+impl From<apollo_core::Error> for ApiError {
+    fn from(err: apollo_core::Error) -> Self {
+        use apollo_core::Error;
+
+        match err {
+            Error::NotFound(resource)
+            | Error::TrackNotFound(resource)
+            | Error::AlbumNotFound(resource) => Self::NotFound(resource),
+            Error::InvalidQuery(_) | Error::Validation(_) => Self::BadRequest(err.to_string()),
+            _ => Self::Library(err),
         }
     }
 }
@@ -0,0 +1,320 @@
+//! Keeping playlist files up to date.
+//!
+//! Each playlist under `playlists.exports` is written as an `.m3u8` file
+//! when the web server starts, and again whenever a
+//! [`LibraryEvent`](apollo_core::LibraryEvent) says its tracks may have
+//! changed. Hardware players and other apps that read the music directory
+//! then always see the current tracks of smart playlists, without anyone
+//! exporting them by hand.
+//!
+//! Events are collected for a moment before the files are written, so an
+//! import of many tracks rewrites each file once. Files whose contents did
+//! not change are left alone.
+
+use crate::error::ApiError;
+use apollo_core::Config;
+use apollo_core::event::EventReceiver;
+use apollo_core::library::Library;
+use apollo_core::playlist::{PlaylistFormat, write_playlist};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+/// Time to wait after a change for more changes before writing the files.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// A playlist and the file it is written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportTarget {
+    /// Name of the playlist.
+    pub playlist: String,
+    /// Path of the playlist file.
+    pub path: PathBuf,
+    /// Write track paths relative to the file.
+    pub relative_paths: bool,
+}
+
+/// Writes configured playlists to files when they change.
+pub struct PlaylistExporter {
+    db: Arc<dyn Library>,
+    targets: Vec<ExportTarget>,
+}
+
+/// Result of writing playlist files.
+#[derive(Debug, Clone, Default)]
+pub struct ExportResult {
+    /// Number of files written because their contents changed.
+    pub written: usize,
+    /// Number of files that were already up to date.
+    pub unchanged: usize,
+    /// Playlists that could not be written.
+    pub errors: Vec<String>,
+}
+
+impl PlaylistExporter {
+    /// Create an exporter for the playlists in `config`.
+    ///
+    /// Playlists with a relative path and no music directory to put it in
+    /// are left out; they are reported when the configuration is loaded.
+    #[must_use]
+    pub fn new(db: Arc<dyn Library>, config: &Config) -> Self {
+        let targets = config
+            .playlists
+            .exports
+            .iter()
+            .filter_map(|export| {
+                Some(ExportTarget {
+                    playlist: export.playlist.clone(),
+                    path: config.playlist_export_path(export)?,
+                    relative_paths: export.relative_paths,
+                })
+            })
+            .collect();
+        Self::with_targets(db, targets)
+    }
+
+    /// Create an exporter that writes the given playlists.
+    #[must_use]
+    pub fn with_targets(db: Arc<dyn Library>, targets: Vec<ExportTarget>) -> Self {
+        Self { db, targets }
+    }
+
+    /// Get the playlists that are written, with their files.
+    #[must_use]
+    pub fn targets(&self) -> &[ExportTarget] {
+        &self.targets
+    }
+
+    /// Check if any playlist is written.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        !self.targets.is_empty()
+    }
+
+    /// Write every configured playlist whose file is out of date.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playlists cannot be listed. Playlists that
+    /// are missing or cannot be written are listed in the result instead.
+    pub async fn export_all(&self) -> Result<ExportResult, ApiError> {
+        let playlists = self.db.list_playlists().await?;
+        let mut result = ExportResult::default();
+        for target in &self.targets {
+            let Some(playlist) = playlists
+                .iter()
+                .find(|playlist| playlist.name.eq_ignore_ascii_case(&target.playlist))
+            else {
+                result
+                    .errors
+                    .push(format!("Playlist not found: {}", target.playlist));
+                continue;
+            };
+
+            let mut tracks = self.db.get_playlist_tracks(&playlist.id).await?;
+            // Library paths are stored as imported, which may be relative
+            for track in &mut tracks {
+                if let Ok(path) = track.path.canonicalize() {
+                    track.path = path;
+                }
+            }
+            let directory = target.path.parent().unwrap_or_else(|| Path::new("/"));
+            let relative_to = target.relative_paths.then(|| {
+                directory
+                    .canonicalize()
+                    .unwrap_or_else(|_| directory.to_path_buf())
+            });
+            let contents = write_playlist(
+                PlaylistFormat::M3u8,
+                &playlist.name,
+                &tracks,
+                relative_to.as_deref(),
+            );
+
+            match write_if_changed(&target.path, &contents) {
+                Ok(true) => {
+                    debug!(
+                        "Wrote playlist '{}' to {}",
+                        playlist.name,
+                        target.path.display()
+                    );
+                    result.written += 1;
+                }
+                Ok(false) => result.unchanged += 1,
+                Err(e) => {
+                    warn!("Failed to write {}: {e}", target.path.display());
+                    result
+                        .errors
+                        .push(format!("Failed to write {}: {e}", target.path.display()));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Write the playlists now, and again after changes from `events`.
+    ///
+    /// This runs until the task is cancelled, and returns right away if no
+    /// playlist is configured.
+    pub async fn run(self, mut events: EventReceiver) {
+        if !self.is_enabled() {
+            return;
+        }
+        loop {
+            match self.export_all().await {
+                Ok(result) => {
+                    if result.written > 0 {
+                        info!("Wrote {} playlist files", result.written);
+                    }
+                    for error in &result.errors {
+                        warn!("Playlist export: {error}");
+                    }
+                }
+                Err(e) => warn!("Failed to export playlists: {e:?}"),
+            }
+
+            // Wait for a change, then for the changes that follow it
+            if !wait_for_change(&mut events).await {
+                return;
+            }
+            while tokio::time::timeout(DEBOUNCE, wait_for_change(&mut events))
+                .await
+                .unwrap_or(false)
+            {}
+        }
+    }
+}
+
+/// Wait for a change to the library.
+///
+/// Any change may change a playlist's tracks, since smart playlists can
+/// match on ratings and plays as well. Returns `false` once the event bus is
+/// closed.
+async fn wait_for_change(events: &mut EventReceiver) -> bool {
+    // Missed events are changes too
+    !matches!(
+        events.recv().await,
+        Err(broadcast::error::RecvError::Closed)
+    )
+}
+
+/// Write a file, unless it already has the contents.
+///
+/// The file is written next to its final path first and then renamed, so
+/// players never read half a playlist. Returns whether the file was written.
+fn write_if_changed(path: &Path, contents: &str) -> std::io::Result<bool> {
+    if std::fs::read_to_string(path).is_ok_and(|current| current == contents) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::config::PlaylistExport;
+    use apollo_core::metadata::Track;
+    use apollo_core::playlist::Playlist;
+    use apollo_core::query::Query;
+    use apollo_db::SqliteLibrary;
+    use std::fs;
+
+    fn track(dir: &Path, title: &str, year: i32) -> Track {
+        let path = dir.join(format!("{title}.mp3"));
+        fs::write(&path, b"").unwrap();
+        let mut track = Track::new(
+            path,
+            title.to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        track.year = Some(year);
+        track
+    }
+
+    #[tokio::test]
+    async fn test_export_all() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let db: Arc<dyn Library> = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        db.add_track(&track(&dir, "Old", 1975)).await.unwrap();
+        db.add_track(&track(&dir, "New", 2024)).await.unwrap();
+        let query = Query::parse("year:2000..2100").unwrap();
+        db.add_playlist(&Playlist::new_smart("Recent", query))
+            .await
+            .unwrap();
+
+        let target = ExportTarget {
+            playlist: "recent".to_string(),
+            path: dir.join("Playlists/Recent.m3u8"),
+            relative_paths: true,
+        };
+        let missing = ExportTarget {
+            playlist: "Nothing".to_string(),
+            path: dir.join("Nothing.m3u8"),
+            relative_paths: false,
+        };
+        let exporter = PlaylistExporter::with_targets(Arc::clone(&db), vec![target, missing]);
+
+        let result = exporter.export_all().await.unwrap();
+        assert_eq!(result.written, 1);
+        assert_eq!(result.errors, vec!["Playlist not found: Nothing"]);
+        let contents = fs::read_to_string(dir.join("Playlists/Recent.m3u8")).unwrap();
+        assert!(contents.contains("#PLAYLIST:Recent"));
+        assert!(contents.contains("../New.mp3"));
+        assert!(!contents.contains("Old.mp3"));
+
+        // Unchanged playlists are not written again
+        let result = exporter.export_all().await.unwrap();
+        assert_eq!(result.written, 0);
+        assert_eq!(result.unchanged, 1);
+
+        db.add_track(&track(&dir, "Newer", 2025)).await.unwrap();
+        let result = exporter.export_all().await.unwrap();
+        assert_eq!(result.written, 1);
+        let contents = fs::read_to_string(dir.join("Playlists/Recent.m3u8")).unwrap();
+        assert!(contents.contains("../Newer.mp3"));
+    }
+
+    #[tokio::test]
+    async fn test_targets_from_config() {
+        let mut config = Config::default();
+        config.paths.music_directory = Some(PathBuf::from("/music"));
+        config.playlists.exports = vec![
+            PlaylistExport {
+                playlist: "Mix".to_string(),
+                path: PathBuf::from("Playlists/Mix.m3u8"),
+                relative_paths: true,
+            },
+            PlaylistExport {
+                playlist: "Car".to_string(),
+                path: PathBuf::from("/media/car/car.m3u8"),
+                relative_paths: false,
+            },
+        ];
+
+        let db: Arc<dyn Library> = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let exporter = PlaylistExporter::new(db, &config);
+        let paths: Vec<&Path> = exporter
+            .targets()
+            .iter()
+            .map(|t| t.path.as_path())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("/music/Playlists/Mix.m3u8"),
+                Path::new("/media/car/car.m3u8")
+            ]
+        );
+    }
+}
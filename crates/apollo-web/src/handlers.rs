@@ -1,52 +1,188 @@
 //! API request handlers.
 
-use crate::import::{ImportOptions, ImportResult, ImportService};
+use crate::artist_image;
+use crate::candidates::{CandidateKind, CandidateSource, TagCandidate};
+use crate::dlna;
+use crate::http_cache::{CacheHeaders, etag_matches};
+use crate::identify::{self, IdentifyCandidate};
+use crate::import::{ImportOptions, ImportPreview, ImportResult, ImportService};
+use crate::schedule::JobStatus;
+use crate::writeback::write_tags;
+use crate::zip::{self, ZipEntry};
 use crate::{error::ApiError, state::AppState};
-use apollo_core::Config;
-use apollo_core::metadata::{Album, AlbumId, Track, TrackId};
-use apollo_core::playlist::{Playlist, PlaylistId, PlaylistLimit, PlaylistSort};
-use apollo_core::query::Query as ApolloQuery;
+use apollo_audio::compute_file_hash;
+use apollo_core::changes::LibraryChanges;
+use apollo_core::config::WebConfig;
+use apollo_core::edit::EditField;
+use apollo_core::import_run::ImportRun;
+use apollo_core::library::Suggestions;
+use apollo_core::lock::FieldLocks;
+use apollo_core::metadata::{Album, AlbumId, ArtistId, Fingerprint, Track, TrackId};
+use apollo_core::playlist::{Playlist, PlaylistId, PlaylistLimit, PlaylistSort, PlaylistVersion};
+use apollo_core::provenance::{MetadataSource, Provenance};
+use apollo_core::query::{Query as ApolloQuery, SortSpec};
+use apollo_core::template::sanitize_path_component;
+use apollo_core::{AlbumSet, Config, PathTemplate, TemplateContext, TrackDiff};
 use axum::{
     Json,
-    extract::{Path, Query, State},
-    http::StatusCode,
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt};
+use tower_http::services::ServeFile;
+use tracing::warn;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-/// Default page size for list operations.
-const DEFAULT_LIMIT: u32 = 50;
-/// Maximum page size for list operations.
-const MAX_LIMIT: u32 = 500;
+/// Seconds that clients may keep an artist image.
+const ARTIST_IMAGE_MAX_AGE: u32 = 7 * 24 * 60 * 60;
+/// Seconds that clients may keep an artist placeholder.
+const PLACEHOLDER_MAX_AGE: u32 = 60 * 60;
+/// Seconds that clients may keep album art before checking it again.
+const ALBUM_ART_MAX_AGE: u32 = 24 * 60 * 60;
+
+/// Default number of names to suggest of each kind.
+const DEFAULT_SUGGESTIONS: u32 = 5;
+
+/// Maximum number of names to suggest of each kind.
+const MAX_SUGGESTIONS: u32 = 20;
+
+/// Default number of imports to list from the import history.
+const DEFAULT_HISTORY_LIMIT: u32 = 20;
+
+/// Track identification query parameters.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct IdentifyQuery {
+    /// Apply the best candidate to the track.
+    #[serde(default)]
+    pub apply: bool,
+    /// Write the tags of the applied candidate to the file as well, or
+    /// queue them with `write.deferred` set.
+    #[serde(default)]
+    pub write: bool,
+    /// Lowest match score of a candidate, from 0 to 1 (default: 0.8).
+    #[serde(default = "default_identify_min_score")]
+    #[param(default = 0.8, minimum = 0, maximum = 1)]
+    pub min_score: f64,
+}
+
+const fn default_identify_min_score() -> f64 {
+    identify::DEFAULT_MIN_SCORE
+}
+
+/// Candidate lookup query parameters.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CandidatesQuery {
+    /// Service to look up candidates on (default: musicbrainz).
+    #[serde(default)]
+    #[param(inline)]
+    pub source: CandidateSource,
+}
+
+/// Request to tag a track with a candidate.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ApplyCandidateRequest {
+    /// Service the candidate comes from.
+    pub source: CandidateSource,
+    /// Whether the candidate is a release or a recording.
+    pub kind: CandidateKind,
+    /// ID of the release or recording on its service.
+    #[schema(example = "b84ee12a-09ef-421b-82de-0441a926375b")]
+    pub id: String,
+    /// Write the tags to the file as well, or queue them with
+    /// `write.deferred` set.
+    #[serde(default)]
+    pub write: bool,
+}
+
+/// Locked fields of a track.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrackLocksResponse {
+    /// Fields locked for the track.
+    #[schema(example = json!(["genre", "title"]))]
+    pub fields: Vec<String>,
+    /// Fields locked for all tracks by `locks.fields`.
+    #[schema(example = json!(["year"]))]
+    pub global: Vec<String>,
+}
+
+/// Request to set the locked fields of a track.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TrackLocksRequest {
+    /// Fields to lock, by their names as in `apollo tag`; other fields are
+    /// unlocked.
+    #[schema(example = json!(["genre", "title"]))]
+    pub fields: Vec<String>,
+}
 
 /// Pagination query parameters.
 #[derive(Debug, Deserialize, IntoParams)]
 pub struct PaginationQuery {
-    /// Maximum number of items to return (default: 50, max: 500).
-    #[serde(default = "default_limit")]
-    #[param(default = 50, minimum = 1, maximum = 500)]
-    pub limit: u32,
+    /// Maximum number of items to return (default: `web.default_page_size`,
+    /// max: `web.max_page_size`).
+    #[param(minimum = 1)]
+    pub limit: Option<u32>,
     /// Number of items to skip.
     #[serde(default)]
     #[param(default = 0, minimum = 0)]
     pub offset: u32,
 }
 
-const fn default_limit() -> u32 {
-    DEFAULT_LIMIT
+impl PaginationQuery {
+    /// Number of items in the page, within the configured page sizes.
+    #[must_use]
+    pub fn page_size(&self, config: &WebConfig) -> u32 {
+        self.limit
+            .unwrap_or(config.default_page_size)
+            .clamp(1, config.max_page_size.max(1))
+    }
+}
+
+/// Offset of the page after the one at `offset` with `count` items, if
+/// there are more than `total` items.
+fn next_offset(offset: u32, count: usize, total: u64) -> Option<u32> {
+    let end = u64::from(offset) + count as u64;
+    u32::try_from(end).ok().filter(|_| end < total)
 }
 
 /// Search query parameters.
 #[derive(Debug, Deserialize, IntoParams)]
 pub struct SearchQuery {
-    /// Search query string. Supports simple text or FTS5 syntax.
+    /// Search query string. Supports simple text or FTS5 syntax, with
+    /// optional sort terms such as `sort:year-`.
     #[param(example = "bohemian rhapsody")]
     pub q: String,
 }
 
+/// Search suggestion query parameters.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SuggestQuery {
+    /// Partly typed search.
+    #[param(example = "bo")]
+    pub q: String,
+    /// Maximum number of names to suggest of each kind (default: 5, max: 20).
+    #[serde(default = "default_suggestions")]
+    #[param(default = 5, minimum = 1, maximum = 20)]
+    pub limit: u32,
+}
+
+const fn default_suggestions() -> u32 {
+    DEFAULT_SUGGESTIONS
+}
+
 /// Paginated response wrapper for tracks.
 #[derive(Debug, Serialize, ToSchema)]
 pub struct PaginatedTracksResponse {
@@ -61,6 +197,27 @@ pub struct PaginatedTracksResponse {
     /// Current offset.
     #[schema(example = 0)]
     pub offset: u32,
+    /// Offset of the next page, if there is one.
+    #[schema(example = 50)]
+    pub next_offset: Option<u32>,
+    /// Whether there are items after this page.
+    pub has_more: bool,
+}
+
+impl PaginatedTracksResponse {
+    /// A page of items at `offset`, out of `total`.
+    #[must_use]
+    pub fn new(items: Vec<Track>, total: u64, limit: u32, offset: u32) -> Self {
+        let next_offset = next_offset(offset, items.len(), total);
+        Self {
+            items,
+            total,
+            limit,
+            offset,
+            next_offset,
+            has_more: next_offset.is_some(),
+        }
+    }
 }
 
 /// Paginated response wrapper for albums.
@@ -77,6 +234,38 @@ pub struct PaginatedAlbumsResponse {
     /// Current offset.
     #[schema(example = 0)]
     pub offset: u32,
+    /// Offset of the next page, if there is one.
+    #[schema(example = 50)]
+    pub next_offset: Option<u32>,
+    /// Whether there are items after this page.
+    pub has_more: bool,
+}
+
+impl PaginatedAlbumsResponse {
+    /// A page of items at `offset`, out of `total`.
+    #[must_use]
+    pub fn new(items: Vec<Album>, total: u64, limit: u32, offset: u32) -> Self {
+        let next_offset = next_offset(offset, items.len(), total);
+        Self {
+            items,
+            total,
+            limit,
+            offset,
+            next_offset,
+            has_more: next_offset.is_some(),
+        }
+    }
+}
+
+/// Track identification response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IdentifyResponse {
+    /// Recordings the track may be, best match first.
+    pub candidates: Vec<IdentifyCandidate>,
+    /// The track, with the best candidate applied if it was.
+    pub track: Track,
+    /// Whether the best candidate was applied.
+    pub applied: bool,
 }
 
 /// Library statistics response.
@@ -135,7 +324,7 @@ impl PlaylistResponse {
             description: playlist.description.clone(),
             kind: format!("{}", playlist.kind),
             query: playlist.query.as_ref().map(|q| format!("{q}")),
-            sort: format!("{}", playlist.sort),
+            sort: playlist.sort_label(),
             max_tracks: playlist.limit.as_ref().and_then(|l| l.max_tracks),
             max_duration_secs: playlist.limit.as_ref().and_then(|l| l.max_duration_secs),
             track_count,
@@ -188,6 +377,43 @@ pub struct UpdatePlaylistRequest {
     pub max_duration_secs: Option<u64>,
 }
 
+/// Request to record a play of a track.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RecordPlayRequest {
+    /// When the track was played (default: now).
+    #[schema(value_type = Option<String>, example = "2024-01-15T10:30:00Z")]
+    pub played_at: Option<DateTime<Utc>>,
+}
+
+/// Request to save the tracks that a query matches as a new playlist.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PlaylistFromQueryRequest {
+    /// Playlist name.
+    #[schema(example = "Beatles Favorites")]
+    pub name: String,
+    /// Optional description.
+    pub description: Option<String>,
+    /// Query selecting the tracks, with optional sort terms such as
+    /// `sort:year-`.
+    #[schema(example = "artist:Beatles rating:>=4 sort:year")]
+    pub query: String,
+    /// Maximum number of tracks to save.
+    pub max_tracks: Option<u32>,
+}
+
+/// Request to save tracks as a new playlist.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PlaylistFromTracksRequest {
+    /// Playlist name.
+    #[schema(example = "Road Trip")]
+    pub name: String,
+    /// Optional description.
+    pub description: Option<String>,
+    /// Track IDs, in playlist order.
+    #[schema(example = json!(["550e8400-e29b-41d4-a716-446655440000"]))]
+    pub track_ids: Vec<String>,
+}
+
 /// Request to add or remove tracks from a playlist.
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct PlaylistTracksRequest {
@@ -216,6 +442,10 @@ pub struct ErrorResponse {
     /// Error message.
     #[schema(example = "Track not found: 550e8400-e29b-41d4-a716-446655440000")]
     pub message: String,
+    /// ID of the request, to find it in the server's log.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "1b4e28ba-2fa1-41d2-883f-0016d3cca427")]
+    pub request_id: Option<String>,
 }
 
 /// Health check endpoint.
@@ -234,6 +464,143 @@ pub async fn health_check() -> Json<HealthResponse> {
     })
 }
 
+/// Stream library change events.
+///
+/// Sends a [server-sent event](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+/// for every change to the library. The event name is the type of change,
+/// such as `track.added`, and the data is the change as JSON.
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    tag = "System",
+    responses(
+        (status = 200, description = "Stream of library change events", content_type = "text/event-stream", body = String)
+    )
+)]
+pub async fn library_events(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events =
+        BroadcastStream::new(state.db.events().subscribe()).filter_map(|event| match event {
+            Ok(event) => Event::default()
+                .event(event.name())
+                .json_data(&event)
+                .ok()
+                .map(Ok),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                warn!("Event stream fell behind, skipped {missed} events");
+                None
+            }
+        });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// Get the status of the scheduled maintenance jobs.
+///
+/// Only jobs that run are listed, so the list is empty unless jobs are
+/// configured under `[schedule]`.
+#[utoipa::path(
+    get,
+    path = "/api/jobs/scheduled",
+    tag = "System",
+    responses(
+        (status = 200, description = "Status of the scheduled jobs", body = Vec<JobStatus>)
+    )
+)]
+pub async fn list_scheduled_jobs(State(state): State<Arc<AppState>>) -> Json<Vec<JobStatus>> {
+    Json(state.jobs.jobs())
+}
+
+/// Cancel a running job, such as an import that was given a `job_id`.
+///
+/// The job stops at its next check for cancellation, and the request that
+/// started it is answered with what was done until then.
+#[utoipa::path(
+    delete,
+    path = "/api/jobs/{id}",
+    tag = "System",
+    params(
+        ("id" = String, Path, description = "ID the job was started with", example = "import-new-albums")
+    ),
+    responses(
+        (status = 204, description = "Job cancelled"),
+        (status = 404, description = "No job with this ID is running", body = ErrorResponse)
+    )
+)]
+pub async fn cancel_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if state.running_jobs.cancel(&id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("Job not running: {id}")))
+    }
+}
+
+/// Query parameters for library changes.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChangesQuery {
+    /// Sequence number of the last sync, as returned in `seq`, or an RFC 3339
+    /// timestamp. Without it, all recorded changes are returned.
+    #[param(example = "1234")]
+    pub since: Option<String>,
+}
+
+/// Get the tracks and playlists that changed since a sync.
+///
+/// Clients keep the returned `seq` and pass it as `since` at the next sync,
+/// to fetch only what changed. The response has the `seq` as its `ETag`, so
+/// a request with a matching `If-None-Match` is answered with 304 Not
+/// Modified.
+#[utoipa::path(
+    get,
+    path = "/api/changes",
+    tag = "Library",
+    params(ChangesQuery),
+    responses(
+        (status = 200, description = "Changes since the sync", body = LibraryChanges),
+        (status = 304, description = "Nothing changed since the sync"),
+        (status = 400, description = "Invalid sequence number or timestamp", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_changes(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ChangesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let since = match query.since.as_deref() {
+        None => 0,
+        Some(since) => {
+            if let Ok(seq) = since.parse::<i64>() {
+                seq
+            } else {
+                let at = DateTime::parse_from_rfc3339(since).map_err(|_| {
+                    ApiError::BadRequest(format!(
+                        "Invalid since, expected a sequence number or timestamp: {since}"
+                    ))
+                })?;
+                state.db.change_seq_at(at.with_timezone(&Utc)).await?
+            }
+        }
+    };
+
+    let changes = state.db.list_changes(since).await?;
+    let changes = LibraryChanges::collect(since, &changes);
+
+    let etag = format!("\"{}\"", changes.seq);
+    let mut response = if etag_matches(&headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(changes).into_response()
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    Ok(response)
+}
+
 /// Get library statistics.
 #[utoipa::path(
     get,
@@ -273,16 +640,16 @@ pub async fn list_tracks(
     State(state): State<Arc<AppState>>,
     Query(query): Query<PaginationQuery>,
 ) -> Result<Json<PaginatedTracksResponse>, ApiError> {
-    let limit = query.limit.min(MAX_LIMIT);
+    let limit = query.page_size(&state.config.web);
     let tracks = state.db.list_tracks(limit, query.offset).await?;
     let total = state.db.count_tracks().await?;
 
-    Ok(Json(PaginatedTracksResponse {
-        items: tracks,
+    Ok(Json(PaginatedTracksResponse::new(
+        tracks,
         total,
         limit,
-        offset: query.offset,
-    }))
+        query.offset,
+    )))
 }
 
 /// Get a single track by ID.
@@ -317,6 +684,608 @@ pub async fn get_track(
     Ok(Json(track))
 }
 
+/// Get where the fields of a track came from.
+///
+/// Fields that are not listed have the value read from the file's tags.
+#[utoipa::path(
+    get,
+    path = "/api/tracks/{id}/provenance",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    responses(
+        (status = 200, description = "Sources of the track's fields", body = Provenance),
+        (status = 400, description = "Invalid track ID", body = ErrorResponse),
+        (status = 404, description = "Track not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_track_provenance(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Provenance>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let track_id = TrackId(uuid);
+    if state.db.get_track(&track_id).await?.is_none() {
+        return Err(ApiError::NotFound(format!("Track not found: {id}")));
+    }
+
+    Ok(Json(state.db.get_track_provenance(&track_id).await?))
+}
+
+/// Get the locked fields of a track, which lookups, plugins and bulk edits
+/// leave alone.
+#[utoipa::path(
+    get,
+    path = "/api/tracks/{id}/locks",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    responses(
+        (status = 200, description = "Locked fields of the track", body = TrackLocksResponse),
+        (status = 400, description = "Invalid track ID", body = ErrorResponse),
+        (status = 404, description = "Track not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_track_locks(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<TrackLocksResponse>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let track_id = TrackId(uuid);
+    if state.db.get_track(&track_id).await?.is_none() {
+        return Err(ApiError::NotFound(format!("Track not found: {id}")));
+    }
+
+    let fields = state.db.get_field_locks(&track_id).await?;
+    Ok(Json(track_locks_response(&state.config, &fields)))
+}
+
+/// Set the locked fields of a track.
+#[utoipa::path(
+    put,
+    path = "/api/tracks/{id}/locks",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    request_body = TrackLocksRequest,
+    responses(
+        (status = 200, description = "Locked fields of the track", body = TrackLocksResponse),
+        (status = 400, description = "Invalid track ID or unknown field", body = ErrorResponse),
+        (status = 404, description = "Track not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn set_track_locks(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<TrackLocksRequest>,
+) -> Result<Json<TrackLocksResponse>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let fields = req
+        .fields
+        .iter()
+        .map(|name| name.parse())
+        .collect::<Result<Vec<EditField>, _>>()?;
+
+    state.db.set_field_locks(&TrackId(uuid), &fields).await?;
+    let fields = state.db.get_field_locks(&TrackId(uuid)).await?;
+    Ok(Json(track_locks_response(&state.config, &fields)))
+}
+
+fn track_locks_response(config: &Config, fields: &[EditField]) -> TrackLocksResponse {
+    let names = |fields: &[EditField]| fields.iter().map(ToString::to_string).collect();
+    TrackLocksResponse {
+        fields: names(fields),
+        global: names(config.locks.locked_fields().fields()),
+    }
+}
+
+/// Get the fields locked for a track, by itself or for all tracks.
+async fn field_locks(state: &AppState, track: &Track) -> Result<FieldLocks, ApiError> {
+    let mut locks = state.config.locks.locked_fields();
+    locks.extend(state.db.get_field_locks(&track.id).await?);
+    Ok(locks)
+}
+
+/// Stream the audio file of a track.
+///
+/// Range requests are answered with part of the file, so players can seek.
+/// DLNA players that ask how the file can be played are told too.
+#[utoipa::path(
+    get,
+    path = "/api/tracks/{id}/stream",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    responses(
+        (status = 200, description = "Audio file", content_type = "audio/*"),
+        (status = 206, description = "Requested range of the audio file", content_type = "audio/*"),
+        (status = 400, description = "Invalid track ID", body = ErrorResponse),
+        (status = 404, description = "Track or audio file not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn stream_track(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let track = state
+        .db
+        .get_track(&TrackId(uuid))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Track not found: {id}")))?;
+    if !track.path.is_file() {
+        return Err(ApiError::NotFound(format!(
+            "Audio file not found: {}",
+            track.path.display()
+        )));
+    }
+
+    let dlna = request.headers().contains_key(dlna::GET_CONTENT_FEATURES);
+    let mut response = ServeFile::new(&track.path)
+        .try_call(request)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to read audio file: {e}")))?;
+    let success = response.status().is_success();
+    let headers = response.headers_mut();
+    if success {
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(track.format.mime_type()),
+        );
+    }
+    if dlna {
+        headers.insert(
+            dlna::CONTENT_FEATURES,
+            HeaderValue::from_static(dlna::STREAM_FEATURES),
+        );
+        headers.insert(dlna::TRANSFER_MODE, HeaderValue::from_static("Streaming"));
+    }
+    Ok(response.map(Body::new))
+}
+
+/// Record a play of a track.
+///
+/// The play counts towards the track's statistics, and is scrobbled to the
+/// configured services while the web server runs.
+#[utoipa::path(
+    post,
+    path = "/api/tracks/{id}/plays",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    request_body(content = Option<RecordPlayRequest>, description = "When the track was played"),
+    responses(
+        (status = 204, description = "Play recorded"),
+        (status = 400, description = "Invalid track ID", body = ErrorResponse),
+        (status = 404, description = "Track not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn record_play(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    req: Option<Json<RecordPlayRequest>>,
+) -> Result<StatusCode, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let played_at = req
+        .and_then(|Json(req)| req.played_at)
+        .unwrap_or_else(Utc::now);
+
+    state.db.record_play(&TrackId(uuid), played_at).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Add a track with its metadata, such as a track copied from another
+/// library.
+///
+/// The track keeps its ID, so that both libraries know it as the same
+/// track. Its file can be uploaded afterwards.
+#[utoipa::path(
+    post,
+    path = "/api/tracks",
+    tag = "Tracks",
+    request_body = Track,
+    responses(
+        (status = 201, description = "Track added", body = Track),
+        (status = 400, description = "Album of the track not found", body = ErrorResponse),
+        (status = 409, description = "Track already in the library", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn add_track(
+    State(state): State<Arc<AppState>>,
+    Json(track): Json<Track>,
+) -> Result<(StatusCode, Json<Track>), ApiError> {
+    if state.db.get_track(&track.id).await?.is_some() {
+        return Err(ApiError::Conflict(format!(
+            "Track already exists: {}",
+            track.id
+        )));
+    }
+    if state.db.get_track_by_path(&track.path).await?.is_some() {
+        return Err(ApiError::Conflict(format!(
+            "Another track has the path {}",
+            track.path.display()
+        )));
+    }
+    if let Some(album_id) = &track.album_id
+        && state.db.get_album(album_id).await?.is_none()
+    {
+        return Err(ApiError::BadRequest(format!("Album not found: {album_id}")));
+    }
+
+    state.db.add_track(&track).await?;
+    Ok((StatusCode::CREATED, Json(track)))
+}
+
+/// Upload the audio file of a track that has none.
+///
+/// The file is placed in the music directory by the path template, and
+/// must match the hash of the track.
+#[utoipa::path(
+    put,
+    path = "/api/tracks/{id}/file",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "File placed", body = Track),
+        (status = 400, description = "No music directory configured, or the file cannot be placed", body = ErrorResponse),
+        (status = 404, description = "Track not found", body = ErrorResponse),
+        (status = 409, description = "Track already has a file", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn upload_track_file(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Body,
+) -> Result<Json<Track>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let mut track = state
+        .db
+        .get_track(&TrackId(uuid))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Track not found: {id}")))?;
+    if track.path.exists() {
+        return Err(ApiError::Conflict(format!(
+            "Track already has a file at {}",
+            track.path.display()
+        )));
+    }
+    let music_dir = state.config.music_directory().ok_or_else(|| {
+        ApiError::BadRequest("No music directory is configured to place files in".to_string())
+    })?;
+
+    let dest = music_dir.join(track_file_path(&state, &track).await?);
+    if dest.exists() {
+        return Err(ApiError::Conflict(format!(
+            "Another file is at {}",
+            dest.display()
+        )));
+    }
+    let partial = dest.with_extension("part");
+    let hash = match receive_file(body, &partial).await {
+        Ok(hash) if track.file_hash.is_empty() || hash == track.file_hash => hash,
+        result => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(match result {
+                Ok(_) => ApiError::BadRequest(
+                    "Uploaded file does not match the hash of the track".to_string(),
+                ),
+                Err(e) => ApiError::Internal(format!("Failed to receive file: {e}")),
+            });
+        }
+    };
+    tokio::fs::rename(&partial, &dest)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to place {}: {e}", dest.display())))?;
+
+    track.path = dest;
+    track.file_hash = hash;
+    state.db.update_track(&track).await?;
+    Ok(Json(track))
+}
+
+/// Render where a track's file belongs, relative to the music directory.
+async fn track_file_path(state: &AppState, track: &Track) -> Result<PathBuf, ApiError> {
+    let template = PathTemplate::parse(&state.config.paths.path_template)
+        .map_err(|e| ApiError::Internal(format!("Invalid path template: {e}")))?;
+
+    // Albums are needed for compilations and %aunique
+    let albums = state.db.list_albums(u32::MAX, 0).await?;
+    let mut ctx = TemplateContext::from_track(track);
+    if let Some(album) = albums
+        .iter()
+        .find(|album| track.album_id.as_ref() == Some(&album.id))
+    {
+        ctx.set_album(album, &AlbumSet::new(albums.iter().cloned()));
+    }
+    template
+        .render_with_extension(&ctx)
+        .map_err(|e| ApiError::BadRequest(format!("Cannot render the path of the track: {e}")))
+}
+
+/// Write a request body to a new file, creating its directory, and hash
+/// the file.
+async fn receive_file(body: Body, path: &std::path::Path) -> std::io::Result<String> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        file.write_all(&chunk.map_err(std::io::Error::other)?)
+            .await?;
+    }
+    file.flush().await?;
+
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || compute_file_hash(&path))
+        .await
+        .map_err(std::io::Error::other)?
+        .map_err(std::io::Error::other)
+}
+
+/// Compute the audio fingerprint of a track and store it.
+///
+/// The stored fingerprint is used to identify the track, so that its audio
+/// is only decoded once.
+#[utoipa::path(
+    post,
+    path = "/api/tracks/{id}/fingerprint",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    responses(
+        (status = 200, description = "Fingerprint computed", body = Fingerprint),
+        (status = 400, description = "Invalid track ID", body = ErrorResponse),
+        (status = 404, description = "Track or audio file not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn fingerprint_track(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Fingerprint>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let track = state
+        .db
+        .get_track(&TrackId(uuid))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Track not found: {id}")))?;
+
+    let fingerprint = identify::fingerprint_track(&track).await?;
+    state.db.set_fingerprint(&track.id, &fingerprint).await?;
+    Ok(Json(fingerprint))
+}
+
+/// Identify a track by its audio.
+///
+/// The track's fingerprint is looked up on `AcoustID`, and the recordings it
+/// matches on `MusicBrainz`, giving candidates with the changes each would
+/// make. The fingerprint is computed first if the track has none stored.
+/// With `apply`, the best candidate is saved to the track.
+#[utoipa::path(
+    post,
+    path = "/api/tracks/{id}/identify",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000"),
+        IdentifyQuery
+    ),
+    responses(
+        (status = 200, description = "Candidates for the track", body = IdentifyResponse),
+        (status = 400, description = "Invalid track ID, or AcoustID is not configured", body = ErrorResponse),
+        (status = 404, description = "Track or audio file not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn identify_track(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<IdentifyQuery>,
+) -> Result<Json<IdentifyResponse>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let identifier = state.identifier.as_ref().ok_or_else(|| {
+        ApiError::BadRequest(
+            "Identifying tracks needs an AcoustID API key and MusicBrainz enabled".to_string(),
+        )
+    })?;
+    let track = state
+        .db
+        .get_track(&TrackId(uuid))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Track not found: {id}")))?;
+
+    let fingerprint = if let Some(fingerprint) = state.db.get_fingerprint(&track.id).await? {
+        fingerprint
+    } else {
+        let fingerprint = identify::fingerprint_track(&track).await?;
+        state.db.set_fingerprint(&track.id, &fingerprint).await?;
+        fingerprint
+    };
+    let candidates = identifier
+        .candidates(&track, &fingerprint, query.min_score)
+        .await
+        .map_err(|e| ApiError::Internal(format!("AcoustID lookup failed: {e}")))?;
+
+    let Some(best) = candidates.first().filter(|_| query.apply) else {
+        return Ok(Json(IdentifyResponse {
+            candidates,
+            track,
+            applied: false,
+        }));
+    };
+    // Fields edited by hand are kept, since the best match is picked
+    // without anyone looking at it
+    let mut matched = best.track.clone();
+    state
+        .db
+        .get_track_provenance(&track.id)
+        .await?
+        .keep_manual_edits(&track, &mut matched)?;
+    field_locks(&state, &track)
+        .await?
+        .restore(&track, &mut matched)?;
+    let track = save_tagged_track(
+        &state,
+        &track,
+        matched,
+        MetadataSource::MusicBrainz,
+        query.write,
+    )
+    .await?;
+    Ok(Json(IdentifyResponse {
+        candidates,
+        track,
+        applied: true,
+    }))
+}
+
+/// Find metadata that a track could be tagged with, by searching for its
+/// tags.
+///
+/// `MusicBrainz` is searched for recordings by the track's title and artist,
+/// and for releases by its album; Discogs for releases. Candidates are
+/// ranked by how well they fit the track, and carry the changes each would
+/// make.
+#[utoipa::path(
+    get,
+    path = "/api/tracks/{id}/candidates",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000"),
+        CandidatesQuery
+    ),
+    responses(
+        (status = 200, description = "Candidates for the track, best first", body = Vec<TagCandidate>),
+        (status = 400, description = "Invalid track ID, or the source is not set up", body = ErrorResponse),
+        (status = 404, description = "Track not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_track_candidates(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<CandidatesQuery>,
+) -> Result<Json<Vec<TagCandidate>>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let track = state
+        .db
+        .get_track(&TrackId(uuid))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Track not found: {id}")))?;
+
+    let candidates = state.tag_sources.candidates(&track, query.source).await?;
+    Ok(Json(candidates))
+}
+
+/// Tag a track with a candidate from `GET /api/tracks/{id}/candidates`.
+///
+/// The candidate is looked up again by its ID, and its metadata saved to
+/// the track.
+#[utoipa::path(
+    post,
+    path = "/api/tracks/{id}/apply-candidate",
+    tag = "Tracks",
+    params(
+        ("id" = String, Path, description = "Track UUID", example = "550e8400-e29b-41d4-a716-446655440000")
+    ),
+    request_body = ApplyCandidateRequest,
+    responses(
+        (status = 200, description = "Track tagged", body = Track),
+        (status = 400, description = "Invalid track or candidate ID, or the source is not set up", body = ErrorResponse),
+        (status = 404, description = "Track or candidate not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn apply_track_candidate(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<ApplyCandidateRequest>,
+) -> Result<Json<Track>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {id}")))?;
+    let track = state
+        .db
+        .get_track(&TrackId(uuid))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Track not found: {id}")))?;
+
+    let mut tagged = state
+        .tag_sources
+        .apply(&track, req.source, req.kind, &req.id)
+        .await?;
+    field_locks(&state, &track)
+        .await?
+        .restore(&track, &mut tagged)?;
+    let track = save_tagged_track(
+        &state,
+        &track,
+        tagged,
+        req.source.metadata_source(),
+        req.write,
+    )
+    .await?;
+    Ok(Json(track))
+}
+
+/// Save a track with new tags from a source, writing them to its file if
+/// asked, or queueing them with `write.deferred` set.
+///
+/// The changed fields are recorded as coming from the source.
+async fn save_tagged_track(
+    state: &AppState,
+    original: &Track,
+    mut track: Track,
+    source: MetadataSource,
+    write: bool,
+) -> Result<Track, ApiError> {
+    if write && !state.config.write.deferred {
+        write_tags(&mut track, &state.config.write.written_fields(), &[]).map_err(|e| {
+            ApiError::Internal(format!(
+                "Failed to write tags to {}: {e}",
+                track.path.display()
+            ))
+        })?;
+    }
+    state.db.update_track(&track).await?;
+    let diff = TrackDiff::between(original, &track);
+    state
+        .db
+        .record_provenance(&track.id, &Provenance::from_diff(&diff, source))
+        .await?;
+    if write && state.config.write.deferred {
+        state.db.queue_write(&track.id, &[]).await?;
+    }
+    Ok(track)
+}
+
 /// List all albums with pagination.
 #[utoipa::path(
     get,
@@ -332,82 +1301,315 @@ pub async fn list_albums(
     State(state): State<Arc<AppState>>,
     Query(query): Query<PaginationQuery>,
 ) -> Result<Json<PaginatedAlbumsResponse>, ApiError> {
-    let limit = query.limit.min(MAX_LIMIT);
+    let limit = query.page_size(&state.config.web);
     let albums = state.db.list_albums(limit, query.offset).await?;
     let total = state.db.count_albums().await?;
 
-    Ok(Json(PaginatedAlbumsResponse {
-        items: albums,
+    Ok(Json(PaginatedAlbumsResponse::new(
+        albums,
         total,
         limit,
-        offset: query.offset,
-    }))
+        query.offset,
+    )))
+}
+
+/// Get a single album by ID.
+#[utoipa::path(
+    get,
+    path = "/api/albums/{id}",
+    tag = "Albums",
+    params(
+        ("id" = String, Path, description = "Album UUID", example = "660e8400-e29b-41d4-a716-446655440001")
+    ),
+    responses(
+        (status = 200, description = "Album found", body = Album),
+        (status = 400, description = "Invalid album ID", body = ErrorResponse),
+        (status = 404, description = "Album not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_album(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Album>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid album ID: {id}")))?;
+    let album_id = AlbumId(uuid);
+
+    let album = state
+        .db
+        .get_album(&album_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Album not found: {id}")))?;
+
+    Ok(Json(album))
+}
+
+/// Add an album, such as an album copied from another library.
+///
+/// The album keeps its ID, so that tracks copied along with it find it.
+#[utoipa::path(
+    post,
+    path = "/api/albums",
+    tag = "Albums",
+    request_body = Album,
+    responses(
+        (status = 201, description = "Album added", body = Album),
+        (status = 409, description = "Album already in the library", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn add_album(
+    State(state): State<Arc<AppState>>,
+    Json(album): Json<Album>,
+) -> Result<(StatusCode, Json<Album>), ApiError> {
+    if state.db.get_album(&album.id).await?.is_some() {
+        return Err(ApiError::Conflict(format!(
+            "Album already exists: {}",
+            album.id
+        )));
+    }
+
+    state.db.add_album(&album).await?;
+    Ok((StatusCode::CREATED, Json(album)))
+}
+
+/// Get all tracks in an album.
+#[utoipa::path(
+    get,
+    path = "/api/albums/{id}/tracks",
+    tag = "Albums",
+    params(
+        ("id" = String, Path, description = "Album UUID", example = "660e8400-e29b-41d4-a716-446655440001")
+    ),
+    responses(
+        (status = 200, description = "List of tracks in the album", body = Vec<Track>),
+        (status = 400, description = "Invalid album ID", body = ErrorResponse),
+        (status = 404, description = "Album not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_album_tracks(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<Track>>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid album ID: {id}")))?;
+    let album_id = AlbumId(uuid);
+
+    // Verify album exists
+    state
+        .db
+        .get_album(&album_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Album not found: {id}")))?;
+
+    let tracks = state.db.get_album_tracks(&album_id).await?;
+    Ok(Json(tracks))
 }
 
-/// Get a single album by ID.
+/// Download the audio files of an album as a ZIP archive.
+///
+/// The archive is written while it is sent, with the files stored as they
+/// are, in a folder named after the album. Files that are missing from disk
+/// are left out.
 #[utoipa::path(
     get,
-    path = "/api/albums/{id}",
+    path = "/api/albums/{id}/download",
     tag = "Albums",
     params(
         ("id" = String, Path, description = "Album UUID", example = "660e8400-e29b-41d4-a716-446655440001")
     ),
     responses(
-        (status = 200, description = "Album found", body = Album),
+        (status = 200, description = "ZIP archive of the album's files", content_type = "application/zip"),
         (status = 400, description = "Invalid album ID", body = ErrorResponse),
-        (status = 404, description = "Album not found", body = ErrorResponse),
+        (status = 404, description = "Album not found, or none of its files found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
-pub async fn get_album(
+pub async fn download_album(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<Album>, ApiError> {
+) -> Result<Response, ApiError> {
     let uuid = Uuid::parse_str(&id)
         .map_err(|_| ApiError::BadRequest(format!("Invalid album ID: {id}")))?;
     let album_id = AlbumId(uuid);
-
     let album = state
         .db
         .get_album(&album_id)
         .await?
         .ok_or_else(|| ApiError::NotFound(format!("Album not found: {id}")))?;
 
-    Ok(Json(album))
+    let mut paths = Vec::new();
+    for track in state.db.get_album_tracks(&album_id).await? {
+        match tokio::fs::metadata(&track.path).await {
+            Ok(metadata) if metadata.is_file() => paths.push(track.path),
+            _ => warn!(
+                "Leaving out missing file from download: {}",
+                track.path.display()
+            ),
+        }
+    }
+    if paths.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "No audio files found for album: {id}"
+        )));
+    }
+
+    let folder = sanitize_path_component(&format!("{} - {}", album.artist, album.title));
+    // Header values are ASCII, so other characters are replaced
+    let filename: String = folder
+        .chars()
+        .map(|c| if c.is_ascii() && c != '"' { c } else { '_' })
+        .collect();
+    let disposition = HeaderValue::from_str(&format!("attachment; filename=\"{filename}.zip\""))
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+    let entries = archive_entries(&folder, paths);
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/zip"),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(zip::stream(entries)),
+    )
+        .into_response())
 }
 
-/// Get all tracks in an album.
+/// Name the files of an album in its archive by their paths under the
+/// directory they share, such as `Disc 2/01 Track.flac`, in a folder.
+fn archive_entries(folder: &str, paths: Vec<PathBuf>) -> Vec<ZipEntry> {
+    let mut common = paths[0].parent().map(PathBuf::from).unwrap_or_default();
+    for path in &paths {
+        while !path.starts_with(&common) && common.pop() {}
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let relative = path.strip_prefix(&common).unwrap_or(&path);
+            let name = relative
+                .iter()
+                .map(std::ffi::OsStr::to_string_lossy)
+                .collect::<Vec<_>>()
+                .join("/");
+            ZipEntry {
+                name: format!("{folder}/{name}"),
+                path,
+            }
+        })
+        .collect()
+}
+
+/// Get an image of an artist.
+///
+/// Images are looked up on fanart.tv and Wikidata the first time they are
+/// asked for, and cached. Artists without an image get a placeholder with
+/// their initials, as SVG.
 #[utoipa::path(
     get,
-    path = "/api/albums/{id}/tracks",
+    path = "/api/artists/{id}/image",
+    tag = "Artists",
+    params(
+        ("id" = String, Path, description = "Artist UUID", example = "880e8400-e29b-51d4-a716-446655440003")
+    ),
+    responses(
+        (status = 200, description = "Artist image, or a placeholder", content_type = "image/*"),
+        (status = 400, description = "Invalid artist ID", body = ErrorResponse),
+        (status = 404, description = "Artist not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn get_artist_image(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid artist ID: {id}")))?;
+    let artist_id = ArtistId(uuid);
+    let artist = state
+        .db
+        .get_artist(&artist_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Artist not found: {id}")))?;
+
+    let image = match &state.artist_images {
+        Some(images) => images.get(&artist_id, &artist.name).await,
+        None => None,
+    };
+    // Placeholders are asked for again sooner, in case an image turns up
+    let (mime_type, data, max_age) = image.map_or_else(
+        || {
+            (
+                "image/svg+xml".to_string(),
+                artist_image::placeholder(&artist.name).into_bytes(),
+                PLACEHOLDER_MAX_AGE,
+            )
+        },
+        |image| (image.mime_type, image.data, ARTIST_IMAGE_MAX_AGE),
+    );
+    let cache = CacheHeaders::new(max_age).with_content_etag(&data);
+    Ok(cache.respond(&headers, image_response(&mime_type, data)))
+}
+
+/// Get the cover art of an album.
+///
+/// Responses carry an `ETag` and a `Last-Modified` date, so that clients
+/// can check whether the art they have is still current.
+#[utoipa::path(
+    get,
+    path = "/api/albums/{id}/art",
     tag = "Albums",
     params(
         ("id" = String, Path, description = "Album UUID", example = "660e8400-e29b-41d4-a716-446655440001")
     ),
     responses(
-        (status = 200, description = "List of tracks in the album", body = Vec<Track>),
+        (status = 200, description = "Album art", content_type = "image/*"),
+        (status = 304, description = "The client's copy is current"),
         (status = 400, description = "Invalid album ID", body = ErrorResponse),
-        (status = 404, description = "Album not found", body = ErrorResponse),
+        (status = 404, description = "Album has no art", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
-pub async fn get_album_tracks(
+pub async fn get_album_art(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<Vec<Track>>, ApiError> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     let uuid = Uuid::parse_str(&id)
         .map_err(|_| ApiError::BadRequest(format!("Invalid album ID: {id}")))?;
     let album_id = AlbumId(uuid);
+    let not_found = || ApiError::NotFound(format!("No art for album: {id}"));
 
-    // Verify album exists
-    state
+    // Clients with a current copy are answered without loading the image
+    let modified = state
         .db
-        .get_album(&album_id)
+        .get_album_artwork_modified(&album_id)
         .await?
-        .ok_or_else(|| ApiError::NotFound(format!("Album not found: {id}")))?;
+        .ok_or_else(not_found)?;
+    let cache = CacheHeaders::new(ALBUM_ART_MAX_AGE)
+        .with_etag(&format!("{uuid}-{}", modified.timestamp_micros()))
+        .with_last_modified(modified);
+    if cache.is_current(&headers) {
+        return Ok(cache.respond(&headers, ()));
+    }
 
-    let tracks = state.db.get_album_tracks(&album_id).await?;
-    Ok(Json(tracks))
+    let artwork = state
+        .db
+        .get_album_artwork(&album_id)
+        .await?
+        .ok_or_else(not_found)?;
+    Ok(cache.respond(&headers, image_response(&artwork.mime_type, artwork.data)))
+}
+
+/// A response with an image.
+fn image_response(mime_type: &str, data: Vec<u8>) -> Response {
+    let mime_type = HeaderValue::from_str(mime_type)
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    ([(header::CONTENT_TYPE, mime_type)], data).into_response()
 }
 
 /// Search tracks by query.
@@ -432,24 +1634,56 @@ pub async fn search_tracks(
         ));
     }
 
+    // Split off sort terms like "sort:year-"
+    let (search, sort) =
+        SortSpec::extract(&query.q).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if search.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Search query cannot be empty".to_string(),
+        ));
+    }
+
     // Convert to FTS5 prefix search for simple queries
-    let fts_query = if query.q.contains(':') || query.q.contains('"') || query.q.contains('*') {
+    let fts_query = if search.contains(':') || search.contains('"') || search.contains('*') {
         // Use as-is if it looks like FTS5 syntax
-        query.q.clone()
+        search
     } else {
         // Add prefix matching for each word
-        query
-            .q
+        search
             .split_whitespace()
             .map(|word| format!("{word}*"))
             .collect::<Vec<_>>()
             .join(" ")
     };
 
-    let tracks = state.db.search_tracks(&fts_query).await?;
+    let tracks = state.db.search_tracks_sorted(&fts_query, &sort).await?;
     Ok(Json(tracks))
 }
 
+/// Suggest artist, album, and track names for a partly typed search.
+///
+/// Meant for type-ahead: only names that start with the typed words are
+/// matched, and only a few of each kind are returned. An empty query has no
+/// suggestions.
+#[utoipa::path(
+    get,
+    path = "/api/search/suggest",
+    tag = "Search",
+    params(SuggestQuery),
+    responses(
+        (status = 200, description = "Suggested names", body = Suggestions),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn suggest(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SuggestQuery>,
+) -> Result<Json<Suggestions>, ApiError> {
+    let limit = query.limit.clamp(1, MAX_SUGGESTIONS);
+    let suggestions = state.db.suggest(&query.q, limit).await?;
+    Ok(Json(suggestions))
+}
+
 // ========================================================================
 // Playlist handlers
 // ========================================================================
@@ -516,7 +1750,7 @@ pub async fn get_playlist(
     let track_count = if playlist.is_static() {
         playlist.track_ids.len()
     } else {
-        state.db.get_playlist_tracks(&playlist_id).await?.len()
+        usize::try_from(state.db.count_playlist_tracks(&playlist_id).await?).unwrap_or(usize::MAX)
     };
 
     Ok(Json(PlaylistResponse::from_playlist(
@@ -525,16 +1759,17 @@ pub async fn get_playlist(
     )))
 }
 
-/// Get all tracks in a playlist.
+/// Get a page of the tracks in a playlist, in playlist order.
 #[utoipa::path(
     get,
     path = "/api/playlists/{id}/tracks",
     tag = "Playlists",
     params(
-        ("id" = String, Path, description = "Playlist UUID", example = "770e8400-e29b-41d4-a716-446655440002")
+        ("id" = String, Path, description = "Playlist UUID", example = "770e8400-e29b-41d4-a716-446655440002"),
+        PaginationQuery
     ),
     responses(
-        (status = 200, description = "List of tracks in the playlist", body = Vec<Track>),
+        (status = 200, description = "Paginated list of tracks in the playlist", body = PaginatedTracksResponse),
         (status = 400, description = "Invalid playlist ID", body = ErrorResponse),
         (status = 404, description = "Playlist not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
@@ -543,20 +1778,70 @@ pub async fn get_playlist(
 pub async fn get_playlist_tracks(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<Vec<Track>>, ApiError> {
+    Query(query): Query<PaginationQuery>,
+) -> Result<Json<PaginatedTracksResponse>, ApiError> {
     let uuid = Uuid::parse_str(&id)
         .map_err(|_| ApiError::BadRequest(format!("Invalid playlist ID: {id}")))?;
     let playlist_id = PlaylistId(uuid);
+    let limit = query.page_size(&state.config.web);
 
     // Verify playlist exists
-    state
+    let playlist = state
         .db
         .get_playlist(&playlist_id)
         .await?
         .ok_or_else(|| ApiError::NotFound(format!("Playlist not found: {id}")))?;
 
-    let tracks = state.db.get_playlist_tracks(&playlist_id).await?;
-    Ok(Json(tracks))
+    // Smart playlists that were evaluated already are paged from the cache
+    let cached = if playlist.is_smart() {
+        state.playlists.get(&playlist_id)
+    } else {
+        None
+    };
+    let (items, total) = if let Some(tracks) = cached {
+        let total = tracks.len() as u64;
+        let items = tracks
+            .into_iter()
+            .skip(query.offset as usize)
+            .take(limit as usize)
+            .collect();
+        (items, total)
+    } else {
+        let items = state
+            .db
+            .get_playlist_tracks_page(&playlist_id, limit, query.offset)
+            .await?;
+        let total = state.db.count_playlist_tracks(&playlist_id).await?;
+        (items, total)
+    };
+
+    Ok(Json(PaginatedTracksResponse::new(
+        items,
+        total,
+        limit,
+        query.offset,
+    )))
+}
+
+/// Get the tracks of a playlist, evaluating smart playlists through the
+/// cache.
+pub async fn playlist_tracks(
+    state: &AppState,
+    playlist: &Playlist,
+) -> Result<Vec<Track>, ApiError> {
+    if !playlist.is_smart() {
+        return Ok(state.db.get_playlist_tracks(&playlist.id).await?);
+    }
+    if let Some(tracks) = state.playlists.get(&playlist.id) {
+        return Ok(tracks);
+    }
+
+    let generation = state.playlists.generation();
+    let tracks = state.db.get_playlist_tracks(&playlist.id).await?;
+    state
+        .playlists
+        .insert(playlist.id.clone(), tracks.clone(), generation);
+    Ok(tracks)
 }
 
 /// Create a new playlist.
@@ -577,10 +1862,10 @@ pub async fn create_playlist(
 ) -> Result<(StatusCode, Json<PlaylistResponse>), ApiError> {
     let playlist = if let Some(query_str) = req.query {
         // Parse the query for smart playlist
-        let parsed_query = ApolloQuery::parse(&query_str)
+        let (parsed_query, sort_spec) = ApolloQuery::parse_sorted(&query_str)
             .map_err(|e| ApiError::BadRequest(format!("Invalid query: {e}")))?;
 
-        let mut pl = Playlist::new_smart(&req.name, parsed_query);
+        let mut pl = Playlist::new_smart(&req.name, parsed_query).with_sort_spec(sort_spec);
 
         if let Some(desc) = req.description {
             pl = pl.with_description(desc);
@@ -614,6 +1899,95 @@ pub async fn create_playlist(
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+/// Save the tracks that a query matches as a new static playlist.
+///
+/// Unlike a smart playlist, the playlist keeps the tracks the query matched
+/// when it was created, such as the results of a search.
+#[utoipa::path(
+    post,
+    path = "/api/playlists/from-query",
+    tag = "Playlists",
+    request_body = PlaylistFromQueryRequest,
+    responses(
+        (status = 201, description = "Playlist created", body = PlaylistResponse),
+        (status = 400, description = "Invalid query", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn create_playlist_from_query(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PlaylistFromQueryRequest>,
+) -> Result<(StatusCode, Json<PlaylistResponse>), ApiError> {
+    let (query, sort) = ApolloQuery::parse_sorted(&req.query)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid query: {e}")))?;
+    let mut tracks = state.db.query_tracks(&query, &sort).await?;
+    if let Some(max) = req.max_tracks {
+        tracks.truncate(max as usize);
+    }
+
+    let track_ids = tracks.into_iter().map(|track| track.id).collect();
+    save_static_playlist(&state, req.name, req.description, track_ids).await
+}
+
+/// Save tracks as a new static playlist.
+#[utoipa::path(
+    post,
+    path = "/api/playlists/from-tracks",
+    tag = "Playlists",
+    request_body = PlaylistFromTracksRequest,
+    responses(
+        (status = 201, description = "Playlist created", body = PlaylistResponse),
+        (status = 400, description = "Invalid, unknown or repeated track ID", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn create_playlist_from_tracks(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PlaylistFromTracksRequest>,
+) -> Result<(StatusCode, Json<PlaylistResponse>), ApiError> {
+    let mut track_ids = Vec::with_capacity(req.track_ids.len());
+    let mut seen = HashSet::new();
+    for track_id_str in &req.track_ids {
+        let track_uuid = Uuid::parse_str(track_id_str)
+            .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {track_id_str}")))?;
+        let track_id = TrackId(track_uuid);
+
+        // Unknown tracks fail the request, so that no playlist is left
+        // half made
+        if state.db.get_track(&track_id).await?.is_none() {
+            return Err(ApiError::BadRequest(format!(
+                "Track not found: {track_id_str}"
+            )));
+        }
+        if !seen.insert(track_id.clone()) {
+            return Err(ApiError::BadRequest(format!(
+                "Track listed more than once: {track_id_str}"
+            )));
+        }
+        track_ids.push(track_id);
+    }
+
+    save_static_playlist(&state, req.name, req.description, track_ids).await
+}
+
+/// Add a static playlist with tracks, in one go.
+async fn save_static_playlist(
+    state: &AppState,
+    name: String,
+    description: Option<String>,
+    track_ids: Vec<TrackId>,
+) -> Result<(StatusCode, Json<PlaylistResponse>), ApiError> {
+    let mut playlist = Playlist::new_static(name);
+    if let Some(desc) = description {
+        playlist = playlist.with_description(desc);
+    }
+    playlist.track_ids = track_ids;
+    state.db.add_playlist(&playlist).await?;
+
+    let response = PlaylistResponse::from_playlist(&playlist, playlist.track_ids.len());
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
 /// Update a playlist.
 #[utoipa::path(
     patch,
@@ -655,9 +2029,10 @@ pub async fn update_playlist(
 
     if let Some(query_str) = req.query {
         if playlist.is_smart() {
-            let parsed_query = ApolloQuery::parse(&query_str)
+            let (parsed_query, sort_spec) = ApolloQuery::parse_sorted(&query_str)
                 .map_err(|e| ApiError::BadRequest(format!("Invalid query: {e}")))?;
             playlist.query = Some(parsed_query);
+            playlist.sort_spec = (!sort_spec.is_empty()).then_some(sort_spec);
         } else {
             return Err(ApiError::BadRequest(
                 "Cannot set query on static playlist".to_string(),
@@ -684,7 +2059,7 @@ pub async fn update_playlist(
     let track_count = if playlist.is_static() {
         playlist.track_ids.len()
     } else {
-        state.db.get_playlist_tracks(&playlist_id).await?.len()
+        usize::try_from(state.db.count_playlist_tracks(&playlist_id).await?).unwrap_or(usize::MAX)
     };
 
     Ok(Json(PlaylistResponse::from_playlist(
@@ -732,7 +2107,8 @@ pub async fn delete_playlist(
     responses(
         (status = 200, description = "Tracks added", body = PlaylistResponse),
         (status = 400, description = "Invalid request or smart playlist", body = ErrorResponse),
-        (status = 404, description = "Playlist not found", body = ErrorResponse),
+        (status = 404, description = "Playlist or track not found", body = ErrorResponse),
+        (status = 409, description = "Playlist already has a track", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
@@ -757,6 +2133,10 @@ pub async fn add_playlist_tracks(
         ));
     }
 
+    // Check every track before adding any, so that a rejected request
+    // leaves the playlist as it was
+    let mut track_ids = Vec::with_capacity(req.track_ids.len());
+    let mut seen: HashSet<TrackId> = playlist.track_ids.iter().cloned().collect();
     for track_id_str in &req.track_ids {
         let track_uuid = Uuid::parse_str(track_id_str)
             .map_err(|_| ApiError::BadRequest(format!("Invalid track ID: {track_id_str}")))?;
@@ -769,9 +2149,18 @@ pub async fn add_playlist_tracks(
             .await?
             .ok_or_else(|| ApiError::NotFound(format!("Track not found: {track_id_str}")))?;
 
+        if !seen.insert(track_id.clone()) {
+            return Err(ApiError::Conflict(format!(
+                "Playlist already has track: {track_id_str}"
+            )));
+        }
+        track_ids.push(track_id);
+    }
+
+    for track_id in &track_ids {
         state
             .db
-            .add_track_to_playlist(&playlist_id, &track_id)
+            .add_track_to_playlist(&playlist_id, track_id)
             .await?;
     }
 
@@ -851,6 +2240,90 @@ pub async fn remove_playlist_tracks(
     )))
 }
 
+/// Get the versions of a static playlist, newest first.
+///
+/// Each version has the tracks the playlist had before they changed.
+#[utoipa::path(
+    get,
+    path = "/api/playlists/{id}/versions",
+    tag = "Playlists",
+    params(
+        ("id" = String, Path, description = "Playlist UUID", example = "770e8400-e29b-41d4-a716-446655440002")
+    ),
+    responses(
+        (status = 200, description = "Versions of the playlist", body = Vec<PlaylistVersion>),
+        (status = 400, description = "Invalid playlist ID", body = ErrorResponse),
+        (status = 404, description = "Playlist not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn list_playlist_versions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PlaylistVersion>>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid playlist ID: {id}")))?;
+    let playlist_id = PlaylistId(uuid);
+
+    state
+        .db
+        .get_playlist(&playlist_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Playlist not found: {id}")))?;
+
+    Ok(Json(state.db.list_playlist_versions(&playlist_id).await?))
+}
+
+/// Give a static playlist the tracks of one of its versions.
+///
+/// The tracks it has are kept as a new version first, so the restore can be
+/// undone too. Tracks no longer in the library are left out.
+#[utoipa::path(
+    post,
+    path = "/api/playlists/{id}/versions/{version}/restore",
+    tag = "Playlists",
+    params(
+        ("id" = String, Path, description = "Playlist UUID", example = "770e8400-e29b-41d4-a716-446655440002"),
+        ("version" = i64, Path, description = "Version ID", example = 12)
+    ),
+    responses(
+        (status = 200, description = "Playlist restored", body = PlaylistResponse),
+        (status = 400, description = "Invalid playlist ID or smart playlist", body = ErrorResponse),
+        (status = 404, description = "Playlist or version not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn restore_playlist_version(
+    State(state): State<Arc<AppState>>,
+    Path((id, version)): Path<(String, i64)>,
+) -> Result<Json<PlaylistResponse>, ApiError> {
+    let uuid = Uuid::parse_str(&id)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid playlist ID: {id}")))?;
+    let playlist_id = PlaylistId(uuid);
+
+    let playlist = state
+        .db
+        .get_playlist(&playlist_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Playlist not found: {id}")))?;
+
+    if playlist.is_smart() {
+        return Err(ApiError::BadRequest(
+            "Smart playlists have no versions".to_string(),
+        ));
+    }
+
+    let restored = state
+        .db
+        .restore_playlist_version(&playlist_id, version)
+        .await?;
+    let track_count = restored.track_ids.len();
+    Ok(Json(PlaylistResponse::from_playlist(
+        &restored,
+        track_count,
+    )))
+}
+
 /// Parse a sort string into a playlist sort order.
 fn parse_sort(s: &str) -> PlaylistSort {
     match s.to_lowercase().as_str() {
@@ -884,7 +2357,7 @@ pub struct ImportRequest {
     /// Look up metadata from `MusicBrainz`.
     #[serde(default)]
     pub auto_tag: bool,
-    /// Minimum score for `MusicBrainz` matches (0-100).
+    /// Minimum score for `MusicBrainz` matches (0-100), per release.
     #[serde(default = "default_min_score")]
     pub min_match_score: u8,
     /// Group tracks into albums and create album entries.
@@ -893,9 +2366,19 @@ pub struct ImportRequest {
     /// Fetch album art from Cover Art Archive.
     #[serde(default)]
     pub fetch_album_art: bool,
+    /// Write fetched album art as `folder.jpg` next to the files.
+    #[serde(default)]
+    pub write_cover_files: bool,
+    /// Embed fetched album art into the files.
+    #[serde(default)]
+    pub embed_album_art: bool,
     /// Write updated metadata back to files.
     #[serde(default)]
     pub write_tags: bool,
+    /// ID to cancel the import with while it runs, using
+    /// `DELETE /api/jobs/{id}`. Imports without one cannot be cancelled.
+    #[schema(example = "import-new-albums")]
+    pub job_id: Option<String>,
 }
 
 const fn default_min_score() -> u8 {
@@ -915,9 +2398,13 @@ pub struct ImportResponse {
     /// Number of tracks successfully imported.
     #[schema(example = 10)]
     pub tracks_imported: usize,
-    /// Number of tracks skipped (duplicates).
+    /// Number of tracks skipped (already imported, or skipped by a plugin).
     #[schema(example = 2)]
     pub tracks_skipped: usize,
+    /// Number of tracks skipped because the same file is in the library
+    /// under another path.
+    #[schema(example = 1)]
+    pub tracks_duplicate: usize,
     /// Number of tracks that failed to import.
     #[schema(example = 0)]
     pub tracks_failed: usize,
@@ -926,6 +2413,11 @@ pub struct ImportResponse {
     pub albums_created: usize,
     /// Errors encountered during import.
     pub errors: Vec<String>,
+    /// Files that could not be read or imported.
+    #[schema(value_type = Vec<String>)]
+    pub failed_files: Vec<PathBuf>,
+    /// Whether the import was cancelled before it finished.
+    pub cancelled: bool,
 }
 
 impl From<ImportResult> for ImportResponse {
@@ -934,9 +2426,12 @@ impl From<ImportResult> for ImportResponse {
             tracks_found: result.tracks_found,
             tracks_imported: result.tracks_imported,
             tracks_skipped: result.tracks_skipped,
+            tracks_duplicate: result.tracks_duplicate,
             tracks_failed: result.tracks_failed,
             albums_created: result.albums_created,
             errors: result.errors,
+            failed_files: result.failed_files,
+            cancelled: result.cancelled,
         }
     }
 }
@@ -950,6 +2445,7 @@ impl From<ImportResult> for ImportResponse {
     responses(
         (status = 200, description = "Import completed", body = ImportResponse),
         (status = 400, description = "Invalid request (path doesn't exist)", body = ErrorResponse),
+        (status = 409, description = "A job with the same ID is running", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     )
 )]
@@ -957,6 +2453,101 @@ pub async fn import_music(
     State(state): State<Arc<AppState>>,
     Json(req): Json<ImportRequest>,
 ) -> Result<Json<ImportResponse>, ApiError> {
+    let options = import_options(&state.config, &req)?;
+
+    // Create the import service
+    let db = Arc::clone(&state.db);
+    let mut service = ImportService::new(db, &state.config);
+    if let Some(ref hooks) = state.hooks {
+        service = service.with_hooks(Arc::clone(hooks));
+    }
+
+    // Run the import, cancellable under its job ID if it has one
+    let job = req
+        .job_id
+        .as_deref()
+        .map(|id| {
+            state
+                .running_jobs
+                .start(id)
+                .ok_or_else(|| ApiError::Conflict(format!("Job already running: {id}")))
+        })
+        .transpose()?;
+    let result = match job {
+        Some(ref job) => {
+            service
+                .import_cancellable(&options, None, job.cancel_flag())
+                .await?
+        }
+        None => service.import(&options, None).await?,
+    };
+
+    Ok(Json(ImportResponse::from(result)))
+}
+
+/// Show what importing music from a directory would do.
+///
+/// The directory is scanned, and its tracks looked up and grouped into
+/// albums, without changing the library or the files. Plugin hooks are not
+/// run.
+#[utoipa::path(
+    post,
+    path = "/api/import/preview",
+    tag = "Import",
+    request_body = ImportRequest,
+    responses(
+        (status = 200, description = "What the import would do", body = ImportPreview),
+        (status = 400, description = "Invalid request (path doesn't exist)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn preview_import(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ImportRequest>,
+) -> Result<Json<ImportPreview>, ApiError> {
+    let options = import_options(&state.config, &req)?;
+    let service = ImportService::new(Arc::clone(&state.db), &state.config);
+    Ok(Json(service.preview(&options).await?))
+}
+
+/// Import history query parameters.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ImportHistoryQuery {
+    /// Maximum number of imports to return (default: 20, max:
+    /// `web.max_page_size`).
+    #[serde(default = "default_history_limit")]
+    #[param(default = 20, minimum = 1)]
+    pub limit: u32,
+}
+
+const fn default_history_limit() -> u32 {
+    DEFAULT_HISTORY_LIMIT
+}
+
+/// Get the most recent imports, newest first.
+///
+/// Imports that found nothing new are not recorded. Each import lists the
+/// files that failed, so that they can be imported again.
+#[utoipa::path(
+    get,
+    path = "/api/import/history",
+    tag = "Import",
+    params(ImportHistoryQuery),
+    responses(
+        (status = 200, description = "Recent imports", body = Vec<ImportRun>),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+pub async fn import_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ImportHistoryQuery>,
+) -> Result<Json<Vec<ImportRun>>, ApiError> {
+    let limit = query.limit.clamp(1, state.config.web.max_page_size.max(1));
+    Ok(Json(state.db.list_import_runs(limit).await?))
+}
+
+/// Import options for a request, checking that its path is a directory.
+fn import_options(config: &Config, req: &ImportRequest) -> Result<ImportOptions, ApiError> {
     let path = PathBuf::from(&req.path);
 
     // Validate the path exists
@@ -974,8 +2565,7 @@ pub async fn import_music(
         )));
     }
 
-    // Create import options
-    let options = ImportOptions {
+    Ok(ImportOptions {
         source_path: path,
         max_depth: req.max_depth,
         follow_symlinks: req.follow_symlinks,
@@ -983,19 +2573,19 @@ pub async fn import_music(
         min_match_score: req.min_match_score,
         create_albums: req.create_albums,
         fetch_album_art: req.fetch_album_art,
+        write_cover_files: req.write_cover_files,
+        embed_album_art: req.embed_album_art,
         write_tags: req.write_tags,
+        organize_into: config
+            .music_directory()
+            .filter(|_| config.import.organizes_files()),
+        move_files: config.import.move_files,
+        path_template: config.paths.path_template.clone(),
         compute_hashes: true,
-    };
-
-    // Create the import service
-    let config = Config::default();
-    let db = Arc::clone(&state.db);
-    let service = ImportService::new(db, &config);
-
-    // Run the import
-    let result = service.import(&options, None).await?;
-
-    Ok(Json(ImportResponse::from(result)))
+        normalize_genres: config.genres.normalize_on_import,
+        normalize_featured: config.featured.normalize_on_import,
+        compilation_min_artists: config.import.compilation_min_artists,
+    })
 }
 
 #[cfg(test)]
@@ -1005,7 +2595,7 @@ mod tests {
     #[test]
     fn test_default_pagination() {
         let query: PaginationQuery = serde_json::from_str("{}").unwrap();
-        assert_eq!(query.limit, DEFAULT_LIMIT);
+        assert_eq!(query.page_size(&WebConfig::default()), 50);
         assert_eq!(query.offset, 0);
     }
 
@@ -1013,7 +2603,25 @@ mod tests {
     fn test_custom_pagination() {
         let query: PaginationQuery =
             serde_json::from_str(r#"{"limit": 100, "offset": 50}"#).unwrap();
-        assert_eq!(query.limit, 100);
+        assert_eq!(query.page_size(&WebConfig::default()), 100);
         assert_eq!(query.offset, 50);
+
+        let config = WebConfig {
+            default_page_size: 20,
+            max_page_size: 80,
+            ..WebConfig::default()
+        };
+        assert_eq!(query.page_size(&config), 80);
+        let query: PaginationQuery = serde_json::from_str(r#"{"limit": 0}"#).unwrap();
+        assert_eq!(query.page_size(&config), 1);
+        let query: PaginationQuery = serde_json::from_str("{}").unwrap();
+        assert_eq!(query.page_size(&config), 20);
+    }
+
+    #[test]
+    fn test_next_offset() {
+        assert_eq!(next_offset(0, 50, 120), Some(50));
+        assert_eq!(next_offset(100, 20, 120), None);
+        assert_eq!(next_offset(200, 0, 120), None);
     }
 }
@@ -0,0 +1,203 @@
+//! Caching headers and conditional requests.
+//!
+//! Responses that rarely change, such as album art, say how long clients may
+//! keep them in a `Cache-Control` header, and carry validators, an `ETag`
+//! and a `Last-Modified` date. Once a client's copy is stale, it sends these
+//! back in `If-None-Match` and `If-Modified-Since`, and gets an empty
+//! `304 Not Modified` if its copy is still current, instead of the whole
+//! response again.
+
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use std::hash::{DefaultHasher, Hasher};
+
+/// Format of dates in HTTP headers, always in GMT.
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Caching headers of a response.
+#[derive(Debug, Clone)]
+pub struct CacheHeaders {
+    max_age: u32,
+    etag: Option<String>,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl CacheHeaders {
+    /// Let clients keep a response for `max_age` seconds.
+    #[must_use]
+    pub const fn new(max_age: u32) -> Self {
+        Self {
+            max_age,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// Tag the response, with a tag that changes whenever it does.
+    #[must_use]
+    pub fn with_etag(mut self, tag: &str) -> Self {
+        self.etag = Some(format!("\"{tag}\""));
+        self
+    }
+
+    /// Tag the response by a hash of its content.
+    ///
+    /// The hash is only stable for a build of the server, so clients fetch
+    /// the content again after an upgrade at worst.
+    #[must_use]
+    pub fn with_content_etag(self, data: &[u8]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(data);
+        self.with_etag(&format!("{:016x}", hasher.finish()))
+    }
+
+    /// Date the content of the response last changed.
+    #[must_use]
+    pub const fn with_last_modified(mut self, at: DateTime<Utc>) -> Self {
+        self.last_modified = Some(at);
+        self
+    }
+
+    /// Whether the copy that a request's conditional headers describe is
+    /// still current.
+    ///
+    /// `If-None-Match` is checked if it is sent, and `If-Modified-Since`
+    /// only otherwise.
+    #[must_use]
+    pub fn is_current(&self, request: &HeaderMap) -> bool {
+        if request.contains_key(header::IF_NONE_MATCH) {
+            return self
+                .etag
+                .as_ref()
+                .is_some_and(|etag| etag_matches(request, etag));
+        }
+        let since = request
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+        match (self.last_modified, since) {
+            // Dates in headers have whole seconds only
+            (Some(modified), Some(since)) => modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    /// Respond with `304 Not Modified` if the request's copy is current, or
+    /// with `response` otherwise, adding the caching headers either way.
+    pub fn respond(&self, request: &HeaderMap, response: impl IntoResponse) -> Response {
+        let mut response = if self.is_current(request) {
+            StatusCode::NOT_MODIFIED.into_response()
+        } else {
+            response.into_response()
+        };
+        self.add_to(response.headers_mut());
+        response
+    }
+
+    /// Add the caching headers to a response's headers.
+    pub fn add_to(&self, headers: &mut HeaderMap) {
+        if let Ok(value) = HeaderValue::from_str(&format!("public, max-age={}", self.max_age)) {
+            headers.insert(header::CACHE_CONTROL, value);
+        }
+        if let Some(value) = self
+            .etag
+            .as_ref()
+            .and_then(|etag| HeaderValue::from_str(etag).ok())
+        {
+            headers.insert(header::ETAG, value);
+        }
+        if let Some(value) = self
+            .last_modified
+            .and_then(|at| HeaderValue::from_str(&at.format(HTTP_DATE).to_string()).ok())
+        {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+    }
+}
+
+/// Whether the `If-None-Match` header of a request lists an entity tag.
+///
+/// Tags are compared weakly, as conditional GET requests are.
+#[must_use]
+pub fn etag_matches(request: &HeaderMap, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    request
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Have clients check static files with the server before using a copy.
+///
+/// Static files already carry a `Last-Modified` date, so checking them
+/// costs an empty `304 Not Modified` for each file that did not change.
+#[allow(clippy::unused_async)] // Middleware must be async
+pub async fn revalidate_static_files(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .entry(header::CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static("no-cache"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn request(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        let headers = request(header::IF_NONE_MATCH, r#""a", W/"b""#);
+        assert!(etag_matches(&headers, r#""a""#));
+        assert!(etag_matches(&headers, r#""b""#));
+        assert!(!etag_matches(&headers, r#""c""#));
+        assert!(etag_matches(&request(header::IF_NONE_MATCH, "*"), r#""c""#));
+        assert!(!etag_matches(&HeaderMap::new(), r#""a""#));
+    }
+
+    #[test]
+    fn test_cache_headers() {
+        let modified = Utc.with_ymd_and_hms(2024, 3, 1, 12, 30, 0).unwrap();
+        let cache = CacheHeaders::new(60)
+            .with_content_etag(b"cover")
+            .with_last_modified(modified);
+
+        let response = cache.respond(&HeaderMap::new(), "cover");
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[header::CACHE_CONTROL], "public, max-age=60");
+        assert_eq!(
+            headers[header::LAST_MODIFIED],
+            "Fri, 01 Mar 2024 12:30:00 GMT"
+        );
+        let etag = headers[header::ETAG].to_str().unwrap().to_string();
+
+        let response = cache.respond(&request(header::IF_NONE_MATCH, &etag), "cover");
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+
+        // A tag that does not match wins over a date that does
+        let mut headers = request(header::IF_NONE_MATCH, r#""other""#);
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Fri, 01 Mar 2024 12:30:00 GMT"),
+        );
+        assert!(!cache.is_current(&headers));
+
+        let since = |value| request(header::IF_MODIFIED_SINCE, value);
+        assert!(cache.is_current(&since("Fri, 01 Mar 2024 12:30:00 GMT")));
+        assert!(cache.is_current(&since("Sat, 02 Mar 2024 08:00:00 GMT")));
+        assert!(!cache.is_current(&since("Fri, 01 Mar 2024 12:29:59 GMT")));
+        assert!(!cache.is_current(&since("yesterday")));
+    }
+}
@@ -0,0 +1,159 @@
+//! Identifying tracks by their audio.
+//!
+//! A track is identified by looking up the [Chromaprint] fingerprint of its
+//! audio on [AcoustID], which links it to [MusicBrainz] recordings. Each
+//! recording is then looked up on `MusicBrainz` for its metadata, giving the
+//! candidates the web UI offers to pick from, with the changes each would
+//! make to the track.
+//!
+//! [Chromaprint]: https://acoustid.org/chromaprint
+//! [AcoustID]: https://acoustid.org/
+//! [MusicBrainz]: https://musicbrainz.org/
+
+use crate::error::ApiError;
+use apollo_audio::generate_fingerprint;
+use apollo_core::diff::{FieldChange, TrackDiff};
+use apollo_core::metadata::{Fingerprint, Track};
+use apollo_sources::SourceResult;
+use apollo_sources::acoustid::AcoustIdClient;
+use apollo_sources::musicbrainz::MusicBrainzClient;
+use chrono::Utc;
+use serde::Serialize;
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// Lowest `AcoustID` score of a match, unless another one is asked for.
+pub const DEFAULT_MIN_SCORE: f64 = 0.8;
+
+/// Most recordings looked up on `MusicBrainz` for one track.
+///
+/// `MusicBrainz` allows about one request a second, so this keeps an
+/// identification from taking long for fingerprints that match many
+/// recordings.
+pub const MAX_CANDIDATES: usize = 5;
+
+/// A recording that a track may be, with the metadata it would get.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct IdentifyCandidate {
+    /// `AcoustID` of the fingerprint match.
+    pub acoustid: String,
+    /// `MusicBrainz` ID of the recording.
+    pub recording_id: String,
+    /// Match score, from 0 to 1.
+    #[schema(example = 0.97)]
+    pub score: f64,
+    /// The track with the metadata of the recording applied.
+    pub track: Track,
+    /// Fields of the track that would change.
+    pub changes: Vec<FieldChange>,
+}
+
+/// Identifies tracks with `AcoustID` and `MusicBrainz`.
+pub struct TrackIdentifier {
+    acoustid: AcoustIdClient,
+    musicbrainz: MusicBrainzClient,
+}
+
+impl TrackIdentifier {
+    /// Identify tracks with an `AcoustID` and a `MusicBrainz` client.
+    #[must_use]
+    pub const fn new(acoustid: AcoustIdClient, musicbrainz: MusicBrainzClient) -> Self {
+        Self {
+            acoustid,
+            musicbrainz,
+        }
+    }
+
+    /// Find the recordings a track may be, best match first.
+    ///
+    /// Only matches with at least `min_score` are returned. Recordings that
+    /// cannot be looked up on `MusicBrainz` are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `AcoustID` lookup fails.
+    pub async fn candidates(
+        &self,
+        track: &Track,
+        fingerprint: &Fingerprint,
+        min_score: f64,
+    ) -> SourceResult<Vec<IdentifyCandidate>> {
+        let results = self
+            .acoustid
+            .lookup(&fingerprint.fingerprint, fingerprint.duration_secs)
+            .await?;
+
+        // Results are sorted by score, so the candidates are as well
+        let matches: Vec<_> = results
+            .into_iter()
+            .filter(|result| result.score >= min_score)
+            .flat_map(|result| {
+                result
+                    .recordings
+                    .into_iter()
+                    .map(move |recording| (result.id.clone(), result.score, recording.id))
+            })
+            .take(MAX_CANDIDATES)
+            .collect();
+
+        let mut candidates = Vec::with_capacity(matches.len());
+        for (acoustid, score, recording_id) in matches {
+            let recording = match self
+                .musicbrainz
+                .lookup_recording(&recording_id, &["artists", "releases", "isrcs"])
+                .await
+            {
+                Ok(recording) => recording,
+                Err(e) => {
+                    warn!("Failed to look up recording {recording_id}: {e}");
+                    continue;
+                }
+            };
+            let mut identified = track.clone();
+            identified.acoustid = Some(acoustid.clone());
+            recording.apply_to_track(&mut identified);
+            let changes = TrackDiff::between(track, &identified).changes;
+            candidates.push(IdentifyCandidate {
+                acoustid,
+                recording_id,
+                score,
+                track: identified,
+                changes,
+            });
+        }
+        Ok(candidates)
+    }
+}
+
+/// Compute the fingerprint of a track's audio.
+///
+/// Decoding the audio takes a while, so this runs on a blocking thread.
+///
+/// # Errors
+///
+/// Returns an error if the track has no file, or its audio cannot be
+/// decoded.
+pub async fn fingerprint_track(track: &Track) -> Result<Fingerprint, ApiError> {
+    if !track.path.exists() {
+        return Err(ApiError::NotFound(format!(
+            "Track file not found: {}",
+            track.path.display()
+        )));
+    }
+    let path = track.path.clone();
+    let result = tokio::task::spawn_blocking(move || generate_fingerprint(&path))
+        .await
+        .map_err(|e| ApiError::Internal(format!("Fingerprinting failed: {e}")))?
+        .map_err(|e| {
+            ApiError::Internal(format!(
+                "Failed to fingerprint {}: {e}",
+                track.path.display()
+            ))
+        })?;
+
+    Ok(Fingerprint {
+        fingerprint: result.fingerprint,
+        duration_secs: result.duration,
+        computed_at: Utc::now(),
+    })
+}
@@ -2,27 +2,69 @@
 //!
 //! This module provides a complete import pipeline that:
 //! 1. Scans a directory for audio files
-//! 2. Reads metadata from files
-//! 3. Optionally looks up metadata from `MusicBrainz`
-//! 4. Groups tracks into albums
-//! 5. Creates album entries in the database
-//! 6. Optionally fetches album art
-//! 7. Optionally writes tags back to files
-//! 8. Imports tracks into the database
-
-use apollo_audio::{ScanOptions, ScanProgress, scan_directory, write_metadata};
-use apollo_core::Config;
-use apollo_core::metadata::{Album, AlbumId, Track};
-use apollo_db::SqliteLibrary;
+//! 2. Reads metadata from files, skipping files that are already in the
+//!    library under another path
+//! 3. Optionally matches albums to `MusicBrainz` releases, and other tracks
+//!    to recordings
+//! 4. Runs the `on_import` hooks of plugins, which may change or skip tracks
+//! 5. Groups tracks into albums
+//! 6. Creates album entries in the database, after their `on_album_import`
+//!    hooks
+//! 7. Optionally copies or moves files into the music directory
+//! 8. Optionally writes tags back to files, or queues them to be written
+//!    later
+//! 9. Optionally fetches album art, which is stored with the album and can
+//!    be written next to or embedded into the files
+//! 10. Imports tracks into the database, then runs the `post_import` and
+//!     `post_album_import` hooks
+//!
+//! Lookups, normalization of genres and featured artists, and hooks leave
+//! the fields locked by
+//! `locks.fields` as they are in the files. The fields they do change are
+//! recorded as coming from `MusicBrainz` or a plugin, rather than the
+//! file's tags.
+//!
+//! [`ImportService::preview`] runs the first steps without changing
+//! anything, to show how the tracks of a directory would be imported.
+
+use crate::writeback::write_tags;
+use apollo_audio::{
+    AudioError, CoverArt, OrganizeOptions, OrganizeResult, ScanOptions, ScanProgress,
+    compute_file_hash, embed_art, find_cover_file, is_same_file, organize_file_with_context,
+    read_embedded_art, scan_directory, unplace_file,
+};
+use apollo_core::config::{FeaturedConfig, ImportProfile};
+use apollo_core::diff::FieldChange;
+use apollo_core::edit::EditField;
+use apollo_core::featured::CreditNormalizer;
+use apollo_core::genre::GenreNormalizer;
+use apollo_core::import_run::ImportRun;
+use apollo_core::library::Library;
+use apollo_core::lock::FieldLocks;
+use apollo_core::metadata::{Album, AlbumId, Artwork, Track, TrackId, VARIOUS_ARTISTS};
+use apollo_core::provenance::{MetadataSource, Provenance};
+use apollo_core::{AlbumSet, Config, PathTemplate, TemplateContext, TrackDiff};
+use apollo_lua::{HookResult, LuaWorkerPool};
 use apollo_sources::coverart::{CoverArtClient, ImageSize};
-use apollo_sources::musicbrainz::MusicBrainzClient;
+use apollo_sources::musicbrainz::{CachedMusicBrainzClient, Release, ReleaseMatcher};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, instrument, warn};
+use utoipa::ToSchema;
+
+/// Number of albums to look up on `MusicBrainz` at once.
+///
+/// Requests still wait for the client's rate limit, but the responses of
+/// one album are read while the next album's requests are sent.
+const LOOKUP_CONCURRENCY: usize = 4;
 
 /// Options for controlling the import process.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -36,22 +78,42 @@ pub struct ImportOptions {
     pub follow_symlinks: bool,
     /// Look up metadata from `MusicBrainz` for tracks without MBIDs.
     pub auto_tag: bool,
-    /// Minimum score for `MusicBrainz` matches (0-100).
+    /// Minimum score for a `MusicBrainz` release to be applied to an album's
+    /// tracks, or a recording to a track without an album (0-100).
     pub min_match_score: u8,
     /// Group tracks into albums and create album entries.
     pub create_albums: bool,
     /// Fetch album art from Cover Art Archive.
     pub fetch_album_art: bool,
+    /// Write fetched album art as `folder.jpg` next to the files, unless
+    /// there is a cover file already.
+    pub write_cover_files: bool,
+    /// Embed fetched album art into files that have none.
+    pub embed_album_art: bool,
     /// Write updated metadata back to files.
     pub write_tags: bool,
+    /// Copy files into this directory, laid out by `path_template`, and
+    /// import the copies.
+    pub organize_into: Option<PathBuf>,
+    /// Move files into `organize_into` instead of copying them.
+    pub move_files: bool,
+    /// Template for the paths of files in `organize_into`.
+    pub path_template: String,
     /// Compute file hashes for deduplication.
     pub compute_hashes: bool,
+    /// Normalize track genres to canonical names.
+    pub normalize_genres: bool,
+    /// Credit featured artists of tracks as `featured.style` says.
+    pub normalize_featured: bool,
+    /// Minimum number of distinct track artists for an album to be detected
+    /// as a compilation (0 disables detection by artist count).
+    pub compilation_min_artists: usize,
 }
 
 impl ImportOptions {
     /// Create options from configuration.
     #[must_use]
-    pub const fn from_config(config: &Config) -> Self {
+    pub fn from_config(config: &Config) -> Self {
         Self {
             source_path: PathBuf::new(),
             max_depth: None,
@@ -60,8 +122,18 @@ impl ImportOptions {
             min_match_score: 80,
             create_albums: config.import.auto_create_albums,
             fetch_album_art: config.import.copy_album_art,
+            write_cover_files: config.import.copy_album_art,
+            embed_album_art: false,
             write_tags: config.import.write_tags,
+            organize_into: config
+                .music_directory()
+                .filter(|_| config.import.organizes_files()),
+            move_files: config.import.move_files,
+            path_template: config.paths.path_template.clone(),
             compute_hashes: config.import.compute_hashes,
+            normalize_genres: config.genres.normalize_on_import,
+            normalize_featured: config.featured.normalize_on_import,
+            compilation_min_artists: config.import.compilation_min_artists,
         }
     }
 
@@ -71,6 +143,21 @@ impl ImportOptions {
         self.source_path = path;
         self
     }
+
+    /// Apply the settings of an import profile.
+    #[must_use]
+    pub fn with_profile(mut self, profile: &ImportProfile) -> Self {
+        if let Some(auto_tag) = profile.auto_tag {
+            self.auto_tag = auto_tag;
+        }
+        if let Some(ref path_template) = profile.path_template {
+            self.path_template.clone_from(path_template);
+        }
+        if let Some(copy_album_art) = profile.copy_album_art {
+            self.fetch_album_art = copy_album_art;
+        }
+        self
+    }
 }
 
 /// Progress update during import.
@@ -82,7 +169,7 @@ pub enum ImportProgress {
         files_found: usize,
         current_file: Option<String>,
     },
-    /// Looking up metadata for a track.
+    /// Looking up metadata, for the album of a track.
     LookingUp { track_index: usize, total: usize },
     /// Creating albums.
     CreatingAlbums { count: usize },
@@ -106,21 +193,99 @@ pub struct ImportResult {
     pub tracks_found: usize,
     /// Number of tracks successfully imported.
     pub tracks_imported: usize,
-    /// Number of tracks skipped (duplicates).
+    /// Number of tracks skipped (already imported, or skipped by a plugin).
     pub tracks_skipped: usize,
+    /// Number of tracks skipped because the same file is in the library, or
+    /// earlier in the import, under another path.
+    pub tracks_duplicate: usize,
     /// Number of tracks that failed to import.
     pub tracks_failed: usize,
     /// Number of albums created.
     pub albums_created: usize,
     /// Errors encountered during import.
     pub errors: Vec<String>,
+    /// Files that could not be read or imported.
+    pub failed_files: Vec<PathBuf>,
+    /// Whether the import was cancelled before it finished.
+    pub cancelled: bool,
+}
+
+impl ImportResult {
+    /// Whether the import changed the library or ran into problems, as
+    /// opposed to finding nothing new.
+    #[must_use]
+    pub const fn is_eventful(&self) -> bool {
+        self.tracks_imported > 0
+            || self.tracks_failed > 0
+            || self.albums_created > 0
+            || !self.errors.is_empty()
+            || self.cancelled
+    }
+}
+
+/// What an import would do, from [`ImportService::preview`].
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ImportPreview {
+    /// Number of tracks found.
+    #[schema(example = 12)]
+    pub tracks_found: usize,
+    /// Albums that would be created, with their tracks.
+    pub albums: Vec<PreviewAlbum>,
+    /// Tracks that would be imported without an album.
+    pub tracks: Vec<PreviewTrack>,
+    /// Tracks that would be skipped because their files are in the library,
+    /// or earlier in the import, under another path.
+    pub duplicates: Vec<ImportDuplicate>,
+    /// Errors encountered while scanning.
+    pub errors: Vec<String>,
+}
+
+/// An album that an import would create.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PreviewAlbum {
+    /// The album, as it would be created.
+    pub album: Album,
+    /// Tracks of the album.
+    pub tracks: Vec<PreviewTrack>,
+}
+
+/// A track that an import would add.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PreviewTrack {
+    /// The track, with the metadata it would be imported with.
+    pub track: Track,
+    /// Fields that differ from the tags in the file.
+    pub changes: Vec<FieldChange>,
+}
+
+/// A file that an import skips because it is a copy of another.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportDuplicate {
+    /// Path of the skipped file.
+    #[schema(value_type = String, example = "/home/user/Music/NewAlbum/01-Track.mp3")]
+    pub path: PathBuf,
+    /// Path of the file it is a copy of.
+    #[schema(value_type = String, example = "/music/Artist/Album/01-Track.mp3")]
+    pub original: PathBuf,
+    /// Whether the original is in the library, rather than in the import.
+    pub in_library: bool,
 }
 
 /// Service for importing music into the library.
 pub struct ImportService {
-    db: Arc<SqliteLibrary>,
-    mb_client: Option<MusicBrainzClient>,
+    db: Arc<dyn Library>,
+    mb_client: Option<CachedMusicBrainzClient>,
     art_client: Option<CoverArtClient>,
+    genre_normalizer: GenreNormalizer,
+    credit_normalizer: CreditNormalizer,
+    profiles: Vec<ImportProfile>,
+    hooks: Option<Arc<LuaWorkerPool>>,
+    /// Fields whose tags are written to files.
+    write_fields: Vec<EditField>,
+    /// Queue tags to be written later instead of writing them.
+    defer_writes: bool,
+    /// Fields that lookups and plugins leave as they are in the files.
+    locks: FieldLocks,
 }
 
 impl ImportService {
@@ -131,9 +296,9 @@ impl ImportService {
     /// * `db` - Database connection
     /// * `config` - Configuration for API clients
     #[must_use]
-    pub fn new(db: Arc<SqliteLibrary>, config: &Config) -> Self {
+    pub fn new(db: Arc<dyn Library>, config: &Config) -> Self {
         let mb_client = if config.musicbrainz.enabled {
-            MusicBrainzClient::new(
+            CachedMusicBrainzClient::with_defaults(
                 &config.musicbrainz.app_name,
                 &config.musicbrainz.app_version,
                 &config.musicbrainz.contact_email,
@@ -153,21 +318,111 @@ impl ImportService {
             db,
             mb_client,
             art_client,
+            genre_normalizer: GenreNormalizer::from_config(&config.genres),
+            credit_normalizer: CreditNormalizer::from_config(&config.featured),
+            profiles: config.import.profiles.clone(),
+            hooks: None,
+            write_fields: config.write.written_fields(),
+            defer_writes: config.write.deferred,
+            locks: config.locks.locked_fields(),
         }
     }
 
     /// Create a new import service with just a database (no external lookups).
     #[must_use]
-    pub const fn new_basic(db: Arc<SqliteLibrary>) -> Self {
+    pub fn new_basic(db: Arc<dyn Library>) -> Self {
         Self {
             db,
             mb_client: None,
             art_client: None,
+            genre_normalizer: GenreNormalizer::new(),
+            credit_normalizer: CreditNormalizer::from_config(&FeaturedConfig::default()),
+            profiles: Vec::new(),
+            hooks: None,
+            write_fields: EditField::ALL.to_vec(),
+            defer_writes: false,
+            locks: FieldLocks::default(),
+        }
+    }
+
+    /// Run the import hooks of the plugins loaded in a worker pool.
+    #[must_use]
+    pub fn with_hooks(mut self, hooks: Arc<LuaWorkerPool>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Keep some fields as they are in the files.
+    #[must_use]
+    pub fn with_locks(mut self, locks: FieldLocks) -> Self {
+        self.locks = locks;
+        self
+    }
+
+    /// Download covers for albums in the library that have none.
+    ///
+    /// Only albums matched to a [MusicBrainz](https://musicbrainz.org/)
+    /// release are looked up. Covers are stored in the library, and written
+    /// to the album directories and embedded as `options` say, like covers
+    /// fetched on import. Returns the number of covers downloaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the library cannot be read or updated.
+    pub async fn fetch_missing_art(
+        &self,
+        options: &ImportOptions,
+    ) -> Result<usize, crate::error::ApiError> {
+        let Some(ref client) = self.art_client else {
+            return Ok(0);
+        };
+
+        let mut fetched = 0;
+        // Failures to save covers are logged by save_cover
+        let mut result = ImportResult::default();
+        for album in self.db.list_albums(u32::MAX, 0).await? {
+            let Some(ref mbid) = album.musicbrainz_id else {
+                continue;
+            };
+            if self.db.get_album_artwork(&album.id).await?.is_some() {
+                continue;
+            }
+            let artwork = match download_cover(client, mbid).await {
+                Ok(artwork) => artwork,
+                Err(e) => {
+                    debug!("No album art for {} - {}: {e}", album.artist, album.title);
+                    continue;
+                }
+            };
+            self.db.set_album_artwork(&album.id, &artwork).await?;
+            fetched += 1;
+
+            // Embedding art changes the files' hashes
+            let mut tracks = self.db.get_album_tracks(&album.id).await?;
+            let hashes: Vec<String> = tracks.iter().map(|t| t.file_hash.clone()).collect();
+            save_cover(&artwork, tracks.iter_mut().collect(), options, &mut result);
+            for (track, hash) in tracks.iter().zip(hashes) {
+                if track.file_hash != hash {
+                    self.db.update_track(track).await?;
+                }
+            }
+        }
+        Ok(fetched)
+    }
+
+    /// Drop expired [MusicBrainz](https://musicbrainz.org/) lookups from the
+    /// cache.
+    pub async fn prune_cache(&self) {
+        if let Some(ref client) = self.mb_client {
+            client.prune_cache().await;
         }
     }
 
     /// Import music from a directory.
     ///
+    /// The settings of the first configured import profile that matches the
+    /// source path override `options`.
+    ///
     /// # Arguments
     ///
     /// * `options` - Import configuration options
@@ -176,13 +431,101 @@ impl ImportService {
     /// # Errors
     ///
     /// Returns an error if scanning fails.
-    #[allow(clippy::too_many_lines)]
     pub async fn import(
         &self,
         options: &ImportOptions,
         progress_tx: Option<mpsc::Sender<ImportProgress>>,
+    ) -> Result<ImportResult, crate::error::ApiError> {
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.import_cancellable(options, progress_tx, &cancel).await
+    }
+
+    /// Import music from a directory until `cancel` is set.
+    ///
+    /// Cancellation is checked while scanning and looking up metadata, and
+    /// between the steps of the import. Nothing is changed if the import is
+    /// cancelled before albums are created. After that, tracks that were
+    /// imported stay in the library, and files placed for the others are
+    /// put back. The result of a cancelled import has `cancelled` set.
+    ///
+    /// Imports that import tracks, or fail to, are recorded in the import
+    /// history. Those that find nothing new are not, so that imports that
+    /// run on a schedule do not fill it up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if scanning fails.
+    #[instrument(skip_all, fields(source = %options.source_path.display()))]
+    pub async fn import_cancellable(
+        &self,
+        options: &ImportOptions,
+        progress_tx: Option<mpsc::Sender<ImportProgress>>,
+        cancel: &Arc<AtomicBool>,
+    ) -> Result<ImportResult, crate::error::ApiError> {
+        let started_at = Utc::now();
+        let start = Instant::now();
+        let result = self.run_import(options, progress_tx, cancel).await?;
+        if result.is_eventful() {
+            self.record_run(options, &result, started_at, start.elapsed())
+                .await;
+        }
+        Ok(result)
+    }
+
+    /// Record an import in the import history.
+    ///
+    /// Failing to record it is logged, as the import itself is done.
+    async fn record_run(
+        &self,
+        options: &ImportOptions,
+        result: &ImportResult,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+    ) {
+        let run = ImportRun {
+            id: 0,
+            source_path: options.source_path.clone(),
+            started_at,
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            options: serde_json::to_value(options).unwrap_or_default(),
+            tracks_found: result.tracks_found,
+            tracks_imported: result.tracks_imported,
+            tracks_skipped: result.tracks_skipped,
+            tracks_duplicate: result.tracks_duplicate,
+            tracks_failed: result.tracks_failed,
+            albums_created: result.albums_created,
+            cancelled: result.cancelled,
+            errors: result.errors.clone(),
+            failed_files: result.failed_files.clone(),
+        };
+        if let Err(e) = self.db.record_import_run(&run).await {
+            warn!(
+                "Failed to record import of {}: {e}",
+                run.source_path.display()
+            );
+        }
+    }
+
+    /// Run an import, as [`Self::import_cancellable`] describes.
+    #[allow(clippy::too_many_lines)]
+    async fn run_import(
+        &self,
+        options: &ImportOptions,
+        progress_tx: Option<mpsc::Sender<ImportProgress>>,
+        cancel: &Arc<AtomicBool>,
     ) -> Result<ImportResult, crate::error::ApiError> {
         let mut result = ImportResult::default();
+        let options = &self.profile_options(options);
+
+        // Check the template before anything is changed
+        let template = options
+            .organize_into
+            .as_ref()
+            .map(|_| PathTemplate::parse(&options.path_template))
+            .transpose()
+            .map_err(|e| {
+                crate::error::ApiError::BadRequest(format!("Invalid path template: {e}"))
+            })?;
 
         // Step 1: Scan directory
         info!("Scanning directory: {}", options.source_path.display());
@@ -202,47 +545,83 @@ impl ImportService {
             compute_hashes: options.compute_hashes,
         };
 
-        let cancel = Arc::new(AtomicBool::new(false));
-
         let no_callback: Option<fn(&ScanProgress)> = None;
-        let scan_result = scan_directory(
+        let scan_result = match scan_directory(
             &options.source_path,
             &scan_options,
-            Some(&cancel),
+            Some(cancel),
             no_callback,
-        )
-        .map_err(|e| crate::error::ApiError::Internal(e.to_string()))?;
+        ) {
+            Ok(scan_result) => scan_result,
+            Err(AudioError::ScanCancelled) => return Ok(cancelled(result)),
+            Err(e) => return Err(crate::error::ApiError::Internal(e.to_string())),
+        };
 
         result.tracks_found = scan_result.tracks.len();
 
         // Collect errors from scanning
         for (path, error) in &scan_result.errors {
             result.errors.push(format!("{}: {}", path.display(), error));
+            result.failed_files.push(path.clone());
         }
 
         if scan_result.tracks.is_empty() {
             return Ok(result);
         }
 
+        // Skip copies of files that are already in the library
+        let (mut tracks, duplicates) = self.skip_content_duplicates(scan_result.tracks).await;
+        result.tracks_duplicate = duplicates.len();
+        if is_cancelled(cancel) {
+            return Ok(cancelled(result));
+        }
+
         // Step 2: Optionally look up metadata from MusicBrainz
-        let mut tracks = scan_result.tracks;
+        let mut releases = HashMap::new();
+        let mut provenance = HashMap::new();
 
         if options.auto_tag
             && let Some(ref mb_client) = self.mb_client
         {
+            let scanned = tracks.clone();
             tracks = self
                 .lookup_metadata(
                     mb_client,
                     tracks,
                     options.min_match_score,
+                    &mut releases,
                     progress_tx.as_ref(),
+                    cancel,
                 )
                 .await;
+            if is_cancelled(cancel) {
+                return Ok(cancelled(result));
+            }
+            restore_locked(&self.locks, &scanned, &mut tracks);
+            record_sources(
+                &mut provenance,
+                &scanned,
+                &tracks,
+                MetadataSource::MusicBrainz,
+            );
+        }
+
+        self.normalize_tracks(options, &mut tracks);
+
+        // Step 3: Run plugin hooks, which may change or skip tracks
+        if let Some(ref hooks) = self.hooks {
+            let looked_up = tracks.clone();
+            tracks = Self::run_import_hooks(hooks, tracks, &mut result).await?;
+            restore_locked(&self.locks, &looked_up, &mut tracks);
+            record_sources(&mut provenance, &looked_up, &tracks, MetadataSource::Plugin);
+        }
+        if is_cancelled(cancel) {
+            return Ok(cancelled(result));
         }
 
-        // Step 3: Group tracks into albums and create album entries
-        let album_map = if options.create_albums {
-            let albums = Self::group_into_albums(&tracks);
+        // Step 4: Group tracks into albums and create album entries
+        let albums_created = if options.create_albums {
+            let albums = group_into_albums(&tracks);
             if let Some(ref tx) = progress_tx {
                 let _ = tx
                     .send(ImportProgress::CreatingAlbums {
@@ -250,27 +629,51 @@ impl ImportService {
                     })
                     .await;
             }
-            self.create_album_entries(&albums, &mut result).await
+            self.create_album_entries(
+                &mut tracks,
+                &albums,
+                &releases,
+                options.compilation_min_artists,
+                &mut result,
+            )
+            .await?
         } else {
-            HashMap::new()
+            Vec::new()
         };
 
-        // Step 4: Optionally fetch album art
+        // Step 5: Optionally copy or move files into the music directory
+        let mut placed = HashMap::new();
+        if let (Some(dir), Some(template)) = (&options.organize_into, &template) {
+            (tracks, placed) = self
+                .place_files(tracks, dir, template, options, &mut result)
+                .await?;
+        }
+
+        // Step 6: Optionally write tags back to files, unless they are queued
+        // once the tracks are in the library
+        if options.write_tags && !self.defer_writes && !is_cancelled(cancel) {
+            self.write_tags_to_files(&mut tracks, &mut result);
+        }
+
+        // Step 7: Optionally fetch album art
         if options.fetch_album_art
+            && !is_cancelled(cancel)
             && let Some(ref art_client) = self.art_client
         {
-            self.fetch_album_art(art_client, &album_map, progress_tx.as_ref())
-                .await;
-        }
-
-        // Step 5: Optionally write tags back to files
-        if options.write_tags {
-            Self::write_tags_to_files(&tracks, &mut result);
+            self.fetch_album_art(
+                art_client,
+                &albums_created,
+                &mut tracks,
+                options,
+                &mut result,
+                progress_tx.as_ref(),
+            )
+            .await;
         }
 
-        // Step 6: Import tracks into database
+        // Step 8: Import tracks into database
         let total = tracks.len();
-        for mut track in tracks {
+        for track in tracks {
             if let Some(ref tx) = progress_tx {
                 let _ = tx
                     .send(ImportProgress::Importing {
@@ -282,28 +685,51 @@ impl ImportService {
                     .await;
             }
 
-            // Link track to album if we created one
-            if let Some(album_title) = track.album_title.as_ref() {
-                let artist = track.album_artist.as_ref().unwrap_or(&track.artist);
-                let key = format!("{}::{}", artist.to_lowercase(), album_title.to_lowercase());
-                if let Some(album_id) = album_map.get(&key) {
-                    track.album_id = Some(album_id.clone());
-                }
+            if is_cancelled(cancel) {
+                // Files placed for tracks that are not imported go back
+                unplace(&placed, &track.path, &mut result);
+                result.cancelled = true;
+                continue;
             }
 
             match self.db.add_track(&track).await {
                 Ok(_) => {
                     result.tracks_imported += 1;
                     debug!("Imported: {} - {}", track.artist, track.title);
+                    if let Some(provenance) = provenance.remove(&track.id)
+                        && let Err(e) = self.db.record_provenance(&track.id, &provenance).await
+                    {
+                        warn!(
+                            "Failed to record the sources of {}: {e}",
+                            track.path.display()
+                        );
+                    }
+                    if options.write_tags
+                        && self.defer_writes
+                        && let Err(e) = self.db.queue_write(&track.id, &[]).await
+                    {
+                        warn!("Failed to queue tags of {}: {e}", track.path.display());
+                        result.errors.push(format!(
+                            "Failed to queue tags of {}: {e}",
+                            track.path.display()
+                        ));
+                    }
+                    if let Some(ref hooks) = self.hooks
+                        && let Err(e) = hooks.run_post_import_async(track).await
+                    {
+                        warn!("post_import hook failed: {e}");
+                        result.errors.push(e.to_string());
+                    }
                 }
-                Err(apollo_db::DbError::Sqlx(ref e))
-                    if e.to_string().contains("UNIQUE constraint") =>
-                {
+                Err(apollo_core::Error::Duplicate(_)) => {
+                    unplace(&placed, &track.path, &mut result);
                     result.tracks_skipped += 1;
                     debug!("Skipped (duplicate): {} - {}", track.artist, track.title);
                 }
                 Err(e) => {
+                    unplace(&placed, &track.path, &mut result);
                     result.tracks_failed += 1;
+                    result.failed_files.push(track.path.clone());
                     result.errors.push(format!(
                         "Failed to import {} - {}: {e}",
                         track.artist, track.title
@@ -313,14 +739,34 @@ impl ImportService {
             }
         }
 
+        if let Some(ref hooks) = self.hooks
+            && !result.cancelled
+        {
+            for album in albums_created {
+                if let Err(e) = hooks.run_post_album_import_async(album).await {
+                    warn!("post_album_import hook failed: {e}");
+                    result.errors.push(e.to_string());
+                }
+            }
+        }
+
         if let Some(ref tx) = progress_tx {
             let _ = tx.send(ImportProgress::Complete(result.clone())).await;
         }
 
+        debug!(
+            monotonic_counter.import_tracks_imported = result.tracks_imported,
+            monotonic_counter.import_tracks_skipped = result.tracks_skipped,
+            monotonic_counter.import_tracks_duplicate = result.tracks_duplicate,
+            monotonic_counter.import_tracks_failed = result.tracks_failed,
+            monotonic_counter.import_albums_created = result.albums_created,
+            "Import metrics"
+        );
         info!(
-            "Import complete: {} imported, {} skipped, {} failed, {} albums created",
+            "Import complete: {} imported, {} skipped, {} duplicates, {} failed, {} albums created",
             result.tracks_imported,
             result.tracks_skipped,
+            result.tracks_duplicate,
             result.tracks_failed,
             result.albums_created
         );
@@ -328,173 +774,561 @@ impl ImportService {
         Ok(result)
     }
 
+    /// Show what importing music from a directory would do, without
+    /// changing the library or the files.
+    ///
+    /// The directory is scanned and its tracks are looked up and grouped
+    /// into albums as an import would, but plugin hooks are not run, and
+    /// files are neither placed nor tagged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if scanning fails.
+    #[instrument(skip_all, fields(source = %options.source_path.display()))]
+    pub async fn preview(
+        &self,
+        options: &ImportOptions,
+    ) -> Result<ImportPreview, crate::error::ApiError> {
+        let options = &self.profile_options(options);
+        let scan_options = ScanOptions {
+            recursive: true,
+            max_depth: options.max_depth,
+            follow_symlinks: options.follow_symlinks,
+            compute_hashes: options.compute_hashes,
+        };
+        let no_callback: Option<fn(&ScanProgress)> = None;
+        let scan_result = scan_directory(&options.source_path, &scan_options, None, no_callback)
+            .map_err(|e| crate::error::ApiError::Internal(e.to_string()))?;
+
+        let mut preview = ImportPreview {
+            tracks_found: scan_result.tracks.len(),
+            errors: scan_result
+                .errors
+                .iter()
+                .map(|(path, error)| format!("{}: {}", path.display(), error))
+                .collect(),
+            ..ImportPreview::default()
+        };
+
+        let (originals, duplicates) = self.skip_content_duplicates(scan_result.tracks).await;
+        preview.duplicates = duplicates;
+
+        let mut tracks = originals.clone();
+        let mut releases = HashMap::new();
+        if options.auto_tag
+            && let Some(ref mb_client) = self.mb_client
+        {
+            let cancel = AtomicBool::new(false);
+            tracks = self
+                .lookup_metadata(
+                    mb_client,
+                    tracks,
+                    options.min_match_score,
+                    &mut releases,
+                    None,
+                    &cancel,
+                )
+                .await;
+            restore_locked(&self.locks, &originals, &mut tracks);
+        }
+        self.normalize_tracks(options, &mut tracks);
+
+        // Lookups keep the order of the tracks of each album, but not of
+        // the albums, so tracks are matched to their files by path
+        let originals: HashMap<&Path, &Track> = originals
+            .iter()
+            .map(|track| (track.path.as_path(), track))
+            .collect();
+        let preview_track = |track: &Track| PreviewTrack {
+            changes: originals
+                .get(track.path.as_path())
+                .map(|original| TrackDiff::between(original, track).changes)
+                .unwrap_or_default(),
+            track: track.clone(),
+        };
+
+        let albums = if options.create_albums {
+            group_into_albums(&tracks)
+        } else {
+            Vec::new()
+        };
+        let mut in_album = vec![false; tracks.len()];
+        for indices in &albums {
+            let album_tracks: Vec<&Track> = indices.iter().map(|&i| &tracks[i]).collect();
+            if album_tracks.is_empty() {
+                continue;
+            }
+            let album = new_album(&album_tracks, &releases, options.compilation_min_artists);
+            for &i in indices {
+                in_album[i] = true;
+            }
+            preview.albums.push(PreviewAlbum {
+                tracks: album_tracks
+                    .iter()
+                    .map(|track| {
+                        let mut entry = preview_track(track);
+                        entry.track.album_id = Some(album.id.clone());
+                        entry
+                    })
+                    .collect(),
+                album,
+            });
+        }
+        preview.tracks = tracks
+            .iter()
+            .zip(in_album)
+            .filter(|(_, in_album)| !in_album)
+            .map(|(track, _)| preview_track(track))
+            .collect();
+
+        Ok(preview)
+    }
+
+    /// The options for importing from a directory, with the settings of the
+    /// first import profile that matches it applied.
+    fn profile_options(&self, options: &ImportOptions) -> ImportOptions {
+        let profile = self
+            .profiles
+            .iter()
+            .find(|profile| profile.matches(&options.source_path));
+        profile.map_or_else(
+            || options.clone(),
+            |profile| {
+                info!("Using import profile: {}", profile.pattern);
+                options.clone().with_profile(profile)
+            },
+        )
+    }
+
     /// Look up metadata from `MusicBrainz` for tracks.
+    ///
+    /// The tracks of each album are matched to a release together, and the
+    /// best release is applied to all of them if it scores at least
+    /// `min_score`. Tracks without an album are matched one recording at a
+    /// time, with one search for tracks with the same title and artist. Up
+    /// to [`LOOKUP_CONCURRENCY`] albums are looked up at once, sharing the
+    /// client's rate limit and cache. The matched release of each album is
+    /// added to `releases`, keyed by [`album_key`].
+    #[instrument(skip_all)]
     async fn lookup_metadata(
         &self,
-        client: &MusicBrainzClient,
-        mut tracks: Vec<Track>,
+        client: &CachedMusicBrainzClient,
+        tracks: Vec<Track>,
         min_score: u8,
+        releases: &mut HashMap<String, Release>,
         progress_tx: Option<&mpsc::Sender<ImportProgress>>,
+        cancel: &AtomicBool,
     ) -> Vec<Track> {
         let total = tracks.len();
+        let matcher = ReleaseMatcher::cached(client);
 
-        for (i, track) in tracks.iter_mut().enumerate() {
+        let mut lookups = futures::stream::iter(lookup_batches(tracks))
+            .map(|batch| self.lookup_batch(client, &matcher, batch, min_score, cancel))
+            .buffered(LOOKUP_CONCURRENCY);
+        let mut looked_up = Vec::with_capacity(total);
+        while let Some((batch, matched)) = lookups.next().await {
+            looked_up.extend(batch);
+            for (key, release) in matched {
+                releases.entry(key).or_insert(release);
+            }
             if let Some(tx) = progress_tx {
                 let _ = tx
                     .send(ImportProgress::LookingUp {
-                        track_index: i,
+                        track_index: looked_up.len(),
                         total,
                     })
                     .await;
             }
+        }
 
-            // Skip if already has a MusicBrainz ID
-            if track.musicbrainz_id.is_some() {
-                continue;
-            }
-
-            // Try to find a match
-            let album = track.album_title.as_deref();
-            #[allow(clippy::cast_possible_truncation)]
-            let duration_ms = track.duration.as_millis() as u64;
-
-            match client
-                .find_best_recording(
-                    &track.title,
-                    &track.artist,
-                    album,
-                    Some(duration_ms),
-                    min_score,
-                )
-                .await
-            {
-                Ok(Some(recording)) => {
-                    // Update track with MusicBrainz data
-                    track.musicbrainz_id = Some(recording.id.clone());
-
-                    // Update title/artist if we got a better match
-                    let artist_name = recording.artist_name();
-                    if !artist_name.is_empty() {
-                        track.artist = artist_name;
-                    }
-                    track.title.clone_from(&recording.title);
+        looked_up
+    }
 
-                    // Set album info from first release if available
-                    if let Some(release) = recording.releases.first()
-                        && track.album_title.is_none()
-                    {
-                        track.album_title = Some(release.title.clone());
-                    }
+    /// Look up metadata for a batch of tracks from [`lookup_batches`].
+    ///
+    /// Returns the tracks, and the releases matched to their albums.
+    async fn lookup_batch(
+        &self,
+        client: &CachedMusicBrainzClient,
+        matcher: &ReleaseMatcher<'_>,
+        mut batch: Vec<Track>,
+        min_score: u8,
+        cancel: &AtomicBool,
+    ) -> (Vec<Track>, HashMap<String, Release>) {
+        let mut releases = HashMap::new();
+        // Tracks keep the metadata from their files once cancelled, or if
+        // they already have MusicBrainz IDs
+        if is_cancelled(cancel) || batch.iter().all(|track| track.musicbrainz_id.is_some()) {
+            return (batch, releases);
+        }
 
-                    debug!(
-                        "MusicBrainz match: {} - {} -> {}",
-                        track.artist, track.title, recording.id
-                    );
-                }
-                Ok(None) => {
-                    debug!(
-                        "No MusicBrainz match for: {} - {}",
-                        track.artist, track.title
-                    );
-                }
-                Err(e) => {
-                    warn!(
-                        "MusicBrainz lookup failed for {} - {}: {e}",
-                        track.artist, track.title
-                    );
-                }
+        if batch[0].album_title.is_some() {
+            Self::match_release(matcher, &mut batch, min_score, &mut releases).await;
+        } else {
+            for track in &mut batch {
+                self.match_recording(client, track, min_score, &mut releases)
+                    .await;
             }
         }
-
-        tracks
+        (batch, releases)
     }
 
-    /// Group tracks into albums based on album title and artist.
-    fn group_into_albums(tracks: &[Track]) -> HashMap<String, Vec<&Track>> {
-        let mut albums: HashMap<String, Vec<&Track>> = HashMap::new();
-
-        for track in tracks {
-            if let Some(album_title) = &track.album_title {
-                let artist = track
-                    .album_artist
-                    .as_ref()
-                    .unwrap_or(&track.artist)
-                    .to_lowercase();
-                let key = format!("{}::{}", artist, album_title.to_lowercase());
-                albums.entry(key).or_default().push(track);
+    /// Apply the best matching release to the tracks of an album.
+    ///
+    /// The release is only applied if it scores at least `min_score`.
+    async fn match_release(
+        matcher: &ReleaseMatcher<'_>,
+        tracks: &mut [Track],
+        min_score: u8,
+        releases: &mut HashMap<String, Release>,
+    ) {
+        let name = album_key(&tracks[0]).unwrap_or_default();
+        let best = match matcher.candidates(tracks).await {
+            Ok(candidates) => candidates.into_iter().next(),
+            Err(e) => {
+                warn!("MusicBrainz release search failed for {name}: {e}");
+                return;
             }
+        };
+        let Some(best) = best else {
+            debug!("No MusicBrainz release for: {name}");
+            return;
+        };
+        let score = best.score * 100.0;
+        if score < f64::from(min_score) {
+            debug!(
+                "MusicBrainz release {} scores too low for {name}: {score:.0}",
+                best.release.id
+            );
+            return;
         }
 
-        albums
+        let originals = tracks.to_vec();
+        best.apply(tracks);
+        // Keep the tracks together if the release has no artist
+        let artist = originals[0]
+            .album_artist
+            .as_ref()
+            .unwrap_or(&originals[0].artist);
+        for track in tracks.iter_mut() {
+            track.album_artist.get_or_insert_with(|| artist.clone());
+        }
+        for (original, track) in originals.iter().zip(tracks.iter()) {
+            debug!(
+                "MusicBrainz match: {} - {} -> {}\n{}",
+                track.artist,
+                track.title,
+                best.release.id,
+                TrackDiff::between(original, track)
+            );
+        }
+
+        if let Some(key) = album_key(&tracks[0]) {
+            releases.insert(key, best.release);
+        }
     }
 
-    /// Create album entries in the database.
-    async fn create_album_entries(
+    /// Apply the best matching recording to a track.
+    async fn match_recording(
         &self,
-        albums: &HashMap<String, Vec<&Track>>,
-        result: &mut ImportResult,
-    ) -> HashMap<String, AlbumId> {
-        let mut album_map = HashMap::new();
+        client: &CachedMusicBrainzClient,
+        track: &mut Track,
+        min_score: u8,
+        releases: &mut HashMap<String, Release>,
+    ) {
+        // Skip if already has a MusicBrainz ID
+        if track.musicbrainz_id.is_some() {
+            return;
+        }
 
-        for (key, tracks) in albums {
-            if tracks.is_empty() {
-                continue;
-            }
+        // Try to find a match
+        let album = track.album_title.as_deref();
+        #[allow(clippy::cast_possible_truncation)]
+        let duration_ms = track.duration.as_millis() as u64;
 
-            // Use first track for album info
-            let first_track = tracks[0];
-            let album_title = first_track
-                .album_title
-                .as_ref()
-                .expect("grouped by album title");
-            let artist = first_track
-                .album_artist
-                .as_ref()
-                .unwrap_or(&first_track.artist)
-                .clone();
-
-            // Check if album already exists (by title and artist)
-            // For now, we just create a new one
-            let mut album = Album::new(album_title.clone(), artist);
-            album.track_count = u32::try_from(tracks.len()).unwrap_or(u32::MAX);
-
-            // Set year from first track that has it
-            for track in tracks {
-                if let Some(year) = track.year {
-                    album.year = Some(year);
-                    break;
-                }
-            }
+        match client
+            .find_best_recording(
+                &track.title,
+                &track.artist,
+                album,
+                Some(duration_ms),
+                min_score,
+            )
+            .await
+        {
+            Ok(Some(recording)) => {
+                let original = track.clone();
 
-            match self.db.add_album(&album).await {
-                Ok(_) => {
-                    album_map.insert(key.clone(), album.id);
-                    result.albums_created += 1;
-                    debug!("Created album: {} - {}", album.artist, album.title);
+                // Update track with MusicBrainz data
+                track.musicbrainz_id = Some(recording.id.clone());
+
+                // Update title/artist if we got a better match
+                let artist_name = recording.artist_name();
+                if !artist_name.is_empty() {
+                    track.artist = artist_name;
                 }
-                Err(e) => {
-                    warn!(
-                        "Failed to create album {} - {}: {e}",
-                        album.artist, album.title
-                    );
-                    result.errors.push(format!("Failed to create album: {e}"));
+                track.title.clone_from(&recording.title);
+                if track.isrc.is_none() {
+                    track.isrc = recording.isrcs.first().cloned();
                 }
+
+                // Set album info from first release if available
+                if let Some(release) = recording.releases.first() {
+                    if track.album_title.is_none() {
+                        track.album_title = Some(release.title.clone());
+                    }
+                    if let Some(key) = album_key(track)
+                        && !releases.contains_key(&key)
+                    {
+                        releases.insert(key, self.release_details(release).await);
+                    }
+                }
+
+                debug!(
+                    "MusicBrainz match: {} - {} -> {}\n{}",
+                    track.artist,
+                    track.title,
+                    recording.id,
+                    TrackDiff::between(&original, track)
+                );
+            }
+            Ok(None) => {
+                debug!(
+                    "No MusicBrainz match for: {} - {}",
+                    track.artist, track.title
+                );
+            }
+            Err(e) => {
+                warn!(
+                    "MusicBrainz lookup failed for {} - {}: {e}",
+                    track.artist, track.title
+                );
             }
         }
-
-        album_map
     }
 
-    /// Fetch album art for albums with `MusicBrainz` IDs.
-    async fn fetch_album_art(
+    /// Remove tracks whose files are in the library, or earlier in the
+    /// tracks, under another path.
+    ///
+    /// Files are compared by hash, so tracks without one are kept. Tracks
+    /// that are in the library under the same path are kept too, and
+    /// skipped when they are imported. Returns the tracks that are kept,
+    /// and the duplicates that are not.
+    #[instrument(skip_all)]
+    async fn skip_content_duplicates(
         &self,
-        client: &CoverArtClient,
-        album_map: &HashMap<String, AlbumId>,
-        progress_tx: Option<&mpsc::Sender<ImportProgress>>,
-    ) {
-        let total = album_map.len();
+        tracks: Vec<Track>,
+    ) -> (Vec<Track>, Vec<ImportDuplicate>) {
+        let mut seen: HashMap<String, PathBuf> = HashMap::new();
+        let mut kept = Vec::with_capacity(tracks.len());
+        let mut duplicates = Vec::new();
+        for track in tracks {
+            if track.file_hash.is_empty() {
+                kept.push(track);
+                continue;
+            }
+            let original = match self.db.get_track_by_hash(&track.file_hash).await {
+                Ok(existing) => existing
+                    .map(|existing| existing.path)
+                    .filter(|path| *path != track.path),
+                Err(e) => {
+                    warn!("Failed to look up {} by hash: {e}", track.path.display());
+                    None
+                }
+            };
+            if let Some(original) = original {
+                debug!(
+                    "Skipped (same file as {}): {}",
+                    original.display(),
+                    track.path.display()
+                );
+                duplicates.push(ImportDuplicate {
+                    path: track.path,
+                    original,
+                    in_library: true,
+                });
+            } else if let Some(original) = seen.get(&track.file_hash) {
+                debug!("Skipped (same file found twice): {}", track.path.display());
+                duplicates.push(ImportDuplicate {
+                    path: track.path,
+                    original: original.clone(),
+                    in_library: false,
+                });
+            } else {
+                seen.insert(track.file_hash.clone(), track.path.clone());
+                kept.push(track);
+            }
+        }
+        (kept, duplicates)
+    }
+
+    /// Normalize the genres and featured artists of tracks, as the options
+    /// say, unless the fields they change are locked.
+    fn normalize_tracks(&self, options: &ImportOptions, tracks: &mut [Track]) {
+        if options.normalize_genres && !self.locks.is_locked(EditField::Genre) {
+            for track in tracks.iter_mut() {
+                track.genres = self.genre_normalizer.normalize(&track.genres);
+            }
+        }
+        // Featured artists move between the artist and the title, so both
+        // must be free to change
+        if options.normalize_featured
+            && !self.locks.is_locked(EditField::Artist)
+            && !self.locks.is_locked(EditField::Title)
+        {
+            for track in tracks.iter_mut() {
+                self.credit_normalizer.normalize(track);
+            }
+        }
+    }
+
+    /// Run the `on_import` hooks of plugins on each track.
+    ///
+    /// Returns the tracks to import. Tracks that a hook fails for are
+    /// imported unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a hook aborts the import.
+    #[instrument(skip_all)]
+    async fn run_import_hooks(
+        hooks: &LuaWorkerPool,
+        tracks: Vec<Track>,
+        result: &mut ImportResult,
+    ) -> Result<Vec<Track>, crate::error::ApiError> {
+        let mut kept = Vec::with_capacity(tracks.len());
+        for track in tracks {
+            match hooks.run_on_import_async(track.clone()).await {
+                Ok((HookResult::Continue, track)) => kept.push(track),
+                Ok((HookResult::Skip, _)) => {
+                    result.tracks_skipped += 1;
+                    debug!("Skipped (plugin): {} - {}", track.artist, track.title);
+                }
+                Ok((HookResult::Abort { reason }, _)) => {
+                    return Err(crate::error::ApiError::BadRequest(format!(
+                        "Import aborted by plugin: {reason}"
+                    )));
+                }
+                Err(e) => {
+                    warn!("on_import hook failed for {}: {e}", track.path.display());
+                    result.errors.push(format!("{}: {e}", track.path.display()));
+                    kept.push(track);
+                }
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Create album entries in the database.
+    ///
+    /// Returns the IDs of the albums by key, and the albums themselves.
+    /// Albums that a plugin skips are not created, so their tracks are
+    /// imported without one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an `on_album_import` hook aborts the import.
+    #[instrument(skip_all)]
+    async fn create_album_entries(
+        &self,
+        all_tracks: &mut [Track],
+        albums: &[Vec<usize>],
+        releases: &HashMap<String, Release>,
+        compilation_min_artists: usize,
+        result: &mut ImportResult,
+    ) -> Result<Vec<Album>, crate::error::ApiError> {
+        // Every hook runs before the first album is created, so that an
+        // aborted import leaves nothing behind
+        let mut new_albums = Vec::with_capacity(albums.len());
+        for indices in albums {
+            let tracks: Vec<&Track> = indices.iter().map(|&i| &all_tracks[i]).collect();
+            if tracks.is_empty() {
+                continue;
+            }
+
+            let mut album = new_album(&tracks, releases, compilation_min_artists);
+
+            if let Some(ref hooks) = self.hooks {
+                match hooks.run_on_album_import_async(album.clone()).await {
+                    Ok((HookResult::Continue, hooked)) => album = hooked,
+                    Ok((HookResult::Skip, _)) => {
+                        debug!("Skipped album (plugin): {} - {}", album.artist, album.title);
+                        continue;
+                    }
+                    Ok((HookResult::Abort { reason }, _)) => {
+                        return Err(crate::error::ApiError::BadRequest(format!(
+                            "Import aborted by plugin: {reason}"
+                        )));
+                    }
+                    Err(e) => {
+                        warn!("on_album_import hook failed: {e}");
+                        result.errors.push(e.to_string());
+                    }
+                }
+            }
+            new_albums.push((indices, album));
+        }
+
+        let mut created = Vec::new();
+        for (indices, album) in new_albums {
+            match self.db.add_album(&album).await {
+                Ok(_) => {
+                    for &i in indices {
+                        all_tracks[i].album_id = Some(album.id.clone());
+                    }
+                    result.albums_created += 1;
+                    debug!("Created album: {} - {}", album.artist, album.title);
+                    created.push(album);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to create album {} - {}: {e}",
+                        album.artist, album.title
+                    );
+                    result.errors.push(format!("Failed to create album: {e}"));
+                }
+            }
+        }
+
+        Ok(created)
+    }
+
+    /// Fetch full details of a release, including its labels.
+    ///
+    /// Falls back to the release as found by the recording search.
+    async fn release_details(&self, release: &Release) -> Release {
+        let Some(ref client) = self.mb_client else {
+            return release.clone();
+        };
+        match client
+            .lookup_release(&release.id, &["labels", "release-groups"])
+            .await
+        {
+            Ok(details) => details,
+            Err(e) => {
+                debug!("Release lookup failed for {}: {e}", release.id);
+                release.clone()
+            }
+        }
+    }
+
+    /// Fetch album art for albums with `MusicBrainz` IDs.
+    ///
+    /// The art is stored with the album, and saved to its tracks' files as
+    /// the options say.
+    #[instrument(skip_all)]
+    async fn fetch_album_art(
+        &self,
+        client: &CoverArtClient,
+        albums: &[Album],
+        tracks: &mut [Track],
+        options: &ImportOptions,
+        result: &mut ImportResult,
+        progress_tx: Option<&mpsc::Sender<ImportProgress>>,
+    ) {
+        let total = albums.len();
 
-        for (index, album_id) in album_map.values().enumerate() {
+        for (index, album) in albums.iter().enumerate() {
             if let Some(tx) = progress_tx {
                 let _ = tx
                     .send(ImportProgress::FetchingArt {
@@ -504,30 +1338,121 @@ impl ImportService {
                     .await;
             }
 
-            // Get album from database to check for MusicBrainz release ID
-            if let Ok(Some(album)) = self.db.get_album(album_id).await
-                && let Some(ref mbid) = album.musicbrainz_id
-            {
-                match client.get_front_cover(mbid, ImageSize::Large).await {
-                    Ok(cover) => {
-                        debug!(
-                            "Found album art for {} - {}: {}",
-                            album.artist, album.title, cover.url
-                        );
-                        // TODO: Save cover art to file or embed in tracks
-                    }
-                    Err(e) => {
-                        debug!("No album art for {} - {}: {e}", album.artist, album.title);
-                    }
+            let Some(ref mbid) = album.musicbrainz_id else {
+                continue;
+            };
+            let artwork = match download_cover(client, mbid).await {
+                Ok(artwork) => artwork,
+                Err(e) => {
+                    debug!("No album art for {} - {}: {e}", album.artist, album.title);
+                    continue;
                 }
+            };
+            debug!(
+                "Found album art for {} - {}: {}",
+                album.artist,
+                album.title,
+                artwork.source.as_deref().unwrap_or_default()
+            );
+
+            if let Err(e) = self.db.set_album_artwork(&album.id, &artwork).await {
+                warn!(
+                    "Failed to store album art for {} - {}: {e}",
+                    album.artist, album.title
+                );
+                result
+                    .errors
+                    .push(format!("Failed to store album art: {e}"));
             }
+            let album_tracks: Vec<&mut Track> = tracks
+                .iter_mut()
+                .filter(|track| track.album_id.as_ref() == Some(&album.id))
+                .collect();
+            save_cover(&artwork, album_tracks, options, result);
         }
     }
 
+    /// Copy or move new tracks into a directory, and point them at their new
+    /// paths.
+    ///
+    /// Tracks that are in the library already, or where they belong, stay
+    /// where they are. A file in the way is kept, and the track gets a
+    /// numbered name next to it. Tracks whose file cannot be placed fail.
+    /// Returns the tracks to import, and how each file was placed by its
+    /// new path.
+    #[instrument(skip_all)]
+    async fn place_files(
+        &self,
+        tracks: Vec<Track>,
+        dir: &Path,
+        template: &PathTemplate,
+        options: &ImportOptions,
+        result: &mut ImportResult,
+    ) -> Result<(Vec<Track>, HashMap<PathBuf, OrganizeResult>), crate::error::ApiError> {
+        // Stored paths should not depend on the working directory
+        let dir = std::path::absolute(dir)
+            .map_err(|e| crate::error::ApiError::Internal(e.to_string()))?;
+        let organize_options = OrganizeOptions {
+            move_files: options.move_files,
+            rename_on_conflict: true,
+            ..OrganizeOptions::default()
+        };
+
+        // Albums are needed for compilations and %aunique
+        let albums = self.db.list_albums(u32::MAX, 0).await?;
+        let album_set = AlbumSet::new(albums.iter().cloned());
+        let albums: HashMap<AlbumId, Album> = albums
+            .into_iter()
+            .map(|album| (album.id.clone(), album))
+            .collect();
+
+        let mut kept = Vec::with_capacity(tracks.len());
+        let mut placed = HashMap::new();
+        for mut track in tracks {
+            if self.db.get_track_by_path(&track.path).await?.is_some() {
+                kept.push(track);
+                continue;
+            }
+
+            let mut ctx = TemplateContext::from_track(&track);
+            if let Some(album) = track.album_id.as_ref().and_then(|id| albums.get(id)) {
+                ctx.set_album(album, &album_set);
+            }
+            let in_place = template
+                .render_with_extension(&ctx)
+                .is_ok_and(|relative| is_same_file(&dir.join(relative), &track.path));
+            if in_place {
+                kept.push(track);
+                continue;
+            }
+
+            match organize_file_with_context(&track.path, &dir, template, &ctx, &organize_options) {
+                Ok(placement) => {
+                    debug!(
+                        "Placed {} at {}",
+                        placement.source.display(),
+                        placement.destination.display()
+                    );
+                    track.path.clone_from(&placement.destination);
+                    placed.insert(placement.destination.clone(), placement);
+                    kept.push(track);
+                }
+                Err(e) => {
+                    warn!("Failed to place {}: {e}", track.path.display());
+                    result.tracks_failed += 1;
+                    result
+                        .errors
+                        .push(format!("Failed to place {}: {e}", track.path.display()));
+                }
+            }
+        }
+        Ok((kept, placed))
+    }
+
     /// Write tags back to audio files.
-    fn write_tags_to_files(tracks: &[Track], result: &mut ImportResult) {
+    fn write_tags_to_files(&self, tracks: &mut [Track], result: &mut ImportResult) {
         for track in tracks {
-            if let Err(e) = write_metadata(&track.path, track) {
+            if let Err(e) = write_tags(track, &self.write_fields, &[]) {
                 warn!("Failed to write tags to {}: {e}", track.path.display());
                 result.errors.push(format!(
                     "Failed to write tags to {}: {e}",
@@ -538,9 +1463,1045 @@ impl ImportService {
     }
 }
 
+/// Download the front cover of a release from the Cover Art Archive.
+async fn download_cover(client: &CoverArtClient, mbid: &str) -> Result<Artwork, String> {
+    let cover = client
+        .get_front_cover(mbid, ImageSize::Large)
+        .await
+        .map_err(|e| e.to_string())?;
+    let data = client
+        .download_image(&cover.url)
+        .await
+        .map_err(|e| e.to_string())?;
+    let art = CoverArt::from_bytes(data).map_err(|e| e.to_string())?;
+    Ok(Artwork {
+        data: art.data,
+        mime_type: art.mime_type,
+        source: Some(cover.url),
+    })
+}
+
+/// Undo the changes from `before` to `after` of locked fields, matching
+/// tracks by ID.
+fn restore_locked(locks: &FieldLocks, before: &[Track], after: &mut [Track]) {
+    if locks.is_empty() {
+        return;
+    }
+    let before: HashMap<&TrackId, &Track> = before.iter().map(|track| (&track.id, track)).collect();
+    for track in after {
+        if let Some(original) = before.get(&track.id)
+            && let Err(e) = locks.restore(original, track)
+        {
+            warn!(
+                "Failed to keep the locked fields of {}: {e}",
+                track.path.display()
+            );
+        }
+    }
+}
+
+/// Note that a source set the fields that changed from `before` to
+/// `after`, matching tracks by ID.
+fn record_sources(
+    provenance: &mut HashMap<TrackId, Provenance>,
+    before: &[Track],
+    after: &[Track],
+    source: MetadataSource,
+) {
+    let before: HashMap<&TrackId, &Track> = before.iter().map(|track| (&track.id, track)).collect();
+    for track in after {
+        if let Some(original) = before.get(&track.id) {
+            let diff = TrackDiff::between(original, track);
+            if !diff.is_empty() {
+                provenance
+                    .entry(track.id.clone())
+                    .or_default()
+                    .record(&diff, source);
+            }
+        }
+    }
+}
+
+/// Check if an import has been cancelled.
+fn is_cancelled(cancel: &AtomicBool) -> bool {
+    cancel.load(Ordering::Relaxed)
+}
+
+/// Mark the result of an import as cancelled.
+fn cancelled(mut result: ImportResult) -> ImportResult {
+    info!("Import cancelled");
+    result.cancelled = true;
+    result
+}
+
+/// Put a placed file back, for a track that was not imported, and record a
+/// failure to do so in the result.
+fn unplace(placed: &HashMap<PathBuf, OrganizeResult>, path: &Path, result: &mut ImportResult) {
+    if let Err(e) = unplace_file(placed, path) {
+        warn!("{e}");
+        result.errors.push(e.to_string());
+    }
+}
+
+/// Save album art to the files of an album's tracks.
+///
+/// Cover files are only written to directories without one, and art is only
+/// embedded into files without any. Embedding changes the files, so their
+/// hashes are updated.
+fn save_cover(
+    artwork: &Artwork,
+    tracks: Vec<&mut Track>,
+    options: &ImportOptions,
+    result: &mut ImportResult,
+) {
+    let art = CoverArt {
+        data: artwork.data.clone(),
+        mime_type: artwork.mime_type.clone(),
+    };
+
+    if options.write_cover_files {
+        let dirs: HashSet<PathBuf> = tracks
+            .iter()
+            .filter_map(|track| track.path.parent().map(PathBuf::from))
+            .collect();
+        for dir in dirs {
+            if find_cover_file(&dir).is_some() {
+                continue;
+            }
+            let path = dir.join(format!("folder.{}", art.extension()));
+            if let Err(e) = std::fs::write(&path, &art.data) {
+                warn!("Failed to write {}: {e}", path.display());
+                result
+                    .errors
+                    .push(format!("Failed to write {}: {e}", path.display()));
+            }
+        }
+    }
+
+    if options.embed_album_art {
+        for track in tracks {
+            if matches!(read_embedded_art(&track.path), Ok(Some(_))) {
+                continue;
+            }
+            match embed_art(&track.path, &art) {
+                Ok(()) if !track.file_hash.is_empty() => match compute_file_hash(&track.path) {
+                    Ok(hash) => track.file_hash = hash,
+                    Err(e) => warn!("Failed to hash {}: {e}", track.path.display()),
+                },
+                Ok(()) => {}
+                Err(e) => {
+                    warn!("Failed to embed album art in {}: {e}", track.path.display());
+                    result.errors.push(format!(
+                        "Failed to embed album art in {}: {e}",
+                        track.path.display()
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Create the album entry for the tracks of one group from
+/// [`group_into_albums`].
+fn new_album(
+    tracks: &[&Track],
+    releases: &HashMap<String, Release>,
+    compilation_min_artists: usize,
+) -> Album {
+    // Use first track for album info
+    let first_track = tracks[0];
+    let album_title = first_track
+        .album_title
+        .as_ref()
+        .expect("grouped by album title");
+    // Discs of a set may each have their own title
+    let album_title = match split_disc_suffix(album_title) {
+        (title, Some(_)) if !title.is_empty() => title.to_string(),
+        _ => album_title.clone(),
+    };
+    let artist = album_artist(tracks);
+
+    let mut album = Album::new(album_title, artist);
+    album.track_count = u32::try_from(tracks.len()).unwrap_or(u32::MAX);
+
+    // Set year from first track that has it
+    album.year = tracks.iter().find_map(|track| track.year);
+
+    // Release type, date, and label come from MusicBrainz
+    if let Some(release) = album_key(first_track).and_then(|key| releases.get(&key)) {
+        apply_release(release, &mut album);
+    }
+    album.is_compilation = album.detect_compilation(tracks, compilation_min_artists);
+    album
+}
+
+/// Copy the metadata of a `MusicBrainz` release to an album.
+///
+/// The track and disc counts are those of the release, if it lists its
+/// media, rather than of the tracks imported.
+fn apply_release(release: &Release, album: &mut Album) {
+    release.apply_to_album(album);
+    if !release.media.is_empty() {
+        album.disc_count = u32::try_from(release.media.len()).unwrap_or(u32::MAX);
+        album.track_count = release
+            .media
+            .iter()
+            .map(|medium| {
+                medium
+                    .track_count
+                    .unwrap_or_else(|| u32::try_from(medium.tracks.len()).unwrap_or(u32::MAX))
+            })
+            .sum();
+    }
+}
+
+/// Group tracks by album, in the order the albums are first found.
+///
+/// Tracks without an album are grouped together.
+fn group_tracks(tracks: Vec<Track>) -> Vec<Vec<Track>> {
+    let mut groups: Vec<Vec<Track>> = Vec::new();
+    let mut index = HashMap::new();
+    for track in tracks {
+        let i = *index.entry(album_key(&track)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[i].push(track);
+    }
+    groups
+}
+
+/// Split tracks into batches to look up together.
+///
+/// The tracks of an album are a batch, like in [`group_tracks`]. Tracks
+/// without an album are batched by title and artist, so each search is only
+/// made once.
+fn lookup_batches(tracks: Vec<Track>) -> Vec<Vec<Track>> {
+    let mut batches = Vec::new();
+    for group in group_tracks(tracks) {
+        if group[0].album_title.is_some() {
+            batches.push(group);
+            continue;
+        }
+        let mut index = HashMap::new();
+        for track in group {
+            let key = (track.artist.to_lowercase(), track.title.to_lowercase());
+            let i = *index.entry(key).or_insert_with(|| {
+                batches.push(Vec::new());
+                batches.len() - 1
+            });
+            batches[i].push(track);
+        }
+    }
+    batches
+}
+
+/// Get the key used to group a track into an album.
+///
+/// Tracks are grouped by album artist (or artist) and album title,
+/// ignoring case.
+fn album_key(track: &Track) -> Option<String> {
+    let album_title = track.album_title.as_ref()?;
+    let artist = track.album_artist.as_ref().unwrap_or(&track.artist);
+    Some(format!(
+        "{}::{}",
+        artist.to_lowercase(),
+        album_title.to_lowercase()
+    ))
+}
+
+/// Group tracks into albums, as indices in the order they are first found.
+///
+/// Tracks are grouped by album title and album artist, ignoring case and
+/// disc suffixes such as "(Disc 2)". Tracks without an album artist that
+/// share a folder and title but not an artist are a compilation.
+///
+/// Within a group, the tracks of each folder and release year are merged
+/// into an album whose disc and track numbers they do not repeat. The discs
+/// of a set ripped into separate folders thereby come together, while other
+/// editions of the album stay apart.
+fn group_into_albums(tracks: &[Track]) -> Vec<Vec<usize>> {
+    let places: Vec<Option<AlbumPlace>> = tracks.iter().map(AlbumPlace::of).collect();
+
+    // Artists of the tracks without an album artist, per folder and title
+    let mut artists: HashMap<(&Path, &str), HashSet<String>> = HashMap::new();
+    for (track, place) in tracks.iter().zip(&places) {
+        if let Some(place) = place
+            && track.album_artist.is_none()
+        {
+            artists
+                .entry((&place.dir, &place.title))
+                .or_default()
+                .insert(track.artist.to_lowercase());
+        }
+    }
+
+    let mut groups: Vec<Vec<AlbumPart>> = Vec::new();
+    let mut index = HashMap::new();
+    for (i, (track, place)) in tracks.iter().zip(&places).enumerate() {
+        let Some(place) = place else {
+            continue;
+        };
+        // A compilation is only grouped with the tracks of its own folder
+        let (artist, dir) = match &track.album_artist {
+            Some(artist) => (artist.to_lowercase(), None),
+            None if artists[&(place.dir.as_path(), place.title.as_str())].len() > 1 => {
+                (VARIOUS_ARTISTS.to_lowercase(), Some(place.dir.as_path()))
+            }
+            None => (track.artist.to_lowercase(), None),
+        };
+        let group = *index
+            .entry((place.title.as_str(), artist, dir))
+            .or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+
+        let parts = &mut groups[group];
+        let part = parts
+            .iter()
+            .position(|part| part.dir == place.dir && part.year == track.year)
+            .unwrap_or_else(|| {
+                parts.push(AlbumPart::new(&place.dir, track.year));
+                parts.len() - 1
+            });
+        parts[part].add(i, track, place.disc);
+    }
+
+    let mut albums: Vec<AlbumPart> = Vec::new();
+    for parts in groups {
+        let start = albums.len();
+        for part in parts {
+            match albums[start..].iter_mut().find(|album| album.fits(&part)) {
+                Some(album) => album.merge(part),
+                None => albums.push(part),
+            }
+        }
+    }
+
+    let mut albums: Vec<Vec<usize>> = albums
+        .into_iter()
+        .map(|album| {
+            let mut tracks = album.tracks;
+            tracks.sort_unstable();
+            tracks
+        })
+        .collect();
+    albums.sort_unstable_by_key(|tracks| tracks[0]);
+    albums
+}
+
+/// The album title, folder, and disc of a track, for grouping into albums.
+struct AlbumPlace {
+    /// Album title in lower case, without a disc suffix.
+    title: String,
+    /// Folder of the album, above any disc folder like "CD1".
+    dir: PathBuf,
+    /// Disc number, from the tags, the album title, or the folder name.
+    disc: Option<u32>,
+}
+
+impl AlbumPlace {
+    /// Get the place of a track, if it has an album.
+    fn of(track: &Track) -> Option<Self> {
+        let album_title = track.album_title.as_ref()?;
+        let (title, title_disc) = split_disc_suffix(album_title);
+        let title = if title.is_empty() {
+            album_title.to_lowercase()
+        } else {
+            title.to_lowercase()
+        };
+
+        let parent = track.path.parent().unwrap_or_else(|| Path::new(""));
+        let (folder, folder_disc) = parent
+            .file_name()
+            .and_then(OsStr::to_str)
+            .map(split_disc_suffix)
+            .unwrap_or_default();
+        let dir = match parent.parent() {
+            Some(grandparent) if folder.is_empty() && folder_disc.is_some() => grandparent,
+            _ => parent,
+        };
+
+        Some(Self {
+            title,
+            dir: dir.to_path_buf(),
+            disc: track.disc_number.or(title_disc).or(folder_disc),
+        })
+    }
+}
+
+/// Tracks of an album from one folder and year, while grouping.
+struct AlbumPart<'a> {
+    dir: &'a Path,
+    year: Option<i32>,
+    disc_total: Option<u32>,
+    tracks: Vec<usize>,
+    /// Disc and track numbers of the tracks.
+    positions: HashSet<(Option<u32>, u32)>,
+}
+
+impl<'a> AlbumPart<'a> {
+    fn new(dir: &'a Path, year: Option<i32>) -> Self {
+        Self {
+            dir,
+            year,
+            disc_total: None,
+            tracks: Vec::new(),
+            positions: HashSet::new(),
+        }
+    }
+
+    fn add(&mut self, index: usize, track: &Track, disc: Option<u32>) {
+        self.tracks.push(index);
+        self.disc_total = self.disc_total.or(track.disc_total);
+        if let Some(number) = track.track_number {
+            self.positions.insert((disc, number));
+        }
+    }
+
+    /// Check if another part can be of the same album: a set of as many
+    /// discs, without any of the same tracks.
+    fn fits(&self, other: &Self) -> bool {
+        let same_total = match (self.disc_total, other.disc_total) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        same_total && self.positions.is_disjoint(&other.positions)
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.tracks.extend(other.tracks);
+        self.positions.extend(other.positions);
+        self.disc_total = self.disc_total.or(other.disc_total);
+    }
+}
+
+/// Split a disc number off the end of an album or folder name.
+///
+/// Recognizes suffixes like "(Disc 2)", "[CD2]", and " - Disk 2", and names
+/// that are only a disc, like "CD1", which leave an empty name.
+fn split_disc_suffix(name: &str) -> (&str, Option<u32>) {
+    const SEPARATORS: [char; 6] = [' ', '(', '[', '-', ',', '_'];
+
+    let name = name.trim();
+    let rest = name.trim_end_matches([')', ']']).trim_end();
+    let head = rest.trim_end_matches(|c: char| c.is_ascii_digit());
+    let Ok(number) = rest[head.len()..].parse() else {
+        return (name, None);
+    };
+    let head = head.trim_end_matches([' ', '_', '-', '.']);
+    for word in ["disc", "disk", "cd"] {
+        let Some(title) = head
+            .len()
+            .checked_sub(word.len())
+            .filter(|&end| head.is_char_boundary(end))
+            .filter(|&end| head[end..].eq_ignore_ascii_case(word))
+            .map(|end| &head[..end])
+        else {
+            continue;
+        };
+        if title.is_empty() || title.ends_with(SEPARATORS) {
+            return (title.trim_end_matches(SEPARATORS), Some(number));
+        }
+    }
+    (name, None)
+}
+
+/// Get the artist of an album from its tracks.
+///
+/// Without an album artist, an album of tracks by several artists is by
+/// [`VARIOUS_ARTISTS`].
+fn album_artist(tracks: &[&Track]) -> String {
+    let first = tracks[0];
+    if let Some(artist) = &first.album_artist {
+        return artist.clone();
+    }
+    if tracks
+        .iter()
+        .any(|track| !track.artist.eq_ignore_ascii_case(&first.artist))
+    {
+        VARIOUS_ARTISTS.to_string()
+    } else {
+        first.artist.clone()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use apollo_audio::write_metadata;
+    use apollo_db::SqliteLibrary;
+    use apollo_lua::LuaRuntime;
+    use std::fs;
+    use std::path::Path;
+
+    /// Write a silent mono 8 kHz WAV file, tagged with a title.
+    fn write_wav(path: &Path) {
+        let samples = vec![0u8; 1600];
+        let size = u32::try_from(samples.len()).unwrap();
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + size).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&samples);
+        fs::write(path, data).unwrap();
+
+        let track = Track::new(
+            path.to_path_buf(),
+            "Song".to_string(),
+            "Artist".to_string(),
+            std::time::Duration::from_millis(100),
+        );
+        write_metadata(path, &track).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_runs_plugin_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir(&music).unwrap();
+        write_wav(&music.join("keep.wav"));
+        write_wav(&music.join("skip.wav"));
+        let plugin = dir.path().join("hooks.lua");
+        fs::write(
+            &plugin,
+            r#"
+            local plugin = { name = "hooks", version = "1.0.0" }
+
+            function plugin.on_import(track)
+                if track.path:match("skip") then
+                    return "skip"
+                elseif track.path:match("abort") then
+                    return { result = "abort", reason = "not today" }
+                end
+                track.title = "Hooked"
+            end
+
+            return plugin
+            "#,
+        )
+        .unwrap();
+        let hooks = LuaWorkerPool::new(1, move || {
+            let mut runtime = LuaRuntime::new()?;
+            runtime.load_plugin(&plugin)?;
+            Ok::<_, apollo_lua::Error>(runtime)
+        })
+        .unwrap();
+
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let service = ImportService::new_basic(Arc::clone(&db) as Arc<dyn Library>)
+            .with_hooks(Arc::new(hooks));
+        let options = ImportOptions::default().with_source(music.clone());
+
+        let result = service.import(&options, None).await.unwrap();
+        assert_eq!(result.tracks_imported, 1);
+        assert_eq!(result.tracks_skipped, 1);
+        let tracks = db.list_tracks(10, 0).await.unwrap();
+        assert_eq!(tracks[0].title, "Hooked");
+        let provenance = db.get_track_provenance(&tracks[0].id).await.unwrap();
+        assert_eq!(provenance.source("title"), MetadataSource::Plugin);
+        assert_eq!(provenance.source("artist"), MetadataSource::File);
+
+        // An abort stops the import before anything is added
+        write_wav(&music.join("new.wav"));
+        write_wav(&music.join("abort.wav"));
+        let result = service.import(&options, None).await;
+        assert!(matches!(result, Err(crate::error::ApiError::BadRequest(_))));
+        assert_eq!(db.count_tracks().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_keeps_locked_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir(&music).unwrap();
+        write_wav(&music.join("song.wav"));
+        let plugin = dir.path().join("retitle.lua");
+        fs::write(
+            &plugin,
+            r#"
+            local plugin = { name = "retitle", version = "1.0.0" }
+
+            function plugin.on_import(track)
+                track.title = "Retitled"
+                track.year = 2001
+            end
+
+            return plugin
+            "#,
+        )
+        .unwrap();
+        let hooks = LuaWorkerPool::new(1, move || {
+            let mut runtime = LuaRuntime::new()?;
+            runtime.load_plugin(&plugin)?;
+            Ok::<_, apollo_lua::Error>(runtime)
+        })
+        .unwrap();
+
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let service = ImportService::new_basic(Arc::clone(&db) as Arc<dyn Library>)
+            .with_hooks(Arc::new(hooks))
+            .with_locks(FieldLocks::new([EditField::Title]));
+        let options = ImportOptions::default().with_source(music);
+
+        let result = service.import(&options, None).await.unwrap();
+        assert_eq!(result.tracks_imported, 1);
+        let tracks = db.list_tracks(10, 0).await.unwrap();
+        assert_eq!(tracks[0].title, "Song");
+        assert_eq!(tracks[0].year, Some(2001));
+        let provenance = db.get_track_provenance(&tracks[0].id).await.unwrap();
+        assert_eq!(provenance.source("title"), MetadataSource::File);
+        assert_eq!(provenance.source("year"), MetadataSource::Plugin);
+    }
+
+    #[tokio::test]
+    async fn test_import_normalizes_featured_artists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.wav");
+        write_wav(&path);
+        let track = Track::new(
+            path.clone(),
+            "Song".to_string(),
+            "Artist ft. Guest".to_string(),
+            std::time::Duration::from_millis(100),
+        );
+        write_metadata(&path, &track).unwrap();
+        let options = ImportOptions {
+            normalize_featured: true,
+            ..ImportOptions::default()
+        }
+        .with_source(dir.path().to_path_buf());
+
+        // Artists are only moved into titles if both may change
+        let db: Arc<dyn Library> = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let service = ImportService::new_basic(Arc::clone(&db))
+            .with_locks(FieldLocks::new([EditField::Title]));
+        let preview = service.preview(&options).await.unwrap();
+        assert!(preview.tracks[0].changes.is_empty());
+
+        let service = ImportService::new_basic(Arc::clone(&db));
+        let result = service.import(&options, None).await.unwrap();
+        assert_eq!(result.tracks_imported, 1);
+        let tracks = db.list_tracks(10, 0).await.unwrap();
+        assert_eq!(tracks[0].artist, "Artist");
+        assert_eq!(tracks[0].title, "Song (feat. Guest)");
+    }
+
+    #[tokio::test]
+    async fn test_import_skips_content_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir_all(music.join("copy")).unwrap();
+        write_wav(&music.join("song.wav"));
+        write_wav(&music.join("copy/song.wav"));
+
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let service = ImportService::new_basic(Arc::clone(&db) as Arc<dyn Library>);
+        let options = ImportOptions {
+            compute_hashes: true,
+            ..ImportOptions::default()
+        }
+        .with_source(music.clone());
+
+        let result = service.import(&options, None).await.unwrap();
+        assert_eq!(result.tracks_imported, 1);
+        assert_eq!(result.tracks_duplicate, 1);
+        let runs = db.list_import_runs(10).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].source_path, music);
+        assert_eq!(runs[0].tracks_imported, 1);
+        assert_eq!(runs[0].options["compute_hashes"], true);
+
+        // Importing again finds the same paths, which are not copies
+        let result = service.import(&options, None).await.unwrap();
+        assert_eq!(result.tracks_imported, 0);
+        assert_eq!(result.tracks_skipped, 1);
+        assert_eq!(result.tracks_duplicate, 1);
+        // Imports that find nothing new are left out of the history
+        assert_eq!(db.list_import_runs(10).await.unwrap().len(), 1);
+
+        let elsewhere = dir.path().join("elsewhere");
+        fs::create_dir(&elsewhere).unwrap();
+        write_wav(&elsewhere.join("song.wav"));
+        let result = service
+            .import(&options.clone().with_source(elsewhere), None)
+            .await
+            .unwrap();
+        assert_eq!(result.tracks_imported, 0);
+        assert_eq!(result.tracks_duplicate, 1);
+        assert_eq!(db.count_tracks().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir_all(music.join("copy")).unwrap();
+        let song = music.join("song.wav");
+        write_wav(&song);
+        let mut track = apollo_audio::read_metadata(&song).unwrap();
+        track.album_title = Some("Album".to_string());
+        write_metadata(&song, &track).unwrap();
+        fs::copy(&song, music.join("copy/song.wav")).unwrap();
+        write_wav(&music.join("single.wav"));
+
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let service = ImportService::new_basic(Arc::clone(&db) as Arc<dyn Library>);
+        let options = ImportOptions {
+            compute_hashes: true,
+            create_albums: true,
+            ..ImportOptions::default()
+        }
+        .with_source(music.clone());
+
+        let preview = service.preview(&options).await.unwrap();
+        assert_eq!(preview.tracks_found, 3);
+        assert_eq!(preview.albums.len(), 1);
+        let album = &preview.albums[0];
+        assert_eq!(album.album.title, "Album");
+        assert_eq!(album.tracks.len(), 1);
+        assert_eq!(album.tracks[0].track.album_id, Some(album.album.id.clone()));
+        assert!(album.tracks[0].changes.is_empty());
+        assert_eq!(preview.tracks.len(), 1);
+        assert_eq!(preview.tracks[0].track.path, music.join("single.wav"));
+        assert_eq!(preview.duplicates.len(), 1);
+        assert!(!preview.duplicates[0].in_library);
+
+        // Nothing is added to the library
+        assert_eq!(db.count_tracks().await.unwrap(), 0);
+        assert_eq!(db.count_albums().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_moves_files_into_music_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let inbox = dir.path().join("inbox");
+        let library = dir.path().join("library");
+        fs::create_dir(&inbox).unwrap();
+        fs::create_dir_all(library.join("Artist")).unwrap();
+        write_wav(&inbox.join("track01.wav"));
+        fs::write(library.join("Artist/Song.wav"), b"another song").unwrap();
+
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let service = ImportService::new_basic(Arc::clone(&db) as Arc<dyn Library>);
+        let options = ImportOptions {
+            organize_into: Some(library.clone()),
+            move_files: true,
+            path_template: "$artist/$title".to_string(),
+            ..ImportOptions::default()
+        }
+        .with_source(inbox.clone());
+
+        let result = service.import(&options, None).await.unwrap();
+        assert_eq!(result.tracks_imported, 1, "{:?}", result.errors);
+
+        // The file in the way is kept, and the import is numbered
+        let moved = library.join("Artist/Song (2).wav");
+        assert!(moved.exists());
+        assert!(!inbox.join("track01.wav").exists());
+        assert_eq!(
+            fs::read(library.join("Artist/Song.wav")).unwrap(),
+            b"another song"
+        );
+        let tracks = db.list_tracks(10, 0).await.unwrap();
+        assert_eq!(tracks[0].path, moved);
+
+        // Files that are where they belong are imported in place
+        fs::remove_file(library.join("Artist/Song.wav")).unwrap();
+        write_wav(&library.join("Artist/Song.wav"));
+        let result = service
+            .import(&options.clone().with_source(library.clone()), None)
+            .await
+            .unwrap();
+        assert_eq!(result.tracks_imported, 1, "{:?}", result.errors);
+        assert_eq!(result.tracks_skipped, 1);
+        assert!(!library.join("Artist/Song (3).wav").exists());
+    }
+
+    #[tokio::test]
+    async fn test_import_cancellable() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["1.wav", "2.wav", "3.wav"] {
+            write_wav(&dir.path().join(name));
+        }
+        let db = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let service = ImportService::new_basic(Arc::clone(&db) as Arc<dyn Library>);
+        let options = ImportOptions::default().with_source(dir.path().to_path_buf());
+
+        // Cancelled before it starts, nothing is imported
+        let cancel = Arc::new(AtomicBool::new(true));
+        let result = service
+            .import_cancellable(&options, None, &cancel)
+            .await
+            .unwrap();
+        assert!(result.cancelled);
+        assert_eq!(db.count_tracks().await.unwrap(), 0);
+
+        // Cancelled while importing, the rest is left out
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, mut rx) = mpsc::channel(1);
+        let watch_progress = async {
+            while let Some(progress) = rx.recv().await {
+                if matches!(progress, ImportProgress::Importing { .. }) {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            }
+        };
+        let (result, ()) = tokio::join!(
+            service.import_cancellable(&options, Some(tx), &cancel),
+            watch_progress
+        );
+        let result = result.unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.tracks_imported, 1);
+        assert_eq!(db.count_tracks().await.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_group_tracks_by_album() {
+        let track = |title: &str, album: Option<&str>| {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/{title}.mp3")),
+                title.to_string(),
+                "Radiohead".to_string(),
+                std::time::Duration::from_mins(3),
+            );
+            track.album_title = album.map(ToString::to_string);
+            track
+        };
+        let groups = group_tracks(vec![
+            track("Creep", Some("Pablo Honey")),
+            track("Airbag", Some("OK Computer")),
+            track("Unreleased", None),
+            track("You", Some("pablo honey")),
+        ]);
+
+        let titles: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|group| group.iter().map(|t| t.title.as_str()).collect())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![vec!["Creep", "You"], vec!["Airbag"], vec!["Unreleased"]]
+        );
+    }
+
+    #[test]
+    fn test_lookup_batches() {
+        let track = |title: &str, album: Option<&str>| {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/{title}.mp3")),
+                title.to_string(),
+                "Radiohead".to_string(),
+                std::time::Duration::from_mins(3),
+            );
+            track.album_title = album.map(ToString::to_string);
+            track
+        };
+        let batches = lookup_batches(vec![
+            track("Creep", Some("Pablo Honey")),
+            track("Lift", None),
+            track("Nude", None),
+            track("You", Some("Pablo Honey")),
+            track("lift", None),
+        ]);
+
+        let titles: Vec<Vec<&str>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|t| t.title.as_str()).collect())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![vec!["Creep", "You"], vec!["Lift", "lift"], vec!["Nude"]]
+        );
+    }
+
+    /// Create a track of an album, in a file under `/music`.
+    fn album_track(path: &str, artist: &str, album: &str, number: u32) -> Track {
+        let mut track = Track::new(
+            PathBuf::from(format!("/music/{path}")),
+            format!("Track {number}"),
+            artist.to_string(),
+            std::time::Duration::from_mins(3),
+        );
+        track.album_title = Some(album.to_string());
+        track.track_number = Some(number);
+        track
+    }
+
+    #[test]
+    fn test_split_disc_suffix() {
+        assert_eq!(
+            split_disc_suffix("Mellon Collie (Disc 2)"),
+            ("Mellon Collie", Some(2))
+        );
+        assert_eq!(
+            split_disc_suffix("Mellon Collie [CD2]"),
+            ("Mellon Collie", Some(2))
+        );
+        assert_eq!(
+            split_disc_suffix("Mellon Collie - disk 1"),
+            ("Mellon Collie", Some(1))
+        );
+        assert_eq!(split_disc_suffix("CD1"), ("", Some(1)));
+        assert_eq!(split_disc_suffix("Disc_02"), ("", Some(2)));
+        assert_eq!(split_disc_suffix("1999"), ("1999", None));
+        assert_eq!(split_disc_suffix("Blink-182"), ("Blink-182", None));
+        assert_eq!(split_disc_suffix("ABCD1"), ("ABCD1", None));
+    }
+
+    #[test]
+    fn test_group_multi_disc_folders() {
+        let mut tracks = vec![
+            album_track(
+                "Pumpkins/Mellon Collie/CD1/01.mp3",
+                "Pumpkins",
+                "Mellon Collie",
+                1,
+            ),
+            album_track(
+                "Pumpkins/Mellon Collie/CD1/02.mp3",
+                "Pumpkins",
+                "Mellon Collie",
+                2,
+            ),
+            album_track(
+                "Pumpkins/Mellon Collie/CD2/01.mp3",
+                "Pumpkins",
+                "Mellon Collie",
+                1,
+            ),
+            album_track(
+                "Pumpkins/Mellon Collie/CD2/02.mp3",
+                "Pumpkins",
+                "Mellon Collie",
+                2,
+            ),
+            album_track(
+                "Pumpkins/Gish (Disc 1)/01.mp3",
+                "Pumpkins",
+                "Gish (Disc 1)",
+                1,
+            ),
+            album_track(
+                "Pumpkins/Gish (Disc 2)/01.mp3",
+                "Pumpkins",
+                "Gish (Disc 2)",
+                1,
+            ),
+        ];
+        tracks[4].disc_total = Some(2);
+        tracks[5].disc_total = Some(2);
+
+        assert_eq!(
+            group_into_albums(&tracks),
+            vec![vec![0, 1, 2, 3], vec![4, 5]]
+        );
+    }
+
+    #[test]
+    fn test_group_compilation_without_album_artist() {
+        let tracks = vec![
+            album_track("Hits/01.mp3", "Blur", "Hits", 1),
+            album_track("Hits/02.mp3", "Oasis", "Hits", 2),
+            album_track("Hits/03.mp3", "Pulp", "Hits", 3),
+            album_track("Oasis/Hits/01.mp3", "Oasis", "Hits", 1),
+        ];
+
+        let groups = group_into_albums(&tracks);
+        assert_eq!(groups, vec![vec![0, 1, 2], vec![3]]);
+
+        let compilation: Vec<&Track> = groups[0].iter().map(|&i| &tracks[i]).collect();
+        assert_eq!(album_artist(&compilation), VARIOUS_ARTISTS);
+        assert_eq!(album_artist(&[&tracks[3]]), "Oasis");
+    }
+
+    #[test]
+    fn test_group_keeps_editions_apart() {
+        let mut tracks = vec![
+            album_track("Blur/Parklife/01.mp3", "Blur", "Parklife", 1),
+            album_track("Blur/Parklife/02.mp3", "Blur", "Parklife", 2),
+            album_track("Blur/Parklife (Remaster)/01.mp3", "Blur", "Parklife", 1),
+            album_track("Blur/Parklife (Remaster)/02.mp3", "Blur", "Parklife", 2),
+            album_track("Blur/Parklife/03.mp3", "Blur", "parklife", 3),
+        ];
+        tracks[2].year = Some(2012);
+        tracks[3].year = Some(2012);
+
+        assert_eq!(group_into_albums(&tracks), vec![vec![0, 1, 4], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_apply_release_counts() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": "release",
+                "title": "Pablo Honey",
+                "date": "1993-02-22",
+                "media": [
+                    {"position": 1, "track-count": 12, "tracks": []},
+                    {"position": 2, "tracks": [{"id": "t1", "title": "Creep"}]}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut album = Album::new("Pablo Honey".to_string(), "Radiohead".to_string());
+        album.track_count = 2;
+
+        apply_release(&release, &mut album);
+        assert_eq!(album.musicbrainz_id.as_deref(), Some("release"));
+        assert_eq!(album.year, Some(1993));
+        assert_eq!(album.track_count, 13);
+        assert_eq!(album.disc_count, 2);
+    }
+
+    #[test]
+    fn test_save_cover() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.wav");
+        write_wav(&path);
+        let mut track = Track::new(
+            path.clone(),
+            "Song".to_string(),
+            "Artist".to_string(),
+            std::time::Duration::from_millis(100),
+        );
+        track.file_hash = compute_file_hash(&path).unwrap();
+        let original_hash = track.file_hash.clone();
+
+        let artwork = Artwork {
+            data: vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'],
+            mime_type: "image/jpeg".to_string(),
+            source: None,
+        };
+        let options = ImportOptions {
+            write_cover_files: true,
+            embed_album_art: true,
+            ..ImportOptions::default()
+        };
+        let mut result = ImportResult::default();
+        save_cover(&artwork, vec![&mut track], &options, &mut result);
+
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(
+            fs::read(dir.path().join("folder.jpg")).unwrap(),
+            artwork.data
+        );
+        let embedded = read_embedded_art(&path).unwrap().unwrap();
+        assert_eq!(embedded.data, artwork.data);
+        assert_ne!(track.file_hash, original_hash);
+        assert_eq!(track.file_hash, compute_file_hash(&path).unwrap());
+    }
 
     #[test]
     fn test_import_options_default() {
@@ -548,10 +2509,31 @@ mod tests {
         assert!(!options.auto_tag);
         assert!(!options.create_albums);
         assert!(!options.fetch_album_art);
+        assert!(!options.write_cover_files);
+        assert!(!options.embed_album_art);
         assert!(!options.write_tags);
         assert!(!options.compute_hashes);
     }
 
+    #[test]
+    fn test_import_options_with_profile() {
+        let profile = ImportProfile {
+            pattern: "/music/audiobooks/**".to_string(),
+            path_template: Some("Audiobooks/$album/$track".to_string()),
+            auto_tag: Some(false),
+            ..ImportProfile::default()
+        };
+        let options = ImportOptions {
+            auto_tag: true,
+            fetch_album_art: true,
+            ..ImportOptions::default()
+        }
+        .with_profile(&profile);
+        assert!(!options.auto_tag);
+        assert!(options.fetch_album_art);
+        assert_eq!(options.path_template, "Audiobooks/$album/$track");
+    }
+
     #[test]
     fn test_import_result_default() {
         let result = ImportResult::default();
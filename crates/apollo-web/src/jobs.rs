@@ -0,0 +1,93 @@
+//! Cancellation of the jobs that API requests start.
+//!
+//! A request that starts a long job, such as an import, can give it an ID.
+//! While the job runs, the [`JobManager`] of the [`AppState`](crate::AppState)
+//! holds the flag that the job checks for cancellation under that ID, and
+//! `DELETE /api/jobs/:id` sets it.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Cancel flags of the running jobs, by job ID.
+#[derive(Debug, Default)]
+pub struct JobManager {
+    jobs: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl JobManager {
+    /// Register a job that runs under `id`.
+    ///
+    /// Returns `None` if a job with the same ID is running already.
+    pub fn start(&self, id: &str) -> Option<RunningJob<'_>> {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        if jobs.contains_key(id) {
+            return None;
+        }
+        jobs.insert(id.to_string(), Arc::clone(&cancel));
+        drop(jobs);
+        Some(RunningJob {
+            manager: self,
+            id: id.to_string(),
+            cancel,
+        })
+    }
+
+    /// Ask the job running under `id` to stop.
+    ///
+    /// Returns whether a job with the ID is running.
+    pub fn cancel(&self, id: &str) -> bool {
+        let jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.get(id)
+            .map(|cancel| cancel.store(true, Ordering::Relaxed))
+            .is_some()
+    }
+}
+
+/// A job registered with a [`JobManager`], until it is dropped.
+#[derive(Debug)]
+pub struct RunningJob<'a> {
+    manager: &'a JobManager,
+    id: String,
+    cancel: Arc<AtomicBool>,
+}
+
+impl RunningJob<'_> {
+    /// The flag that is set when the job is cancelled.
+    #[must_use]
+    pub const fn cancel_flag(&self) -> &Arc<AtomicBool> {
+        &self.cancel
+    }
+}
+
+impl Drop for RunningJob<'_> {
+    fn drop(&mut self) {
+        self.manager
+            .jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_running_job() {
+        let manager = JobManager::default();
+        assert!(!manager.cancel("import"));
+
+        let job = manager.start("import").unwrap();
+        assert!(manager.start("import").is_none());
+        assert!(!job.cancel_flag().load(Ordering::Relaxed));
+        assert!(manager.cancel("import"));
+        assert!(job.cancel_flag().load(Ordering::Relaxed));
+
+        drop(job);
+        assert!(!manager.cancel("import"));
+        assert!(manager.start("import").is_some());
+    }
+}
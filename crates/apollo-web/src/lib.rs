@@ -10,47 +10,128 @@
 //! ## Endpoints
 //!
 //! - `GET /api/tracks` - List all tracks with pagination
+//! - `POST /api/tracks` - Add a track, such as one from another library
 //! - `GET /api/tracks/:id` - Get a single track by ID
+//! - `GET /api/tracks/:id/stream` - Stream the audio file of a track
+//! - `GET /api/tracks/:id/provenance` - Get where the fields of a track came from
+//! - `GET /api/tracks/:id/locks` - Get the locked fields of a track
+//! - `PUT /api/tracks/:id/locks` - Set the locked fields of a track
+//! - `POST /api/tracks/:id/plays` - Record a play of a track
+//! - `PUT /api/tracks/:id/file` - Upload the audio file of a track
+//! - `POST /api/tracks/:id/fingerprint` - Compute and store the audio fingerprint of a track
+//! - `POST /api/tracks/:id/identify` - Find the recordings a track may be, and apply the best one
+//! - `GET /api/tracks/:id/candidates` - Find releases and recordings to tag a track with
+//! - `POST /api/tracks/:id/apply-candidate` - Tag a track with one of its candidates
 //! - `GET /api/albums` - List all albums with pagination
+//! - `POST /api/albums` - Add an album, such as one from another library
 //! - `GET /api/albums/:id` - Get a single album by ID
 //! - `GET /api/albums/:id/tracks` - Get all tracks in an album
+//! - `GET /api/albums/:id/download` - Download an album as a ZIP archive
+//! - `GET /api/albums/:id/art` - Get the cover art of an album
+//! - `GET /api/artists/:id/image` - Get an image of an artist
 //! - `GET /api/playlists` - List all playlists
 //! - `GET /api/playlists/:id` - Get a single playlist by ID
-//! - `GET /api/playlists/:id/tracks` - Get all tracks in a playlist
+//! - `GET /api/playlists/:id/tracks` - Get the tracks in a playlist with pagination
 //! - `POST /api/playlists` - Create a new playlist
+//! - `POST /api/playlists/from-query` - Save the tracks a query matches as a playlist
+//! - `POST /api/playlists/from-tracks` - Save tracks as a playlist
 //! - `PATCH /api/playlists/:id` - Update a playlist
 //! - `DELETE /api/playlists/:id` - Delete a playlist
 //! - `POST /api/playlists/:id/tracks` - Add tracks to a playlist
 //! - `DELETE /api/playlists/:id/tracks` - Remove tracks from a playlist
+//! - `GET /api/playlists/:id/versions` - Get the track lists a playlist had before it changed
+//! - `POST /api/playlists/:id/versions/:version/restore` - Give a playlist the tracks of a version
 //! - `GET /api/search` - Search tracks by query
+//! - `GET /api/search/suggest` - Suggest names for a partly typed search
 //! - `GET /api/stats` - Get library statistics
+//! - `GET /api/changes` - Get the tracks and playlists changed since a sync
 //! - `POST /api/import` - Import music from a directory
+//! - `POST /api/import/preview` - Show what importing a directory would do
+//! - `GET /api/import/history` - Get the most recent imports and what came of them
+//! - `GET /api/events` - Stream library changes as server-sent events
+//! - `GET /api/jobs/scheduled` - Get the status of scheduled maintenance jobs
+//! - `DELETE /api/jobs/:id` - Cancel a running job, such as an import given a job ID
 //! - `GET /swagger-ui` - Interactive API documentation
+//!
+//! With `web.read_only` set, the endpoints that change the library, such as
+//! those that edit playlists or import music, are not served, and the API
+//! documentation leaves them out. This suits a public instance for browsing.
+//!
+//! Album art, artist images, and static files carry caching headers, and
+//! are answered with `304 Not Modified` when a client's copy is current; see
+//! [`http_cache`].
+//!
+//! Each request is logged with its method, path, status, and duration, and
+//! given an ID that is sent back in the `x-request-id` header and in error
+//! responses; see [`access_log`] and `web.logging`.
+//!
+//! With `dlna.enabled` set, the routes of the [`dlna`] media server are
+//! served under `/dlna` as well. MPD clients are served on a port of their
+//! own by the [`mpd`] listener.
 
+pub mod access_log;
+pub mod artist_image;
+mod cache;
+pub mod candidates;
+pub mod dlna;
 mod error;
+pub mod export;
 mod handlers;
+pub mod http_cache;
+pub mod identify;
 pub mod import;
+pub mod jobs;
+pub mod mpd;
+pub mod queue;
+pub mod schedule;
+pub mod scrobble;
 mod state;
+pub mod watch;
+pub mod writeback;
+mod zip;
 
+pub use candidates::{CandidateKind, CandidateSource, TagCandidate, TagSources};
 pub use error::ApiError;
+pub use export::{ExportResult, ExportTarget, PlaylistExporter};
 pub use handlers::{
-    CreatePlaylistRequest, ErrorResponse, HealthResponse, ImportRequest, ImportResponse,
-    PaginatedAlbumsResponse, PaginatedTracksResponse, PlaylistResponse, PlaylistTracksRequest,
-    StatsResponse, UpdatePlaylistRequest,
+    ApplyCandidateRequest, CreatePlaylistRequest, ErrorResponse, HealthResponse, IdentifyResponse,
+    ImportRequest, ImportResponse, PaginatedAlbumsResponse, PaginatedTracksResponse,
+    PlaylistFromQueryRequest, PlaylistFromTracksRequest, PlaylistResponse, PlaylistTracksRequest,
+    RecordPlayRequest, StatsResponse, TrackLocksRequest, TrackLocksResponse, UpdatePlaylistRequest,
 };
-pub use import::{ImportOptions, ImportProgress, ImportResult, ImportService};
+pub use identify::{IdentifyCandidate, TrackIdentifier};
+pub use import::{
+    ImportDuplicate, ImportOptions, ImportPreview, ImportProgress, ImportResult, ImportService,
+    PreviewAlbum, PreviewTrack,
+};
+pub use jobs::{JobManager, RunningJob};
+pub use mpd::MpdServer;
+pub use queue::{PlayQueue, PlayState, QueueChange, QueueEntry, QueueStatus};
+pub use schedule::{JobScheduler, JobStatus, ScheduledJob};
+pub use scrobble::{ScrobbleResult, Scrobbler};
 pub use state::AppState;
+pub use watch::FolderWatcher;
+pub use writeback::{TagWriter, WriteResult};
 
-use apollo_core::metadata::{Album, AlbumId, Artist, AudioFormat, Track, TrackId};
+use apollo_core::changes::{ChangedIds, LibraryChanges};
+use apollo_core::diff::FieldChange;
+use apollo_core::import_run::ImportRun;
+use apollo_core::library::{Suggestion, Suggestions};
+use apollo_core::metadata::{
+    Album, AlbumId, AlbumType, Artist, AudioFormat, Fingerprint, Media, Track, TrackId,
+};
+use apollo_core::playlist::PlaylistVersion;
+use apollo_core::provenance::{FieldSource, MetadataSource, Provenance};
 use axum::{
     Router,
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, patch, post, put},
 };
 use std::path::Path;
 use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::{ServeDir, ServeFile};
-use tower_http::trace::TraceLayer;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -70,6 +151,7 @@ use utoipa_swagger_ui::SwaggerUi;
     tags(
         (name = "Tracks", description = "Track management endpoints"),
         (name = "Albums", description = "Album management endpoints"),
+        (name = "Artists", description = "Artist endpoints"),
         (name = "Playlists", description = "Playlist management endpoints"),
         (name = "Import", description = "Music import endpoints"),
         (name = "Search", description = "Search endpoints"),
@@ -78,22 +160,48 @@ use utoipa_swagger_ui::SwaggerUi;
     ),
     paths(
         handlers::health_check,
+        handlers::library_events,
         handlers::get_stats,
+        handlers::get_changes,
         handlers::list_tracks,
+        handlers::add_track,
         handlers::get_track,
+        handlers::stream_track,
+        handlers::get_track_provenance,
+        handlers::get_track_locks,
+        handlers::set_track_locks,
+        handlers::record_play,
+        handlers::upload_track_file,
+        handlers::fingerprint_track,
+        handlers::identify_track,
+        handlers::get_track_candidates,
+        handlers::apply_track_candidate,
         handlers::list_albums,
+        handlers::add_album,
         handlers::get_album,
         handlers::get_album_tracks,
+        handlers::download_album,
+        handlers::get_album_art,
+        handlers::get_artist_image,
         handlers::search_tracks,
+        handlers::suggest,
         handlers::list_playlists,
         handlers::get_playlist,
         handlers::get_playlist_tracks,
         handlers::create_playlist,
+        handlers::create_playlist_from_query,
+        handlers::create_playlist_from_tracks,
         handlers::update_playlist,
         handlers::delete_playlist,
         handlers::add_playlist_tracks,
         handlers::remove_playlist_tracks,
-        handlers::import_music
+        handlers::list_playlist_versions,
+        handlers::restore_playlist_version,
+        handlers::import_music,
+        handlers::preview_import,
+        handlers::import_history,
+        handlers::list_scheduled_jobs,
+        handlers::cancel_job
     ),
     components(
         schemas(
@@ -102,7 +210,22 @@ use utoipa_swagger_ui::SwaggerUi;
             Artist,
             TrackId,
             AlbumId,
+            AlbumType,
+            Media,
             AudioFormat,
+            Fingerprint,
+            FieldChange,
+            Provenance,
+            FieldSource,
+            MetadataSource,
+            IdentifyCandidate,
+            IdentifyResponse,
+            CandidateSource,
+            CandidateKind,
+            TagCandidate,
+            ApplyCandidateRequest,
+            TrackLocksResponse,
+            TrackLocksRequest,
             HealthResponse,
             StatsResponse,
             ErrorResponse,
@@ -110,15 +233,48 @@ use utoipa_swagger_ui::SwaggerUi;
             PaginatedAlbumsResponse,
             PlaylistResponse,
             CreatePlaylistRequest,
+            PlaylistFromQueryRequest,
+            PlaylistFromTracksRequest,
             UpdatePlaylistRequest,
             PlaylistTracksRequest,
+            PlaylistVersion,
+            RecordPlayRequest,
             ImportRequest,
-            ImportResponse
+            ImportResponse,
+            ImportPreview,
+            PreviewAlbum,
+            PreviewTrack,
+            ImportDuplicate,
+            ImportRun,
+            JobStatus,
+            LibraryChanges,
+            ChangedIds,
+            Suggestion,
+            Suggestions
         )
     )
 )]
 pub struct ApiDoc;
 
+/// Build the [`ApiDoc`] document for the endpoints that a server serves.
+///
+/// A read-only server leaves out the operations that change the library,
+/// and the paths that have no other operations.
+#[must_use]
+pub fn openapi(read_only: bool) -> utoipa::openapi::OpenApi {
+    let mut doc = ApiDoc::openapi();
+    if read_only {
+        doc.paths.paths.retain(|_, item| {
+            item.post = None;
+            item.put = None;
+            item.patch = None;
+            item.delete = None;
+            item.get.is_some()
+        });
+    }
+    doc
+}
+
 /// Create the API router with all endpoints.
 ///
 /// # Arguments
@@ -151,59 +307,146 @@ pub fn create_router_with_static_files(
         .allow_methods(Any)
         .allow_headers(Any);
 
+    let dlna_enabled = state.config.dlna.enabled;
+    let read_only = state.config.web.read_only;
     let mut router = Router::new()
         // Track endpoints
         .route("/api/tracks", get(handlers::list_tracks))
         .route("/api/tracks/:id", get(handlers::get_track))
+        .route("/api/tracks/:id/stream", get(handlers::stream_track))
+        .route(
+            "/api/tracks/:id/provenance",
+            get(handlers::get_track_provenance),
+        )
+        .route("/api/tracks/:id/locks", get(handlers::get_track_locks))
+        .route(
+            "/api/tracks/:id/candidates",
+            get(handlers::get_track_candidates),
+        )
         // Album endpoints
         .route("/api/albums", get(handlers::list_albums))
         .route("/api/albums/:id", get(handlers::get_album))
         .route("/api/albums/:id/tracks", get(handlers::get_album_tracks))
+        .route("/api/albums/:id/download", get(handlers::download_album))
+        .route("/api/albums/:id/art", get(handlers::get_album_art))
+        // Artist endpoints
+        .route("/api/artists/:id/image", get(handlers::get_artist_image))
         // Playlist endpoints
+        .route("/api/playlists", get(handlers::list_playlists))
+        .route("/api/playlists/:id", get(handlers::get_playlist))
         .route(
-            "/api/playlists",
-            get(handlers::list_playlists).post(handlers::create_playlist),
-        )
-        .route(
-            "/api/playlists/:id",
-            get(handlers::get_playlist)
-                .patch(handlers::update_playlist)
-                .delete(handlers::delete_playlist),
+            "/api/playlists/:id/tracks",
+            get(handlers::get_playlist_tracks),
         )
         .route(
-            "/api/playlists/:id/tracks",
-            get(handlers::get_playlist_tracks)
-                .post(handlers::add_playlist_tracks)
-                .delete(handlers::remove_playlist_tracks),
+            "/api/playlists/:id/versions",
+            get(handlers::list_playlist_versions),
         )
         // Search endpoint
         .route("/api/search", get(handlers::search_tracks))
+        .route("/api/search/suggest", get(handlers::suggest))
         // Stats endpoint
         .route("/api/stats", get(handlers::get_stats))
-        // Import endpoint
-        .route("/api/import", post(handlers::import_music))
+        // Changes for incremental sync
+        .route("/api/changes", get(handlers::get_changes))
+        // Import history
+        .route("/api/import/history", get(handlers::import_history))
+        // Change events
+        .route("/api/events", get(handlers::library_events))
+        // Scheduled jobs
+        .route("/api/jobs/scheduled", get(handlers::list_scheduled_jobs))
         // Health check
         .route("/health", get(handlers::health_check))
         // OpenAPI documentation
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-        // Add shared state
-        .with_state(state);
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", openapi(read_only)));
+
+    // Endpoints that change the library
+    if !read_only {
+        router = router.merge(mutating_routes());
+    }
+
+    // DLNA media server
+    if dlna_enabled {
+        router = router.merge(dlna::routes());
+    }
+
+    // Add shared state
+    let access_log = middleware::from_fn_with_state(Arc::clone(&state), access_log::log_requests);
+    let mut router = router.with_state(state);
 
     // Serve static files if path is provided (for embedded web UI)
     if let Some(path) = static_files_path {
         let index_file = path.join("index.html");
-        router = router
-            .fallback_service(ServeDir::new(path).not_found_service(ServeFile::new(index_file)));
+        let static_files = Router::new()
+            .fallback_service(ServeDir::new(path).not_found_service(ServeFile::new(index_file)))
+            .layer(middleware::map_response(
+                http_cache::revalidate_static_files,
+            ));
+        router = router.merge(static_files);
     }
 
     // Add middleware
-    router.layer(cors).layer(TraceLayer::new_for_http())
+    router.layer(cors).layer(access_log)
+}
+
+/// Routes of the endpoints that change the library, which a read-only
+/// server does not serve.
+fn mutating_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        // Track endpoints
+        .route("/api/tracks", post(handlers::add_track))
+        .route("/api/tracks/:id/plays", post(handlers::record_play))
+        .route(
+            "/api/tracks/:id/file",
+            put(handlers::upload_track_file).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/api/tracks/:id/fingerprint",
+            post(handlers::fingerprint_track),
+        )
+        .route("/api/tracks/:id/identify", post(handlers::identify_track))
+        .route(
+            "/api/tracks/:id/apply-candidate",
+            post(handlers::apply_track_candidate),
+        )
+        .route("/api/tracks/:id/locks", put(handlers::set_track_locks))
+        // Album endpoints
+        .route("/api/albums", post(handlers::add_album))
+        // Playlist endpoints
+        .route("/api/playlists", post(handlers::create_playlist))
+        .route(
+            "/api/playlists/from-query",
+            post(handlers::create_playlist_from_query),
+        )
+        .route(
+            "/api/playlists/from-tracks",
+            post(handlers::create_playlist_from_tracks),
+        )
+        .route(
+            "/api/playlists/:id",
+            patch(handlers::update_playlist).delete(handlers::delete_playlist),
+        )
+        .route(
+            "/api/playlists/:id/tracks",
+            post(handlers::add_playlist_tracks).delete(handlers::remove_playlist_tracks),
+        )
+        .route(
+            "/api/playlists/:id/versions/:version/restore",
+            post(handlers::restore_playlist_version),
+        )
+        // Import endpoint
+        .route("/api/import", post(handlers::import_music))
+        .route("/api/import/preview", post(handlers::preview_import))
+        // Job endpoints
+        .route("/api/jobs/:id", delete(handlers::cancel_job))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use apollo_core::metadata::{Album, Track};
+    use apollo_core::playlist::{Playlist, PlaylistId, PlaylistLimit, PlaylistSort};
+    use apollo_core::query::Query as ApolloQuery;
     use apollo_db::SqliteLibrary;
     use axum_test::TestServer;
     use std::path::PathBuf;
@@ -225,7 +468,7 @@ mod tests {
                 PathBuf::from(format!("/music/track{i}.mp3")),
                 format!("Track {i}"),
                 "Test Artist".to_string(),
-                Duration::from_secs(180),
+                Duration::from_mins(3),
             );
             db.add_track(&track).await.unwrap();
         }
@@ -239,6 +482,539 @@ mod tests {
         TestServer::new(router).unwrap()
     }
 
+    /// Collect the schema names that a document refers to.
+    fn schema_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    match value.as_str() {
+                        Some(target) if key == "$ref" => refs.push(
+                            target
+                                .trim_start_matches("#/components/schemas/")
+                                .to_string(),
+                        ),
+                        _ => schema_refs(value, refs),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    schema_refs(item, refs);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_openapi_schemas_are_complete() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &doc["components"]["schemas"];
+
+        let mut refs = Vec::new();
+        schema_refs(&doc, &mut refs);
+        for name in refs {
+            assert!(
+                schemas.get(&name).is_some(),
+                "schema {name} is not registered"
+            );
+        }
+
+        let track = &schemas["Track"]["properties"];
+        for field in ["isrc", "rg_track_gain", "rg_album_peak", "loudness_lufs"] {
+            assert!(track.get(field).is_some(), "Track schema lacks {field}");
+        }
+        let album = &schemas["Album"]["properties"];
+        for field in ["album_type", "release_date", "barcode", "discogs_id"] {
+            assert!(album.get(field).is_some(), "Album schema lacks {field}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mode() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let playlist = Playlist::new_static("Road Trip");
+        db.add_playlist(&playlist).await.unwrap();
+        let mut config = apollo_core::Config::default();
+        config.web.read_only = true;
+        let state = Arc::new(AppState::new(db).with_config(config));
+        let server = TestServer::new(create_router(state)).unwrap();
+
+        server.get("/api/playlists").await.assert_status_ok();
+        server
+            .get(&format!("/api/playlists/{}", playlist.id))
+            .await
+            .assert_status_ok();
+        server
+            .post("/api/playlists")
+            .json(&serde_json::json!({ "name": "Mine" }))
+            .await
+            .assert_status(axum::http::StatusCode::METHOD_NOT_ALLOWED);
+        server
+            .delete(&format!("/api/playlists/{}", playlist.id))
+            .await
+            .assert_status(axum::http::StatusCode::METHOD_NOT_ALLOWED);
+        server
+            .post("/api/import")
+            .json(&serde_json::json!({ "path": "/music" }))
+            .await
+            .assert_status_not_found();
+
+        let doc = serde_json::to_value(openapi(true)).unwrap();
+        let paths = &doc["paths"];
+        assert!(paths["/api/playlists"]["get"].is_object());
+        assert!(paths["/api/playlists"].get("post").is_none());
+        assert!(paths["/api/playlists/{id}"].get("delete").is_none());
+        assert!(paths.get("/api/import").is_none());
+        assert!(paths.get("/api/tracks/{id}/file").is_none());
+
+        let doc = serde_json::to_value(openapi(false)).unwrap();
+        assert!(doc["paths"]["/api/import"]["post"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_changes_since_sync() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/track.mp3"),
+            "Track".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        let state = Arc::new(AppState::new(db));
+        let server = TestServer::new(create_router(Arc::clone(&state))).unwrap();
+
+        let response = server.get("/api/changes").await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["tracks"]["created"][0], track.id.0.to_string());
+        let seq = body["seq"].as_i64().unwrap();
+        let etag = response.header("etag");
+        assert_eq!(etag, format!("\"{seq}\""));
+
+        // Nothing changed since the last sync
+        let response = server
+            .get(&format!("/api/changes?since={seq}"))
+            .add_header("if-none-match", etag.clone())
+            .await;
+        response.assert_status(axum::http::StatusCode::NOT_MODIFIED);
+
+        state.db.remove_track(&track.id).await.unwrap();
+        let response = server.get(&format!("/api/changes?since={seq}")).await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["tracks"]["deleted"][0], track.id.0.to_string());
+        assert!(body["seq"].as_i64().unwrap() > seq);
+
+        let response = server.get("/api/changes?since=2000-01-01T00:00:00Z").await;
+        let body: serde_json::Value = response.json();
+        // Created and deleted since then, so there is nothing to sync
+        assert_eq!(body["tracks"]["created"].as_array().unwrap().len(), 0);
+        assert_eq!(body["tracks"]["deleted"].as_array().unwrap().len(), 0);
+
+        server
+            .get("/api/changes?since=yesterday")
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_artist_image_placeholder() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/track.mp3"),
+            "Track".to_string(),
+            "Nina Simone".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        let server = TestServer::new(create_router(Arc::new(AppState::new(db)))).unwrap();
+
+        // Without artist images set up, every artist gets a placeholder
+        let id = apollo_core::metadata::ArtistId::from_name("Nina Simone");
+        let response = server.get(&format!("/api/artists/{id}/image")).await;
+        response.assert_status_ok();
+        assert_eq!(response.header("content-type"), "image/svg+xml");
+        assert!(response.text().contains(">NS</text>"));
+        let etag = response.header("etag");
+        server
+            .get(&format!("/api/artists/{id}/image"))
+            .add_header("if-none-match", etag)
+            .await
+            .assert_status(axum::http::StatusCode::NOT_MODIFIED);
+
+        let unknown = apollo_core::metadata::ArtistId::from_name("Nobody");
+        server
+            .get(&format!("/api/artists/{unknown}/image"))
+            .await
+            .assert_status_not_found();
+        server
+            .get("/api/artists/not-an-id/image")
+            .await
+            .assert_status_bad_request();
+    }
+
+    /// Start mock `AcoustID` and `MusicBrainz` services that identify one
+    /// recording, and an identifier that looks tracks up on them.
+    ///
+    /// The services stop when the returned servers are dropped.
+    async fn mock_identifier() -> (TrackIdentifier, [wiremock::MockServer; 2]) {
+        use apollo_sources::acoustid::AcoustIdClient;
+        use apollo_sources::musicbrainz::MusicBrainzClient;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let acoustid = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/lookup"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "status": "ok",
+                "results": [
+                    {"id": "acoustid-1", "score": 0.95, "recordings": [{"id": "recording-1"}]},
+                    {"id": "acoustid-2", "score": 0.4, "recordings": [{"id": "recording-2"}]}
+                ]
+            })))
+            .mount(&acoustid)
+            .await;
+        let musicbrainz = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/recording/recording-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "recording-1",
+                "title": "Feeling Good",
+                "artist-credit": [{"name": "Nina Simone", "artist": {"id": "a", "name": "Nina Simone"}}],
+                "releases": [{"id": "r", "title": "I Put a Spell on You", "date": "1965"}]
+            })))
+            .mount(&musicbrainz)
+            .await;
+
+        let identifier = TrackIdentifier::new(
+            AcoustIdClient::new("key")
+                .unwrap()
+                .with_base_url(acoustid.uri()),
+            MusicBrainzClient::new("Apollo", "test", "")
+                .unwrap()
+                .with_base_url(musicbrainz.uri()),
+        );
+        (identifier, [acoustid, musicbrainz])
+    }
+
+    #[tokio::test]
+    async fn test_identify_track() {
+        use apollo_core::metadata::Fingerprint;
+
+        let (identifier, _services) = mock_identifier().await;
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/feeling-good.mp3"),
+            "Feelin Good".to_string(),
+            "Nina Simone".to_string(),
+            Duration::from_mins(3),
+        );
+        let mut edited = track.clone();
+        edited.album_title = Some("The Best of Nina Simone".to_string());
+        let id = db.add_track(&edited).await.unwrap();
+        let diff = apollo_core::TrackDiff::between(&track, &edited);
+        db.record_provenance(&id, &Provenance::from_diff(&diff, MetadataSource::Manual))
+            .await
+            .unwrap();
+        let other = Track::new(
+            PathBuf::from("/music/missing.mp3"),
+            "Missing".to_string(),
+            "Nobody".to_string(),
+            Duration::from_mins(3),
+        );
+        let other_id = db.add_track(&other).await.unwrap();
+        let fingerprint = Fingerprint {
+            fingerprint: "AQADtMkSJYkSZQmS".to_string(),
+            duration_secs: 180,
+            computed_at: chrono::Utc::now(),
+        };
+        db.set_fingerprint(&id, &fingerprint).await.unwrap();
+
+        let state = AppState::new(db).with_identifier(identifier);
+        let server = TestServer::new(create_router(Arc::new(state))).unwrap();
+
+        // Candidates are returned without changing the track
+        let response = server.post(&format!("/api/tracks/{id}/identify")).await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["applied"], false);
+        let candidates = body["candidates"].as_array().unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0]["recording_id"], "recording-1");
+        assert_eq!(candidates[0]["track"]["title"], "Feeling Good");
+        assert!(
+            candidates[0]["changes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|change| change["field"] == "title")
+        );
+        let response = server.get(&format!("/api/tracks/{id}")).await;
+        assert_eq!(response.json::<serde_json::Value>()["title"], "Feelin Good");
+
+        // Applying saves the best candidate, but keeps fields edited by hand
+        let response = server
+            .post(&format!("/api/tracks/{id}/identify?apply=true"))
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.json::<serde_json::Value>()["applied"], true);
+        let track: serde_json::Value = server.get(&format!("/api/tracks/{id}")).await.json();
+        assert_eq!(track["title"], "Feeling Good");
+        assert_eq!(track["album_title"], "The Best of Nina Simone");
+        assert_eq!(track["acoustid"], "acoustid-1");
+        assert_eq!(track["musicbrainz_id"], "recording-1");
+
+        let provenance: serde_json::Value = server
+            .get(&format!("/api/tracks/{id}/provenance"))
+            .await
+            .json();
+        assert_eq!(provenance["fields"]["title"]["source"], "musicbrainz");
+        assert_eq!(provenance["fields"]["album_title"]["source"], "manual");
+        assert!(provenance["fields"].get("artist").is_none());
+        server
+            .get(&format!("/api/tracks/{}/provenance", TrackId::new()))
+            .await
+            .assert_status_not_found();
+
+        // Tracks without a file cannot be fingerprinted
+        server
+            .post(&format!("/api/tracks/{other_id}/fingerprint"))
+            .await
+            .assert_status_not_found();
+        server
+            .post(&format!("/api/tracks/{other_id}/identify"))
+            .await
+            .assert_status_not_found();
+
+        // Without AcoustID set up, tracks cannot be identified
+        let server = create_test_server().await;
+        server
+            .post(&format!("/api/tracks/{id}/identify"))
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_track_locks() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/feeling-good.flac"),
+            "Feeling Good".to_string(),
+            "Nina Simone".to_string(),
+            Duration::from_secs(177),
+        );
+        let id = track.id.clone();
+        db.add_track(&track).await.unwrap();
+        let mut config = apollo_core::Config::default();
+        config.locks.fields = vec!["year".to_string()];
+        let state = AppState::new(db).with_config(config);
+        let server = TestServer::new(create_router(Arc::new(state))).unwrap();
+
+        let response = server.get(&format!("/api/tracks/{id}/locks")).await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["fields"], serde_json::json!([]));
+        assert_eq!(body["global"], serde_json::json!(["year"]));
+
+        let response = server
+            .put(&format!("/api/tracks/{id}/locks"))
+            .json(&serde_json::json!({"fields": ["title", "genres", "genre"]}))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["fields"], serde_json::json!(["title", "genre"]));
+
+        server
+            .put(&format!("/api/tracks/{id}/locks"))
+            .json(&serde_json::json!({"fields": ["lyrics"]}))
+            .await
+            .assert_status_bad_request();
+        let missing = TrackId::new();
+        server
+            .put(&format!("/api/tracks/{missing}/locks"))
+            .json(&serde_json::json!({"fields": ["title"]}))
+            .await
+            .assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_import_history() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        for path in ["/incoming/first", "/incoming/second"] {
+            let run = ImportRun {
+                id: 0,
+                source_path: PathBuf::from(path),
+                started_at: chrono::Utc::now(),
+                duration_ms: 100,
+                options: serde_json::json!({}),
+                tracks_found: 2,
+                tracks_imported: 1,
+                tracks_skipped: 0,
+                tracks_duplicate: 0,
+                tracks_failed: 1,
+                albums_created: 0,
+                cancelled: false,
+                errors: vec!["Failed to import".to_string()],
+                failed_files: vec![PathBuf::from(path).join("02.mp3")],
+            };
+            db.record_import_run(&run).await.unwrap();
+        }
+        let server = TestServer::new(create_router(Arc::new(AppState::new(db)))).unwrap();
+
+        let response = server.get("/api/import/history").await;
+        response.assert_status_ok();
+        let runs: serde_json::Value = response.json();
+        assert_eq!(runs.as_array().unwrap().len(), 2);
+        assert_eq!(runs[0]["source_path"], "/incoming/second");
+        assert_eq!(runs[0]["failed_files"][0], "/incoming/second/02.mp3");
+
+        let runs: serde_json::Value = server.get("/api/import/history?limit=1").await.json();
+        assert_eq!(runs.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_preview() {
+        let server = create_test_server().await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let response = server
+            .post("/api/import/preview")
+            .json(&serde_json::json!({"path": dir.path()}))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["tracks_found"], 0);
+        assert_eq!(body["albums"], serde_json::json!([]));
+
+        server
+            .post("/api/import/preview")
+            .json(&serde_json::json!({"path": dir.path().join("missing")}))
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_track_candidates() {
+        use apollo_sources::musicbrainz::MusicBrainzClient;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let recording = serde_json::json!({
+            "id": "recording-1",
+            "title": "Feeling Good",
+            "score": 90,
+            "artist-credit": [{"name": "Nina Simone", "artist": {"id": "a", "name": "Nina Simone"}}],
+            "releases": [{"id": "r", "title": "I Put a Spell on You", "date": "1965"}]
+        });
+        let musicbrainz = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/recording"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "recordings": [recording.clone()]
+            })))
+            .mount(&musicbrainz)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/recording/recording-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(recording))
+            .mount(&musicbrainz)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/recording/unknown"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&musicbrainz)
+            .await;
+
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/feeling-good.mp3"),
+            "Feelin Good".to_string(),
+            "Nina Simone".to_string(),
+            Duration::from_mins(3),
+        );
+        let id = db.add_track(&track).await.unwrap();
+        let sources = TagSources::default().with_musicbrainz(
+            MusicBrainzClient::new("Apollo", "test", "")
+                .unwrap()
+                .with_base_url(musicbrainz.uri()),
+        );
+        let state = AppState::new(db).with_tag_sources(sources);
+        let server = TestServer::new(create_router(Arc::new(state))).unwrap();
+
+        let response = server.get(&format!("/api/tracks/{id}/candidates")).await;
+        response.assert_status_ok();
+        let candidates: serde_json::Value = response.json();
+        assert_eq!(candidates[0]["source"], "musicbrainz");
+        assert_eq!(candidates[0]["kind"], "recording");
+        assert_eq!(candidates[0]["id"], "recording-1");
+        assert_eq!(candidates[0]["score"], 0.9);
+        assert_eq!(candidates[0]["track"]["title"], "Feeling Good");
+        assert!(
+            candidates[0]["changes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|change| change["field"] == "album_title")
+        );
+
+        let response = server
+            .post(&format!("/api/tracks/{id}/apply-candidate"))
+            .json(&serde_json::json!({
+                "source": "musicbrainz",
+                "kind": "recording",
+                "id": "recording-1"
+            }))
+            .await;
+        response.assert_status_ok();
+        let track: serde_json::Value = server.get(&format!("/api/tracks/{id}")).await.json();
+        assert_eq!(track["title"], "Feeling Good");
+        assert_eq!(track["musicbrainz_id"], "recording-1");
+
+        server
+            .post(&format!("/api/tracks/{id}/apply-candidate"))
+            .json(&serde_json::json!({
+                "source": "musicbrainz",
+                "kind": "recording",
+                "id": "unknown"
+            }))
+            .await
+            .assert_status_not_found();
+
+        // Discogs is not set up without a token
+        server
+            .get(&format!("/api/tracks/{id}/candidates?source=discogs"))
+            .await
+            .assert_status_bad_request();
+        server
+            .get(&format!("/api/tracks/{id}/candidates?source=nowhere"))
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_search_suggestions() {
+        let server = create_test_server_with_data().await;
+
+        let response = server.get("/api/search/suggest?q=tes").await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["artists"][0]["name"], "Test Artist");
+        assert_eq!(body["albums"][0]["name"], "Test Album");
+        assert_eq!(body["tracks"].as_array().unwrap().len(), 0);
+
+        let response = server.get("/api/search/suggest?q=track&limit=2").await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["tracks"].as_array().unwrap().len(), 2);
+        assert_eq!(body["tracks"][0]["artist"], "Test Artist");
+
+        let response = server.get("/api/search/suggest?q=").await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["artists"].as_array().unwrap().len(), 0);
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let server = create_test_server().await;
@@ -250,6 +1026,34 @@ mod tests {
         assert_eq!(body["status"], "healthy");
     }
 
+    #[tokio::test]
+    async fn test_request_ids() {
+        let server = create_test_server().await;
+
+        let response = server.get("/health").await;
+        let id = response.header("x-request-id");
+        assert_eq!(id.len(), 36);
+
+        // An ID set by a proxy is kept, and error responses carry it
+        let response = server
+            .get("/api/tracks/00000000-0000-0000-0000-000000000000")
+            .add_header("x-request-id", "proxy-42")
+            .await;
+        response.assert_status_not_found();
+        assert_eq!(response.header("x-request-id"), "proxy-42");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["request_id"], "proxy-42");
+
+        let mut config = apollo_core::Config::default();
+        config.web.logging.request_id_header = "X-Trace-Id".to_string();
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let state = Arc::new(AppState::new(db).with_config(config));
+        let server = TestServer::new(create_router(state)).unwrap();
+        let response = server.get("/health").add_header("x-trace-id", "abc").await;
+        assert_eq!(response.header("x-trace-id"), "abc");
+        assert!(response.headers().get("x-request-id").is_none());
+    }
+
     #[tokio::test]
     async fn test_stats_empty_library() {
         let server = create_test_server().await;
@@ -298,6 +1102,41 @@ mod tests {
         assert_eq!(body["items"].as_array().unwrap().len(), 2);
         assert_eq!(body["limit"], 2);
         assert_eq!(body["offset"], 0);
+        assert_eq!(body["next_offset"], 2);
+        assert_eq!(body["has_more"], true);
+
+        let body: serde_json::Value = server.get("/api/tracks?limit=2&offset=2").await.json();
+        assert_eq!(body["items"].as_array().unwrap().len(), 1);
+        assert!(body["next_offset"].is_null());
+        assert_eq!(body["has_more"], false);
+    }
+
+    #[tokio::test]
+    async fn test_configured_page_sizes() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        for i in 1..=3 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/{i}.mp3")),
+                format!("Track {i}"),
+                "Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            db.add_track(&track).await.unwrap();
+        }
+        let mut config = apollo_core::Config::default();
+        config.web.default_page_size = 1;
+        config.web.max_page_size = 2;
+        let state = AppState::new(db).with_config(config);
+        let server = TestServer::new(create_router(Arc::new(state))).unwrap();
+
+        let body: serde_json::Value = server.get("/api/tracks").await.json();
+        assert_eq!(body["limit"], 1);
+        assert_eq!(body["items"].as_array().unwrap().len(), 1);
+        assert_eq!(body["next_offset"], 1);
+
+        let body: serde_json::Value = server.get("/api/tracks?limit=10").await.json();
+        assert_eq!(body["limit"], 2);
+        assert_eq!(body["items"].as_array().unwrap().len(), 2);
     }
 
     #[tokio::test]
@@ -330,6 +1169,234 @@ mod tests {
         response.assert_status_bad_request();
     }
 
+    #[tokio::test]
+    async fn test_record_play() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/track.mp3"),
+            "Track".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        let state = Arc::new(AppState::new(db));
+        let server = TestServer::new(create_router(Arc::clone(&state))).unwrap();
+
+        let response = server
+            .post(&format!("/api/tracks/{}/plays", track.id))
+            .await;
+        response.assert_status(axum::http::StatusCode::NO_CONTENT);
+        let response = server
+            .post(&format!("/api/tracks/{}/plays", track.id))
+            .json(&serde_json::json!({ "played_at": "2024-05-01T12:00:00Z" }))
+            .await;
+        response.assert_status(axum::http::StatusCode::NO_CONTENT);
+
+        let played = state.db.get_track_stats(&track.id).await.unwrap();
+        assert_eq!(played.play_count, 2);
+
+        let response = server
+            .post("/api/tracks/00000000-0000-0000-0000-000000000000/plays")
+            .await;
+        response.assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_download_album() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let album = Album::new("Abbey Road".to_string(), "The Beatles".to_string());
+        db.add_album(&album).await.unwrap();
+        for (i, file) in ["CD1/01.flac", "CD2/01.flac", "CD2/missing.flac"]
+            .into_iter()
+            .enumerate()
+        {
+            let path = dir.path().join(file);
+            if !file.contains("missing") {
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, b"audio").unwrap();
+            }
+            let mut track = Track::new(
+                path,
+                format!("Track {i}"),
+                "The Beatles".to_string(),
+                Duration::from_mins(3),
+            );
+            track.album_id = Some(album.id.clone());
+            db.add_track(&track).await.unwrap();
+        }
+        let empty = Album::new("Empty".to_string(), "Nobody".to_string());
+        db.add_album(&empty).await.unwrap();
+        let server = TestServer::new(create_router(Arc::new(AppState::new(db)))).unwrap();
+
+        let response = server
+            .get(&format!("/api/albums/{}/download", album.id))
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.header("content-type"), "application/zip");
+        assert_eq!(
+            response.header("content-disposition"),
+            "attachment; filename=\"The Beatles - Abbey Road.zip\""
+        );
+        let archive = response.as_bytes();
+        assert!(archive.starts_with(b"PK\x03\x04"));
+        let contains = |name: &str| {
+            archive
+                .windows(name.len())
+                .any(|window| window == name.as_bytes())
+        };
+        assert!(contains("The Beatles - Abbey Road/CD1/01.flac"));
+        assert!(contains("The Beatles - Abbey Road/CD2/01.flac"));
+        assert!(!contains("missing.flac"));
+
+        server
+            .get(&format!("/api/albums/{}/download", empty.id))
+            .await
+            .assert_status_not_found();
+        server
+            .get("/api/albums/not-an-id/download")
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_album_art() {
+        use apollo_core::metadata::Artwork;
+
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let album = Album::new("Abbey Road".to_string(), "The Beatles".to_string());
+        db.add_album(&album).await.unwrap();
+        let artwork = Artwork {
+            data: vec![0xFF, 0xD8, 0xFF, 0xE0],
+            mime_type: "image/jpeg".to_string(),
+            source: None,
+        };
+        db.set_album_artwork(&album.id, &artwork).await.unwrap();
+        let bare = Album::new("Bare".to_string(), "Nobody".to_string());
+        db.add_album(&bare).await.unwrap();
+        let server = TestServer::new(create_router(Arc::new(AppState::new(db)))).unwrap();
+
+        let path = format!("/api/albums/{}/art", album.id);
+        let response = server.get(&path).await;
+        response.assert_status_ok();
+        assert_eq!(response.header("content-type"), "image/jpeg");
+        assert_eq!(response.header("cache-control"), "public, max-age=86400");
+        assert_eq!(response.as_bytes().as_ref(), artwork.data.as_slice());
+        let etag = response.header("etag");
+        let last_modified = response.header("last-modified");
+
+        // Clients with a current copy get no image
+        let response = server
+            .get(&path)
+            .add_header("if-none-match", etag.clone())
+            .await;
+        response.assert_status(axum::http::StatusCode::NOT_MODIFIED);
+        assert!(response.as_bytes().is_empty());
+        assert_eq!(response.header("etag"), etag);
+        server
+            .get(&path)
+            .add_header("if-modified-since", last_modified)
+            .await
+            .assert_status(axum::http::StatusCode::NOT_MODIFIED);
+        server
+            .get(&path)
+            .add_header("if-none-match", "\"stale\"")
+            .await
+            .assert_status_ok();
+
+        server
+            .get(&format!("/api/albums/{}/art", bare.id))
+            .await
+            .assert_status_not_found();
+        server
+            .get("/api/albums/not-an-id/art")
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_static_files_are_revalidated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let router = create_router_with_static_files(Arc::new(AppState::new(db)), Some(dir.path()));
+        let server = TestServer::new(router).unwrap();
+
+        let response = server.get("/index.html").await;
+        response.assert_status_ok();
+        assert_eq!(response.header("cache-control"), "no-cache");
+        let last_modified = response.header("last-modified");
+        server
+            .get("/index.html")
+            .add_header("if-modified-since", last_modified)
+            .await
+            .assert_status(axum::http::StatusCode::NOT_MODIFIED);
+
+        // API responses are left alone
+        let response = server.get("/api/stats").await;
+        assert!(response.headers().get("cache-control").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_add_track_with_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("source.mp3");
+        std::fs::write(&source, b"audio").unwrap();
+        let mut config = apollo_core::Config::default();
+        config.paths.music_directory = Some(dir.path().join("music"));
+        let state =
+            Arc::new(AppState::new(SqliteLibrary::in_memory().await.unwrap()).with_config(config));
+        let server = TestServer::new(create_router(Arc::clone(&state))).unwrap();
+
+        let album = Album::new("Album".to_string(), "Artist".to_string());
+        let mut track = Track::new(
+            PathBuf::from("/elsewhere/track.mp3"),
+            "Track".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        track.album_id = Some(album.id.clone());
+        track.album_title = Some(album.title.clone());
+        track.track_number = Some(1);
+        track.file_hash = apollo_audio::compute_file_hash(&source).unwrap();
+
+        // The album has to come first
+        let response = server.post("/api/tracks").json(&track).await;
+        response.assert_status_bad_request();
+        let response = server.post("/api/albums").json(&album).await;
+        response.assert_status(axum::http::StatusCode::CREATED);
+        let response = server.post("/api/albums").json(&album).await;
+        response.assert_status(axum::http::StatusCode::CONFLICT);
+        let response = server.post("/api/tracks").json(&track).await;
+        response.assert_status(axum::http::StatusCode::CREATED);
+        let response = server.post("/api/tracks").json(&track).await;
+        response.assert_status(axum::http::StatusCode::CONFLICT);
+
+        let upload = format!("/api/tracks/{}/file", track.id);
+        let response = server
+            .put(&upload)
+            .bytes(axum::body::Bytes::from_static(b"other"))
+            .await;
+        response.assert_status_bad_request();
+        let response = server
+            .put(&upload)
+            .bytes(axum::body::Bytes::from_static(b"audio"))
+            .await;
+        response.assert_status_ok();
+        let placed: Track = response.json();
+        assert!(placed.path.starts_with(dir.path().join("music")));
+        assert_eq!(std::fs::read(&placed.path).unwrap(), b"audio");
+        let stored = state.db.get_track(&track.id).await.unwrap().unwrap();
+        assert_eq!(stored.path, placed.path);
+
+        // Tracks with a file keep it
+        let response = server
+            .put(&upload)
+            .bytes(axum::body::Bytes::from_static(b"audio"))
+            .await;
+        response.assert_status(axum::http::StatusCode::CONFLICT);
+    }
+
     #[tokio::test]
     async fn test_search_empty_query() {
         let server = create_test_server().await;
@@ -349,4 +1416,412 @@ mod tests {
         let items = body.as_array().unwrap();
         assert_eq!(items.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_search_tracks_sorted() {
+        let server = create_test_server_with_data().await;
+
+        let response = server.get("/api/search?q=Track%20sort:title-").await;
+        response.assert_status_ok();
+
+        let body: serde_json::Value = response.json();
+        let titles: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, vec!["Track 3", "Track 2", "Track 1"]);
+
+        let response = server.get("/api/search?q=Track%20sort:bogus").await;
+        response.assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_jobs_empty() {
+        let server = create_test_server().await;
+
+        let response = server.get("/api/jobs/scheduled").await;
+        response.assert_status_ok();
+
+        let body: serde_json::Value = response.json();
+        assert_eq!(body, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_job() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let state = Arc::new(AppState::new(db));
+        let server = TestServer::new(create_router(Arc::clone(&state))).unwrap();
+        server
+            .delete("/api/jobs/import-new-albums")
+            .await
+            .assert_status_not_found();
+
+        let job = state.running_jobs.start("import-new-albums").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        server
+            .post("/api/import")
+            .json(&serde_json::json!({"path": dir.path(), "job_id": "import-new-albums"}))
+            .await
+            .assert_status(axum::http::StatusCode::CONFLICT);
+        server
+            .delete("/api/jobs/import-new-albums")
+            .await
+            .assert_status(axum::http::StatusCode::NO_CONTENT);
+        assert!(job.cancel_flag().load(std::sync::atomic::Ordering::Relaxed));
+        drop(job);
+
+        let response = server
+            .post("/api/import")
+            .json(&serde_json::json!({"path": dir.path(), "job_id": "import-new-albums"}))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["cancelled"], false);
+        assert!(state.running_jobs.start("import-new-albums").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_stream_track() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("track.mp3");
+        std::fs::write(&path, b"0123456789").unwrap();
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut track = Track::new(
+            path,
+            "Track".to_string(),
+            "Artist".to_string(),
+            Duration::from_secs(1),
+        );
+        track.format = AudioFormat::Mp3;
+        db.add_track(&track).await.unwrap();
+        let missing = Track::new(
+            PathBuf::from("/music/missing.mp3"),
+            "Missing".to_string(),
+            "Artist".to_string(),
+            Duration::from_secs(1),
+        );
+        db.add_track(&missing).await.unwrap();
+        let server = TestServer::new(create_router(Arc::new(AppState::new(db)))).unwrap();
+
+        let response = server
+            .get(&format!("/api/tracks/{}/stream", track.id))
+            .await;
+        response.assert_status_ok();
+        assert_eq!(response.header("content-type"), "audio/mpeg");
+        assert_eq!(response.text(), "0123456789");
+
+        // Players seek with byte ranges
+        let response = server
+            .get(&format!("/api/tracks/{}/stream", track.id))
+            .add_header("range", "bytes=2-4")
+            .add_header(dlna::GET_CONTENT_FEATURES, "1")
+            .await;
+        response.assert_status(axum::http::StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.text(), "234");
+        assert_eq!(
+            response.header(dlna::CONTENT_FEATURES),
+            dlna::STREAM_FEATURES
+        );
+
+        let response = server
+            .get(&format!("/api/tracks/{}/stream", missing.id))
+            .await;
+        response.assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_dlna_routes() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let album = Album::new("Abbey Road".to_string(), "The Beatles".to_string());
+        db.add_album(&album).await.unwrap();
+        let mut config = apollo_core::Config::default();
+        config.dlna.name = "Living Room & Kitchen".to_string();
+
+        // The routes are only served with DLNA enabled
+        let state = Arc::new(AppState::new(db).with_config(config.clone()));
+        let server = TestServer::new(create_router(Arc::clone(&state))).unwrap();
+        server
+            .get("/dlna/description.xml")
+            .await
+            .assert_status_not_found();
+
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        db.add_album(&album).await.unwrap();
+        config.dlna.enabled = true;
+        let state = Arc::new(AppState::new(db).with_config(config.clone()));
+        let server = TestServer::new(create_router(state)).unwrap();
+
+        let response = server.get("/dlna/description.xml").await;
+        response.assert_status_ok();
+        let description = response.text();
+        assert!(description.contains("<friendlyName>Living Room &amp; Kitchen</friendlyName>"));
+        assert!(description.contains(&format!("<UDN>uuid:{}</UDN>", dlna::device_uuid(&config))));
+
+        let browse = r#"<?xml version="1.0"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1"><ObjectID>albums</ObjectID><BrowseFlag>BrowseDirectChildren</BrowseFlag><Filter>*</Filter><StartingIndex>0</StartingIndex><RequestedCount>10</RequestedCount><SortCriteria></SortCriteria></u:Browse></s:Body></s:Envelope>"#;
+        let response = server
+            .post("/dlna/control/ContentDirectory")
+            .add_header("host", "192.168.1.10:8337")
+            .add_header(
+                "soapaction",
+                "\"urn:schemas-upnp-org:service:ContentDirectory:1#Browse\"",
+            )
+            .text(browse)
+            .await;
+        response.assert_status_ok();
+        let body = response.text();
+        assert!(body.contains("<NumberReturned>1</NumberReturned>"));
+        assert!(body.contains("<TotalMatches>1</TotalMatches>"));
+        // The DIDL-Lite document is escaped inside the SOAP response
+        assert!(body.contains("&lt;dc:title&gt;Abbey Road&lt;/dc:title&gt;"));
+        assert!(body.contains(&format!("http://192.168.1.10:8337/dlna/art/{}", album.id)));
+
+        let response = server
+            .post("/dlna/control/ContentDirectory")
+            .add_header("host", "192.168.1.10:8337")
+            .add_header(
+                "soapaction",
+                "\"urn:schemas-upnp-org:service:ContentDirectory:1#Browse\"",
+            )
+            .text(browse.replace(">albums<", ">album/nothing<"))
+            .await;
+        response.assert_status(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.text().contains("<errorCode>701</errorCode>"));
+
+        server
+            .get(&format!("/dlna/art/{}", album.id))
+            .await
+            .assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_smart_playlist_tracks_follow_changes() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let playlist = Playlist::new_smart("Tests", ApolloQuery::parse("artist:Test").unwrap());
+        db.add_playlist(&playlist).await.unwrap();
+        let state = Arc::new(AppState::new(db));
+        let server = TestServer::new(create_router(state.clone())).unwrap();
+        let path = format!("/api/playlists/{}/tracks", playlist.id);
+
+        let response = server.get(&path).await;
+        assert_eq!(response.json::<serde_json::Value>()["total"], 0);
+
+        let track = Track::new(
+            PathBuf::from("/music/new.mp3"),
+            "New Track".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        state.db.add_track(&track).await.unwrap();
+
+        let response = server.get(&path).await;
+        assert_eq!(response.json::<serde_json::Value>()["total"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_from_query_and_tracks() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let state = Arc::new(AppState::new(db));
+        let mut ids = Vec::new();
+        for i in 1..=3 {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/track{i}.mp3")),
+                format!("Track {i}"),
+                "Test Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            track.year = Some(2000 + i);
+            state.db.add_track(&track).await.unwrap();
+            ids.push(track.id.to_string());
+        }
+        let server = TestServer::new(create_router(Arc::clone(&state))).unwrap();
+
+        let response = server
+            .post("/api/playlists/from-query")
+            .json(&serde_json::json!({
+                "name": "Newest",
+                "query": "artist:Test sort:year-",
+                "max_tracks": 2
+            }))
+            .await;
+        response.assert_status(axum::http::StatusCode::CREATED);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["track_count"], 2);
+        assert_eq!(body["kind"], "static");
+        let id = PlaylistId(uuid::Uuid::parse_str(body["id"].as_str().unwrap()).unwrap());
+        let playlist = state.db.get_playlist(&id).await.unwrap().unwrap();
+        assert_eq!(playlist.track_ids[0].to_string(), ids[2]);
+
+        // Later tracks are not added, as they would be to a smart playlist
+        let track = Track::new(
+            PathBuf::from("/music/track4.mp3"),
+            "Track 4".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        state.db.add_track(&track).await.unwrap();
+        let tracks = state.db.get_playlist_tracks(&id).await.unwrap();
+        assert_eq!(tracks.len(), 2);
+
+        let response = server
+            .post("/api/playlists/from-tracks")
+            .json(&serde_json::json!({
+                "name": "Picked",
+                "track_ids": [ids[1], ids[0]]
+            }))
+            .await;
+        response.assert_status(axum::http::StatusCode::CREATED);
+        assert_eq!(response.json::<serde_json::Value>()["track_count"], 2);
+
+        let response = server
+            .post("/api/playlists/from-tracks")
+            .json(&serde_json::json!({
+                "name": "Broken",
+                "track_ids": [ids[0], "00000000-0000-0000-0000-000000000000"]
+            }))
+            .await;
+        response.assert_status_bad_request();
+        assert_eq!(state.db.list_playlists().await.unwrap().len(), 2);
+
+        server
+            .post("/api/playlists/from-tracks")
+            .json(&serde_json::json!({
+                "name": "Twice",
+                "track_ids": [ids[0], ids[1], ids[0]]
+            }))
+            .await
+            .assert_status_bad_request();
+        assert_eq!(state.db.list_playlists().await.unwrap().len(), 2);
+
+        // A track is only added once, and a rejected request adds nothing
+        server
+            .post(&format!("/api/playlists/{id}/tracks"))
+            .json(&serde_json::json!({ "track_ids": [track.id.to_string(), ids[2]] }))
+            .await
+            .assert_status(axum::http::StatusCode::CONFLICT);
+        let tracks = state.db.get_playlist_tracks(&id).await.unwrap();
+        assert_eq!(tracks.len(), 2);
+
+        server
+            .post("/api/playlists/from-query")
+            .json(&serde_json::json!({ "name": "Bad", "query": "sort:nosuchfield" }))
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_playlist_tracks_pagination() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut mix = Playlist::new_static("Mix");
+        for i in 1..=5 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/track{i}.mp3")),
+                format!("Track {i}"),
+                "Test Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            db.add_track(&track).await.unwrap();
+            mix.track_ids.insert(0, track.id.clone());
+        }
+        db.add_playlist(&mix).await.unwrap();
+        let mut smart = Playlist::new_smart("Tests", ApolloQuery::parse("artist:Test").unwrap());
+        smart.sort = PlaylistSort::Title;
+        smart.limit = Some(PlaylistLimit {
+            max_tracks: Some(4),
+            max_duration_secs: None,
+        });
+        db.add_playlist(&smart).await.unwrap();
+        let server = TestServer::new(create_router(Arc::new(AppState::new(db)))).unwrap();
+
+        // Static playlists keep their order
+        let response = server
+            .get(&format!(
+                "/api/playlists/{}/tracks?limit=2&offset=1",
+                mix.id
+            ))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["total"], 5);
+        assert_eq!(body["limit"], 2);
+        assert_eq!(body["offset"], 1);
+        assert_eq!(body["items"][0]["title"], "Track 4");
+        assert_eq!(body["items"][1]["title"], "Track 3");
+
+        // Pages of smart playlists stop at the playlist's limit
+        let path = format!("/api/playlists/{}/tracks", smart.id);
+        let body: serde_json::Value = server.get(&format!("{path}?limit=3&offset=2")).await.json();
+        assert_eq!(body["total"], 4);
+        let titles: Vec<_> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["title"].clone())
+            .collect();
+        assert_eq!(titles, vec!["Track 3", "Track 4"]);
+        let body: serde_json::Value = server.get(&format!("{path}?offset=10")).await.json();
+        assert_eq!(body["items"].as_array().unwrap().len(), 0);
+
+        let body: serde_json::Value = server
+            .get(&format!("/api/playlists/{}", smart.id))
+            .await
+            .json();
+        assert_eq!(body["track_count"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_playlist_versions() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut mix = Playlist::new_static("Mix");
+        for i in 1..=2 {
+            let track = Track::new(
+                PathBuf::from(format!("/music/track{i}.mp3")),
+                format!("Track {i}"),
+                "Test Artist".to_string(),
+                Duration::from_mins(3),
+            );
+            db.add_track(&track).await.unwrap();
+            mix.track_ids.push(track.id.clone());
+        }
+        db.add_playlist(&mix).await.unwrap();
+        let smart = Playlist::new_smart("Tests", ApolloQuery::All);
+        db.add_playlist(&smart).await.unwrap();
+        let server = TestServer::new(create_router(Arc::new(AppState::new(db)))).unwrap();
+
+        // Removing the tracks one at a time keeps a version for each
+        let track_ids: Vec<String> = mix.track_ids.iter().map(ToString::to_string).collect();
+        server
+            .delete(&format!("/api/playlists/{}/tracks", mix.id))
+            .json(&serde_json::json!({ "track_ids": track_ids }))
+            .await
+            .assert_status_ok();
+
+        let path = format!("/api/playlists/{}/versions", mix.id);
+        let versions: Vec<PlaylistVersion> = server.get(&path).await.json();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[1].track_ids, mix.track_ids);
+
+        let response = server
+            .post(&format!("{path}/{}/restore", versions[1].id))
+            .await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["track_count"], 2);
+        let versions: Vec<PlaylistVersion> = server.get(&path).await.json();
+        assert_eq!(versions.len(), 2);
+
+        server
+            .post(&format!("{path}/1000/restore"))
+            .await
+            .assert_status_not_found();
+        server
+            .post(&format!("/api/playlists/{}/versions/1/restore", smart.id))
+            .await
+            .assert_status_bad_request();
+        server
+            .get(&format!("/api/playlists/{}/versions", PlaylistId::new()))
+            .await
+            .assert_status_not_found();
+    }
 }
@@ -0,0 +1,627 @@
+//! MPD protocol listener, for the clients of the Music Player Daemon.
+//!
+//! With `mpd.enabled` set, an [`MpdServer`] accepts MPD clients alongside
+//! the web server. Clients browse and search the library, and manage the
+//! server's [play queue](crate::queue): the commands they send are mapped
+//! onto the library and the queue, and songs are named by their path within
+//! the music directory, as MPD names them.
+//!
+//! Each connection gets one reply per command: the lines of the reply
+//! followed by `OK`, or an `ACK` line saying what went wrong. Clients that
+//! wait with `idle` are told which parts of the server changed.
+
+mod commands;
+mod filter;
+
+use crate::error::ApiError;
+use crate::queue::QueueChange;
+use crate::state::AppState;
+use apollo_core::event::{EventReceiver, LibraryEvent};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicU32;
+use std::time::Instant;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+/// First line sent to clients, with the protocol version that is spoken.
+const GREETING: &str = "OK MPD 0.23.0\n";
+
+/// Longest command line that is read, in bytes, as MPD's own input buffer
+/// limits them. Clients that send longer lines are disconnected.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// Subsystems that clients can wait for changes of with `idle`.
+const SUBSYSTEMS: [&str; 14] = [
+    "database",
+    "update",
+    "stored_playlist",
+    "playlist",
+    "player",
+    "mixer",
+    "output",
+    "options",
+    "partition",
+    "sticker",
+    "subscription",
+    "message",
+    "neighbor",
+    "mount",
+];
+
+/// An error answered to a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ack {
+    code: u8,
+    message: String,
+}
+
+impl Ack {
+    /// Invalid or missing arguments.
+    fn arg(message: impl Into<String>) -> Self {
+        Self {
+            code: 2,
+            message: message.into(),
+        }
+    }
+
+    /// A command that is not known.
+    fn unknown(command: &str) -> Self {
+        Self {
+            code: 5,
+            message: format!("unknown command \"{command}\""),
+        }
+    }
+
+    /// A command that is not allowed on this server.
+    fn permission(message: impl Into<String>) -> Self {
+        Self {
+            code: 4,
+            message: message.into(),
+        }
+    }
+
+    /// A song, directory, or playlist that does not exist.
+    fn no_exist(message: impl Into<String>) -> Self {
+        Self {
+            code: 50,
+            message: message.into(),
+        }
+    }
+
+    /// A playlist or file that exists already.
+    fn exist(message: impl Into<String>) -> Self {
+        Self {
+            code: 56,
+            message: message.into(),
+        }
+    }
+
+    /// Write the `ACK` line for the command at `index` of a command list.
+    fn line(&self, index: usize, command: &str) -> String {
+        format!(
+            "ACK [{}@{index}] {{{command}}} {}\n",
+            self.code, self.message
+        )
+    }
+}
+
+impl From<ApiError> for Ack {
+    fn from(error: ApiError) -> Self {
+        match error {
+            ApiError::NotFound(message) => Self::no_exist(message),
+            ApiError::BadRequest(message) => Self::arg(message),
+            ApiError::Conflict(message) => Self::exist(message),
+            error => {
+                warn!("MPD command failed: {error:?}");
+                Self {
+                    code: 52,
+                    message: "Internal error".to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl From<apollo_core::Error> for Ack {
+    fn from(error: apollo_core::Error) -> Self {
+        ApiError::from(error).into()
+    }
+}
+
+/// What commands of every connection share.
+struct Context {
+    state: Arc<AppState>,
+    /// Directory that song URIs are relative to.
+    music_dir: Option<PathBuf>,
+    started: Instant,
+    /// Number of database updates started by clients.
+    updates: AtomicU32,
+}
+
+impl Context {
+    /// Get the URI of a song: its path within the music directory, or its
+    /// full path if it is elsewhere.
+    fn uri(&self, path: &Path) -> String {
+        self.music_dir
+            .as_deref()
+            .and_then(|dir| path.strip_prefix(dir).ok())
+            .map_or_else(
+                || path.to_string_lossy().into_owned(),
+                |relative| relative.to_string_lossy().replace('\\', "/"),
+            )
+    }
+}
+
+/// Accepts MPD clients.
+pub struct MpdServer {
+    context: Arc<Context>,
+}
+
+impl MpdServer {
+    /// Create a server for the library and queue in `state`.
+    #[must_use]
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self {
+            context: Arc::new(Context {
+                music_dir: state.config.music_directory(),
+                state,
+                started: Instant::now(),
+                updates: AtomicU32::new(0),
+            }),
+        }
+    }
+
+    /// Serve the clients that connect to `listener` until the task is
+    /// cancelled, which also closes their connections.
+    pub async fn serve(self, listener: TcpListener) {
+        let mut connections = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        debug!("MPD client connected from {peer}");
+                        let context = Arc::clone(&self.context);
+                        connections.spawn(async move {
+                            if let Err(e) = serve_client(&context, stream).await {
+                                debug!("MPD client {peer} disconnected: {e}");
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Failed to accept MPD client: {e}"),
+                },
+                // Finished connections are dropped from the set
+                Some(_) = connections.join_next() => {}
+            }
+        }
+    }
+}
+
+/// Answer the commands of one client until it disconnects.
+async fn serve_client(context: &Context, stream: TcpStream) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut session = Session::new(&context.state);
+    // Commands between `command_list_begin` and `command_list_end`, and
+    // whether each of them is acknowledged with `list_OK`
+    let mut list: Option<(bool, Vec<Vec<String>>)> = None;
+    writer.write_all(GREETING.as_bytes()).await?;
+
+    while let Some(line) = next_line(&mut reader, &mut writer).await? {
+        let words = match tokenize(&line) {
+            Ok(words) if !words.is_empty() => words,
+            Ok(_) => {
+                list = None;
+                writer
+                    .write_all(Ack::unknown("").line(0, "").as_bytes())
+                    .await?;
+                continue;
+            }
+            Err(ack) => {
+                list = None;
+                writer.write_all(ack.line(0, "").as_bytes()).await?;
+                continue;
+            }
+        };
+
+        if let Some((_, commands)) = list.as_mut() {
+            if words[0] != "command_list_end" {
+                commands.push(words);
+                continue;
+            }
+            let (ok, commands) = list.take().unwrap_or_default();
+            let reply = run_list(context, ok, &commands).await;
+            writer.write_all(reply.as_bytes()).await?;
+            continue;
+        }
+
+        let reply = match words[0].as_str() {
+            "close" => return Ok(()),
+            "command_list_begin" | "command_list_ok_begin" => {
+                list = Some((words[0] == "command_list_ok_begin", Vec::new()));
+                continue;
+            }
+            "idle" => {
+                let wanted = match idle_subsystems(&words[1..]) {
+                    Ok(wanted) => wanted,
+                    Err(ack) => {
+                        writer.write_all(ack.line(0, "idle").as_bytes()).await?;
+                        continue;
+                    }
+                };
+                let changed = tokio::select! {
+                    // Changes that are already known are told before `noidle`
+                    biased;
+                    changed = session.wait(&wanted) => changed,
+                    line = next_line(&mut reader, &mut writer) => match line? {
+                        // Only `noidle` may be sent while waiting
+                        Some(line) if line.trim() == "noidle" => Vec::new(),
+                        _ => return Ok(()),
+                    },
+                };
+                let mut reply = String::new();
+                for subsystem in changed {
+                    let _ = writeln!(reply, "changed: {subsystem}");
+                }
+                reply + "OK\n"
+            }
+            // Not waiting, so there is nothing to stop or answer, as a
+            // change can end `idle` before its `noidle` arrives
+            "noidle" => continue,
+            _ => match commands::execute(context, &words[0], &words[1..]).await {
+                Ok(out) => out + "OK\n",
+                Err(ack) => ack.line(0, &words[0]),
+            },
+        };
+        writer.write_all(reply.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Read the next line of a client without its line ending, or `None` once
+/// the client disconnects.
+///
+/// A line over [`MAX_LINE_LENGTH`] bytes is answered with an `ACK` and
+/// fails with [`InvalidData`](std::io::ErrorKind::InvalidData), as does a
+/// line that is not UTF-8.
+async fn next_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    // Room for the line ending tells a line that is too long from one that
+    // just fits
+    let read = reader
+        .take(MAX_LINE_LENGTH as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+    } else if line.len() > MAX_LINE_LENGTH {
+        writer
+            .write_all(Ack::arg("Line too long").line(0, "").as_bytes())
+            .await?;
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "line too long",
+        ));
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Run a command list, stopping at the first command that fails.
+async fn run_list(context: &Context, ok: bool, commands: &[Vec<String>]) -> String {
+    let mut reply = String::new();
+    for (index, words) in commands.iter().enumerate() {
+        match commands::execute(context, &words[0], &words[1..]).await {
+            Ok(out) => {
+                reply.push_str(&out);
+                if ok {
+                    reply.push_str("list_OK\n");
+                }
+            }
+            Err(ack) => {
+                reply.push_str(&ack.line(index, &words[0]));
+                return reply;
+            }
+        }
+    }
+    reply + "OK\n"
+}
+
+/// Split a command line into words, which may be quoted.
+fn tokenize(line: &str) -> Result<Vec<String>, Ack> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return Ok(words);
+        };
+        let mut word = String::new();
+        if first == '"' {
+            loop {
+                match chars.next() {
+                    Some('\\') => word.extend(chars.next()),
+                    Some('"') => break,
+                    Some(c) => word.push(c),
+                    None => return Err(Ack::arg("Missing closing '\"'")),
+                }
+            }
+        } else {
+            word.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+}
+
+/// Get the subsystems that `idle` waits for, which are all of them
+/// without arguments.
+fn idle_subsystems(args: &[String]) -> Result<Vec<&'static str>, Ack> {
+    args.iter()
+        .map(|name| {
+            SUBSYSTEMS
+                .iter()
+                .find(|subsystem| subsystem.eq_ignore_ascii_case(name))
+                .copied()
+                .ok_or_else(|| Ack::arg(format!("Unrecognized idle event: {name}")))
+        })
+        .collect()
+}
+
+/// Changes that a client has not been told about yet.
+struct Session {
+    events: EventReceiver,
+    queue: broadcast::Receiver<QueueChange>,
+    /// Subsystems that changed since the client last waited for them.
+    changed: BTreeSet<&'static str>,
+}
+
+impl Session {
+    fn new(state: &AppState) -> Self {
+        Self {
+            events: state.db.events().subscribe(),
+            queue: state.queue.subscribe(),
+            changed: BTreeSet::new(),
+        }
+    }
+
+    /// Wait until one of the `wanted` subsystems, or any without them,
+    /// changes, and take the changes to them.
+    async fn wait(&mut self, wanted: &[&'static str]) -> Vec<&'static str> {
+        loop {
+            self.collect();
+            let changed: Vec<&'static str> = self
+                .changed
+                .iter()
+                .copied()
+                .filter(|subsystem| wanted.is_empty() || wanted.contains(subsystem))
+                .collect();
+            if !changed.is_empty() {
+                for subsystem in &changed {
+                    self.changed.remove(subsystem);
+                }
+                return changed;
+            }
+            tokio::select! {
+                event = next(&mut self.events) => self.changed.extend(event_subsystems(event.as_ref())),
+                change = next(&mut self.queue) => self.changed.extend(queue_subsystems(change)),
+            }
+        }
+    }
+
+    /// Note the changes received so far.
+    fn collect(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.changed.extend(event_subsystems(Some(&event))),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {
+                    self.changed.extend(event_subsystems(None));
+                }
+                Err(_) => break,
+            }
+        }
+        loop {
+            match self.queue.try_recv() {
+                Ok(change) => self.changed.extend(queue_subsystems(Some(change))),
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {
+                    self.changed.extend(queue_subsystems(None));
+                }
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Receive the next message, or `None` if some were missed.
+async fn next<T: Clone>(receiver: &mut broadcast::Receiver<T>) -> Option<T> {
+    match receiver.recv().await {
+        Ok(message) => Some(message),
+        Err(broadcast::error::RecvError::Lagged(_)) => None,
+        // The server is shutting down, so nothing changes anymore
+        Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+    }
+}
+
+/// Get the subsystems that a library event changed, or that missed events
+/// could have changed.
+fn event_subsystems(event: Option<&LibraryEvent>) -> Vec<&'static str> {
+    match event {
+        Some(event) if event.playlist_id().is_some() => vec!["stored_playlist"],
+        Some(_) => vec!["database"],
+        None => vec!["database", "stored_playlist"],
+    }
+}
+
+/// Get the subsystems that a queue change changed, or that missed changes
+/// could have changed.
+fn queue_subsystems(change: Option<QueueChange>) -> Vec<&'static str> {
+    match change {
+        Some(QueueChange::Tracks) => vec!["playlist"],
+        Some(QueueChange::Playback) => vec!["player"],
+        None => vec!["playlist", "player"],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::metadata::Track;
+    use apollo_db::SqliteLibrary;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize(r#"find artist "Simon \"&\" Garfunkel"  album Bookends"#).unwrap(),
+            vec![
+                "find",
+                "artist",
+                r#"Simon "&" Garfunkel"#,
+                "album",
+                "Bookends"
+            ]
+        );
+        assert_eq!(tokenize("  status ").unwrap(), vec!["status"]);
+        assert!(tokenize("").unwrap().is_empty());
+        assert!(tokenize(r#"find "artist"#).is_err());
+    }
+
+    #[test]
+    fn test_ack_line() {
+        assert_eq!(
+            Ack::unknown("shout").line(1, "shout"),
+            "ACK [5@1] {shout} unknown command \"shout\"\n"
+        );
+        assert_eq!(
+            Ack::from(ApiError::NotFound("No such song".to_string())).code,
+            50
+        );
+    }
+
+    /// Send lines to a server, and read the reply up to the last line that
+    /// starts with `last`.
+    async fn exchange(
+        writer: &mut tokio::net::tcp::OwnedWriteHalf,
+        reader: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+        lines: &str,
+        last: &str,
+    ) -> Vec<String> {
+        writer.write_all(lines.as_bytes()).await.unwrap();
+        let mut reply = Vec::new();
+        while let Some(line) = reader.next_line().await.unwrap() {
+            let done = line.starts_with(last);
+            reply.push(line);
+            if done {
+                break;
+            }
+        }
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_client_session() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut config = apollo_core::Config::default();
+        config.paths.music_directory = Some(PathBuf::from("/music"));
+        for (artist, title) in [("Nina Simone", "Sinnerman"), ("Miles Davis", "So What")] {
+            let track = Track::new(
+                PathBuf::from(format!("/music/{artist}/{title}.flac")),
+                title.to_string(),
+                artist.to_string(),
+                Duration::from_mins(10),
+            );
+            db.add_track(&track).await.unwrap();
+        }
+        let state = Arc::new(AppState::new(db).with_config(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(MpdServer::new(Arc::clone(&state)).serve(listener));
+
+        let (reader, mut writer) = TcpStream::connect(address).await.unwrap().into_split();
+        let mut reader = BufReader::new(reader).lines();
+        assert_eq!(reader.next_line().await.unwrap().unwrap(), GREETING.trim());
+
+        let reply = exchange(&mut writer, &mut reader, "list artist\n", "OK").await;
+        assert_eq!(
+            reply,
+            vec!["Artist: Miles Davis", "Artist: Nina Simone", "OK"]
+        );
+
+        let reply = exchange(
+            &mut writer,
+            &mut reader,
+            "command_list_ok_begin\nadd \"Nina Simone\"\nplay\ncommand_list_end\n",
+            "OK",
+        )
+        .await;
+        assert_eq!(reply, vec!["list_OK", "list_OK", "OK"]);
+
+        let reply = exchange(&mut writer, &mut reader, "playlistinfo\n", "OK").await;
+        assert!(reply.contains(&"file: Nina Simone/Sinnerman.flac".to_string()));
+        assert!(reply.contains(&"Pos: 0".to_string()));
+
+        // Changes since the client last waited are told right away
+        let reply = exchange(&mut writer, &mut reader, "idle\n", "OK").await;
+        assert_eq!(reply, vec!["changed: player", "changed: playlist", "OK"]);
+
+        // Waiting clients hear about changes as they happen
+        writer.write_all(b"idle playlist\n").await.unwrap();
+        state.queue.clear();
+        let reply = exchange(&mut writer, &mut reader, "", "OK").await;
+        assert_eq!(reply, vec!["changed: playlist", "OK"]);
+        let reply = exchange(&mut writer, &mut reader, "idle\nnoidle\n", "OK").await;
+        assert_eq!(reply, vec!["changed: player", "OK"]);
+        let reply = exchange(&mut writer, &mut reader, "idle\nnoidle\n", "OK").await;
+        assert_eq!(reply, vec!["OK"]);
+
+        let reply = exchange(&mut writer, &mut reader, "shout\n", "ACK").await;
+        assert_eq!(reply, vec!["ACK [5@0] {shout} unknown command \"shout\""]);
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_long_lines_disconnect() {
+        let state = Arc::new(AppState::new(SqliteLibrary::in_memory().await.unwrap()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(MpdServer::new(state).serve(listener));
+
+        let (reader, mut writer) = TcpStream::connect(address).await.unwrap().into_split();
+        let mut reader = BufReader::new(reader).lines();
+        assert_eq!(reader.next_line().await.unwrap().unwrap(), GREETING.trim());
+
+        // Lines up to the limit are read
+        let line = format!("find artist \"{}\"\n", "a".repeat(MAX_LINE_LENGTH - 14));
+        assert_eq!(line.len(), MAX_LINE_LENGTH + 1);
+        let reply = exchange(&mut writer, &mut reader, &line, "OK").await;
+        assert_eq!(reply, vec!["OK"]);
+
+        let line = format!("find artist \"{}\"\n", "a".repeat(MAX_LINE_LENGTH));
+        let reply = exchange(&mut writer, &mut reader, &line, "ACK").await;
+        assert_eq!(reply, vec!["ACK [2@0] {} Line too long"]);
+        assert!(reader.next_line().await.unwrap().is_none());
+
+        server.abort();
+    }
+}
@@ -0,0 +1,867 @@
+//! The commands that MPD clients send.
+//!
+//! Database commands read the whole library and filter it, as MPD itself
+//! does with its song database. Songs and directories are named by their
+//! URIs, so directories are the folders within the music directory that
+//! hold songs. Queue and playback commands change the server's
+//! [play queue](crate::queue::PlayQueue).
+
+use super::filter::{Filter, Tag};
+use super::{Ack, Context};
+use crate::handlers::playlist_tracks;
+use crate::queue::{PlayState, QueueEntry};
+use crate::schedule::{JobScheduler, ScheduledJob};
+use apollo_core::metadata::Track;
+use apollo_core::playlist::Playlist;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tracing::{info, warn};
+
+/// Commands that clients may send, for `commands`.
+const COMMANDS: [&str; 45] = [
+    "add",
+    "addid",
+    "clear",
+    "close",
+    "command_list_begin",
+    "command_list_end",
+    "command_list_ok_begin",
+    "commands",
+    "count",
+    "currentsong",
+    "decoders",
+    "delete",
+    "deleteid",
+    "find",
+    "findadd",
+    "idle",
+    "list",
+    "listall",
+    "listallinfo",
+    "listplaylist",
+    "listplaylistinfo",
+    "listplaylists",
+    "load",
+    "lsinfo",
+    "move",
+    "next",
+    "noidle",
+    "notcommands",
+    "outputs",
+    "pause",
+    "ping",
+    "play",
+    "playid",
+    "playlistid",
+    "playlistinfo",
+    "plchanges",
+    "plchangesposid",
+    "previous",
+    "replay_gain_status",
+    "rescan",
+    "search",
+    "searchadd",
+    "stats",
+    "status",
+    "stop",
+];
+
+/// A song of the library, with its URI.
+type Song = (String, Track);
+
+/// Run a command, and get the lines of its reply.
+pub(super) async fn execute(
+    context: &Context,
+    command: &str,
+    args: &[String],
+) -> Result<String, Ack> {
+    let queue = &context.state.queue;
+    let mut out = String::new();
+    match command {
+        "ping" | "notcommands" | "outputs" | "decoders" | "urlhandlers" => {}
+        "commands" => {
+            for command in COMMANDS {
+                let _ = writeln!(out, "command: {command}");
+            }
+        }
+        "tagtypes" => tag_types(args, &mut out)?,
+        "replay_gain_status" => out.push_str("replay_gain_mode: off\n"),
+        "status" => status(context, &mut out),
+        "stats" => stats(context, &mut out).await?,
+        "update" | "rescan" => update(context, &mut out)?,
+
+        "currentsong" => {
+            if let Some((position, entry)) = queue.current() {
+                write_entry(context, &mut out, position, &entry);
+            }
+        }
+        "play" => queue.play(optional_position(args)?)?,
+        "playid" => {
+            let position = match optional_position(args)? {
+                Some(id) => Some(queue.position_of(number(&id.to_string())?)?),
+                None => None,
+            };
+            queue.play(position)?;
+        }
+        "pause" => queue.pause(match args.first() {
+            Some(paused) => Some(number::<u8>(paused)? == 1),
+            None => None,
+        }),
+        "stop" => queue.stop(),
+        "next" => queue.next(),
+        "previous" => queue.previous(),
+
+        "add" | "addid" => add(context, command == "addid", args, &mut out).await?,
+        "clear" => queue.clear(),
+        "delete" => queue.remove(range(arg(args, 0)?, queue.status().length)?)?,
+        "deleteid" => queue.remove_id(number(arg(args, 0)?)?)?,
+        "move" => queue.move_track(number(arg(args, 0)?)?, number(arg(args, 1)?)?)?,
+        "playlistinfo" => playlist_info(context, args, &mut out)?,
+        "playlistid" => playlist_id(context, args, &mut out)?,
+        "plchanges" | "plchangesposid" => {
+            changes(context, args, command == "plchangesposid", &mut out)?;
+        }
+
+        "find" | "search" => {
+            for (uri, track) in find(context, args, command == "search").await? {
+                write_song(&mut out, &uri, &track);
+            }
+        }
+        "findadd" | "searchadd" => {
+            let songs = find(context, args, command == "searchadd").await?;
+            queue.add(songs.into_iter().map(|(_, track)| track).collect(), None)?;
+        }
+        "count" => count(context, args, &mut out).await?,
+        "list" => list(context, args, &mut out).await?,
+        "lsinfo" => ls_info(context, args, &mut out).await?,
+        "listall" | "listallinfo" => {
+            list_all(context, args, command == "listallinfo", &mut out).await?;
+        }
+
+        "listplaylists" => {
+            for playlist in context.state.db.list_playlists().await? {
+                let _ = writeln!(out, "playlist: {}", playlist.name);
+                let _ = writeln!(out, "Last-Modified: {}", timestamp(&playlist.modified_at));
+            }
+        }
+        "listplaylist" | "listplaylistinfo" => {
+            let playlist = playlist_named(context, arg(args, 0)?).await?;
+            for track in playlist_tracks(&context.state, &playlist).await? {
+                let uri = context.uri(&track.path);
+                if command == "listplaylist" {
+                    let _ = writeln!(out, "file: {uri}");
+                } else {
+                    write_song(&mut out, &uri, &track);
+                }
+            }
+        }
+        "load" => {
+            let playlist = playlist_named(context, arg(args, 0)?).await?;
+            let tracks = playlist_tracks(&context.state, &playlist).await?;
+            let range = match args.get(1) {
+                Some(text) => range(text, tracks.len())?,
+                None => 0..tracks.len(),
+            };
+            let tracks = tracks
+                .get(range)
+                .ok_or_else(|| Ack::arg("Bad song index"))?;
+            queue.add(tracks.to_vec(), None)?;
+        }
+        _ => return Err(Ack::unknown(command)),
+    }
+    Ok(out)
+}
+
+/// Get an argument.
+fn arg(args: &[String], index: usize) -> Result<&str, Ack> {
+    args.get(index)
+        .map(String::as_str)
+        .ok_or_else(|| Ack::arg("Missing argument"))
+}
+
+/// Parse a number.
+fn number<T: FromStr>(text: &str) -> Result<T, Ack> {
+    text.trim()
+        .parse()
+        .map_err(|_| Ack::arg(format!("Integer expected: {text}")))
+}
+
+/// Parse the optional position of `play`, where -1 means none.
+fn optional_position(args: &[String]) -> Result<Option<usize>, Ack> {
+    match args.first() {
+        Some(text) if text.trim() == "-1" => Ok(None),
+        Some(text) => number(text).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Parse a position, or a range such as `2:5` or `2:`, in a queue or list
+/// of `length` songs.
+fn range(text: &str, length: usize) -> Result<Range<usize>, Ack> {
+    match text.split_once(':') {
+        Some((start, "")) => Ok(number(start)?..length),
+        Some((start, end)) => Ok(number(start)?..number(end)?),
+        None => {
+            let position: usize = number(text)?;
+            Ok(position..position + 1)
+        }
+    }
+}
+
+/// Format a time as MPD does.
+fn timestamp(time: &chrono::DateTime<chrono::Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Write the description of a song.
+fn write_song(out: &mut String, uri: &str, track: &Track) {
+    let _ = writeln!(out, "file: {uri}");
+    let _ = writeln!(out, "Last-Modified: {}", timestamp(&track.modified_at));
+    for tag in Tag::TYPES {
+        for value in tag.values(track, uri) {
+            // A line break would end the value early
+            let _ = writeln!(out, "{}: {}", tag.name(), value.replace('\n', " "));
+        }
+    }
+    let duration = track.duration.as_secs_f64();
+    let _ = writeln!(out, "Time: {}", track.duration.as_secs());
+    let _ = writeln!(out, "duration: {duration:.3}");
+}
+
+/// Write the description of a song in the queue.
+fn write_entry(context: &Context, out: &mut String, position: usize, entry: &QueueEntry) {
+    write_song(out, &context.uri(&entry.track.path), &entry.track);
+    let _ = writeln!(out, "Pos: {position}");
+    let _ = writeln!(out, "Id: {}", entry.id);
+}
+
+/// List the songs of the library, by URI.
+async fn library(context: &Context) -> Result<Vec<Song>, Ack> {
+    let mut songs: Vec<Song> = context
+        .state
+        .db
+        .list_tracks(u32::MAX, 0)
+        .await?
+        .into_iter()
+        .map(|track| (context.uri(&track.path), track))
+        .collect();
+    songs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(songs)
+}
+
+/// Find the playlist with a name.
+async fn playlist_named(context: &Context, name: &str) -> Result<Playlist, Ack> {
+    context
+        .state
+        .db
+        .list_playlists()
+        .await?
+        .into_iter()
+        .find(|playlist| playlist.name == name)
+        .ok_or_else(|| Ack::no_exist("No such playlist"))
+}
+
+fn tag_types(args: &[String], out: &mut String) -> Result<(), Ack> {
+    match args.first().map(String::as_str) {
+        None => {
+            for tag in Tag::TYPES {
+                let _ = writeln!(out, "tagtype: {}", tag.name());
+            }
+            Ok(())
+        }
+        // Every tag is always sent, which clients cope with
+        Some("all" | "clear" | "enable" | "disable" | "reset") => Ok(()),
+        Some(other) => Err(Ack::arg(format!("Unknown sub command: {other}"))),
+    }
+}
+
+fn status(context: &Context, out: &mut String) {
+    let status = context.state.queue.status();
+    let state = match status.state {
+        PlayState::Stopped => "stop",
+        PlayState::Playing => "play",
+        PlayState::Paused => "pause",
+    };
+    for option in ["repeat", "random", "single", "consume"] {
+        let _ = writeln!(out, "{option}: 0");
+    }
+    let _ = writeln!(out, "playlist: {}", status.version);
+    let _ = writeln!(out, "playlistlength: {}", status.length);
+    let _ = writeln!(out, "state: {state}");
+    if let Some((position, id)) = status.current {
+        let _ = writeln!(out, "song: {position}");
+        let _ = writeln!(out, "songid: {id}");
+    }
+    if let Some((_, entry)) = context.state.queue.current() {
+        let _ = writeln!(out, "duration: {:.3}", entry.track.duration.as_secs_f64());
+    }
+}
+
+async fn stats(context: &Context, out: &mut String) -> Result<(), Ack> {
+    let songs = library(context).await?;
+    let artists: HashSet<&str> = songs
+        .iter()
+        .map(|(_, track)| track.artist.as_str())
+        .collect();
+    let playtime: u64 = songs
+        .iter()
+        .map(|(_, track)| track.duration.as_secs())
+        .sum();
+    let updated = songs
+        .iter()
+        .map(|(_, track)| track.modified_at.max(track.added_at))
+        .max();
+    let _ = writeln!(out, "artists: {}", artists.len());
+    let _ = writeln!(out, "albums: {}", context.state.db.count_albums().await?);
+    let _ = writeln!(out, "songs: {}", songs.len());
+    let _ = writeln!(out, "uptime: {}", context.started.elapsed().as_secs());
+    let _ = writeln!(out, "db_playtime: {playtime}");
+    let _ = writeln!(
+        out,
+        "db_update: {}",
+        updated.map_or(0, |time| time.timestamp())
+    );
+    let _ = writeln!(out, "playtime: 0");
+    Ok(())
+}
+
+/// Check the library for changed and missing files in the background.
+///
+/// Refused when the server is read-only, as the rescan changes the library.
+fn update(context: &Context, out: &mut String) -> Result<(), Ack> {
+    if context.state.config.web.read_only {
+        return Err(Ack::permission("Database updates are disabled"));
+    }
+    let job = context.updates.fetch_add(1, Ordering::Relaxed) + 1;
+    let scheduler = JobScheduler::new(Arc::clone(&context.state));
+    tokio::spawn(async move {
+        match scheduler.run_job(ScheduledJob::Rescan).await {
+            Ok(summary) => info!("MPD database update {job}: {summary}"),
+            Err(e) => warn!("MPD database update {job} failed: {e:?}"),
+        }
+    });
+    let _ = writeln!(out, "updating_db: {job}");
+    Ok(())
+}
+
+/// Add the song at a URI, or the songs in a directory, to the queue.
+async fn add(
+    context: &Context,
+    with_id: bool,
+    args: &[String],
+    out: &mut String,
+) -> Result<(), Ack> {
+    let uri = arg(args, 0)?.trim_end_matches('/');
+    let position = args.get(1).map(|text| number(text)).transpose()?;
+    let songs = library(context).await?;
+    let tracks: Vec<Track> = if let Some((_, track)) = songs.iter().find(|(song, _)| song == uri) {
+        vec![track.clone()]
+    } else if with_id {
+        // Only a single song gets an ID back
+        return Err(Ack::no_exist("No such song"));
+    } else {
+        let prefix = format!("{uri}/");
+        songs
+            .into_iter()
+            .filter(|(song, _)| uri.is_empty() || song.starts_with(&prefix))
+            .map(|(_, track)| track)
+            .collect()
+    };
+    if tracks.is_empty() {
+        return Err(Ack::no_exist("No such directory"));
+    }
+    let ids = context.state.queue.add(tracks, position)?;
+    if with_id {
+        let _ = writeln!(out, "Id: {}", ids[0]);
+    }
+    Ok(())
+}
+
+fn playlist_info(context: &Context, args: &[String], out: &mut String) -> Result<(), Ack> {
+    let entries = context.state.queue.entries();
+    let range = match args.first() {
+        Some(text) => range(text, entries.len())?,
+        None => 0..entries.len(),
+    };
+    let start = range.start;
+    let selected = entries
+        .get(range)
+        .ok_or_else(|| Ack::arg("Bad song index"))?;
+    for (offset, entry) in selected.iter().enumerate() {
+        write_entry(context, out, start + offset, entry);
+    }
+    Ok(())
+}
+
+fn playlist_id(context: &Context, args: &[String], out: &mut String) -> Result<(), Ack> {
+    let id: Option<u32> = args.first().map(|text| number(text)).transpose()?;
+    let entries = context.state.queue.entries();
+    let mut found = false;
+    for (position, entry) in entries.iter().enumerate() {
+        if id.is_none_or(|id| id == entry.id) {
+            write_entry(context, out, position, entry);
+            found = true;
+        }
+    }
+    if id.is_some() && !found {
+        return Err(Ack::no_exist("No such song"));
+    }
+    Ok(())
+}
+
+/// List the queue if it changed since a version.
+///
+/// Changes to single songs are not tracked, so any change lists every song,
+/// which clients apply like a list of changed songs.
+fn changes(
+    context: &Context,
+    args: &[String],
+    positions_only: bool,
+    out: &mut String,
+) -> Result<(), Ack> {
+    let version: u32 = number(arg(args, 0)?)?;
+    if version == context.state.queue.status().version {
+        return Ok(());
+    }
+    for (position, entry) in context.state.queue.entries().iter().enumerate() {
+        if positions_only {
+            let _ = writeln!(out, "cpos: {position}");
+            let _ = writeln!(out, "Id: {}", entry.id);
+        } else {
+            write_entry(context, out, position, entry);
+        }
+    }
+    Ok(())
+}
+
+/// Find the songs that match the filter of `find` or `search`, and apply
+/// its `sort` and `window` options.
+async fn find(context: &Context, args: &[String], fold_case: bool) -> Result<Vec<Song>, Ack> {
+    let (filter, mut options) = Filter::parse(args, fold_case)?;
+    if filter.is_empty() {
+        return Err(Ack::arg("Missing filter"));
+    }
+    let mut songs: Vec<Song> = library(context)
+        .await?
+        .into_iter()
+        .filter(|(uri, track)| filter.matches(track, uri))
+        .collect();
+    while let [name, value, rest @ ..] = options {
+        match name.to_ascii_lowercase().as_str() {
+            "sort" => {
+                let descending = value.starts_with('-');
+                let tag = Tag::parse(value.trim_start_matches('-'))
+                    .ok_or_else(|| Ack::arg(format!("Unknown sort tag: {value}")))?;
+                songs.sort_by_cached_key(|(uri, track)| tag.values(track, uri).into_iter().next());
+                if descending {
+                    songs.reverse();
+                }
+            }
+            "window" => {
+                let window = range(value, songs.len())?;
+                let end = window.end.min(songs.len());
+                songs = songs.drain(window.start.min(end)..end).collect();
+            }
+            _ => return Err(Ack::arg(format!("Unknown option: {name}"))),
+        }
+        options = rest;
+    }
+    if !options.is_empty() {
+        return Err(Ack::arg("Too many arguments"));
+    }
+    Ok(songs)
+}
+
+async fn count(context: &Context, args: &[String], out: &mut String) -> Result<(), Ack> {
+    let (filter, rest) = Filter::parse(args, false)?;
+    if !rest.is_empty() {
+        return Err(Ack::arg("Too many arguments"));
+    }
+    let songs: Vec<Song> = library(context)
+        .await?
+        .into_iter()
+        .filter(|(uri, track)| filter.matches(track, uri))
+        .collect();
+    let playtime: u64 = songs
+        .iter()
+        .map(|(_, track)| track.duration.as_secs())
+        .sum();
+    let _ = writeln!(out, "songs: {}", songs.len());
+    let _ = writeln!(out, "playtime: {playtime}");
+    Ok(())
+}
+
+/// List the values of a tag, optionally grouped by the values of others.
+async fn list(context: &Context, args: &[String], out: &mut String) -> Result<(), Ack> {
+    let tag = Tag::parse(arg(args, 0)?)
+        .filter(|tag| *tag != Tag::Any)
+        .ok_or_else(|| Ack::arg(format!("Unknown tag type: {}", args[0])))?;
+    let rest = &args[1..];
+    // Older clients list the albums of an artist with `list album ARTIST`
+    let (filter, mut options) = match rest {
+        [artist] if tag == Tag::Album && !artist.starts_with('(') => {
+            (Filter::equals(Tag::Artist, artist), &rest[1..])
+        }
+        _ => Filter::parse(rest, false)?,
+    };
+    let mut groups = Vec::new();
+    while let [name, value, rest @ ..] = options {
+        if !name.eq_ignore_ascii_case("group") {
+            return Err(Ack::arg(format!("Unknown option: {name}")));
+        }
+        groups
+            .push(Tag::parse(value).ok_or_else(|| Ack::arg(format!("Unknown tag type: {value}")))?);
+        options = rest;
+    }
+    if !options.is_empty() {
+        return Err(Ack::arg("Too many arguments"));
+    }
+
+    let mut values: BTreeMap<Vec<String>, BTreeSet<String>> = BTreeMap::new();
+    for (uri, track) in library(context).await? {
+        if !filter.matches(&track, &uri) {
+            continue;
+        }
+        let key = groups
+            .iter()
+            .map(|group| {
+                group
+                    .values(&track, &uri)
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default()
+            })
+            .collect();
+        values
+            .entry(key)
+            .or_default()
+            .extend(tag.values(&track, &uri));
+    }
+
+    let mut previous: Vec<String> = Vec::new();
+    for (key, tag_values) in values {
+        // A group's value is written when it changes, before its values
+        let changed = key
+            .iter()
+            .zip(&previous)
+            .take_while(|(value, previous)| value == previous)
+            .count();
+        for (group, value) in groups.iter().zip(&key).skip(changed) {
+            let _ = writeln!(out, "{}: {value}", group.name());
+        }
+        for value in tag_values {
+            let _ = writeln!(out, "{}: {value}", tag.name());
+        }
+        previous = key;
+    }
+    Ok(())
+}
+
+/// List the directories and songs in a directory, or describe a song.
+async fn ls_info(context: &Context, args: &[String], out: &mut String) -> Result<(), Ack> {
+    let dir = args.first().map_or("", |uri| uri.trim_end_matches('/'));
+    let songs = library(context).await?;
+    if let Some((uri, track)) = songs.iter().find(|(uri, _)| uri == dir) {
+        write_song(out, uri, track);
+        return Ok(());
+    }
+
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{dir}/")
+    };
+    let mut directories = BTreeSet::new();
+    let mut files = Vec::new();
+    for (uri, track) in &songs {
+        // Songs outside the music directory are in no directory
+        let Some(rest) = uri.strip_prefix(&prefix).filter(|_| !uri.starts_with('/')) else {
+            continue;
+        };
+        match rest.split_once('/') {
+            Some((directory, _)) => {
+                directories.insert(format!("{prefix}{directory}"));
+            }
+            None => files.push((uri, track)),
+        }
+    }
+    if !dir.is_empty() && directories.is_empty() && files.is_empty() {
+        return Err(Ack::no_exist("No such directory"));
+    }
+    for directory in directories {
+        let _ = writeln!(out, "directory: {directory}");
+    }
+    for (uri, track) in files {
+        write_song(out, uri, track);
+    }
+    if dir.is_empty() {
+        for playlist in context.state.db.list_playlists().await? {
+            let _ = writeln!(out, "playlist: {}", playlist.name);
+        }
+    }
+    Ok(())
+}
+
+/// List every directory and song in a directory, recursively.
+async fn list_all(
+    context: &Context,
+    args: &[String],
+    info: bool,
+    out: &mut String,
+) -> Result<(), Ack> {
+    let dir = args.first().map_or("", |uri| uri.trim_end_matches('/'));
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{dir}/")
+    };
+    let mut directories = BTreeSet::new();
+    let mut found = dir.is_empty();
+    for (uri, track) in library(context).await? {
+        if uri != dir && !uri.starts_with(&prefix) {
+            continue;
+        }
+        found = true;
+        // Directories are written before the first song in them, and songs
+        // outside the music directory are in none
+        let mut end = if uri.starts_with('/') {
+            uri.len()
+        } else {
+            prefix.len()
+        };
+        while let Some(slash) = uri[end..].find('/') {
+            end += slash;
+            let directory = &uri[..end];
+            if !directory.is_empty() && directories.insert(directory.to_string()) {
+                let _ = writeln!(out, "directory: {directory}");
+            }
+            end += 1;
+        }
+        if info {
+            write_song(out, &uri, &track);
+        } else {
+            let _ = writeln!(out, "file: {uri}");
+        }
+    }
+    if found {
+        Ok(())
+    } else {
+        Err(Ack::no_exist("No such directory"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use apollo_core::Config;
+    use apollo_db::SqliteLibrary;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicU32;
+    use std::time::{Duration, Instant};
+
+    async fn context() -> Context {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let songs = [
+            ("Nina Simone", "Pastel Blues", 1965, "Sinnerman"),
+            ("Nina Simone", "Pastel Blues", 1965, "Be My Husband"),
+            ("Nina Simone", "Wild Is the Wind", 1966, "Four Women"),
+            ("Miles Davis", "Kind of Blue", 1959, "So What"),
+        ];
+        for (number, (artist, album, year, title)) in (1..).zip(songs) {
+            let mut track = Track::new(
+                PathBuf::from(format!("/music/{artist}/{album}/{number:02} {title}.flac")),
+                title.to_string(),
+                artist.to_string(),
+                Duration::from_mins(5),
+            );
+            track.album_title = Some(album.to_string());
+            track.year = Some(year);
+            track.track_number = Some(number);
+            db.add_track(&track).await.unwrap();
+        }
+        let elsewhere = Track::new(
+            PathBuf::from("/downloads/single.mp3"),
+            "Single".to_string(),
+            "Someone".to_string(),
+            Duration::from_secs(200),
+        );
+        db.add_track(&elsewhere).await.unwrap();
+
+        let mut config = Config::default();
+        config.paths.music_directory = Some(PathBuf::from("/music"));
+        Context {
+            music_dir: config.music_directory(),
+            state: Arc::new(AppState::new(db).with_config(config)),
+            started: Instant::now(),
+            updates: AtomicU32::new(0),
+        }
+    }
+
+    async fn run(context: &Context, line: &str) -> Result<Vec<String>, Ack> {
+        let words = super::super::tokenize(line).unwrap();
+        let out = execute(context, &words[0], &words[1..]).await?;
+        Ok(out.lines().map(str::to_string).collect())
+    }
+
+    fn values<'a>(lines: &'a [String], name: &str) -> Vec<&'a str> {
+        lines
+            .iter()
+            .filter_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let context = context().await;
+        let lines = run(&context, "list album").await.unwrap();
+        assert_eq!(
+            values(&lines, "Album"),
+            vec!["Kind of Blue", "Pastel Blues", "Wild Is the Wind"]
+        );
+
+        let lines = run(&context, r#"list album "Miles Davis""#).await.unwrap();
+        assert_eq!(lines, vec!["Album: Kind of Blue"]);
+
+        let lines = run(&context, r#"list album "(date == '1965')" group artist"#)
+            .await
+            .unwrap();
+        assert_eq!(lines, vec!["Artist: Nina Simone", "Album: Pastel Blues"]);
+
+        let lines = run(&context, "list date group artist").await.unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "Artist: Miles Davis",
+                "Date: 1959",
+                "Artist: Nina Simone",
+                "Date: 1965",
+                "Date: 1966",
+                "Artist: Someone",
+            ]
+        );
+        assert_eq!(run(&context, "list mood").await.unwrap_err().code, 2);
+    }
+
+    #[tokio::test]
+    async fn test_find_and_search() {
+        let context = context().await;
+        let lines = run(&context, r#"find album "Pastel Blues" sort title"#)
+            .await
+            .unwrap();
+        assert_eq!(values(&lines, "Title"), vec!["Be My Husband", "Sinnerman"]);
+        assert_eq!(
+            values(&lines, "file")[0],
+            "Nina Simone/Pastel Blues/02 Be My Husband.flac"
+        );
+        assert!(lines.contains(&"duration: 300.000".to_string()));
+
+        let lines = run(&context, "search title WOMEN").await.unwrap();
+        assert_eq!(values(&lines, "Title"), vec!["Four Women"]);
+        let lines = run(&context, "search any blue window 1:2").await.unwrap();
+        assert_eq!(values(&lines, "file").len(), 1);
+
+        let lines = run(&context, "count artist \"Nina Simone\"").await.unwrap();
+        assert_eq!(lines, vec!["songs: 3", "playtime: 900"]);
+        assert_eq!(run(&context, "find").await.unwrap_err().code, 2);
+    }
+
+    #[tokio::test]
+    async fn test_browse() {
+        let context = context().await;
+        let lines = run(&context, "lsinfo").await.unwrap();
+        assert_eq!(
+            lines,
+            vec!["directory: Miles Davis", "directory: Nina Simone"]
+        );
+        let lines = run(&context, r#"lsinfo "Nina Simone""#).await.unwrap();
+        assert_eq!(
+            values(&lines, "directory"),
+            vec!["Nina Simone/Pastel Blues", "Nina Simone/Wild Is the Wind"]
+        );
+        assert_eq!(run(&context, "lsinfo Nobody").await.unwrap_err().code, 50);
+
+        let lines = run(&context, r#"listall "Miles Davis""#).await.unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "directory: Miles Davis/Kind of Blue",
+                "file: Miles Davis/Kind of Blue/04 So What.flac",
+            ]
+        );
+        let lines = run(&context, "listall").await.unwrap();
+        assert_eq!(lines[0], "file: /downloads/single.mp3");
+        assert_eq!(values(&lines, "directory").len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_queue() {
+        let context = context().await;
+        run(&context, r#"add "Nina Simone/Pastel Blues""#)
+            .await
+            .unwrap();
+        let lines = run(
+            &context,
+            r#"addid "Miles Davis/Kind of Blue/04 So What.flac" 0"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(lines, vec!["Id: 2"]);
+        assert!(run(&context, r#"addid "Nina Simone""#).await.is_err());
+
+        let lines = run(&context, "playlistinfo").await.unwrap();
+        assert_eq!(
+            values(&lines, "Title"),
+            vec!["So What", "Sinnerman", "Be My Husband"]
+        );
+        assert_eq!(values(&lines, "Pos"), vec!["0", "1", "2"]);
+        let lines = run(&context, "playlistinfo 1:").await.unwrap();
+        assert_eq!(values(&lines, "Id"), vec!["0", "1"]);
+        assert!(run(&context, "playlistinfo 7").await.is_err());
+
+        run(&context, "playid 1").await.unwrap();
+        let lines = run(&context, "status").await.unwrap();
+        assert!(lines.contains(&"state: play".to_string()));
+        assert!(lines.contains(&"song: 2".to_string()));
+        assert!(lines.contains(&"playlistlength: 3".to_string()));
+        let lines = run(&context, "currentsong").await.unwrap();
+        assert_eq!(values(&lines, "Title"), vec!["Be My Husband"]);
+
+        let version = context.state.queue.status().version;
+        assert!(
+            run(&context, &format!("plchanges {version}"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        run(&context, "delete 0:2").await.unwrap();
+        let lines = run(&context, &format!("plchangesposid {version}"))
+            .await
+            .unwrap();
+        assert_eq!(lines, vec!["cpos: 0", "Id: 1"]);
+
+        run(&context, "clear").await.unwrap();
+        run(&context, "findadd date 1966").await.unwrap();
+        assert_eq!(context.state.queue.entries().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_refused_when_read_only() {
+        let mut config = Config::default();
+        config.web.read_only = true;
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let context = Context {
+            music_dir: config.music_directory(),
+            state: Arc::new(AppState::new(db).with_config(config)),
+            started: Instant::now(),
+            updates: AtomicU32::new(0),
+        };
+        assert_eq!(run(&context, "update").await.unwrap_err().code, 4);
+        assert_eq!(run(&context, "rescan").await.unwrap_err().code, 4);
+        assert_eq!(context.updates.load(Ordering::Relaxed), 0);
+    }
+}
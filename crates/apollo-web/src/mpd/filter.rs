@@ -0,0 +1,419 @@
+//! Filters of MPD's `find`, `search`, `list`, and `count` commands.
+//!
+//! Clients send filters in two forms: pairs of a tag and a value, such as
+//! `find artist "Nina Simone" album "Pastel Blues"`, or an expression such
+//! as `find "((artist == 'Nina Simone') AND (date != '1965'))"`. Both are
+//! parsed into a [`Filter`], whose conditions must all match.
+
+use super::Ack;
+use apollo_core::metadata::Track;
+
+/// Names that end a filter and start the options of a command.
+const OPTIONS: [&str; 4] = ["sort", "window", "group", "position"];
+
+/// A tag that songs are described and filtered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    Artist,
+    AlbumArtist,
+    Album,
+    Title,
+    Track,
+    Disc,
+    Date,
+    Genre,
+    MusicBrainzTrackId,
+    /// The URI of the song.
+    File,
+    /// Any tag except the URI.
+    Any,
+}
+
+impl Tag {
+    /// Tags that songs are described with, for `tagtypes`.
+    pub const TYPES: [Self; 9] = [
+        Self::Artist,
+        Self::AlbumArtist,
+        Self::Album,
+        Self::Title,
+        Self::Track,
+        Self::Disc,
+        Self::Date,
+        Self::Genre,
+        Self::MusicBrainzTrackId,
+    ];
+
+    /// Parse a tag name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "artist" => Self::Artist,
+            "albumartist" => Self::AlbumArtist,
+            "album" => Self::Album,
+            "title" => Self::Title,
+            "track" => Self::Track,
+            "disc" => Self::Disc,
+            "date" => Self::Date,
+            "genre" => Self::Genre,
+            "musicbrainz_trackid" => Self::MusicBrainzTrackId,
+            "file" => Self::File,
+            "any" => Self::Any,
+            _ => return None,
+        })
+    }
+
+    /// Get the name that MPD writes the tag with.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Artist => "Artist",
+            Self::AlbumArtist => "AlbumArtist",
+            Self::Album => "Album",
+            Self::Title => "Title",
+            Self::Track => "Track",
+            Self::Disc => "Disc",
+            Self::Date => "Date",
+            Self::Genre => "Genre",
+            Self::MusicBrainzTrackId => "MUSICBRAINZ_TRACKID",
+            Self::File => "file",
+            Self::Any => "any",
+        }
+    }
+
+    /// Get the values of the tag for a song at `uri`, leaving out empty
+    /// ones.
+    pub fn values(self, track: &Track, uri: &str) -> Vec<String> {
+        let values = match self {
+            Self::Artist => vec![track.artist.clone()],
+            Self::AlbumArtist => track.album_artist.iter().cloned().collect(),
+            Self::Album => track.album_title.iter().cloned().collect(),
+            Self::Title => vec![track.title.clone()],
+            Self::Track => track.track_number.iter().map(u32::to_string).collect(),
+            Self::Disc => track.disc_number.iter().map(u32::to_string).collect(),
+            Self::Date => track.year.iter().map(i32::to_string).collect(),
+            Self::Genre => track.genres.clone(),
+            Self::MusicBrainzTrackId => track.musicbrainz_id.iter().cloned().collect(),
+            Self::File => vec![uri.to_string()],
+            Self::Any => Self::TYPES
+                .iter()
+                .flat_map(|tag| tag.values(track, uri))
+                .collect(),
+        };
+        values
+            .into_iter()
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+}
+
+/// How a tag is compared with a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equals,
+    NotEquals,
+    Contains,
+    StartsWith,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Compare(Tag, Op, String),
+    Not(Box<Self>),
+    And(Vec<Self>),
+}
+
+impl Expr {
+    fn matches(&self, track: &Track, uri: &str, fold_case: bool) -> bool {
+        match self {
+            Self::Compare(tag, op, value) => {
+                let fold = |text: &str| {
+                    if fold_case {
+                        text.to_lowercase()
+                    } else {
+                        text.to_string()
+                    }
+                };
+                let value = fold(value);
+                let values: Vec<String> = tag
+                    .values(track, uri)
+                    .iter()
+                    .map(|text| fold(text))
+                    .collect();
+                // An empty value matches songs without the tag
+                let equals = || values.contains(&value) || (value.is_empty() && values.is_empty());
+                match op {
+                    Op::Equals => equals(),
+                    Op::NotEquals => !equals(),
+                    Op::Contains => values.iter().any(|text| text.contains(&value)),
+                    Op::StartsWith => values.iter().any(|text| text.starts_with(&value)),
+                }
+            }
+            Self::Not(expr) => !expr.matches(track, uri, fold_case),
+            Self::And(exprs) => exprs.iter().all(|expr| expr.matches(track, uri, fold_case)),
+        }
+    }
+}
+
+/// Conditions that songs must all match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    exprs: Vec<Expr>,
+    fold_case: bool,
+}
+
+impl Filter {
+    /// Parse the filter at the start of the arguments of a command, and get
+    /// the arguments after it.
+    ///
+    /// Pairs match exactly, or with `search`, which sets `fold_case`, by a
+    /// part of the value in any case. Comparisons in expressions ignore case
+    /// with `search` as well.
+    pub fn parse(args: &[String], fold_case: bool) -> Result<(Self, &[String]), Ack> {
+        let mut filter = Self {
+            exprs: Vec::new(),
+            fold_case,
+        };
+        let mut rest = args;
+        while let Some(first) = rest.first() {
+            if first.starts_with('(') {
+                filter.exprs.push(Parser::new(first).parse()?);
+                rest = &rest[1..];
+                continue;
+            }
+            if OPTIONS.contains(&first.to_ascii_lowercase().as_str()) {
+                break;
+            }
+            let tag = Tag::parse(first)
+                .ok_or_else(|| Ack::arg(format!("Unknown filter type: {first}")))?;
+            let value = rest
+                .get(1)
+                .ok_or_else(|| Ack::arg(format!("Missing value for filter: {first}")))?;
+            let op = if fold_case { Op::Contains } else { Op::Equals };
+            filter.exprs.push(Expr::Compare(tag, op, value.clone()));
+            rest = &rest[2..];
+        }
+        Ok((filter, rest))
+    }
+
+    /// Create a filter on the value of one tag.
+    pub fn equals(tag: Tag, value: &str) -> Self {
+        Self {
+            exprs: vec![Expr::Compare(tag, Op::Equals, value.to_string())],
+            fold_case: false,
+        }
+    }
+
+    /// Check if the filter has no conditions, which every song matches.
+    pub const fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+
+    /// Check if a song at `uri` matches the filter.
+    pub fn matches(&self, track: &Track, uri: &str) -> bool {
+        self.exprs
+            .iter()
+            .all(|expr| expr.matches(track, uri, self.fold_case))
+    }
+}
+
+/// Parses a filter expression.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    const fn new(text: &'a str) -> Self {
+        Self { rest: text }
+    }
+
+    fn parse(mut self) -> Result<Expr, Ack> {
+        let expr = self.expr()?;
+        if self.rest.trim().is_empty() {
+            Ok(expr)
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, Ack> {
+        self.expect('(')?;
+        let expr = if self.peek() == Some('(') {
+            let mut exprs = vec![self.expr()?];
+            while self.keyword("AND") {
+                exprs.push(self.expr()?);
+            }
+            if exprs.len() == 1 {
+                exprs.remove(0)
+            } else {
+                Expr::And(exprs)
+            }
+        } else if self.peek() == Some('!') {
+            self.rest = &self.rest[1..];
+            Expr::Not(Box::new(self.expr()?))
+        } else {
+            self.compare()?
+        };
+        self.expect(')')?;
+        Ok(expr)
+    }
+
+    fn compare(&mut self) -> Result<Expr, Ack> {
+        let name = self.word();
+        if name == "base" {
+            let mut base = self.quoted()?;
+            base.push('/');
+            return Ok(Expr::Compare(Tag::File, Op::StartsWith, base));
+        }
+        let tag =
+            Tag::parse(name).ok_or_else(|| Ack::arg(format!("Unknown filter type: {name}")))?;
+        let op = match self.word() {
+            "==" => Op::Equals,
+            "!=" => Op::NotEquals,
+            "contains" => Op::Contains,
+            "starts_with" => Op::StartsWith,
+            op => return Err(Ack::arg(format!("Unsupported filter operator: {op}"))),
+        };
+        Ok(Expr::Compare(tag, op, self.quoted()?))
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.rest = self.rest.trim_start();
+        self.rest.chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Ack> {
+        if self.peek() == Some(c) {
+            self.rest = &self.rest[c.len_utf8()..];
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.peek();
+        match self.rest.strip_prefix(keyword) {
+            Some(rest) if rest.starts_with(char::is_whitespace) || rest.starts_with('(') => {
+                self.rest = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn word(&mut self) -> &'a str {
+        self.peek();
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '\'' | '"'))
+            .unwrap_or(self.rest.len());
+        let (word, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        word
+    }
+
+    fn quoted(&mut self) -> Result<String, Ack> {
+        let Some(quote @ ('\'' | '"')) = self.peek() else {
+            return Err(self.error());
+        };
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                c if c == quote => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(value);
+                }
+                c => value.push(c),
+            }
+        }
+        Err(self.error())
+    }
+
+    fn error(&self) -> Ack {
+        Ack::arg(format!("Invalid filter expression near: {}", self.rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn track() -> Track {
+        let mut track = Track::new(
+            PathBuf::from("/music/Nina Simone/Pastel Blues/01.flac"),
+            "Be My Husband".to_string(),
+            "Nina Simone".to_string(),
+            Duration::from_mins(3),
+        );
+        track.album_title = Some("Pastel Blues".to_string());
+        track.year = Some(1965);
+        track.genres = vec!["Jazz".to_string(), "Blues".to_string()];
+        track
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| (*arg).to_string()).collect()
+    }
+
+    fn parse(text: &[&str], fold_case: bool) -> Filter {
+        Filter::parse(&args(text), fold_case).unwrap().0
+    }
+
+    const URI: &str = "Nina Simone/Pastel Blues/01.flac";
+
+    #[test]
+    fn test_pairs() {
+        let track = track();
+        assert!(parse(&["artist", "Nina Simone", "Genre", "Blues"], false).matches(&track, URI));
+        assert!(!parse(&["artist", "nina simone"], false).matches(&track, URI));
+        assert!(parse(&["artist", "nina"], true).matches(&track, URI));
+        assert!(parse(&["any", "pastel"], true).matches(&track, URI));
+        assert!(parse(&["albumartist", ""], false).matches(&track, URI));
+        assert!(parse(&[], false).is_empty());
+
+        let all = args(&["date", "1965", "sort", "title", "window", "0:1"]);
+        let (filter, rest) = Filter::parse(&all, false).unwrap();
+        assert!(filter.matches(&track, URI));
+        assert_eq!(rest, &all[2..]);
+
+        assert!(Filter::parse(&args(&["mood", "happy"]), false).is_err());
+        assert!(Filter::parse(&args(&["artist"]), false).is_err());
+    }
+
+    #[test]
+    fn test_expressions() {
+        let track = track();
+        let matches = |text: &str, fold_case| parse(&[text], fold_case).matches(&track, URI);
+        assert!(matches("(artist == 'Nina Simone')", false));
+        assert!(matches(r#"(Album == "Pastel Blues")"#, false));
+        assert!(matches(
+            "((artist == 'Nina Simone') AND (date != '1964'))",
+            false
+        ));
+        assert!(!matches(
+            "((artist == 'Nina Simone') AND (!(genre == 'Jazz')))",
+            false
+        ));
+        assert!(matches("(title contains 'husband')", true));
+        assert!(!matches("(title contains 'husband')", false));
+        assert!(matches("(title starts_with 'Be ')", false));
+        assert!(matches("(base 'Nina Simone')", false));
+        assert!(!matches("(base 'Nina')", false));
+        assert!(!matches(r"(artist == 'Nina\'s')", false));
+
+        for invalid in [
+            "(artist == 'Nina Simone'",
+            "(artist =~ 'Nina.*')",
+            "(mood == 'happy')",
+            "(artist == Nina)",
+            "(artist == 'Nina') extra",
+        ] {
+            assert!(
+                Filter::parse(&args(&[invalid]), false).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! The server's play queue.
+//!
+//! Clients that control playback, such as MPD clients, share one queue of
+//! tracks on the server, with a current track and a playback state. The
+//! queue keeps copies of its tracks, so a track stays queued when it is
+//! edited or removed from the library. Every change is announced as a
+//! [`QueueChange`], and changes to the list of tracks increment the
+//! queue's version.
+
+use crate::error::ApiError;
+use apollo_core::metadata::Track;
+use std::ops::Range;
+use std::sync::{Mutex, PoisonError};
+use tokio::sync::broadcast;
+
+/// Number of changes buffered for each subscriber.
+const CHANGE_CAPACITY: usize = 16;
+
+/// Playback state of the queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlayState {
+    /// Not playing.
+    #[default]
+    Stopped,
+    /// Playing the current track.
+    Playing,
+    /// Paused on the current track.
+    Paused,
+}
+
+/// Kind of change to the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueChange {
+    /// Tracks were added, removed, or moved.
+    Tracks,
+    /// The current track or the playback state changed.
+    Playback,
+}
+
+/// A track in the queue.
+#[derive(Debug, Clone)]
+pub struct QueueEntry {
+    /// ID of the entry, which stays the same while it is queued.
+    pub id: u32,
+    /// The queued track.
+    pub track: Track,
+}
+
+/// Summary of the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueStatus {
+    /// Incremented whenever tracks are added, removed, or moved.
+    pub version: u32,
+    /// Number of queued tracks.
+    pub length: usize,
+    /// Position and entry ID of the current track.
+    pub current: Option<(usize, u32)>,
+    /// Playback state.
+    pub state: PlayState,
+}
+
+/// A shared play queue.
+pub struct PlayQueue {
+    state: Mutex<QueueState>,
+    changes: broadcast::Sender<QueueChange>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    entries: Vec<QueueEntry>,
+    /// Position of the current track.
+    current: Option<usize>,
+    play_state: PlayState,
+    version: u32,
+    next_id: u32,
+}
+
+impl Default for PlayQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlayQueue {
+    /// Create an empty queue.
+    #[must_use]
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_CAPACITY);
+        Self {
+            state: Mutex::new(QueueState::default()),
+            changes,
+        }
+    }
+
+    /// Subscribe to changes made from now on.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<QueueChange> {
+        self.changes.subscribe()
+    }
+
+    /// Get the queued tracks.
+    pub fn entries(&self) -> Vec<QueueEntry> {
+        self.lock().entries.clone()
+    }
+
+    /// Get a summary of the queue.
+    pub fn status(&self) -> QueueStatus {
+        let state = self.lock();
+        QueueStatus {
+            version: state.version,
+            length: state.entries.len(),
+            current: state
+                .current
+                .map(|position| (position, state.entries[position].id)),
+            state: state.play_state,
+        }
+    }
+
+    /// Get the current track.
+    pub fn current(&self) -> Option<(usize, QueueEntry)> {
+        let state = self.lock();
+        state
+            .current
+            .map(|position| (position, state.entries[position].clone()))
+    }
+
+    /// Add tracks at `position`, or at the end, and get their entry IDs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `position` is past the end of the queue.
+    pub fn add(&self, tracks: Vec<Track>, position: Option<usize>) -> Result<Vec<u32>, ApiError> {
+        let mut state = self.lock();
+        let position = position.unwrap_or(state.entries.len());
+        if position > state.entries.len() {
+            return Err(bad_position(position));
+        }
+        let count = tracks.len();
+        let first_id = state.next_id;
+        let entries: Vec<QueueEntry> = tracks
+            .into_iter()
+            .zip(first_id..)
+            .map(|(track, id)| QueueEntry { id, track })
+            .collect();
+        let ids = entries.iter().map(|entry| entry.id).collect();
+        state.next_id = state
+            .next_id
+            .wrapping_add(u32::try_from(count).unwrap_or(u32::MAX));
+        state.entries.splice(position..position, entries);
+        if let Some(current) = state.current.as_mut()
+            && *current >= position
+        {
+            *current += count;
+        }
+        if count > 0 {
+            state.version = state.version.wrapping_add(1);
+            drop(state);
+            self.announce(QueueChange::Tracks);
+        }
+        Ok(ids)
+    }
+
+    /// Remove the tracks at `range`.
+    ///
+    /// Removing the current track stops playback.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `range` is not within the queue.
+    pub fn remove(&self, range: Range<usize>) -> Result<(), ApiError> {
+        let mut state = self.lock();
+        if range.start > range.end || range.end > state.entries.len() {
+            return Err(bad_position(range.end));
+        }
+        if range.is_empty() {
+            return Ok(());
+        }
+        let count = range.len();
+        let stopped = match state.current {
+            Some(current) if range.contains(&current) => {
+                state.current = None;
+                state.play_state = PlayState::Stopped;
+                true
+            }
+            Some(current) if current >= range.end => {
+                state.current = Some(current - count);
+                false
+            }
+            _ => false,
+        };
+        state.entries.drain(range);
+        state.version = state.version.wrapping_add(1);
+        drop(state);
+        self.announce(QueueChange::Tracks);
+        if stopped {
+            self.announce(QueueChange::Playback);
+        }
+        Ok(())
+    }
+
+    /// Remove the track with an entry ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no track has the ID.
+    pub fn remove_id(&self, id: u32) -> Result<(), ApiError> {
+        let position = self.position_of(id)?;
+        self.remove(position..position + 1)
+    }
+
+    /// Move the track at `from` to `to`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either position is not within the queue.
+    pub fn move_track(&self, from: usize, to: usize) -> Result<(), ApiError> {
+        let mut state = self.lock();
+        let length = state.entries.len();
+        if from >= length || to >= length {
+            return Err(bad_position(from.max(to)));
+        }
+        let entry = state.entries.remove(from);
+        state.entries.insert(to, entry);
+        state.current = state.current.map(|current| {
+            if current == from {
+                to
+            } else if from < current && current <= to {
+                current - 1
+            } else if to <= current && current < from {
+                current + 1
+            } else {
+                current
+            }
+        });
+        state.version = state.version.wrapping_add(1);
+        drop(state);
+        self.announce(QueueChange::Tracks);
+        Ok(())
+    }
+
+    /// Remove all tracks, which stops playback.
+    pub fn clear(&self) {
+        let len = self.lock().entries.len();
+        // An empty queue is left alone, so that nothing is announced
+        if len > 0 {
+            let _ = self.remove(0..len);
+        }
+    }
+
+    /// Get the position of the track with an entry ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no track has the ID.
+    pub fn position_of(&self, id: u32) -> Result<usize, ApiError> {
+        self.lock()
+            .entries
+            .iter()
+            .position(|entry| entry.id == id)
+            .ok_or_else(|| ApiError::NotFound(format!("No such song: {id}")))
+    }
+
+    /// Play the track at `position`, or the current track, or the first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `position` is not within the queue.
+    pub fn play(&self, position: Option<usize>) -> Result<(), ApiError> {
+        let mut state = self.lock();
+        let position = match position {
+            Some(position) if position >= state.entries.len() => {
+                return Err(bad_position(position));
+            }
+            Some(position) => position,
+            None => match state.current {
+                Some(current) => current,
+                None if state.entries.is_empty() => return Ok(()),
+                None => 0,
+            },
+        };
+        state.current = Some(position);
+        state.play_state = PlayState::Playing;
+        drop(state);
+        self.announce(QueueChange::Playback);
+        Ok(())
+    }
+
+    /// Pause or resume playback, or toggle it without `paused`.
+    ///
+    /// Nothing happens while stopped.
+    pub fn pause(&self, paused: Option<bool>) {
+        let mut state = self.lock();
+        let paused = paused.unwrap_or(state.play_state == PlayState::Playing);
+        let play_state = match (state.play_state, paused) {
+            (PlayState::Stopped, _) => return,
+            (_, true) => PlayState::Paused,
+            (_, false) => PlayState::Playing,
+        };
+        if state.play_state != play_state {
+            state.play_state = play_state;
+            drop(state);
+            self.announce(QueueChange::Playback);
+        }
+    }
+
+    /// Stop playback, keeping the current track.
+    pub fn stop(&self) {
+        let mut state = self.lock();
+        if state.play_state != PlayState::Stopped {
+            state.play_state = PlayState::Stopped;
+            drop(state);
+            self.announce(QueueChange::Playback);
+        }
+    }
+
+    /// Go to the next track, or stop after the last one.
+    pub fn next(&self) {
+        self.step(|current, length| (current + 1 < length).then_some(current + 1));
+    }
+
+    /// Go to the previous track, or stay on the first one.
+    pub fn previous(&self) {
+        self.step(|current, _| Some(current.saturating_sub(1)));
+    }
+
+    /// Move from the current track while not stopped.
+    fn step(&self, to: impl FnOnce(usize, usize) -> Option<usize>) {
+        let mut state = self.lock();
+        let Some(current) = state.current else {
+            return;
+        };
+        if state.play_state == PlayState::Stopped {
+            return;
+        }
+        state.current = to(current, state.entries.len());
+        if state.current.is_none() {
+            state.play_state = PlayState::Stopped;
+        }
+        drop(state);
+        self.announce(QueueChange::Playback);
+    }
+
+    fn announce(&self, change: QueueChange) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.changes.send(change);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn bad_position(position: usize) -> ApiError {
+    ApiError::BadRequest(format!("Bad song index: {position}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn tracks(titles: &[&str]) -> Vec<Track> {
+        titles
+            .iter()
+            .map(|title| {
+                Track::new(
+                    PathBuf::from(format!("/music/{title}.mp3")),
+                    (*title).to_string(),
+                    "Artist".to_string(),
+                    Duration::from_mins(3),
+                )
+            })
+            .collect()
+    }
+
+    fn titles(queue: &PlayQueue) -> Vec<String> {
+        queue
+            .entries()
+            .into_iter()
+            .map(|entry| entry.track.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let queue = PlayQueue::new();
+        let mut changes = queue.subscribe();
+        let ids = queue.add(tracks(&["a", "c"]), None).unwrap();
+        assert_eq!(ids, vec![0, 1]);
+        assert_eq!(queue.add(tracks(&["b"]), Some(1)).unwrap(), vec![2]);
+        assert_eq!(titles(&queue), vec!["a", "b", "c"]);
+        assert_eq!(queue.status().version, 2);
+        assert_eq!(changes.try_recv().unwrap(), QueueChange::Tracks);
+        assert!(queue.add(tracks(&["x"]), Some(9)).is_err());
+
+        queue.remove_id(2).unwrap();
+        assert_eq!(titles(&queue), vec!["a", "c"]);
+        queue.move_track(0, 1).unwrap();
+        assert_eq!(titles(&queue), vec!["c", "a"]);
+        assert!(queue.remove(1..3).is_err());
+        queue.clear();
+        assert!(queue.entries().is_empty());
+        assert!(matches!(queue.remove_id(0), Err(ApiError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_playback() {
+        let queue = PlayQueue::new();
+        queue.play(None).unwrap();
+        assert_eq!(queue.status().state, PlayState::Stopped);
+
+        queue.add(tracks(&["a", "b", "c"]), None).unwrap();
+        queue.play(Some(1)).unwrap();
+        assert_eq!(queue.status().current, Some((1, 1)));
+        assert_eq!(queue.status().state, PlayState::Playing);
+
+        // The current track follows its entry as others are added or moved
+        queue.add(tracks(&["first"]), Some(0)).unwrap();
+        assert_eq!(queue.status().current, Some((2, 1)));
+        queue.move_track(3, 0).unwrap();
+        assert_eq!(queue.status().current, Some((3, 1)));
+        assert_eq!(queue.current().unwrap().1.track.title, "b");
+
+        queue.pause(None);
+        assert_eq!(queue.status().state, PlayState::Paused);
+        queue.pause(Some(false));
+        assert_eq!(queue.status().state, PlayState::Playing);
+
+        queue.previous();
+        assert_eq!(queue.status().current, Some((2, 0)));
+        queue.next();
+        queue.next();
+        assert_eq!(queue.status().current, None);
+        assert_eq!(queue.status().state, PlayState::Stopped);
+
+        queue.play(Some(0)).unwrap();
+        queue.remove(0..1).unwrap();
+        assert_eq!(queue.status().state, PlayState::Stopped);
+    }
+}
@@ -0,0 +1,495 @@
+//! Periodic maintenance while the web server runs.
+//!
+//! A [`JobScheduler`] runs the tasks configured under `[schedule]`: importing
+//! new files from the watched folders, evaluating smart playlists ahead of
+//! requests, dropping expired metadata lookups, downloading missing covers,
+//! and backing up the library. Jobs run one at a time, so a backup never
+//! overlaps an import, and how each job last went is kept in the
+//! [`ScheduleStatus`] of the [`AppState`] for `/api/jobs/scheduled`.
+
+use crate::error::ApiError;
+use crate::import::{ImportOptions, ImportService};
+use crate::state::AppState;
+use apollo_core::config::ScheduleConfig;
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// A maintenance task that runs periodically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledJob {
+    /// Import new files from the watched folders.
+    Rescan,
+    /// Evaluate smart playlists that are not cached.
+    Playlists,
+    /// Drop expired metadata lookups.
+    PruneCache,
+    /// Download covers for albums without one.
+    FetchArt,
+    /// Back up the library.
+    Backup,
+}
+
+impl ScheduledJob {
+    /// All jobs, in the order they are listed.
+    pub const ALL: [Self; 5] = [
+        Self::Rescan,
+        Self::Playlists,
+        Self::PruneCache,
+        Self::FetchArt,
+        Self::Backup,
+    ];
+
+    /// Get the name of the job.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Rescan => "rescan",
+            Self::Playlists => "playlists",
+            Self::PruneCache => "prune_cache",
+            Self::FetchArt => "fetch_art",
+            Self::Backup => "backup",
+        }
+    }
+
+    /// Get the configured interval of the job, which is zero if it does not
+    /// run.
+    #[must_use]
+    pub const fn interval(self, config: &ScheduleConfig) -> Duration {
+        Duration::from_secs(match self {
+            Self::Rescan => config.rescan_secs,
+            Self::Playlists => config.playlists_secs,
+            Self::PruneCache => config.prune_cache_secs,
+            Self::FetchArt => config.fetch_art_secs,
+            Self::Backup => config.backup_secs,
+        })
+    }
+}
+
+/// Status of a scheduled job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct JobStatus {
+    /// Job name.
+    #[schema(example = "backup")]
+    pub name: String,
+    /// Seconds between runs.
+    #[schema(example = 86400)]
+    pub interval_secs: u64,
+    /// Whether the job is running now.
+    pub running: bool,
+    /// When the job last started.
+    pub last_run: Option<DateTime<Utc>>,
+    /// How long the last run took, in milliseconds.
+    pub last_duration_ms: Option<u64>,
+    /// What the last run did, if it succeeded.
+    #[schema(example = "Backed up to /home/user/.apollo/apollo-scheduled-20250101-030000.db")]
+    pub last_result: Option<String>,
+    /// Why the last run failed, if it did.
+    pub last_error: Option<String>,
+    /// When the job runs next.
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// Status of the scheduled jobs, shared with the API.
+#[derive(Debug, Default)]
+pub struct ScheduleStatus {
+    jobs: Mutex<Vec<JobStatus>>,
+}
+
+impl ScheduleStatus {
+    /// Get the status of the jobs that are scheduled.
+    pub fn jobs(&self) -> Vec<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Add a job that first runs at `next_run`.
+    fn schedule(&self, job: ScheduledJob, interval: Duration, next_run: DateTime<Utc>) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.retain(|status| status.name != job.name());
+        jobs.push(JobStatus {
+            name: job.name().to_string(),
+            interval_secs: interval.as_secs(),
+            running: false,
+            last_run: None,
+            last_duration_ms: None,
+            last_result: None,
+            last_error: None,
+            next_run: Some(next_run),
+        });
+    }
+
+    /// Change the status of a scheduled job.
+    fn update(&self, job: ScheduledJob, change: impl FnOnce(&mut JobStatus)) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(status) = jobs.iter_mut().find(|status| status.name == job.name()) {
+            change(status);
+        }
+    }
+}
+
+/// Runs the configured maintenance jobs.
+pub struct JobScheduler {
+    state: Arc<AppState>,
+    service: ImportService,
+    options: ImportOptions,
+    directories: Vec<PathBuf>,
+    library_path: PathBuf,
+    backup_directory: PathBuf,
+    backups_kept: usize,
+    intervals: Vec<(ScheduledJob, Duration)>,
+}
+
+impl JobScheduler {
+    /// Create a scheduler for the jobs in the configuration of `state`.
+    #[must_use]
+    pub fn new(state: Arc<AppState>) -> Self {
+        let config = &state.config;
+        let mut service = ImportService::new(Arc::clone(&state.db), config);
+        if let Some(hooks) = &state.hooks {
+            service = service.with_hooks(Arc::clone(hooks));
+        }
+        Self {
+            service,
+            options: ImportOptions::from_config(config),
+            directories: config.watch_directories(),
+            library_path: config.library_path(),
+            backup_directory: config.backup_directory(),
+            backups_kept: config.schedule.backups_kept,
+            intervals: ScheduledJob::ALL
+                .into_iter()
+                .map(|job| (job, job.interval(&config.schedule)))
+                .filter(|(_, interval)| !interval.is_zero())
+                .collect(),
+            state,
+        }
+    }
+
+    /// Back up the library at `path`, naming backups after it.
+    ///
+    /// Backups go next to it unless a directory is configured.
+    #[must_use]
+    pub fn with_library_path(mut self, path: PathBuf) -> Self {
+        if self.state.config.schedule.backup_directory.is_none() {
+            self.backup_directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        }
+        self.library_path = path;
+        self
+    }
+
+    /// Get the jobs that run, with their intervals.
+    #[must_use]
+    pub fn jobs(&self) -> &[(ScheduledJob, Duration)] {
+        &self.intervals
+    }
+
+    /// Run each job every interval, starting after the first one.
+    ///
+    /// This runs until the task is cancelled, and returns right away if no
+    /// jobs are scheduled.
+    pub async fn run(self) {
+        let start = Instant::now();
+        let mut due: Vec<(ScheduledJob, Duration, Instant)> = self
+            .intervals
+            .iter()
+            .map(|&(job, interval)| (job, interval, start + interval))
+            .collect();
+        for &(job, interval, _) in &due {
+            self.state
+                .jobs
+                .schedule(job, interval, next_run_at(interval));
+        }
+
+        while let Some((job, interval, at)) = due.iter_mut().min_by_key(|(_, _, at)| *at) {
+            tokio::time::sleep_until(*at).await;
+            self.run_and_record(*job).await;
+            // Runs that take longer than the interval do not pile up
+            *at = Instant::now() + *interval;
+            let next_run = next_run_at(*interval);
+            self.state
+                .jobs
+                .update(*job, |status| status.next_run = Some(next_run));
+        }
+    }
+
+    /// Run a job and record how it went.
+    async fn run_and_record(&self, job: ScheduledJob) {
+        let started = Utc::now();
+        self.state.jobs.update(job, |status| status.running = true);
+        let timer = Instant::now();
+        let result = self.run_job(job).await;
+        let duration = u64::try_from(timer.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+        match &result {
+            Ok(message) => info!("Scheduled {}: {message}", job.name()),
+            Err(e) => warn!("Scheduled {} failed: {e:?}", job.name()),
+        }
+        self.state.jobs.update(job, |status| {
+            status.running = false;
+            status.last_run = Some(started);
+            status.last_duration_ms = Some(duration);
+            match result {
+                Ok(message) => {
+                    status.last_result = Some(message);
+                    status.last_error = None;
+                }
+                Err(e) => {
+                    status.last_result = None;
+                    status.last_error = Some(error_message(e));
+                }
+            }
+        });
+    }
+
+    /// Run a job once, and describe what it did.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the job fails.
+    pub async fn run_job(&self, job: ScheduledJob) -> Result<String, ApiError> {
+        match job {
+            ScheduledJob::Rescan => self.rescan().await,
+            ScheduledJob::Playlists => self.refresh_playlists().await,
+            ScheduledJob::PruneCache => {
+                self.service.prune_cache().await;
+                Ok("Dropped expired metadata lookups".to_string())
+            }
+            ScheduledJob::FetchArt => {
+                let fetched = self.service.fetch_missing_art(&self.options).await?;
+                Ok(format!("Downloaded {fetched} covers"))
+            }
+            ScheduledJob::Backup => self.backup().await,
+        }
+    }
+
+    /// Import new files from the watched folders.
+    async fn rescan(&self) -> Result<String, ApiError> {
+        let mut imported = 0;
+        for directory in &self.directories {
+            let options = self.options.clone().with_source(directory.clone());
+            imported += self.service.import(&options, None).await?.tracks_imported;
+        }
+        Ok(format!(
+            "Imported {imported} tracks from {} folders",
+            self.directories.len()
+        ))
+    }
+
+    /// Evaluate the smart playlists that are not cached, so that requests
+    /// for them are answered from the cache.
+    async fn refresh_playlists(&self) -> Result<String, ApiError> {
+        let cache = &self.state.playlists;
+        let mut evaluated = 0;
+        for playlist in self.state.db.list_playlists().await? {
+            if !playlist.is_smart() || cache.get(&playlist.id).is_some() {
+                continue;
+            }
+            let generation = cache.generation();
+            let tracks = self.state.db.get_playlist_tracks(&playlist.id).await?;
+            cache.insert(playlist.id, tracks, generation);
+            evaluated += 1;
+        }
+        Ok(format!("Evaluated {evaluated} smart playlists"))
+    }
+
+    /// Back up the library, and remove the oldest scheduled backups.
+    async fn backup(&self) -> Result<String, ApiError> {
+        let prefix = format!("{}-scheduled-", self.library_stem());
+        let time = Local::now().format("%Y%m%d-%H%M%S");
+        let path = self.backup_directory.join(format!("{prefix}{time}.db"));
+        std::fs::create_dir_all(&self.backup_directory).map_err(|e| {
+            ApiError::Internal(format!(
+                "Failed to create {}: {e}",
+                self.backup_directory.display()
+            ))
+        })?;
+        self.state.db.backup(&path).await?;
+
+        if self.backups_kept > 0 {
+            self.remove_old_backups(&prefix);
+        }
+        Ok(format!("Backed up to {}", path.display()))
+    }
+
+    /// Remove scheduled backups beyond the number to keep, oldest first.
+    ///
+    /// Backups are named by the time they were made, so sorting their names
+    /// sorts them by age. Backups made by hand are named differently and are
+    /// left alone.
+    fn remove_old_backups(&self, prefix: &str) {
+        let Ok(entries) = std::fs::read_dir(&self.backup_directory) else {
+            return;
+        };
+        let mut backups: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == "db")
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(prefix))
+            })
+            .collect();
+        backups.sort();
+        let excess = backups.len().saturating_sub(self.backups_kept);
+        for path in backups.into_iter().take(excess) {
+            if let Err(e) = std::fs::remove_file(&path) {
+                warn!("Failed to remove old backup {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Get the file name of the library without its extension.
+    fn library_stem(&self) -> String {
+        self.library_path.file_stem().map_or_else(
+            || "library".to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+/// Describe why a job failed.
+fn error_message(error: ApiError) -> String {
+    match error {
+        ApiError::NotFound(message)
+        | ApiError::BadRequest(message)
+        | ApiError::Conflict(message)
+        | ApiError::Internal(message) => message,
+        ApiError::Library(e) => e.to_string(),
+    }
+}
+
+/// Get the time a job that runs every `interval` runs next, from now.
+fn next_run_at(interval: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(interval)
+        .map_or(DateTime::<Utc>::MAX_UTC, |interval| Utc::now() + interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::Config;
+    use apollo_core::metadata::Track;
+    use apollo_core::playlist::Playlist;
+    use apollo_core::query::Query;
+    use apollo_db::SqliteLibrary;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_backup_keeps_latest() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let library = dir.path().join("apollo.db");
+        let db = SqliteLibrary::new(&format!("sqlite:{}?mode=rwc", library.display()))
+            .await
+            .unwrap();
+        let backups = dir.path().join("backups");
+        fs::create_dir(&backups).unwrap();
+        for name in [
+            "apollo-scheduled-20200101-000000.db",
+            "apollo-scheduled-20210101-000000.db",
+            "apollo-backup-20200101-000000.db",
+        ] {
+            fs::write(backups.join(name), b"old").unwrap();
+        }
+
+        let mut config = Config::default();
+        config.schedule.backup_directory = Some(backups.clone());
+        config.schedule.backups_kept = 2;
+        let state = Arc::new(AppState::new(db).with_config(config));
+        let scheduler = JobScheduler::new(state).with_library_path(library);
+        scheduler.run_job(ScheduledJob::Backup).await.unwrap();
+
+        let mut names: Vec<String> = fs::read_dir(&backups)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], "apollo-backup-20200101-000000.db");
+        assert_eq!(names[1], "apollo-scheduled-20210101-000000.db");
+        assert!(names[2].starts_with("apollo-scheduled-"));
+        assert!(fs::metadata(backups.join(&names[2])).unwrap().len() > 3);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_playlists() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let playlist = Playlist::new_smart("Tests", Query::parse("artist:Test").unwrap());
+        db.add_playlist(&playlist).await.unwrap();
+        let track = Track::new(
+            PathBuf::from("/music/song.mp3"),
+            "Song".to_string(),
+            "Test Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+        let state = Arc::new(AppState::new(db));
+
+        let scheduler = JobScheduler::new(Arc::clone(&state));
+        assert!(scheduler.jobs().is_empty());
+        let message = scheduler.run_job(ScheduledJob::Playlists).await.unwrap();
+        assert_eq!(message, "Evaluated 1 smart playlists");
+        assert_eq!(state.playlists.get(&playlist.id).unwrap().len(), 1);
+
+        // Cached playlists are left as they are
+        let message = scheduler.run_job(ScheduledJob::Playlists).await.unwrap();
+        assert_eq!(message, "Evaluated 0 smart playlists");
+    }
+
+    #[tokio::test]
+    async fn test_run_records_status() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let mut config = Config::default();
+        config.schedule.playlists_secs = 60;
+        config.schedule.backup_secs = 3600;
+        // A file where the backup directory should be
+        config.schedule.backup_directory = Some(dir.path().join("file"));
+        fs::write(dir.path().join("file"), b"").unwrap();
+        let state = Arc::new(AppState::new(db).with_config(config));
+        let scheduler = JobScheduler::new(Arc::clone(&state));
+        assert_eq!(
+            scheduler.jobs(),
+            &[
+                (ScheduledJob::Playlists, Duration::from_mins(1)),
+                (ScheduledJob::Backup, Duration::from_hours(1)),
+            ]
+        );
+
+        for &(job, interval) in scheduler.jobs() {
+            state.jobs.schedule(job, interval, next_run_at(interval));
+        }
+        scheduler.run_and_record(ScheduledJob::Playlists).await;
+        scheduler.run_and_record(ScheduledJob::Backup).await;
+
+        let jobs = state.jobs.jobs();
+        assert_eq!(jobs[0].name, "playlists");
+        assert_eq!(jobs[0].interval_secs, 60);
+        assert!(!jobs[0].running);
+        assert!(jobs[0].last_run.is_some());
+        assert_eq!(
+            jobs[0].last_result.as_deref(),
+            Some("Evaluated 0 smart playlists")
+        );
+        assert!(jobs[0].last_error.is_none());
+        assert_eq!(jobs[1].name, "backup");
+        assert!(jobs[1].last_result.is_none());
+        assert!(jobs[1].last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_run_without_jobs() {
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let state = Arc::new(AppState::new(db));
+        JobScheduler::new(Arc::clone(&state)).run().await;
+        assert!(state.jobs.jobs().is_empty());
+    }
+}
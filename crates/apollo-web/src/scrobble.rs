@@ -0,0 +1,260 @@
+//! Scrobbling recorded plays to [Last.fm](https://www.last.fm/) and
+//! [ListenBrainz](https://listenbrainz.org/).
+//!
+//! With either service configured, every recorded play is queued in the
+//! library for each of them, and a [`Scrobbler`] sends the queue. Plays that
+//! cannot be sent, such as while offline, stay queued and are tried again
+//! the next time the queue is sent: after the next play, every so often
+//! while the web server runs, or by `apollo scrobble sync`.
+
+use crate::error::ApiError;
+use apollo_core::Config;
+use apollo_core::event::{EventReceiver, LibraryEvent};
+use apollo_core::library::{Library, QueuedScrobble};
+use apollo_core::metadata::TrackId;
+use apollo_sources::scrobble::{LastFmClient, ListenBrainzClient, Scrobble, ScrobbleClient};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, broadcast};
+use tracing::{info, warn};
+
+/// Time between sending queued plays while the web server runs.
+const RETRY_INTERVAL: Duration = Duration::from_mins(10);
+
+/// Sends recorded plays to the configured scrobble services.
+pub struct Scrobbler {
+    db: Arc<dyn Library>,
+    clients: Vec<ScrobbleClient>,
+    /// Held while sending, so that a play is not sent twice.
+    sending: Mutex<()>,
+}
+
+/// Result of sending queued plays.
+#[derive(Debug, Clone, Default)]
+pub struct ScrobbleResult {
+    /// Number of plays sent.
+    pub sent: usize,
+    /// Number of plays still queued.
+    pub queued: usize,
+    /// Why plays could not be sent, one error for each service.
+    pub errors: Vec<String>,
+}
+
+impl Scrobbler {
+    /// Create a scrobbler for the services configured in `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a client cannot be created, such as for a token
+    /// that cannot be sent in a header.
+    pub fn new(db: Arc<dyn Library>, config: &Config) -> Result<Self, ApiError> {
+        let mut clients = Vec::new();
+        let lastfm = &config.lastfm;
+        if lastfm.is_enabled() {
+            let client =
+                LastFmClient::new(&lastfm.api_key, &lastfm.api_secret, &lastfm.session_key)
+                    .map_err(|e| ApiError::Internal(format!("Failed to set up Last.fm: {e}")))?;
+            clients.push(ScrobbleClient::LastFm(client));
+        }
+        if !config.listenbrainz.token.is_empty() {
+            let client = ListenBrainzClient::new(&config.listenbrainz.token)
+                .map_err(|e| ApiError::Internal(format!("Failed to set up ListenBrainz: {e}")))?;
+            clients.push(ScrobbleClient::ListenBrainz(client));
+        }
+        Ok(Self::with_clients(db, clients))
+    }
+
+    /// Create a scrobbler that sends plays with the given clients.
+    #[must_use]
+    pub fn with_clients(db: Arc<dyn Library>, clients: Vec<ScrobbleClient>) -> Self {
+        Self {
+            db,
+            clients,
+            sending: Mutex::new(()),
+        }
+    }
+
+    /// Get the names of the services that plays are sent to.
+    #[must_use]
+    pub fn services(&self) -> Vec<&'static str> {
+        self.clients.iter().map(ScrobbleClient::service).collect()
+    }
+
+    /// Check if any service is configured.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    /// Queue a play of a track for each service.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the track does not exist or the queue cannot be
+    /// updated.
+    pub async fn queue(&self, id: &TrackId, played_at: DateTime<Utc>) -> Result<(), ApiError> {
+        for client in &self.clients {
+            self.db
+                .queue_scrobble(id, client.service(), played_at)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send the queued plays, oldest first.
+    ///
+    /// A service that fails is not tried again until the next time, so its
+    /// remaining plays stay queued in order. Plays queued for services that
+    /// are no longer configured stay queued as well.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the queue cannot be read or updated. Plays that
+    /// cannot be sent are listed in the result instead.
+    pub async fn sync(&self) -> Result<ScrobbleResult, ApiError> {
+        let _sending = self.sending.lock().await;
+        let queue = self.db.list_scrobbles().await?;
+        let mut result = ScrobbleResult {
+            queued: queue.len(),
+            ..ScrobbleResult::default()
+        };
+
+        for client in &self.clients {
+            let plays: Vec<&QueuedScrobble> = queue
+                .iter()
+                .filter(|play| play.service == client.service())
+                .collect();
+            for batch in plays.chunks(client.batch_size()) {
+                let scrobbles: Vec<Scrobble> = batch
+                    .iter()
+                    .map(|play| Scrobble::new(&play.track, play.played_at))
+                    .collect();
+                if let Err(e) = client.submit(&scrobbles).await {
+                    let error = e.to_string();
+                    warn!("Failed to scrobble to {}: {error}", client.service());
+                    for play in batch {
+                        self.db.fail_scrobble(play.id, &error).await?;
+                    }
+                    result.errors.push(format!("{}: {error}", client.service()));
+                    break;
+                }
+                for play in batch {
+                    self.db.remove_scrobble(play.id).await?;
+                }
+                result.sent += batch.len();
+                result.queued -= batch.len();
+            }
+        }
+        Ok(result)
+    }
+
+    /// Queue and send the plays recorded from `events`, and send the queue
+    /// every so often in case a service was unavailable.
+    ///
+    /// This runs until the task is cancelled, and returns right away if no
+    /// service is configured.
+    pub async fn run(self, mut events: EventReceiver) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + RETRY_INTERVAL, RETRY_INTERVAL);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(LibraryEvent::TrackPlayed { track_id, played_at }) => {
+                        if let Err(e) = self.queue(&track_id, played_at).await {
+                            warn!("Failed to queue play for scrobbling: {e:?}");
+                        }
+                    }
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Missed {missed} library events, some plays may not be scrobbled");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = ticker.tick() => {}
+            }
+            match self.sync().await {
+                Ok(result) if result.sent > 0 => info!("Scrobbled {} plays", result.sent),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to send queued scrobbles: {e:?}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::metadata::Track;
+    use apollo_db::SqliteLibrary;
+    use std::path::PathBuf;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_sync() {
+        let db: Arc<dyn Library> = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let track = Track::new(
+            PathBuf::from("/music/played.flac"),
+            "Played".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+
+        let listenbrainz = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/1/submit-listens"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"status":"ok"}"#))
+            .expect(1)
+            .mount(&listenbrainz)
+            .await;
+        // Last.fm is down
+        let lastfm = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&lastfm)
+            .await;
+
+        let clients = vec![
+            ScrobbleClient::LastFm(
+                LastFmClient::new("key", "secret", "session")
+                    .unwrap()
+                    .with_base_url(lastfm.uri()),
+            ),
+            ScrobbleClient::ListenBrainz(
+                ListenBrainzClient::new("token")
+                    .unwrap()
+                    .with_base_url(listenbrainz.uri()),
+            ),
+        ];
+        let scrobbler = Scrobbler::with_clients(Arc::clone(&db), clients);
+        assert_eq!(scrobbler.services(), vec!["lastfm", "listenbrainz"]);
+        scrobbler.queue(&track.id, Utc::now()).await.unwrap();
+        scrobbler.queue(&track.id, Utc::now()).await.unwrap();
+
+        let result = scrobbler.sync().await.unwrap();
+        assert_eq!(result.sent, 2);
+        assert_eq!(result.queued, 2);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].starts_with("lastfm: "));
+
+        // The plays for Last.fm stay queued for the next time
+        let queued = db.list_scrobbles().await.unwrap();
+        assert!(queued.iter().all(|play| play.service == "lastfm"));
+        assert!(queued.iter().all(|play| play.attempts == 1));
+    }
+
+    #[tokio::test]
+    async fn test_not_configured() {
+        let db: Arc<dyn Library> = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+        let scrobbler = Scrobbler::new(Arc::clone(&db), &Config::default()).unwrap();
+        assert!(!scrobbler.is_enabled());
+        scrobbler.queue(&TrackId::new(), Utc::now()).await.unwrap();
+        assert_eq!(scrobbler.sync().await.unwrap().sent, 0);
+    }
+}
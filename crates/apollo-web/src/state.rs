@@ -1,18 +1,92 @@
 //! Application state for the web server.
 
-use apollo_db::SqliteLibrary;
+use crate::artist_image::ArtistImages;
+use crate::cache::PlaylistCache;
+use crate::candidates::TagSources;
+use crate::identify::TrackIdentifier;
+use crate::jobs::JobManager;
+use crate::queue::PlayQueue;
+use crate::schedule::ScheduleStatus;
+use apollo_core::Config;
+use apollo_core::library::Library;
+use apollo_lua::LuaWorkerPool;
 use std::sync::Arc;
 
 /// Shared application state.
 pub struct AppState {
-    /// Database connection.
-    pub db: Arc<SqliteLibrary>,
+    /// Library storage backend.
+    pub db: Arc<dyn Library>,
+    /// Configuration for imports.
+    pub config: Config,
+    /// Cached smart playlist results.
+    pub playlists: PlaylistCache,
+    /// Plugins whose import hooks run on imports.
+    pub hooks: Option<Arc<LuaWorkerPool>>,
+    /// Status of the scheduled maintenance jobs.
+    pub jobs: ScheduleStatus,
+    /// Cancel flags of the jobs that requests started with an ID.
+    pub running_jobs: JobManager,
+    /// The play queue that clients control playback with.
+    pub queue: PlayQueue,
+    /// Artist images, if they are looked up.
+    pub artist_images: Option<ArtistImages>,
+    /// Identifies tracks by their audio, if `AcoustID` is configured.
+    pub identifier: Option<TrackIdentifier>,
+    /// Services that candidate tags for tracks are looked up on.
+    pub tag_sources: TagSources,
 }
 
 impl AppState {
     /// Create a new application state.
     #[must_use]
-    pub fn new(db: SqliteLibrary) -> Self {
-        Self { db: Arc::new(db) }
+    pub fn new(db: impl Library + 'static) -> Self {
+        let playlists = PlaylistCache::new(db.events());
+        Self {
+            db: Arc::new(db),
+            config: Config::default(),
+            playlists,
+            hooks: None,
+            jobs: ScheduleStatus::default(),
+            running_jobs: JobManager::default(),
+            queue: PlayQueue::new(),
+            artist_images: None,
+            identifier: None,
+            tag_sources: TagSources::default(),
+        }
+    }
+
+    /// Use a configuration instead of the defaults.
+    #[must_use]
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Run the import hooks of the plugins loaded in a worker pool.
+    #[must_use]
+    pub fn with_hooks(mut self, hooks: Arc<LuaWorkerPool>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Look up artist images, instead of serving placeholders only.
+    #[must_use]
+    pub fn with_artist_images(mut self, images: ArtistImages) -> Self {
+        self.artist_images = Some(images);
+        self
+    }
+
+    /// Identify tracks by their audio.
+    #[must_use]
+    pub fn with_identifier(mut self, identifier: TrackIdentifier) -> Self {
+        self.identifier = Some(identifier);
+        self
+    }
+
+    /// Look up candidate tags for tracks on some services.
+    #[must_use]
+    pub fn with_tag_sources(mut self, sources: TagSources) -> Self {
+        self.tag_sources = sources;
+        self
     }
 }
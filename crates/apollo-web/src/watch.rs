@@ -0,0 +1,196 @@
+//! Watching folders for new music.
+//!
+//! A [`FolderWatcher`] imports audio files that are added to watched folders,
+//! with the same [`ImportService`] pipeline as manual imports. Changes are
+//! collected until the folders have been quiet for the debounce interval, so
+//! that an album being copied is imported once, after the copy finishes.
+
+use crate::error::ApiError;
+use crate::import::{ImportOptions, ImportResult, ImportService};
+use apollo_audio::is_audio_file;
+use apollo_core::Config;
+use apollo_core::library::Library;
+use apollo_lua::LuaWorkerPool;
+use notify::RecursiveMode;
+use notify_debouncer_mini::{DebounceEventResult, new_debouncer};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Imports new audio files from watched folders.
+pub struct FolderWatcher {
+    db: Arc<dyn Library>,
+    service: ImportService,
+    options: ImportOptions,
+    directories: Vec<PathBuf>,
+    debounce: Duration,
+}
+
+impl FolderWatcher {
+    /// Create a watcher for the folders and import settings in `config`.
+    #[must_use]
+    pub fn new(db: Arc<dyn Library>, config: &Config) -> Self {
+        Self {
+            service: ImportService::new(Arc::clone(&db), config),
+            db,
+            options: ImportOptions::from_config(config),
+            directories: config.watch_directories(),
+            debounce: Duration::from_secs(config.watch.debounce_secs),
+        }
+    }
+
+    /// Watch these directories instead of the configured ones.
+    #[must_use]
+    pub fn with_directories(mut self, directories: Vec<PathBuf>) -> Self {
+        self.directories = directories;
+        self
+    }
+
+    /// Run the import hooks of the plugins loaded in a worker pool.
+    #[must_use]
+    pub fn with_hooks(mut self, hooks: Arc<LuaWorkerPool>) -> Self {
+        self.service = self.service.with_hooks(hooks);
+        self
+    }
+
+    /// Get the watched directories.
+    #[must_use]
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// Watch the directories and import new files as they appear.
+    ///
+    /// This runs until the task is cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory cannot be watched.
+    pub async fn run(self) -> Result<(), ApiError> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut debouncer = new_debouncer(self.debounce, move |result: DebounceEventResult| {
+            let _ = tx.send(result);
+        })
+        .map_err(|e| ApiError::Internal(format!("Failed to start watcher: {e}")))?;
+
+        for directory in &self.directories {
+            debouncer
+                .watcher()
+                .watch(directory, RecursiveMode::Recursive)
+                .map_err(|e| {
+                    ApiError::Internal(format!("Failed to watch {}: {e}", directory.display()))
+                })?;
+            info!("Watching {}", directory.display());
+        }
+
+        while let Some(result) = rx.recv().await {
+            match result {
+                Ok(events) => {
+                    let paths: Vec<PathBuf> = events.into_iter().map(|event| event.path).collect();
+                    self.import_changes(&paths).await;
+                }
+                Err(e) => warn!("Watch error: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Import new audio files among changed paths.
+    pub async fn import_changes(&self, paths: &[PathBuf]) -> Vec<ImportResult> {
+        let mut results = Vec::new();
+        for root in self.import_roots(paths).await {
+            let options = self.options.clone().with_source(root.clone());
+            match self.service.import(&options, None).await {
+                Ok(result) => {
+                    info!(
+                        "Imported {} tracks from {}",
+                        result.tracks_imported,
+                        root.display()
+                    );
+                    results.push(result);
+                }
+                Err(e) => warn!("Failed to import {}: {e:?}", root.display()),
+            }
+        }
+        results
+    }
+
+    /// Get the directories to import for changed paths.
+    ///
+    /// Audio files that are already in the library are left out, so that
+    /// writing tags to imported files does not import them again.
+    async fn import_roots(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                candidates.push(path.clone());
+            } else if path.is_file() && is_audio_file(path) {
+                match self.db.get_track_by_path(path).await {
+                    Ok(Some(_)) => debug!("Already imported: {}", path.display()),
+                    Ok(None) => candidates.extend(path.parent().map(PathBuf::from)),
+                    Err(e) => warn!("Failed to look up {}: {e}", path.display()),
+                }
+            }
+        }
+
+        // Imports are recursive, so skip directories inside other ones
+        candidates.sort();
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for candidate in candidates {
+            if !roots.iter().any(|root| candidate.starts_with(root)) {
+                roots.push(candidate);
+            }
+        }
+        roots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_core::metadata::Track;
+    use apollo_db::SqliteLibrary;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_import_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let known = dir.path().join("known");
+        let new = dir.path().join("new");
+        fs::create_dir_all(&known).unwrap();
+        fs::create_dir_all(new.join("disc 2")).unwrap();
+        for file in [
+            "known/song.mp3",
+            "new/song.mp3",
+            "new/disc 2/song.flac",
+            "notes.txt",
+        ] {
+            fs::write(dir.path().join(file), b"").unwrap();
+        }
+
+        let db = SqliteLibrary::in_memory().await.unwrap();
+        let track = Track::new(
+            known.join("song.mp3"),
+            "Song".to_string(),
+            "Artist".to_string(),
+            Duration::from_mins(3),
+        );
+        db.add_track(&track).await.unwrap();
+
+        let watcher = FolderWatcher::new(Arc::new(db), &Config::default())
+            .with_directories(vec![dir.path().to_path_buf()]);
+        let roots = watcher
+            .import_roots(&[
+                known.join("song.mp3"),
+                new.join("disc 2/song.flac"),
+                new.join("song.mp3"),
+                dir.path().join("notes.txt"),
+                dir.path().join("deleted.mp3"),
+            ])
+            .await;
+        assert_eq!(roots, vec![new]);
+    }
+}
@@ -0,0 +1,204 @@
+//! Writing queued tag changes to audio files.
+//!
+//! With `write.deferred` set, tag changes from edits and imports are queued
+//! in the library instead of being written to the files right away, which
+//! keeps imports fast and leaves the files alone until the tags are wanted.
+//! A [`TagWriter`] writes the queue, from `apollo write` or in the background
+//! while the web server runs.
+
+use crate::error::ApiError;
+use apollo_audio::{AudioError, compute_file_hash, write_metadata_fields};
+use apollo_core::Config;
+use apollo_core::edit::EditField;
+use apollo_core::library::Library;
+use apollo_core::metadata::{Track, TrackId};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Writes queued tag changes to audio files.
+pub struct TagWriter {
+    db: Arc<dyn Library>,
+    fields: Vec<EditField>,
+    interval: Duration,
+}
+
+/// Result of writing queued tag changes.
+#[derive(Debug, Clone, Default)]
+pub struct WriteResult {
+    /// Number of files written.
+    pub written: usize,
+    /// Files that could not be written, which stay queued.
+    pub errors: Vec<String>,
+}
+
+impl TagWriter {
+    /// Create a writer for the fields and interval in `config`.
+    #[must_use]
+    pub fn new(db: Arc<dyn Library>, config: &Config) -> Self {
+        Self {
+            db,
+            fields: config.write.written_fields(),
+            interval: Duration::from_secs(config.write.interval_secs),
+        }
+    }
+
+    /// Get the fields written to files.
+    #[must_use]
+    pub fn fields(&self) -> &[EditField] {
+        &self.fields
+    }
+
+    /// Write the queued tags of all tracks, or only of the tracks in
+    /// `only`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the queue cannot be read or updated. Files that
+    /// cannot be written are listed in the result instead.
+    pub async fn write_pending(
+        &self,
+        only: Option<&HashSet<TrackId>>,
+    ) -> Result<WriteResult, ApiError> {
+        let mut result = WriteResult::default();
+        for (mut track, cleared) in self.db.list_pending_writes().await? {
+            if only.is_some_and(|ids| !ids.contains(&track.id)) {
+                continue;
+            }
+            if let Err(e) = write_tags(&mut track, &self.fields, &cleared) {
+                warn!("Failed to write tags to {}: {e}", track.path.display());
+                result.errors.push(format!(
+                    "Failed to write tags to {}: {e}",
+                    track.path.display()
+                ));
+                continue;
+            }
+            self.db.update_track(&track).await?;
+            self.db.remove_pending_write(&track.id).await?;
+            result.written += 1;
+        }
+        Ok(result)
+    }
+
+    /// Write queued tags every interval, starting after the first one.
+    ///
+    /// This runs until the task is cancelled, and returns right away if the
+    /// interval is zero.
+    pub async fn run(self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let mut ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + self.interval, self.interval);
+        loop {
+            ticker.tick().await;
+            match self.write_pending(None).await {
+                Ok(result) if result.written > 0 => {
+                    info!("Wrote queued tags to {} files", result.written);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to write queued tags: {e:?}"),
+            }
+        }
+    }
+}
+
+/// Write the tags of some fields of a track to its file, and update the
+/// track's hash to match.
+///
+/// Of the `cleared` fields, only those the track has no value for are
+/// removed, since a field may have been set again after it was cleared.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written or read back.
+pub fn write_tags(
+    track: &mut Track,
+    fields: &[EditField],
+    cleared: &[EditField],
+) -> Result<(), AudioError> {
+    let cleared: Vec<EditField> = cleared
+        .iter()
+        .copied()
+        .filter(|field| !field.is_set(track))
+        .collect();
+    write_metadata_fields(&track.path, track, fields, &cleared)?;
+    // Writing tags changes the file contents, and so its hash
+    track.file_hash = compute_file_hash(&track.path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apollo_audio::read_metadata;
+    use apollo_db::SqliteLibrary;
+    use std::fs;
+    use std::path::Path;
+
+    /// Write a silent mono 8 kHz WAV file.
+    fn write_wav(path: &Path) {
+        let samples = vec![0u8; 1600];
+        let size = u32::try_from(samples.len()).unwrap();
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + size).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&samples);
+        fs::write(path, data).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_pending() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db: Arc<dyn Library> = Arc::new(SqliteLibrary::in_memory().await.unwrap());
+
+        let mut tracks = Vec::new();
+        for name in ["one", "two"] {
+            let path = dir.path().join(format!("{name}.wav"));
+            write_wav(&path);
+            let mut track = Track::new(
+                path,
+                format!("Edited {name}"),
+                "Artist".to_string(),
+                Duration::from_millis(100),
+            );
+            track.genres = vec!["Rock".to_string()];
+            db.add_track(&track).await.unwrap();
+            db.queue_write(&track.id, &[]).await.unwrap();
+            tracks.push(track);
+        }
+        fs::remove_file(&tracks[1].path).unwrap();
+
+        let mut config = Config::default();
+        config.write.exclude = vec!["genre".to_string()];
+        let writer = TagWriter::new(Arc::clone(&db), &config);
+        let result = writer.write_pending(None).await.unwrap();
+
+        assert_eq!(result.written, 1);
+        assert_eq!(result.errors.len(), 1);
+        let written = read_metadata(&tracks[0].path).unwrap();
+        assert_eq!(written.title, "Edited one");
+        assert!(written.genres.is_empty());
+        let stored = db.get_track(&tracks[0].id).await.unwrap().unwrap();
+        assert_eq!(
+            stored.file_hash,
+            compute_file_hash(&tracks[0].path).unwrap()
+        );
+
+        // The missing file stays queued
+        let pending = db.list_pending_writes().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0.id, tracks[1].id);
+    }
+}
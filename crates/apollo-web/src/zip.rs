@@ -0,0 +1,545 @@
+//! ZIP archives of audio files, written while they are sent.
+//!
+//! Audio files hardly compress, so files are stored as they are. Each file's
+//! checksum is only known once it has been read, so it follows the file in
+//! a data descriptor, and the archive can be sent without reading any file
+//! twice or holding it in memory. Files of 4 GiB or more, and archives that
+//! outgrow what plain ZIP allows, get the Zip64 records that hold their
+//! sizes and offsets, so albums of any size can be downloaded.
+
+use axum::body::Bytes;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Size of the chunks that files are read and sent in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Version of the ZIP format needed to extract plain entries.
+const VERSION: u16 = 20;
+
+/// Version of the ZIP format needed to extract entries with Zip64 records.
+const VERSION_ZIP64: u16 = 45;
+
+/// Flags of each entry: sizes and checksum follow the data, and the name is
+/// UTF-8.
+const FLAGS: u16 = 0x0008 | 0x0800;
+
+/// Value of a 32-bit size or offset that is in a Zip64 record instead.
+const ZIP64_U32: u32 = u32::MAX;
+
+/// Value of a 16-bit count that is in a Zip64 record instead.
+const ZIP64_U16: u16 = u16::MAX;
+
+/// A file to put in an archive.
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    /// Path of the file in the archive, with `/` between directories.
+    pub name: String,
+    /// Path of the file on disk.
+    pub path: PathBuf,
+}
+
+/// An entry as listed in the central directory at the end of the archive.
+struct Written {
+    name: String,
+    time: u16,
+    date: u16,
+    crc: u32,
+    size: u64,
+    offset: u64,
+}
+
+/// Stream an archive of files.
+///
+/// The archive is written by a task that stops when the stream is dropped,
+/// such as when the client disconnects. A file that cannot be read, or that
+/// changes size while it is read, ends the stream with an error.
+pub fn stream(entries: Vec<ZipEntry>) -> impl Stream<Item = io::Result<Bytes>> {
+    let (tx, rx) = mpsc::channel(4);
+    tokio::spawn(async move {
+        if let Err(e) = write_archive(entries, &tx).await {
+            // Nothing to tell if the client is gone
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+/// Write an archive to a channel.
+async fn write_archive(
+    entries: Vec<ZipEntry>,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+) -> io::Result<()> {
+    let mut offset = 0;
+    let mut written = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if entry.name.len() > usize::from(u16::MAX) {
+            return Err(io::Error::other(format!("name too long: {}", entry.name)));
+        }
+        let mut file = tokio::fs::File::open(&entry.path).await?;
+        let metadata = file.metadata().await?;
+        let expected = metadata.len();
+        let (time, date) = dos_date_time(metadata.modified().ok());
+
+        // Whether the sizes need Zip64 records is decided before the data
+        // is sent, from the size the file has now
+        let zip64 = expected >= u64::from(ZIP64_U32);
+        let header = local_header(&entry.name, time, date, zip64);
+        let entry_offset = offset;
+        send(tx, header, &mut offset).await?;
+
+        let mut crc = Crc32::new();
+        let mut size = 0u64;
+        loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let read = file.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            chunk.truncate(read);
+            crc.update(&chunk);
+            size += read as u64;
+            if size > expected {
+                return Err(changed(&entry.path));
+            }
+            send(tx, chunk, &mut offset).await?;
+        }
+        if size != expected {
+            return Err(changed(&entry.path));
+        }
+
+        let crc = crc.finish();
+        send(tx, data_descriptor(crc, size, zip64), &mut offset).await?;
+
+        written.push(Written {
+            name: entry.name,
+            time,
+            date,
+            crc,
+            size,
+            offset: entry_offset,
+        });
+    }
+
+    let directory_offset = offset;
+    let mut directory = Vec::new();
+    for entry in &written {
+        central_header(&mut directory, entry);
+    }
+    let directory_size = directory.len() as u64;
+    end_records(
+        &mut directory,
+        written.len() as u64,
+        directory_size,
+        directory_offset,
+    );
+    send(tx, directory, &mut offset).await
+}
+
+/// Send part of the archive, keeping track of the offset in it.
+async fn send(
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+    data: Vec<u8>,
+    offset: &mut u64,
+) -> io::Result<()> {
+    *offset += data.len() as u64;
+    tx.send(Ok(Bytes::from(data)))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))
+}
+
+/// Build the header that precedes a file's data.
+///
+/// The header of a file that needs Zip64 sizes says so with an empty Zip64
+/// extra field, so that its data descriptor holds 64-bit sizes.
+fn local_header(name: &str, time: u16, date: u16, zip64: bool) -> Vec<u8> {
+    let mut header = Vec::with_capacity(50 + name.len());
+    put_u32(&mut header, 0x0403_4b50);
+    put_u16(&mut header, if zip64 { VERSION_ZIP64 } else { VERSION });
+    put_u16(&mut header, FLAGS);
+    put_u16(&mut header, 0); // stored
+    put_u16(&mut header, time);
+    put_u16(&mut header, date);
+    // Checksum and sizes are in the data descriptor
+    put_u32(&mut header, 0);
+    let size = if zip64 { ZIP64_U32 } else { 0 };
+    put_u32(&mut header, size);
+    put_u32(&mut header, size);
+    put_name(&mut header, name);
+    put_u16(&mut header, if zip64 { 20 } else { 0 }); // extra field length
+    header.extend_from_slice(name.as_bytes());
+    if zip64 {
+        put_u16(&mut header, 0x0001);
+        put_u16(&mut header, 16);
+        put_u64(&mut header, 0);
+        put_u64(&mut header, 0);
+    }
+    header
+}
+
+/// Build the data descriptor that follows a file's data.
+fn data_descriptor(crc: u32, size: u64, zip64: bool) -> Vec<u8> {
+    let mut descriptor = Vec::with_capacity(24);
+    put_u32(&mut descriptor, 0x0807_4b50);
+    put_u32(&mut descriptor, crc);
+    if zip64 {
+        put_u64(&mut descriptor, size);
+        put_u64(&mut descriptor, size);
+    } else {
+        // The file was checked to be smaller than this
+        let size = u32::try_from(size).unwrap_or(ZIP64_U32);
+        put_u32(&mut descriptor, size);
+        put_u32(&mut descriptor, size);
+    }
+    descriptor
+}
+
+/// Add the central directory header of a file.
+///
+/// Sizes and offsets that do not fit in 32 bits are in a Zip64 extra field.
+fn central_header(out: &mut Vec<u8>, entry: &Written) {
+    let size = u32::try_from(entry.size)
+        .ok()
+        .filter(|&size| size != ZIP64_U32);
+    let offset = u32::try_from(entry.offset)
+        .ok()
+        .filter(|&offset| offset != ZIP64_U32);
+    let mut extra = Vec::new();
+    if size.is_none() {
+        put_u64(&mut extra, entry.size);
+        put_u64(&mut extra, entry.size);
+    }
+    if offset.is_none() {
+        put_u64(&mut extra, entry.offset);
+    }
+    let version = if extra.is_empty() {
+        VERSION
+    } else {
+        VERSION_ZIP64
+    };
+
+    put_u32(out, 0x0201_4b50);
+    put_u16(out, version); // made by
+    put_u16(out, version); // needed to extract
+    put_u16(out, FLAGS);
+    put_u16(out, 0); // stored
+    put_u16(out, entry.time);
+    put_u16(out, entry.date);
+    put_u32(out, entry.crc);
+    put_u32(out, size.unwrap_or(ZIP64_U32));
+    put_u32(out, size.unwrap_or(ZIP64_U32));
+    put_name(out, &entry.name);
+    // The extra field holds at most two sizes and an offset
+    let extra_len = if extra.is_empty() { 0 } else { extra.len() + 4 };
+    put_u16(out, u16::try_from(extra_len).unwrap_or(0)); // extra field length
+    put_u16(out, 0); // comment length
+    put_u16(out, 0); // disk number
+    put_u16(out, 0); // internal attributes
+    put_u32(out, 0); // external attributes
+    put_u32(out, offset.unwrap_or(ZIP64_U32));
+    out.extend_from_slice(entry.name.as_bytes());
+    if !extra.is_empty() {
+        put_u16(out, 0x0001);
+        put_u16(out, u16::try_from(extra.len()).unwrap_or(0));
+        out.extend_from_slice(&extra);
+    }
+}
+
+/// Add the records that end the archive and point at its central directory.
+///
+/// An archive with too many entries, or a central directory too large or
+/// too far in, gets a Zip64 end of central directory record and its
+/// locator before the plain record.
+fn end_records(out: &mut Vec<u8>, count: u64, directory_size: u64, directory_offset: u64) {
+    let plain_count = u16::try_from(count)
+        .ok()
+        .filter(|&count| count != ZIP64_U16);
+    let plain_size = u32::try_from(directory_size)
+        .ok()
+        .filter(|&size| size != ZIP64_U32);
+    let plain_offset = u32::try_from(directory_offset)
+        .ok()
+        .filter(|&offset| offset != ZIP64_U32);
+
+    if plain_count.is_none() || plain_size.is_none() || plain_offset.is_none() {
+        let record_offset = directory_offset + directory_size;
+        put_u32(out, 0x0606_4b50);
+        put_u64(out, 44); // size of the rest of the record
+        put_u16(out, VERSION_ZIP64); // made by
+        put_u16(out, VERSION_ZIP64); // needed to extract
+        put_u32(out, 0); // this disk
+        put_u32(out, 0); // disk with the central directory
+        put_u64(out, count);
+        put_u64(out, count);
+        put_u64(out, directory_size);
+        put_u64(out, directory_offset);
+
+        put_u32(out, 0x0706_4b50);
+        put_u32(out, 0); // disk with the Zip64 record
+        put_u64(out, record_offset);
+        put_u32(out, 1); // number of disks
+    }
+
+    put_u32(out, 0x0605_4b50);
+    put_u16(out, 0); // this disk
+    put_u16(out, 0); // disk with the central directory
+    put_u16(out, plain_count.unwrap_or(ZIP64_U16));
+    put_u16(out, plain_count.unwrap_or(ZIP64_U16));
+    put_u32(out, plain_size.unwrap_or(ZIP64_U32));
+    put_u32(out, plain_offset.unwrap_or(ZIP64_U32));
+    put_u16(out, 0); // comment length
+}
+
+/// Add the length of a name, which is checked to fit before.
+fn put_name(out: &mut Vec<u8>, name: &str) {
+    put_u16(out, u16::try_from(name.len()).unwrap_or(u16::MAX));
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn changed(path: &std::path::Path) -> io::Error {
+    io::Error::other(format!(
+        "file changed while it was read: {}",
+        path.display()
+    ))
+}
+
+/// Convert a modification time to the MS-DOS time and date that ZIP uses.
+///
+/// Times before 1980, which MS-DOS cannot represent, become 1 January 1980.
+fn dos_date_time(modified: Option<SystemTime>) -> (u16, u16) {
+    let Some(modified) = modified.map(DateTime::<Utc>::from) else {
+        return (0, (1 << 5) | 1);
+    };
+    let Ok(year) = u32::try_from(modified.year() - 1980) else {
+        return (0, (1 << 5) | 1);
+    };
+    let time = (modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2);
+    let date = (year.min(127) << 9) | (modified.month() << 5) | modified.day();
+    (
+        u16::try_from(time).unwrap_or(0),
+        u16::try_from(date).unwrap_or(0),
+    )
+}
+
+/// Table of the CRC-32 checksum that ZIP uses, by byte.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte as usize] = crc;
+        byte += 1;
+    }
+    table
+};
+
+/// CRC-32 checksum of data read in parts.
+struct Crc32(u32);
+
+impl Crc32 {
+    const fn new() -> Self {
+        Self(u32::MAX)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 = CRC_TABLE[((self.0 ^ u32::from(byte)) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    const fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([data[at], data[at + 1]])
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    #[test]
+    fn test_crc32() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+        assert_eq!(Crc32::new().finish(), 0);
+    }
+
+    #[test]
+    fn test_dos_date_time() {
+        let time = DateTime::parse_from_rfc3339("2024-03-15T13:45:30Z").unwrap();
+        let (time, date) = dos_date_time(Some(time.into()));
+        assert_eq!(time, (13 << 11) | (45 << 5) | 15);
+        assert_eq!(date, (44 << 9) | (3 << 5) | 15);
+        assert_eq!(dos_date_time(Some(SystemTime::UNIX_EPOCH)), (0, 33));
+    }
+
+    #[tokio::test]
+    async fn test_archive_layout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let first = dir.path().join("01.flac");
+        let second = dir.path().join("02.flac");
+        std::fs::write(&first, b"123456789").unwrap();
+        std::fs::write(&second, b"").unwrap();
+        let entries = vec![
+            ZipEntry {
+                name: "Album/01.flac".to_string(),
+                path: first,
+            },
+            ZipEntry {
+                name: "Album/Disc 2/02.flac".to_string(),
+                path: second,
+            },
+        ];
+
+        let mut archive = Vec::new();
+        let mut parts = Box::pin(stream(entries));
+        while let Some(part) = parts.next().await {
+            archive.extend_from_slice(&part.unwrap());
+        }
+
+        // The end of central directory record points at each entry
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), 0x0605_4b50);
+        assert_eq!(u16_at(&archive, end + 10), 2);
+        let mut at = u32_at(&archive, end + 16) as usize;
+        let mut names = Vec::new();
+        for _ in 0..2 {
+            assert_eq!(u32_at(&archive, at), 0x0201_4b50);
+            let crc = u32_at(&archive, at + 16);
+            let size = u32_at(&archive, at + 24) as usize;
+            let name_len = u16_at(&archive, at + 28) as usize;
+            let name = &archive[at + 46..at + 46 + name_len];
+            let local = u32_at(&archive, at + 42) as usize;
+
+            assert_eq!(u32_at(&archive, local), 0x0403_4b50);
+            assert_eq!(&archive[local + 30..local + 30 + name_len], name);
+            let data = local + 30 + name_len;
+            let descriptor = data + size;
+            assert_eq!(u32_at(&archive, descriptor), 0x0807_4b50);
+            assert_eq!(u32_at(&archive, descriptor + 4), crc);
+
+            names.push(String::from_utf8(name.to_vec()).unwrap());
+            if size > 0 {
+                assert_eq!(&archive[data..descriptor], b"123456789");
+                assert_eq!(crc, 0xCBF4_3926);
+            }
+            at += 46 + name_len;
+        }
+        assert_eq!(names, vec!["Album/01.flac", "Album/Disc 2/02.flac"]);
+        assert_eq!(at, end);
+    }
+
+    #[test]
+    fn test_zip64_entry() {
+        let header = local_header("big.flac", 0, 0, true);
+        assert_eq!(u16_at(&header, 4), VERSION_ZIP64);
+        assert_eq!(u32_at(&header, 22), ZIP64_U32);
+        assert_eq!(u16_at(&header, 28), 20);
+        assert_eq!(u16_at(&header, 38), 0x0001);
+        assert_eq!(header.len(), 30 + 8 + 20);
+        assert_eq!(data_descriptor(7, 5 << 32, true).len(), 24);
+        assert_eq!(data_descriptor(7, 5, false).len(), 16);
+
+        let entry = Written {
+            name: "big.flac".to_string(),
+            time: 0,
+            date: 0,
+            crc: 7,
+            size: 5 << 32,
+            offset: 6 << 32,
+        };
+        let mut directory = Vec::new();
+        central_header(&mut directory, &entry);
+        assert_eq!(u16_at(&directory, 6), VERSION_ZIP64);
+        assert_eq!(u32_at(&directory, 20), ZIP64_U32);
+        assert_eq!(u32_at(&directory, 24), ZIP64_U32);
+        assert_eq!(u32_at(&directory, 42), ZIP64_U32);
+        let extra = 46 + 8;
+        assert_eq!(u16_at(&directory, 30), 28);
+        assert_eq!(u16_at(&directory, extra), 0x0001);
+        assert_eq!(u16_at(&directory, extra + 2), 24);
+        assert_eq!(
+            &directory[extra + 4..extra + 12],
+            &(5u64 << 32).to_le_bytes()
+        );
+        assert_eq!(
+            &directory[extra + 20..extra + 28],
+            &(6u64 << 32).to_le_bytes()
+        );
+
+        // Small entries far into the archive only move their offset
+        let entry = Written { size: 9, ..entry };
+        let mut directory = Vec::new();
+        central_header(&mut directory, &entry);
+        assert_eq!(u32_at(&directory, 24), 9);
+        assert_eq!(u16_at(&directory, 30), 12);
+    }
+
+    #[test]
+    fn test_zip64_end_records() {
+        let mut end = Vec::new();
+        end_records(&mut end, 2, 100, 1000);
+        assert_eq!(end.len(), 22);
+        assert_eq!(u32_at(&end, 0), 0x0605_4b50);
+
+        let offset = 5u64 << 32;
+        let mut end = Vec::new();
+        end_records(&mut end, 70_000, 100, offset);
+        assert_eq!(end.len(), 56 + 20 + 22);
+        assert_eq!(u32_at(&end, 0), 0x0606_4b50);
+        assert_eq!(&end[24..32], &70_000u64.to_le_bytes());
+        assert_eq!(&end[48..56], &offset.to_le_bytes());
+        assert_eq!(u32_at(&end, 56), 0x0706_4b50);
+        assert_eq!(&end[64..72], &(offset + 100).to_le_bytes());
+        assert_eq!(u32_at(&end, 76), 0x0605_4b50);
+        assert_eq!(u16_at(&end, 76 + 10), ZIP64_U16);
+        assert_eq!(u32_at(&end, 76 + 12), 100);
+        assert_eq!(u32_at(&end, 76 + 16), ZIP64_U32);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_file_ends_stream_with_error() {
+        let entries = vec![ZipEntry {
+            name: "missing.flac".to_string(),
+            path: PathBuf::from("/nonexistent/missing.flac"),
+        }];
+        let mut parts = Box::pin(stream(entries));
+        assert!(parts.next().await.unwrap().is_err());
+    }
+}